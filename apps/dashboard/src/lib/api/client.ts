@@ -358,6 +358,26 @@ export const settings = {
 		}, true)
 };
 
+// --- Smoothing API ---
+
+export interface SmoothingConfig {
+	bt_window_secs: number;
+	et_window_secs: number;
+	ror_window_secs: number;
+	ror_algorithm: string;
+	ror_unit: 'per_minute' | 'per_30s';
+}
+
+export const smoothing = {
+	/** The caller's smoothing, with the roaster's RoR window and unit when given. */
+	get: (deviceId?: string | null) =>
+		request<SmoothingConfig>(
+			`/api/smoothing${deviceId ? `?device_id=${encodeURIComponent(deviceId)}` : ''}`,
+			{},
+			true
+		)
+};
+
 // --- Autotune API ---
 
 export interface AutotuneStatusResponse {
@@ -1,6 +1,6 @@
 import { writable, derived, readonly } from 'svelte/store';
 import type { Telemetry, TelemetryMessage, AutotuneWsMessage, ConnectionStatus } from '$lib/types/telemetry.js';
-import { settings } from '$lib/api/client.js';
+import { smoothing, type SmoothingConfig } from '$lib/api/client.js';
 
 function getWsUrl(): string {
 	if (import.meta.env.VITE_WS_URL) return import.meta.env.VITE_WS_URL;
@@ -30,9 +30,13 @@ export const autotuneEvent = readonly(_autotuneEvent);
 /** RoR configuration stores. */
 const _rorWindowMs = writable(30_000);
 const _rorAlgorithm = writable<string>('moving_average');
+const _rorUnit = writable<SmoothingConfig['ror_unit']>('per_minute');
 
 export const rorWindowSeconds = derived(_rorWindowMs, ($ms) => $ms / 1000);
 export const rorAlgorithm = readonly(_rorAlgorithm);
+/** Unit the selected roaster shows RoR in, and RoR alarm thresholds are set in. */
+export const rorUnit = readonly(_rorUnit);
+export const rorUnitLabel = derived(_rorUnit, ($unit) => ($unit === 'per_30s' ? '°C/30s' : '°C/min'));
 
 /** Update RoR configuration. */
 export function setRorConfig(windowSeconds: number, algorithm: string) {
@@ -40,16 +44,15 @@ export function setRorConfig(windowSeconds: number, algorithm: string) {
 	_rorAlgorithm.set(algorithm);
 }
 
-/** Load RoR settings from the server. */
-export async function loadRorSettings() {
+/** Load RoR settings from the server, including the roaster's own window and unit. */
+export async function loadRorSettings(deviceId: string | null = selectedDevice) {
 	try {
-		const s = await settings.get();
-		const windowSec = parseInt(s['ror_window_seconds'] ?? '30', 10);
-		const algo = s['ror_smoothing_algorithm'] ?? 'moving_average';
-		if (!isNaN(windowSec) && windowSec >= 5 && windowSec <= 120) {
-			_rorWindowMs.set(windowSec * 1000);
+		const s = await smoothing.get(deviceId);
+		if (s.ror_window_secs >= 1 && s.ror_window_secs <= 300) {
+			_rorWindowMs.set(s.ror_window_secs * 1000);
 		}
-		_rorAlgorithm.set(algo);
+		_rorAlgorithm.set(s.ror_algorithm);
+		_rorUnit.set(s.ror_unit ?? 'per_minute');
 	} catch {
 		// Use defaults on error
 	}
@@ -160,6 +163,11 @@ export const rateOfRise = derived(
 	null as number | null
 );
 
+/** Rate of Rise in the selected roaster's unit, for display. */
+export const displayRateOfRise = derived([rateOfRise, _rorUnit], ([$ror, $unit]) =>
+	$ror === null || $unit !== 'per_30s' ? $ror : Math.round($ror * 5) / 10
+);
+
 let ws: WebSocket | null = null;
 let reconnectAttempts = 0;
 let reconnectTimer: ReturnType<typeof setTimeout> | null = null;
@@ -236,6 +244,7 @@ export function setSelectedDevice(deviceId: string | null) {
 	_telemetry.set(null);
 	_deviceId.set(null);
 	_history.set([]);
+	loadRorSettings(deviceId);
 }
 
 /** Start the WebSocket connection. Call once on app init. */
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+/// A control command, tagged by `op` as accepted by
+/// `POST /api/roaster/{device_id}/control/batch`. The same body without
+/// `op` is what the single-command endpoint `/control/{op}` takes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ControlCommand {
+    /// Target bean temperature, 0..=300 °C.
+    Setpoint {
+        value: f64,
+    },
+    /// Fan duty, 0..=255.
+    FanPwm {
+        value: u16,
+    },
+    /// Heater duty, 0..=100.
+    HeaterPwm {
+        value: u8,
+    },
+    /// `auto` or `manual`.
+    Mode {
+        mode: String,
+    },
+    HeaterEnable {
+        enabled: bool,
+    },
+    Pid {
+        kp: f64,
+        ki: f64,
+        kd: f64,
+    },
+}
+
+impl ControlCommand {
+    /// The `op` tag, which is also the endpoint name under `/control/`.
+    pub fn op(&self) -> &'static str {
+        match self {
+            ControlCommand::Setpoint { .. } => "setpoint",
+            ControlCommand::FanPwm { .. } => "fan_pwm",
+            ControlCommand::HeaterPwm { .. } => "heater_pwm",
+            ControlCommand::Mode { .. } => "mode",
+            ControlCommand::HeaterEnable { .. } => "heater_enable",
+            ControlCommand::Pid { .. } => "pid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlBatchRequest {
+    pub operations: Vec<ControlCommand>,
+    /// Skip the remaining operations after the first failure.
+    #[serde(default)]
+    pub abort_on_failure: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlBatchStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlBatchItemResult {
+    pub index: usize,
+    pub op: String,
+    pub status: ControlBatchStatus,
+    /// HTTP status the single-command endpoint would have returned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlBatchResponse {
+    pub results: Vec<ControlBatchItemResult>,
+    pub aborted: bool,
+}
+
+/// How a control's value is entered.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlValueKind {
+    Number,
+    Integer,
+    Boolean,
+    Choice,
+    /// `kp`, `ki` and `kd` together.
+    Gains,
+    /// No value, e.g. emergency stop.
+    Action,
+}
+
+/// One control a device accepts, from `GET /api/roaster/{device_id}/controls`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceControl {
+    pub op: String,
+    /// `POST` endpoint taking the command body.
+    pub endpoint: String,
+    pub kind: ControlValueKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<String>>,
+    /// Value from the latest telemetry.
+    #[serde(default)]
+    pub current: Option<serde_json::Value>,
+    /// Default from the device's profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// Why a strict command would be refused right now.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceControlSurface {
+    pub device_id: String,
+    pub online: bool,
+    /// Whether the controls come from the device's telemetry rather than
+    /// the assumed full firmware set.
+    pub reported: bool,
+    pub profile_id: Option<String>,
+    pub controls: Vec<DeviceControl>,
+}
+
+impl DeviceControlSurface {
+    pub fn control(&self, op: &str) -> Option<&DeviceControl> {
+        self.controls.iter().find(|c| c.op == op)
+    }
+}
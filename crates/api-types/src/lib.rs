@@ -0,0 +1,18 @@
+//! Wire types of the rustRoast REST and WebSocket APIs.
+//!
+//! These mirror the server's request and response bodies without its
+//! database bindings, so clients can share them without pulling in the
+//! server. Nested documents that are not modelled yet are kept as
+//! [`serde_json::Value`].
+
+pub mod alert;
+pub mod control;
+pub mod session;
+pub mod smoothing;
+pub mod telemetry;
+
+pub use alert::*;
+pub use control::*;
+pub use session::*;
+pub use smoothing::*;
+pub use telemetry::*;
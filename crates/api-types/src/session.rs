@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    Planning,
+    Active,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionType {
+    #[default]
+    Profile,
+    Manual,
+    Sample,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoastSession {
+    pub id: String,
+    pub name: String,
+    pub device_id: String,
+    pub profile_id: Option<String>,
+    #[serde(default)]
+    pub bean_id: Option<String>,
+    pub session_type: SessionType,
+    #[serde(default)]
+    pub sample_group_id: Option<String>,
+    pub status: SessionStatus,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub bean_origin: Option<String>,
+    pub bean_variety: Option<String>,
+    pub green_weight: Option<f32>,
+    pub roasted_weight: Option<f32>,
+    pub target_roast_level: Option<String>,
+    pub notes: Option<String>,
+    pub ambient_temp: Option<f32>,
+    pub humidity: Option<f32>,
+
+    pub max_temp: Option<f32>,
+    pub total_time_seconds: Option<i32>,
+    pub first_crack_time: Option<i32>,
+    pub development_time_ratio: Option<f32>,
+    pub weight_loss_pct: Option<f32>,
+    pub max_ror: Option<f32>,
+    pub avg_ror_drying: Option<f32>,
+    pub avg_ror_maillard: Option<f32>,
+    pub avg_ror_development: Option<f32>,
+    pub drying_end_time: Option<i32>,
+    pub drying_end_temp: Option<f32>,
+    pub auc_value: Option<f32>,
+
+    pub roaster: Option<String>,
+    pub signed_off_by: Option<String>,
+    pub signed_off_at: Option<DateTime<Utc>>,
+
+    pub profile_fc_delta: Option<f32>,
+    pub profile_drop_time_delta: Option<f32>,
+    pub profile_drop_temp_delta: Option<f32>,
+    pub profile_deviation_integral: Option<f32>,
+}
+
+/// One stored telemetry sample of a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionTelemetry {
+    pub id: String,
+    pub session_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub elapsed_seconds: f32,
+    pub bean_temp: Option<f32>,
+    pub env_temp: Option<f32>,
+    pub rate_of_rise: Option<f32>,
+    pub heater_pwm: Option<i32>,
+    pub fan_pwm: Option<i32>,
+    pub setpoint: Option<f32>,
+    pub airflow: Option<f32>,
+}
+
+/// `GET /api/sessions/{id}` and `GET /api/sessions/{id}/telemetry`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionWithTelemetry {
+    #[serde(flatten)]
+    pub session: RoastSession,
+    pub telemetry: Vec<SessionTelemetry>,
+    pub profile: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cupping: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scoreboard: Option<serde_json::Value>,
+    #[serde(default)]
+    pub defects: Vec<serde_json::Value>,
+    /// Bean, energy, labor and overhead cost of the session.
+    #[serde(default)]
+    pub cost: Option<serde_json::Value>,
+    /// The caller's curve smoothing (session telemetry endpoint only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smoothing: Option<crate::SmoothingConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateSessionRequest {
+    pub name: String,
+    pub device_id: String,
+    pub profile_id: Option<String>,
+    pub bean_origin: Option<String>,
+    pub bean_variety: Option<String>,
+    pub green_weight: Option<f32>,
+    pub target_roast_level: Option<String>,
+    pub notes: Option<String>,
+    pub ambient_temp: Option<f32>,
+    pub humidity: Option<f32>,
+    pub roaster: Option<String>,
+    #[serde(default)]
+    pub session_type: SessionType,
+    /// Green bean lot, see `/api/beans`.
+    #[serde(default)]
+    pub bean_id: Option<String>,
+}
+
+/// Filters for `GET /api/sessions`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionListQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roaster: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i32>,
+}
+
+/// Window and resolution for `GET /api/sessions/{id}/telemetry`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TelemetryRangeQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_secs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_secs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_points: Option<usize>,
+    /// View whose smoothing is returned (default `live`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoastEventType {
+    Charge,
+    Drop,
+    DryingEnd,
+    FirstCrackStart,
+    FirstCrackEnd,
+    SecondCrackStart,
+    SecondCrackEnd,
+    DevelopmentStart,
+    DropOut,
+    HeaterChange,
+    FanChange,
+    Custom,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoastEvent {
+    pub id: String,
+    pub session_id: String,
+    pub event_type: RoastEventType,
+    pub elapsed_seconds: f32,
+    pub temperature: Option<f32>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateRoastEventRequest {
+    pub event_type: RoastEventType,
+    pub elapsed_seconds: f32,
+    pub temperature: Option<f32>,
+    pub notes: Option<String>,
+}
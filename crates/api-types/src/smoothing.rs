@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::SessionTelemetry;
+
+/// How rate of rise is derived from the bean temperatures in its window.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RorAlgorithm {
+    #[default]
+    MovingAverage,
+    WeightedMovingAverage,
+    SavitzkyGolay,
+}
+
+/// Unit RoR curves are reported in.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RorUnit {
+    #[default]
+    #[serde(rename = "per_minute")]
+    PerMinute,
+    /// °C per 30 s, common on fast fluid-bed roasters.
+    #[serde(rename = "per_30s")]
+    Per30Secs,
+}
+
+/// Trailing windows (seconds) for drawing curves. A BT/ET window of 0
+/// leaves that curve unsmoothed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct SmoothingConfig {
+    pub bt_window_secs: f32,
+    pub et_window_secs: f32,
+    pub ror_window_secs: f32,
+    pub ror_algorithm: RorAlgorithm,
+    /// Set per roaster with its RoR settings.
+    #[serde(default)]
+    pub ror_unit: RorUnit,
+}
+
+/// `GET /api/me/smoothing` entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmoothingPreset {
+    pub subject: String,
+    pub view: String,
+    #[serde(flatten)]
+    pub config: SmoothingConfig,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Overrides for `GET /api/sessions/{id}/telemetry/smoothed`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmoothedTelemetryQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bt_window_secs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub et_window_secs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ror_window_secs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ror_algorithm: Option<RorAlgorithm>,
+}
+
+/// A telemetry sample after smoothing. RoR is in the config's `ror_unit`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SmoothedPoint {
+    pub elapsed_seconds: f32,
+    pub bean_temp: Option<f32>,
+    pub env_temp: Option<f32>,
+    pub rate_of_rise: Option<f32>,
+}
+
+/// `GET /api/sessions/{id}/telemetry/smoothed`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmoothedSessionTelemetry {
+    pub session_id: String,
+    pub smoothing: SmoothingConfig,
+    pub raw: Vec<SessionTelemetry>,
+    pub smoothed: Vec<SmoothedPoint>,
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+/// A device telemetry message in the ESP32 wire format (camelCase keys).
+/// Fields the server does not interpret are kept in `extra`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceTelemetry {
+    pub bean_temp: Option<f64>,
+    pub env_temp: Option<f64>,
+    pub rate_of_rise: Option<f64>,
+    #[serde(rename = "heaterPWM")]
+    pub heater_pwm: Option<i64>,
+    #[serde(rename = "fanPWM")]
+    pub fan_pwm: Option<i64>,
+    pub setpoint: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub airflow: Option<f64>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// `GET /api/roaster/{device_id}/telemetry/latest`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatestTelemetry {
+    pub device_id: String,
+    /// Unix seconds when the sample was received.
+    pub timestamp: u64,
+    pub telemetry: DeviceTelemetry,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub device_id: String,
+    pub last_seen: u64,
+    /// The status is the broker's retained copy: no live status has
+    /// arrived since the server started.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retained: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_raw: Option<serde_json::Value>,
+    /// Set while several boards appear to publish under this device_id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<DeviceConflict>,
+    /// Decoded error `systemStatus`, also found as `systemError` in telemetry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_error: Option<DeviceErrorInfo>,
+}
+
+/// A decoded firmware `systemStatus` code, `GET /api/devices/error-codes`
+/// lists the known ones.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeviceErrorInfo {
+    pub code: i64,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictReason {
+    MultipleHardwareIds,
+    IpFlapping,
+    UptimeRegressions,
+}
+
+/// `GET /api/devices/conflicts` entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceConflict {
+    pub device_id: String,
+    pub reason: ConflictReason,
+    pub detected_at: chrono::DateTime<chrono::Utc>,
+    pub hardware_ids: Vec<String>,
+    pub ips: Vec<String>,
+}
+
+/// A firmware log line, from `GET /api/roaster/{device_id}/logs` or
+/// `/ws/logs/{device_id}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceLogLine {
+    pub device_id: String,
+    /// Unix seconds when the server received the line.
+    pub ts: i64,
+    #[serde(default)]
+    pub level: Option<String>,
+    pub message: String,
+}
+
+/// A device flagged for troubleshooting, whose log lines are persisted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogCapture {
+    pub device_id: String,
+    pub enabled_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// `GET /api/devices/registry`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevicesResponse {
+    pub devices: Vec<DeviceInfo>,
+}
+
+/// Autotune progress forwarded from `roaster/{device_id}/autotune/{type}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutotuneUpdate {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub data: serde_json::Value,
+}
+
+/// A message on `/ws/telemetry`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WsEvent {
+    Telemetry {
+        device_id: String,
+        telemetry: DeviceTelemetry,
+        /// Values per the subscription's [`DerivedSpec`], if it asked for
+        /// them and the device has a temperature reading.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        derived: Option<DerivedValues>,
+    },
+    /// First message on connect: the caller's live-view curve smoothing.
+    Smoothing { smoothing: crate::SmoothingConfig },
+    Cue {
+        device_id: String,
+        cue: serde_json::Value,
+    },
+    /// Several boards were detected publishing under `device_id`.
+    Conflict {
+        device_id: String,
+        conflict: DeviceConflict,
+    },
+    /// An alert was raised, acknowledged or resolved.
+    Alert {
+        device_id: Option<String>,
+        alert: crate::Alert,
+    },
+    /// A between-batches preheat run started, got ready or ended.
+    Preheat {
+        device_id: String,
+        preheat: serde_json::Value,
+    },
+    /// A device status message, sent when opted in to [`WsOptIn::Status`].
+    Status {
+        device_id: String,
+        status: serde_json::Value,
+    },
+    /// A device was created, updated or deleted (`op`), sent when opted in
+    /// to [`WsOptIn::Devices`].
+    Device {
+        device_id: String,
+        device: serde_json::Value,
+    },
+    Autotune {
+        device_id: String,
+        autotune: AutotuneUpdate,
+    },
+    /// Autotune payload that was not valid JSON.
+    AutotuneRaw {
+        device_id: String,
+        autotune_raw: AutotuneUpdate,
+    },
+    /// The last subscribe's `derived` was invalid and was ignored.
+    SubscribeError { subscribe_error: String },
+}
+
+impl WsEvent {
+    /// `None` for alerts not tied to a device and the smoothing hint.
+    pub fn device_id(&self) -> Option<&str> {
+        match self {
+            WsEvent::Telemetry { device_id, .. }
+            | WsEvent::Cue { device_id, .. }
+            | WsEvent::Conflict { device_id, .. }
+            | WsEvent::Preheat { device_id, .. }
+            | WsEvent::Status { device_id, .. }
+            | WsEvent::Device { device_id, .. }
+            | WsEvent::Autotune { device_id, .. }
+            | WsEvent::AutotuneRaw { device_id, .. } => Some(device_id),
+            WsEvent::Alert { device_id, .. } => device_id.as_deref(),
+            WsEvent::Smoothing { .. } | WsEvent::SubscribeError { .. } => None,
+        }
+    }
+}
+
+/// Opt-in message types on `/ws/telemetry`, off by default.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum WsOptIn {
+    Status,
+    Devices,
+}
+
+/// How telemetry is derived for one `/ws/telemetry` client. Fields left
+/// `None` take the server's defaults: RoR per minute over 30 s by moving
+/// average, raw temperatures, °C.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DerivedSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ror_window_secs: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ror_algorithm: Option<crate::RorAlgorithm>,
+    /// `per_minute` or `per_30s`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ror_unit: Option<String>,
+    /// Readings the temperatures are averaged over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smoothing_samples: Option<u32>,
+    /// `C` or `F`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<String>,
+}
+
+/// A telemetry reading derived per the client's [`DerivedSpec`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedValues {
+    pub bean_temp: Option<f64>,
+    pub env_temp: Option<f64>,
+    pub rate_of_rise: Option<f64>,
+    pub unit: String,
+}
+
+/// Client-to-server message on `/ws/telemetry` limiting the devices
+/// forwarded (empty = all). `events` replaces the opt-ins and `derived`
+/// the derived values; `None` keeps them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsSubscribe {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub device_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<WsOptIn>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derived: Option<DerivedSpec>,
+}
+
+impl WsSubscribe {
+    pub fn new(device_ids: Vec<String>) -> Self {
+        Self {
+            kind: "subscribe".to_string(),
+            device_ids,
+            events: None,
+            derived: None,
+        }
+    }
+
+    pub fn with_events(device_ids: Vec<String>, events: Vec<WsOptIn>) -> Self {
+        Self {
+            events: Some(events),
+            ..Self::new(device_ids)
+        }
+    }
+
+    pub fn with_derived(device_ids: Vec<String>, derived: DerivedSpec) -> Self {
+        Self {
+            derived: Some(derived),
+            ..Self::new(device_ids)
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+use reqwest::StatusCode;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The server answered with a non-success status.
+    Api {
+        status: StatusCode,
+        message: String,
+    },
+    /// The request could not be sent or its body not read.
+    Http(reqwest::Error),
+    /// A response or WebSocket message did not match the expected type.
+    Decode(serde_json::Error),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    InvalidUrl(String),
+}
+
+impl Error {
+    /// HTTP status for API errors.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Api { status, .. } => Some(*status),
+            Error::Http(e) => e.status(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Api { status, message } => write!(f, "{}: {}", status, message),
+            Error::Http(e) => write!(f, "request failed: {}", e),
+            Error::Decode(e) => write!(f, "unexpected response: {}", e),
+            Error::WebSocket(e) => write!(f, "websocket error: {}", e),
+            Error::InvalidUrl(url) => write!(f, "invalid url: {}", url),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::WebSocket(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
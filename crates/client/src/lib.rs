@@ -0,0 +1,541 @@
+//! Typed client for the rustRoast REST and WebSocket APIs.
+//!
+//! ```no_run
+//! # async fn demo() -> rustroast_client::Result<()> {
+//! use rustroast_client::{Client, ControlCommand};
+//!
+//! let client = Client::builder("http://localhost:8080")
+//!     .token("rr_...")
+//!     .build()?;
+//! client
+//!     .control("roaster-1", &ControlCommand::FanPwm { value: 200 })
+//!     .await?;
+//! let mut stream = client.telemetry_stream().await?;
+//! while let Some(event) = stream.next().await {
+//!     println!("{:?}", event?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Idempotent requests (GET, PUT, DELETE) are retried with exponential
+//! backoff on connection errors and 502/503/504 responses; see
+//! [`RetryPolicy`].
+
+mod error;
+mod ws;
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+pub use error::{Error, Result};
+pub use rustroast_api_types::*;
+pub use ws::TelemetryStream;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+pub struct ClientBuilder {
+    base_url: String,
+    token: Option<String>,
+    timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl ClientBuilder {
+    /// Bearer token: an `rr_` API key, a session token or the admin token.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Per-request timeout (default 10 s).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let base_url =
+            Url::parse(&self.base_url).map_err(|_| Error::InvalidUrl(self.base_url.clone()))?;
+        if base_url.cannot_be_a_base() {
+            return Err(Error::InvalidUrl(self.base_url));
+        }
+        let http = reqwest::Client::builder().timeout(self.timeout).build()?;
+        Ok(Client {
+            http,
+            base_url,
+            token: self.token,
+            retry: self.retry,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    /// Client for the server at `base_url`, e.g. `http://localhost:8080`.
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            token: None,
+            timeout: Duration::from_secs(10),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn new(base_url: impl Into<String>) -> Result<Self> {
+        Self::builder(base_url).build()
+    }
+
+    // ---- Health and devices ----
+
+    /// `Ok` when `/healthz` answers.
+    pub async fn health(&self) -> Result<()> {
+        self.send(Method::GET, &["healthz"], |r| r).await?;
+        Ok(())
+    }
+
+    /// Devices seen on MQTT status topics since the server started.
+    pub async fn devices(&self) -> Result<Vec<DeviceInfo>> {
+        let resp: DevicesResponse = self.get_json(&["api", "devices", "registry"]).await?;
+        Ok(resp.devices)
+    }
+
+    /// Device ids currently flagged as shared by several boards.
+    pub async fn device_conflicts(&self) -> Result<Vec<DeviceConflict>> {
+        self.get_json(&["api", "devices", "conflicts"]).await
+    }
+
+    /// Clear a device id conflict after fixing the duplicate board.
+    pub async fn clear_device_conflict(&self, device_id: &str) -> Result<()> {
+        self.delete(&["api", "roaster", device_id, "conflict"])
+            .await
+    }
+
+    /// The newest buffered firmware log lines of a device, oldest first.
+    pub async fn device_logs(
+        &self,
+        device_id: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<DeviceLogLine>> {
+        let resp = self
+            .send(Method::GET, &["api", "roaster", device_id, "logs"], |r| {
+                r.query(&[("limit", limit)])
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Flag a device for troubleshooting so its log lines are persisted.
+    pub async fn start_troubleshooting(&self, device_id: &str) -> Result<LogCapture> {
+        let resp = self
+            .send(
+                Method::PUT,
+                &["api", "roaster", device_id, "troubleshooting"],
+                |r| r,
+            )
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn stop_troubleshooting(&self, device_id: &str) -> Result<()> {
+        self.delete(&["api", "roaster", device_id, "troubleshooting"])
+            .await
+    }
+
+    /// Alert history, newest first.
+    pub async fn alerts(&self, query: &AlertListQuery) -> Result<Vec<Alert>> {
+        let resp = self
+            .send(Method::GET, &["api", "alerts"], |r| r.query(query))
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Mark a firing alert as being handled. `by` defaults to the token's
+    /// user.
+    pub async fn acknowledge_alert(&self, id: &str, by: Option<&str>) -> Result<Alert> {
+        let request = AlertActionRequest {
+            by: by.map(str::to_string),
+        };
+        self.post_json(&["api", "alerts", id, "acknowledge"], &request)
+            .await
+    }
+
+    pub async fn resolve_alert(&self, id: &str, by: Option<&str>) -> Result<Alert> {
+        let request = AlertActionRequest {
+            by: by.map(str::to_string),
+        };
+        self.post_json(&["api", "alerts", id, "resolve"], &request)
+            .await
+    }
+
+    /// Most recent telemetry from a device, `None` if it has not sent any.
+    pub async fn latest_telemetry(&self, device_id: &str) -> Result<Option<LatestTelemetry>> {
+        not_found_as_none(
+            self.get_json(&["api", "roaster", device_id, "telemetry", "latest"])
+                .await,
+        )
+    }
+
+    // ---- Control ----
+
+    /// Send one control command through its `/control/{op}` endpoint.
+    pub async fn control(&self, device_id: &str, command: &ControlCommand) -> Result<()> {
+        let mut body = serde_json::to_value(command)?;
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("op");
+        }
+        self.send(
+            Method::POST,
+            &["api", "roaster", device_id, "control", command.op()],
+            |r| r.json(&body),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Controls the device offers with their ranges and current values,
+    /// `None` for a device the server does not know.
+    pub async fn controls(&self, device_id: &str) -> Result<Option<DeviceControlSurface>> {
+        not_found_as_none(
+            self.get_json(&["api", "roaster", device_id, "controls"])
+                .await,
+        )
+    }
+
+    pub async fn control_batch(
+        &self,
+        device_id: &str,
+        request: &ControlBatchRequest,
+    ) -> Result<ControlBatchResponse> {
+        self.post_json(&["api", "roaster", device_id, "control", "batch"], request)
+            .await
+    }
+
+    // ---- Sessions ----
+
+    pub async fn list_sessions(&self, query: &SessionListQuery) -> Result<Vec<RoastSession>> {
+        let resp = self
+            .send(Method::GET, &["api", "sessions"], |r| r.query(query))
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn create_session(&self, request: &CreateSessionRequest) -> Result<RoastSession> {
+        self.post_json(&["api", "sessions"], request).await
+    }
+
+    /// A session with its telemetry, profile and results.
+    pub async fn get_session(&self, id: &str) -> Result<Option<SessionWithTelemetry>> {
+        not_found_as_none(self.get_json(&["api", "sessions", id]).await)
+    }
+
+    /// Session telemetry narrowed to a window and/or downsampled.
+    pub async fn session_telemetry(
+        &self,
+        id: &str,
+        range: &TelemetryRangeQuery,
+    ) -> Result<SessionWithTelemetry> {
+        let resp = self
+            .send(Method::GET, &["api", "sessions", id, "telemetry"], |r| {
+                r.query(range)
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Raw and smoothed series of a session, with the caller's smoothing
+    /// for `query.view` and any overrides applied.
+    pub async fn smoothed_telemetry(
+        &self,
+        id: &str,
+        query: &SmoothedTelemetryQuery,
+    ) -> Result<SmoothedSessionTelemetry> {
+        let resp = self
+            .send(
+                Method::GET,
+                &["api", "sessions", id, "telemetry", "smoothed"],
+                |r| r.query(query),
+            )
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Curve smoothing for a view (`live` when `None`).
+    pub async fn smoothing(&self, view: Option<&str>) -> Result<SmoothingConfig> {
+        let resp = self
+            .send(Method::GET, &["api", "smoothing"], |r| {
+                r.query(&[("view", view)])
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Save the signed-in user's smoothing for a view.
+    pub async fn set_smoothing_preset(
+        &self,
+        view: &str,
+        config: &SmoothingConfig,
+    ) -> Result<SmoothingPreset> {
+        let resp = self
+            .send(Method::PUT, &["api", "me", "smoothing", view], |r| {
+                r.json(config)
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn start_session(&self, id: &str) -> Result<RoastSession> {
+        self.session_action(id, "start").await
+    }
+
+    pub async fn pause_session(&self, id: &str) -> Result<RoastSession> {
+        self.session_action(id, "pause").await
+    }
+
+    pub async fn resume_session(&self, id: &str) -> Result<RoastSession> {
+        self.session_action(id, "resume").await
+    }
+
+    pub async fn complete_session(&self, id: &str) -> Result<RoastSession> {
+        self.session_action(id, "complete").await
+    }
+
+    async fn session_action(&self, id: &str, action: &str) -> Result<RoastSession> {
+        let resp = self
+            .send(Method::POST, &["api", "sessions", id, action], |r| r)
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn add_event(
+        &self,
+        session_id: &str,
+        request: &CreateRoastEventRequest,
+    ) -> Result<RoastEvent> {
+        self.post_json(&["api", "sessions", session_id, "events"], request)
+            .await
+    }
+
+    /// Add several events at once. Either all are stored or none.
+    pub async fn add_events(
+        &self,
+        session_id: &str,
+        events: &[CreateRoastEventRequest],
+    ) -> Result<Vec<RoastEvent>> {
+        #[derive(Serialize)]
+        struct Bulk<'a> {
+            events: &'a [CreateRoastEventRequest],
+        }
+        self.post_json(
+            &["api", "sessions", session_id, "events", "bulk"],
+            &Bulk { events },
+        )
+        .await
+    }
+
+    /// Mark an event now. The server fills in the elapsed time and the
+    /// latest bean temperature.
+    pub async fn tap_event(
+        &self,
+        session_id: &str,
+        event_type: RoastEventType,
+    ) -> Result<RoastEvent> {
+        #[derive(Serialize)]
+        struct Tap {
+            #[serde(rename = "type")]
+            event_type: RoastEventType,
+        }
+        let resp = self
+            .send(
+                Method::POST,
+                &["api", "sessions", session_id, "events", "now"],
+                |r| r.query(&Tap { event_type }),
+            )
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn list_events(&self, session_id: &str) -> Result<Vec<RoastEvent>> {
+        self.get_json(&["api", "sessions", session_id, "events"])
+            .await
+    }
+
+    // ---- WebSocket ----
+
+    /// Connect to `/ws/telemetry` for live telemetry, cue and autotune events.
+    pub async fn telemetry_stream(&self) -> Result<TelemetryStream> {
+        let mut url = self.url(&["ws", "telemetry"])?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .map_err(|_| Error::InvalidUrl(url.to_string()))?;
+        let mut request = url.as_str().into_client_request()?;
+        if let Some(token) = &self.token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|_| Error::InvalidUrl("token is not a valid header value".into()))?;
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(TelemetryStream::new(socket))
+    }
+
+    // ---- Raw requests ----
+
+    /// GET a JSON resource by path segments, for endpoints without a
+    /// dedicated method. Segments are percent-encoded.
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &[&str]) -> Result<T> {
+        let resp = self.send(Method::GET, path, |r| r).await?;
+        Ok(resp.json().await?)
+    }
+
+    /// POST a JSON body and decode the JSON response.
+    pub async fn post_json<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &[&str],
+        body: &B,
+    ) -> Result<T> {
+        let resp = self.send(Method::POST, path, |r| r.json(body)).await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn delete(&self, path: &[&str]) -> Result<()> {
+        self.send(Method::DELETE, path, |r| r).await?;
+        Ok(())
+    }
+
+    fn url(&self, path: &[&str]) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| Error::InvalidUrl(self.base_url.to_string()))?
+            .pop_if_empty()
+            .extend(path);
+        Ok(url)
+    }
+
+    /// Send a request with auth, retrying idempotent methods on transient
+    /// failures, and turn non-success statuses into [`Error::Api`].
+    async fn send(
+        &self,
+        method: Method,
+        path: &[&str],
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response> {
+        let url = self.url(path)?;
+        let idempotent = matches!(
+            method,
+            Method::GET | Method::HEAD | Method::PUT | Method::DELETE
+        );
+        let mut attempt = 0;
+        loop {
+            let mut request = self.http.request(method.clone(), url.clone());
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            let result = build(request).send().await;
+            let retryable = match &result {
+                Ok(resp) => matches!(
+                    resp.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if idempotent && retryable && attempt < self.retry.max_retries {
+                tokio::time::sleep(self.retry.backoff(attempt)).await;
+                attempt += 1;
+                continue;
+            }
+            let resp = result?;
+            if resp.status().is_success() {
+                return Ok(resp);
+            }
+            return Err(api_error(resp).await);
+        }
+    }
+}
+
+/// Error from a failed response, using the `error` field of JSON bodies.
+async fn api_error(resp: Response) -> Error {
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|v| v.get("error")?.as_str().map(str::to_string))
+        .unwrap_or(text);
+    Error::Api { status, message }
+}
+
+fn not_found_as_none<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_segments_and_backoff() {
+        let client = Client::new("http://localhost:8080/roast/").unwrap();
+        assert_eq!(
+            client
+                .url(&["api", "roaster", "dev 1/a", "telemetry"])
+                .unwrap()
+                .as_str(),
+            "http://localhost:8080/roast/api/roaster/dev%201%2Fa/telemetry"
+        );
+        assert!(matches!(
+            Client::new("not a url"),
+            Err(Error::InvalidUrl(_))
+        ));
+
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.backoff(0), Duration::from_millis(200));
+        assert_eq!(retry.backoff(2), Duration::from_millis(800));
+        assert_eq!(retry.backoff(10), Duration::from_secs(5));
+    }
+}
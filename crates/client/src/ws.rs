@@ -0,0 +1,69 @@
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::error::Result;
+use rustroast_api_types::{DerivedSpec, WsEvent, WsOptIn, WsSubscribe};
+
+/// A connection to `/ws/telemetry`. Server pings are answered while
+/// reading, so call [`TelemetryStream::next`] continuously.
+pub struct TelemetryStream {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl TelemetryStream {
+    pub(crate) fn new(socket: WebSocketStream<MaybeTlsStream<TcpStream>>) -> Self {
+        Self { socket }
+    }
+
+    /// Next event, or `None` once the server closes the connection.
+    pub async fn next(&mut self) -> Option<Result<WsEvent>> {
+        loop {
+            match self.socket.next().await? {
+                Ok(Message::Text(text)) => {
+                    return Some(serde_json::from_str(&text).map_err(Into::into))
+                }
+                Ok(Message::Close(_)) => return None,
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+
+    /// Only receive events for these devices (empty = all).
+    pub async fn subscribe(&mut self, device_ids: Vec<String>) -> Result<()> {
+        let msg = serde_json::to_string(&WsSubscribe::new(device_ids))?;
+        self.socket.send(Message::Text(msg)).await?;
+        Ok(())
+    }
+
+    /// Like [`TelemetryStream::subscribe`], also choosing the opt-in
+    /// message types (status updates, device registry changes).
+    pub async fn subscribe_with(
+        &mut self,
+        device_ids: Vec<String>,
+        events: Vec<WsOptIn>,
+    ) -> Result<()> {
+        let msg = serde_json::to_string(&WsSubscribe::with_events(device_ids, events))?;
+        self.socket.send(Message::Text(msg)).await?;
+        Ok(())
+    }
+
+    /// Like [`TelemetryStream::subscribe`], also asking for telemetry
+    /// derived the client's way (RoR window, smoothing, units).
+    pub async fn subscribe_derived(
+        &mut self,
+        device_ids: Vec<String>,
+        derived: DerivedSpec,
+    ) -> Result<()> {
+        let msg = serde_json::to_string(&WsSubscribe::with_derived(device_ids, derived))?;
+        self.socket.send(Message::Text(msg)).await?;
+        Ok(())
+    }
+
+    pub async fn close(mut self) -> Result<()> {
+        self.socket.close(None).await?;
+        Ok(())
+    }
+}
@@ -1,13 +1,141 @@
 use serde::{Deserialize, Serialize};
 
-// Initial set of commands. We can evolve this schema.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::topics::{
+    control_emergency_stop, control_fan_pwm, control_heater_enable, control_heater_pwm,
+    control_mode, control_pid, control_setpoint,
+};
+
+/// A command to a roaster's firmware, one per `roaster/{device_id}/control/*`
+/// topic. Build the message with [`Command::topic`] and [`Command::payload`]
+/// after [`Command::validate`], rather than formatting it by hand.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "value")]
 pub enum Command {
-    Start,
-    Stop,
-    SetHeaterPower(u8), // 0..=100
-    SetFanSpeed(u8),    // 0..=100
-    SetDrumSpeed(u8),   // 0..=100
-    SetProfileId(String),
+    /// Target bean temperature, 0..=300 °C.
+    Setpoint(f64),
+    /// Fan duty, 0..=255.
+    FanPwm(u16),
+    /// Heater duty, 0..=100.
+    HeaterPwm(u8),
+    Mode(ControlMode),
+    HeaterEnable(bool),
+    Pid {
+        kp: f64,
+        ki: f64,
+        kd: f64,
+    },
+    EmergencyStop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlMode {
+    Auto,
+    Manual,
+}
+
+impl ControlMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ControlMode::Auto => "auto",
+            ControlMode::Manual => "manual",
+        }
+    }
+}
+
+impl std::str::FromStr for ControlMode {
+    type Err = &'static str;
+
+    /// Case-insensitive `auto` or `manual`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ControlMode::Auto),
+            "manual" => Ok(ControlMode::Manual),
+            _ => Err("mode must be 'auto' or 'manual'"),
+        }
+    }
+}
+
+impl Command {
+    /// Whether the firmware accepts the value; the error is user-facing.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            Command::Setpoint(value) if !(0.0..=300.0).contains(value) => {
+                Err("setpoint must be between 0 and 300 C")
+            }
+            Command::FanPwm(value) if *value > 255 => Err("fan_pwm must be 0..255"),
+            Command::HeaterPwm(value) if *value > 100 => Err("heater_pwm must be 0..100"),
+            Command::Pid { kp, ki, kd } if ![kp, ki, kd].iter().all(|g| g.is_finite()) => {
+                Err("PID gains must be numbers")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn topic(&self, device_id: &str) -> String {
+        match self {
+            Command::Setpoint(_) => control_setpoint(device_id),
+            Command::FanPwm(_) => control_fan_pwm(device_id),
+            Command::HeaterPwm(_) => control_heater_pwm(device_id),
+            Command::Mode(_) => control_mode(device_id),
+            Command::HeaterEnable(_) => control_heater_enable(device_id),
+            Command::Pid { .. } => control_pid(device_id),
+            Command::EmergencyStop => control_emergency_stop(device_id),
+        }
+    }
+
+    /// The message body the firmware parses: plain numbers and words, JSON
+    /// for the PID gains.
+    pub fn payload(&self) -> String {
+        match self {
+            Command::Setpoint(value) => value.to_string(),
+            Command::FanPwm(value) => value.to_string(),
+            Command::HeaterPwm(value) => value.to_string(),
+            Command::Mode(mode) => mode.as_str().to_string(),
+            Command::HeaterEnable(enabled) => if *enabled { "1" } else { "0" }.to_string(),
+            Command::Pid { kp, ki, kd } => {
+                serde_json::json!({"kp": kp, "ki": ki, "kd": kd}).to_string()
+            }
+            Command::EmergencyStop => "1".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_messages() {
+        let message = |cmd: Command| (cmd.topic("dev1"), cmd.payload());
+        assert_eq!(
+            message(Command::Setpoint(220.0)),
+            (
+                "roaster/dev1/control/setpoint".to_string(),
+                "220".to_string()
+            )
+        );
+        assert_eq!(message(Command::Setpoint(212.5)).1, "212.5");
+        assert_eq!(message(Command::Mode("Auto".parse().unwrap())).1, "auto");
+        assert_eq!(message(Command::HeaterEnable(false)).1, "0");
+        let (topic, payload) = message(Command::Pid {
+            kp: 2.0,
+            ki: 0.5,
+            kd: 1.0,
+        });
+        assert_eq!(topic, "roaster/dev1/control/pid");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&payload).unwrap(),
+            serde_json::json!({"kp": 2.0, "ki": 0.5, "kd": 1.0})
+        );
+        assert_eq!(
+            message(Command::EmergencyStop).0,
+            "roaster/dev1/control/emergency_stop"
+        );
+
+        assert!(Command::Setpoint(301.0).validate().is_err());
+        assert!(Command::FanPwm(256).validate().is_err());
+        assert!(Command::HeaterPwm(100).validate().is_ok());
+        assert!("off".parse::<ControlMode>().is_err());
+    }
 }
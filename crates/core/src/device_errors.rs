@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// Error codes for the telemetry `systemStatus` field (0 = normal).
+///
+/// Only codes taken from the firmware get a variant. The firmware source is
+/// not part of this repository and current firmware only reports 0, so no
+/// code is known yet and every non-zero one decodes as `Unknown`. Add a
+/// variant (and its row in the hardware integration guide) once the
+/// firmware defines the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceError {
+    Unknown(i64),
+}
+
+/// A decoded `systemStatus`, as attached to telemetry and status responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceErrorInfo {
+    pub code: i64,
+    pub name: String,
+    pub description: String,
+}
+
+impl DeviceError {
+    /// The codes with a known meaning.
+    pub const ALL: [DeviceError; 0] = [];
+
+    /// Decode a `systemStatus` value. `None` for 0 (no error).
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            0 => None,
+            other => Some(DeviceError::Unknown(other)),
+        }
+    }
+
+    /// Decode the `systemStatus` field of a telemetry or status payload.
+    pub fn from_payload(payload: &serde_json::Value) -> Option<Self> {
+        payload
+            .get("systemStatus")
+            .and_then(|v| v.as_i64())
+            .and_then(Self::from_code)
+    }
+
+    pub fn code(&self) -> i64 {
+        match self {
+            DeviceError::Unknown(code) => *code,
+        }
+    }
+
+    /// Stable snake_case name, e.g. for metric labels.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceError::Unknown(_) => "unknown",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            DeviceError::Unknown(_) => "Unknown error code",
+        }
+    }
+
+    pub fn info(&self) -> DeviceErrorInfo {
+        DeviceErrorInfo {
+            code: self.code(),
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.description(), self.code())
+    }
+}
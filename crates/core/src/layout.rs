@@ -0,0 +1,186 @@
+// Versioned topic layouts. Firmware up to schema 1 publishes under
+// `roaster/{device_id}/...`; schema 2 firmware moves the device tree to
+// `roaster/v2/{device_id}/...` and announces `"topic_schema": 2` in its
+// status. The paths below the device root are the same in both, so `v2`
+// is reserved and can't be used as a device id in the v1 layout.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::topics::{ParsedTopic, ROOT};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TopicLayout {
+    /// `roaster/{device_id}/...`
+    #[default]
+    V1,
+    /// `roaster/v2/{device_id}/...`
+    V2,
+}
+
+impl TopicLayout {
+    pub const ALL: [TopicLayout; 2] = [TopicLayout::V1, TopicLayout::V2];
+
+    /// The layout for a `topic_schema` advertised by firmware, if known.
+    pub fn from_schema(schema: u64) -> Option<Self> {
+        match schema {
+            1 => Some(TopicLayout::V1),
+            2 => Some(TopicLayout::V2),
+            _ => None,
+        }
+    }
+
+    pub fn schema(self) -> u64 {
+        match self {
+            TopicLayout::V1 => 1,
+            TopicLayout::V2 => 2,
+        }
+    }
+
+    pub fn device_root(self, device_id: &str) -> String {
+        match self {
+            TopicLayout::V1 => format!("{}/{}", ROOT, device_id),
+            TopicLayout::V2 => format!("{}/v2/{}", ROOT, device_id),
+        }
+    }
+
+    /// `path` below the device root, e.g. `control/setpoint`.
+    pub fn topic(self, device_id: &str, path: &str) -> String {
+        format!("{}/{}", self.device_root(device_id), path)
+    }
+
+    /// `path` below any device root, e.g. `roaster/v2/+/telemetry`.
+    pub fn wildcard(self, path: &str) -> String {
+        self.topic("+", path)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Negotiated {
+    advertised: Option<TopicLayout>,
+    observed: Option<TopicLayout>,
+}
+
+/// Which layout to address each device in. A device's advertised
+/// `topic_schema` wins; otherwise the layout it was last heard publishing
+/// in, and [`TopicLayout::V1`] for devices not heard from yet.
+#[derive(Debug, Default)]
+pub struct TopicNegotiator {
+    devices: RwLock<HashMap<String, Negotiated>>,
+}
+
+impl TopicNegotiator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A message from `device_id` arrived in `layout`.
+    pub fn observe(&self, device_id: &str, layout: TopicLayout) {
+        let mut devices = self.devices.write().unwrap();
+        devices.entry(device_id.to_string()).or_default().observed = Some(layout);
+    }
+
+    /// `device_id` announced `topic_schema`. Unknown schemas are ignored and
+    /// leave the device on the layout it was observed in. Returns the
+    /// layout the schema maps to.
+    pub fn advertise(&self, device_id: &str, schema: u64) -> Option<TopicLayout> {
+        let layout = TopicLayout::from_schema(schema)?;
+        let mut devices = self.devices.write().unwrap();
+        devices.entry(device_id.to_string()).or_default().advertised = Some(layout);
+        Some(layout)
+    }
+
+    pub fn layout(&self, device_id: &str) -> TopicLayout {
+        self.devices
+            .read()
+            .unwrap()
+            .get(device_id)
+            .and_then(|n| n.advertised.or(n.observed))
+            .unwrap_or_default()
+    }
+
+    /// Move a device topic built by the `topics` helpers (or in any layout)
+    /// into the layout negotiated for its device. Other topics are returned
+    /// unchanged.
+    pub fn route(&self, topic: &str) -> String {
+        let Ok((current, parsed)) = ParsedTopic::parse_layout(topic) else {
+            return topic.to_string();
+        };
+        let device_id = parsed.device_id();
+        let target = self.layout(device_id);
+        if target == current {
+            return topic.to_string();
+        }
+        let path = &topic[current.device_root(device_id).len() + 1..];
+        target.topic(device_id, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topics::{control_setpoint, telemetry_topic};
+
+    #[test]
+    fn test_layouts_parse_back() {
+        for layout in TopicLayout::ALL {
+            let topic = layout.topic("dev1", "control/setpoint");
+            assert_eq!(
+                ParsedTopic::parse_layout(&topic),
+                Ok((
+                    layout,
+                    ParsedTopic::Control {
+                        device_id: "dev1".to_string(),
+                        name: "setpoint".to_string()
+                    }
+                ))
+            );
+        }
+        assert_eq!(
+            TopicLayout::V1.topic("dev1", "telemetry"),
+            telemetry_topic("dev1")
+        );
+        assert_eq!(TopicLayout::V2.wildcard("status"), "roaster/v2/+/status");
+        // A v1 device called `v2` still parses, as long as it has no sub path
+        assert_eq!(
+            ParsedTopic::parse_layout("roaster/v2/status"),
+            Ok((
+                TopicLayout::V1,
+                ParsedTopic::Status {
+                    device_id: "v2".to_string()
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_negotiation_routes_per_device() {
+        let negotiator = TopicNegotiator::new();
+        let v1 = control_setpoint("old");
+        assert_eq!(negotiator.route(&v1), v1);
+
+        negotiator.observe("new", TopicLayout::V2);
+        assert_eq!(
+            negotiator.route(&control_setpoint("new")),
+            "roaster/v2/new/control/setpoint"
+        );
+        assert_eq!(negotiator.route(&v1), v1);
+
+        // The advertised schema beats what was observed (e.g. a stale
+        // retained message in the other tree)
+        negotiator.advertise("new", 1);
+        negotiator.observe("new", TopicLayout::V2);
+        assert_eq!(negotiator.layout("new"), TopicLayout::V1);
+        assert_eq!(
+            negotiator.route("roaster/v2/new/control/setpoint"),
+            control_setpoint("new")
+        );
+        assert_eq!(negotiator.advertise("new", 9), None);
+        assert_eq!(negotiator.layout("new"), TopicLayout::V1);
+
+        assert_eq!(
+            negotiator.route("rustroast/cluster/heartbeat"),
+            "rustroast/cluster/heartbeat"
+        );
+    }
+}
@@ -1,5 +1,13 @@
 pub mod commands;
+pub mod device_errors;
+pub mod layout;
+pub mod status;
+pub mod temperature;
 pub mod topics;
 
 pub use commands::*;
+pub use device_errors::*;
+pub use layout::*;
+pub use status::*;
+pub use temperature::*;
 pub use topics::*;
@@ -0,0 +1,168 @@
+use std::net::IpAddr;
+
+use serde_json::Value;
+
+/// Signal strengths a radio can report, in dBm.
+const RSSI_RANGE: std::ops::RangeInclusive<i64> = -127..=0;
+
+/// The fields of a device status message (`roaster/{device_id}/status`)
+/// the server reads. Firmware builds disagree on types, so each field is
+/// read the same way everywhere:
+///
+/// - `status`, `id`, `ip` and `version` are trimmed strings; a number `id`
+///   or `version` (e.g. `"version": 2`) is taken as its text.
+/// - `ip` must be an IPv4 or IPv6 address.
+/// - `rssi` (dBm, between -127 and 0) and `uptime` (seconds, not
+///   negative) are rounded, from a number or numeric string.
+/// - `topic_schema` is the topic layout the firmware publishes in (see
+///   [`TopicLayout`](crate::TopicLayout)), read like `uptime`.
+///
+/// A field that is present but unusable is left `None` and named in
+/// `invalid`. Empty strings and `null` count as absent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceStatus {
+    pub status: Option<String>,
+    /// Hardware id, e.g. the chip id, which tells boards apart.
+    pub id: Option<String>,
+    pub ip: Option<String>,
+    pub version: Option<String>,
+    pub rssi: Option<i64>,
+    pub uptime: Option<u64>,
+    pub topic_schema: Option<u64>,
+    pub invalid: Vec<&'static str>,
+}
+
+impl DeviceStatus {
+    pub fn from_payload(payload: &Value) -> Self {
+        let mut fields = Fields {
+            payload,
+            invalid: Vec::new(),
+        };
+        DeviceStatus {
+            status: fields.read("status", text),
+            id: fields.read("id", text_or_number),
+            ip: fields.read("ip", |v| text(v).filter(|ip| ip.parse::<IpAddr>().is_ok())),
+            version: fields.read("version", text_or_number),
+            rssi: fields.read("rssi", |v| {
+                rounded(v).filter(|rssi| RSSI_RANGE.contains(rssi))
+            }),
+            uptime: fields.read("uptime", |v| {
+                rounded(v).and_then(|secs| u64::try_from(secs).ok())
+            }),
+            topic_schema: fields.read("topic_schema", |v| {
+                rounded(v).and_then(|schema| u64::try_from(schema).ok())
+            }),
+            invalid: fields.invalid,
+        }
+    }
+
+    /// Parse a raw status payload; `None` when it is not a JSON object.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        match serde_json::from_slice::<Value>(payload).ok()? {
+            value @ Value::Object(_) => Some(Self::from_payload(&value)),
+            _ => None,
+        }
+    }
+}
+
+struct Fields<'a> {
+    payload: &'a Value,
+    invalid: Vec<&'static str>,
+}
+
+impl Fields<'_> {
+    fn read<T>(&mut self, name: &'static str, read: impl Fn(&Value) -> Option<T>) -> Option<T> {
+        match self.payload.get(name) {
+            None | Some(Value::Null) => None,
+            Some(Value::String(s)) if s.trim().is_empty() => None,
+            Some(value) => {
+                let parsed = read(value);
+                if parsed.is_none() {
+                    self.invalid.push(name);
+                }
+                parsed
+            }
+        }
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(|s| s.trim().to_string())
+}
+
+fn text_or_number(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(n) => Some(n.to_string()),
+        other => text(other),
+    }
+}
+
+fn rounded(value: &Value) -> Option<i64> {
+    let number = match value {
+        Value::Number(n) => n.as_f64()?,
+        Value::String(s) => s.trim().parse::<f64>().ok()?,
+        _ => return None,
+    };
+    number.is_finite().then(|| number.round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_valid_status() {
+        let status = DeviceStatus::parse(
+            br#"{"status": " online ", "id": 11259375, "ip": "192.168.1.20",
+                 "version": "1.4.2", "rssi": "-61.6", "uptime": 3600.4, "topic_schema": 2}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            status,
+            DeviceStatus {
+                status: Some("online".to_string()),
+                id: Some("11259375".to_string()),
+                ip: Some("192.168.1.20".to_string()),
+                version: Some("1.4.2".to_string()),
+                rssi: Some(-62),
+                uptime: Some(3600),
+                topic_schema: Some(2),
+                invalid: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_fields_are_absent() {
+        assert_eq!(DeviceStatus::parse(b"{}"), Some(DeviceStatus::default()));
+        let status =
+            DeviceStatus::from_payload(&json!({"status": "online", "ip": "", "rssi": null}));
+        assert_eq!(status.status.as_deref(), Some("online"));
+        assert_eq!(status.ip, None);
+        assert_eq!(status.rssi, None);
+        assert!(status.invalid.is_empty());
+        // Not an object at all
+        assert_eq!(DeviceStatus::parse(b"[1, 2]"), None);
+        assert_eq!(DeviceStatus::parse(b"online"), None);
+    }
+
+    #[test]
+    fn test_unknown_values_are_named_invalid() {
+        let status = DeviceStatus::from_payload(&json!({
+            "status": 1,
+            "ip": "not-an-ip",
+            "rssi": 20,
+            "uptime": -5,
+            "topic_schema": "v2",
+            "version": ["1"],
+        }));
+        assert_eq!(
+            status,
+            DeviceStatus {
+                invalid: vec!["status", "ip", "version", "rssi", "uptime", "topic_schema"],
+                ..DeviceStatus::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+/// Unit temperatures are shown in. Everything is measured, stored and
+/// computed in °C and only converted on the way out.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum TemperatureUnit {
+    #[default]
+    #[serde(alias = "c", alias = "celsius")]
+    C,
+    #[serde(alias = "f", alias = "fahrenheit")]
+    F,
+}
+
+impl TemperatureUnit {
+    /// Convert an absolute temperature from °C.
+    pub fn convert(self, value: f32) -> f32 {
+        Temperature::celsius(value).in_unit(self)
+    }
+
+    /// Convert a temperature difference or rate (e.g. RoR) from °C.
+    pub fn convert_delta(self, value: f32) -> f32 {
+        match self {
+            TemperatureUnit::C => value,
+            TemperatureUnit::F => value * 9.0 / 5.0,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TemperatureUnit::C => "C",
+            TemperatureUnit::F => "F",
+        }
+    }
+}
+
+impl std::str::FromStr for TemperatureUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "c" | "celsius" => Ok(TemperatureUnit::C),
+            "f" | "fahrenheit" => Ok(TemperatureUnit::F),
+            _ => Err(format!("Invalid temperature unit: {}", s)),
+        }
+    }
+}
+
+/// An absolute temperature, held in °C. Serializes as the bare °C number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Temperature(f32);
+
+impl Temperature {
+    pub fn celsius(value: f32) -> Self {
+        Temperature(value)
+    }
+
+    /// A reading taken in `unit`.
+    pub fn from_unit(value: f32, unit: TemperatureUnit) -> Self {
+        match unit {
+            TemperatureUnit::C => Temperature(value),
+            TemperatureUnit::F => Temperature((value - 32.0) * 5.0 / 9.0),
+        }
+    }
+
+    pub fn as_celsius(self) -> f32 {
+        self.0
+    }
+
+    pub fn in_unit(self, unit: TemperatureUnit) -> f32 {
+        match unit {
+            TemperatureUnit::C => self.0,
+            TemperatureUnit::F => self.0 * 9.0 / 5.0 + 32.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temperature_units() {
+        let boiling = Temperature::celsius(100.0);
+        assert_eq!(boiling.in_unit(TemperatureUnit::F), 212.0);
+        assert_eq!(
+            Temperature::from_unit(212.0, TemperatureUnit::F).as_celsius(),
+            100.0
+        );
+        assert_eq!(TemperatureUnit::F.convert(-40.0), -40.0);
+        // A rate of 10 °C/min is 18 °F/min, without the offset
+        assert_eq!(TemperatureUnit::F.convert_delta(10.0), 18.0);
+        assert_eq!("Fahrenheit".parse(), Ok(TemperatureUnit::F));
+        assert!("kelvin".parse::<TemperatureUnit>().is_err());
+        assert_eq!(
+            serde_json::from_str::<TemperatureUnit>("\"f\"").unwrap(),
+            TemperatureUnit::F
+        );
+    }
+}
@@ -1,4 +1,7 @@
-// Topic layout helpers and constants matching ESP32 firmware
+// Topic layout helpers and constants matching ESP32 firmware. These build
+// the v1 layout; see `TopicLayout` for the versioned ones.
+
+use crate::layout::TopicLayout;
 
 pub const ROOT: &str = "roaster";
 
@@ -37,6 +40,36 @@ pub fn control_emergency_stop(device_id: &str) -> String {
     format!("{}/emergency_stop", control_root(device_id))
 }
 
+/// Safety-critical controls (emergency stop, heater enable) that must not
+/// wait behind other outgoing traffic, in either [`TopicLayout`].
+pub fn is_safety_control(topic: &str) -> bool {
+    matches!(
+        ParsedTopic::parse(topic),
+        Ok(ParsedTopic::Control { name, .. }) if name == "emergency_stop" || name == "heater_enable"
+    )
+}
+
+// Session-scoped topics (server-published)
+pub const SESSION_ROOT: &str = "rustroast";
+
+pub fn session_telemetry_topic(session_id: &str) -> String {
+    format!("{}/sessions/{}/telemetry", SESSION_ROOT, session_id)
+}
+
+// Coordination between server instances sharing the broker
+pub fn cluster_heartbeat_topic() -> &'static str {
+    "rustroast/cluster/heartbeat"
+}
+
+// Presence of a server instance: retained `online` after connecting, and
+// `offline` on shutdown or, as its Last Will, when the connection drops
+pub const SERVER_ONLINE: &str = "online";
+pub const SERVER_OFFLINE: &str = "offline";
+
+pub fn server_status_topic(client_id: &str) -> String {
+    format!("{}/server/{}/status", SESSION_ROOT, client_id)
+}
+
 // Auto-tune topics
 pub fn autotune_status(device_id: &str) -> String {
     format!("{}/{}/autotune/status", ROOT, device_id)
@@ -55,6 +88,9 @@ pub fn autotune_results(device_id: &str) -> String {
 }
 
 // Wildcards
+pub fn roaster_wildcard_all() -> &'static str {
+    "roaster/+/#"
+}
 pub fn telemetry_wildcard_all() -> &'static str {
     "roaster/+/telemetry"
 }
@@ -67,3 +103,198 @@ pub fn control_wildcard(device_id: &str) -> String {
 pub fn autotune_wildcard_all() -> &'static str {
     "roaster/+/autotune/#"
 }
+pub fn log_wildcard_all() -> &'static str {
+    "roaster/+/log"
+}
+
+/// Longest device id accepted in a topic.
+pub const MAX_DEVICE_ID_LEN: usize = 64;
+
+/// Whether `device_id` is safe to use as a cache key, in SQL and in topics:
+/// 1 to [`MAX_DEVICE_ID_LEN`] ASCII letters, digits, `-`, `_`, `.` or `:`
+/// (MAC addresses), not starting with `.`.
+pub fn is_valid_device_id(device_id: &str) -> bool {
+    !device_id.is_empty()
+        && device_id.len() <= MAX_DEVICE_ID_LEN
+        && !device_id.starts_with('.')
+        && device_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// Why a topic isn't a [`ParsedTopic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicError {
+    /// Not a `roaster/{device_id}/...` topic.
+    NotRoaster,
+    /// The device id segment fails [`is_valid_device_id`].
+    InvalidDeviceId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutotuneTopic {
+    Status,
+    Results,
+    Start,
+    Stop,
+    Apply,
+}
+
+impl AutotuneTopic {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AutotuneTopic::Status => "status",
+            AutotuneTopic::Results => "results",
+            AutotuneTopic::Start => "start",
+            AutotuneTopic::Stop => "stop",
+            AutotuneTopic::Apply => "apply",
+        }
+    }
+}
+
+/// A `roaster/{device_id}/...` topic by what it carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedTopic {
+    Telemetry {
+        device_id: String,
+    },
+    Status {
+        device_id: String,
+    },
+    Log {
+        device_id: String,
+    },
+    Autotune {
+        device_id: String,
+        topic: AutotuneTopic,
+    },
+    /// `control/{name}`, e.g. `setpoint`.
+    Control {
+        device_id: String,
+        name: String,
+    },
+    /// Anything else below the device, e.g. `error`.
+    Other {
+        device_id: String,
+        path: String,
+    },
+}
+
+impl ParsedTopic {
+    /// A device topic in either [`TopicLayout`].
+    pub fn parse(topic: &str) -> Result<Self, TopicError> {
+        Self::parse_layout(topic).map(|(_, parsed)| parsed)
+    }
+
+    /// [`parse`](Self::parse), along with the layout the topic is in.
+    pub fn parse_layout(topic: &str) -> Result<(TopicLayout, Self), TopicError> {
+        let rest = topic
+            .strip_prefix(ROOT)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .ok_or(TopicError::NotRoaster)?;
+        let (layout, rest) = match rest.strip_prefix("v2/") {
+            Some(v2) if v2.contains('/') => (TopicLayout::V2, v2),
+            // `roaster/v2/{path}` is a v1 device named `v2`
+            _ => (TopicLayout::V1, rest),
+        };
+        let (device_id, path) = rest
+            .split_once('/')
+            .filter(|(_, path)| !path.is_empty())
+            .ok_or(TopicError::NotRoaster)?;
+        if !is_valid_device_id(device_id) {
+            return Err(TopicError::InvalidDeviceId);
+        }
+        let device_id = device_id.to_string();
+        let (kind, sub) = path.split_once('/').unwrap_or((path, ""));
+        let autotune = match (kind, sub) {
+            ("autotune", "status") => Some(AutotuneTopic::Status),
+            ("autotune", "results") => Some(AutotuneTopic::Results),
+            ("autotune", "start") => Some(AutotuneTopic::Start),
+            ("autotune", "stop") => Some(AutotuneTopic::Stop),
+            ("autotune", "apply") => Some(AutotuneTopic::Apply),
+            _ => None,
+        };
+        if let Some(topic) = autotune {
+            return Ok((layout, ParsedTopic::Autotune { device_id, topic }));
+        }
+        let parsed = match (kind, sub) {
+            ("telemetry", "") => ParsedTopic::Telemetry { device_id },
+            ("status", "") => ParsedTopic::Status { device_id },
+            ("log", "") => ParsedTopic::Log { device_id },
+            ("control", name) if !name.is_empty() && !name.contains('/') => ParsedTopic::Control {
+                device_id,
+                name: name.to_string(),
+            },
+            _ => ParsedTopic::Other {
+                device_id,
+                path: path.to_string(),
+            },
+        };
+        Ok((layout, parsed))
+    }
+
+    pub fn device_id(&self) -> &str {
+        match self {
+            ParsedTopic::Telemetry { device_id }
+            | ParsedTopic::Status { device_id }
+            | ParsedTopic::Log { device_id }
+            | ParsedTopic::Autotune { device_id, .. }
+            | ParsedTopic::Control { device_id, .. }
+            | ParsedTopic::Other { device_id, .. } => device_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_roaster_topics() {
+        let parse = |topic: &str| ParsedTopic::parse(topic);
+        assert_eq!(
+            parse(&telemetry_topic("esp32-001")),
+            Ok(ParsedTopic::Telemetry {
+                device_id: "esp32-001".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&autotune_results("dev1")),
+            Ok(ParsedTopic::Autotune {
+                device_id: "dev1".to_string(),
+                topic: AutotuneTopic::Results
+            })
+        );
+        assert_eq!(
+            parse(&control_setpoint("AA:BB:CC:00:11:22")),
+            Ok(ParsedTopic::Control {
+                device_id: "AA:BB:CC:00:11:22".to_string(),
+                name: "setpoint".to_string()
+            })
+        );
+        assert_eq!(
+            parse("roaster/dev1/telemetry/extra"),
+            Ok(ParsedTopic::Other {
+                device_id: "dev1".to_string(),
+                path: "telemetry/extra".to_string()
+            })
+        );
+        assert_eq!(
+            parse("roaster/dev1").map(|_| ()),
+            Err(TopicError::NotRoaster)
+        );
+        assert_eq!(
+            parse(cluster_heartbeat_topic()).map(|_| ()),
+            Err(TopicError::NotRoaster)
+        );
+
+        for hostile in ["dev'1", "..", "dev 1", "dev%2F1", "", &"x".repeat(65)] {
+            assert_eq!(
+                parse(&format!("roaster/{}/status", hostile)).map(|_| ()),
+                Err(TopicError::InvalidDeviceId),
+                "{:?}",
+                hostile
+            );
+        }
+    }
+}
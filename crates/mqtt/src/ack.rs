@@ -0,0 +1,170 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+use rumqttc::ClientError;
+use tokio::sync::oneshot;
+
+/// Why [`publish_and_wait`](crate::MqttService::publish_and_wait) gave up.
+#[derive(Debug, thiserror::Error)]
+pub enum PublishAckError {
+    #[error("publish failed: {0}")]
+    Client(#[from] ClientError),
+    /// No ack within the timeout. `packet_id` is set once the publish was
+    /// sent to the broker.
+    #[error("no ack within the timeout")]
+    Timeout { packet_id: Option<u16> },
+    /// The connection was lost before the ack arrived.
+    #[error("connection lost before the ack arrived")]
+    ConnectionLost { packet_id: Option<u16> },
+    /// The broker refused the publish (MQTT 5 only).
+    #[error("broker rejected publish {packet_id}: {rejection}")]
+    Rejected {
+        packet_id: u16,
+        rejection: PublishRejection,
+    },
+}
+
+/// A broker's refusal of a publish, from the reason code of its MQTT 5 ack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishRejection {
+    pub reason_code: u8,
+    /// Name of the reason code, e.g. `NotAuthorized`.
+    pub reason: String,
+    /// Detail the broker may add to the ack.
+    pub reason_string: Option<String>,
+}
+
+impl fmt::Display for PublishRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (0x{:02x})", self.reason, self.reason_code)?;
+        if let Some(detail) = &self.reason_string {
+            write!(f, ": {detail}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How the broker answered a publish.
+pub(crate) type AckOutcome = Result<(), PublishRejection>;
+
+/// The two halves of a publish waiting for its ack: the packet id the client
+/// assigns it, then the broker's ack of that packet id.
+pub(crate) struct AckWaiter {
+    pub packet_id: oneshot::Sender<u16>,
+    pub acked: oneshot::Sender<AckOutcome>,
+}
+
+impl AckWaiter {
+    pub fn new() -> (Self, oneshot::Receiver<u16>, oneshot::Receiver<AckOutcome>) {
+        let (packet_id, packet_id_rx) = oneshot::channel();
+        let (acked, acked_rx) = oneshot::channel();
+        (Self { packet_id, acked }, packet_id_rx, acked_rx)
+    }
+}
+
+/// Matches publishes to the packet ids rumqttc assigns them and to the acks
+/// the broker sends back. rumqttc reports outgoing publishes in the order
+/// they were handed to the client, so every publish is noted here in that
+/// same order, while holding the client lock.
+#[derive(Default)]
+pub(crate) struct AckTracker {
+    /// Publishes handed to the client and not yet reported as sent.
+    sent: Mutex<VecDeque<Option<AckWaiter>>>,
+    /// Sent QoS 1/2 publishes waiting for their ack, by packet id.
+    pending: Mutex<HashMap<u16, oneshot::Sender<AckOutcome>>>,
+}
+
+impl AckTracker {
+    /// A publish is about to be handed to the client.
+    pub fn queued(&self, waiter: Option<AckWaiter>) {
+        self.sent.lock().unwrap().push_back(waiter);
+    }
+
+    /// The client refused the publish just noted with [`queued`](Self::queued).
+    pub fn refused(&self) {
+        self.sent.lock().unwrap().pop_back();
+    }
+
+    /// rumqttc sent the oldest queued publish as `packet_id` (0 for QoS 0).
+    pub fn outgoing(&self, packet_id: u16) {
+        let Some(Some(waiter)) = self.sent.lock().unwrap().pop_front() else {
+            return;
+        };
+        let _ = waiter.packet_id.send(packet_id);
+        if packet_id == 0 {
+            // QoS 0 is never acked
+            let _ = waiter.acked.send(Ok(()));
+        } else {
+            self.pending.lock().unwrap().insert(packet_id, waiter.acked);
+        }
+    }
+
+    /// The broker answered `packet_id`: PubAck for QoS 1, PubComp for
+    /// QoS 2, or a PubAck/PubRec refusing it.
+    pub fn resolved(&self, packet_id: u16, outcome: AckOutcome) {
+        if let Some(acked) = self.pending.lock().unwrap().remove(&packet_id) {
+            let _ = acked.send(outcome);
+        }
+    }
+
+    /// The client was rebuilt; nothing in flight will be reported. Dropping
+    /// the waiters tells them the connection was lost.
+    pub fn reset(&self) {
+        self.sent.lock().unwrap().clear();
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acks_resolve_only_their_own_publish() {
+        let tracker = AckTracker::default();
+        let (first, first_id, mut first_acked) = AckWaiter::new();
+        let (second, second_id, mut second_acked) = AckWaiter::new();
+        tracker.queued(Some(first));
+        // A publish nobody waits for sits between them
+        tracker.queued(None);
+        tracker.queued(Some(second));
+
+        tracker.outgoing(7);
+        tracker.outgoing(8);
+        tracker.outgoing(9);
+        assert_eq!(first_id.blocking_recv(), Ok(7));
+        assert_eq!(second_id.blocking_recv(), Ok(9));
+
+        tracker.resolved(9, Ok(()));
+        assert_eq!(second_acked.try_recv(), Ok(Ok(())));
+        assert!(first_acked.try_recv().is_err());
+        tracker.resolved(8, Ok(()));
+        assert!(first_acked.try_recv().is_err());
+
+        tracker.reset();
+        assert!(matches!(
+            first_acked.try_recv(),
+            Err(oneshot::error::TryRecvError::Closed)
+        ));
+    }
+
+    #[test]
+    fn test_rejections_reach_the_waiting_publish() {
+        let tracker = AckTracker::default();
+        let (waiter, _, mut acked) = AckWaiter::new();
+        tracker.queued(Some(waiter));
+        tracker.outgoing(3);
+        let rejection = PublishRejection {
+            reason_code: 0x87,
+            reason: "NotAuthorized".to_string(),
+            reason_string: Some("acl denies roaster/dev1/control/#".to_string()),
+        };
+        tracker.resolved(3, Err(rejection.clone()));
+        assert_eq!(acked.try_recv(), Ok(Err(rejection.clone())));
+        assert_eq!(
+            rejection.to_string(),
+            "NotAuthorized (0x87): acl denies roaster/dev1/control/#"
+        );
+    }
+}
@@ -1,46 +1,217 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU16, Ordering},
     Arc,
 };
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
-use rumqttc::{AsyncClient, ClientError, Event, EventLoop, Incoming, MqttOptions, Outgoing, QoS};
-use tokio::sync::{broadcast, Mutex, RwLock};
+use rumqttc::{
+    AsyncClient, ClientError, LastWill, MqttOptions, Publish, QoS, Request, TlsConfiguration,
+    Transport as MqttTransport,
+};
+use rustroast_core::{server_status_topic, SERVER_OFFLINE, SERVER_ONLINE};
+use tokio::sync::{mpsc, oneshot, Mutex, Notify, RwLock};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
-use crate::config::MqttConfig;
+use crate::ack::{AckTracker, AckWaiter, PublishAckError};
+use crate::config::{
+    MqttConfig, MqttConfigError, MqttCredentials, MqttProtocol, MqttTlsConfig, MqttTransportKind,
+    DEFAULT_MAX_PAYLOAD_SIZE,
+};
+use crate::events::{EventBus, EventReceiver, EventRecvError, DEFAULT_EVENT_QUEUE};
+use crate::metrics::{MqttMetrics, MqttMetricsSnapshot};
+use crate::offline::{HeldPublish, OfflineQueue};
+use crate::protocol::{qos_v5, BrokerClient, BrokerEvent, BrokerEventLoop, UserProperties};
+use crate::router::topic_captures;
+use crate::watchdog::{self, Watchdog};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MqttEvent {
     Connected,
     Disconnected,
-    Publish { topic: String, payload: Vec<u8> },
+    /// An incoming publish with its delivery flags. `retain` is set when
+    /// the broker delivers a retained message on subscribing, rather than
+    /// a live publish, and `dup` when it resends a QoS 1/2 publish it may
+    /// have delivered before. `received_at` is when the client read it off
+    /// the connection, so consumers queued behind a slow one still know
+    /// when it arrived.
+    Publish {
+        topic: String,
+        payload: Vec<u8>,
+        qos: QoS,
+        retain: bool,
+        dup: bool,
+        received_at: SystemTime,
+    },
     PubAck(u16),
+    /// An incoming publish dropped for a payload over `max_payload_size`,
+    /// sent in its place so a misbehaving device shows up without its
+    /// payload being held in every subscriber's queue.
+    Oversized {
+        topic: String,
+        size: usize,
+    },
+    /// Nothing heard from the broker for `stale_keepalives` keep-alive
+    /// intervals. The service stops counting as ready until the broker is
+    /// heard from again, which sends `Connected`, or rumqttc gives up on
+    /// the connection, which sends `Disconnected`.
+    Stale {
+        silent_for: Duration,
+    },
     // Other events can be added as needed
 }
 
+/// A message published through a mock [`MqttService`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishedMessage {
+    pub topic: String,
+    pub qos: QoS,
+    pub retain: bool,
+    pub payload: Vec<u8>,
+    pub user_properties: UserProperties,
+}
+
+/// A retained message found by [`MqttService::scan_retained`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetainedMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+/// Outgoing queue a publish waits in. Safety controls use `Priority` and are
+/// dispatched ahead of anything queued on `Normal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishLane {
+    Priority,
+    Normal,
+}
+
+impl PublishLane {
+    pub fn for_topic(topic: &str) -> Self {
+        if rustroast_core::is_safety_control(topic) {
+            PublishLane::Priority
+        } else {
+            PublishLane::Normal
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PublishLane::Priority => "priority",
+            PublishLane::Normal => "normal",
+        }
+    }
+}
+
+/// Called after every publish with its lane and the time from the
+/// `publish` call until the client accepted the message.
+pub type PublishObserver = Arc<dyn Fn(PublishLane, Duration) + Send + Sync>;
+
+/// Capacity of the normal publish queue; callers wait when it is full.
+const NORMAL_QUEUE_CAPACITY: usize = 256;
+
+struct PublishRequest {
+    publish: QueuedPublish,
+    done: oneshot::Sender<Result<(), ClientError>>,
+}
+
+struct QueuedPublish {
+    topic: String,
+    qos: QoS,
+    retain: bool,
+    payload: Vec<u8>,
+    user_properties: UserProperties,
+    /// Set by [`MqttService::publish_and_wait`].
+    ack: Option<AckWaiter>,
+}
+
+impl From<HeldPublish> for QueuedPublish {
+    fn from(held: HeldPublish) -> Self {
+        Self {
+            topic: held.topic,
+            qos: held.qos,
+            retain: held.retain,
+            payload: held.payload,
+            user_properties: held.user_properties,
+            ack: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PublishQueues {
+    priority: mpsc::UnboundedSender<PublishRequest>,
+    normal: mpsc::Sender<PublishRequest>,
+}
+
+#[derive(Clone)]
+enum Transport {
+    Broker {
+        client: Arc<Mutex<BrokerClient>>,
+        queues: PublishQueues,
+        config: Arc<std::sync::RwLock<MqttConfig>>,
+        /// Wakes the event loop to reconnect with the current `config`.
+        reconnect: Arc<Notify>,
+        // We keep the join handles alive by storing them to ensure the loops aren't dropped
+        _loop_handle: Arc<JoinHandle<()>>,
+        _dispatch_handle: Arc<JoinHandle<()>>,
+        _watchdog_handle: Option<Arc<JoinHandle<()>>>,
+    },
+    /// In-process loopback for tests: publishes are recorded on a channel and
+    /// delivered back as incoming messages when a subscription matches.
+    Mock {
+        published: mpsc::UnboundedSender<PublishedMessage>,
+        next_packet_id: Arc<AtomicU16>,
+        /// Retained messages by topic, replayed on subscribing.
+        retained: Arc<std::sync::Mutex<HashMap<String, Vec<u8>>>>,
+    },
+}
+
 #[derive(Clone)]
 pub struct MqttService {
-    client: Arc<Mutex<AsyncClient>>,
+    transport: Transport,
     ready: Arc<AtomicBool>,
-    events_tx: broadcast::Sender<MqttEvent>,
+    events: EventBus,
     subscriptions: Arc<RwLock<HashMap<String, QoS>>>,
-    // We keep the join handle alive by storing it to ensure the loop isn't dropped
-    _loop_handle: Arc<JoinHandle<()>>,
+    publish_observer: Arc<std::sync::RwLock<Option<PublishObserver>>>,
+    metrics: Arc<MqttMetrics>,
+    offline: Arc<OfflineQueue>,
+    /// Larger publishes are dropped on the way in and refused on the way out.
+    max_payload_size: usize,
 }
 
 impl MqttService {
-    pub async fn connect(config: MqttConfig) -> Result<Self, ClientError> {
+    pub async fn connect(config: MqttConfig) -> Result<Self, MqttConfigError> {
         let (client, eventloop) = build_client(&config)?;
         let ready = Arc::new(AtomicBool::new(false));
-        let (tx, _) = broadcast::channel(256);
+        let events = EventBus::new(config.event_queue, config.event_overflow);
         let subscriptions = Arc::new(RwLock::new(HashMap::new()));
         let ready_clone = ready.clone();
-        let tx_clone = tx.clone();
+        let events_clone = events.clone();
         let subscriptions_clone = subscriptions.clone();
+        let offline = Arc::new(OfflineQueue::new(config.offline_queue, config.offline_ttl));
+        let offline_clone = offline.clone();
+        let max_payload_size = config.max_payload_size;
+        let watchdog = Arc::new(Watchdog::new(Instant::now()));
+        let watchdog_handle = config.stale_after().map(|stale_after| {
+            Arc::new(tokio::spawn(watchdog::run(
+                watchdog.clone(),
+                stale_after,
+                ready.clone(),
+                events.clone(),
+            )))
+        });
+        let config = Arc::new(std::sync::RwLock::new(config));
+        let config_clone = config.clone();
+        let reconnect = Arc::new(Notify::new());
+        let reconnect_clone = reconnect.clone();
+        let acks = Arc::new(AckTracker::default());
+        let acks_clone = acks.clone();
+        let metrics = Arc::new(MqttMetrics::default());
+        let metrics_clone = metrics.clone();
 
         let client_shared = Arc::new(Mutex::new(client));
         let client_clone = client_shared.clone();
@@ -49,30 +220,202 @@ impl MqttService {
                 eventloop,
                 client_clone,
                 ready_clone,
-                tx_clone,
+                events_clone,
                 subscriptions_clone,
-                config,
+                config_clone,
+                reconnect_clone,
+                acks_clone,
+                metrics_clone,
+                offline_clone,
+                watchdog,
+                max_payload_size,
             )
             .await;
         });
 
+        let (priority, priority_rx) = mpsc::unbounded_channel();
+        let (normal, normal_rx) = mpsc::channel(NORMAL_QUEUE_CAPACITY);
+        let dispatch_client = client_shared.clone();
+        let dispatch_acks = acks;
+        let dispatch_handle = tokio::spawn(run_dispatcher(
+            priority_rx,
+            normal_rx,
+            move |publish: QueuedPublish| {
+                let client = dispatch_client.clone();
+                let acks = dispatch_acks.clone();
+                async move {
+                    let client = client.lock().await;
+                    acks.queued(publish.ack);
+                    let result = client
+                        .publish(
+                            publish.topic,
+                            publish.qos,
+                            publish.retain,
+                            publish.payload,
+                            publish.user_properties,
+                        )
+                        .await;
+                    if result.is_err() {
+                        acks.refused();
+                    }
+                    result
+                }
+            },
+        ));
+
         Ok(Self {
-            client: client_shared,
+            transport: Transport::Broker {
+                client: client_shared,
+                queues: PublishQueues { priority, normal },
+                config,
+                reconnect,
+                _loop_handle: Arc::new(loop_handle),
+                _dispatch_handle: Arc::new(dispatch_handle),
+                _watchdog_handle: watchdog_handle,
+            },
             ready,
-            events_tx: tx,
+            events,
             subscriptions,
-            _loop_handle: Arc::new(loop_handle),
+            publish_observer: Arc::new(std::sync::RwLock::new(None)),
+            metrics,
+            offline,
+            max_payload_size,
         })
     }
 
+    /// A connected service with no broker behind it. Every publish is sent to
+    /// the returned receiver and, if it matches a subscription, echoed back on
+    /// [`events`](Self::events) as a broker would.
+    pub fn mock() -> (Self, mpsc::UnboundedReceiver<PublishedMessage>) {
+        let (published, rx) = mpsc::unbounded_channel();
+        let service = Self {
+            transport: Transport::Mock {
+                published,
+                next_packet_id: Arc::new(AtomicU16::new(1)),
+                retained: Arc::default(),
+            },
+            ready: Arc::new(AtomicBool::new(true)),
+            events: EventBus::new(DEFAULT_EVENT_QUEUE, Default::default()),
+            subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            publish_observer: Arc::new(std::sync::RwLock::new(None)),
+            metrics: Arc::default(),
+            offline: Arc::new(OfflineQueue::disabled()),
+            max_payload_size: DEFAULT_MAX_PAYLOAD_SIZE,
+        };
+        (service, rx)
+    }
+
     pub fn is_ready(&self) -> bool {
         self.ready.load(Ordering::Relaxed)
     }
 
-    pub fn events(&self) -> broadcast::Receiver<MqttEvent> {
-        self.events_tx.subscribe()
+    /// `host:port` of the broker in use, which changes on failover. `None`
+    /// for the mock.
+    pub fn broker(&self) -> Option<String> {
+        match &self.transport {
+            Transport::Broker { config, .. } => {
+                let config = config.read().unwrap();
+                Some(format!("{}:{}", config.host, config.port))
+            }
+            Transport::Mock { .. } => None,
+        }
+    }
+
+    /// The configuration in use, with `host` and `port` on the current
+    /// broker. `None` for the mock.
+    pub fn config(&self) -> Option<MqttConfig> {
+        match &self.transport {
+            Transport::Broker { config, .. } => Some(config.read().unwrap().clone()),
+            Transport::Mock { .. } => None,
+        }
+    }
+
+    /// Events from now on, queued for this receiver alone (see
+    /// [`OverflowPolicy`](crate::OverflowPolicy)).
+    pub fn events(&self) -> EventReceiver {
+        self.events.subscribe()
+    }
+
+    /// Events dropped from full subscriber queues since the service started.
+    pub fn dropped_events(&self) -> u64 {
+        self.events.dropped()
     }
 
+    /// Message, byte and reconnect counters since the service started.
+    pub fn metrics(&self) -> MqttMetricsSnapshot {
+        MqttMetricsSnapshot {
+            offline_queued: self.offline.depth() as u64,
+            offline_overflowed: self.offline.overflowed(),
+            offline_expired: self.offline.expired(),
+            ..self
+                .metrics
+                .snapshot(self.is_ready(), self.events.dropped())
+        }
+    }
+
+    /// Report per-lane publish latency, e.g. to a metrics histogram.
+    pub fn set_publish_observer(&self, observer: PublishObserver) {
+        *self.publish_observer.write().unwrap() = Some(observer);
+    }
+
+    /// Deliver a message to local event subscribers as if it had arrived from
+    /// the broker, without publishing it.
+    pub fn inject(&self, topic: &str, payload: impl Into<Vec<u8>>) {
+        self.deliver(
+            topic,
+            payload.into(),
+            QoS::AtMostOnce,
+            false,
+            SystemTime::now(),
+        );
+    }
+
+    /// Like [`inject`](Self::inject), stamped as received at `received_at`.
+    pub fn inject_at(&self, topic: &str, payload: impl Into<Vec<u8>>, received_at: SystemTime) {
+        self.deliver(topic, payload.into(), QoS::AtMostOnce, false, received_at);
+    }
+
+    /// Like [`inject`](Self::inject), as a retained message delivered on
+    /// subscribing.
+    pub fn inject_retained(&self, topic: &str, payload: impl Into<Vec<u8>>) {
+        self.deliver(
+            topic,
+            payload.into(),
+            QoS::AtMostOnce,
+            true,
+            SystemTime::now(),
+        );
+    }
+
+    fn deliver(
+        &self,
+        topic: &str,
+        payload: Vec<u8>,
+        qos: QoS,
+        retain: bool,
+        received_at: SystemTime,
+    ) {
+        let publish = MqttEvent::Publish {
+            topic: topic.to_string(),
+            payload,
+            qos,
+            retain,
+            dup: false,
+            received_at,
+        };
+        self.events.send(incoming_event(
+            &self.metrics,
+            self.max_payload_size,
+            publish,
+        ));
+    }
+
+    /// Publish a message. Safety controls (see
+    /// [`is_safety_control`](rustroast_core::is_safety_control)) skip ahead
+    /// of queued normal traffic. With an `offline_queue` configured, a
+    /// publish made while the broker is unreachable is held and sent after
+    /// reconnecting instead of failing. A payload over `max_payload_size`
+    /// is refused.
     pub async fn publish<T: Into<Vec<u8>>>(
         &self,
         topic: &str,
@@ -80,30 +423,406 @@ impl MqttService {
         retain: bool,
         payload: T,
     ) -> Result<(), ClientError> {
-        let client = self.client.lock().await;
-        client.publish(topic, qos, retain, payload).await
+        self.publish_with_properties(topic, qos, retain, payload, Vec::new())
+            .await
+    }
+
+    /// [`publish`](Self::publish) with MQTT 5 user properties.
+    pub async fn publish_with_properties<T: Into<Vec<u8>>>(
+        &self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: T,
+        user_properties: UserProperties,
+    ) -> Result<(), ClientError> {
+        let publish = QueuedPublish {
+            topic: topic.to_string(),
+            qos,
+            retain,
+            payload: payload.into(),
+            user_properties,
+            ack: None,
+        };
+        self.observed_publish(publish).await
+    }
+
+    /// Publish and wait until the broker acks this very publish. Acks are
+    /// matched by packet id, so a concurrent publish can't end the wait.
+    /// Returns the packet id; QoS 0 has none (0) and returns once sent. An
+    /// MQTT 5 broker refusing the publish gives its reason code.
+    pub async fn publish_and_wait<T: Into<Vec<u8>>>(
+        &self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: T,
+        user_properties: UserProperties,
+        timeout: Duration,
+    ) -> Result<u16, PublishAckError> {
+        let (waiter, packet_id_rx, acked_rx) = AckWaiter::new();
+        let publish = QueuedPublish {
+            topic: topic.to_string(),
+            qos,
+            retain,
+            payload: payload.into(),
+            user_properties,
+            ack: Some(waiter),
+        };
+        let mut packet_id = None;
+        let wait = async {
+            self.observed_publish(publish).await?;
+            let id = packet_id_rx
+                .await
+                .map_err(|_| PublishAckError::ConnectionLost { packet_id: None })?;
+            packet_id = Some(id);
+            acked_rx
+                .await
+                .map_err(|_| PublishAckError::ConnectionLost {
+                    packet_id: Some(id),
+                })?
+                .map_err(|rejection| PublishAckError::Rejected {
+                    packet_id: id,
+                    rejection,
+                })?;
+            Ok(id)
+        };
+        let outcome = tokio::time::timeout(timeout, wait).await;
+        outcome.unwrap_or(Err(PublishAckError::Timeout { packet_id }))
+    }
+
+    async fn observed_publish(&self, publish: QueuedPublish) -> Result<(), ClientError> {
+        if publish.payload.len() > self.max_payload_size {
+            warn!(
+                topic = %publish.topic,
+                size = publish.payload.len(),
+                max = self.max_payload_size,
+                "Refusing to publish an oversized MQTT payload"
+            );
+            self.metrics.publish_failed();
+            return Err(ClientError::Request(Request::Publish(Publish::new(
+                &publish.topic,
+                publish.qos,
+                publish.payload,
+            ))));
+        }
+        let Some(publish) = self.hold_offline(publish) else {
+            return Ok(());
+        };
+        let lane = PublishLane::for_topic(&publish.topic);
+        let topic = publish.topic.clone();
+        let bytes = publish.payload.len();
+        let started = Instant::now();
+        let result = self.send_publish(lane, publish).await;
+        match &result {
+            Ok(()) => self.metrics.published(&topic, bytes),
+            Err(_) => self.metrics.publish_failed(),
+        }
+        if let Some(observer) = self.publish_observer.read().unwrap().as_ref() {
+            observer(lane, started.elapsed());
+        }
+        result
+    }
+
+    /// Hold `publish` in the offline queue when it has to wait for the
+    /// broker, else hand it back to be sent now.
+    fn hold_offline(&self, publish: QueuedPublish) -> Option<QueuedPublish> {
+        // A caller waiting for the ack wants to hear about the outage now
+        if !self.offline.is_enabled() || publish.ack.is_some() {
+            return Some(publish);
+        }
+        let held = HeldPublish {
+            topic: publish.topic,
+            qos: publish.qos,
+            retain: publish.retain,
+            payload: publish.payload,
+            user_properties: publish.user_properties,
+            held_at: Instant::now(),
+        };
+        self.offline
+            .try_hold(self.is_ready(), held)
+            .err()
+            .map(QueuedPublish::from)
+    }
+
+    async fn send_publish(
+        &self,
+        lane: PublishLane,
+        publish: QueuedPublish,
+    ) -> Result<(), ClientError> {
+        let QueuedPublish {
+            topic,
+            qos,
+            retain,
+            payload,
+            user_properties,
+            ack,
+        } = publish;
+        match &self.transport {
+            Transport::Broker { queues, .. } => {
+                let (done, done_rx) = oneshot::channel();
+                let req = PublishRequest {
+                    publish: QueuedPublish {
+                        topic: topic.clone(),
+                        qos,
+                        retain,
+                        payload,
+                        user_properties,
+                        ack,
+                    },
+                    done,
+                };
+                let queued = match lane {
+                    PublishLane::Priority => queues.priority.send(req).map_err(|e| e.0),
+                    PublishLane::Normal => queues.normal.send(req).await.map_err(|e| e.0),
+                };
+                let closed = |payload| {
+                    ClientError::Request(Request::Publish(Publish::new(&topic, qos, payload)))
+                };
+                match queued {
+                    Ok(()) => done_rx.await.unwrap_or_else(|_| Err(closed(Vec::new()))),
+                    Err(req) => Err(closed(req.publish.payload)),
+                }
+            }
+            Transport::Mock {
+                published,
+                next_packet_id,
+                retained: retained_messages,
+            } => {
+                if retain {
+                    // An empty retained publish clears the topic
+                    let mut retained_messages = retained_messages.lock().unwrap();
+                    if payload.is_empty() {
+                        retained_messages.remove(&topic);
+                    } else {
+                        retained_messages.insert(topic.clone(), payload.clone());
+                    }
+                }
+                let subscribed = self
+                    .subscriptions
+                    .read()
+                    .await
+                    .keys()
+                    .any(|filter| topic_matches(filter, &topic));
+                if subscribed {
+                    self.deliver(&topic, payload.clone(), qos, false, SystemTime::now());
+                }
+                // The mock broker acks right away
+                let packet_id = match qos {
+                    QoS::AtMostOnce => 0,
+                    _ => next_packet_id
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| {
+                            Some(id.checked_add(1).unwrap_or(1))
+                        })
+                        .unwrap_or(1),
+                };
+                if packet_id != 0 {
+                    self.events.send(MqttEvent::PubAck(packet_id));
+                }
+                if let Some(ack) = ack {
+                    let _ = ack.packet_id.send(packet_id);
+                    let _ = ack.acked.send(Ok(()));
+                }
+                let _ = published.send(PublishedMessage {
+                    topic,
+                    qos,
+                    retain,
+                    payload,
+                    user_properties,
+                });
+                Ok(())
+            }
+        }
     }
 
     pub async fn subscribe(&self, topic: &str, qos: QoS) -> Result<(), ClientError> {
-        let client = self.client.lock().await;
-        let result = client.subscribe(topic, qos).await;
+        let result = match &self.transport {
+            Transport::Broker { client, .. } => client.lock().await.subscribe(topic, qos).await,
+            Transport::Mock { .. } => Ok(()),
+        };
         if result.is_ok() {
             // Track successful subscriptions
             let mut subs = self.subscriptions.write().await;
             subs.insert(topic.to_string(), qos);
         }
+        if let Transport::Mock { retained, .. } = &self.transport {
+            let replay: Vec<(String, Vec<u8>)> = retained
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(retained_topic, _)| topic_matches(topic, retained_topic))
+                .map(|(retained_topic, payload)| (retained_topic.clone(), payload.clone()))
+                .collect();
+            for (retained_topic, payload) in replay {
+                self.deliver(&retained_topic, payload, qos, true, SystemTime::now());
+            }
+        }
         result
     }
 
+    pub async fn unsubscribe(&self, topic: &str) -> Result<(), ClientError> {
+        let result = match &self.transport {
+            Transport::Broker { client, .. } => client.lock().await.unsubscribe(topic).await,
+            Transport::Mock { .. } => Ok(()),
+        };
+        if result.is_ok() {
+            self.subscriptions.write().await.remove(topic);
+        }
+        result
+    }
+
+    /// Retained messages under `filter`: (re)subscribe and collect what the
+    /// broker replays within `window`. Every event receiver sees the replay,
+    /// flagged `retain`, so consumers must not take those for live traffic.
+    /// A filter that wasn't subscribed before is unsubscribed again
+    /// afterwards.
+    pub async fn scan_retained(
+        &self,
+        filter: &str,
+        window: Duration,
+    ) -> Result<Vec<RetainedMessage>, ClientError> {
+        let mut events = self.events();
+        let tracked = self.subscriptions.read().await.get(filter).copied();
+        self.subscribe(filter, tracked.unwrap_or(QoS::AtMostOnce))
+            .await?;
+        let mut found = std::collections::BTreeMap::new();
+        let _ = tokio::time::timeout(window, async {
+            loop {
+                match events.recv().await {
+                    Ok(MqttEvent::Publish {
+                        topic,
+                        payload,
+                        retain: true,
+                        ..
+                    }) if topic_matches(filter, &topic) => {
+                        found.insert(topic, payload);
+                    }
+                    Err(EventRecvError::Closed) => break,
+                    _ => {}
+                }
+            }
+        })
+        .await;
+        if tracked.is_none() {
+            self.unsubscribe(filter).await?;
+        }
+        Ok(found
+            .into_iter()
+            .map(|(topic, payload)| RetainedMessage { topic, payload })
+            .collect())
+    }
+
+    /// Leave the broker cleanly: mark this server `offline` on its presence
+    /// topic and disconnect, waiting up to `timeout` for both to be sent.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), ClientError> {
+        if let Transport::Broker { config, .. } = &self.transport {
+            let topic = server_status_topic(&config.read().unwrap().client_id);
+            self.publish(&topic, QoS::AtLeastOnce, true, SERVER_OFFLINE)
+                .await?;
+        }
+        let mut events = self.events();
+        self.disconnect().await?;
+        let _ = tokio::time::timeout(timeout, async {
+            loop {
+                match events.recv().await {
+                    Ok(MqttEvent::Disconnected) | Err(EventRecvError::Closed) => break,
+                    _ => {}
+                }
+            }
+        })
+        .await;
+        Ok(())
+    }
+
     pub async fn disconnect(&self) -> Result<(), ClientError> {
         self.ready.store(false, Ordering::Relaxed);
-        let client = self.client.lock().await;
-        client.disconnect().await
+        match &self.transport {
+            Transport::Broker { client, .. } => client.lock().await.disconnect().await,
+            Transport::Mock { .. } => {
+                self.events.send(MqttEvent::Disconnected);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reconnect with new broker credentials, e.g. when a token expires.
+    /// Tracked subscriptions are restored on connect. Returns whether the
+    /// broker accepted the connection within `timeout`.
+    pub async fn update_credentials(
+        &self,
+        credentials: MqttCredentials,
+        timeout: Duration,
+    ) -> bool {
+        let mut events = self.events();
+        match &self.transport {
+            Transport::Broker {
+                config, reconnect, ..
+            } => {
+                {
+                    let mut config = config.write().unwrap();
+                    if let Some(username) = credentials.username {
+                        config.username = Some(username);
+                    }
+                    config.password = Some(credentials.password);
+                }
+                reconnect.notify_one();
+            }
+            Transport::Mock { .. } => {
+                self.events.send(MqttEvent::Disconnected);
+                self.events.send(MqttEvent::Connected);
+            }
+        }
+        wait_connected(&mut events, timeout).await
+    }
+
+    /// Reconnect with the connection settings of `config` (see
+    /// [`MqttConfig::with_connection_from`]), e.g. to move to another
+    /// broker or rotate a password without restarting. The client is
+    /// rebuilt and swapped in place, so publishes and subscribers carry on
+    /// and tracked subscriptions are restored on connect. Fails without
+    /// touching the running client when `config` can't be built, e.g. over
+    /// an unreadable certificate; otherwise returns whether the broker
+    /// accepted the connection within `timeout`.
+    pub async fn reconfigure(
+        &self,
+        config: MqttConfig,
+        timeout: Duration,
+    ) -> Result<bool, MqttConfigError> {
+        let mut events = self.events();
+        match &self.transport {
+            Transport::Broker {
+                config: current,
+                reconnect,
+                ..
+            } => {
+                let config = current.read().unwrap().with_connection_from(config);
+                build_client(&config)?;
+                *current.write().unwrap() = config;
+                reconnect.notify_one();
+            }
+            Transport::Mock { .. } => {
+                self.events.send(MqttEvent::Disconnected);
+                self.events.send(MqttEvent::Connected);
+            }
+        }
+        Ok(wait_connected(&mut events, timeout).await)
     }
 
     pub async fn resubscribe_tracked(&self) -> Result<(), ClientError> {
+        let Transport::Broker { client, .. } = &self.transport else {
+            // The mock matches against tracked subscriptions directly
+            self.ready.store(true, Ordering::Relaxed);
+            self.events.send(MqttEvent::Connected);
+            while let Some(held) = self.offline.next() {
+                let (lane, bytes) = (PublishLane::for_topic(&held.topic), held.payload.len());
+                let topic = held.topic.clone();
+                self.send_publish(lane, held.into()).await?;
+                self.metrics.published(&topic, bytes);
+            }
+            return Ok(());
+        };
         let subs = self.subscriptions.read().await;
-        let client = self.client.lock().await;
+        let client = client.lock().await;
         for (topic, qos) in subs.iter() {
             debug!("Re-subscribing to {} with QoS {:?}", topic, qos);
             if let Err(err) = client.subscribe(topic, *qos).await {
@@ -118,34 +837,212 @@ impl MqttService {
     }
 }
 
-fn build_client(config: &MqttConfig) -> Result<(AsyncClient, EventLoop), ClientError> {
-    let mut opts = MqttOptions::new(&config.client_id, &config.host, config.port);
-    opts.set_keep_alive(Duration::from_secs(config.keep_alive_secs as u64));
-    opts.set_clean_session(config.clean_session);
+/// The event for an incoming publish: the publish itself, or
+/// [`MqttEvent::Oversized`] when its payload is over `max_payload_size`.
+fn incoming_event(metrics: &MqttMetrics, max_payload_size: usize, publish: MqttEvent) -> MqttEvent {
+    let MqttEvent::Publish { topic, payload, .. } = &publish else {
+        return publish;
+    };
+    if payload.len() > max_payload_size {
+        debug!(%topic, size = payload.len(), "Dropping oversized MQTT publish");
+        metrics.oversized();
+        return MqttEvent::Oversized {
+            topic: topic.clone(),
+            size: payload.len(),
+        };
+    }
+    metrics.received(topic, payload.len());
+    publish
+}
+
+/// Whether `events` sees the client connect within `timeout`.
+async fn wait_connected(events: &mut EventReceiver, timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, async {
+        loop {
+            match events.recv().await {
+                Ok(MqttEvent::Connected) => return true,
+                Err(EventRecvError::Closed) => return false,
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap_or(false)
+}
+
+/// MQTT topic filter matching with `+` and `#` wildcards.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    topic_captures(filter, topic).is_some()
+}
+
+/// Hand queued publishes to `send` one at a time, always draining the
+/// priority queue before taking the next normal publish.
+async fn run_dispatcher<F, Fut>(
+    mut priority_rx: mpsc::UnboundedReceiver<PublishRequest>,
+    mut normal_rx: mpsc::Receiver<PublishRequest>,
+    mut send: F,
+) where
+    F: FnMut(QueuedPublish) -> Fut,
+    Fut: Future<Output = Result<(), ClientError>>,
+{
+    loop {
+        let req = tokio::select! {
+            biased;
+            Some(req) = priority_rx.recv() => req,
+            Some(req) = normal_rx.recv() => req,
+            else => break,
+        };
+        let result = send(req.publish).await;
+        let _ = req.done.send(result);
+    }
+}
+
+fn build_client(config: &MqttConfig) -> Result<(BrokerClient, BrokerEventLoop), MqttConfigError> {
+    // The WebSocket transports take the broker URL in place of the host
+    let host = match config.transport {
+        MqttTransportKind::Tcp => config.host.clone(),
+        MqttTransportKind::WebSocket => config.websocket_url(),
+    };
+    let transport = match (config.transport, &config.tls) {
+        (MqttTransportKind::Tcp, None) => MqttTransport::Tcp,
+        (MqttTransportKind::Tcp, Some(tls)) => {
+            MqttTransport::tls_with_config(tls_configuration(tls)?)
+        }
+        (MqttTransportKind::WebSocket, None) => MqttTransport::ws(),
+        (MqttTransportKind::WebSocket, Some(tls)) => {
+            MqttTransport::wss_with_config(tls_configuration(tls)?)
+        }
+    };
+    // The broker marks this server offline if the connection drops
+    let will_topic = server_status_topic(&config.client_id);
+    let keep_alive = Duration::from_secs(config.keep_alive_secs as u64);
+    // Token-only brokers take the token as password with an empty username
+    let credentials = config
+        .password
+        .as_ref()
+        .map(|p| (config.username.clone().unwrap_or_default(), p.clone()));
     // Connection timeout not available in this rumqttc version; rely on defaults
-    if let (Some(u), Some(p)) = (&config.username, &config.password) {
-        opts.set_credentials(u.clone(), p.clone());
+    match config.protocol {
+        MqttProtocol::V311 => {
+            let mut opts = MqttOptions::new(&config.client_id, host, config.port);
+            opts.set_transport(transport);
+            opts.set_last_will(LastWill::new(
+                will_topic,
+                SERVER_OFFLINE,
+                QoS::AtLeastOnce,
+                true,
+            ));
+            opts.set_keep_alive(keep_alive);
+            opts.set_clean_session(config.clean_session);
+            opts.set_max_packet_size(config.max_packet_size, config.max_packet_size);
+            if let Some((username, password)) = credentials {
+                opts.set_credentials(username, password);
+            }
+            // Reasonable channel capacity for requests
+            opts.set_request_channel_capacity(64);
+            let (client, eventloop) = AsyncClient::new(opts, 64);
+            Ok((
+                BrokerClient::V311(client),
+                BrokerEventLoop::V311(Box::new(eventloop)),
+            ))
+        }
+        MqttProtocol::V5 => {
+            let mut opts = rumqttc::v5::MqttOptions::new(&config.client_id, host, config.port);
+            opts.set_transport(transport);
+            opts.set_last_will(rumqttc::v5::mqttbytes::v5::LastWill::new(
+                will_topic,
+                SERVER_OFFLINE,
+                qos_v5(QoS::AtLeastOnce),
+                true,
+                None,
+            ));
+            opts.set_keep_alive(keep_alive);
+            opts.set_clean_start(config.clean_session);
+            opts.set_max_packet_size(Some(config.max_packet_size as u32));
+            opts.set_topic_alias_max(config.topic_alias_max);
+            if let Some((username, password)) = credentials {
+                opts.set_credentials(username, password);
+            }
+            opts.set_request_channel_capacity(64);
+            let (client, eventloop) = rumqttc::v5::AsyncClient::new(opts, 64);
+            Ok((
+                BrokerClient::V5(client),
+                BrokerEventLoop::V5(Box::new(eventloop)),
+            ))
+        }
     }
-    // Reasonable channel capacity for requests
-    opts.set_request_channel_capacity(64);
-    Ok(AsyncClient::new(opts, 64))
 }
 
+/// TLS settings from the configured PEM files, which rumqttc parses when
+/// connecting.
+fn tls_configuration(tls: &MqttTlsConfig) -> Result<TlsConfiguration, MqttConfigError> {
+    let read = |path: &std::path::PathBuf| {
+        std::fs::read(path).map_err(|source| MqttConfigError::ReadFile {
+            path: path.clone(),
+            source,
+        })
+    };
+    let client_auth = match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => Some((read(cert)?, read(key)?)),
+        (None, None) => None,
+        _ => return Err(MqttConfigError::IncompleteClientAuth),
+    };
+    match (&tls.ca_cert, client_auth) {
+        (Some(ca), client_auth) => Ok(TlsConfiguration::Simple {
+            ca: read(ca)?,
+            alpn: None,
+            client_auth,
+        }),
+        (None, None) => Ok(TlsConfiguration::default()),
+        (None, Some(_)) => Err(MqttConfigError::ClientAuthWithoutCa),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run_eventloop(
-    mut eventloop: EventLoop,
-    client_shared: Arc<Mutex<AsyncClient>>,
+    mut eventloop: BrokerEventLoop,
+    client_shared: Arc<Mutex<BrokerClient>>,
     ready: Arc<AtomicBool>,
-    events_tx: broadcast::Sender<MqttEvent>,
+    events: EventBus,
     subscriptions: Arc<RwLock<HashMap<String, QoS>>>,
-    config: MqttConfig,
+    config: Arc<std::sync::RwLock<MqttConfig>>,
+    reconnect: Arc<Notify>,
+    acks: Arc<AckTracker>,
+    metrics: Arc<MqttMetrics>,
+    offline: Arc<OfflineQueue>,
+    watchdog: Arc<Watchdog>,
+    max_payload_size: usize,
 ) {
     let mut backoff_secs = 1u64;
     loop {
-        match eventloop.poll().await {
-            Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+        let event = tokio::select! {
+            event = eventloop.poll() => event,
+            _ = reconnect.notified() => {
+                info!("MQTT configuration changed; reconnecting");
+                ready.store(false, Ordering::Relaxed);
+                events.send(MqttEvent::Disconnected);
+                metrics.reconnecting();
+                if let Some(new_eventloop) = rebuild_client(&config, &client_shared).await {
+                    acks.reset();
+                    eventloop = new_eventloop;
+                }
+                backoff_secs = 1;
+                continue;
+            }
+        };
+        if let Ok(Some(broker_event)) = &event {
+            let connected = matches!(broker_event, BrokerEvent::Connected);
+            if broker_event.is_incoming() && watchdog.heard(Instant::now()) && !connected {
+                info!("MQTT broker heard from again");
+                ready.store(true, Ordering::Relaxed);
+                events.send(MqttEvent::Connected);
+            }
+        }
+        match event {
+            Ok(Some(BrokerEvent::Connected)) => {
                 info!("MQTT connected");
                 ready.store(true, Ordering::Relaxed);
-                let _ = events_tx.send(MqttEvent::Connected);
+                events.send(MqttEvent::Connected);
 
                 // Restore all tracked subscriptions after reconnection
                 let subs = subscriptions.read().await;
@@ -156,56 +1053,762 @@ async fn run_eventloop(
                         warn!(?err, "Failed to restore subscription to {}", topic);
                     }
                 }
+                // Announce presence, replacing the Last Will or a shutdown's offline
+                let presence = server_status_topic(&config.read().unwrap().client_id);
+                acks.queued(None);
+                if let Err(err) = client
+                    .publish(
+                        presence,
+                        QoS::AtLeastOnce,
+                        true,
+                        SERVER_ONLINE.into(),
+                        Vec::new(),
+                    )
+                    .await
+                {
+                    acks.refused();
+                    warn!(?err, "Failed to publish server presence");
+                }
                 drop(client); // Release the client lock
                 drop(subs); // Release the read lock
+                if offline.is_enabled() {
+                    tokio::spawn(flush_offline(
+                        offline.clone(),
+                        client_shared.clone(),
+                        acks.clone(),
+                        metrics.clone(),
+                    ));
+                }
 
                 // Reset backoff on successful connect
                 backoff_secs = 1;
+                metrics.set_backoff(Duration::ZERO);
+            }
+            Ok(Some(BrokerEvent::Publish(publish))) => {
+                let event = incoming_event(&metrics, max_payload_size, publish);
+                events.send_from_broker(event).await;
             }
-            Ok(Event::Incoming(Incoming::Publish(p))) => {
-                let topic = p.topic.to_string();
-                let payload = p.payload.to_vec();
-                let _ = events_tx.send(MqttEvent::Publish { topic, payload });
+            Ok(Some(BrokerEvent::Sent(pkid))) => acks.outgoing(pkid),
+            Ok(Some(BrokerEvent::PubAck(pkid, outcome))) => {
+                if let Err(rejection) = &outcome {
+                    warn!(pkid, %rejection, "MQTT publish rejected");
+                }
+                acks.resolved(pkid, outcome);
+                events.send(MqttEvent::PubAck(pkid));
             }
-            Ok(Event::Incoming(Incoming::PubAck(ack))) => {
-                let _ = events_tx.send(MqttEvent::PubAck(ack.pkid));
+            Ok(Some(BrokerEvent::PubRecRejected(pkid, rejection))) => {
+                warn!(pkid, %rejection, "MQTT publish rejected");
+                acks.resolved(pkid, Err(rejection));
             }
-            Ok(Event::Outgoing(Outgoing::Disconnect)) => {
+            Ok(Some(BrokerEvent::PubComp(pkid))) => acks.resolved(pkid, Ok(())),
+            Ok(Some(BrokerEvent::Disconnecting)) => {
                 warn!("MQTT disconnect requested");
                 ready.store(false, Ordering::Relaxed);
-                let _ = events_tx.send(MqttEvent::Disconnected);
-            }
-            Ok(other) => {
-                debug!(?other, "MQTT event");
+                events.send(MqttEvent::Disconnected);
             }
+            Ok(Some(BrokerEvent::Activity)) | Ok(None) => {}
             Err(e) => {
-                error!(error = ?e, "MQTT error; will attempt reconnect");
+                error!(error = %e, "MQTT error; will attempt reconnect");
                 ready.store(false, Ordering::Relaxed);
-                let _ = events_tx.send(MqttEvent::Disconnected);
-
-                // Exponential backoff with cap
-                let wait = backoff_secs.min(30);
-                sleep(Duration::from_secs(wait)).await;
-                backoff_secs = (backoff_secs * 2).min(60);
-
-                // Attempt to rebuild client and eventloop
-                match build_client(&config) {
-                    Ok((new_client, new_eventloop)) => {
-                        // Replace both eventloop and client with fresh instances
-                        eventloop = new_eventloop;
-                        {
-                            let mut client_guard = client_shared.lock().await;
-                            *client_guard = new_client;
-                        }
-                        info!("MQTT client and eventloop rebuilt, attempting reconnection");
-                        // Continue loop; next poll should connect
-                        continue;
-                    }
-                    Err(err) => {
-                        error!(?err, "Failed to rebuild MQTT client; retrying");
+                events.send(MqttEvent::Disconnected);
+
+                // Try the next broker straight away, backing off only once
+                // every one has failed
+                let failover = config.write().unwrap().next_broker();
+                if let Some((_, broker)) = &failover {
+                    warn!(%broker, "Failing over to the next MQTT broker");
+                }
+                if failover.is_none_or(|(index, _)| index == 0) {
+                    // Exponential backoff with cap
+                    let wait = Duration::from_secs(backoff_secs.min(30));
+                    metrics.set_backoff(wait);
+                    sleep(wait).await;
+                    backoff_secs = (backoff_secs * 2).min(60);
+                }
+                metrics.set_backoff(Duration::ZERO);
+                metrics.reconnecting();
+
+                // Attempt to rebuild client and eventloop; next poll should connect
+                if let Some(new_eventloop) = rebuild_client(&config, &client_shared).await {
+                    acks.reset();
+                    eventloop = new_eventloop;
+                }
+            }
+        }
+    }
+}
+
+/// Send the publishes held while disconnected, oldest first. Runs apart
+/// from the event loop, which has to keep polling for them to go out.
+async fn flush_offline(
+    offline: Arc<OfflineQueue>,
+    client_shared: Arc<Mutex<BrokerClient>>,
+    acks: Arc<AckTracker>,
+    metrics: Arc<MqttMetrics>,
+) {
+    let expired_before = offline.expired();
+    let mut sent = 0;
+    while let Some(held) = offline.next() {
+        let client = client_shared.lock().await;
+        acks.queued(None);
+        let result = client
+            .publish(
+                held.topic.clone(),
+                held.qos,
+                held.retain,
+                held.payload.clone(),
+                held.user_properties.clone(),
+            )
+            .await;
+        drop(client);
+        if let Err(err) = result {
+            acks.refused();
+            warn!(
+                ?err,
+                "Failed to send held MQTT publishes; keeping them for the next reconnect"
+            );
+            offline.requeue(held);
+            return;
+        }
+        metrics.published(&held.topic, held.payload.len());
+        sent += 1;
+    }
+    let expired = offline.expired() - expired_before;
+    if sent > 0 || expired > 0 {
+        info!(sent, expired, "Sent MQTT publishes held while disconnected");
+    }
+}
+
+/// Replace the shared client with a fresh one built from `config`, reading
+/// TLS files again, and return its event loop.
+async fn rebuild_client(
+    config: &std::sync::RwLock<MqttConfig>,
+    client_shared: &Mutex<BrokerClient>,
+) -> Option<BrokerEventLoop> {
+    let config = config.read().unwrap().clone();
+    match build_client(&config) {
+        Ok((new_client, new_eventloop)) => {
+            *client_shared.lock().await = new_client;
+            info!("MQTT client and eventloop rebuilt, attempting reconnection");
+            Some(new_eventloop)
+        }
+        Err(err) => {
+            error!(?err, "Failed to rebuild MQTT client; retrying");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("roaster/#", "roaster/dev1/telemetry"));
+        assert!(topic_matches(
+            "roaster/+/telemetry",
+            "roaster/dev1/telemetry"
+        ));
+        assert!(!topic_matches("roaster/+/telemetry", "roaster/dev1/status"));
+        assert!(!topic_matches("roaster/+", "roaster/dev1/status"));
+        assert!(topic_matches("roaster/dev1/status", "roaster/dev1/status"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_sends_priority_ahead_of_queued_traffic() {
+        let (priority, priority_rx) = mpsc::unbounded_channel();
+        let (normal, normal_rx) = mpsc::channel(NORMAL_QUEUE_CAPACITY);
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = sent.clone();
+        tokio::spawn(run_dispatcher(
+            priority_rx,
+            normal_rx,
+            move |publish: QueuedPublish| {
+                log.lock().unwrap().push(publish.topic);
+                async {
+                    // A slow broker connection
+                    sleep(Duration::from_millis(20)).await;
+                    Ok(())
+                }
+            },
+        ));
+
+        let request = |topic: &str| {
+            let (done, rx) = oneshot::channel();
+            let req = PublishRequest {
+                publish: QueuedPublish {
+                    topic: topic.to_string(),
+                    qos: QoS::AtLeastOnce,
+                    retain: false,
+                    payload: Vec::new(),
+                    user_properties: Vec::new(),
+                    ack: None,
+                },
+                done,
+            };
+            (req, rx)
+        };
+        let mut pending = Vec::new();
+        for i in 0..3 {
+            let (req, rx) = request(&format!("roaster/dev1/telemetry-era/{}", i));
+            normal.send(req).await.unwrap();
+            pending.push(rx);
+        }
+        sleep(Duration::from_millis(5)).await;
+        let (req, rx) = request("roaster/dev1/control/emergency_stop");
+        priority.send(req).unwrap();
+        pending.push(rx);
+        for rx in pending {
+            rx.await.unwrap().unwrap();
+        }
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                "roaster/dev1/telemetry-era/0",
+                "roaster/dev1/control/emergency_stop",
+                "roaster/dev1/telemetry-era/1",
+                "roaster/dev1/telemetry-era/2",
+            ]
+        );
+        assert_eq!(
+            PublishLane::for_topic("roaster/dev1/control/heater_enable"),
+            PublishLane::Priority
+        );
+        assert_eq!(
+            PublishLane::for_topic("roaster/dev1/control/setpoint"),
+            PublishLane::Normal
+        );
+    }
+
+    #[test]
+    fn test_tls_configuration_reads_certificates() {
+        let dir = std::env::temp_dir().join(format!("rr-mqtt-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, pem: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, pem).unwrap();
+            Some(path)
+        };
+        let tls = MqttTlsConfig {
+            ca_cert: write("ca.pem", "CA"),
+            client_cert: write("client.pem", "CERT"),
+            client_key: write("client.key", "KEY"),
+        };
+        match tls_configuration(&tls).unwrap() {
+            TlsConfiguration::Simple {
+                ca, client_auth, ..
+            } => {
+                assert_eq!(ca, b"CA");
+                assert_eq!(client_auth, Some((b"CERT".to_vec(), b"KEY".to_vec())));
+            }
+            _ => panic!("expected the configured certificates"),
+        }
+        // Rebuilding the client after a renewal picks up the new files
+        write("ca.pem", "RENEWED");
+        match tls_configuration(&tls).unwrap() {
+            TlsConfiguration::Simple { ca, .. } => assert_eq!(ca, b"RENEWED"),
+            _ => panic!("expected the configured certificates"),
+        }
+
+        let no_key = MqttTlsConfig {
+            client_key: None,
+            ..tls.clone()
+        };
+        assert!(matches!(
+            tls_configuration(&no_key),
+            Err(MqttConfigError::IncompleteClientAuth)
+        ));
+        let no_ca = MqttTlsConfig {
+            ca_cert: None,
+            ..tls.clone()
+        };
+        assert!(matches!(
+            tls_configuration(&no_ca),
+            Err(MqttConfigError::ClientAuthWithoutCa)
+        ));
+        let missing = MqttTlsConfig {
+            ca_cert: Some(dir.join("missing.pem")),
+            ..MqttTlsConfig::default()
+        };
+        assert!(matches!(
+            tls_configuration(&missing),
+            Err(MqttConfigError::ReadFile { .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_websocket_transport_uses_broker_url() {
+        let config = MqttConfig {
+            host: "broker.local".to_string(),
+            port: 9001,
+            transport: MqttTransportKind::WebSocket,
+            ..MqttConfig::default()
+        };
+        assert_eq!(config.websocket_url(), "ws://broker.local:9001/mqtt");
+        let Ok((_, BrokerEventLoop::V311(eventloop))) = build_client(&config) else {
+            panic!("expected an MQTT 3.1.1 client");
+        };
+        assert!(matches!(
+            eventloop.mqtt_options.transport(),
+            MqttTransport::Ws
+        ));
+        assert_eq!(
+            eventloop.mqtt_options.broker_address().0,
+            "ws://broker.local:9001/mqtt"
+        );
+
+        let config = MqttConfig {
+            host: "wss://proxy.example.com/broker".to_string(),
+            tls: Some(MqttTlsConfig::default()),
+            ..config
+        };
+        assert_eq!(config.websocket_url(), "wss://proxy.example.com/broker");
+        let Ok((_, BrokerEventLoop::V311(eventloop))) = build_client(&config) else {
+            panic!("expected an MQTT 3.1.1 client");
+        };
+        assert!(matches!(
+            eventloop.mqtt_options.transport(),
+            MqttTransport::Wss(_)
+        ));
+
+        let config = MqttConfig {
+            protocol: MqttProtocol::V5,
+            ..config
+        };
+        assert!(matches!(
+            build_client(&config),
+            Ok((BrokerClient::V5(_), BrokerEventLoop::V5(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mock_loops_back_subscribed_publishes() {
+        let (mqtt, mut published) = MqttService::mock();
+        let mut events = mqtt.events();
+        mqtt.subscribe("roaster/+/telemetry", QoS::AtMostOnce)
+            .await
+            .unwrap();
+
+        mqtt.publish(
+            "roaster/dev1/control/setpoint",
+            QoS::AtMostOnce,
+            false,
+            "200",
+        )
+        .await
+        .unwrap();
+        mqtt.publish("roaster/dev1/telemetry", QoS::AtMostOnce, false, "{}")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            published.recv().await.unwrap().topic,
+            "roaster/dev1/control/setpoint"
+        );
+        assert_eq!(published.recv().await.unwrap().payload, b"{}");
+        // Only the subscribed topic comes back as an incoming message
+        match events.try_recv().unwrap() {
+            MqttEvent::Publish {
+                topic,
+                qos,
+                dup,
+                received_at,
+                ..
+            } => {
+                assert_eq!(topic, "roaster/dev1/telemetry");
+                assert_eq!(qos, QoS::AtMostOnce);
+                assert!(!dup);
+                assert!(received_at <= SystemTime::now());
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(events.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mock_scans_and_clears_retained_messages() {
+        let (mqtt, _published) = MqttService::mock();
+        for (topic, payload) in [
+            ("roaster/dev1/status", "{}"),
+            ("roaster/dev2/control/setpoint", "200"),
+            ("rustroast/server/a/status", "online"),
+        ] {
+            mqtt.publish(topic, QoS::AtLeastOnce, true, payload)
+                .await
+                .unwrap();
+        }
+        let window = Duration::from_millis(50);
+
+        let found = mqtt.scan_retained("roaster/#", window).await.unwrap();
+        let topics: Vec<&str> = found.iter().map(|m| m.topic.as_str()).collect();
+        assert_eq!(
+            topics,
+            vec!["roaster/dev1/status", "roaster/dev2/control/setpoint"]
+        );
+        assert_eq!(found[1].payload, b"200");
+        // The scan doesn't leave its subscription behind
+        assert!(mqtt.subscriptions.read().await.is_empty());
+
+        mqtt.publish("roaster/dev2/control/setpoint", QoS::AtLeastOnce, true, "")
+            .await
+            .unwrap();
+        let found = mqtt.scan_retained("roaster/#", window).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].topic, "roaster/dev1/status");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_traffic_per_topic() {
+        let (mqtt, _published) = MqttService::mock();
+        mqtt.subscribe("roaster/+/telemetry", QoS::AtMostOnce)
+            .await
+            .unwrap();
+        mqtt.publish("roaster/dev1/telemetry", QoS::AtMostOnce, false, "{}")
+            .await
+            .unwrap();
+        mqtt.inject("roaster/dev1/status", "online");
+
+        let metrics = mqtt.metrics();
+        assert!(metrics.connected);
+        assert_eq!(
+            (metrics.messages_published, metrics.bytes_published),
+            (1, 2)
+        );
+        // The loopback of the subscribed publish counts as received too
+        assert_eq!((metrics.messages_received, metrics.bytes_received), (2, 8));
+        let telemetry = metrics.topics["roaster/dev1/telemetry"];
+        assert_eq!(
+            (telemetry.messages_published, telemetry.messages_received),
+            (1, 1)
+        );
+        assert_eq!(metrics.topics["roaster/dev1/status"].bytes_received, 6);
+        assert_eq!(metrics.backoff, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_publishes_are_dropped_and_refused() {
+        let (mut mqtt, mut published) = MqttService::mock();
+        mqtt.max_payload_size = 16;
+        let mut events = mqtt.events();
+
+        mqtt.inject("roaster/dev1/telemetry", vec![b'x'; 17]);
+        mqtt.inject("roaster/dev1/telemetry", "{}");
+        assert_eq!(
+            events.try_recv(),
+            Some(MqttEvent::Oversized {
+                topic: "roaster/dev1/telemetry".to_string(),
+                size: 17,
+            })
+        );
+        assert!(matches!(
+            events.try_recv(),
+            Some(MqttEvent::Publish { payload, .. }) if payload == b"{}"
+        ));
+
+        let refused = mqtt
+            .publish(
+                "roaster/dev1/control/setpoint",
+                QoS::AtLeastOnce,
+                false,
+                vec![b'1'; 17],
+            )
+            .await;
+        assert!(refused.is_err());
+        assert!(published.try_recv().is_err());
+        let metrics = mqtt.metrics();
+        assert_eq!(
+            (metrics.messages_oversized, metrics.messages_received),
+            (1, 1)
+        );
+        assert_eq!(metrics.publish_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_publishes_while_disconnected_are_sent_after_reconnecting() {
+        let (mut mqtt, mut published) = MqttService::mock();
+        mqtt.offline = Arc::new(OfflineQueue::new(8, Duration::from_secs(60)));
+        mqtt.disconnect().await.unwrap();
+        for (control, value) in [("setpoint", "200"), ("fan_pwm", "120")] {
+            let topic = format!("roaster/dev1/control/{control}");
+            mqtt.publish(&topic, QoS::AtLeastOnce, false, value)
+                .await
+                .unwrap();
+        }
+        assert!(published.try_recv().is_err());
+        assert_eq!(mqtt.metrics().offline_queued, 2);
+
+        mqtt.resubscribe_tracked().await.unwrap();
+        assert_eq!(
+            published.recv().await.unwrap().topic,
+            "roaster/dev1/control/setpoint"
+        );
+        assert_eq!(published.recv().await.unwrap().payload, b"120");
+        let metrics = mqtt.metrics();
+        assert_eq!((metrics.offline_queued, metrics.messages_published), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_mock_publish_and_wait_returns_packet_ids() {
+        let (mqtt, mut published) = MqttService::mock();
+        let mut events = mqtt.events();
+        let wait = Duration::from_secs(1);
+        let request_id = vec![("request-id".to_string(), "r-1".to_string())];
+        let first = mqtt
+            .publish_and_wait(
+                "roaster/dev1/control/setpoint",
+                QoS::AtLeastOnce,
+                false,
+                "200",
+                request_id.clone(),
+                wait,
+            )
+            .await
+            .unwrap();
+        let second = mqtt
+            .publish_and_wait(
+                "roaster/dev1/control/fan_pwm",
+                QoS::AtLeastOnce,
+                false,
+                "120",
+                Vec::new(),
+                wait,
+            )
+            .await
+            .unwrap();
+        assert_ne!(first, second);
+        assert_eq!(published.recv().await.unwrap().user_properties, request_id);
+        assert!(matches!(events.try_recv(), Some(MqttEvent::PubAck(id)) if id == first));
+        assert!(matches!(events.try_recv(), Some(MqttEvent::PubAck(id)) if id == second));
+
+        let qos0 = mqtt
+            .publish_and_wait(
+                "roaster/dev1/control/mode",
+                QoS::AtMostOnce,
+                false,
+                "auto",
+                Vec::new(),
+                wait,
+            )
+            .await
+            .unwrap();
+        assert_eq!(qos0, 0);
+    }
+
+    #[tokio::test]
+    async fn test_presence_birth_will_and_clean_shutdown() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Minimal broker: accept the CONNECT and record everything after it
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (chunks_tx, mut chunks) = mpsc::unbounded_channel::<Vec<u8>>();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 512];
+            let n = stream.read(&mut buf).await.unwrap_or(0);
+            let _ = chunks_tx.send(buf[..n].to_vec());
+            let _ = stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await;
+            loop {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        let _ = chunks_tx.send(buf[..n].to_vec());
                     }
                 }
             }
+        });
+        let contains =
+            |bytes: &[u8], needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+        let topic = server_status_topic("rr-presence");
+
+        let config = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            client_id: "rr-presence".to_string(),
+            ..MqttConfig::default()
+        };
+        let mqtt = MqttService::connect(config).await.unwrap();
+        let connect = chunks.recv().await.unwrap();
+        // Will flag, QoS 1 and retain set in the connect flags
+        assert_eq!(connect[9] & 0b0011_1100, 0b0010_1100);
+        assert!(contains(&connect, topic.as_bytes()));
+        assert!(contains(&connect, SERVER_OFFLINE.as_bytes()));
+
+        let mut received = Vec::new();
+        while !contains(&received, SERVER_ONLINE.as_bytes()) {
+            received.extend(chunks.recv().await.unwrap());
+        }
+        // Retained QoS 1 publish
+        assert_eq!(received[0], 0x33);
+
+        mqtt.shutdown(Duration::from_secs(2)).await.unwrap();
+        let mut received = Vec::new();
+        while !contains(&received, &[0xE0, 0x00]) {
+            received.extend(chunks.recv().await.unwrap());
         }
+        assert_eq!(received[0], 0x33);
+        assert!(contains(&received, topic.as_bytes()));
+        assert!(contains(&received, SERVER_OFFLINE.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_update_credentials_reconnects_with_new_password() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Minimal broker: record each CONNECT packet and accept it
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (connects_tx, mut connects) = mpsc::unbounded_channel::<Vec<u8>>();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let tx = connects_tx.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 512];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let _ = tx.send(buf[..n].to_vec());
+                    let _ = stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await;
+                    // Hold the connection open until the client drops it
+                    let _ = stream.read(&mut buf).await;
+                });
+            }
+        });
+
+        let config = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: Some("roaster".to_string()),
+            password: Some("old-token".to_string()),
+            ..MqttConfig::default()
+        };
+        let mqtt = MqttService::connect(config).await.unwrap();
+        let first = connects.recv().await.unwrap();
+        assert!(first.windows(9).any(|w| w == b"old-token"));
+
+        let connected = mqtt
+            .update_credentials(
+                MqttCredentials {
+                    username: None,
+                    password: "new-token".to_string(),
+                },
+                Duration::from_secs(2),
+            )
+            .await;
+        assert!(connected);
+        let second = connects.recv().await.unwrap();
+        assert!(second.windows(9).any(|w| w == b"new-token"));
+        assert!(second.windows(7).any(|w| w == b"roaster"));
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure_moves_to_another_broker() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Minimal brokers accepting every CONNECT, which they pass on
+        let (connects_tx, mut connects) = mpsc::unbounded_channel::<(u16, Vec<u8>)>();
+        let mut ports = Vec::new();
+        for _ in 0..2 {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            ports.push(port);
+            let connects_tx = connects_tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let tx = connects_tx.clone();
+                    tokio::spawn(async move {
+                        let mut buf = vec![0u8; 512];
+                        let n = stream.read(&mut buf).await.unwrap_or(0);
+                        let _ = tx.send((port, buf[..n].to_vec()));
+                        let _ = stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await;
+                        let _ = stream.read(&mut buf).await;
+                    });
+                }
+            });
+        }
+
+        let config = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port: ports[0],
+            client_id: "rustroast-test".to_string(),
+            ..MqttConfig::default()
+        };
+        let mqtt = MqttService::connect(config).await.unwrap();
+        assert_eq!(connects.recv().await.unwrap().0, ports[0]);
+
+        // A config the client can't be built from leaves it alone
+        let broken = MqttConfig {
+            tls: Some(MqttTlsConfig {
+                client_cert: Some("client.pem".into()),
+                ..MqttTlsConfig::default()
+            }),
+            ..mqtt.config().unwrap()
+        };
+        assert!(matches!(
+            mqtt.reconfigure(broken, Duration::from_secs(2)).await,
+            Err(MqttConfigError::IncompleteClientAuth)
+        ));
+        assert_eq!(mqtt.broker(), Some(format!("127.0.0.1:{}", ports[0])));
+
+        let moved = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port: ports[1],
+            username: Some("roaster".to_string()),
+            password: Some("rotated".to_string()),
+            ..MqttConfig::default()
+        };
+        let connected = mqtt
+            .reconfigure(moved, Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert!(connected);
+        let (port, connect) = connects.recv().await.unwrap();
+        assert_eq!(port, ports[1]);
+        assert!(connect.windows(7).any(|w| w == b"rotated"));
+        // The client id is kept
+        assert_eq!(mqtt.config().unwrap().client_id, "rustroast-test");
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_to_the_next_broker() {
+        use crate::config::MqttBroker;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Nothing listens on the primary any more
+        let primary = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary_port = primary.local_addr().unwrap().port();
+        drop(primary);
+        let backup = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backup_port = backup.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = backup.accept().await.unwrap();
+            let mut buf = vec![0u8; 512];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await;
+            // Hold the connection open until the client drops it
+            while stream.read(&mut buf).await.is_ok_and(|n| n > 0) {}
+        });
+
+        let broker = |port| MqttBroker {
+            host: "127.0.0.1".to_string(),
+            port,
+        };
+        let config = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port: primary_port,
+            brokers: vec![broker(primary_port), broker(backup_port)],
+            ..MqttConfig::default()
+        };
+        let mqtt = MqttService::connect(config).await.unwrap();
+        let mut events = mqtt.events();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while events.recv().await != Ok(MqttEvent::Connected) {}
+        })
+        .await
+        .expect("connected to the backup broker");
+        assert_eq!(mqtt.broker(), Some(format!("127.0.0.1:{backup_port}")));
     }
 }
@@ -1,15 +1,150 @@
 use hostname::get as get_hostname;
 use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::events::{OverflowPolicy, DEFAULT_EVENT_QUEUE};
+use crate::offline::DEFAULT_OFFLINE_TTL;
+
+/// Default port of MQTT over TLS.
+pub const MQTT_TLS_PORT: u16 = 8883;
+/// Default largest MQTT packet sent or accepted. A bigger incoming packet
+/// fails the connection, so it is kept well above any legitimate payload.
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 256 * 1024;
+/// Default largest publish payload handed to subscribers or sent.
+pub const DEFAULT_MAX_PAYLOAD_SIZE: usize = 128 * 1024;
+/// Default keep-alive intervals without a packet from the broker after
+/// which the connection counts as stale.
+pub const DEFAULT_STALE_KEEPALIVES: f32 = 1.5;
+/// Path brokers and proxies commonly serve MQTT over WebSockets on.
+const DEFAULT_WS_PATH: &str = "/mqtt";
+
+/// How the client reaches the broker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MqttTransportKind {
+    /// MQTT over TCP (TLS when `tls` is set).
+    #[default]
+    Tcp,
+    /// MQTT over WebSockets, `wss://` when `tls` is set.
+    WebSocket,
+}
+
+/// MQTT protocol version spoken to the broker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MqttProtocol {
+    #[default]
+    V311,
+    /// MQTT 5: user properties on publishes and reason codes in acks.
+    V5,
+}
 
 #[derive(Debug, Clone)]
 pub struct MqttConfig {
+    /// The broker in use, one of `brokers` when those are set.
     pub host: String,
     pub port: u16,
+    /// Brokers to fail over between, in order: when the one in use is
+    /// unreachable the client moves on to the next, wrapping around.
+    /// Empty for a single broker.
+    pub brokers: Vec<MqttBroker>,
     pub client_id: String,
     pub username: Option<String>,
     pub password: Option<String>,
     pub keep_alive_secs: u16,
     pub clean_session: bool,
+    /// Connect over TLS when set.
+    pub tls: Option<MqttTlsConfig>,
+    pub transport: MqttTransportKind,
+    pub protocol: MqttProtocol,
+    /// Events each local subscriber can have queued.
+    pub event_queue: usize,
+    /// What a subscriber's full event queue does with the next event.
+    pub event_overflow: OverflowPolicy,
+    /// Publishes held while the broker is unreachable and sent once it is
+    /// back; 0 fails them right away.
+    pub offline_queue: usize,
+    /// How long a held publish stays worth sending.
+    pub offline_ttl: Duration,
+    /// Largest packet sent or accepted, enforced by the protocol client:
+    /// a bigger incoming packet fails the connection before it is read
+    /// into memory.
+    pub max_packet_size: usize,
+    /// Largest payload of a publish. Bigger incoming publishes are dropped
+    /// as [`MqttEvent::Oversized`](crate::MqttEvent::Oversized) instead of
+    /// reaching subscribers, and bigger outgoing ones are refused.
+    pub max_payload_size: usize,
+    /// Topic aliases the broker may use on publishes to this client
+    /// (MQTT 5 only); `None` allows none.
+    pub topic_alias_max: Option<u16>,
+    /// Keep-alive intervals without a packet from the broker after which
+    /// the connection is reported stale; 0 turns the watchdog off.
+    pub stale_keepalives: f32,
+}
+
+/// One broker of a failover list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttBroker {
+    pub host: String,
+    pub port: u16,
+}
+
+impl MqttBroker {
+    /// A broker from `host:port`, `host` (on `default_port`) or a
+    /// `ws://`/`wss://` URL, as in `MQTT_BROKER_HOSTS`.
+    pub fn parse(entry: &str, default_port: u16) -> Self {
+        let (host, port) = parse_broker(entry.trim());
+        Self {
+            host,
+            port: port.unwrap_or(default_port),
+        }
+    }
+}
+
+impl std::fmt::Display for MqttBroker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+/// TLS to the broker. Without `ca_cert` the platform's root certificates
+/// are trusted. Files are PEM and read each time the client is built: at
+/// start, on reconfiguration and when reconnecting after a connection
+/// error, so renewed certificates are picked up without a restart.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MqttTlsConfig {
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate and private key, for brokers requiring mutual TLS.
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+/// An MQTT configuration the client can't be built from.
+#[derive(Debug, thiserror::Error)]
+pub enum MqttConfigError {
+    #[error("failed to read {}: {source}", path.display())]
+    ReadFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("a client certificate and key must be configured together")]
+    IncompleteClientAuth,
+    #[error("a client certificate needs the broker's CA certificate")]
+    ClientAuthWithoutCa,
+    #[error("unknown MQTT_TRANSPORT {0:?}, expected tcp, ws or wss")]
+    UnknownTransport(String),
+    #[error("broker URL {host} doesn't match the {transport} transport")]
+    TransportMismatch {
+        host: String,
+        transport: &'static str,
+    },
+}
+
+/// Broker credentials applied at runtime. A bearer token is sent as the
+/// password; `username: None` keeps the configured username.
+#[derive(Debug, Clone)]
+pub struct MqttCredentials {
+    pub username: Option<String>,
+    pub password: String,
 }
 
 impl Default for MqttConfig {
@@ -21,17 +156,29 @@ impl Default for MqttConfig {
         Self {
             host,
             port,
+            brokers: Vec::new(),
             client_id,
             username: None,
             password: None,
             keep_alive_secs,
             clean_session: true,
+            tls: None,
+            transport: MqttTransportKind::Tcp,
+            protocol: MqttProtocol::V311,
+            event_queue: DEFAULT_EVENT_QUEUE,
+            event_overflow: OverflowPolicy::default(),
+            offline_queue: 0,
+            offline_ttl: DEFAULT_OFFLINE_TTL,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            max_payload_size: DEFAULT_MAX_PAYLOAD_SIZE,
+            topic_alias_max: None,
+            stale_keepalives: DEFAULT_STALE_KEEPALIVES,
         }
     }
 }
 
 impl MqttConfig {
-    pub fn from_env() -> Self {
+    pub fn from_env() -> Result<Self, MqttConfigError> {
         let mut cfg = MqttConfig::default();
 
         if let Ok(v) = env::var("MQTT_BROKER_HOST") {
@@ -39,11 +186,60 @@ impl MqttConfig {
                 cfg.host = v;
             }
         }
+        // `primary:1883,backup:1883` overrides MQTT_BROKER_HOST; ports
+        // are resolved below once the default port is known
+        let hosts = env::var("MQTT_BROKER_HOSTS").unwrap_or_default();
+        let hosts: Vec<(String, Option<u16>)> = hosts
+            .split(',')
+            .map(str::trim)
+            .filter(|h| !h.is_empty())
+            .map(parse_broker)
+            .collect();
+        if let Some((host, _)) = hosts.first() {
+            cfg.host = host.clone();
+        }
+        // TLS when MQTT_TLS is set or any certificate is configured
+        let tls = MqttTlsConfig {
+            ca_cert: env_path("MQTT_CA_CERT"),
+            client_cert: env_path("MQTT_CLIENT_CERT"),
+            client_key: env_path("MQTT_CLIENT_KEY"),
+        };
+        let mut tls_enabled = env::var("MQTT_TLS")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false)
+            || tls != MqttTlsConfig::default();
+        // WebSockets via MQTT_TRANSPORT (`ws`/`wss`) or a ws(s):// broker URL
+        let urls: Vec<&str> = if hosts.is_empty() {
+            vec![cfg.host.as_str()]
+        } else {
+            hosts.iter().map(|(host, _)| host.as_str()).collect()
+        };
+        let setting = env::var("MQTT_TRANSPORT").ok().filter(|v| !v.is_empty());
+        (cfg.transport, tls_enabled) = resolve_transport(setting.as_deref(), tls_enabled, &urls)?;
+        if tls_enabled {
+            cfg.tls = Some(tls);
+        }
+        cfg.port = match (cfg.transport, tls_enabled) {
+            (MqttTransportKind::Tcp, false) => cfg.port,
+            (MqttTransportKind::Tcp, true) => MQTT_TLS_PORT,
+            (MqttTransportKind::WebSocket, false) => 80,
+            (MqttTransportKind::WebSocket, true) => 443,
+        };
         if let Ok(v) = env::var("MQTT_BROKER_PORT") {
             if let Ok(p) = v.parse::<u16>() {
                 cfg.port = p;
             }
         }
+        cfg.brokers = hosts
+            .into_iter()
+            .map(|(host, port)| MqttBroker {
+                host,
+                port: port.unwrap_or(cfg.port),
+            })
+            .collect();
+        if let Some(first) = cfg.brokers.first() {
+            cfg.port = first.port;
+        }
         if let Ok(v) = env::var("MQTT_CLIENT_ID") {
             if !v.is_empty() {
                 cfg.client_id = v;
@@ -64,11 +260,173 @@ impl MqttConfig {
                 cfg.keep_alive_secs = s;
             }
         }
+        if let Ok(v) = env::var("MQTT_PROTOCOL") {
+            if matches!(v.as_str(), "5" | "5.0" | "v5") {
+                cfg.protocol = MqttProtocol::V5;
+            }
+        }
+        if let Ok(v) = env::var("MQTT_EVENT_QUEUE") {
+            if let Ok(n) = v.parse::<usize>() {
+                cfg.event_queue = n.max(1);
+            }
+        }
+        if let Ok(v) = env::var("MQTT_EVENT_OVERFLOW") {
+            if let Ok(policy) = v.parse() {
+                cfg.event_overflow = policy;
+            }
+        }
+        if let Ok(v) = env::var("MQTT_OFFLINE_QUEUE") {
+            if let Ok(n) = v.parse::<usize>() {
+                cfg.offline_queue = n;
+            }
+        }
+        if let Ok(v) = env::var("MQTT_OFFLINE_TTL_SECS") {
+            if let Ok(secs) = v.parse::<u64>() {
+                cfg.offline_ttl = Duration::from_secs(secs.max(1));
+            }
+        }
+        if let Ok(v) = env::var("MQTT_MAX_PACKET_SIZE") {
+            if let Ok(n) = v.parse::<usize>() {
+                cfg.max_packet_size = n.max(1024);
+            }
+        }
+        if let Ok(v) = env::var("MQTT_MAX_PAYLOAD_SIZE") {
+            if let Ok(n) = v.parse::<usize>() {
+                cfg.max_payload_size = n;
+            }
+        }
+        if let Ok(v) = env::var("MQTT_TOPIC_ALIAS_MAX") {
+            if let Ok(n) = v.parse::<u16>() {
+                cfg.topic_alias_max = Some(n).filter(|n| *n > 0);
+            }
+        }
+        if let Ok(v) = env::var("MQTT_STALE_KEEPALIVES") {
+            if let Ok(n) = v.parse::<f32>() {
+                if n.is_finite() && n >= 0.0 {
+                    cfg.stale_keepalives = n;
+                }
+            }
+        }
+
+        Ok(cfg)
+    }
+
+    /// `self` with the connection settings of `new`: brokers, credentials,
+    /// TLS, transport, protocol, keep-alive, packet size and topic aliases.
+    /// The client id, the local queue settings and the payload limit stay,
+    /// as they can't change while running.
+    pub fn with_connection_from(&self, new: MqttConfig) -> MqttConfig {
+        MqttConfig {
+            client_id: self.client_id.clone(),
+            event_queue: self.event_queue,
+            event_overflow: self.event_overflow,
+            offline_queue: self.offline_queue,
+            offline_ttl: self.offline_ttl,
+            max_payload_size: self.max_payload_size,
+            ..new
+        }
+    }
+
+    /// Silence from the broker after which the connection is stale, or
+    /// `None` without a watchdog (or a keep-alive).
+    pub fn stale_after(&self) -> Option<Duration> {
+        let secs = f32::from(self.keep_alive_secs) * self.stale_keepalives;
+        (secs > 0.0).then(|| Duration::from_secs_f32(secs))
+    }
+
+    /// Switch `host` and `port` to the broker after the one in use,
+    /// wrapping around. Returns it with its index in `brokers`, or `None`
+    /// when there is nothing to fail over to.
+    pub fn next_broker(&mut self) -> Option<(usize, MqttBroker)> {
+        if self.brokers.len() < 2 {
+            return None;
+        }
+        let current = self
+            .brokers
+            .iter()
+            .position(|b| b.host == self.host && b.port == self.port);
+        let next = current.map_or(0, |i| (i + 1) % self.brokers.len());
+        let broker = self.brokers[next].clone();
+        self.host = broker.host.clone();
+        self.port = broker.port;
+        Some((next, broker))
+    }
+
+    /// Broker URL for the WebSocket transport: `host` when it already is a
+    /// `ws://`/`wss://` URL, otherwise built from host and port.
+    pub fn websocket_url(&self) -> String {
+        if self.host.starts_with("ws://") || self.host.starts_with("wss://") {
+            return self.host.clone();
+        }
+        let scheme = if self.tls.is_some() { "wss" } else { "ws" };
+        format!(
+            "{}://{}:{}{}",
+            scheme, self.host, self.port, DEFAULT_WS_PATH
+        )
+    }
+}
+
+/// The transport and whether it uses TLS, from `MQTT_TRANSPORT` or else the
+/// scheme of the first broker URL. Every `scheme://` broker must match it,
+/// so a `wss://` URL can't end up without TLS or a URL used as a TCP host.
+fn resolve_transport(
+    setting: Option<&str>,
+    mut tls: bool,
+    urls: &[&str],
+) -> Result<(MqttTransportKind, bool), MqttConfigError> {
+    let url_scheme = |url: &str| url.split_once("://").map(|(scheme, _)| scheme.to_string());
+    let mut kind = MqttTransportKind::Tcp;
+    match setting
+        .map(str::to_string)
+        .or_else(|| urls.first().and_then(|url| url_scheme(url)))
+        .as_deref()
+    {
+        None | Some("tcp") => {}
+        Some("ws") => kind = MqttTransportKind::WebSocket,
+        Some("wss") => {
+            kind = MqttTransportKind::WebSocket;
+            tls = true;
+        }
+        Some(other) => return Err(MqttConfigError::UnknownTransport(other.to_string())),
+    }
+    let transport = match (kind, tls) {
+        (MqttTransportKind::Tcp, _) => "tcp",
+        (MqttTransportKind::WebSocket, false) => "ws",
+        (MqttTransportKind::WebSocket, true) => "wss",
+    };
+    match urls
+        .iter()
+        .find(|url| url_scheme(url).is_some_and(|scheme| scheme != transport))
+    {
+        Some(url) => Err(MqttConfigError::TransportMismatch {
+            host: url.to_string(),
+            transport,
+        }),
+        None => Ok((kind, tls)),
+    }
+}
 
-        cfg
+/// `host:port`, `host`, or a `ws://`/`wss://` URL (which keeps its port).
+fn parse_broker(entry: &str) -> (String, Option<u16>) {
+    if entry.contains("://") {
+        return (entry.to_string(), None);
+    }
+    match entry.rsplit_once(':') {
+        Some((host, port)) => match port.parse() {
+            Ok(port) => (host.to_string(), Some(port)),
+            Err(_) => (entry.to_string(), None),
+        },
+        None => (entry.to_string(), None),
     }
 }
 
+fn env_path(key: &str) -> Option<PathBuf> {
+    env::var(key)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
 fn default_client_id() -> String {
     let host = get_hostname()
         .ok()
@@ -77,3 +435,83 @@ fn default_client_id() -> String {
     let pid = std::process::id();
     format!("rustroast-{}-{}", host, pid)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_must_match_broker_urls() {
+        use MqttTransportKind::{Tcp, WebSocket};
+        let resolve = |setting, tls, urls: &[&str]| resolve_transport(setting, tls, urls).ok();
+        assert_eq!(resolve(None, false, &["broker"]), Some((Tcp, false)));
+        assert_eq!(
+            resolve(None, false, &["wss://p/mqtt"]),
+            Some((WebSocket, true))
+        );
+        assert_eq!(
+            resolve(Some("ws"), true, &["broker"]),
+            Some((WebSocket, true))
+        );
+        assert_eq!(
+            resolve(Some("wss"), false, &["wss://p/mqtt"]),
+            Some((WebSocket, true))
+        );
+
+        assert!(matches!(
+            resolve_transport(Some("websocket"), false, &["broker"]),
+            Err(MqttConfigError::UnknownTransport(_))
+        ));
+        for (setting, tls, url) in [
+            (Some("ws"), false, "wss://p/mqtt"),
+            (Some("tcp"), false, "ws://p/mqtt"),
+            (None, true, "ws://p/mqtt"),
+            (None, false, "mqtts://broker"),
+        ] {
+            assert!(
+                matches!(
+                    resolve_transport(setting, tls, &[url]),
+                    Err(MqttConfigError::TransportMismatch { .. }
+                        | MqttConfigError::UnknownTransport(_))
+                ),
+                "{setting:?} {url}"
+            );
+        }
+        assert!(resolve(None, false, &["ws://a/mqtt", "wss://b/mqtt"]).is_none());
+    }
+
+    #[test]
+    fn test_next_broker_wraps_around() {
+        let mut config = MqttConfig {
+            host: "primary".to_string(),
+            port: 1883,
+            brokers: vec![
+                MqttBroker {
+                    host: "primary".to_string(),
+                    port: 1883,
+                },
+                MqttBroker {
+                    host: "backup".to_string(),
+                    port: 1884,
+                },
+            ],
+            ..MqttConfig::default()
+        };
+        assert_eq!(config.next_broker().map(|(i, _)| i), Some(1));
+        assert_eq!((config.host.as_str(), config.port), ("backup", 1884));
+        assert_eq!(config.next_broker().map(|(i, _)| i), Some(0));
+        assert_eq!((config.host.as_str(), config.port), ("primary", 1883));
+
+        config.brokers.truncate(1);
+        assert!(config.next_broker().is_none());
+        assert_eq!(
+            parse_broker("backup:1884"),
+            ("backup".to_string(), Some(1884))
+        );
+        assert_eq!(parse_broker("backup"), ("backup".to_string(), None));
+        assert_eq!(
+            parse_broker("wss://proxy.example.com:443/mqtt"),
+            ("wss://proxy.example.com:443/mqtt".to_string(), None)
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Delivery of [`MqttEvent`]s to local subscribers. Every subscriber has a
+//! bounded queue of its own, so a slow one (say a WebSocket client on a poor
+//! link) only ever loses its own events. What happens when a queue is full
+//! is set by the [`OverflowPolicy`].
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use tokio::sync::Notify;
+
+use crate::client::MqttEvent;
+
+/// Events a subscriber can have queued before its overflow policy applies.
+pub const DEFAULT_EVENT_QUEUE: usize = 256;
+
+/// What a full subscriber queue does with the next event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the subscriber's oldest queued event to make room.
+    #[default]
+    DropOldest,
+    /// Drop the new event for that subscriber.
+    DropNewest,
+    /// Stop reading from the broker until the subscriber catches up. Nothing
+    /// is lost, but a stalled subscriber stalls all of them.
+    Block,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop-oldest" => Ok(OverflowPolicy::DropOldest),
+            "drop-newest" => Ok(OverflowPolicy::DropNewest),
+            "block" => Ok(OverflowPolicy::Block),
+            _ => Err(format!("Invalid overflow policy: {}", s)),
+        }
+    }
+}
+
+/// Why [`EventReceiver::recv`] returned no event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum EventRecvError {
+    /// This many events were dropped from the subscriber's full queue since
+    /// its last receive.
+    #[error("subscriber dropped {0} events")]
+    Lagged(u64),
+    /// The service is gone.
+    #[error("event bus closed")]
+    Closed,
+}
+
+struct Queue {
+    events: Mutex<VecDeque<MqttEvent>>,
+    /// Events dropped and not yet reported as [`EventRecvError::Lagged`].
+    dropped: AtomicU64,
+    /// Set when either the bus or the receiver is gone.
+    closed: AtomicBool,
+    readable: Notify,
+    writable: Notify,
+}
+
+struct Bus {
+    subscribers: Mutex<Vec<Weak<Queue>>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    /// Events dropped across all subscribers.
+    dropped: AtomicU64,
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        for queue in self.subscribers.get_mut().unwrap().drain(..) {
+            if let Some(queue) = queue.upgrade() {
+                queue.closed.store(true, Ordering::Release);
+                queue.readable.notify_one();
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct EventBus {
+    bus: Arc<Bus>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            bus: Arc::new(Bus {
+                subscribers: Mutex::new(Vec::new()),
+                capacity: capacity.max(1),
+                policy,
+                dropped: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn subscribe(&self) -> EventReceiver {
+        let queue = Arc::new(Queue {
+            events: Mutex::new(VecDeque::new()),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            readable: Notify::new(),
+            writable: Notify::new(),
+        });
+        self.bus
+            .subscribers
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&queue));
+        EventReceiver { queue }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.bus.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Live subscriber queues, forgetting dropped receivers.
+    fn queues(&self) -> Vec<Arc<Queue>> {
+        let mut subscribers = self.bus.subscribers.lock().unwrap();
+        subscribers.retain(|q| q.strong_count() > 0);
+        subscribers.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Queue `event` for every subscriber without waiting. Under
+    /// [`OverflowPolicy::Block`] a full queue takes it anyway: these are
+    /// the service's own state changes and injected messages, not broker
+    /// traffic that can be held back.
+    pub fn send(&self, event: MqttEvent) {
+        for queue in self.queues() {
+            self.push(&queue, event.clone(), true);
+        }
+    }
+
+    /// Queue an event read from the broker for every subscriber, waiting for
+    /// room under [`OverflowPolicy::Block`].
+    pub async fn send_from_broker(&self, event: MqttEvent) {
+        for queue in self.queues() {
+            while !self.push(&queue, event.clone(), false) {
+                queue.writable.notified().await;
+            }
+        }
+    }
+
+    /// Returns false when the queue is full and the policy is to wait,
+    /// unless `force` is set.
+    fn push(&self, queue: &Queue, event: MqttEvent, force: bool) -> bool {
+        if queue.closed.load(Ordering::Acquire) {
+            return true;
+        }
+        let mut events = queue.events.lock().unwrap();
+        if events.len() >= self.bus.capacity {
+            match self.bus.policy {
+                OverflowPolicy::DropOldest => {
+                    events.pop_front();
+                    self.record_drop(queue);
+                }
+                OverflowPolicy::DropNewest => {
+                    self.record_drop(queue);
+                    return true;
+                }
+                OverflowPolicy::Block if !force => return false,
+                OverflowPolicy::Block => {}
+            }
+        }
+        events.push_back(event);
+        drop(events);
+        queue.readable.notify_one();
+        true
+    }
+
+    fn record_drop(&self, queue: &Queue) {
+        queue.dropped.fetch_add(1, Ordering::Relaxed);
+        self.bus.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// One subscriber's queue of events, from
+/// [`MqttService::events`](crate::MqttService::events).
+pub struct EventReceiver {
+    queue: Arc<Queue>,
+}
+
+impl EventReceiver {
+    /// The next event, after reporting any events dropped from this
+    /// subscriber's queue since the last call. Receiving is cancel safe.
+    pub async fn recv(&mut self) -> Result<MqttEvent, EventRecvError> {
+        loop {
+            let dropped = self.queue.dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                return Err(EventRecvError::Lagged(dropped));
+            }
+            let event = self.queue.events.lock().unwrap().pop_front();
+            if let Some(event) = event {
+                self.queue.writable.notify_one();
+                return Ok(event);
+            }
+            if self.queue.closed.load(Ordering::Acquire) {
+                return Err(EventRecvError::Closed);
+            }
+            self.queue.readable.notified().await;
+        }
+    }
+
+    /// The next queued event, if any, without waiting or reporting drops.
+    pub fn try_recv(&mut self) -> Option<MqttEvent> {
+        let event = self.queue.events.lock().unwrap().pop_front();
+        if event.is_some() {
+            self.queue.writable.notify_one();
+        }
+        event
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        // Release a sender blocked on this queue
+        self.queue.closed.store(true, Ordering::Release);
+        self.queue.writable.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn publish(n: u8) -> MqttEvent {
+        MqttEvent::Publish {
+            topic: "roaster/dev1/telemetry".to_string(),
+            payload: vec![n],
+            qos: rumqttc::QoS::AtMostOnce,
+            retain: false,
+            dup: false,
+            received_at: std::time::SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    fn payload(event: MqttEvent) -> u8 {
+        match event {
+            MqttEvent::Publish { payload, .. } => payload[0],
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_only_loses_its_own_events() {
+        let bus = EventBus::new(2, OverflowPolicy::DropOldest);
+        let mut slow = bus.subscribe();
+        let mut fast = bus.subscribe();
+        for n in 0..4 {
+            bus.send_from_broker(publish(n)).await;
+            assert_eq!(payload(fast.recv().await.unwrap()), n);
+        }
+        assert_eq!(slow.recv().await, Err(EventRecvError::Lagged(2)));
+        assert_eq!(payload(slow.recv().await.unwrap()), 2);
+        assert_eq!(payload(slow.recv().await.unwrap()), 3);
+        assert_eq!(bus.dropped(), 2);
+
+        let bus = EventBus::new(2, OverflowPolicy::DropNewest);
+        let mut slow = bus.subscribe();
+        for n in 0..4 {
+            bus.send_from_broker(publish(n)).await;
+        }
+        assert_eq!(slow.recv().await, Err(EventRecvError::Lagged(2)));
+        assert_eq!(payload(slow.recv().await.unwrap()), 0);
+        assert_eq!(payload(slow.recv().await.unwrap()), 1);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_the_subscriber() {
+        let bus = EventBus::new(1, OverflowPolicy::Block);
+        let mut rx = bus.subscribe();
+        bus.send_from_broker(publish(0)).await;
+        let sender = bus.clone();
+        let blocked = tokio::spawn(async move { sender.send_from_broker(publish(1)).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(payload(rx.recv().await.unwrap()), 0);
+        tokio::time::timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload(rx.recv().await.unwrap()), 1);
+        assert_eq!(bus.dropped(), 0);
+
+        // A dropped receiver doesn't hold anyone up
+        drop(rx);
+        bus.send_from_broker(publish(2)).await;
+        bus.send_from_broker(publish(3)).await;
+    }
+
+    #[tokio::test]
+    async fn test_receivers_close_with_the_bus() {
+        let bus = EventBus::new(4, OverflowPolicy::default());
+        let mut rx = bus.subscribe();
+        bus.send(MqttEvent::Connected);
+        drop(bus);
+        assert_eq!(rx.recv().await, Ok(MqttEvent::Connected));
+        assert_eq!(rx.recv().await, Err(EventRecvError::Closed));
+    }
+}
@@ -1,5 +1,23 @@
+pub mod ack;
 pub mod client;
 pub mod config;
+pub mod events;
+pub mod metrics;
+mod offline;
+pub mod protocol;
+pub mod router;
+mod watchdog;
 
-pub use client::{MqttEvent, MqttService};
-pub use config::MqttConfig;
+pub use ack::{PublishAckError, PublishRejection};
+pub use client::{
+    topic_matches, MqttEvent, MqttService, PublishLane, PublishObserver, PublishedMessage,
+    RetainedMessage,
+};
+pub use config::{
+    MqttBroker, MqttConfig, MqttConfigError, MqttCredentials, MqttProtocol, MqttTlsConfig,
+    MqttTransportKind,
+};
+pub use events::{EventReceiver, EventRecvError, OverflowPolicy};
+pub use metrics::{MqttMetricsSnapshot, TopicTraffic};
+pub use protocol::UserProperties;
+pub use router::{shared_filter, topic_captures, unshared_filter, TopicMessage, TopicSubscription};
@@ -0,0 +1,175 @@
+//! Traffic and connection counters kept by [`MqttService`](crate::MqttService)
+//! as messages pass through it, read with
+//! [`metrics`](crate::MqttService::metrics).
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Topics counted one by one. Traffic on further topics is counted under
+/// [`OTHER_TOPICS`] so a flood of device ids can't grow the map unbounded.
+pub const MAX_TRACKED_TOPICS: usize = 512;
+/// Key collecting the traffic of topics past [`MAX_TRACKED_TOPICS`].
+pub const OTHER_TOPICS: &str = "(other)";
+
+/// Message and byte counts on one topic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TopicTraffic {
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    pub messages_published: u64,
+    pub bytes_published: u64,
+}
+
+/// Counters since the service started.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MqttMetricsSnapshot {
+    pub connected: bool,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    /// Incoming publishes dropped for a payload over `max_payload_size`,
+    /// not counted as received.
+    pub messages_oversized: u64,
+    /// Publishes the client accepted.
+    pub messages_published: u64,
+    pub bytes_published: u64,
+    /// Publishes the client refused, e.g. because the service is shutting
+    /// down.
+    pub publish_failures: u64,
+    /// Times the client was rebuilt to reconnect, after a connection error
+    /// or a credential change.
+    pub reconnects: u64,
+    /// How long the client is waiting before its next connection attempt,
+    /// zero unless it is backing off.
+    #[serde(serialize_with = "as_secs")]
+    pub backoff: Duration,
+    /// Events dropped from full subscriber queues.
+    pub events_dropped: u64,
+    /// Publishes held while disconnected, waiting to be sent.
+    pub offline_queued: u64,
+    /// Held publishes dropped to make room for newer ones.
+    pub offline_overflowed: u64,
+    /// Held publishes dropped for going stale before the reconnect.
+    pub offline_expired: u64,
+    pub topics: BTreeMap<String, TopicTraffic>,
+}
+
+fn as_secs<S: serde::Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64())
+}
+
+#[derive(Default)]
+pub(crate) struct MqttMetrics {
+    messages_received: AtomicU64,
+    bytes_received: AtomicU64,
+    messages_oversized: AtomicU64,
+    messages_published: AtomicU64,
+    bytes_published: AtomicU64,
+    publish_failures: AtomicU64,
+    reconnects: AtomicU64,
+    backoff_ms: AtomicU64,
+    topics: Mutex<HashMap<String, TopicTraffic>>,
+}
+
+impl MqttMetrics {
+    pub fn received(&self, topic: &str, bytes: usize) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.with_topic(topic, |t| {
+            t.messages_received += 1;
+            t.bytes_received += bytes as u64;
+        });
+    }
+
+    pub fn oversized(&self) {
+        self.messages_oversized.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn published(&self, topic: &str, bytes: usize) {
+        self.messages_published.fetch_add(1, Ordering::Relaxed);
+        self.bytes_published
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.with_topic(topic, |t| {
+            t.messages_published += 1;
+            t.bytes_published += bytes as u64;
+        });
+    }
+
+    pub fn publish_failed(&self) {
+        self.publish_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reconnecting(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_backoff(&self, backoff: Duration) {
+        self.backoff_ms
+            .store(backoff.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn with_topic(&self, topic: &str, update: impl FnOnce(&mut TopicTraffic)) {
+        let mut topics = self.topics.lock().unwrap();
+        let key = if topics.contains_key(topic) || topics.len() < MAX_TRACKED_TOPICS {
+            topic
+        } else {
+            OTHER_TOPICS
+        };
+        update(topics.entry(key.to_string()).or_default());
+    }
+
+    pub fn snapshot(&self, connected: bool, events_dropped: u64) -> MqttMetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MqttMetricsSnapshot {
+            connected,
+            messages_received: load(&self.messages_received),
+            bytes_received: load(&self.bytes_received),
+            messages_oversized: load(&self.messages_oversized),
+            messages_published: load(&self.messages_published),
+            bytes_published: load(&self.bytes_published),
+            publish_failures: load(&self.publish_failures),
+            reconnects: load(&self.reconnects),
+            backoff: Duration::from_millis(load(&self.backoff_ms)),
+            events_dropped,
+            offline_queued: 0,
+            offline_overflowed: 0,
+            offline_expired: 0,
+            topics: self
+                .topics
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(topic, traffic)| (topic.clone(), *traffic))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topics_past_the_limit_are_counted_together() {
+        let metrics = MqttMetrics::default();
+        for i in 0..MAX_TRACKED_TOPICS {
+            metrics.received(&format!("roaster/dev{i}/telemetry"), 10);
+        }
+        metrics.received("roaster/late/telemetry", 7);
+        metrics.received("roaster/later/telemetry", 3);
+        metrics.published("roaster/dev0/telemetry", 5);
+        metrics.set_backoff(Duration::from_secs(4));
+
+        let snapshot = metrics.snapshot(false, 0);
+        assert_eq!(snapshot.messages_received, MAX_TRACKED_TOPICS as u64 + 2);
+        assert_eq!(snapshot.topics.len(), MAX_TRACKED_TOPICS + 1);
+        assert_eq!(snapshot.topics[OTHER_TOPICS].bytes_received, 10);
+        let dev0 = snapshot.topics["roaster/dev0/telemetry"];
+        assert_eq!((dev0.messages_published, dev0.bytes_published), (1, 5));
+        assert_eq!(snapshot.backoff, Duration::from_secs(4));
+    }
+}
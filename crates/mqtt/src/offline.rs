@@ -0,0 +1,171 @@
+//! Publishes held while the broker is unreachable. Rather than failing
+//! right away, a publish made while disconnected waits here (up to
+//! `offline_queue` of them, the oldest dropped first) and is sent in order
+//! once the client reconnects, unless it is older than `offline_ttl` by
+//! then: a setpoint from a minute ago is better dropped than applied.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rumqttc::QoS;
+
+use crate::protocol::UserProperties;
+
+/// Default time a held publish stays worth sending.
+pub const DEFAULT_OFFLINE_TTL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub(crate) struct HeldPublish {
+    pub topic: String,
+    pub qos: QoS,
+    pub retain: bool,
+    pub payload: Vec<u8>,
+    pub user_properties: UserProperties,
+    pub held_at: Instant,
+}
+
+#[derive(Default)]
+struct Held {
+    publishes: VecDeque<HeldPublish>,
+    /// Set from the start of a flush until the queue is empty, so new
+    /// publishes line up behind the held ones.
+    flushing: bool,
+}
+
+pub(crate) struct OfflineQueue {
+    capacity: usize,
+    ttl: Duration,
+    held: Mutex<Held>,
+    /// Held publishes dropped to make room for newer ones.
+    overflowed: AtomicU64,
+    /// Held publishes dropped for being older than the TTL.
+    expired: AtomicU64,
+}
+
+impl OfflineQueue {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            held: Mutex::default(),
+            overflowed: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+        }
+    }
+
+    /// A queue that holds nothing.
+    pub fn disabled() -> Self {
+        Self::new(0, DEFAULT_OFFLINE_TTL)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Hold `publish` when it has to wait: the client is disconnected, or
+    /// earlier publishes are still waiting. Otherwise it is handed back to
+    /// be sent now.
+    pub fn try_hold(&self, connected: bool, publish: HeldPublish) -> Result<(), HeldPublish> {
+        if self.capacity == 0 {
+            return Err(publish);
+        }
+        let mut held = self.held.lock().unwrap();
+        if connected && !held.flushing && held.publishes.is_empty() {
+            return Err(publish);
+        }
+        if held.publishes.len() >= self.capacity {
+            held.publishes.pop_front();
+            self.overflowed.fetch_add(1, Ordering::Relaxed);
+        }
+        held.publishes.push_back(publish);
+        Ok(())
+    }
+
+    /// The oldest publish still worth sending, marking the queue as being
+    /// flushed. `None` once it is empty, which ends the flush.
+    pub fn next(&self) -> Option<HeldPublish> {
+        let mut held = self.held.lock().unwrap();
+        let now = Instant::now();
+        while let Some(publish) = held.publishes.pop_front() {
+            if now.duration_since(publish.held_at) < self.ttl {
+                held.flushing = true;
+                return Some(publish);
+            }
+            self.expired.fetch_add(1, Ordering::Relaxed);
+        }
+        held.flushing = false;
+        None
+    }
+
+    /// Put back a publish the client refused mid-flush and stop flushing
+    /// until the next reconnect.
+    pub fn requeue(&self, publish: HeldPublish) {
+        let mut held = self.held.lock().unwrap();
+        held.publishes.push_front(publish);
+        held.flushing = false;
+    }
+
+    pub fn depth(&self) -> usize {
+        self.held.lock().unwrap().publishes.len()
+    }
+
+    pub fn overflowed(&self) -> u64 {
+        self.overflowed.load(Ordering::Relaxed)
+    }
+
+    pub fn expired(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish(topic: &str) -> HeldPublish {
+        HeldPublish {
+            topic: topic.to_string(),
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            payload: Vec::new(),
+            user_properties: Vec::new(),
+            held_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_holds_the_newest_publishes_until_they_expire() {
+        let disabled = OfflineQueue::disabled();
+        assert!(disabled.try_hold(false, publish("a")).is_err());
+
+        let queue = OfflineQueue::new(2, Duration::from_millis(50));
+        assert!(queue
+            .try_hold(true, publish("roaster/dev1/telemetry"))
+            .is_err());
+        for i in 1..=3 {
+            let setpoint = publish(&format!("roaster/dev1/control/setpoint/{i}"));
+            queue.try_hold(false, setpoint).unwrap();
+        }
+        assert_eq!((queue.depth(), queue.overflowed()), (2, 1));
+
+        let first = queue.next().unwrap();
+        assert_eq!(first.topic, "roaster/dev1/control/setpoint/2");
+        queue.requeue(first);
+        assert_eq!(
+            queue.next().unwrap().topic,
+            "roaster/dev1/control/setpoint/2"
+        );
+        // Connected again, but newer publishes wait behind the held ones
+        queue
+            .try_hold(true, publish("roaster/dev1/control/fan_pwm"))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(queue.next().is_none());
+        assert_eq!(queue.expired(), 2);
+        assert!(queue
+            .try_hold(true, publish("roaster/dev1/telemetry"))
+            .is_err());
+    }
+}
@@ -0,0 +1,321 @@
+//! The broker connection for either protocol version. rumqttc has separate
+//! clients for MQTT 3.1.1 and MQTT 5; these wrap both behind the calls the
+//! service makes and the events its loop handles.
+
+use rumqttc::v5::mqttbytes::v5::{
+    Packet, PubAck, PubAckReason, PubRec, PubRecReason, PublishProperties,
+};
+use rumqttc::v5::mqttbytes::QoS as QoSV5;
+use rumqttc::{
+    AsyncClient, ClientError, Disconnect, Event, EventLoop, Incoming, Outgoing, Publish, QoS,
+    Request, Subscribe, Unsubscribe,
+};
+use std::time::SystemTime;
+
+use tracing::debug;
+
+use crate::ack::{AckOutcome, PublishRejection};
+use crate::client::MqttEvent;
+
+/// Key/value pairs attached to an MQTT 5 publish, e.g. a request id. MQTT
+/// 3.1.1 has no properties and drops them.
+pub type UserProperties = Vec<(String, String)>;
+
+pub(crate) enum BrokerClient {
+    V311(AsyncClient),
+    V5(rumqttc::v5::AsyncClient),
+}
+
+impl BrokerClient {
+    pub async fn publish(
+        &self,
+        topic: String,
+        qos: QoS,
+        retain: bool,
+        payload: Vec<u8>,
+        user_properties: UserProperties,
+    ) -> Result<(), ClientError> {
+        match self {
+            BrokerClient::V311(client) => client.publish(topic, qos, retain, payload).await,
+            BrokerClient::V5(client) => {
+                let properties = PublishProperties {
+                    user_properties,
+                    ..Default::default()
+                };
+                client
+                    .publish_with_properties(
+                        topic.clone(),
+                        qos_v5(qos),
+                        retain,
+                        payload,
+                        properties,
+                    )
+                    .await
+                    .map_err(|_| {
+                        ClientError::Request(Request::Publish(Publish::new(topic, qos, Vec::new())))
+                    })
+            }
+        }
+    }
+
+    pub async fn subscribe(&self, topic: &str, qos: QoS) -> Result<(), ClientError> {
+        match self {
+            BrokerClient::V311(client) => client.subscribe(topic, qos).await,
+            BrokerClient::V5(client) => client
+                .subscribe(topic, qos_v5(qos))
+                .await
+                .map_err(|_| ClientError::Request(Request::Subscribe(Subscribe::new(topic, qos)))),
+        }
+    }
+
+    pub async fn unsubscribe(&self, topic: &str) -> Result<(), ClientError> {
+        match self {
+            BrokerClient::V311(client) => client.unsubscribe(topic).await,
+            BrokerClient::V5(client) => client
+                .unsubscribe(topic)
+                .await
+                .map_err(|_| ClientError::Request(Request::Unsubscribe(Unsubscribe::new(topic)))),
+        }
+    }
+
+    pub async fn disconnect(&self) -> Result<(), ClientError> {
+        match self {
+            BrokerClient::V311(client) => client.disconnect().await,
+            BrokerClient::V5(client) => client
+                .disconnect()
+                .await
+                .map_err(|_| ClientError::Request(Request::Disconnect(Disconnect))),
+        }
+    }
+}
+
+pub(crate) enum BrokerEventLoop {
+    // Boxed, as both event loops are large and far apart in size
+    V311(Box<EventLoop>),
+    V5(Box<rumqttc::v5::EventLoop>),
+}
+
+#[derive(Debug)]
+pub(crate) enum ConnectionError {
+    V311(rumqttc::ConnectionError),
+    V5(rumqttc::v5::ConnectionError),
+}
+
+impl std::fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionError::V311(e) => e.fmt(f),
+            ConnectionError::V5(e) => e.fmt(f),
+        }
+    }
+}
+
+/// What the service's event loop reacts to, from either protocol.
+pub(crate) enum BrokerEvent {
+    Connected,
+    /// An [`MqttEvent::Publish`], stamped as it was read off the connection.
+    Publish(MqttEvent),
+    /// A publish was written to the connection with this packet id.
+    Sent(u16),
+    /// The broker acked a QoS 1 publish, or refused it (MQTT 5).
+    PubAck(u16, AckOutcome),
+    /// The broker refused a QoS 2 publish in its PubRec (MQTT 5).
+    PubRecRejected(u16, PublishRejection),
+    /// A QoS 2 publish completed.
+    PubComp(u16),
+    Disconnecting,
+    /// Any other packet from the broker, pings included.
+    Activity,
+}
+
+impl BrokerEvent {
+    /// Whether this came from the broker, rather than being sent to it.
+    pub fn is_incoming(&self) -> bool {
+        !matches!(self, BrokerEvent::Sent(_) | BrokerEvent::Disconnecting)
+    }
+}
+
+impl BrokerEventLoop {
+    /// The next event, or `None` for traffic the service ignores.
+    pub async fn poll(&mut self) -> Result<Option<BrokerEvent>, ConnectionError> {
+        match self {
+            BrokerEventLoop::V311(eventloop) => {
+                let event = eventloop.poll().await.map_err(ConnectionError::V311)?;
+                Ok(match event {
+                    Event::Incoming(Incoming::ConnAck(_)) => Some(BrokerEvent::Connected),
+                    Event::Incoming(Incoming::Publish(p)) => {
+                        Some(BrokerEvent::Publish(MqttEvent::Publish {
+                            topic: p.topic,
+                            payload: p.payload.to_vec(),
+                            qos: p.qos,
+                            retain: p.retain,
+                            dup: p.dup,
+                            received_at: SystemTime::now(),
+                        }))
+                    }
+                    Event::Incoming(Incoming::PubAck(ack)) => {
+                        Some(BrokerEvent::PubAck(ack.pkid, Ok(())))
+                    }
+                    Event::Incoming(Incoming::PubComp(comp)) => {
+                        Some(BrokerEvent::PubComp(comp.pkid))
+                    }
+                    Event::Outgoing(Outgoing::Publish(pkid)) => Some(BrokerEvent::Sent(pkid)),
+                    Event::Outgoing(Outgoing::Disconnect) => Some(BrokerEvent::Disconnecting),
+                    Event::Incoming(other) => {
+                        debug!(?other, "MQTT event");
+                        Some(BrokerEvent::Activity)
+                    }
+                    other => {
+                        debug!(?other, "MQTT event");
+                        None
+                    }
+                })
+            }
+            BrokerEventLoop::V5(eventloop) => {
+                let event = eventloop.poll().await.map_err(ConnectionError::V5)?;
+                Ok(match event {
+                    rumqttc::v5::Event::Incoming(Packet::ConnAck(_)) => {
+                        Some(BrokerEvent::Connected)
+                    }
+                    rumqttc::v5::Event::Incoming(Packet::Publish(p)) => {
+                        Some(BrokerEvent::Publish(MqttEvent::Publish {
+                            topic: String::from_utf8_lossy(&p.topic).into_owned(),
+                            payload: p.payload.to_vec(),
+                            qos: qos_from_v5(p.qos),
+                            retain: p.retain,
+                            dup: p.dup,
+                            received_at: SystemTime::now(),
+                        }))
+                    }
+                    rumqttc::v5::Event::Incoming(Packet::PubAck(ack)) => {
+                        Some(BrokerEvent::PubAck(ack.pkid, puback_outcome(&ack)))
+                    }
+                    rumqttc::v5::Event::Incoming(Packet::PubRec(rec)) => {
+                        Some(match pubrec_outcome(&rec) {
+                            Ok(()) => BrokerEvent::Activity,
+                            Err(rejection) => BrokerEvent::PubRecRejected(rec.pkid, rejection),
+                        })
+                    }
+                    rumqttc::v5::Event::Incoming(Packet::PubComp(comp)) => {
+                        Some(BrokerEvent::PubComp(comp.pkid))
+                    }
+                    rumqttc::v5::Event::Outgoing(Outgoing::Publish(pkid)) => {
+                        Some(BrokerEvent::Sent(pkid))
+                    }
+                    rumqttc::v5::Event::Outgoing(Outgoing::Disconnect) => {
+                        Some(BrokerEvent::Disconnecting)
+                    }
+                    rumqttc::v5::Event::Incoming(other) => {
+                        debug!(?other, "MQTT event");
+                        Some(BrokerEvent::Activity)
+                    }
+                    other => {
+                        debug!(?other, "MQTT event");
+                        None
+                    }
+                })
+            }
+        }
+    }
+}
+
+pub(crate) fn qos_v5(qos: QoS) -> QoSV5 {
+    match qos {
+        QoS::AtMostOnce => QoSV5::AtMostOnce,
+        QoS::AtLeastOnce => QoSV5::AtLeastOnce,
+        QoS::ExactlyOnce => QoSV5::ExactlyOnce,
+    }
+}
+
+fn qos_from_v5(qos: QoSV5) -> QoS {
+    match qos {
+        QoSV5::AtMostOnce => QoS::AtMostOnce,
+        QoSV5::AtLeastOnce => QoS::AtLeastOnce,
+        QoSV5::ExactlyOnce => QoS::ExactlyOnce,
+    }
+}
+
+/// Reason codes from 0x80 up refuse the publish.
+fn outcome(reason_code: u8, reason: String, reason_string: Option<String>) -> AckOutcome {
+    if reason_code < 0x80 {
+        return Ok(());
+    }
+    Err(PublishRejection {
+        reason_code,
+        reason,
+        reason_string,
+    })
+}
+
+fn puback_outcome(ack: &PubAck) -> AckOutcome {
+    let code = match ack.reason {
+        PubAckReason::Success => 0x00,
+        PubAckReason::NoMatchingSubscribers => 0x10,
+        PubAckReason::UnspecifiedError => 0x80,
+        PubAckReason::ImplementationSpecificError => 0x83,
+        PubAckReason::NotAuthorized => 0x87,
+        PubAckReason::TopicNameInvalid => 0x90,
+        PubAckReason::PacketIdentifierInUse => 0x91,
+        PubAckReason::QuotaExceeded => 0x97,
+        PubAckReason::PayloadFormatInvalid => 0x99,
+    };
+    let detail = ack
+        .properties
+        .as_ref()
+        .and_then(|p| p.reason_string.clone());
+    outcome(code, format!("{:?}", ack.reason), detail)
+}
+
+fn pubrec_outcome(rec: &PubRec) -> AckOutcome {
+    let code = match rec.reason {
+        PubRecReason::Success => 0x00,
+        PubRecReason::NoMatchingSubscribers => 0x10,
+        PubRecReason::UnspecifiedError => 0x80,
+        PubRecReason::ImplementationSpecificError => 0x83,
+        PubRecReason::NotAuthorized => 0x87,
+        PubRecReason::TopicNameInvalid => 0x90,
+        PubRecReason::PacketIdentifierInUse => 0x91,
+        PubRecReason::QuotaExceeded => 0x97,
+        PubRecReason::PayloadFormatInvalid => 0x99,
+    };
+    let detail = rec
+        .properties
+        .as_ref()
+        .and_then(|p| p.reason_string.clone());
+    outcome(code, format!("{:?}", rec.reason), detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rumqttc::v5::mqttbytes::v5::PubAckProperties;
+
+    #[test]
+    fn test_puback_reason_codes() {
+        let ack = |reason, reason_string: Option<&str>| PubAck {
+            pkid: 4,
+            reason,
+            properties: reason_string.map(|s| PubAckProperties {
+                reason_string: Some(s.to_string()),
+                user_properties: Vec::new(),
+            }),
+        };
+        assert_eq!(puback_outcome(&ack(PubAckReason::Success, None)), Ok(()));
+        assert_eq!(
+            puback_outcome(&ack(PubAckReason::NoMatchingSubscribers, None)),
+            Ok(())
+        );
+        assert_eq!(
+            puback_outcome(&ack(PubAckReason::NotAuthorized, Some("acl"))),
+            Err(PublishRejection {
+                reason_code: 0x87,
+                reason: "NotAuthorized".to_string(),
+                reason_string: Some("acl".to_string()),
+            })
+        );
+        assert_eq!(
+            puback_outcome(&ack(PubAckReason::QuotaExceeded, None)).map_err(|r| r.reason_code),
+            Err(0x97)
+        );
+    }
+}
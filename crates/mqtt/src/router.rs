@@ -0,0 +1,228 @@
+use std::future::Future;
+
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::client::{MqttEvent, MqttService};
+use crate::events::{EventReceiver, EventRecvError};
+
+/// An incoming publish that matched a topic filter, with the levels the
+/// filter's wildcards matched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicMessage {
+    pub topic: String,
+    /// One entry per `+` in the filter, in order, then the levels matched
+    /// by a trailing `#` joined with `/` (empty when it matched none).
+    pub captures: Vec<String>,
+    pub payload: Vec<u8>,
+    /// A retained message delivered on subscribing.
+    pub retain: bool,
+}
+
+impl TopicMessage {
+    pub fn capture(&self, index: usize) -> Option<&str> {
+        self.captures.get(index).map(String::as_str)
+    }
+}
+
+/// Prefix of a shared subscription filter, `$share/{group}/{filter}`.
+const SHARE_PREFIX: &str = "$share/";
+
+/// `filter` as a shared subscription of `group`: the broker hands each
+/// matching publish to one subscriber of the group instead of all of them.
+pub fn shared_filter(group: &str, filter: &str) -> String {
+    format!("{}{}/{}", SHARE_PREFIX, group, filter)
+}
+
+/// The topic filter of a shared subscription, or `filter` itself when it
+/// isn't one. Publishes arrive on their own topic, without the prefix.
+pub fn unshared_filter(filter: &str) -> &str {
+    filter
+        .strip_prefix(SHARE_PREFIX)
+        .and_then(|rest| rest.split_once('/'))
+        .map_or(filter, |(_, filter)| filter)
+}
+
+/// Levels of `topic` matched by the `+` and `#` wildcards of `filter`, or
+/// `None` when the topic doesn't match. A shared subscription filter
+/// matches as its topic filter.
+pub fn topic_captures(filter: &str, topic: &str) -> Option<Vec<String>> {
+    let filter = unshared_filter(filter);
+    let mut captures = Vec::new();
+    let mut levels = topic.split('/');
+    let mut rest = topic;
+    for part in filter.split('/') {
+        if part == "#" {
+            captures.push(rest.to_string());
+            return Some(captures);
+        }
+        let level = levels.next()?;
+        match part {
+            "+" => captures.push(level.to_string()),
+            p if p == level => {}
+            _ => return None,
+        }
+        rest = rest.get(level.len() + 1..).unwrap_or("");
+    }
+    levels.next().is_none().then_some(captures)
+}
+
+/// Incoming publishes matching one topic filter, for loops that select over
+/// several sources. Receiving is cancel safe.
+pub struct TopicSubscription {
+    filter: String,
+    rx: EventReceiver,
+}
+
+impl TopicSubscription {
+    /// The next matching publish, or `None` once the service is gone.
+    /// Publishes missed while lagging behind are skipped.
+    pub async fn recv(&mut self) -> Option<TopicMessage> {
+        loop {
+            match self.rx.recv().await {
+                Ok(MqttEvent::Publish {
+                    topic,
+                    payload,
+                    retain,
+                    ..
+                }) => {
+                    if let Some(captures) = topic_captures(&self.filter, &topic) {
+                        return Some(TopicMessage {
+                            topic,
+                            captures,
+                            payload,
+                            retain,
+                        });
+                    }
+                }
+                Ok(_) => {}
+                Err(EventRecvError::Lagged(skipped)) => {
+                    warn!(filter = %self.filter, skipped, "Topic subscription lagged behind");
+                }
+                Err(EventRecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl MqttService {
+    /// Incoming publishes matching `filter` (`+` and `#` wildcards). This
+    /// only routes what the service receives; subscribe at the broker with
+    /// [`subscribe`](Self::subscribe).
+    pub fn topic_messages(&self, filter: &str) -> TopicSubscription {
+        TopicSubscription {
+            filter: filter.to_string(),
+            rx: self.events(),
+        }
+    }
+
+    /// Run `handler` for every incoming publish matching `filter`, one at a
+    /// time and in arrival order, until the service is dropped or the
+    /// returned task is aborted.
+    pub fn on_topic<F, Fut>(&self, filter: &str, handler: F) -> JoinHandle<()>
+    where
+        F: Fn(TopicMessage) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut messages = self.topic_messages(filter);
+        tokio::spawn(async move {
+            while let Some(message) = messages.recv().await {
+                handler(message).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rumqttc::QoS;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_topic_captures() {
+        assert_eq!(
+            topic_captures("roaster/+/telemetry", "roaster/dev1/telemetry"),
+            Some(vec!["dev1".to_string()])
+        );
+        assert_eq!(
+            topic_captures("roaster/+/#", "roaster/dev1/autotune/results"),
+            Some(vec!["dev1".to_string(), "autotune/results".to_string()])
+        );
+        assert_eq!(
+            topic_captures("roaster/+/#", "roaster/dev1"),
+            Some(vec!["dev1".to_string(), String::new()])
+        );
+        assert_eq!(
+            topic_captures("rustroast/cluster/heartbeat", "rustroast/cluster/heartbeat"),
+            Some(vec![])
+        );
+        assert_eq!(
+            topic_captures("roaster/+/telemetry", "roaster/dev1/status"),
+            None
+        );
+        assert_eq!(topic_captures("roaster/+", "roaster/dev1/status"), None);
+        assert_eq!(topic_captures("roaster/+/status", "roaster/dev1"), None);
+        let shared = shared_filter("rustroast", "roaster/+/telemetry");
+        assert_eq!(shared, "$share/rustroast/roaster/+/telemetry");
+        assert_eq!(unshared_filter(&shared), "roaster/+/telemetry");
+        assert_eq!(
+            topic_captures(&shared, "roaster/dev1/telemetry"),
+            Some(vec!["dev1".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_on_topic_dispatches_matching_publishes() {
+        let (mqtt, _published) = MqttService::mock();
+        let (tx, mut handled) = mpsc::unbounded_channel();
+        let _route = mqtt.on_topic("roaster/+/autotune/+", move |msg| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send((
+                    msg.capture(0).unwrap().to_string(),
+                    msg.capture(1).unwrap().to_string(),
+                    msg.payload,
+                ));
+            }
+        });
+        mqtt.subscribe("roaster/#", QoS::AtMostOnce).await.unwrap();
+
+        mqtt.publish("roaster/dev1/telemetry", QoS::AtMostOnce, false, "{}")
+            .await
+            .unwrap();
+        mqtt.publish(
+            "roaster/dev2/autotune/results",
+            QoS::AtMostOnce,
+            false,
+            "{}",
+        )
+        .await
+        .unwrap();
+        mqtt.inject("roaster/dev1/autotune/status", "running");
+
+        let first = tokio::time::timeout(Duration::from_secs(1), handled.recv())
+            .await
+            .unwrap();
+        assert_eq!(
+            first,
+            Some(("dev2".to_string(), "results".to_string(), b"{}".to_vec()))
+        );
+        let second = handled.recv().await.unwrap();
+        assert_eq!(second.0, "dev1");
+        assert_eq!(second.2, b"running");
+        assert!(handled.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_topic_messages_flag_retained_deliveries() {
+        let (mqtt, _published) = MqttService::mock();
+        let mut results = mqtt.topic_messages("roaster/+/autotune/results");
+        mqtt.inject_retained("roaster/dev1/autotune/results", "{}");
+        mqtt.inject("roaster/dev1/autotune/results", "{}");
+
+        assert!(results.recv().await.unwrap().retain);
+        assert!(!results.recv().await.unwrap().retain);
+    }
+}
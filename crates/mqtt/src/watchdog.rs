@@ -0,0 +1,100 @@
+//! Broker silence detection. A half-open connection (a NAT entry that
+//! expired, a broker that hung) can take rumqttc several keep-alives and a
+//! TCP timeout to notice. Every packet from the broker, pings included, is
+//! noted here; once nothing has arrived for `stale_keepalives` keep-alive
+//! intervals, the service reports [`MqttEvent::Stale`] and stops counting
+//! as ready until the broker is heard from again.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::client::MqttEvent;
+use crate::events::EventBus;
+
+pub(crate) struct Watchdog {
+    epoch: Instant,
+    /// Milliseconds after `epoch` the broker was last heard from.
+    last_heard: AtomicU64,
+    stale: AtomicBool,
+}
+
+impl Watchdog {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            epoch: now,
+            last_heard: AtomicU64::new(0),
+            stale: AtomicBool::new(false),
+        }
+    }
+
+    /// Note a packet from the broker. Returns whether the connection was
+    /// stale until now.
+    pub fn heard(&self, now: Instant) -> bool {
+        let ms = now.saturating_duration_since(self.epoch).as_millis() as u64;
+        self.last_heard.store(ms, Ordering::Relaxed);
+        self.stale.swap(false, Ordering::Relaxed)
+    }
+
+    /// How long the broker has been silent, the first time that reaches
+    /// `stale_after`; `None` while it is heard from, or already stale.
+    pub fn check(&self, now: Instant, stale_after: Duration) -> Option<Duration> {
+        let heard = self.epoch + Duration::from_millis(self.last_heard.load(Ordering::Relaxed));
+        let silent = now.saturating_duration_since(heard);
+        if silent < stale_after || self.stale.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        Some(silent)
+    }
+}
+
+/// Check for silence a few times per `stale_after` while the service is
+/// connected. Whatever is heard next clears it, from the event loop.
+pub(crate) async fn run(
+    watchdog: Arc<Watchdog>,
+    stale_after: Duration,
+    ready: Arc<AtomicBool>,
+    events: EventBus,
+) {
+    let mut ticker = tokio::time::interval((stale_after / 4).max(Duration::from_millis(100)));
+    loop {
+        ticker.tick().await;
+        if !ready.load(Ordering::Relaxed) {
+            continue;
+        }
+        if let Some(silent_for) = watchdog.check(Instant::now(), stale_after) {
+            warn!(
+                ?silent_for,
+                "Nothing heard from the MQTT broker; connection is stale"
+            );
+            ready.store(false, Ordering::Relaxed);
+            events.send(MqttEvent::Stale { silent_for });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_once_until_heard_again() {
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+        let stale_after = Duration::from_secs(45);
+        let watchdog = Watchdog::new(t0);
+        assert!(!watchdog.heard(at(10)));
+        assert_eq!(watchdog.check(at(54), stale_after), None);
+        assert_eq!(
+            watchdog.check(at(55), stale_after),
+            Some(Duration::from_secs(45))
+        );
+        assert_eq!(watchdog.check(at(70), stale_after), None);
+
+        assert!(watchdog.heard(at(71)));
+        assert!(!watchdog.heard(at(72)));
+        assert_eq!(watchdog.check(at(100), stale_after), None);
+    }
+}
@@ -0,0 +1,8 @@
+-- Migration: 008_session_signoff.sql
+-- Roaster (person) attribution and QA sign-off for roast sessions.
+-- A session with signed_off_at set is locked against edits except by admins.
+
+ALTER TABLE roast_sessions ADD COLUMN roaster TEXT;
+ALTER TABLE roast_sessions ADD COLUMN signed_off_by TEXT;
+ALTER TABLE roast_sessions ADD COLUMN signed_off_at DATETIME;
+CREATE INDEX IF NOT EXISTS idx_roast_sessions_roaster ON roast_sessions(roaster);
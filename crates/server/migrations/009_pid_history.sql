@@ -0,0 +1,18 @@
+-- Migration: 009_pid_history.sql
+-- History of PID gains applied to each roaster, with provenance.
+-- source: 'manual' (control API), 'autotune' (applied autotune result),
+-- 'copy' (copied from another roaster, see source_device_id / scale).
+
+CREATE TABLE IF NOT EXISTS pid_history (
+    id TEXT PRIMARY KEY,
+    device_id TEXT NOT NULL,
+    kp REAL NOT NULL,
+    ki REAL NOT NULL,
+    kd REAL NOT NULL,
+    source TEXT NOT NULL,
+    source_device_id TEXT,
+    scale REAL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_pid_history_device_created ON pid_history(device_id, created_at DESC);
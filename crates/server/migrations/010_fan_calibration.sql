@@ -0,0 +1,23 @@
+-- Migration: 010_fan_calibration.sql
+-- Per-device fan PWM -> airflow calibration curve, so fan settings are
+-- comparable across machines. Airflow units are user-defined (e.g. m3/h or CFM),
+-- static pressure likewise (e.g. Pa).
+
+CREATE TABLE IF NOT EXISTS fan_calibration_points (
+    id TEXT PRIMARY KEY,
+    device_id TEXT NOT NULL,
+    fan_pwm INTEGER NOT NULL,
+    airflow REAL NOT NULL,
+    static_pressure REAL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (device_id) REFERENCES devices(id) ON DELETE CASCADE,
+    UNIQUE (device_id, fan_pwm)
+);
+
+CREATE INDEX IF NOT EXISTS idx_fan_calibration_device ON fan_calibration_points(device_id, fan_pwm);
+
+-- Derived airflow recorded alongside session telemetry
+ALTER TABLE session_telemetry ADD COLUMN airflow REAL;
+
+-- Target airflow for profile design
+ALTER TABLE profile_points ADD COLUMN target_airflow REAL;
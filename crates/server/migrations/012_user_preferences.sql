@@ -0,0 +1,11 @@
+-- Migration: 012_user_preferences.sql
+-- Per-user dashboard preferences, keyed by the authenticated subject
+-- (user id or API key id). ws_subscriptions is a JSON array of device_ids
+-- (empty = all devices), dashboard_layout is opaque JSON owned by the frontend.
+
+CREATE TABLE IF NOT EXISTS user_preferences (
+    subject TEXT PRIMARY KEY,
+    ws_subscriptions JSON NOT NULL DEFAULT '[]',
+    dashboard_layout JSON,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
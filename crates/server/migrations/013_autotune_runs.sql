@@ -0,0 +1,22 @@
+-- Migration: 013_autotune_runs.sql
+-- One row per autotune start request, so incoming results can be paired with
+-- the request that triggered them.
+-- state: 'running', 'completed', 'failed', 'stopped' or 'expired'.
+-- At most one run per device may be 'running'.
+
+CREATE TABLE IF NOT EXISTS autotune_runs (
+    id TEXT PRIMARY KEY,
+    device_id TEXT NOT NULL,
+    target_temperature REAL NOT NULL,
+    mode TEXT,
+    tuning_method TEXT,
+    requested_by TEXT,
+    state TEXT NOT NULL DEFAULT 'running',
+    last_phase TEXT,
+    results JSON,
+    started_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_autotune_runs_device_started ON autotune_runs(device_id, started_at DESC);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_autotune_runs_one_running ON autotune_runs(device_id) WHERE state = 'running';
@@ -0,0 +1,9 @@
+-- Migration: 014_profile_scoreboard.sql
+-- Final profile-following scoreboard stored with completion stats.
+-- Deltas are actual minus profile target (seconds or degrees C), the
+-- deviation integral is the signed area between bean temp and profile in C*min.
+
+ALTER TABLE roast_sessions ADD COLUMN profile_fc_delta REAL;
+ALTER TABLE roast_sessions ADD COLUMN profile_drop_time_delta REAL;
+ALTER TABLE roast_sessions ADD COLUMN profile_drop_temp_delta REAL;
+ALTER TABLE roast_sessions ADD COLUMN profile_deviation_integral REAL;
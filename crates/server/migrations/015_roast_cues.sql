@@ -0,0 +1,34 @@
+-- Migration: 015_roast_cues.sql
+-- Bench reminders ("check color", "reduce gas") fired during active roasts.
+-- A cue belongs to either a profile (applies to every session using it) or a
+-- single session. trigger_type is 'elapsed' (seconds since start) or
+-- 'temperature' (bean temp in C, fired once reached).
+
+CREATE TABLE IF NOT EXISTS roast_cues (
+    id TEXT PRIMARY KEY,
+    profile_id TEXT,
+    session_id TEXT,
+    message TEXT NOT NULL,
+    trigger_type TEXT NOT NULL,
+    trigger_value REAL NOT NULL,
+    notify BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((profile_id IS NULL) != (session_id IS NULL)),
+    FOREIGN KEY (profile_id) REFERENCES roast_profiles(id) ON DELETE CASCADE,
+    FOREIGN KEY (session_id) REFERENCES roast_sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_roast_cues_profile ON roast_cues(profile_id);
+CREATE INDEX IF NOT EXISTS idx_roast_cues_session ON roast_cues(session_id);
+
+-- Each cue fires at most once per session.
+CREATE TABLE IF NOT EXISTS session_cue_firings (
+    session_id TEXT NOT NULL,
+    cue_id TEXT NOT NULL,
+    elapsed_seconds REAL NOT NULL,
+    bean_temp REAL,
+    fired_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (session_id, cue_id),
+    FOREIGN KEY (session_id) REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    FOREIGN KEY (cue_id) REFERENCES roast_cues(id) ON DELETE CASCADE
+);
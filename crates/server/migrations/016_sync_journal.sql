@@ -0,0 +1,18 @@
+-- Migration: 016_sync_journal.sql
+-- Change journal for offline sync clients. Every write to a session, roast
+-- event or cupping appends a row, and clients pull changes after the last
+-- seq they saw.
+-- entity: 'session', 'event' or 'cupping' (cupping rows use the session id).
+-- op: 'upsert' or 'delete'.
+-- origin: client_id for changes pushed by a sync client, NULL otherwise.
+
+CREATE TABLE IF NOT EXISTS sync_changes (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    entity TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    op TEXT NOT NULL,
+    origin TEXT,
+    changed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_changes_entity ON sync_changes(entity, entity_id, seq);
@@ -0,0 +1,19 @@
+-- Migration: 017_session_defects.sql
+-- Roast defects tagged on a session, optionally tied to a stretch of the curve.
+-- defect: 'scorching', 'tipping', 'underdevelopment', 'baked' or 'other'.
+-- start_seconds and end_seconds are elapsed seconds on the session curve.
+
+CREATE TABLE IF NOT EXISTS session_defects (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    defect TEXT NOT NULL,
+    start_seconds REAL,
+    end_seconds REAL,
+    notes TEXT,
+    tagged_by TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (session_id) REFERENCES roast_sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_defects_session ON session_defects(session_id);
+CREATE INDEX IF NOT EXISTS idx_session_defects_defect ON session_defects(defect);
@@ -0,0 +1,17 @@
+-- Migration: 018_manual_sessions.sql
+-- Session type (profile-driven or fully manual) and an audit log of control
+-- commands sent to each roaster. Heater and fan changes made during an active
+-- manual session are also recorded as session events.
+
+ALTER TABLE roast_sessions ADD COLUMN session_type TEXT NOT NULL DEFAULT 'profile';
+
+CREATE TABLE IF NOT EXISTS control_audit (
+    id TEXT PRIMARY KEY,
+    device_id TEXT NOT NULL,
+    op TEXT NOT NULL,
+    value TEXT NOT NULL,
+    session_id TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_control_audit_device_created ON control_audit(device_id, created_at DESC);
@@ -0,0 +1,47 @@
+-- Migration: 019_automations.sql
+-- Automation rules ("when bean temp crosses 150 rising, set fan 220") attached
+-- to a profile or to a roaster, and a log of each rule execution. A rule runs
+-- at most once per session.
+-- trigger_type is 'bean_temp_rising', 'bean_temp_falling' (trigger_value in C),
+-- 'elapsed' (trigger_value in seconds) or 'event' (trigger_event names a roast
+-- event type). command is 'fan_pwm', 'heater_pwm' or 'setpoint', and relative
+-- rules add value to the current reading instead of setting it.
+-- An execution's success stays NULL while its command is in flight.
+
+CREATE TABLE IF NOT EXISTS automation_rules (
+    id TEXT PRIMARY KEY,
+    profile_id TEXT,
+    device_id TEXT,
+    name TEXT NOT NULL,
+    trigger_type TEXT NOT NULL,
+    trigger_value REAL,
+    trigger_event TEXT,
+    command TEXT NOT NULL,
+    value REAL NOT NULL,
+    relative BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((profile_id IS NULL) != (device_id IS NULL)),
+    FOREIGN KEY (profile_id) REFERENCES roast_profiles(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_automation_rules_profile ON automation_rules(profile_id);
+CREATE INDEX IF NOT EXISTS idx_automation_rules_device ON automation_rules(device_id);
+
+CREATE TABLE IF NOT EXISTS automation_executions (
+    id TEXT PRIMARY KEY,
+    rule_id TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    device_id TEXT NOT NULL,
+    elapsed_seconds REAL NOT NULL,
+    bean_temp REAL,
+    command TEXT NOT NULL,
+    sent_value TEXT,
+    success BOOLEAN,
+    error TEXT,
+    executed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (session_id, rule_id),
+    FOREIGN KEY (rule_id) REFERENCES automation_rules(id) ON DELETE CASCADE,
+    FOREIGN KEY (session_id) REFERENCES roast_sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_automation_executions_session ON automation_executions(session_id, executed_at);
@@ -0,0 +1,25 @@
+-- Migration: 020_profile_batch_scaling.sql
+-- Batch size a profile was tuned for, its heater cap (percent), and profile
+-- versions. A version is a profile whose parent_profile_id points at the
+-- original (root) profile, numbered within that family.
+-- Per-roaster batch scaling rules: charge temp and heater cap move by the
+-- given amount per 100 g of batch difference from the profile's batch size,
+-- the heater cap clamped to [min_heater_cap, max_heater_cap] and the charge
+-- temp to max_charge_temp when set.
+
+ALTER TABLE roast_profiles ADD COLUMN batch_size_g REAL;
+ALTER TABLE roast_profiles ADD COLUMN heater_cap INTEGER;
+ALTER TABLE roast_profiles ADD COLUMN parent_profile_id TEXT REFERENCES roast_profiles(id) ON DELETE SET NULL;
+ALTER TABLE roast_profiles ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS idx_roast_profiles_parent ON roast_profiles(parent_profile_id, version);
+
+CREATE TABLE IF NOT EXISTS batch_scaling_rules (
+    device_id TEXT PRIMARY KEY,
+    charge_temp_per_100g REAL NOT NULL DEFAULT 0,
+    heater_cap_per_100g REAL NOT NULL DEFAULT 0,
+    min_heater_cap INTEGER NOT NULL DEFAULT 0,
+    max_heater_cap INTEGER NOT NULL DEFAULT 100,
+    max_charge_temp REAL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,25 @@
+-- Migration: 021_alerts.sql
+-- Every alert the server raises, with its acknowledgement workflow.
+-- kind is 'device_conflict', 'over_temperature' or 'automation_failed',
+-- severity 'warning' or 'critical'. state moves from 'firing' to
+-- 'acknowledged' (someone is on it) and ends at 'resolved', either when the
+-- condition clears (resolved_by NULL) or by hand.
+
+CREATE TABLE IF NOT EXISTS alerts (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    device_id TEXT,
+    session_id TEXT,
+    message TEXT NOT NULL,
+    details TEXT,
+    state TEXT NOT NULL DEFAULT 'firing',
+    fired_at DATETIME NOT NULL,
+    acknowledged_by TEXT,
+    acknowledged_at DATETIME,
+    resolved_by TEXT,
+    resolved_at DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_alerts_state ON alerts(state, fired_at);
+CREATE INDEX IF NOT EXISTS idx_alerts_device ON alerts(device_id, fired_at);
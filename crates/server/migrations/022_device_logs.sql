@@ -0,0 +1,19 @@
+-- Migration: 022_device_logs.sql
+-- Firmware debug logs (roaster/{device_id}/log) are kept in memory only,
+-- except for devices flagged for troubleshooting, whose lines are also
+-- written to device_logs until the flag is removed.
+
+CREATE TABLE IF NOT EXISTS device_log_capture (
+    device_id TEXT PRIMARY KEY,
+    enabled_at DATETIME NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS device_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL,
+    ts INTEGER NOT NULL,
+    level TEXT,
+    message TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_logs_device ON device_logs(device_id, ts);
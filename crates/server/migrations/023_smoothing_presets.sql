@@ -0,0 +1,16 @@
+-- Migration: 023_smoothing_presets.sql
+-- Curve smoothing presets per user (subject) and view, e.g. 'live',
+-- 'replay' or 'compare'. Windows are seconds, 0 leaves BT/ET unsmoothed.
+-- ror_algorithm is 'moving_average', 'weighted_moving_average' or
+-- 'savitzky_golay'. Without a preset the server settings apply.
+
+CREATE TABLE IF NOT EXISTS smoothing_presets (
+    subject TEXT NOT NULL,
+    view TEXT NOT NULL,
+    bt_window_secs REAL NOT NULL DEFAULT 0,
+    et_window_secs REAL NOT NULL DEFAULT 0,
+    ror_window_secs REAL NOT NULL,
+    ror_algorithm TEXT NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (subject, view)
+);
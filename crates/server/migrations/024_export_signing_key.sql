@@ -0,0 +1,10 @@
+-- Migration: 024_export_signing_key.sql
+-- Key for signing session exports, generated on first use unless
+-- RUSTROAST_EXPORT_SIGNING_KEY is set. A single row (id = 1) holding the
+-- key as hex. Replacing it invalidates every earlier export signature.
+
+CREATE TABLE IF NOT EXISTS export_signing_key (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    secret TEXT NOT NULL,
+    created_at DATETIME NOT NULL
+);
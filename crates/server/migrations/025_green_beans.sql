@@ -0,0 +1,22 @@
+-- Migration: 025_green_beans.sql
+-- Green coffee lots roasted in sessions. density is g/L, moisture_pct is
+-- the measured moisture content in percent. process is free text such as
+-- 'washed', 'natural' or 'honey'. Sessions may reference the lot they
+-- roasted, bean_origin/bean_variety stay as free text for older sessions.
+
+CREATE TABLE IF NOT EXISTS beans (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    origin TEXT,
+    variety TEXT,
+    process TEXT,
+    density REAL,
+    moisture_pct REAL,
+    notes TEXT,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+
+ALTER TABLE roast_sessions ADD COLUMN bean_id TEXT REFERENCES beans(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_roast_sessions_bean_id ON roast_sessions(bean_id);
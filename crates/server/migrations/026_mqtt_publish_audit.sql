@@ -0,0 +1,16 @@
+-- Migration: 026_mqtt_publish_audit.sql
+-- Audit log of raw MQTT messages published by admins through
+-- POST /api/admin/mqtt/publish or the debug WebSocket.
+
+CREATE TABLE IF NOT EXISTS mqtt_publish_audit (
+    id TEXT PRIMARY KEY,
+    topic TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    qos INTEGER NOT NULL,
+    retain BOOLEAN NOT NULL,
+    subject TEXT,
+    source TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_mqtt_publish_audit_created ON mqtt_publish_audit(created_at DESC);
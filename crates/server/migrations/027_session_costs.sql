@@ -0,0 +1,5 @@
+-- Migration: 027_session_costs.sql
+-- Purchase price of green bean lots, per kg in the cost currency. The
+-- cost rates themselves are cost_* keys in the settings table.
+
+ALTER TABLE beans ADD COLUMN cost_per_kg REAL;
@@ -0,0 +1,13 @@
+-- Migration: 028_telemetry_rollup.sql
+-- Per-minute rollups of raw telemetry compacted after
+-- RUSTROAST_TELEMETRY_COMPACT_AFTER_DAYS. ts is the start of the minute
+-- (unix seconds), payload holds the mean of each numeric field and the
+-- last value of the others, and samples counts the raw rows averaged.
+
+CREATE TABLE IF NOT EXISTS telemetry_rollup (
+    device_id TEXT NOT NULL,
+    ts INTEGER NOT NULL,
+    samples INTEGER NOT NULL,
+    payload TEXT NOT NULL,
+    PRIMARY KEY (device_id, ts)
+);
@@ -0,0 +1,14 @@
+-- Migration: 029_batch_preheat.sql
+-- Between-batches preheat per roaster. When enabled, completing a session
+-- drives the roaster back to the profile's charge temp and holds it until
+-- the next session starts or timeout_secs pass. max_temp caps the target
+-- and aborts the preheat when exceeded. The bean temp must be within
+-- ready_band of the target to count as ready.
+CREATE TABLE IF NOT EXISTS batch_preheat_settings (
+    device_id TEXT PRIMARY KEY,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    timeout_secs INTEGER NOT NULL DEFAULT 1200,
+    max_temp REAL NOT NULL DEFAULT 230,
+    ready_band REAL NOT NULL DEFAULT 3,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,15 @@
+-- Migration: 030_sample_roasts.sql
+-- Sample roasts for green-buying evaluation. Sample sessions of one bean
+-- are grouped in threes, each on a different profile, and evaluated side
+-- by side.
+CREATE TABLE IF NOT EXISTS sample_groups (
+    id TEXT PRIMARY KEY,
+    bean_id TEXT NOT NULL REFERENCES beans(id) ON DELETE CASCADE,
+    created_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_sample_groups_bean ON sample_groups(bean_id, created_at);
+
+ALTER TABLE roast_sessions ADD COLUMN sample_group_id TEXT REFERENCES sample_groups(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_roast_sessions_sample_group ON roast_sessions(sample_group_id);
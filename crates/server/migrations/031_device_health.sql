@@ -0,0 +1,11 @@
+-- Migration: 031_device_health.sql
+-- Per-minute device health: moving averages of the WiFi RSSI and free heap
+-- reported in telemetry, and the health score derived from them.
+CREATE TABLE IF NOT EXISTS device_health (
+    device_id TEXT NOT NULL,
+    ts INTEGER NOT NULL,
+    rssi REAL,
+    free_heap REAL,
+    score REAL NOT NULL,
+    PRIMARY KEY (device_id, ts)
+);
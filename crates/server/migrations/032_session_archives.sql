@@ -0,0 +1,11 @@
+-- Migration: 032_session_archives.sql
+-- Completed sessions exported to object storage. Once `pruned_at` is set
+-- the session's telemetry only lives in the archive until it is restored.
+CREATE TABLE IF NOT EXISTS session_archives (
+    session_id TEXT PRIMARY KEY REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    object_prefix TEXT NOT NULL,
+    telemetry_points INTEGER NOT NULL,
+    archived_at DATETIME NOT NULL,
+    pruned_at DATETIME,
+    restored_at DATETIME
+);
@@ -0,0 +1,11 @@
+-- Migration: 034_roaster_ror_settings.sql
+-- RoR window and unit per roaster. A fluid-bed roaster reacts in seconds
+-- and reads best over a short window in °C/30s, while a drum wants a longer
+-- one in °C/min. They override the RoR window of the caller's smoothing and
+-- the scoreboard's RoR window for sessions on that roaster.
+CREATE TABLE IF NOT EXISTS roaster_ror_settings (
+    device_id TEXT PRIMARY KEY,
+    window_secs REAL NOT NULL DEFAULT 30,
+    unit TEXT NOT NULL DEFAULT 'per_minute',
+    updated_at TEXT NOT NULL
+);
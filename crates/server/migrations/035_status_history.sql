@@ -0,0 +1,17 @@
+-- Migration: 035_status_history.sql
+-- Device status snapshots from roaster/{device_id}/status. A row is
+-- written when the status, chip id, IP or firmware version changes
+-- (changed = 1) and otherwise at most once a minute.
+CREATE TABLE IF NOT EXISTS status_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL,
+    ts INTEGER NOT NULL,
+    status TEXT,
+    ip TEXT,
+    version TEXT,
+    rssi INTEGER,
+    changed INTEGER NOT NULL DEFAULT 0,
+    payload TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_status_history_device_ts ON status_history(device_id, ts);
@@ -0,0 +1,13 @@
+-- Migration: 036_roast_queue.sql
+-- Production queue of planned batches. Each entry is a session moving
+-- through 'queued', 'preheating', 'roasting', 'cooling' and 'done'.
+-- Position orders the board, lowest first.
+CREATE TABLE IF NOT EXISTS roast_queue (
+    session_id TEXT PRIMARY KEY REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    state TEXT NOT NULL DEFAULT 'queued',
+    state_changed_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_roast_queue_state ON roast_queue(state, position);
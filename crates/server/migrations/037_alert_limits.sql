@@ -0,0 +1,8 @@
+-- Migration: 037_alert_limits.sql
+-- Per-roaster over-temperature alert limit, overriding the server-wide
+-- RUSTROAST_ALERT_MAX_BEAN_TEMP for that roaster.
+CREATE TABLE IF NOT EXISTS alert_limits (
+    device_id TEXT PRIMARY KEY,
+    max_bean_temp REAL NOT NULL,
+    updated_at DATETIME NOT NULL
+);
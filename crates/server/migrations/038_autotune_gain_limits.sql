@@ -0,0 +1,10 @@
+-- Migration: 038_autotune_gain_limits.sql
+-- Per-roaster upper bounds on autotuned PID gains, overriding the server
+-- defaults when deciding whether results are safe to apply.
+CREATE TABLE IF NOT EXISTS autotune_gain_limits (
+    device_id TEXT PRIMARY KEY,
+    max_kp REAL NOT NULL,
+    max_ki REAL NOT NULL,
+    max_kd REAL NOT NULL,
+    updated_at DATETIME NOT NULL
+);
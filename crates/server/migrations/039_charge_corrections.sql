@@ -0,0 +1,24 @@
+-- Migration: 039_charge_corrections.sql
+-- Correction tables for charge temp and heater cap by green bean moisture
+-- and density. Each row is a band [min_value, max_value) of one factor, open
+-- at a NULL end. A bean gets the deltas of the band it falls into for each
+-- factor. The adjustment applied to a session is kept for evaluating the
+-- tables against how the roast went.
+CREATE TABLE IF NOT EXISTS charge_corrections (
+    factor TEXT NOT NULL,
+    min_value REAL,
+    max_value REAL,
+    charge_temp_delta REAL NOT NULL DEFAULT 0,
+    heater_cap_delta REAL NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS session_charge_adjustments (
+    session_id TEXT PRIMARY KEY REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    profile_id TEXT NOT NULL,
+    moisture_pct REAL,
+    density REAL,
+    charge_temp_delta REAL NOT NULL,
+    heater_cap_delta REAL NOT NULL,
+    adjustment TEXT NOT NULL,
+    created_at DATETIME NOT NULL
+);
@@ -0,0 +1,13 @@
+-- Migration: 040_profile_tolerances.sql
+-- Tolerance band of a profile: how far (°C) the bean temp may stray from
+-- the profile curve and for how long before the roast counts as off
+-- profile, and what to do then (alert only, pause the session, or send a
+-- safe setpoint).
+CREATE TABLE IF NOT EXISTS profile_tolerances (
+    profile_id TEXT PRIMARY KEY REFERENCES roast_profiles(id) ON DELETE CASCADE,
+    max_deviation REAL NOT NULL,
+    max_outside_secs REAL NOT NULL,
+    action TEXT NOT NULL DEFAULT 'alert',
+    safe_setpoint REAL,
+    updated_at DATETIME NOT NULL
+);
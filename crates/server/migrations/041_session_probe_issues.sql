@@ -0,0 +1,16 @@
+-- Migration: 041_session_probe_issues.sql
+-- Temperature probe faults seen during a session: a stuck (flatlined)
+-- reading or probes disagreeing implausibly. A session with any of them has
+-- suspect telemetry.
+CREATE TABLE IF NOT EXISTS session_probe_issues (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    device_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    probes TEXT NOT NULL,
+    value REAL NOT NULL,
+    elapsed_seconds REAL,
+    detected_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_probe_issues_session ON session_probe_issues(session_id);
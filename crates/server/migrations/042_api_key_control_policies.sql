@@ -0,0 +1,11 @@
+-- Migration: 042_api_key_control_policies.sql
+-- Per API key limits on control commands: how far the setpoint and heater
+-- PWM may move within a minute and which ops are refused outright.
+-- banned_ops: JSON array of op names, e.g. ["emergency_stop", "pid"]
+CREATE TABLE IF NOT EXISTS api_key_control_policies (
+    api_key_id TEXT PRIMARY KEY REFERENCES api_keys(id) ON DELETE CASCADE,
+    max_setpoint_delta_per_min REAL,
+    max_heater_pwm_delta_per_min REAL,
+    banned_ops TEXT NOT NULL DEFAULT '[]',
+    updated_at DATETIME NOT NULL
+);
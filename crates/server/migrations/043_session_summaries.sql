@@ -0,0 +1,47 @@
+-- Migration: 043_session_summaries.sql
+-- Completed-session aggregates per day, bean origin and profile, kept up
+-- to date by the summary refresher so analytics don't regroup every
+-- session on each dashboard load. Rolling days up gives the per-bean and
+-- per-profile figures. Sessions without a bean origin or profile are
+-- grouped under ''.
+CREATE TABLE IF NOT EXISTS session_summaries (
+    day TEXT,
+    bean_origin TEXT NOT NULL,
+    profile_id TEXT NOT NULL,
+    sessions INTEGER NOT NULL,
+    roast_seconds REAL,
+    green_weight REAL,
+    roasted_weight REAL,
+    development_time_ratio_n INTEGER NOT NULL,
+    development_time_ratio_sum REAL,
+    development_time_ratio_min REAL,
+    development_time_ratio_max REAL,
+    weight_loss_pct_n INTEGER NOT NULL,
+    weight_loss_pct_sum REAL,
+    weight_loss_pct_min REAL,
+    weight_loss_pct_max REAL,
+    first_crack_time_n INTEGER NOT NULL,
+    first_crack_time_sum REAL,
+    first_crack_time_min REAL,
+    first_crack_time_max REAL,
+    PRIMARY KEY (day, bean_origin, profile_id)
+);
+
+-- What each completed session last contributed to session_summaries.
+-- Comparing it with roast_sessions finds the sessions that changed, and
+-- it outlives deleted sessions so their groups can be recounted.
+CREATE TABLE IF NOT EXISTS session_summary_sources (
+    session_id TEXT PRIMARY KEY,
+    day TEXT,
+    bean_origin TEXT NOT NULL,
+    profile_id TEXT NOT NULL,
+    total_time_seconds REAL,
+    green_weight REAL,
+    roasted_weight REAL,
+    development_time_ratio REAL,
+    weight_loss_pct REAL,
+    first_crack_time REAL
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_summary_sources_group
+    ON session_summary_sources(day, bean_origin, profile_id);
@@ -0,0 +1,14 @@
+-- Migration: 044_report_emails.sql
+-- Daily report emails sent, so the scheduled one goes out once a day
+-- whichever instance leads and however often it restarts.
+CREATE TABLE IF NOT EXISTS report_emails (
+    id TEXT PRIMARY KEY,
+    date TEXT NOT NULL,
+    sent_at TEXT NOT NULL,
+    recipients TEXT NOT NULL,
+    session_count INTEGER NOT NULL,
+    -- Who sent it with the send-now endpoint, NULL when scheduled
+    sent_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_report_emails_date ON report_emails(date);
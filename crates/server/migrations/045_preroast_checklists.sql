@@ -0,0 +1,26 @@
+-- Migration: 045_preroast_checklists.sql
+-- Pre-roast checklist per roaster (chaff bin emptied, afterburner on, ...).
+-- A session on that roaster only starts once every item has been checked
+-- for it, or with an override. The items required and any override are
+-- recorded when the session starts.
+CREATE TABLE IF NOT EXISTS preroast_checklists (
+    device_id TEXT PRIMARY KEY,
+    items TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS session_checklist_checks (
+    session_id TEXT NOT NULL REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    item TEXT NOT NULL,
+    checked_by TEXT NOT NULL,
+    checked_at TEXT NOT NULL,
+    PRIMARY KEY (session_id, item)
+);
+
+CREATE TABLE IF NOT EXISTS session_checklists (
+    session_id TEXT PRIMARY KEY REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    items TEXT NOT NULL,
+    overridden_by TEXT,
+    override_reason TEXT,
+    recorded_at TEXT NOT NULL
+);
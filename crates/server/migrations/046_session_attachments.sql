@@ -0,0 +1,17 @@
+-- Migration: 046_session_attachments.sql
+-- Voice memos and photos sent over the session notes WebSocket while a
+-- roast runs, each marked with the seconds since the session started.
+CREATE TABLE IF NOT EXISTS session_attachments (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL REFERENCES roast_sessions(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    elapsed_seconds REAL NOT NULL,
+    data BLOB NOT NULL,
+    created_by TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_attachments_session
+    ON session_attachments(session_id, elapsed_seconds);
@@ -0,0 +1,10 @@
+-- Migration: 047_telemetry_wal_state.sql
+-- Highest telemetry journal sequence number stored in the database. It is
+-- advanced in the same transaction as the rows it covers, so replaying the
+-- journal after a crash skips records that were already committed.
+CREATE TABLE IF NOT EXISTS telemetry_wal_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    committed_seq INTEGER NOT NULL
+);
+
+INSERT OR IGNORE INTO telemetry_wal_state (id, committed_seq) VALUES (1, 0);
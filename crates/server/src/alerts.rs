@@ -0,0 +1,482 @@
+//! Alert history and its acknowledgement workflow.
+//!
+//! Alerts are stored in the `alerts` table and move from firing to
+//! acknowledged to resolved. Every state change is broadcast so that all
+//! `/ws/telemetry` clients, and with them every open dashboard, agree on
+//! what has been handled. Sources:
+//!
+//! - `device_conflict`: several boards publish under one device_id (see
+//!   [`crate::device_conflict`]), resolved when the conflict clears.
+//! - `over_temperature`: bean temp reached the roaster's alert limit, else
+//!   `RUSTROAST_ALERT_MAX_BEAN_TEMP` (default 240 °C), resolved once it is
+//!   [`OVER_TEMP_HYSTERESIS`] below.
+//! - `automation_failed`: an automation rule could not send its command.
+//! - `preheat_failed`: a between-batches preheat was aborted or timed out
+//!   (see [`crate::preheat`]).
+//! - `device_health`: a device's RSSI or free heap is low or trending down
+//!   (see [`crate::device_health`]), resolved once it recovers.
+//! - `tolerance_breach`: an active roast stayed outside its profile's
+//!   tolerance band (see [`crate::tolerance`]), resolved once it is back.
+//! - `probe_fault`: a temperature probe flatlined or disagrees with another
+//!   during a roast (see [`crate::probes`]), resolved once they look sane.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::{
+    Alert, AlertKind, AlertLimit, AlertListQuery, AlertSeverity, AlertState, SessionStatus,
+};
+use crate::telemetry::TelemetryEvent;
+use crate::AppState;
+
+const DEFAULT_MAX_BEAN_TEMP: f64 = 240.0;
+/// Degrees below the limit the bean temp must fall to resolve the alert.
+pub const OVER_TEMP_HYSTERESIS: f64 = 5.0;
+/// How often the monitor reloads per-roaster limits.
+const LIMITS_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_LIST_LIMIT: i64 = 100;
+const MAX_LIST_LIMIT: i64 = 1000;
+
+#[derive(Clone)]
+pub struct AlertService {
+    db: SqlitePool,
+    changes_tx: broadcast::Sender<Alert>,
+}
+
+impl AlertService {
+    pub fn new(db: SqlitePool) -> Self {
+        let (changes_tx, _) = broadcast::channel(64);
+        Self { db, changes_tx }
+    }
+
+    /// Subscribe to alerts as they are raised, acknowledged and resolved.
+    pub fn subscribe(&self) -> broadcast::Receiver<Alert> {
+        self.changes_tx.subscribe()
+    }
+
+    fn publish(&self, alert: &Alert) {
+        let _ = self.changes_tx.send(alert.clone());
+    }
+
+    pub async fn raise(
+        &self,
+        kind: AlertKind,
+        severity: AlertSeverity,
+        device_id: Option<&str>,
+        session_id: Option<&str>,
+        message: &str,
+        details: Option<serde_json::Value>,
+    ) -> Result<Alert> {
+        let alert = sqlx::query_as::<_, Alert>(
+            r#"
+            INSERT INTO alerts (id, kind, severity, device_id, session_id, message, details, state, fired_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(kind)
+        .bind(severity)
+        .bind(device_id)
+        .bind(session_id)
+        .bind(message)
+        .bind(details.map(sqlx::types::Json))
+        .bind(AlertState::Firing)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        tracing::warn!(alert_id = %alert.id, %kind, device_id = ?device_id, %message, "Alert raised");
+        self.publish(&alert);
+        Ok(alert)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Alert>> {
+        let alert = sqlx::query_as::<_, Alert>("SELECT * FROM alerts WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(alert)
+    }
+
+    /// Newest first.
+    pub async fn list(&self, q: &AlertListQuery) -> Result<Vec<Alert>> {
+        let mut query = "SELECT * FROM alerts".to_string();
+        let mut conditions = Vec::new();
+        if q.state.is_some() {
+            conditions.push("state = ?");
+        }
+        if q.kind.is_some() {
+            conditions.push("kind = ?");
+        }
+        if q.device_id.is_some() {
+            conditions.push("device_id = ?");
+        }
+        if q.session_id.is_some() {
+            conditions.push("session_id = ?");
+        }
+        if q.since.is_some() {
+            conditions.push("fired_at >= ?");
+        }
+        if q.until.is_some() {
+            conditions.push("fired_at <= ?");
+        }
+        if !conditions.is_empty() {
+            query.push_str(" WHERE ");
+            query.push_str(&conditions.join(" AND "));
+        }
+        query.push_str(" ORDER BY fired_at DESC LIMIT ?");
+
+        let mut query_builder = sqlx::query_as::<_, Alert>(&query);
+        if let Some(state) = q.state {
+            query_builder = query_builder.bind(state);
+        }
+        if let Some(kind) = q.kind {
+            query_builder = query_builder.bind(kind);
+        }
+        if let Some(device_id) = &q.device_id {
+            query_builder = query_builder.bind(device_id);
+        }
+        if let Some(session_id) = &q.session_id {
+            query_builder = query_builder.bind(session_id);
+        }
+        if let Some(since) = q.since {
+            query_builder = query_builder.bind(since);
+        }
+        if let Some(until) = q.until {
+            query_builder = query_builder.bind(until);
+        }
+        let limit = q
+            .limit
+            .unwrap_or(DEFAULT_LIST_LIMIT)
+            .clamp(1, MAX_LIST_LIMIT);
+        let alerts = query_builder.bind(limit).fetch_all(&self.db).await?;
+        Ok(alerts)
+    }
+
+    /// Acknowledge a firing alert. `None` when it doesn't exist or isn't firing.
+    pub async fn acknowledge(&self, id: &str, by: &str) -> Result<Option<Alert>> {
+        let alert = sqlx::query_as::<_, Alert>(
+            r#"
+            UPDATE alerts SET state = ?, acknowledged_by = ?, acknowledged_at = ?
+            WHERE id = ? AND state = ?
+            RETURNING *
+            "#,
+        )
+        .bind(AlertState::Acknowledged)
+        .bind(by)
+        .bind(Utc::now())
+        .bind(id)
+        .bind(AlertState::Firing)
+        .fetch_optional(&self.db)
+        .await?;
+        if let Some(alert) = &alert {
+            self.publish(alert);
+        }
+        Ok(alert)
+    }
+
+    /// Resolve an open alert by hand. `None` when it doesn't exist or is
+    /// already resolved.
+    pub async fn resolve(&self, id: &str, by: &str) -> Result<Option<Alert>> {
+        let alert = sqlx::query_as::<_, Alert>(
+            r#"
+            UPDATE alerts SET state = ?, resolved_by = ?, resolved_at = ?
+            WHERE id = ? AND state != ?
+            RETURNING *
+            "#,
+        )
+        .bind(AlertState::Resolved)
+        .bind(by)
+        .bind(Utc::now())
+        .bind(id)
+        .bind(AlertState::Resolved)
+        .fetch_optional(&self.db)
+        .await?;
+        if let Some(alert) = &alert {
+            self.publish(alert);
+        }
+        Ok(alert)
+    }
+
+    /// Resolve every open alert of `kind` for a device whose condition cleared.
+    pub async fn resolve_cleared(&self, kind: AlertKind, device_id: &str) -> Result<()> {
+        let resolved = sqlx::query_as::<_, Alert>(
+            r#"
+            UPDATE alerts SET state = ?, resolved_at = ?
+            WHERE kind = ? AND device_id = ? AND state != ?
+            RETURNING *
+            "#,
+        )
+        .bind(AlertState::Resolved)
+        .bind(Utc::now())
+        .bind(kind)
+        .bind(device_id)
+        .bind(AlertState::Resolved)
+        .fetch_all(&self.db)
+        .await?;
+        for alert in &resolved {
+            tracing::info!(alert_id = %alert.id, %kind, %device_id, "Alert condition cleared");
+            self.publish(alert);
+        }
+        Ok(())
+    }
+
+    pub async fn limits(&self) -> Result<Vec<AlertLimit>> {
+        let limits =
+            sqlx::query_as::<_, AlertLimit>("SELECT * FROM alert_limits ORDER BY device_id")
+                .fetch_all(&self.db)
+                .await?;
+        Ok(limits)
+    }
+
+    pub async fn limit(&self, device_id: &str) -> Result<Option<AlertLimit>> {
+        let limit =
+            sqlx::query_as::<_, AlertLimit>("SELECT * FROM alert_limits WHERE device_id = ?")
+                .bind(device_id)
+                .fetch_optional(&self.db)
+                .await?;
+        Ok(limit)
+    }
+
+    pub async fn set_limit(&self, device_id: &str, max_bean_temp: f64) -> Result<AlertLimit> {
+        let limit = sqlx::query_as::<_, AlertLimit>(
+            r#"
+            INSERT INTO alert_limits (device_id, max_bean_temp, updated_at) VALUES (?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                max_bean_temp = excluded.max_bean_temp,
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(max_bean_temp)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+        Ok(limit)
+    }
+
+    /// Back to the server-wide limit. Returns whether the roaster had its own.
+    pub async fn remove_limit(&self, device_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM alert_limits WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Devices with an open (firing or acknowledged) alert of `kind`.
+    pub(crate) async fn open_devices(&self, kind: AlertKind) -> Result<HashSet<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT DISTINCT device_id FROM alerts WHERE kind = ? AND state != ? AND device_id IS NOT NULL",
+        )
+        .bind(kind)
+        .bind(AlertState::Resolved)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows.into_iter().map(|(d,)| d).collect())
+    }
+}
+
+/// Raises and clears condition-based alerts (device conflicts and bean
+/// over-temperature) from the telemetry stream.
+pub struct AlertMonitor {
+    state: AppState,
+    max_bean_temp: f64,
+    /// Per-roaster limits and when they were last loaded.
+    limits: HashMap<String, f64>,
+    limits_loaded: Option<Instant>,
+    conflicted: HashSet<String>,
+    overheated: HashSet<String>,
+}
+
+impl AlertMonitor {
+    /// Default limit from `RUSTROAST_ALERT_MAX_BEAN_TEMP` (default 240 °C).
+    pub fn new(state: AppState) -> Self {
+        let max_bean_temp = std::env::var("RUSTROAST_ALERT_MAX_BEAN_TEMP")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+            .unwrap_or(DEFAULT_MAX_BEAN_TEMP);
+        Self {
+            state,
+            max_bean_temp,
+            limits: HashMap::new(),
+            limits_loaded: None,
+            conflicted: HashSet::new(),
+            overheated: HashSet::new(),
+        }
+    }
+
+    pub async fn run(mut self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        // Pick up alerts left open by a previous run so they can still clear
+        let alerts = &self.state.alerts;
+        match (
+            alerts.open_devices(AlertKind::DeviceConflict).await,
+            alerts.open_devices(AlertKind::OverTemperature).await,
+        ) {
+            (Ok(conflicted), Ok(overheated)) => {
+                self.conflicted = conflicted;
+                self.overheated = overheated;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::warn!(error = %e, "Failed to load open alerts");
+            }
+        }
+
+        let mut conflict_rx = self.state.conflicts.subscribe();
+        loop {
+            tokio::select! {
+                evt = telemetry_rx.recv() => match evt {
+                    // Only the cluster leader raises alerts
+                    Ok(evt) if self.state.cluster.is_leader() => {
+                        self.check_telemetry(&evt.device_id, &evt.payload).await
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "Alert monitor lagged behind telemetry");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                conflict = conflict_rx.recv() => match conflict {
+                    Ok(conflict) if self.state.cluster.is_leader() => {
+                        self.check_conflict(&conflict.device_id).await
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+
+    async fn check_telemetry(&mut self, device_id: &str, payload: &serde_json::Value) {
+        self.check_conflict(device_id).await;
+
+        let Some(bean_temp) = payload.get("beanTemp").and_then(|v| v.as_f64()) else {
+            return;
+        };
+        let limit = self.limit_for(device_id).await;
+        let hot = self.overheated.contains(device_id);
+        if !hot && bean_temp >= limit {
+            let session_id = match self
+                .state
+                .session_service
+                .get_active_session(device_id)
+                .await
+            {
+                Ok(Some(s)) if s.status == SessionStatus::Active => Some(s.id),
+                _ => None,
+            };
+            let message = format!(
+                "Bean temperature {:.1} °C reached the {:.0} °C limit",
+                bean_temp, limit
+            );
+            let details = serde_json::json!({
+                "bean_temp": bean_temp,
+                "limit": limit,
+            });
+            self.raise(
+                AlertKind::OverTemperature,
+                AlertSeverity::Critical,
+                device_id,
+                session_id.as_deref(),
+                &message,
+                details,
+            )
+            .await;
+            self.overheated.insert(device_id.to_string());
+        } else if hot && bean_temp <= limit - OVER_TEMP_HYSTERESIS {
+            self.clear(AlertKind::OverTemperature, device_id).await;
+            self.overheated.remove(device_id);
+        }
+    }
+
+    /// The roaster's own limit, else the default.
+    async fn limit_for(&mut self, device_id: &str) -> f64 {
+        if self
+            .limits_loaded
+            .is_none_or(|at| at.elapsed() >= LIMITS_RELOAD_INTERVAL)
+        {
+            match self.state.alerts.limits().await {
+                Ok(limits) => {
+                    self.limits = limits
+                        .into_iter()
+                        .map(|l| (l.device_id, l.max_bean_temp))
+                        .collect();
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to load alert limits"),
+            }
+            self.limits_loaded = Some(Instant::now());
+        }
+        self.limits
+            .get(device_id)
+            .copied()
+            .unwrap_or(self.max_bean_temp)
+    }
+
+    async fn check_conflict(&mut self, device_id: &str) {
+        let conflict = self.state.conflicts.conflict(device_id);
+        let known = self.conflicted.contains(device_id);
+        match conflict {
+            Some(conflict) if !known => {
+                let message = format!("Several devices appear to publish as {}", device_id);
+                let details = serde_json::to_value(&conflict).unwrap_or_default();
+                self.raise(
+                    AlertKind::DeviceConflict,
+                    AlertSeverity::Critical,
+                    device_id,
+                    None,
+                    &message,
+                    details,
+                )
+                .await;
+                self.conflicted.insert(device_id.to_string());
+            }
+            None if known => {
+                self.clear(AlertKind::DeviceConflict, device_id).await;
+                self.conflicted.remove(device_id);
+            }
+            _ => {}
+        }
+    }
+
+    async fn raise(
+        &self,
+        kind: AlertKind,
+        severity: AlertSeverity,
+        device_id: &str,
+        session_id: Option<&str>,
+        message: &str,
+        details: serde_json::Value,
+    ) {
+        if let Err(e) = self
+            .state
+            .alerts
+            .raise(
+                kind,
+                severity,
+                Some(device_id),
+                session_id,
+                message,
+                Some(details),
+            )
+            .await
+        {
+            tracing::warn!(%device_id, %kind, error = %e, "Failed to record alert");
+        }
+    }
+
+    async fn clear(&self, kind: AlertKind, device_id: &str) {
+        if let Err(e) = self.state.alerts.resolve_cleared(kind, device_id).await {
+            tracing::warn!(%device_id, %kind, error = %e, "Failed to resolve alert");
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! Archival of completed sessions to S3-compatible object storage.
+//!
+//! With object storage configured (see [`S3Store::from_env`]), sessions
+//! completed more than `RUSTROAST_ARCHIVE_AFTER_DAYS` ago (default 30, 0
+//! leaves archival to `POST /api/admin/sessions/archive`) are uploaded under
+//! `{RUSTROAST_ARCHIVE_PREFIX}sessions/{id}/` as `session.json` (session,
+//! events and telemetry), `telemetry.parquet` and a Markdown `report.md`.
+//! With `RUSTROAST_ARCHIVE_PRUNE_TELEMETRY=true` the session's local
+//! telemetry is then deleted. `POST /api/sessions/{id}/restore` loads it back
+//! from `session.json`.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use parquet::data_type::{DataType, FloatType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::FileReader;
+use parquet::file::serialized_reader::SerializedFileReader;
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::record::Field;
+use parquet::schema::parser::parse_message_type;
+use uuid::Uuid;
+
+use crate::i18n::Locale;
+use crate::models::{SessionArchive, SessionArchiveBundle, SessionTelemetry};
+use crate::object_storage::S3Store;
+use crate::services::RoastSessionService;
+
+const DEFAULT_ARCHIVE_AFTER_DAYS: i64 = 30;
+const DEFAULT_PREFIX: &str = "rustroast/";
+
+const TELEMETRY_SCHEMA: &str = "
+message session_telemetry {
+    REQUIRED INT64 timestamp (TIMESTAMP(MILLIS,true));
+    REQUIRED FLOAT elapsed_seconds;
+    OPTIONAL FLOAT bean_temp;
+    OPTIONAL FLOAT env_temp;
+    OPTIONAL FLOAT rate_of_rise;
+    OPTIONAL INT32 heater_pwm;
+    OPTIONAL INT32 fan_pwm;
+    OPTIONAL FLOAT setpoint;
+    OPTIONAL FLOAT airflow;
+}";
+
+#[derive(Clone)]
+pub struct SessionArchiver {
+    store: Option<Arc<S3Store>>,
+    prefix: String,
+    /// Age after which completed sessions are archived, `None` when only
+    /// archived on request.
+    pub archive_after_secs: Option<i64>,
+    pub prune_telemetry: bool,
+}
+
+impl SessionArchiver {
+    pub fn new(store: Option<S3Store>, prefix: &str, prune_telemetry: bool) -> Self {
+        Self {
+            store: store.map(Arc::new),
+            prefix: prefix.to_string(),
+            archive_after_secs: None,
+            prune_telemetry,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let days = std::env::var("RUSTROAST_ARCHIVE_AFTER_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_ARCHIVE_AFTER_DAYS);
+        let prefix =
+            std::env::var("RUSTROAST_ARCHIVE_PREFIX").unwrap_or_else(|_| DEFAULT_PREFIX.into());
+        let prune = std::env::var("RUSTROAST_ARCHIVE_PRUNE_TELEMETRY")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        Self {
+            archive_after_secs: (days > 0).then_some(days * 86_400),
+            ..Self::new(S3Store::from_env(), &prefix, prune)
+        }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.store.is_some()
+    }
+
+    fn store(&self) -> Result<&S3Store> {
+        self.store
+            .as_deref()
+            .ok_or_else(|| anyhow!("object storage is not configured"))
+    }
+
+    fn object_prefix(&self, session_id: &str) -> String {
+        format!("{}sessions/{}/", self.prefix, session_id)
+    }
+
+    /// Upload a session's archive objects and record the archive, pruning
+    /// local telemetry when configured. `None` for an unknown session.
+    pub async fn archive(
+        &self,
+        sessions: &RoastSessionService,
+        session_id: &str,
+    ) -> Result<Option<SessionArchive>> {
+        let store = self.store()?;
+        let Some(bundle) = sessions.session_archive_bundle(session_id).await? else {
+            return Ok(None);
+        };
+        let prefix = self.object_prefix(session_id);
+        let parquet = telemetry_parquet(&bundle.telemetry)?;
+        let report = session_report(&bundle, Locale::server_default());
+        store
+            .put(
+                &format!("{}session.json", prefix),
+                serde_json::to_vec(&bundle)?,
+                "application/json",
+            )
+            .await?;
+        store
+            .put(
+                &format!("{}telemetry.parquet", prefix),
+                parquet,
+                "application/vnd.apache.parquet",
+            )
+            .await?;
+        store
+            .put(
+                &format!("{}report.md", prefix),
+                report.into_bytes(),
+                "text/markdown; charset=utf-8",
+            )
+            .await?;
+
+        let mut archive = sessions
+            .record_session_archive(session_id, &prefix, bundle.telemetry.len() as i64)
+            .await?;
+        if self.prune_telemetry {
+            if let Some(pruned) = sessions.prune_session_telemetry(session_id).await? {
+                archive = pruned;
+            }
+        }
+        Ok(Some(archive))
+    }
+
+    /// Re-hydrate an archived session's telemetry from its `session.json`.
+    /// `None` when the session was never archived.
+    pub async fn restore(
+        &self,
+        sessions: &RoastSessionService,
+        session_id: &str,
+    ) -> Result<Option<SessionArchive>> {
+        let store = self.store()?;
+        let Some(archive) = sessions.get_session_archive(session_id).await? else {
+            return Ok(None);
+        };
+        let body = store
+            .get(&format!("{}session.json", archive.object_prefix))
+            .await?;
+        let bundle: SessionArchiveBundle = serde_json::from_slice(&body)?;
+        if bundle.session.id != session_id {
+            return Err(anyhow!(
+                "archive at {} belongs to session {}",
+                archive.object_prefix,
+                bundle.session.id
+            ));
+        }
+        sessions
+            .restore_session_telemetry(session_id, &bundle.telemetry)
+            .await
+    }
+}
+
+/// Archive every completed session that ended before `cutoff` (unix
+/// seconds). Returns how many were archived.
+pub async fn archive_due(
+    archiver: &SessionArchiver,
+    sessions: &RoastSessionService,
+    cutoff: i64,
+) -> Result<usize> {
+    let cutoff = chrono::DateTime::from_timestamp(cutoff, 0).unwrap_or_else(Utc::now);
+    let mut archived = 0;
+    for id in sessions.sessions_to_archive(cutoff).await? {
+        match archiver.archive(sessions, &id).await {
+            Ok(Some(_)) => archived += 1,
+            Ok(None) => {}
+            Err(e) => tracing::warn!(session_id = %id, error = %e, "Failed to archive session"),
+        }
+    }
+    Ok(archived)
+}
+
+/// Telemetry as a single-row-group Parquet file, one column per field.
+pub(crate) fn telemetry_parquet(telemetry: &[SessionTelemetry]) -> Result<Vec<u8>> {
+    let schema = Arc::new(parse_message_type(TELEMETRY_SCHEMA)?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(Vec::new(), schema, props)?;
+    let mut rows = writer.next_row_group()?;
+    write_column::<Int64Type>(
+        &mut rows,
+        telemetry
+            .iter()
+            .map(|p| Some(p.timestamp.timestamp_millis())),
+    )?;
+    write_column::<FloatType>(&mut rows, telemetry.iter().map(|p| Some(p.elapsed_seconds)))?;
+    write_column::<FloatType>(&mut rows, telemetry.iter().map(|p| p.bean_temp))?;
+    write_column::<FloatType>(&mut rows, telemetry.iter().map(|p| p.env_temp))?;
+    write_column::<FloatType>(&mut rows, telemetry.iter().map(|p| p.rate_of_rise))?;
+    write_column::<Int32Type>(&mut rows, telemetry.iter().map(|p| p.heater_pwm))?;
+    write_column::<Int32Type>(&mut rows, telemetry.iter().map(|p| p.fan_pwm))?;
+    write_column::<FloatType>(&mut rows, telemetry.iter().map(|p| p.setpoint))?;
+    write_column::<FloatType>(&mut rows, telemetry.iter().map(|p| p.airflow))?;
+    rows.close()?;
+    Ok(writer.into_inner()?)
+}
+
+/// Read back a file written by [`telemetry_parquet`] as telemetry of
+/// `session_id`, under fresh ids.
+pub(crate) fn telemetry_from_parquet(
+    parquet: Bytes,
+    session_id: &str,
+) -> Result<Vec<SessionTelemetry>> {
+    let reader = SerializedFileReader::new(parquet)?;
+    let mut telemetry = Vec::new();
+    for row in reader.get_row_iter(None)? {
+        let row = row?;
+        let mut point = SessionTelemetry {
+            id: Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            timestamp: Utc::now(),
+            elapsed_seconds: 0.0,
+            bean_temp: None,
+            env_temp: None,
+            rate_of_rise: None,
+            heater_pwm: None,
+            fan_pwm: None,
+            setpoint: None,
+            airflow: None,
+        };
+        for (name, field) in row.get_column_iter() {
+            let float = match field {
+                Field::Float(v) => Some(*v),
+                _ => None,
+            };
+            let int = match field {
+                Field::Int(v) => Some(*v),
+                _ => None,
+            };
+            match name.as_str() {
+                "timestamp" => match field {
+                    Field::TimestampMillis(ms) => {
+                        point.timestamp = DateTime::from_timestamp_millis(*ms)
+                            .ok_or_else(|| anyhow!("telemetry timestamp {ms} out of range"))?;
+                    }
+                    _ => return Err(anyhow!("telemetry timestamp is not a timestamp")),
+                },
+                "elapsed_seconds" => {
+                    point.elapsed_seconds =
+                        float.ok_or_else(|| anyhow!("telemetry elapsed_seconds is missing"))?
+                }
+                "bean_temp" => point.bean_temp = float,
+                "env_temp" => point.env_temp = float,
+                "rate_of_rise" => point.rate_of_rise = float,
+                "heater_pwm" => point.heater_pwm = int,
+                "fan_pwm" => point.fan_pwm = int,
+                "setpoint" => point.setpoint = float,
+                "airflow" => point.airflow = float,
+                _ => {}
+            }
+        }
+        telemetry.push(point);
+    }
+    Ok(telemetry)
+}
+
+/// Write the row group's next column. Required columns get no definition
+/// levels, so their values must all be `Some`.
+fn write_column<T: DataType>(
+    rows: &mut SerializedRowGroupWriter<'_, Vec<u8>>,
+    values: impl Iterator<Item = Option<T::T>>,
+) -> Result<()> {
+    let mut column = rows
+        .next_column()?
+        .ok_or_else(|| anyhow!("telemetry schema has fewer columns than written"))?;
+    let required = column.typed::<T>().get_descriptor().max_def_level() == 0;
+    let mut levels = Vec::new();
+    let mut present = Vec::new();
+    for value in values {
+        levels.push(value.is_some() as i16);
+        present.extend(value);
+    }
+    let levels = (!required).then_some(levels.as_slice());
+    column.typed::<T>().write_batch(&present, levels, None)?;
+    column.close()?;
+    Ok(())
+}
+
+/// Human-readable summary kept next to the data.
+fn session_report(bundle: &SessionArchiveBundle, locale: Locale) -> String {
+    let s = &bundle.session;
+    let mut out = format!("# {}\n\n", s.name);
+    let mut row = |label: &str, value: Option<String>| {
+        if let Some(value) = value {
+            out.push_str(&format!("- **{}:** {}\n", label, value));
+        }
+    };
+    row("Session", Some(s.id.clone()));
+    row("Device", Some(s.device_id.clone()));
+    row("Roaster", s.roaster.clone());
+    row("Started", s.start_time.map(|t| t.to_rfc3339()));
+    row("Ended", s.end_time.map(|t| t.to_rfc3339()));
+    row("Bean origin", s.bean_origin.clone());
+    row("Bean variety", s.bean_variety.clone());
+    row(
+        "Green weight",
+        s.green_weight.map(|w| format!("{:.0} g", w)),
+    );
+    row(
+        "Roasted weight",
+        s.roasted_weight.map(|w| format!("{:.0} g", w)),
+    );
+    row(
+        "Weight loss",
+        s.weight_loss_pct.map(|p| format!("{:.1} %", p)),
+    );
+    row("Total time", s.total_time_seconds.map(format_secs));
+    row("First crack", s.first_crack_time.map(format_secs));
+    row(
+        "Development ratio",
+        s.development_time_ratio
+            .map(|r| format!("{:.1} %", r * 100.0)),
+    );
+    row("Max temp", s.max_temp.map(|t| format!("{:.1} °C", t)));
+    row("Max RoR", s.max_ror.map(|r| format!("{:.1} °C/min", r)));
+    row("Telemetry points", Some(bundle.telemetry.len().to_string()));
+    if !bundle.events.is_empty() {
+        out.push_str("\n| Time | Event | Temp |\n|---|---|---|\n");
+        for e in &bundle.events {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                format_secs(e.elapsed_seconds as i32),
+                locale.event_name(&e.event_type),
+                e.temperature
+                    .map(|t| format!("{:.1} °C", t))
+                    .unwrap_or_default(),
+            ));
+        }
+    }
+    if let Some(notes) = s.notes.as_deref().filter(|n| !n.is_empty()) {
+        out.push_str(&format!("\n## Notes\n\n{}\n", notes));
+    }
+    out
+}
+
+pub(crate) fn format_secs(secs: i32) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::routing::put;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    type Objects = Arc<Mutex<HashMap<String, Bytes>>>;
+
+    /// In-memory stand-in for an S3 bucket.
+    async fn fake_s3() -> (String, Objects) {
+        let objects: Objects = Arc::default();
+        let app = axum::Router::new()
+            .route(
+                "/:bucket/*key",
+                put(
+                    |State(o): State<Objects>,
+                     Path((_, key)): Path<(String, String)>,
+                     body: Bytes| async move {
+                        o.lock().unwrap().insert(key, body);
+                        StatusCode::OK
+                    },
+                )
+                .get(
+                    |State(o): State<Objects>, Path((_, key)): Path<(String, String)>| async move {
+                        o.lock()
+                            .unwrap()
+                            .get(&key)
+                            .cloned()
+                            .ok_or(StatusCode::NOT_FOUND)
+                    },
+                ),
+            )
+            .with_state(objects.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), objects)
+    }
+
+    #[tokio::test]
+    async fn test_archive_prune_and_restore_session() {
+        let (endpoint, objects) = fake_s3().await;
+        let store = S3Store::new(&endpoint, "roasts", "us-east-1", None).unwrap();
+        let archiver = SessionArchiver::new(Some(store), "rr/", true);
+        let sessions = RoastSessionService::new(crate::init_memory_db().await.unwrap());
+        let req = serde_json::from_value(serde_json::json!({"name": "Kenya", "device_id": "dev1"}));
+        let session = sessions.create_session(req.unwrap()).await.unwrap();
+        sessions.start_session(&session.id).await.unwrap();
+        for t in 0..5 {
+            let bt = 150.0 + t as f32;
+            sessions
+                .add_telemetry_point(
+                    &session.id,
+                    t as f32,
+                    Some(bt),
+                    None,
+                    None,
+                    Some(80),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        sessions.complete_session(&session.id).await.unwrap();
+        let due = sessions
+            .sessions_to_archive(Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(due, vec![session.id.clone()]);
+
+        let archived = archive_due(&archiver, &sessions, Utc::now().timestamp() + 1)
+            .await
+            .unwrap();
+        assert_eq!(archived, 1);
+        let archive = sessions
+            .get_session_archive(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(archive.telemetry_points, 5);
+        assert!(archive.pruned_at.is_some());
+        assert!(sessions
+            .get_session_telemetry(&session.id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let prefix = format!("rr/sessions/{}/", session.id);
+        let parquet = objects.lock().unwrap()[&format!("{}telemetry.parquet", prefix)].clone();
+        let reader = SerializedFileReader::new(parquet).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 5);
+        let report = objects.lock().unwrap()[&format!("{}report.md", prefix)].clone();
+        assert!(String::from_utf8_lossy(&report).starts_with("# Kenya"));
+
+        let restored = archiver
+            .restore(&sessions, &session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(restored.pruned_at.is_none() && restored.restored_at.is_some());
+        let telemetry = sessions.get_session_telemetry(&session.id).await.unwrap();
+        assert_eq!(telemetry.len(), 5);
+        assert_eq!(telemetry[4].bean_temp, Some(154.0));
+        assert!(archiver
+            .restore(&sessions, "missing")
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,303 @@
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::{AUTHORIZATION, COOKIE};
+use axum::http::request::Parts;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+
+use crate::models::{User, UserRole};
+use crate::oidc::OidcClient;
+use crate::services::API_KEY_PREFIX;
+use crate::AppState;
+
+/// Cookie carrying the rustRoast session JWT after an OIDC login.
+pub(crate) const SESSION_COOKIE: &str = "rustroast_session";
+
+/// Shared admin token, read once from `RUSTROAST_ADMIN_TOKEN`.
+/// When unset, no request is treated as an admin via this token.
+fn admin_token() -> Option<&'static str> {
+    static TOKEN: OnceLock<Option<String>> = OnceLock::new();
+    TOKEN
+        .get_or_init(|| {
+            std::env::var("RUSTROAST_ADMIN_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty())
+        })
+        .as_deref()
+}
+
+/// Whether `token` is the admin token. Compared in constant time so the
+/// response time doesn't tell how much of a guess was right.
+fn is_admin_token(token: &str) -> bool {
+    admin_token().is_some_and(|admin| admin.as_bytes().ct_eq(token.as_bytes()).into())
+}
+
+fn epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// `aud` of the read-only tokens embedded in dashboard QR codes.
+pub(crate) const DASHBOARD_AUDIENCE: &str = "qr";
+
+/// Claims of the session JWTs rustRoast issues after an OIDC login.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SessionClaims {
+    pub sub: String,
+    pub name: Option<String>,
+    pub role: UserRole,
+    pub iat: u64,
+    pub exp: u64,
+    /// [`DASHBOARD_AUDIENCE`] for dashboard tokens, absent on sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    /// The one device a dashboard token may watch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+}
+
+/// Signs and verifies session JWTs (HS256).
+pub(crate) struct SessionSigner {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    pub ttl_secs: u64,
+}
+
+impl SessionSigner {
+    /// Reads `RUSTROAST_JWT_SECRET` and `RUSTROAST_SESSION_TTL_SECS`. Without a
+    /// configured secret a random one is generated, so sessions end on restart.
+    pub fn from_env() -> Self {
+        let secret = match std::env::var("RUSTROAST_JWT_SECRET") {
+            Ok(s) if !s.is_empty() => s.into_bytes(),
+            _ => {
+                use rand::RngCore;
+                let mut buf = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut buf);
+                buf
+            }
+        };
+        let ttl_secs = std::env::var("RUSTROAST_SESSION_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(12 * 3600);
+        Self {
+            encoding: EncodingKey::from_secret(&secret),
+            decoding: DecodingKey::from_secret(&secret),
+            ttl_secs,
+        }
+    }
+
+    pub fn issue(&self, user: &User) -> anyhow::Result<String> {
+        let now = epoch_secs();
+        let claims = SessionClaims {
+            sub: user.id.clone(),
+            name: user.display_name.clone().or_else(|| user.email.clone()),
+            role: user.role,
+            iat: now,
+            exp: now + self.ttl_secs,
+            aud: None,
+            device_id: None,
+        };
+        Ok(jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &self.encoding,
+        )?)
+    }
+
+    /// The read-only token in a dashboard QR code. It only opens
+    /// `device_id`'s live streams (see [`DashboardToken`]) and is no session
+    /// anywhere else. It ends after `ttl_secs` or when the secret changes.
+    pub fn issue_dashboard(&self, device_id: &str, ttl_secs: u64) -> anyhow::Result<String> {
+        let now = epoch_secs();
+        let exp = now
+            .checked_add(ttl_secs)
+            .ok_or_else(|| anyhow::anyhow!("Token lifetime of {ttl_secs} s is too long"))?;
+        let claims = SessionClaims {
+            sub: format!("qr:{device_id}"),
+            name: Some(format!("{device_id} dashboard QR")),
+            role: UserRole::Viewer,
+            iat: now,
+            exp,
+            aud: Some(DASHBOARD_AUDIENCE.to_string()),
+            device_id: Some(device_id.to_string()),
+        };
+        Ok(jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &self.encoding,
+        )?)
+    }
+
+    /// Claims of a session token. Tokens with an audience, such as
+    /// dashboard tokens, are refused.
+    pub fn verify(&self, token: &str) -> Option<SessionClaims> {
+        jsonwebtoken::decode::<SessionClaims>(token, &self.decoding, &Validation::default())
+            .ok()
+            .map(|data| data.claims)
+            .filter(|claims| claims.aud.is_none())
+    }
+
+    /// The device a dashboard token was issued for.
+    pub fn verify_dashboard(&self, token: &str) -> Option<String> {
+        let mut validation = Validation::default();
+        validation.set_audience(&[DASHBOARD_AUDIENCE]);
+        validation.set_required_spec_claims(&["exp", "aud"]);
+        jsonwebtoken::decode::<SessionClaims>(token, &self.decoding, &validation)
+            .ok()
+            .and_then(|data| data.claims.device_id)
+    }
+}
+
+/// Authentication configuration shared through `AppState`.
+pub(crate) struct AuthState {
+    pub signer: SessionSigner,
+    /// Present when OIDC login is configured.
+    pub oidc: Option<OidcClient>,
+}
+
+/// Identity of the caller making an API request. Anonymous callers have no
+/// role; endpoints that don't require a role keep working without auth.
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct Caller {
+    /// User id, API key id, or `admin-token`.
+    pub subject: Option<String>,
+    pub name: Option<String>,
+    pub role: Option<UserRole>,
+}
+
+impl Caller {
+    pub fn is_admin(&self) -> bool {
+        self.role == Some(UserRole::Admin)
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.role.is_some()
+    }
+
+    /// Who to record as having done something: the name, else the id.
+    pub fn label(&self) -> Option<&str> {
+        self.name.as_deref().or(self.subject.as_deref())
+    }
+}
+
+fn bearer(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+fn cookie_value<'a>(parts: &'a Parts, name: &str) -> Option<&'a str> {
+    parts
+        .headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let bearer = bearer(parts);
+
+        if let Some(token) = bearer {
+            if is_admin_token(token) {
+                return Ok(Caller {
+                    subject: Some("admin-token".to_string()),
+                    name: None,
+                    role: Some(UserRole::Admin),
+                });
+            }
+            if token.starts_with(API_KEY_PREFIX) {
+                return Ok(match state.user_service.authenticate_api_key(token).await {
+                    Ok(Some(key)) => Caller {
+                        subject: Some(key.id),
+                        name: Some(key.name),
+                        role: Some(key.role),
+                    },
+                    Ok(None) => Caller::default(),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "API key lookup failed");
+                        Caller::default()
+                    }
+                });
+            }
+        }
+
+        let session = bearer.or_else(|| cookie_value(parts, SESSION_COOKIE));
+        Ok(session
+            .and_then(|token| state.auth.signer.verify(token))
+            .map(|claims| Caller {
+                subject: Some(claims.sub),
+                name: claims.name,
+                role: Some(claims.role),
+            })
+            .unwrap_or_default())
+    }
+}
+
+/// The device of a dashboard QR token sent as bearer or session cookie, if
+/// any. Only the live, read-only streams of that device take it; [`Caller`]
+/// treats it as no credential at all.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DashboardToken(pub Option<String>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for DashboardToken {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(DashboardToken(
+            bearer(parts)
+                .or_else(|| cookie_value(parts, SESSION_COOKIE))
+                .and_then(|token| state.auth.signer.verify_dashboard(token)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_tokens_are_not_sessions() {
+        let signer = SessionSigner::from_env();
+        let token = signer.issue_dashboard("r1", 3600).unwrap();
+        assert!(signer.verify(&token).is_none());
+        assert_eq!(signer.verify_dashboard(&token).as_deref(), Some("r1"));
+
+        let now = epoch_secs();
+        let session = SessionClaims {
+            sub: "user-1".to_string(),
+            name: None,
+            role: UserRole::Viewer,
+            iat: now,
+            exp: now + 3600,
+            aud: None,
+            device_id: None,
+        };
+        let session = jsonwebtoken::encode(&Header::default(), &session, &signer.encoding).unwrap();
+        assert_eq!(signer.verify(&session).unwrap().sub, "user-1");
+        assert!(signer.verify_dashboard(&session).is_none());
+    }
+}
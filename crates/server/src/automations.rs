@@ -0,0 +1,201 @@
+//! Automation rules: control commands sent on cue while a session is active.
+//!
+//! Like the cue engine, this follows the unified telemetry broadcast fed by
+//! the MQTT consumer. Each sample is checked against the rules of the active
+//! session's profile and roaster; a due rule claims its single run for the
+//! session, sends its command through the regular control path (so it shows
+//! up in the control audit) and records the outcome in the execution log.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tokio::sync::broadcast;
+
+use crate::models::{
+    AlertKind, AlertSeverity, AutomationCommand, AutomationRule, AutomationTrigger, RoastSession,
+    SessionStatus,
+};
+use crate::telemetry::TelemetryEvent;
+use crate::{AppState, ControlOp, FanPwmPayload, HeaterPwmPayload, PublishOpts, SetpointPayload};
+
+pub struct AutomationEngine {
+    state: AppState,
+    /// Last bean temperature per device, tagged with its session, for
+    /// detecting threshold crossings.
+    last_bean_temp: HashMap<String, (String, f64)>,
+}
+
+impl AutomationEngine {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            last_bean_temp: HashMap::new(),
+        }
+    }
+
+    /// Evaluate rules for every telemetry event until the channel closes.
+    pub async fn run(mut self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        loop {
+            match telemetry_rx.recv().await {
+                Ok(evt) => self.evaluate(&evt.device_id, &evt.payload).await,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "Automation engine lagged behind telemetry");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn evaluate(&mut self, device_id: &str, payload: &serde_json::Value) {
+        // Followers leave commands to the cluster leader
+        if !self.state.cluster.is_leader() {
+            return;
+        }
+        let session_service = &self.state.session_service;
+        let session = match session_service.get_active_session(device_id).await {
+            Ok(Some(s)) if s.status == SessionStatus::Active => s,
+            Ok(_) => return,
+            Err(e) => {
+                tracing::warn!(%device_id, error = %e, "Failed to look up active session for automations");
+                return;
+            }
+        };
+        let Some(start) = session.start_time else {
+            return;
+        };
+        let elapsed = (Utc::now() - start).num_milliseconds() as f64 / 1000.0;
+        let bean_temp = payload.get("beanTemp").and_then(|v| v.as_f64());
+        let previous_bean_temp = self
+            .last_bean_temp
+            .get(device_id)
+            .filter(|(session_id, _)| *session_id == session.id)
+            .map(|(_, t)| *t);
+        if let Some(t) = bean_temp {
+            self.last_bean_temp
+                .insert(device_id.to_string(), (session.id.clone(), t));
+        }
+
+        let pending: Vec<AutomationRule> = match session_service
+            .list_session_automations(&session)
+            .await
+        {
+            Ok(rules) => rules
+                .into_iter()
+                .filter(|r| r.executed_at.is_none())
+                .map(|r| r.rule)
+                .collect(),
+            Err(e) => {
+                tracing::warn!(session_id = %session.id, error = %e, "Failed to load automation rules");
+                return;
+            }
+        };
+        if pending.is_empty() {
+            return;
+        }
+        // Only event-triggered rules need the session's events
+        let events = if pending
+            .iter()
+            .any(|r| r.trigger_type == AutomationTrigger::Event)
+        {
+            match session_service.get_roast_events(&session.id).await {
+                Ok(events) => events.into_iter().map(|e| e.event_type).collect(),
+                Err(e) => {
+                    tracing::warn!(session_id = %session.id, error = %e, "Failed to load events for automations");
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        for rule in pending {
+            if !rule.is_due(elapsed, previous_bean_temp, bean_temp, &events) {
+                continue;
+            }
+            match session_service
+                .claim_automation_execution(&session, &rule, elapsed, bean_temp)
+                .await
+            {
+                Ok(Some(execution_id)) => {
+                    self.execute(&session, &rule, payload, &execution_id).await
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(rule_id = %rule.id, error = %e, "Failed to claim automation run");
+                }
+            }
+        }
+    }
+
+    async fn execute(
+        &self,
+        session: &RoastSession,
+        rule: &AutomationRule,
+        payload: &serde_json::Value,
+        execution_id: &str,
+    ) {
+        let device_id = session.device_id.as_str();
+        let (sent_value, error) = match rule.target_value(payload) {
+            Some(value) => {
+                let op = match rule.command {
+                    AutomationCommand::FanPwm => ControlOp::FanPwm(FanPwmPayload {
+                        value: value.round() as u16,
+                    }),
+                    AutomationCommand::HeaterPwm => ControlOp::HeaterPwm(HeaterPwmPayload {
+                        value: value.round() as u8,
+                    }),
+                    AutomationCommand::Setpoint => ControlOp::Setpoint(SetpointPayload { value }),
+                };
+                let sent_value = op.message(device_id).ok().map(|(_, payload)| payload);
+                let resp =
+                    crate::publish_control(&self.state, device_id, &op, &PublishOpts::default())
+                        .await;
+                let error = (!resp.status().is_success())
+                    .then(|| format!("control command failed with status {}", resp.status()));
+                (sent_value, error)
+            }
+            None => (
+                None,
+                Some(format!(
+                    "no current {} reading for a relative change",
+                    rule.command.telemetry_key()
+                )),
+            ),
+        };
+        match &error {
+            None => tracing::info!(%device_id, rule = %rule.name, "Automation rule ran"),
+            Some(e) => {
+                tracing::warn!(%device_id, rule = %rule.name, error = %e, "Automation rule failed");
+                let message = format!("Automation rule \"{}\" failed: {}", rule.name, e);
+                let details = serde_json::json!({
+                    "rule_id": rule.id,
+                    "execution_id": execution_id,
+                    "command": rule.command,
+                });
+                if let Err(e) = self
+                    .state
+                    .alerts
+                    .raise(
+                        AlertKind::AutomationFailed,
+                        AlertSeverity::Warning,
+                        Some(device_id),
+                        Some(&session.id),
+                        &message,
+                        Some(details),
+                    )
+                    .await
+                {
+                    tracing::warn!(rule_id = %rule.id, error = %e, "Failed to record automation alert");
+                }
+            }
+        }
+        if let Err(e) = self
+            .state
+            .session_service
+            .finish_automation_execution(execution_id, sent_value.as_deref(), error.as_deref())
+            .await
+        {
+            tracing::warn!(rule_id = %rule.id, error = %e, "Failed to record automation run");
+        }
+    }
+}
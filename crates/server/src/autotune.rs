@@ -0,0 +1,235 @@
+//! Typed autotune results and the sanity checks `autotune/apply` runs before
+//! letting the firmware switch to them.
+//!
+//! A relay test that never settled, or a thermocouple that dropped out
+//! mid-run, produces gains that will run the heater into the ground. Results
+//! are refused when a gain is zero or negative, exceeds the roaster's limit
+//! (`autotune_gain_limits`, else [`DEFAULT_MAX_GAINS`]), the measured `ku`
+//! or `tu` is not positive, or the firmware itself rated the run poor.
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+use crate::models::{AutotuneCheck, AutotuneGainLimits, AutotuneResults, GainCandidate, PidGains};
+
+/// Bounds for roasters without their own limits.
+pub const DEFAULT_MAX_GAINS: PidGains = PidGains {
+    kp: 100.0,
+    ki: 20.0,
+    kd: 500.0,
+};
+
+/// Tuning rules as (method, Kp/Ku, Ti/Tu, Td/Tu).
+const TUNING_RULES: [(&str, f64, f64, f64); 4] = [
+    ("zn_classic", 0.6, 0.5, 0.125),
+    ("tyreus_luyben", 1.0 / 2.2, 2.2, 1.0 / 6.3),
+    ("zn_some_overshoot", 1.0 / 3.0, 0.5, 1.0 / 3.0),
+    ("zn_no_overshoot", 0.2, 0.5, 1.0 / 3.0),
+];
+
+/// Parse a results payload, or `None` when it carries no recommended gains
+/// (`recommended_kp`/`ki`/`kd`, or the older `Kp`/`Ki`/`Kd` keys).
+pub fn parse_results(val: &Value) -> Option<AutotuneResults> {
+    let num = |keys: &[&str]| keys.iter().find_map(|k| val.get(*k)?.as_f64());
+    let text = |key: &str| val.get(key)?.as_str().map(str::to_string);
+    let recommended = PidGains {
+        kp: num(&["recommended_kp", "Kp"])?,
+        ki: num(&["recommended_ki", "Ki"])?,
+        kd: num(&["recommended_kd", "Kd"])?,
+    };
+    let original = match (
+        num(&["original_kp"]),
+        num(&["original_ki"]),
+        num(&["original_kd"]),
+    ) {
+        (Some(kp), Some(ki), Some(kd)) => Some(PidGains { kp, ki, kd }),
+        _ => None,
+    };
+    let ku = num(&["ultimate_gain", "Ku"]);
+    let tu = num(&["oscillation_period", "Tu"]);
+    let candidates = match (ku, tu) {
+        (Some(ku), Some(tu)) if ku > 0.0 && tu > 0.0 => TUNING_RULES
+            .iter()
+            .map(|&(method, p, i, d)| {
+                let kp = p * ku;
+                GainCandidate {
+                    method,
+                    gains: PidGains {
+                        kp,
+                        ki: kp / (i * tu),
+                        kd: kp * d * tu,
+                    },
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Some(AutotuneResults {
+        method: text("tuning_method"),
+        quality: text("quality"),
+        ku,
+        tu,
+        recommended,
+        original,
+        candidates,
+    })
+}
+
+/// Why `results` should not be applied on a roaster bounded by `max`.
+pub fn problems(results: &AutotuneResults, max: PidGains) -> Vec<String> {
+    let mut problems = Vec::new();
+    let gains = results.recommended;
+    for (name, value, limit) in [
+        ("kp", gains.kp, max.kp),
+        ("ki", gains.ki, max.ki),
+        ("kd", gains.kd, max.kd),
+    ] {
+        if value < 0.0 {
+            problems.push(format!("{name} {value} is negative"));
+        } else if value > limit {
+            problems.push(format!("{name} {value} exceeds the limit of {limit}"));
+        }
+    }
+    if gains.kp == 0.0 {
+        problems.push("kp is zero".to_string());
+    }
+    for (name, value) in [("ku", results.ku), ("tu", results.tu)] {
+        if let Some(value) = value.filter(|v| *v <= 0.0) {
+            problems.push(format!("{name} {value} is not positive"));
+        }
+    }
+    if results.quality.as_deref() == Some("poor") {
+        problems.push("the firmware rated the run poor".to_string());
+    }
+    problems
+}
+
+/// Check `results` (the device's latest payload, if any) against the
+/// roaster's limits.
+pub async fn check(
+    db: &SqlitePool,
+    device_id: &str,
+    results: Option<&Value>,
+) -> Result<AutotuneCheck> {
+    let limits = gain_limits(db, device_id).await?;
+    let max_gains = limits.as_ref().map_or(DEFAULT_MAX_GAINS, |l| PidGains {
+        kp: l.max_kp,
+        ki: l.max_ki,
+        kd: l.max_kd,
+    });
+    let parsed = results.and_then(parse_results);
+    let problems = match (results, &parsed) {
+        (_, Some(parsed)) => problems(parsed, max_gains),
+        (Some(_), None) => vec!["the results carry no recommended gains".to_string()],
+        (None, None) => Vec::new(),
+    };
+    Ok(AutotuneCheck {
+        device_id: device_id.to_string(),
+        results: parsed,
+        max_gains,
+        custom_limits: limits.is_some(),
+        problems,
+    })
+}
+
+pub async fn gain_limits(db: &SqlitePool, device_id: &str) -> Result<Option<AutotuneGainLimits>> {
+    let limits = sqlx::query_as::<_, AutotuneGainLimits>(
+        "SELECT * FROM autotune_gain_limits WHERE device_id = ?",
+    )
+    .bind(device_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(limits)
+}
+
+pub async fn set_gain_limits(
+    db: &SqlitePool,
+    device_id: &str,
+    max: PidGains,
+) -> Result<AutotuneGainLimits> {
+    let limits = sqlx::query_as::<_, AutotuneGainLimits>(
+        r#"
+        INSERT INTO autotune_gain_limits (device_id, max_kp, max_ki, max_kd, updated_at)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(device_id) DO UPDATE SET
+            max_kp = excluded.max_kp,
+            max_ki = excluded.max_ki,
+            max_kd = excluded.max_kd,
+            updated_at = excluded.updated_at
+        RETURNING *
+        "#,
+    )
+    .bind(device_id)
+    .bind(max.kp)
+    .bind(max.ki)
+    .bind(max.kd)
+    .bind(Utc::now())
+    .fetch_one(db)
+    .await?;
+    Ok(limits)
+}
+
+/// Back to [`DEFAULT_MAX_GAINS`]. Returns whether the roaster had its own.
+pub async fn remove_gain_limits(db: &SqlitePool, device_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM autotune_gain_limits WHERE device_id = ?")
+        .bind(device_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_check_flags_pathological_gains() {
+        let db = crate::init_memory_db().await.unwrap();
+        let sane = json!({
+            "recommended_kp": 8.0, "recommended_ki": 0.4, "recommended_kd": 30.0,
+            "tuning_method": "zn_classic", "quality": "good",
+            "ultimate_gain": 10.0, "oscillation_period": 40.0,
+        });
+        let checked = check(&db, "dev1", Some(&sane)).await.unwrap();
+        assert!(checked.problems.is_empty(), "{:?}", checked.problems);
+        let results = checked.results.unwrap();
+        assert_eq!(results.method.as_deref(), Some("zn_classic"));
+        assert!(results.original.is_none());
+        let zn = &results.candidates[0];
+        assert_eq!(
+            (zn.method, zn.gains.kp, zn.gains.kd),
+            ("zn_classic", 6.0, 30.0)
+        );
+
+        let garbage = json!({"Kp": 250.0, "Ki": -1.0, "Kd": 30.0, "Ku": 0.0, "quality": "poor"});
+        let checked = check(&db, "dev1", Some(&garbage)).await.unwrap();
+        assert_eq!(checked.problems.len(), 4, "{:?}", checked.problems);
+        assert!(checked.problems[0].starts_with("kp 250"));
+
+        // A roaster with its own, tighter limits
+        set_gain_limits(
+            &db,
+            "dev1",
+            PidGains {
+                kp: 5.0,
+                ki: 1.0,
+                kd: 50.0,
+            },
+        )
+        .await
+        .unwrap();
+        let checked = check(&db, "dev1", Some(&sane)).await.unwrap();
+        assert!(checked.custom_limits);
+        assert_eq!(checked.problems, ["kp 8 exceeds the limit of 5"]);
+        assert!(remove_gain_limits(&db, "dev1").await.unwrap());
+
+        let checked = check(&db, "dev1", Some(&json!({"kp": 12.5})))
+            .await
+            .unwrap();
+        assert!(checked.results.is_none() && checked.problems.len() == 1);
+        assert!(check(&db, "dev1", None).await.unwrap().problems.is_empty());
+    }
+}
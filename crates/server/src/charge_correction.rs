@@ -0,0 +1,300 @@
+//! Charge temp and heater cap corrections for the green bean being roasted.
+//!
+//! Wetter and denser beans take up heat more slowly, so a profile dialed in
+//! on one lot charges too cool on the next. The correction tables map a
+//! bean's measured moisture and density to deltas on the profile's charge
+//! temp and heater cap; the adjustment actually used is recorded on the
+//! session so the tables can be checked against how the roasts went.
+
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::models::{
+    ChargeAdjustment, ChargeCorrection, CorrectionFactor, RoastProfile, SessionChargeAdjustment,
+};
+
+/// Largest charge temp delta (°C) a single band may apply.
+const MAX_CHARGE_TEMP_DELTA: f32 = 50.0;
+
+pub async fn corrections(db: &SqlitePool) -> Result<Vec<ChargeCorrection>> {
+    let corrections = sqlx::query_as::<_, ChargeCorrection>(
+        "SELECT * FROM charge_corrections ORDER BY factor, min_value IS NOT NULL, min_value",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(corrections)
+}
+
+/// Replace every correction table with `corrections`.
+pub async fn replace_corrections(
+    db: &SqlitePool,
+    bands: &[ChargeCorrection],
+) -> Result<Vec<ChargeCorrection>> {
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM charge_corrections")
+        .execute(&mut *tx)
+        .await?;
+    for c in bands {
+        sqlx::query(
+            r#"
+            INSERT INTO charge_corrections
+                (factor, min_value, max_value, charge_temp_delta, heater_cap_delta)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(c.factor)
+        .bind(c.min_value)
+        .bind(c.max_value)
+        .bind(c.charge_temp_delta)
+        .bind(c.heater_cap_delta)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    corrections(db).await
+}
+
+/// Check bands are well formed and don't overlap within a table.
+pub fn validate(corrections: &[ChargeCorrection]) -> Result<(), String> {
+    for c in corrections {
+        let bounds = [c.min_value, c.max_value];
+        if bounds.iter().flatten().any(|v| !v.is_finite()) {
+            return Err(format!("{} band bounds must be numbers", c.factor));
+        }
+        if let (Some(min), Some(max)) = (c.min_value, c.max_value) {
+            if min >= max {
+                return Err(format!(
+                    "{} band min_value {min} must be below max_value {max}",
+                    c.factor
+                ));
+            }
+        }
+        if !(c.charge_temp_delta.is_finite() && c.charge_temp_delta.abs() <= MAX_CHARGE_TEMP_DELTA)
+        {
+            return Err(format!(
+                "charge_temp_delta must be between -{MAX_CHARGE_TEMP_DELTA} and {MAX_CHARGE_TEMP_DELTA} C"
+            ));
+        }
+        if !(c.heater_cap_delta.is_finite() && c.heater_cap_delta.abs() <= 100.0) {
+            return Err("heater_cap_delta must be between -100 and 100".to_string());
+        }
+    }
+    for factor in [CorrectionFactor::Moisture, CorrectionFactor::Density] {
+        let mut bands: Vec<_> = corrections.iter().filter(|c| c.factor == factor).collect();
+        bands.sort_by(|a, b| {
+            let min = |c: &ChargeCorrection| c.min_value.unwrap_or(f32::NEG_INFINITY);
+            min(a).total_cmp(&min(b))
+        });
+        for pair in bands.windows(2) {
+            let overlaps = match (pair[0].max_value, pair[1].min_value) {
+                (Some(max), Some(min)) => max > min,
+                _ => true,
+            };
+            if overlaps {
+                return Err(format!("{factor} bands overlap"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Correct `profile` for a bean with the given measurements. A measurement
+/// outside every band of its table, or not given, changes nothing.
+pub fn adjust(
+    profile: &RoastProfile,
+    moisture_pct: Option<f32>,
+    density: Option<f32>,
+    corrections: &[ChargeCorrection],
+) -> ChargeAdjustment {
+    let applied: Vec<ChargeCorrection> = [
+        (CorrectionFactor::Moisture, moisture_pct),
+        (CorrectionFactor::Density, density),
+    ]
+    .into_iter()
+    .filter_map(|(factor, value)| {
+        let value = value?;
+        corrections
+            .iter()
+            .find(|c| c.factor == factor && c.contains(value))
+            .cloned()
+    })
+    .collect();
+    let charge_temp_delta = applied.iter().map(|c| c.charge_temp_delta).sum::<f32>();
+    let heater_cap_delta = applied.iter().map(|c| c.heater_cap_delta).sum::<f32>();
+    ChargeAdjustment {
+        profile_id: profile.id.clone(),
+        moisture_pct,
+        density,
+        base_charge_temp: profile.charge_temp,
+        base_heater_cap: profile.heater_cap,
+        charge_temp: profile.charge_temp.map(|t| t + charge_temp_delta),
+        heater_cap: profile
+            .heater_cap
+            .map(|cap| (cap as f32 + heater_cap_delta).round().clamp(0.0, 100.0) as i32),
+        charge_temp_delta,
+        heater_cap_delta,
+        applied,
+    }
+}
+
+/// Record `adjustment` as the one `session_id` is roasted with, replacing
+/// any earlier one.
+pub async fn record(
+    db: &SqlitePool,
+    session_id: &str,
+    adjustment: &ChargeAdjustment,
+) -> Result<SessionChargeAdjustment> {
+    let recorded = sqlx::query_as::<_, SessionChargeAdjustment>(
+        r#"
+        INSERT INTO session_charge_adjustments (
+            session_id, profile_id, moisture_pct, density,
+            charge_temp_delta, heater_cap_delta, adjustment, created_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(session_id) DO UPDATE SET
+            profile_id = excluded.profile_id,
+            moisture_pct = excluded.moisture_pct,
+            density = excluded.density,
+            charge_temp_delta = excluded.charge_temp_delta,
+            heater_cap_delta = excluded.heater_cap_delta,
+            adjustment = excluded.adjustment,
+            created_at = excluded.created_at
+        RETURNING session_id, adjustment, created_at
+        "#,
+    )
+    .bind(session_id)
+    .bind(&adjustment.profile_id)
+    .bind(adjustment.moisture_pct)
+    .bind(adjustment.density)
+    .bind(adjustment.charge_temp_delta)
+    .bind(adjustment.heater_cap_delta)
+    .bind(serde_json::to_string(adjustment)?)
+    .bind(Utc::now())
+    .fetch_one(db)
+    .await?;
+    Ok(recorded)
+}
+
+pub async fn for_session(
+    db: &SqlitePool,
+    session_id: &str,
+) -> Result<Option<SessionChargeAdjustment>> {
+    let recorded = sqlx::query_as::<_, SessionChargeAdjustment>(
+        "SELECT session_id, adjustment, created_at FROM session_charge_adjustments WHERE session_id = ?",
+    )
+    .bind(session_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(recorded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateProfilePointRequest, CreateProfileRequest, CreateSessionRequest};
+    use crate::services::RoastSessionService;
+
+    fn band(
+        factor: CorrectionFactor,
+        min_value: Option<f32>,
+        max_value: Option<f32>,
+        charge_temp_delta: f32,
+        heater_cap_delta: f32,
+    ) -> ChargeCorrection {
+        ChargeCorrection {
+            factor,
+            min_value,
+            max_value,
+            charge_temp_delta,
+            heater_cap_delta,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adjust_applies_one_band_per_table() {
+        let db = crate::init_memory_db().await.unwrap();
+        let tables = vec![
+            band(CorrectionFactor::Moisture, None, Some(10.0), -4.0, -5.0),
+            band(CorrectionFactor::Moisture, Some(12.0), None, 6.0, 5.0),
+            band(CorrectionFactor::Density, Some(750.0), None, 5.0, 8.0),
+        ];
+        validate(&tables).unwrap();
+        let mut overlapping = tables.clone();
+        overlapping.push(band(
+            CorrectionFactor::Moisture,
+            Some(9.0),
+            Some(11.0),
+            0.0,
+            0.0,
+        ));
+        assert_eq!(
+            validate(&overlapping).unwrap_err(),
+            "moisture bands overlap"
+        );
+        let stored = replace_corrections(&db, &tables).await.unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[0].factor, CorrectionFactor::Density);
+
+        let service = RoastSessionService::new(db.clone());
+        let profile = service
+            .create_profile(CreateProfileRequest {
+                name: "House".to_string(),
+                description: None,
+                target_total_time: None,
+                target_first_crack: None,
+                target_end_temp: None,
+                preheat_temp: None,
+                charge_temp: Some(190.0),
+                batch_size_g: None,
+                heater_cap: Some(90),
+                points: vec![CreateProfilePointRequest {
+                    time_seconds: 0,
+                    target_temp: 190.0,
+                    fan_speed: None,
+                    notes: None,
+                    target_env_temp: None,
+                    target_airflow: None,
+                }],
+            })
+            .await
+            .unwrap()
+            .profile;
+
+        // Wet and dense: both tables apply, the heater cap stops at 100
+        let wet = adjust(&profile, Some(12.5), Some(780.0), &stored);
+        assert_eq!(wet.charge_temp, Some(201.0));
+        assert_eq!(wet.heater_cap, Some(100));
+        assert_eq!(wet.applied.len(), 2);
+        // Between the moisture bands and without a density
+        let plain = adjust(&profile, Some(11.0), None, &stored);
+        assert_eq!(plain.charge_temp, Some(190.0));
+        assert!(plain.applied.is_empty());
+        let dry = adjust(&profile, Some(9.0), Some(700.0), &stored);
+        assert_eq!((dry.charge_temp, dry.heater_cap), (Some(186.0), Some(85)));
+
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "batch".to_string(),
+                device_id: "dev1".to_string(),
+                profile_id: Some(profile.id.clone()),
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: Default::default(),
+                bean_id: None,
+            })
+            .await
+            .unwrap();
+        assert!(for_session(&db, &session.id).await.unwrap().is_none());
+        record(&db, &session.id, &wet).await.unwrap();
+        let recorded = record(&db, &session.id, &dry).await.unwrap();
+        assert_eq!(recorded.adjustment, dry);
+        let recorded = for_session(&db, &session.id).await.unwrap().unwrap();
+        assert_eq!(recorded.adjustment.charge_temp_delta, -4.0);
+    }
+}
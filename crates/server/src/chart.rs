@@ -0,0 +1,290 @@
+//! Dashboard-ready chart payloads, so browsers draw a roast without
+//! reshaping its telemetry on every load.
+//!
+//! Curves are smoothed at full resolution, then decimated, and laid out
+//! column-wise: one `x` array of elapsed seconds shared by a `y` array per
+//! channel, `null` where a sample has no value. Channels no sample has are
+//! left out. Events become markers, and the drying, Maillard and
+//! development phases bands between the marked events. A session's profile
+//! is drawn as one more series, starting at its charge event.
+
+use crate::i18n::Locale;
+use crate::models::{
+    ChartAxis, ChartMarker, ChartPhase, ChartSeries, RoastEvent, RoastEventType, RoastPhase,
+    SessionChart, SessionTelemetry, SmoothingConfig,
+};
+use crate::profile_curve::ProfileCurve;
+use crate::services::decimate_telemetry;
+use crate::smoothing::smooth_telemetry;
+
+type Channel = (
+    &'static str,
+    Option<&'static str>,
+    ChartAxis,
+    fn(&SessionTelemetry) -> Option<f32>,
+);
+
+const CHANNELS: [Channel; 7] = [
+    ("bean_temp", Some("°C"), ChartAxis::Temperature, |p| {
+        p.bean_temp
+    }),
+    ("env_temp", Some("°C"), ChartAxis::Temperature, |p| {
+        p.env_temp
+    }),
+    ("setpoint", Some("°C"), ChartAxis::Temperature, |p| {
+        p.setpoint
+    }),
+    (
+        "rate_of_rise",
+        Some("°C/min"),
+        ChartAxis::RateOfRise,
+        |p| p.rate_of_rise,
+    ),
+    ("heater_pwm", Some("%"), ChartAxis::Output, |p| {
+        p.heater_pwm.map(|v| v as f32)
+    }),
+    ("fan_pwm", Some("pwm"), ChartAxis::Output, |p| {
+        p.fan_pwm.map(|v| v as f32)
+    }),
+    ("airflow", None, ChartAxis::Airflow, |p| p.airflow),
+];
+
+/// Shape a session's telemetry, ordered by elapsed time, and events into a
+/// chart of at most `max_points` samples. `profile` is the curve of the
+/// session's profile with the elapsed seconds it starts at.
+pub fn build_chart(
+    session_id: &str,
+    mut telemetry: Vec<SessionTelemetry>,
+    events: &[RoastEvent],
+    profile: Option<(&ProfileCurve, f32)>,
+    smoothing: Option<SmoothingConfig>,
+    max_points: usize,
+    locale: Locale,
+) -> SessionChart {
+    let total_points = telemetry.len();
+    if let Some(config) = &smoothing {
+        let smoothed = smooth_telemetry(&telemetry, config);
+        for (point, s) in telemetry.iter_mut().zip(smoothed) {
+            point.bean_temp = s.bean_temp;
+            point.env_temp = s.env_temp;
+            point.rate_of_rise = s.rate_of_rise;
+        }
+    }
+    let markers = events
+        .iter()
+        .map(|e| ChartMarker {
+            event_type: e.event_type.clone(),
+            label: locale.event_name(&e.event_type).to_string(),
+            x: e.elapsed_seconds,
+            y: e.temperature
+                .or_else(|| bean_temp_at(&telemetry, e.elapsed_seconds)),
+            notes: e.notes.clone(),
+        })
+        .collect();
+    let last_x = telemetry.last().map(|p| p.elapsed_seconds);
+    let phases = phase_bands(events, last_x, locale);
+
+    let telemetry = decimate_telemetry(telemetry, max_points);
+    let mut series: Vec<ChartSeries> = CHANNELS
+        .iter()
+        .filter(|(_, _, _, value)| telemetry.iter().any(|p| value(p).is_some()))
+        .map(|(key, unit, axis, value)| ChartSeries {
+            key,
+            label: locale.t(&format!("series.{}", key)).to_string(),
+            // Smoothed RoR follows the roaster's unit, raw RoR is as reported
+            unit: match (&smoothing, axis) {
+                (Some(config), ChartAxis::RateOfRise) => Some(config.ror_unit.label()),
+                _ => *unit,
+            },
+            axis: *axis,
+            y: telemetry.iter().map(value).collect(),
+        })
+        .collect();
+    if let Some((curve, zero)) = profile {
+        series.push(ChartSeries {
+            key: "profile_target",
+            label: locale.t("series.profile_target").to_string(),
+            unit: Some("°C"),
+            axis: ChartAxis::Temperature,
+            y: telemetry
+                .iter()
+                .map(|p| {
+                    (p.elapsed_seconds >= zero).then(|| curve.target_at(p.elapsed_seconds - zero))
+                })
+                .collect(),
+        });
+    }
+    SessionChart {
+        session_id: session_id.to_string(),
+        smoothing,
+        total_points,
+        x: telemetry.iter().map(|p| p.elapsed_seconds).collect(),
+        series,
+        markers,
+        phases,
+        profile_zero: profile.map(|(_, zero)| zero),
+    }
+}
+
+/// Bean temp of the last sample at or before `elapsed`.
+fn bean_temp_at(telemetry: &[SessionTelemetry], elapsed: f32) -> Option<f32> {
+    let i = telemetry.partition_point(|p| p.elapsed_seconds <= elapsed);
+    telemetry[..i].last().and_then(|p| p.bean_temp)
+}
+
+/// Drying from charge (else the session start) to drying end, Maillard from
+/// there to first crack, development from first crack to the drop (else the
+/// last sample). Phases whose bounding events are missing are left out.
+fn phase_bands(events: &[RoastEvent], last_x: Option<f32>, locale: Locale) -> Vec<ChartPhase> {
+    let at = |t: RoastEventType| {
+        events
+            .iter()
+            .find(|e| e.event_type == t)
+            .map(|e| e.elapsed_seconds)
+    };
+    let drying_end = at(RoastEventType::DryingEnd);
+    let first_crack = at(RoastEventType::FirstCrackStart);
+    let end = at(RoastEventType::Drop)
+        .or(at(RoastEventType::DropOut))
+        .or(last_x);
+    let bands = [
+        (
+            RoastPhase::Drying,
+            at(RoastEventType::Charge).or(Some(0.0)),
+            drying_end,
+        ),
+        (RoastPhase::Maillard, drying_end, first_crack),
+        (RoastPhase::Development, first_crack, end),
+    ];
+    bands
+        .into_iter()
+        .filter_map(|(phase, start, end)| {
+            let (start, end) = (start?, end?);
+            (end > start).then(|| ChartPhase {
+                phase,
+                label: locale.t(&format!("phase.{}", phase_key(phase))).to_string(),
+                start,
+                end,
+            })
+        })
+        .collect()
+}
+
+fn phase_key(phase: RoastPhase) -> &'static str {
+    match phase {
+        RoastPhase::Drying => "drying",
+        RoastPhase::Maillard => "maillard",
+        RoastPhase::Development => "development",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProfilePoint;
+    use chrono::Utc;
+
+    fn sample(elapsed: f32, bean_temp: f32) -> SessionTelemetry {
+        SessionTelemetry {
+            id: elapsed.to_string(),
+            session_id: "s1".to_string(),
+            timestamp: Utc::now(),
+            elapsed_seconds: elapsed,
+            bean_temp: Some(bean_temp),
+            env_temp: Some(bean_temp + 20.0),
+            rate_of_rise: None,
+            heater_pwm: Some(80),
+            fan_pwm: None,
+            setpoint: None,
+            airflow: None,
+        }
+    }
+
+    fn event(event_type: RoastEventType, elapsed: f32) -> RoastEvent {
+        RoastEvent {
+            id: elapsed.to_string(),
+            session_id: "s1".to_string(),
+            event_type,
+            elapsed_seconds: elapsed,
+            temperature: None,
+            notes: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_chart_series_markers_and_phases() {
+        let telemetry: Vec<_> = (0..=600)
+            .map(|t| sample(t as f32, 100.0 + t as f32 * 0.2))
+            .collect();
+        let events = [
+            event(RoastEventType::DryingEnd, 240.0),
+            event(RoastEventType::FirstCrackStart, 480.0),
+        ];
+        let chart = build_chart("s1", telemetry, &events, None, None, 50, Locale::De);
+
+        assert_eq!(chart.total_points, 601);
+        assert_eq!(chart.x.len(), 50);
+        assert_eq!((chart.x[0], chart.x[49]), (0.0, 600.0));
+        let keys: Vec<_> = chart.series.iter().map(|s| s.key).collect();
+        assert_eq!(keys, ["bean_temp", "env_temp", "heater_pwm"]);
+        assert!(chart.series.iter().all(|s| s.y.len() == 50));
+        assert_eq!(chart.series[0].label, "Bohnentemperatur");
+
+        assert_eq!(chart.markers[0].label, "Ende Trocknung");
+        assert_eq!(chart.markers[1].y, Some(196.0));
+        let bands: Vec<_> = chart
+            .phases
+            .iter()
+            .map(|p| (p.phase, p.start, p.end))
+            .collect();
+        assert_eq!(
+            bands,
+            [
+                (RoastPhase::Drying, 0.0, 240.0),
+                (RoastPhase::Maillard, 240.0, 480.0),
+                (RoastPhase::Development, 480.0, 600.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profile_series_starts_at_charge() {
+        let telemetry: Vec<_> = (0..=10).map(|i| sample(i as f32 * 60.0, 150.0)).collect();
+        let events = [
+            event(RoastEventType::Charge, 60.0),
+            event(RoastEventType::DryingEnd, 300.0),
+        ];
+        let point = |time_seconds: i32, target_temp: f32| ProfilePoint {
+            id: time_seconds.to_string(),
+            profile_id: "p1".to_string(),
+            time_seconds,
+            target_temp,
+            fan_speed: None,
+            notes: None,
+            created_at: Utc::now(),
+            target_env_temp: None,
+            target_airflow: None,
+        };
+        let points = [point(0, 100.0), point(600, 220.0)];
+        let curve = ProfileCurve::sample(&points, 1.0).unwrap();
+        let chart = build_chart(
+            "s1",
+            telemetry,
+            &events,
+            Some((&curve, 60.0)),
+            None,
+            100,
+            Locale::En,
+        );
+
+        let profile = chart.series.last().unwrap();
+        assert_eq!(profile.key, "profile_target");
+        assert_eq!(profile.y[..3], [None, Some(100.0), Some(112.0)]);
+        assert_eq!(chart.profile_zero, Some(60.0));
+        assert_eq!(
+            (chart.phases[0].phase, chart.phases[0].start),
+            (RoastPhase::Drying, 60.0)
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Leader election between server instances sharing an MQTT broker.
+//!
+//! With `RUSTROAST_CLUSTER=true`, every instance publishes a heartbeat on
+//! `rustroast/cluster/heartbeat` every `RUSTROAST_CLUSTER_HEARTBEAT_SECS`
+//! (default 2) and tracks those of its peers, forgetting a peer it has not
+//! heard from for `RUSTROAST_CLUSTER_LEASE_SECS` (default 6). The longest
+//! running live instance leads, ties broken by instance id, so a restarted
+//! instance does not take over from a healthy leader. A new instance stays
+//! a follower for its first lease, until it has heard from the others.
+//!
+//! Both roles ingest telemetry and serve the API. Only the leader runs the
+//! automation engine (which sends control commands) and the session MQTT
+//! export, so neither happens twice. Without clustering an instance is
+//! standalone and always acts as the leader.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rumqttc::QoS;
+use rustroast_core::cluster_heartbeat_topic;
+use rustroast_mqtt::MqttService;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_HEARTBEAT_SECS: u64 = 2;
+const DEFAULT_LEASE_SECS: u64 = 6;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterRole {
+    Standalone,
+    Leader,
+    Follower,
+}
+
+/// Published on the heartbeat topic by every instance.
+#[derive(Debug, Serialize, Deserialize)]
+struct Heartbeat {
+    instance_id: String,
+    /// Unix milliseconds the instance started at.
+    started_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerStatus {
+    pub instance_id: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub last_seen_ms_ago: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterStatus {
+    pub instance_id: String,
+    pub role: ClusterRole,
+    /// `None` while no leader is known yet.
+    pub leader_id: Option<String>,
+    pub peers: Vec<PeerStatus>,
+}
+
+struct Peer {
+    started_at: i64,
+    last_seen: Instant,
+}
+
+#[derive(Clone)]
+pub struct Cluster {
+    enabled: bool,
+    instance_id: String,
+    started_at: i64,
+    booted: Instant,
+    heartbeat: Duration,
+    lease: Duration,
+    peers: Arc<Mutex<HashMap<String, Peer>>>,
+    leader: Arc<AtomicBool>,
+    leader_id: Arc<Mutex<Option<String>>>,
+}
+
+impl Cluster {
+    pub fn new(enabled: bool, instance_id: String, heartbeat: Duration, lease: Duration) -> Self {
+        Self {
+            enabled,
+            leader_id: Arc::new(Mutex::new((!enabled).then(|| instance_id.clone()))),
+            instance_id,
+            started_at: Utc::now().timestamp_millis(),
+            booted: Instant::now(),
+            heartbeat,
+            lease,
+            peers: Arc::new(Mutex::new(HashMap::new())),
+            leader: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Reads `RUSTROAST_CLUSTER`, `RUSTROAST_INSTANCE_ID` (default a random
+    /// id), `RUSTROAST_CLUSTER_HEARTBEAT_SECS` and `RUSTROAST_CLUSTER_LEASE_SECS`.
+    pub fn from_env() -> Self {
+        let enabled = std::env::var("RUSTROAST_CLUSTER")
+            .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        let instance_id = std::env::var("RUSTROAST_INSTANCE_ID")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let secs = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        let heartbeat = secs("RUSTROAST_CLUSTER_HEARTBEAT_SECS", DEFAULT_HEARTBEAT_SECS);
+        let lease = secs("RUSTROAST_CLUSTER_LEASE_SECS", DEFAULT_LEASE_SECS).max(heartbeat * 2);
+        Self::new(
+            enabled,
+            instance_id,
+            Duration::from_secs(heartbeat),
+            Duration::from_secs(lease),
+        )
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether this instance should run leader-only work.
+    pub fn is_leader(&self) -> bool {
+        !self.enabled || self.leader.load(Ordering::Relaxed)
+    }
+
+    pub fn role(&self) -> ClusterRole {
+        match (self.enabled, self.is_leader()) {
+            (false, _) => ClusterRole::Standalone,
+            (true, true) => ClusterRole::Leader,
+            (true, false) => ClusterRole::Follower,
+        }
+    }
+
+    /// Record a heartbeat received on the coordination topic.
+    pub fn observe(&self, payload: &[u8], now: Instant) {
+        let Ok(heartbeat) = serde_json::from_slice::<Heartbeat>(payload) else {
+            tracing::debug!("Ignoring malformed cluster heartbeat");
+            return;
+        };
+        if heartbeat.instance_id == self.instance_id {
+            return;
+        }
+        self.peers.lock().unwrap().insert(
+            heartbeat.instance_id,
+            Peer {
+                started_at: heartbeat.started_at,
+                last_seen: now,
+            },
+        );
+    }
+
+    /// Drop expired peers and decide the leader. Returns the new role when
+    /// it changed.
+    fn elect(&self, now: Instant) -> Option<ClusterRole> {
+        if !self.enabled {
+            return None;
+        }
+        let mut peers = self.peers.lock().unwrap();
+        peers.retain(|_, p| now.duration_since(p.last_seen) < self.lease);
+        let oldest = peers
+            .iter()
+            .map(|(id, p)| (p.started_at, id.as_str()))
+            .min();
+        let leader_id = if now.duration_since(self.booted) < self.lease {
+            // Still listening for the others
+            oldest.map(|(_, id)| id.to_string())
+        } else {
+            let me = (self.started_at, self.instance_id.as_str());
+            Some(oldest.map_or(me, |o| o.min(me)).1.to_string())
+        };
+        let leading = leader_id.as_deref() == Some(self.instance_id.as_str());
+        *self.leader_id.lock().unwrap() = leader_id;
+        let was_leading = self.leader.swap(leading, Ordering::Relaxed);
+        (was_leading != leading).then(|| self.role())
+    }
+
+    pub fn status(&self, now: Instant) -> ClusterStatus {
+        let mut peers: Vec<PeerStatus> = self
+            .peers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, p)| PeerStatus {
+                instance_id: id.clone(),
+                started_at: Utc.timestamp_millis_opt(p.started_at).single(),
+                last_seen_ms_ago: now.duration_since(p.last_seen).as_millis() as u64,
+            })
+            .collect();
+        peers.sort_by(|a, b| a.instance_id.cmp(&b.instance_id));
+        ClusterStatus {
+            instance_id: self.instance_id.clone(),
+            role: self.role(),
+            leader_id: self.leader_id.lock().unwrap().clone(),
+            peers,
+        }
+    }
+
+    /// Publish heartbeats and re-elect every heartbeat interval.
+    pub async fn run(self, mqtt: MqttService) {
+        let topic = cluster_heartbeat_topic();
+        if let Err(e) = mqtt.subscribe(topic, QoS::AtMostOnce).await {
+            tracing::warn!(?e, "Failed to subscribe to cluster heartbeats");
+        }
+        let heartbeat = serde_json::to_vec(&Heartbeat {
+            instance_id: self.instance_id.clone(),
+            started_at: self.started_at,
+        })
+        .unwrap_or_default();
+        tracing::info!(instance_id = %self.instance_id, "Joining cluster as follower");
+        let mut ticker = tokio::time::interval(self.heartbeat);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = mqtt
+                .publish(topic, QoS::AtMostOnce, false, heartbeat.clone())
+                .await
+            {
+                tracing::warn!(?e, "Failed to publish cluster heartbeat");
+            }
+            if let Some(role) = self.elect(Instant::now()) {
+                let leader_id = self.leader_id.lock().unwrap().clone();
+                tracing::info!(instance_id = %self.instance_id, ?role, ?leader_id, "Cluster role changed");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(id: &str, started_at: i64) -> Vec<u8> {
+        serde_json::to_vec(&Heartbeat {
+            instance_id: id.to_string(),
+            started_at,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_oldest_live_instance_leads() {
+        let lease = Duration::from_secs(6);
+        let cluster = Cluster::new(true, "b".into(), Duration::from_secs(2), lease);
+        let t0 = cluster.booted;
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // Listening during the first lease, even when alone
+        assert_eq!(cluster.role(), ClusterRole::Follower);
+        assert_eq!(cluster.elect(at(1)), None);
+        assert!(!cluster.is_leader());
+
+        // An older peer keeps leading once we are settled
+        cluster.observe(&heartbeat("a", cluster.started_at - 60_000), at(5));
+        cluster.observe(&heartbeat("b", 0), at(5));
+        cluster.observe(b"not json", at(5));
+        assert_eq!(cluster.elect(at(7)), None);
+        assert_eq!(cluster.status(at(7)).leader_id.as_deref(), Some("a"));
+        // A newer peer does not take over
+        cluster.observe(&heartbeat("c", cluster.started_at + 1_000), at(8));
+        cluster.observe(&heartbeat("a", cluster.started_at - 60_000), at(8));
+        assert_eq!(cluster.elect(at(9)), None);
+
+        // The leader's heartbeats stop and we, older than c, take over
+        cluster.observe(&heartbeat("c", cluster.started_at + 1_000), at(13));
+        assert_eq!(cluster.elect(at(14)), Some(ClusterRole::Leader));
+        assert!(cluster.is_leader());
+        let status = cluster.status(at(14));
+        assert_eq!(status.leader_id.as_deref(), Some("b"));
+        assert_eq!(status.peers.len(), 1);
+        assert_eq!(status.peers[0].instance_id, "c");
+
+        let standalone = Cluster::new(false, "x".into(), Duration::from_secs(2), lease);
+        assert_eq!(standalone.role(), ClusterRole::Standalone);
+        assert!(standalone.is_leader());
+    }
+}
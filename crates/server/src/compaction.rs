@@ -0,0 +1,276 @@
+//! Compaction of old raw telemetry into per-minute rollups.
+//!
+//! Raw rows older than `RUSTROAST_TELEMETRY_COMPACT_AFTER_DAYS` (default 7,
+//! 0 turns compaction off and deletes them at `RUSTROAST_DB_RETENTION_SECS`
+//! as before) are averaged into one `telemetry_rollup` row per device and
+//! minute, then deleted. Numeric fields are averaged and other fields keep
+//! their last value, so a rollup reads like a telemetry payload. Rollups are
+//! kept for `RUSTROAST_TELEMETRY_ROLLUP_RETENTION_DAYS` (default 0, forever).
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::SqlitePool;
+
+const BUCKET_SECS: i64 = 60;
+/// Each transaction compacts at most this much of one device's telemetry.
+const WINDOW_SECS: i64 = 3600;
+const DEFAULT_COMPACT_AFTER_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompactionConfig {
+    /// Age after which raw telemetry is compacted, `None` when disabled.
+    pub compact_after_secs: Option<i64>,
+    /// Age after which rollups are deleted, `None` to keep them.
+    pub rollup_retention_secs: Option<i64>,
+}
+
+impl CompactionConfig {
+    pub fn from_env() -> Self {
+        let days = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(default)
+        };
+        let secs = |days: i64| (days > 0).then_some(days * 86_400);
+        Self {
+            compact_after_secs: secs(days(
+                "RUSTROAST_TELEMETRY_COMPACT_AFTER_DAYS",
+                DEFAULT_COMPACT_AFTER_DAYS,
+            )),
+            rollup_retention_secs: secs(days("RUSTROAST_TELEMETRY_ROLLUP_RETENTION_DAYS", 0)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct CompactionStats {
+    /// Raw rows compacted and deleted.
+    pub raw_rows: u64,
+    /// Rollups written, including ones merged with late raw rows.
+    pub rollups: u64,
+}
+
+/// Devices with raw telemetry before `cutoff` (unix seconds).
+pub async fn devices_to_compact(db: &SqlitePool, cutoff: i64) -> Result<Vec<String>> {
+    let devices =
+        sqlx::query_scalar::<_, String>("SELECT DISTINCT device_id FROM telemetry WHERE ts < ?")
+            .bind(whole_minute(cutoff))
+            .fetch_all(db)
+            .await?;
+    Ok(devices)
+}
+
+/// Compact every device's raw telemetry before `cutoff`.
+pub async fn compact_telemetry(db: &SqlitePool, cutoff: i64) -> Result<CompactionStats> {
+    let mut stats = CompactionStats::default();
+    for device_id in devices_to_compact(db, cutoff).await? {
+        let device = compact_device(db, &device_id, cutoff).await?;
+        stats.raw_rows += device.raw_rows;
+        stats.rollups += device.rollups;
+    }
+    Ok(stats)
+}
+
+/// Compact a device's raw telemetry before `cutoff`, rounded down to a
+/// whole minute so no minute is split between raw rows and a rollup. Raw
+/// rows arriving late for a compacted minute are merged into its rollup.
+pub async fn compact_device(
+    db: &SqlitePool,
+    device_id: &str,
+    cutoff: i64,
+) -> Result<CompactionStats> {
+    let cutoff = whole_minute(cutoff);
+    let mut stats = CompactionStats::default();
+    loop {
+        let first: Option<i64> =
+            sqlx::query_scalar("SELECT MIN(ts) FROM telemetry WHERE device_id = ? AND ts < ?")
+                .bind(device_id)
+                .bind(cutoff)
+                .fetch_one(db)
+                .await?;
+        let Some(first) = first else {
+            break;
+        };
+        let start = whole_minute(first);
+        let end = (start + WINDOW_SECS).min(cutoff);
+
+        let mut tx = db.begin().await?;
+        let existing = sqlx::query_as::<_, (i64, i64, String)>(
+            "SELECT ts, samples, payload FROM telemetry_rollup WHERE device_id = ? AND ts >= ? AND ts < ?",
+        )
+        .bind(device_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(&mut *tx)
+        .await?;
+        let raw = sqlx::query_as::<_, (i64, String)>(
+            "SELECT ts, payload FROM telemetry WHERE device_id = ? AND ts >= ? AND ts < ? ORDER BY ts",
+        )
+        .bind(device_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut buckets: BTreeMap<i64, Rollup> = BTreeMap::new();
+        for (ts, samples, payload) in &existing {
+            buckets.entry(*ts).or_default().add(payload, *samples);
+        }
+        for (ts, payload) in &raw {
+            buckets
+                .entry(whole_minute(*ts))
+                .or_default()
+                .add(payload, 1);
+        }
+        for (ts, rollup) in &buckets {
+            sqlx::query(
+                "INSERT OR REPLACE INTO telemetry_rollup (device_id, ts, samples, payload) VALUES (?, ?, ?, ?)",
+            )
+            .bind(device_id)
+            .bind(ts)
+            .bind(rollup.samples)
+            .bind(rollup.payload().to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query("DELETE FROM telemetry WHERE device_id = ? AND ts >= ? AND ts < ?")
+            .bind(device_id)
+            .bind(start)
+            .bind(end)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        stats.raw_rows += raw.len() as u64;
+        stats.rollups += buckets.len() as u64;
+    }
+    Ok(stats)
+}
+
+fn whole_minute(ts: i64) -> i64 {
+    ts - ts.rem_euclid(BUCKET_SECS)
+}
+
+/// One minute of telemetry being averaged.
+#[derive(Default)]
+struct Rollup {
+    samples: i64,
+    /// Weighted sum and weight per numeric field.
+    sums: BTreeMap<String, (f64, i64)>,
+    last: Map<String, Value>,
+}
+
+impl Rollup {
+    /// Add a payload standing for `weight` samples. Unparseable payloads
+    /// still count as samples.
+    fn add(&mut self, payload: &str, weight: i64) {
+        self.samples += weight;
+        let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(payload) else {
+            return;
+        };
+        for (key, value) in fields {
+            match value.as_f64() {
+                Some(v) => {
+                    let (sum, n) = self.sums.entry(key).or_default();
+                    *sum += v * weight as f64;
+                    *n += weight;
+                }
+                None => {
+                    self.last.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn payload(&self) -> Value {
+        let mut fields = self.last.clone();
+        for (key, (sum, n)) in &self.sums {
+            let mean = (sum / *n as f64 * 1000.0).round() / 1000.0;
+            fields.insert(key.clone(), serde_json::json!(mean));
+        }
+        Value::Object(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert(db: &SqlitePool, ts: i64, payload: serde_json::Value) {
+        sqlx::query("INSERT INTO telemetry (device_id, ts, payload) VALUES ('dev1', ?, ?)")
+            .bind(ts)
+            .bind(payload.to_string())
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn rollups(db: &SqlitePool) -> Vec<(i64, i64, serde_json::Value)> {
+        sqlx::query_as::<_, (i64, i64, String)>(
+            "SELECT ts, samples, payload FROM telemetry_rollup ORDER BY ts",
+        )
+        .fetch_all(db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(ts, n, p)| (ts, n, serde_json::from_str(&p).unwrap()))
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn test_old_telemetry_compacts_to_minute_rollups() {
+        let db = crate::init_memory_db().await.unwrap();
+        let t0 = 1_700_000_040; // a whole minute
+        for (dt, bt, mode) in [
+            (0, 100.0, "auto"),
+            (20, 110.0, "auto"),
+            (40, 120.0, "manual"),
+        ] {
+            insert(
+                &db,
+                t0 + dt,
+                serde_json::json!({"beanTemp": bt, "heaterPWM": 50, "controlMode": mode}),
+            )
+            .await;
+        }
+        insert(&db, t0 + 60, serde_json::json!({"beanTemp": 130.0})).await;
+        // In the minute the cutoff splits, and newer than the cutoff
+        insert(&db, t0 + 7330, serde_json::json!({"beanTemp": 200.0})).await;
+        insert(&db, t0 + 7350, serde_json::json!({"beanTemp": 201.0})).await;
+
+        let stats = compact_telemetry(&db, t0 + 7340).await.unwrap();
+        assert_eq!(
+            stats,
+            CompactionStats {
+                raw_rows: 4,
+                rollups: 2
+            }
+        );
+        let rolled = rollups(&db).await;
+        assert_eq!(rolled.len(), 2);
+        assert_eq!(rolled[0].0, t0);
+        assert_eq!(rolled[0].1, 3);
+        assert_eq!(
+            rolled[0].2,
+            serde_json::json!({"beanTemp": 110.0, "heaterPWM": 50.0, "controlMode": "manual"})
+        );
+        assert_eq!((rolled[1].0, rolled[1].1), (t0 + 60, 1));
+        let raw: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM telemetry")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(raw, 2);
+
+        // A late row merges into its minute's rollup
+        insert(&db, t0 + 50, serde_json::json!({"beanTemp": 150.0})).await;
+        compact_telemetry(&db, t0 + 7340).await.unwrap();
+        let rolled = rollups(&db).await;
+        assert_eq!(rolled[0].1, 4);
+        assert_eq!(rolled[0].2["beanTemp"], serde_json::json!(120.0));
+        assert_eq!(rolled[0].2["heaterPWM"], serde_json::json!(50.0));
+    }
+}
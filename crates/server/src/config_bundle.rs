@@ -0,0 +1,385 @@
+//! Automations, alert limits and preheat safety limits as one document, to
+//! keep in git or copy to another instance.
+//!
+//! Export writes the stored configuration as a [`ConfigBundle`]. Import
+//! compares a bundle with it section by section, keying automations by
+//! their profile or roaster and name and everything else by roaster, and
+//! applies the differences in one transaction. A dry run reports the same
+//! changes and rolls them back.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{SqliteConnection, SqlitePool};
+use uuid::Uuid;
+
+use crate::models::{
+    AlertLimit, AlertLimitConfig, AutomationConfig, AutomationRule, BatchPreheatSettings,
+    ConfigBundle, ConfigChange, ConfigChangeAction, ConfigImportReport, PreheatConfig,
+    CONFIG_BUNDLE_VERSION,
+};
+
+#[derive(Clone)]
+pub struct ConfigBundles {
+    db: SqlitePool,
+}
+
+impl ConfigBundles {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Every section of the stored configuration, sorted by key so that
+    /// exports diff cleanly.
+    pub async fn export(&self) -> Result<ConfigBundle> {
+        let mut conn = self.db.acquire().await?;
+        let automations = current_automations(&mut conn).await?;
+        let alert_limits = current_alert_limits(&mut conn).await?;
+        let preheat = current_preheat(&mut conn).await?;
+        Ok(ConfigBundle {
+            version: CONFIG_BUNDLE_VERSION,
+            automations: Some(automations.into_values().map(|(_, a)| a).collect()),
+            alert_limits: Some(alert_limits.into_values().collect()),
+            preheat: Some(preheat.into_values().collect()),
+        })
+    }
+
+    /// Make the stored configuration match the sections present in
+    /// `bundle`, or with `dry_run` only report what that would change.
+    pub async fn import(&self, bundle: &ConfigBundle, dry_run: bool) -> Result<ConfigImportReport> {
+        let mut tx = self.db.begin().await?;
+        let mut changes = Vec::new();
+        if let Some(automations) = &bundle.automations {
+            sync_automations(&mut tx, automations, &mut changes).await?;
+        }
+        if let Some(limits) = &bundle.alert_limits {
+            sync_alert_limits(&mut tx, limits, &mut changes).await?;
+        }
+        if let Some(preheat) = &bundle.preheat {
+            sync_preheat(&mut tx, preheat, &mut changes).await?;
+        }
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+        Ok(ConfigImportReport { dry_run, changes })
+    }
+}
+
+/// Stored automations by key, with their ids.
+async fn current_automations(
+    conn: &mut SqliteConnection,
+) -> Result<BTreeMap<String, (String, AutomationConfig)>> {
+    let rules = sqlx::query_as::<_, AutomationRule>("SELECT * FROM automation_rules")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rules
+        .into_iter()
+        .map(|rule| {
+            let id = rule.id.clone();
+            let config = AutomationConfig::from(rule);
+            (config.key(), (id, config))
+        })
+        .collect())
+}
+
+async fn current_alert_limits(
+    conn: &mut SqliteConnection,
+) -> Result<BTreeMap<String, AlertLimitConfig>> {
+    let limits = sqlx::query_as::<_, AlertLimit>("SELECT * FROM alert_limits")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(limits
+        .into_iter()
+        .map(|l| {
+            let config = AlertLimitConfig {
+                device_id: l.device_id,
+                max_bean_temp: l.max_bean_temp,
+            };
+            (config.device_id.clone(), config)
+        })
+        .collect())
+}
+
+async fn current_preheat(conn: &mut SqliteConnection) -> Result<BTreeMap<String, PreheatConfig>> {
+    let settings =
+        sqlx::query_as::<_, BatchPreheatSettings>("SELECT * FROM batch_preheat_settings")
+            .fetch_all(&mut *conn)
+            .await?;
+    Ok(settings
+        .into_iter()
+        .map(|s| {
+            let config = PreheatConfig {
+                device_id: s.device_id,
+                enabled: s.enabled,
+                timeout_secs: s.timeout_secs,
+                max_temp: s.max_temp,
+                ready_band: s.ready_band,
+            };
+            (config.device_id.clone(), config)
+        })
+        .collect())
+}
+
+fn change<T: Serialize>(
+    section: &str,
+    key: &str,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> ConfigChange {
+    let action = match (before, after) {
+        (None, _) => ConfigChangeAction::Add,
+        (Some(_), Some(_)) => ConfigChangeAction::Change,
+        (Some(_), None) => ConfigChangeAction::Remove,
+    };
+    let value = |v: Option<&T>| v.and_then(|v| serde_json::to_value(v).ok());
+    ConfigChange {
+        section: section.to_string(),
+        key: key.to_string(),
+        action,
+        before: value(before),
+        after: value(after),
+    }
+}
+
+async fn sync_automations(
+    conn: &mut SqliteConnection,
+    desired: &[AutomationConfig],
+    changes: &mut Vec<ConfigChange>,
+) -> Result<()> {
+    let mut current = current_automations(conn).await?;
+    for automation in desired {
+        let key = automation.key();
+        match current.remove(&key) {
+            Some((_, ref existing)) if existing == automation => {}
+            Some((id, existing)) => {
+                sqlx::query(
+                    r#"
+                    UPDATE automation_rules SET trigger_type = ?, trigger_value = ?,
+                        trigger_event = ?, command = ?, value = ?, relative = ?
+                    WHERE id = ?
+                    "#,
+                )
+                .bind(automation.trigger_type)
+                .bind(automation.trigger_value)
+                .bind(&automation.trigger_event)
+                .bind(automation.command)
+                .bind(automation.value)
+                .bind(automation.relative)
+                .bind(&id)
+                .execute(&mut *conn)
+                .await?;
+                changes.push(change(
+                    "automations",
+                    &key,
+                    Some(&existing),
+                    Some(automation),
+                ));
+            }
+            None => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO automation_rules (
+                        id, profile_id, device_id, name, trigger_type, trigger_value,
+                        trigger_event, command, value, relative, created_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                )
+                .bind(Uuid::new_v4().to_string())
+                .bind(&automation.profile_id)
+                .bind(&automation.device_id)
+                .bind(&automation.name)
+                .bind(automation.trigger_type)
+                .bind(automation.trigger_value)
+                .bind(&automation.trigger_event)
+                .bind(automation.command)
+                .bind(automation.value)
+                .bind(automation.relative)
+                .bind(Utc::now())
+                .execute(&mut *conn)
+                .await?;
+                changes.push(change("automations", &key, None, Some(automation)));
+            }
+        }
+    }
+    for (key, (id, existing)) in current {
+        sqlx::query("DELETE FROM automation_rules WHERE id = ?")
+            .bind(&id)
+            .execute(&mut *conn)
+            .await?;
+        changes.push(change("automations", &key, Some(&existing), None));
+    }
+    Ok(())
+}
+
+async fn sync_alert_limits(
+    conn: &mut SqliteConnection,
+    desired: &[AlertLimitConfig],
+    changes: &mut Vec<ConfigChange>,
+) -> Result<()> {
+    let mut current = current_alert_limits(conn).await?;
+    for limit in desired {
+        let existing = current.remove(&limit.device_id);
+        if existing.as_ref() == Some(limit) {
+            continue;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO alert_limits (device_id, max_bean_temp, updated_at) VALUES (?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                max_bean_temp = excluded.max_bean_temp,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&limit.device_id)
+        .bind(limit.max_bean_temp)
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?;
+        changes.push(change(
+            "alert_limits",
+            &limit.device_id,
+            existing.as_ref(),
+            Some(limit),
+        ));
+    }
+    for (device_id, existing) in current {
+        sqlx::query("DELETE FROM alert_limits WHERE device_id = ?")
+            .bind(&device_id)
+            .execute(&mut *conn)
+            .await?;
+        changes.push(change("alert_limits", &device_id, Some(&existing), None));
+    }
+    Ok(())
+}
+
+async fn sync_preheat(
+    conn: &mut SqliteConnection,
+    desired: &[PreheatConfig],
+    changes: &mut Vec<ConfigChange>,
+) -> Result<()> {
+    let mut current = current_preheat(conn).await?;
+    for preheat in desired {
+        let existing = current.remove(&preheat.device_id);
+        if existing.as_ref() == Some(preheat) {
+            continue;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO batch_preheat_settings (
+                device_id, enabled, timeout_secs, max_temp, ready_band, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                enabled = excluded.enabled,
+                timeout_secs = excluded.timeout_secs,
+                max_temp = excluded.max_temp,
+                ready_band = excluded.ready_band,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&preheat.device_id)
+        .bind(preheat.enabled)
+        .bind(preheat.timeout_secs)
+        .bind(preheat.max_temp)
+        .bind(preheat.ready_band)
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?;
+        changes.push(change(
+            "preheat",
+            &preheat.device_id,
+            existing.as_ref(),
+            Some(preheat),
+        ));
+    }
+    for (device_id, existing) in current {
+        sqlx::query("DELETE FROM batch_preheat_settings WHERE device_id = ?")
+            .bind(&device_id)
+            .execute(&mut *conn)
+            .await?;
+        changes.push(change("preheat", &device_id, Some(&existing), None));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AutomationCommand, AutomationTrigger};
+
+    #[tokio::test]
+    async fn test_import_reports_and_applies_the_diff() {
+        let db = crate::init_memory_db().await.unwrap();
+        let bundles = ConfigBundles::new(db.clone());
+        let automation = |name: &str, value: f64| AutomationConfig {
+            profile_id: None,
+            device_id: Some("dev1".to_string()),
+            name: name.to_string(),
+            trigger_type: AutomationTrigger::Elapsed,
+            trigger_value: Some(60.0),
+            trigger_event: None,
+            command: AutomationCommand::FanPwm,
+            value,
+            relative: false,
+        };
+        let bundle = ConfigBundle {
+            version: CONFIG_BUNDLE_VERSION,
+            automations: Some(vec![
+                automation("fan up", 180.0),
+                automation("fan max", 255.0),
+            ]),
+            alert_limits: Some(vec![AlertLimitConfig {
+                device_id: "dev1".to_string(),
+                max_bean_temp: 230.0,
+            }]),
+            preheat: None,
+        };
+
+        let report = bundles.import(&bundle, true).await.unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.changes.len(), 3);
+        assert!(report
+            .changes
+            .iter()
+            .all(|c| c.action == ConfigChangeAction::Add));
+        // Nothing was applied
+        let exported = bundles.export().await.unwrap();
+        assert_eq!(exported.automations, Some(Vec::new()));
+
+        bundles.import(&bundle, false).await.unwrap();
+        let exported = bundles.export().await.unwrap();
+        assert_eq!(exported.automations.as_ref().unwrap().len(), 2);
+        assert_eq!(exported.alert_limits, bundle.alert_limits);
+
+        // Importing the export again changes nothing
+        let report = bundles.import(&exported, true).await.unwrap();
+        assert!(report.changes.is_empty());
+
+        // The bundle is the complete set of each section it has
+        let edited = ConfigBundle {
+            automations: Some(vec![automation("fan up", 200.0)]),
+            alert_limits: None,
+            ..bundle
+        };
+        let report = bundles.import(&edited, false).await.unwrap();
+        let actions: Vec<_> = report
+            .changes
+            .iter()
+            .map(|c| (c.key.as_str(), c.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("roaster:dev1/fan up", ConfigChangeAction::Change),
+                ("roaster:dev1/fan max", ConfigChangeAction::Remove),
+            ]
+        );
+        assert_eq!(report.changes[0].after.as_ref().unwrap()["value"], 200.0);
+        let exported = bundles.export().await.unwrap();
+        assert_eq!(exported.automations, edited.automations);
+        assert_eq!(exported.alert_limits.unwrap().len(), 1);
+    }
+}
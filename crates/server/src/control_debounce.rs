@@ -0,0 +1,171 @@
+//! Coalescing of rapid setpoint commands, e.g. from a dashboard slider.
+//!
+//! Per control topic, at most one command is published per window
+//! (`RUSTROAST_SETPOINT_DEBOUNCE_MS`, default 250, 0 disables). The first
+//! command after a quiet period goes out right away. Commands arriving
+//! within the window replace each other and only the latest is published
+//! when the window ends, so the firmware's control queue sees the value
+//! the user settled on rather than every step in between.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_WINDOW_MS: u64 = 250;
+
+/// What to do with a submitted command.
+#[derive(Debug, PartialEq)]
+pub enum Debounce {
+    /// Publish now.
+    Publish,
+    /// Held as the pending value for the topic. `flush_in` is set for the
+    /// command that opened the pending slot, whose caller must schedule
+    /// [`ControlDebouncer::flush`] after it. `coalesced` counts the
+    /// commands replaced so far in this window.
+    Deferred {
+        flush_in: Option<Duration>,
+        coalesced: u64,
+    },
+}
+
+struct Slot {
+    last_published: Instant,
+    pending: Option<String>,
+    coalesced: u64,
+}
+
+#[derive(Clone)]
+pub struct ControlDebouncer {
+    window: Duration,
+    slots: Arc<Mutex<HashMap<String, Slot>>>,
+}
+
+impl ControlDebouncer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            slots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Window from `RUSTROAST_SETPOINT_DEBOUNCE_MS` (default 250).
+    pub fn from_env() -> Self {
+        let window_ms = std::env::var("RUSTROAST_SETPOINT_DEBOUNCE_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_WINDOW_MS);
+        Self::new(Duration::from_millis(window_ms))
+    }
+
+    /// Submit a command for `topic`. `immediate` commands (e.g. waiting for
+    /// an ack) are always published and drop any pending value.
+    pub fn submit(&self, topic: &str, payload: &str, now: Instant, immediate: bool) -> Debounce {
+        if self.window.is_zero() {
+            return Debounce::Publish;
+        }
+        let mut slots = self.slots.lock().unwrap();
+        let slot = match slots.get_mut(topic) {
+            Some(slot)
+                if !immediate
+                    && (slot.pending.is_some()
+                        || now.duration_since(slot.last_published) < self.window) =>
+            {
+                slot
+            }
+            _ => {
+                slots.insert(
+                    topic.to_string(),
+                    Slot {
+                        last_published: now,
+                        pending: None,
+                        coalesced: 0,
+                    },
+                );
+                return Debounce::Publish;
+            }
+        };
+        let flush_in = match slot.pending.replace(payload.to_string()) {
+            Some(_) => {
+                slot.coalesced += 1;
+                None
+            }
+            None => Some(
+                self.window
+                    .saturating_sub(now.duration_since(slot.last_published)),
+            ),
+        };
+        Debounce::Deferred {
+            flush_in,
+            coalesced: slot.coalesced,
+        }
+    }
+
+    /// Take the pending value of `topic` for publishing, with the number of
+    /// commands it replaced. `None` when an immediate command got there first.
+    pub fn flush(&self, topic: &str, now: Instant) -> Option<(String, u64)> {
+        let mut slots = self.slots.lock().unwrap();
+        let slot = slots.get_mut(topic)?;
+        let payload = slot.pending.take()?;
+        slot.last_published = now;
+        Some((payload, std::mem::take(&mut slot.coalesced)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setpoints_coalesce_to_latest_per_window() {
+        let debouncer = ControlDebouncer::new(Duration::from_millis(250));
+        let topic = "roaster/dev1/control/setpoint";
+        let t0 = Instant::now();
+        let ms = |n| t0 + Duration::from_millis(n);
+
+        assert_eq!(debouncer.submit(topic, "200", t0, false), Debounce::Publish);
+        // Other topics are independent
+        assert_eq!(
+            debouncer.submit("roaster/dev2/control/setpoint", "180", ms(10), false),
+            Debounce::Publish
+        );
+        assert_eq!(
+            debouncer.submit(topic, "201", ms(50), false),
+            Debounce::Deferred {
+                flush_in: Some(Duration::from_millis(200)),
+                coalesced: 0
+            }
+        );
+        assert_eq!(
+            debouncer.submit(topic, "202", ms(100), false),
+            Debounce::Deferred {
+                flush_in: None,
+                coalesced: 1
+            }
+        );
+        debouncer.submit(topic, "203", ms(150), false);
+        assert_eq!(
+            debouncer.flush(topic, ms(250)),
+            Some(("203".to_string(), 2))
+        );
+        assert_eq!(debouncer.flush(topic, ms(260)), None);
+
+        // The window restarts at the flush
+        assert!(matches!(
+            debouncer.submit(topic, "204", ms(400), false),
+            Debounce::Deferred { .. }
+        ));
+        assert_eq!(
+            debouncer.submit(topic, "205", ms(410), true),
+            Debounce::Publish
+        );
+        assert_eq!(debouncer.flush(topic, ms(500)), None);
+        assert_eq!(
+            debouncer.submit(topic, "206", ms(700), false),
+            Debounce::Publish
+        );
+
+        let disabled = ControlDebouncer::new(Duration::ZERO);
+        assert_eq!(disabled.submit(topic, "200", t0, false), Debounce::Publish);
+        assert_eq!(disabled.submit(topic, "201", t0, false), Debounce::Publish);
+    }
+}
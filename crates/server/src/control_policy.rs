@@ -0,0 +1,233 @@
+//! Control guardrails per API key.
+//!
+//! An admin can give an API key a policy
+//! (`PUT /api/auth/api-keys/{id}/control-policy`) limiting what the
+//! integration behind it may do to a roaster: control ops it may not send
+//! at all, e.g. `emergency_stop` or `pid`, and how far it may move the
+//! setpoint and heater PWM within any minute. The control endpoints check
+//! the caller's policy before publishing and refuse with 403 naming the
+//! limit. Movement is measured across the device's last reported value and
+//! every value the key sent in the last minute, so small steps in a row
+//! can't add up past the limit. Keys without a policy, users and the admin
+//! token are not limited.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::models::{ApiKeyControlPolicy, SetApiKeyControlPolicyRequest};
+
+/// Ops a policy can ban, as named by the `/control/*` endpoints.
+pub const CONTROL_OPS: &[&str] = &[
+    "setpoint",
+    "fan_pwm",
+    "heater_pwm",
+    "mode",
+    "heater_enable",
+    "pid",
+    "emergency_stop",
+];
+
+/// Span the per-minute movement limits are measured over.
+const WINDOW: Duration = Duration::from_secs(60);
+
+pub async fn get(db: &SqlitePool, api_key_id: &str) -> Result<Option<ApiKeyControlPolicy>> {
+    let policy = sqlx::query_as::<_, ApiKeyControlPolicy>(
+        "SELECT * FROM api_key_control_policies WHERE api_key_id = ?",
+    )
+    .bind(api_key_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(policy)
+}
+
+pub async fn set(
+    db: &SqlitePool,
+    api_key_id: &str,
+    req: &SetApiKeyControlPolicyRequest,
+) -> Result<ApiKeyControlPolicy> {
+    let policy = sqlx::query_as::<_, ApiKeyControlPolicy>(
+        r#"
+        INSERT INTO api_key_control_policies
+            (api_key_id, max_setpoint_delta_per_min, max_heater_pwm_delta_per_min,
+             banned_ops, updated_at)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(api_key_id) DO UPDATE SET
+            max_setpoint_delta_per_min = excluded.max_setpoint_delta_per_min,
+            max_heater_pwm_delta_per_min = excluded.max_heater_pwm_delta_per_min,
+            banned_ops = excluded.banned_ops,
+            updated_at = excluded.updated_at
+        RETURNING *
+        "#,
+    )
+    .bind(api_key_id)
+    .bind(req.max_setpoint_delta_per_min)
+    .bind(req.max_heater_pwm_delta_per_min)
+    .bind(sqlx::types::Json(&req.banned_ops))
+    .bind(Utc::now())
+    .fetch_one(db)
+    .await?;
+    Ok(policy)
+}
+
+/// Returns whether the key had a policy.
+pub async fn remove(db: &SqlitePool, api_key_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM api_key_control_policies WHERE api_key_id = ?")
+        .bind(api_key_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub fn validate(req: &SetApiKeyControlPolicyRequest) -> Result<(), String> {
+    for (name, delta) in [
+        ("max_setpoint_delta_per_min", req.max_setpoint_delta_per_min),
+        (
+            "max_heater_pwm_delta_per_min",
+            req.max_heater_pwm_delta_per_min,
+        ),
+    ] {
+        if delta.is_some_and(|d| !(d.is_finite() && d >= 0.0)) {
+            return Err(format!("{} must be 0 or more", name));
+        }
+    }
+    let unknown: Vec<&str> = req
+        .banned_ops
+        .iter()
+        .map(String::as_str)
+        .filter(|op| !CONTROL_OPS.contains(op))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "unknown control ops: {} (expected one of {})",
+            unknown.join(", "),
+            CONTROL_OPS.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// API key id, device id and op.
+type GuardKey = (String, String, &'static str);
+/// Values sent and when, oldest first.
+type SentValues = VecDeque<(Instant, f64)>;
+
+/// The values each key sent in the last minute, per device and op.
+#[derive(Clone, Default)]
+pub struct ControlGuard {
+    recent: Arc<Mutex<HashMap<GuardKey, SentValues>>>,
+}
+
+impl ControlGuard {
+    /// Check one command of the key behind `policy`. `value` is the
+    /// setpoint or heater PWM being sent and `current` the one the device
+    /// last reported. An allowed value counts towards the key's movement
+    /// from then on, whether or not the publish goes through.
+    pub fn check(
+        &self,
+        policy: &ApiKeyControlPolicy,
+        device_id: &str,
+        op: &'static str,
+        value: Option<f64>,
+        current: Option<f64>,
+        now: Instant,
+    ) -> Result<(), String> {
+        if policy.banned_ops.iter().any(|banned| banned == op) {
+            return Err(format!("This API key may not send {}", op));
+        }
+        let max_delta = match op {
+            "setpoint" => policy.max_setpoint_delta_per_min,
+            "heater_pwm" => policy.max_heater_pwm_delta_per_min,
+            _ => None,
+        };
+        let (Some(max_delta), Some(value)) = (max_delta, value) else {
+            return Ok(());
+        };
+        let mut recent = self.recent.lock().unwrap();
+        let key = (policy.api_key_id.clone(), device_id.to_string(), op);
+        let sent = recent.entry(key).or_default();
+        while sent
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= WINDOW)
+        {
+            sent.pop_front();
+        }
+        let (low, high) = sent
+            .iter()
+            .map(|(_, v)| *v)
+            .chain(current)
+            .chain([value])
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), v| {
+                (low.min(v), high.max(v))
+            });
+        if high - low > max_delta {
+            return Err(format!(
+                "This API key may move {} by at most {} per minute; {} would span {} to {}",
+                op, max_delta, value, low, high
+            ));
+        }
+        sent.push_back((now, value));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_policy_bans_ops_and_limits_movement() {
+        let pool = crate::init_memory_db().await.unwrap();
+        sqlx::query(
+            "INSERT INTO api_keys (id, name, key_prefix, key_hash) VALUES ('k1', 'mes', 'rr_k1', 'h1')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(validate(&SetApiKeyControlPolicyRequest {
+            max_setpoint_delta_per_min: None,
+            max_heater_pwm_delta_per_min: None,
+            banned_ops: vec!["self_destruct".to_string()],
+        })
+        .is_err());
+        let req = SetApiKeyControlPolicyRequest {
+            max_setpoint_delta_per_min: Some(10.0),
+            max_heater_pwm_delta_per_min: None,
+            banned_ops: vec!["emergency_stop".to_string()],
+        };
+        validate(&req).unwrap();
+        let policy = set(&pool, "k1", &req).await.unwrap();
+        assert_eq!(get(&pool, "k1").await.unwrap(), Some(policy.clone()));
+
+        let guard = ControlGuard::default();
+        let t0 = Instant::now();
+        let check = |op, value, current, secs| {
+            guard.check(
+                &policy,
+                "dev1",
+                op,
+                value,
+                current,
+                t0 + Duration::from_secs(secs),
+            )
+        };
+        assert!(check("emergency_stop", None, None, 0).is_err());
+        assert!(check("fan_pwm", Some(255.0), None, 0).is_ok());
+        assert!(check("setpoint", Some(215.0), Some(220.0), 0).is_ok());
+        assert!(check("setpoint", Some(210.0), Some(215.0), 10).is_ok());
+        // Small steps can't add up past the limit within a minute
+        assert!(check("setpoint", Some(204.0), Some(210.0), 20).is_err());
+        assert!(check("setpoint", Some(204.0), Some(210.0), 61).is_ok());
+        // Another device is tracked apart
+        assert!(guard
+            .check(&policy, "dev2", "setpoint", Some(150.0), None, t0)
+            .is_ok());
+
+        assert!(remove(&pool, "k1").await.unwrap());
+        assert!(get(&pool, "k1").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! Roast cues: bench reminders fired while a session is active.
+//!
+//! The engine follows the unified telemetry broadcast, checks the active
+//! session's cues against elapsed time and bean temperature, and announces
+//! each cue once on a broadcast channel (forwarded to `/ws/telemetry`).
+//! Cues flagged `notify` are also POSTed to `RUSTROAST_NOTIFY_WEBHOOK_URL`
+//! with a `title` in the `RUSTROAST_LOCALE` language.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::i18n::Locale;
+use crate::models::{CueTrigger, SessionStatus};
+use crate::services::RoastSessionService;
+use crate::telemetry::TelemetryEvent;
+
+/// Broadcast when a cue fires during a session.
+#[derive(Debug, Clone, Serialize)]
+pub struct CueEvent {
+    pub device_id: String,
+    pub session_id: String,
+    pub cue_id: String,
+    pub message: String,
+    pub trigger_type: CueTrigger,
+    pub trigger_value: f64,
+    pub elapsed_seconds: f64,
+    pub bean_temp: Option<f64>,
+    pub fired_at: DateTime<Utc>,
+}
+
+/// Webhook body: the cue event plus a localized title.
+#[derive(Serialize)]
+struct CueNotification<'a> {
+    title: &'a str,
+    #[serde(flatten)]
+    event: &'a CueEvent,
+}
+
+#[derive(Clone)]
+pub struct CueEngine {
+    session_service: RoastSessionService,
+    cue_tx: broadcast::Sender<CueEvent>,
+    webhook_url: Option<String>,
+    locale: Locale,
+    http: reqwest::Client,
+}
+
+impl CueEngine {
+    pub fn new(session_service: RoastSessionService) -> Self {
+        let (cue_tx, _) = broadcast::channel(64);
+        Self {
+            session_service,
+            cue_tx,
+            webhook_url: std::env::var("RUSTROAST_NOTIFY_WEBHOOK_URL")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            locale: Locale::server_default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Subscribe to fired cues.
+    pub fn subscribe(&self) -> broadcast::Receiver<CueEvent> {
+        self.cue_tx.subscribe()
+    }
+
+    /// Evaluate cues for every telemetry event until the channel closes.
+    pub async fn run(self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        loop {
+            match telemetry_rx.recv().await {
+                Ok(evt) => self.evaluate(&evt.device_id, &evt.payload).await,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "Cue engine lagged behind telemetry");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn evaluate(&self, device_id: &str, payload: &serde_json::Value) {
+        let session = match self.session_service.get_active_session(device_id).await {
+            Ok(Some(s)) if s.status == SessionStatus::Active => s,
+            Ok(_) => return,
+            Err(e) => {
+                tracing::warn!(%device_id, error = %e, "Failed to look up active session for cues");
+                return;
+            }
+        };
+        let Some(start) = session.start_time else {
+            return;
+        };
+        let elapsed = (Utc::now() - start).num_milliseconds() as f64 / 1000.0;
+        let bean_temp = payload.get("beanTemp").and_then(|v| v.as_f64());
+
+        let cues = match self.session_service.list_session_cues(&session).await {
+            Ok(cues) => cues,
+            Err(e) => {
+                tracing::warn!(session_id = %session.id, error = %e, "Failed to load cues");
+                return;
+            }
+        };
+        for sc in cues {
+            if sc.fired_at.is_some() || !sc.cue.is_due(elapsed, bean_temp) {
+                continue;
+            }
+            match self
+                .session_service
+                .record_cue_firing(&session.id, &sc.cue.id, elapsed, bean_temp)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    tracing::warn!(cue_id = %sc.cue.id, error = %e, "Failed to record cue firing");
+                    continue;
+                }
+            }
+            let event = CueEvent {
+                device_id: device_id.to_string(),
+                session_id: session.id.clone(),
+                cue_id: sc.cue.id,
+                message: sc.cue.message,
+                trigger_type: sc.cue.trigger_type,
+                trigger_value: sc.cue.trigger_value,
+                elapsed_seconds: elapsed,
+                bean_temp,
+                fired_at: Utc::now(),
+            };
+            tracing::info!(%device_id, message = %event.message, "Roast cue fired");
+            if sc.cue.notify {
+                self.push_notification(&event);
+            }
+            let _ = self.cue_tx.send(event);
+        }
+    }
+
+    /// Fire-and-forget webhook POST so a slow receiver never stalls cues.
+    fn push_notification(&self, event: &CueEvent) {
+        let Some(url) = self.webhook_url.clone() else {
+            return;
+        };
+        let request = self.http.post(url).json(&CueNotification {
+            title: self.locale.t("roast_cue"),
+            event,
+        });
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "Cue notification webhook failed"),
+            }
+        });
+    }
+}
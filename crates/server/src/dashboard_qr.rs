@@ -0,0 +1,100 @@
+//! QR codes linking to a roaster's live dashboard, for printing and
+//! sticking next to the machine.
+//!
+//! The link is `{base}/?device={device_id}`, optionally with a `token` the
+//! dashboard stores and sends as its bearer. `base` is
+//! `RUSTROAST_PUBLIC_URL` when set, else the scheme and host the request
+//! came in on.
+
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+use qrcode::render::svg;
+use qrcode::{EcLevel, QrCode};
+use reqwest::Url;
+
+pub const DEFAULT_SCALE: u32 = 8;
+pub const MAX_SCALE: u32 = 40;
+/// Lifetime of an embedded read-only token, in days.
+pub const DEFAULT_TOKEN_TTL_DAYS: u64 = 365;
+pub const MAX_TOKEN_TTL_DAYS: u64 = 3650;
+
+/// Base URL of the dashboard from `RUSTROAST_PUBLIC_URL`.
+pub fn public_url_from_env() -> Option<String> {
+    std::env::var("RUSTROAST_PUBLIC_URL")
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// The live dashboard URL for `device_id` under `base`.
+pub fn dashboard_link(base: &str, device_id: &str, token: Option<&str>) -> Result<String> {
+    let mut url =
+        Url::parse(base).with_context(|| format!("invalid dashboard base URL: {base}"))?;
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url.set_query(None);
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("device", device_id);
+        if let Some(token) = token {
+            query.append_pair("token", token);
+        }
+    }
+    Ok(url.into())
+}
+
+fn encode(link: &str) -> Result<QrCode> {
+    // Medium correction survives a scuffed or partly glare-covered print
+    // while keeping links with a token scannable from a phone.
+    QrCode::with_error_correction_level(link.as_bytes(), EcLevel::M)
+        .context("link too long for a QR code")
+}
+
+/// PNG with `scale` pixels per module and the standard quiet zone.
+pub fn render_png(link: &str, scale: u32) -> Result<Vec<u8>> {
+    let image = encode(link)?
+        .render::<image::Luma<u8>>()
+        .module_dimensions(scale, scale)
+        .build();
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// SVG document with `scale` units per module.
+pub fn render_svg(link: &str, scale: u32) -> Result<String> {
+    Ok(encode(link)?
+        .render::<svg::Color<'_>>()
+        .module_dimensions(scale, scale)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_link() {
+        assert_eq!(
+            dashboard_link("https://example.com/roastery", "roaster 1", None).unwrap(),
+            "https://example.com/roastery/?device=roaster+1"
+        );
+        assert_eq!(
+            dashboard_link("http://10.0.0.5:8080", "r1", Some("a.b&c")).unwrap(),
+            "http://10.0.0.5:8080/?device=r1&token=a.b%26c"
+        );
+        assert!(dashboard_link("not a url", "r1", None).is_err());
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let link = "http://localhost:8080/?device=r1";
+        let png = render_png(link, 4).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let svg = render_svg(link, 4).unwrap();
+        assert!(svg.contains("<svg"));
+    }
+}
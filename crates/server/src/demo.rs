@@ -0,0 +1,350 @@
+//! Demo mode (`RUSTROAST_DEMO=true`) for evaluating rustRoast, or running a
+//! public demo, without hardware.
+//!
+//! No broker is needed: the server runs on an in-process MQTT service and a
+//! simulated roaster (`RUSTROAST_DEMO_DEVICE_ID`, default `demo-roaster`)
+//! publishes status and telemetry into it, roasting the medium profile over
+//! and over. A fresh database is seeded with green beans, profiles and a few
+//! completed sessions. Device control is refused (403), so the instance is
+//! read-only towards control topics whatever is connected.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use rand::Rng;
+use serde_json::json;
+
+use crate::models::RoastEventType;
+use crate::AppState;
+
+pub const DEFAULT_DEVICE_ID: &str = "demo-roaster";
+
+const TICK: Duration = Duration::from_secs(1);
+const COOLDOWN_SECS: u32 = 90;
+const STATUS_EVERY_SECS: u32 = 30;
+/// Seconds between telemetry points of seeded sessions.
+const SEED_STEP_SECS: usize = 2;
+
+/// A roast curve: `(elapsed seconds, bean temp)` points, first crack and drop.
+struct Roast {
+    name: &'static str,
+    charge_temp: f32,
+    points: &'static [(f32, f32)],
+    drying_end: f32,
+    first_crack: f32,
+    drop: f32,
+}
+
+const LIGHT: Roast = Roast {
+    name: "Demo Light",
+    charge_temp: 200.0,
+    points: &[
+        (0.0, 200.0),
+        (30.0, 110.0),
+        (60.0, 105.0),
+        (120.0, 130.0),
+        (180.0, 150.0),
+        (240.0, 165.0),
+        (300.0, 178.0),
+        (360.0, 188.0),
+        (420.0, 193.0),
+        (480.0, 196.0),
+        (540.0, 200.0),
+        (600.0, 205.0),
+    ],
+    drying_end: 210.0,
+    first_crack: 480.0,
+    drop: 600.0,
+};
+
+const MEDIUM: Roast = Roast {
+    name: "Demo Medium",
+    charge_temp: 205.0,
+    points: &[
+        (0.0, 205.0),
+        (30.0, 115.0),
+        (60.0, 108.0),
+        (120.0, 132.0),
+        (180.0, 152.0),
+        (240.0, 168.0),
+        (300.0, 180.0),
+        (360.0, 190.0),
+        (420.0, 196.0),
+        (480.0, 200.0),
+        (540.0, 205.0),
+        (600.0, 210.0),
+        (660.0, 213.0),
+        (720.0, 215.0),
+    ],
+    drying_end: 200.0,
+    first_crack: 480.0,
+    drop: 720.0,
+};
+
+impl Roast {
+    /// Bean temp at `t`, linear between the curve points.
+    fn bean_temp(&self, t: f32) -> f32 {
+        let points = self.points;
+        let i = points.partition_point(|(pt, _)| *pt <= t);
+        match (i.checked_sub(1).map(|j| points[j]), points.get(i)) {
+            (Some((t0, v0)), Some(&(t1, v1))) => v0 + (v1 - v0) * (t - t0) / (t1 - t0),
+            (Some((_, v)), None) | (None, Some(&(_, v))) => v,
+            (None, None) => 0.0,
+        }
+    }
+
+    /// Rate of rise in °C/min over the last 30 s.
+    fn rate_of_rise(&self, t: f32) -> f32 {
+        let window = 30.0_f32.min(t);
+        if window <= 0.0 {
+            return 0.0;
+        }
+        (self.bean_temp(t) - self.bean_temp(t - window)) * 60.0 / window
+    }
+}
+
+pub fn enabled_from_env() -> bool {
+    std::env::var("RUSTROAST_DEMO")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
+pub fn device_id_from_env() -> String {
+    std::env::var("RUSTROAST_DEMO_DEVICE_ID")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_DEVICE_ID.to_string())
+}
+
+/// Seed beans, profiles, the demo device and completed sessions, unless the
+/// database already has sessions. Returns whether anything was seeded.
+pub async fn seed(state: &AppState, device_id: &str) -> Result<bool> {
+    let sessions = state
+        .session_service
+        .list_sessions(None, None, Some(1))
+        .await?;
+    if !sessions.is_empty() {
+        return Ok(false);
+    }
+    let service = &state.session_service;
+    let beans = [
+        json!({"name": "Ethiopia Yirgacheffe", "origin": "Ethiopia", "variety": "Heirloom", "process": "washed", "cost_per_kg": 14.5}),
+        json!({"name": "Colombia Huila", "origin": "Colombia", "variety": "Caturra", "process": "washed", "cost_per_kg": 11.0}),
+    ];
+    let mut bean_ids = Vec::new();
+    for bean in beans {
+        bean_ids.push(service.create_bean(serde_json::from_value(bean)?).await?.id);
+    }
+    let mut profile_ids = Vec::new();
+    for roast in [&LIGHT, &MEDIUM] {
+        let points: Vec<_> = roast
+            .points
+            .iter()
+            .skip(2) // past the charge drop and turning point
+            .map(|(t, temp)| json!({"time_seconds": *t as i32, "target_temp": temp}))
+            .collect();
+        let profile = json!({
+            "name": roast.name,
+            "description": "Seeded by demo mode",
+            "target_total_time": roast.drop as i32,
+            "target_first_crack": roast.first_crack as i32,
+            "target_end_temp": roast.bean_temp(roast.drop),
+            "charge_temp": roast.charge_temp,
+            "points": points,
+        });
+        profile_ids.push(
+            service
+                .create_profile(serde_json::from_value(profile)?)
+                .await?
+                .profile
+                .id,
+        );
+    }
+    if state
+        .device_service
+        .get_device_by_device_id(device_id)
+        .await?
+        .is_none()
+    {
+        let device = json!({
+            "name": "Demo roaster",
+            "device_id": device_id,
+            "description": "Simulated roaster (demo mode)",
+        });
+        state
+            .device_service
+            .create_device(serde_json::from_value(device)?)
+            .await?;
+    }
+
+    let roasts = [(&LIGHT, 0, 0), (&MEDIUM, 1, 0), (&MEDIUM, 1, 1)];
+    for (n, (roast, profile, bean)) in roasts.into_iter().enumerate() {
+        let req = json!({
+            "name": format!("{} #{}", roast.name, n + 1),
+            "device_id": device_id,
+            "profile_id": profile_ids[profile],
+            "bean_id": bean_ids[bean],
+            "green_weight": 250.0,
+            "roaster": "demo",
+        });
+        let session = service.create_session(serde_json::from_value(req)?).await?;
+        service.start_session(&session.id).await?;
+        for t in (0..=roast.drop as usize).step_by(SEED_STEP_SECS) {
+            let t = t as f32;
+            let bt = roast.bean_temp(t);
+            service
+                .add_telemetry_point(
+                    &session.id,
+                    t,
+                    Some(bt),
+                    Some(bt + 25.0),
+                    Some(roast.rate_of_rise(t)),
+                    Some(heater_pwm(roast, t) as i32),
+                    Some(180),
+                    Some(roast.bean_temp(t + 30.0)),
+                )
+                .await?;
+        }
+        for (event_type, t) in [
+            (RoastEventType::DryingEnd, roast.drying_end),
+            (RoastEventType::FirstCrackStart, roast.first_crack),
+            (RoastEventType::Drop, roast.drop),
+        ] {
+            let event = json!({
+                "event_type": event_type,
+                "elapsed_seconds": t,
+                "temperature": roast.bean_temp(t),
+            });
+            service
+                .create_roast_event(&session.id, serde_json::from_value(event)?)
+                .await?;
+        }
+        let roasted = json!({"roasted_weight": 250.0 * (0.86 - 0.02 * n as f32)});
+        service
+            .update_session(&session.id, serde_json::from_value(roasted)?)
+            .await?;
+        service.complete_session(&session.id).await?;
+    }
+    Ok(true)
+}
+
+fn heater_pwm(roast: &Roast, t: f32) -> f32 {
+    if t >= roast.first_crack {
+        45.0
+    } else {
+        85.0 - 30.0 * t / roast.first_crack
+    }
+}
+
+/// Feed the simulated roaster's status and telemetry into the in-process
+/// MQTT service, as if it were publishing to a broker.
+pub async fn run_simulator(state: AppState, device_id: String) {
+    let status_topic = rustroast_core::status_topic(&device_id);
+    let telemetry_topic = rustroast_core::telemetry_topic(&device_id);
+    let cycle = MEDIUM.drop as u32 + COOLDOWN_SECS;
+    let mut ticker = tokio::time::interval(TICK);
+    let mut uptime: u32 = 0;
+    loop {
+        ticker.tick().await;
+        if uptime.is_multiple_of(STATUS_EVERY_SECS) {
+            let status = json!({
+                "status": "online",
+                "id": format!("{}-SIM", device_id),
+                "ip": "127.0.0.1",
+                "rssi": -52,
+                "freeHeap": 180_000,
+                "version": "demo",
+            });
+            state.mqtt.inject(&status_topic, status.to_string());
+        }
+        let t = (uptime % cycle) as f32;
+        let roasting = t <= MEDIUM.drop;
+        let noise = rand::thread_rng().gen_range(-0.3..0.3);
+        let (bean_temp, ror, heater, setpoint) = if roasting {
+            (
+                MEDIUM.bean_temp(t) + noise,
+                MEDIUM.rate_of_rise(t),
+                heater_pwm(&MEDIUM, t),
+                MEDIUM.bean_temp(t + 30.0),
+            )
+        } else {
+            // Cooling back down towards the next charge
+            let cooled = (t - MEDIUM.drop) / COOLDOWN_SECS as f32;
+            let end = MEDIUM.bean_temp(MEDIUM.drop);
+            let bt = end + (MEDIUM.charge_temp - end) * cooled;
+            (bt + noise, 0.0, 70.0, MEDIUM.charge_temp)
+        };
+        let telemetry = json!({
+            "timestamp": uptime,
+            "beanTemp": bean_temp,
+            "envTemp": bean_temp + 25.0,
+            "rateOfRise": ror,
+            "heaterPWM": heater.round() as i64,
+            "fanPWM": 180,
+            "setpoint": setpoint,
+            "controlMode": 1,
+            "heaterEnable": 1,
+            "uptime": uptime,
+            "freeHeap": 180_000,
+            "rssi": -52,
+            "systemStatus": 0,
+        });
+        state.mqtt.inject(&telemetry_topic, telemetry.to_string());
+        uptime += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SessionStatus;
+
+    #[tokio::test]
+    async fn test_demo_seed_is_complete_and_runs_once() {
+        let db = crate::init_memory_db().await.unwrap();
+        let (mqtt, _published) = rustroast_mqtt::MqttService::mock();
+        let state = crate::build_state(mqtt, db.clone(), db);
+        assert!(seed(&state, DEFAULT_DEVICE_ID).await.unwrap());
+        assert!(!seed(&state, DEFAULT_DEVICE_ID).await.unwrap());
+
+        let sessions = state
+            .session_service
+            .list_sessions(Some(DEFAULT_DEVICE_ID), None, None)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert!(sessions
+            .iter()
+            .all(|s| s.status == SessionStatus::Completed && s.first_crack_time == Some(480)));
+        assert!(sessions.iter().all(|s| s.weight_loss_pct.is_some()));
+        assert_eq!(state.session_service.list_beans().await.unwrap().len(), 2);
+        assert!(state
+            .device_service
+            .get_device_by_device_id(DEFAULT_DEVICE_ID)
+            .await
+            .unwrap()
+            .is_some());
+
+        assert_eq!(MEDIUM.bean_temp(450.0), 198.0);
+        assert_eq!(MEDIUM.rate_of_rise(480.0), 4.0);
+    }
+
+    #[tokio::test]
+    async fn test_demo_mode_refuses_device_control() {
+        let db = crate::init_memory_db().await.unwrap();
+        let (mqtt, mut published) = rustroast_mqtt::MqttService::mock();
+        let mut state = crate::build_state(mqtt, db.clone(), db);
+        state.demo = true;
+        let command = rustroast_core::Command::Setpoint(200.0);
+        let resp = crate::publish_qos1_and_maybe_wait_ack(
+            &state,
+            &command.topic(DEFAULT_DEVICE_ID),
+            command.payload(),
+            false,
+            0,
+        )
+        .await;
+        assert_eq!(resp.status(), axum::http::StatusCode::FORBIDDEN);
+        assert!(published.try_recv().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Derived telemetry computed to each `/ws/telemetry` client's liking.
+//!
+//! A subscribe command can carry a [`DerivedSpec`] (RoR window and
+//! algorithm, how many samples to average temperatures over, and the
+//! units). Every telemetry message to that client then also carries
+//! `"derived": {"beanTemp", "envTemp", "rateOfRise", "unit"}` computed that
+//! way, so roasters sharing an instance can each keep their usual display.
+//!
+//! Recent readings are kept once per device, and each variant is computed
+//! once per reading however many clients asked for it.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{RorAlgorithm, RorUnit, TemperatureUnit};
+use crate::smoothing;
+
+/// Readings older than this are dropped beyond the last
+/// `MAX_SMOOTHING_SAMPLES`.
+const MAX_WINDOW: Duration = Duration::from_secs(300);
+const MAX_SMOOTHING_SAMPLES: u32 = 60;
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct DerivedSpec {
+    pub ror_window_secs: u32,
+    pub ror_algorithm: RorAlgorithm,
+    pub ror_unit: RorUnit,
+    /// Readings the temperatures are averaged over; 1 leaves them raw.
+    pub smoothing_samples: u32,
+    pub units: TemperatureUnit,
+}
+
+impl Default for DerivedSpec {
+    fn default() -> Self {
+        Self {
+            ror_window_secs: 30,
+            ror_algorithm: RorAlgorithm::MovingAverage,
+            ror_unit: RorUnit::PerMinute,
+            smoothing_samples: 1,
+            units: TemperatureUnit::C,
+        }
+    }
+}
+
+impl DerivedSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_WINDOW.as_secs() as u32).contains(&self.ror_window_secs) {
+            return Err(format!(
+                "ror_window_secs must be between 1 and {}",
+                MAX_WINDOW.as_secs()
+            ));
+        }
+        if !(1..=MAX_SMOOTHING_SAMPLES).contains(&self.smoothing_samples) {
+            return Err(format!(
+                "smoothing_samples must be between 1 and {}",
+                MAX_SMOOTHING_SAMPLES
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Temperatures in the spec's unit; RoR per its RoR unit, rounded to 0.1.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Derived {
+    pub bean_temp: Option<f32>,
+    pub env_temp: Option<f32>,
+    pub rate_of_rise: Option<f32>,
+    pub unit: TemperatureUnit,
+}
+
+struct Reading {
+    at: Instant,
+    bean_temp: Option<f64>,
+    env_temp: Option<f64>,
+}
+
+#[derive(Default)]
+struct DeviceHistory {
+    readings: VecDeque<Reading>,
+    /// Variants computed for the latest reading.
+    computed: HashMap<DerivedSpec, Derived>,
+}
+
+#[derive(Clone, Default)]
+pub struct DerivedTelemetry {
+    devices: Arc<Mutex<HashMap<String, DeviceHistory>>>,
+}
+
+impl DerivedTelemetry {
+    /// Record a processed telemetry payload, received at `at`.
+    pub fn observe(&self, device_id: &str, payload: &serde_json::Value, at: Instant) {
+        let temp = |key: &str| payload.get(key).and_then(|v| v.as_f64());
+        let (bean_temp, env_temp) = (temp("beanTemp"), temp("envTemp"));
+        if bean_temp.is_none() && env_temp.is_none() {
+            return;
+        }
+        let mut devices = self.devices.lock().unwrap();
+        let history = devices.entry(device_id.to_string()).or_default();
+        history.readings.push_back(Reading {
+            at,
+            bean_temp,
+            env_temp,
+        });
+        while history.readings.len() > MAX_SMOOTHING_SAMPLES as usize
+            && history
+                .readings
+                .front()
+                .is_some_and(|r| at.duration_since(r.at) > MAX_WINDOW)
+        {
+            history.readings.pop_front();
+        }
+        history.computed.clear();
+    }
+
+    /// The device's latest reading derived per `spec`, or `None` before
+    /// its first reading.
+    pub fn get(&self, device_id: &str, spec: &DerivedSpec) -> Option<Derived> {
+        let mut devices = self.devices.lock().unwrap();
+        let history = devices.get_mut(device_id)?;
+        if let Some(derived) = history.computed.get(spec) {
+            return Some(derived.clone());
+        }
+        let derived = derive(&history.readings, spec)?;
+        history.computed.insert(*spec, derived.clone());
+        Some(derived)
+    }
+}
+
+fn derive(readings: &VecDeque<Reading>, spec: &DerivedSpec) -> Option<Derived> {
+    let latest = readings.back()?;
+    let mean = |value: fn(&Reading) -> Option<f64>| {
+        value(latest)?;
+        let values: Vec<f64> = readings
+            .iter()
+            .rev()
+            .take(spec.smoothing_samples as usize)
+            .filter_map(value)
+            .collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        Some(spec.units.convert(mean as f32))
+    };
+    let window = Duration::from_secs(spec.ror_window_secs.into());
+    let rate_of_rise = latest.bean_temp.and_then(|_| {
+        let mut samples: Vec<(f64, f64)> = readings
+            .iter()
+            .rev()
+            .take_while(|r| latest.at.duration_since(r.at) <= window)
+            .filter_map(|r| {
+                let secs = -latest.at.duration_since(r.at).as_secs_f64();
+                r.bean_temp.map(|bt| (secs, bt))
+            })
+            .collect();
+        samples.reverse();
+        let slope = smoothing::slope(spec.ror_algorithm, &samples)?;
+        let ror = spec
+            .units
+            .convert_delta((slope * 60.0) as f32 * spec.ror_unit.factor());
+        Some((ror * 10.0).round() / 10.0)
+    });
+    Some(Derived {
+        bean_temp: mean(|r| r.bean_temp),
+        env_temp: mean(|r| r.env_temp),
+        rate_of_rise,
+        unit: spec.units,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variants_per_spec_from_shared_readings() {
+        let derived = DerivedTelemetry::default();
+        let t0 = Instant::now();
+        // Bean temp rising 10 °C/min, one reading a second
+        for i in 0..=60u64 {
+            let payload = serde_json::json!({
+                "beanTemp": 150.0 + i as f64 / 6.0,
+                "envTemp": 200.0,
+            });
+            derived.observe("dev1", &payload, t0 + Duration::from_secs(i));
+        }
+        assert!(derived.get("dev2", &DerivedSpec::default()).is_none());
+
+        let celsius = derived.get("dev1", &DerivedSpec::default()).unwrap();
+        assert_eq!(celsius.rate_of_rise, Some(10.0));
+        assert_eq!(celsius.bean_temp, Some(160.0));
+
+        let fahrenheit = DerivedSpec {
+            ror_window_secs: 60,
+            ror_unit: RorUnit::Per30Secs,
+            smoothing_samples: 3,
+            units: TemperatureUnit::F,
+            ..Default::default()
+        };
+        assert!(fahrenheit.validate().is_ok());
+        let f = derived.get("dev1", &fahrenheit).unwrap();
+        assert_eq!(f.rate_of_rise, Some(9.0));
+        // Mean of the last three readings, 159.83 °C
+        assert!((f.bean_temp.unwrap() - 319.7).abs() < 0.01);
+        assert_eq!(f.env_temp, Some(392.0));
+        assert_eq!(derived.get("dev1", &fahrenheit), Some(f));
+
+        assert!(DerivedSpec {
+            smoothing_samples: 0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
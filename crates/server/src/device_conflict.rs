@@ -0,0 +1,247 @@
+//! Detection of several physical roasters publishing under one device_id
+//! (e.g. a cloned flash image).
+//!
+//! Status messages carry the board's hardware `id` and `ip`, and status and
+//! telemetry carry `uptime`. Within a sliding window, a device_id is flagged
+//! as conflicted when it reports more than one hardware id, when its IP
+//! flaps back to an address it already left, or when uptime goes backwards
+//! more than once (a single reboot accounts for one). While flagged, its
+//! telemetry is not recorded into sessions. The flag clears once a full
+//! window passes without any of these signals, or when cleared by hand.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use rustroast_core::DeviceStatus;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+const DEFAULT_WINDOW_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictReason {
+    MultipleHardwareIds,
+    IpFlapping,
+    UptimeRegressions,
+}
+
+/// Why and since when a device_id is considered shared.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceConflict {
+    pub device_id: String,
+    pub reason: ConflictReason,
+    pub detected_at: DateTime<Utc>,
+    /// Hardware ids and IPs seen for the device_id within the window.
+    pub hardware_ids: Vec<String>,
+    pub ips: Vec<String>,
+}
+
+struct Sighting {
+    at: u64,
+    hardware_id: Option<String>,
+    ip: Option<String>,
+    uptime: Option<u64>,
+}
+
+#[derive(Default)]
+struct DeviceTrack {
+    sightings: VecDeque<Sighting>,
+    conflict: Option<DeviceConflict>,
+}
+
+#[derive(Clone)]
+pub struct ConflictDetector {
+    window_secs: u64,
+    devices: Arc<Mutex<HashMap<String, DeviceTrack>>>,
+    alert_tx: broadcast::Sender<DeviceConflict>,
+}
+
+impl ConflictDetector {
+    pub fn new(window_secs: u64) -> Self {
+        let (alert_tx, _) = broadcast::channel(16);
+        Self {
+            window_secs,
+            devices: Arc::new(Mutex::new(HashMap::new())),
+            alert_tx,
+        }
+    }
+
+    /// Window from `RUSTROAST_DEVICE_CONFLICT_WINDOW_SECS` (default 600).
+    pub fn from_env() -> Self {
+        let window_secs = std::env::var("RUSTROAST_DEVICE_CONFLICT_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_WINDOW_SECS);
+        Self::new(window_secs)
+    }
+
+    /// Subscribe to newly detected conflicts.
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceConflict> {
+        self.alert_tx.subscribe()
+    }
+
+    /// Record a status or telemetry message (at unix time `now`) and
+    /// return the device's current conflict, if any.
+    pub fn observe(
+        &self,
+        device_id: &str,
+        payload: &serde_json::Value,
+        now: u64,
+    ) -> Option<DeviceConflict> {
+        let status = DeviceStatus::from_payload(payload);
+        let sighting = Sighting {
+            at: now,
+            hardware_id: status.id,
+            ip: status.ip,
+            uptime: status.uptime,
+        };
+        if sighting.hardware_id.is_none() && sighting.ip.is_none() && sighting.uptime.is_none() {
+            return self.conflict(device_id);
+        }
+
+        let mut devices = self.devices.lock().unwrap();
+        let track = devices.entry(device_id.to_string()).or_default();
+        track.sightings.push_back(sighting);
+        let cutoff = now.saturating_sub(self.window_secs);
+        while track.sightings.front().is_some_and(|s| s.at < cutoff) {
+            track.sightings.pop_front();
+        }
+
+        match (detect(&track.sightings), &track.conflict) {
+            (Some(reason), None) => {
+                let conflict = DeviceConflict {
+                    device_id: device_id.to_string(),
+                    reason,
+                    detected_at: Utc::now(),
+                    hardware_ids: distinct(track.sightings.iter().map(|s| &s.hardware_id)),
+                    ips: distinct(track.sightings.iter().map(|s| &s.ip)),
+                };
+                tracing::error!(
+                    %device_id,
+                    reason = ?reason,
+                    hardware_ids = ?conflict.hardware_ids,
+                    ips = ?conflict.ips,
+                    "Several devices appear to publish under one device_id; session recording suspended"
+                );
+                let _ = self.alert_tx.send(conflict.clone());
+                track.conflict = Some(conflict);
+            }
+            (None, Some(_)) => {
+                tracing::info!(%device_id, "Device id conflict cleared");
+                track.conflict = None;
+            }
+            _ => {}
+        }
+        track.conflict.clone()
+    }
+
+    pub fn conflict(&self, device_id: &str) -> Option<DeviceConflict> {
+        self.devices
+            .lock()
+            .unwrap()
+            .get(device_id)
+            .and_then(|t| t.conflict.clone())
+    }
+
+    /// All devices currently flagged.
+    pub fn conflicts(&self) -> Vec<DeviceConflict> {
+        self.devices
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|t| t.conflict.clone())
+            .collect()
+    }
+
+    /// Clear a flag by hand (e.g. after reflashing one of the boards) and
+    /// forget the evidence. Returns whether the device was flagged.
+    pub fn clear(&self, device_id: &str) -> bool {
+        self.devices
+            .lock()
+            .unwrap()
+            .remove(device_id)
+            .is_some_and(|t| t.conflict.is_some())
+    }
+}
+
+fn detect(sightings: &VecDeque<Sighting>) -> Option<ConflictReason> {
+    if distinct(sightings.iter().map(|s| &s.hardware_id)).len() > 1 {
+        return Some(ConflictReason::MultipleHardwareIds);
+    }
+
+    // An IP change is normal (DHCP), returning to an address already left is not
+    let mut left: Vec<&str> = Vec::new();
+    let mut current: Option<&str> = None;
+    for ip in sightings.iter().filter_map(|s| s.ip.as_deref()) {
+        if current.is_some_and(|c| c != ip) {
+            if left.contains(&ip) {
+                return Some(ConflictReason::IpFlapping);
+            }
+            left.extend(current);
+        }
+        current = Some(ip);
+    }
+
+    let uptimes: Vec<u64> = sightings.iter().filter_map(|s| s.uptime).collect();
+    let regressions = uptimes.windows(2).filter(|w| w[1] < w[0]).count();
+    (regressions > 1).then_some(ConflictReason::UptimeRegressions)
+}
+
+fn distinct<'a>(values: impl Iterator<Item = &'a Option<String>>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for v in values.flatten() {
+        if !out.contains(v) {
+            out.push(v.clone());
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_conflict_detection_and_clearing() {
+        let detector = ConflictDetector::new(60);
+        let status = |id: &str, ip: &str| json!({"id": id, "ip": ip});
+
+        // DHCP change and a single reboot are fine
+        assert!(detector
+            .observe("a", &status("A1", "10.0.0.5"), 0)
+            .is_none());
+        assert!(detector
+            .observe("a", &status("A1", "10.0.0.6"), 5)
+            .is_none());
+        for (t, uptime) in [(6, 500), (7, 501), (8, 3), (9, 4)] {
+            assert!(detector
+                .observe("a", &json!({"uptime": uptime}), t)
+                .is_none());
+        }
+
+        // A second board with the same id interleaves its uptime
+        assert!(detector.observe("a", &json!({"uptime": 900}), 10).is_none());
+        let conflict = detector.observe("a", &json!({"uptime": 6}), 11).unwrap();
+        assert_eq!(conflict.reason, ConflictReason::UptimeRegressions);
+        assert!(detector.conflict("a").is_some());
+
+        // Quiet for a full window clears it
+        assert!(detector.observe("a", &json!({"uptime": 7}), 80).is_none());
+
+        // IP flapping back, then two hardware ids
+        detector.observe("b", &status("B1", "10.0.0.7"), 0);
+        detector.observe("b", &status("B1", "10.0.0.8"), 1);
+        let conflict = detector.observe("b", &status("B1", "10.0.0.7"), 2).unwrap();
+        assert_eq!(conflict.reason, ConflictReason::IpFlapping);
+        assert!(detector.clear("b"));
+        detector.observe("b", &status("B1", "10.0.0.7"), 3);
+        let conflict = detector.observe("b", &status("B2", "10.0.0.7"), 4).unwrap();
+        assert_eq!(conflict.reason, ConflictReason::MultipleHardwareIds);
+        assert_eq!(conflict.hardware_ids, ["B1", "B2"]);
+        assert_eq!(detector.conflicts().len(), 1);
+    }
+}
@@ -0,0 +1,377 @@
+//! Connectivity and memory health per device, from telemetry `rssi` and
+//! `freeHeap`.
+//!
+//! Every sample updates exponential moving averages (α = [`EMA_ALPHA`]) of
+//! the WiFi RSSI and free heap. The health score (0-100) is the mean of a
+//! signal score, linear from -90 dBm (0) to -55 dBm (100), and a memory
+//! score, linear from 20 KB (0) to 100 KB (100) of free heap. Devices that
+//! report only one of them are scored on it alone. Once a minute the
+//! averages are written to `device_health` for charting.
+//!
+//! The last hour of minute points is fitted with a line per metric, so a
+//! slow heap leak or a failing antenna shows up before it drops a roast.
+//! [`DeviceHealthMonitor`] raises a `device_health` alert when a device
+//! has any [`HealthIssue`] and resolves it once none is left.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+
+use crate::models::{
+    AlertKind, AlertSeverity, DeviceHealthPoint, DeviceHealthQuery, DeviceHealthSnapshot,
+    HealthIssue,
+};
+use crate::telemetry::TelemetryEvent;
+use crate::AppState;
+
+pub const EMA_ALPHA: f64 = 0.1;
+const MINUTE_SECS: i64 = 60;
+/// Minute points kept in memory for the trend lines.
+const TREND_WINDOW_SECS: i64 = 3600;
+/// Fewer minute points than this give no trend.
+const MIN_TREND_POINTS: usize = 30;
+const RSSI_FLOOR: f64 = -90.0;
+const RSSI_GOOD: f64 = -55.0;
+const WEAK_RSSI: f64 = -80.0;
+/// dB per minute, 6 dB an hour.
+const FALLING_RSSI_PER_MIN: f64 = -0.1;
+const HEAP_FLOOR: f64 = 20_000.0;
+const HEAP_GOOD: f64 = 100_000.0;
+/// Bytes per minute.
+const HEAP_LEAK_PER_MIN: f64 = -100.0;
+const DEFAULT_HISTORY_SECS: i64 = 24 * 3600;
+
+#[derive(Default)]
+struct Tracker {
+    rssi: Option<f64>,
+    free_heap: Option<f64>,
+    minute: i64,
+    /// Minute points within [`TREND_WINDOW_SECS`], oldest first.
+    points: VecDeque<DeviceHealthPoint>,
+}
+
+impl Tracker {
+    fn point(&self, ts: i64) -> DeviceHealthPoint {
+        DeviceHealthPoint {
+            ts,
+            rssi: self.rssi.map(round1),
+            free_heap: self.free_heap.map(f64::round),
+            score: score(self.rssi, self.free_heap),
+        }
+    }
+
+    fn snapshot(&self, device_id: &str) -> DeviceHealthSnapshot {
+        let heap_trend = self.trend(|p| p.free_heap);
+        let rssi_trend = self.trend(|p| p.rssi);
+        let mut issues = Vec::new();
+        if heap_trend.is_some_and(|t| t <= HEAP_LEAK_PER_MIN) {
+            issues.push(HealthIssue::HeapLeak);
+        }
+        if self.free_heap.is_some_and(|h| h < HEAP_FLOOR) {
+            issues.push(HealthIssue::LowHeap);
+        }
+        if self.rssi.is_some_and(|r| r < WEAK_RSSI) {
+            issues.push(HealthIssue::WeakSignal);
+        }
+        if rssi_trend.is_some_and(|t| t <= FALLING_RSSI_PER_MIN) {
+            issues.push(HealthIssue::FallingSignal);
+        }
+        let ts = self.points.back().map_or(self.minute, |p| p.ts);
+        DeviceHealthSnapshot {
+            device_id: device_id.to_string(),
+            point: self.point(ts),
+            heap_trend: heap_trend.map(round1),
+            rssi_trend: rssi_trend.map(|t| (t * 1000.0).round() / 1000.0),
+            issues,
+        }
+    }
+
+    /// Least-squares slope per minute of a metric over the minute points.
+    fn trend(&self, metric: impl Fn(&DeviceHealthPoint) -> Option<f64>) -> Option<f64> {
+        let xy: Vec<(f64, f64)> = self
+            .points
+            .iter()
+            .filter_map(|p| metric(p).map(|v| (p.ts as f64 / MINUTE_SECS as f64, v)))
+            .collect();
+        if xy.len() < MIN_TREND_POINTS {
+            return None;
+        }
+        let n = xy.len() as f64;
+        let mean_x = xy.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = xy.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let cov: f64 = xy.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let var: f64 = xy.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        (var > 0.0).then(|| cov / var)
+    }
+}
+
+fn round1(v: f64) -> f64 {
+    (v * 10.0).round() / 10.0
+}
+
+/// Health score of the given averages, 100 when the device reports neither.
+pub fn score(rssi: Option<f64>, free_heap: Option<f64>) -> f64 {
+    let scale = |v: f64, floor: f64, good: f64| ((v - floor) / (good - floor)).clamp(0.0, 1.0);
+    let parts: Vec<f64> = [
+        rssi.map(|r| scale(r, RSSI_FLOOR, RSSI_GOOD)),
+        free_heap.map(|h| scale(h, HEAP_FLOOR, HEAP_GOOD)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if parts.is_empty() {
+        return 100.0;
+    }
+    round1(parts.iter().sum::<f64>() / parts.len() as f64 * 100.0)
+}
+
+#[derive(Clone)]
+pub struct DeviceHealth {
+    db: SqlitePool,
+    devices: Arc<Mutex<HashMap<String, Tracker>>>,
+}
+
+impl DeviceHealth {
+    pub fn new(db: SqlitePool) -> Self {
+        Self {
+            db,
+            devices: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Fold a telemetry payload received at unix time `now` into the
+    /// device's averages. Returns the point of the minute just begun, once
+    /// per minute, for the caller to store.
+    pub fn observe(
+        &self,
+        device_id: &str,
+        payload: &serde_json::Value,
+        now: i64,
+    ) -> Option<DeviceHealthPoint> {
+        let rssi = payload.get("rssi").and_then(|v| v.as_f64());
+        let free_heap = payload.get("freeHeap").and_then(|v| v.as_f64());
+        if rssi.is_none() && free_heap.is_none() {
+            return None;
+        }
+        let ema = |avg: Option<f64>, v: Option<f64>| match (avg, v) {
+            (Some(avg), Some(v)) => Some(avg + EMA_ALPHA * (v - avg)),
+            (None, v) => v,
+            (avg, None) => avg,
+        };
+        let mut devices = self.devices.lock().unwrap();
+        let tracker = devices.entry(device_id.to_string()).or_default();
+        tracker.rssi = ema(tracker.rssi, rssi);
+        tracker.free_heap = ema(tracker.free_heap, free_heap);
+
+        let minute = now - now.rem_euclid(MINUTE_SECS);
+        if minute <= tracker.minute {
+            return None;
+        }
+        tracker.minute = minute;
+        let point = tracker.point(minute);
+        tracker.points.push_back(point.clone());
+        while tracker
+            .points
+            .front()
+            .is_some_and(|p| p.ts <= minute - TREND_WINDOW_SECS)
+        {
+            tracker.points.pop_front();
+        }
+        Some(point)
+    }
+
+    pub fn snapshot(&self, device_id: &str) -> Option<DeviceHealthSnapshot> {
+        let devices = self.devices.lock().unwrap();
+        devices.get(device_id).map(|t| t.snapshot(device_id))
+    }
+
+    /// Every device that reported health telemetry, worst score first.
+    pub fn snapshots(&self) -> Vec<DeviceHealthSnapshot> {
+        let devices = self.devices.lock().unwrap();
+        let mut snapshots: Vec<_> = devices.iter().map(|(id, t)| t.snapshot(id)).collect();
+        snapshots.sort_by(|a, b| {
+            a.point
+                .score
+                .total_cmp(&b.point.score)
+                .then_with(|| a.device_id.cmp(&b.device_id))
+        });
+        snapshots
+    }
+
+    pub async fn record(&self, device_id: &str, point: &DeviceHealthPoint) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO device_health (device_id, ts, rssi, free_heap, score) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(device_id)
+        .bind(point.ts)
+        .bind(point.rssi)
+        .bind(point.free_heap)
+        .bind(point.score)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Stored minute points, oldest first. `now` anchors the default range.
+    pub async fn history(
+        &self,
+        device_id: &str,
+        q: &DeviceHealthQuery,
+        now: i64,
+    ) -> Result<Vec<DeviceHealthPoint>> {
+        let points = sqlx::query_as::<_, DeviceHealthPoint>(
+            "SELECT ts, rssi, free_heap, score FROM device_health WHERE device_id = ? AND ts >= ? AND ts <= ? ORDER BY ts",
+        )
+        .bind(device_id)
+        .bind(q.since.unwrap_or(now - DEFAULT_HISTORY_SECS))
+        .bind(q.until.unwrap_or(now))
+        .fetch_all(&self.db)
+        .await?;
+        Ok(points)
+    }
+}
+
+/// Stores the minute points of every device and alerts on their issues.
+pub struct DeviceHealthMonitor {
+    state: AppState,
+    alerted: HashSet<String>,
+}
+
+impl DeviceHealthMonitor {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            alerted: HashSet::new(),
+        }
+    }
+
+    pub async fn run(mut self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        match self
+            .state
+            .alerts
+            .open_devices(AlertKind::DeviceHealth)
+            .await
+        {
+            Ok(alerted) => self.alerted = alerted,
+            Err(e) => tracing::warn!(error = %e, "Failed to load open device health alerts"),
+        }
+        loop {
+            match telemetry_rx.recv().await {
+                Ok(evt) => self.observe(&evt.device_id, &evt.payload).await,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "Device health monitor lagged behind telemetry");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn observe(&mut self, device_id: &str, payload: &serde_json::Value) {
+        let now = chrono::Utc::now().timestamp();
+        let Some(point) = self.state.device_health.observe(device_id, payload, now) else {
+            return;
+        };
+        // Every instance tracks the averages, only the leader stores and alerts
+        if !self.state.cluster.is_leader() {
+            return;
+        }
+        if let Err(e) = self.state.device_health.record(device_id, &point).await {
+            tracing::warn!(%device_id, error = %e, "Failed to store device health");
+        }
+        let Some(snapshot) = self.state.device_health.snapshot(device_id) else {
+            return;
+        };
+        let alerted = self.alerted.contains(device_id);
+        if !snapshot.issues.is_empty() && !alerted {
+            let issues: Vec<String> = snapshot.issues.iter().map(|i| i.to_string()).collect();
+            let message = format!(
+                "Health of {} is degrading (score {:.0}): {}",
+                device_id,
+                snapshot.point.score,
+                issues.join(", ")
+            );
+            let details = serde_json::to_value(&snapshot).unwrap_or_default();
+            if let Err(e) = self
+                .state
+                .alerts
+                .raise(
+                    AlertKind::DeviceHealth,
+                    AlertSeverity::Warning,
+                    Some(device_id),
+                    None,
+                    &message,
+                    Some(details),
+                )
+                .await
+            {
+                tracing::warn!(%device_id, error = %e, "Failed to record device health alert");
+            }
+            self.alerted.insert(device_id.to_string());
+        } else if snapshot.issues.is_empty() && alerted {
+            if let Err(e) = self
+                .state
+                .alerts
+                .resolve_cleared(AlertKind::DeviceHealth, device_id)
+                .await
+            {
+                tracing::warn!(%device_id, error = %e, "Failed to resolve device health alert");
+            }
+            self.alerted.remove(device_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_score_and_heap_leak_trend() {
+        let db = crate::init_memory_db().await.unwrap();
+        let health = DeviceHealth::new(db);
+        let t0 = 1_700_000_040; // a whole minute
+
+        assert_eq!(score(Some(-55.0), Some(150_000.0)), 100.0);
+        assert_eq!(score(Some(-90.0), None), 0.0);
+        assert_eq!(score(Some(-72.5), Some(60_000.0)), 50.0);
+
+        assert!(health
+            .observe("dev1", &serde_json::json!({"beanTemp": 20.0}), t0)
+            .is_none());
+        let first = health
+            .observe(
+                "dev1",
+                &serde_json::json!({"rssi": -60, "freeHeap": 180000}),
+                t0,
+            )
+            .unwrap();
+        assert_eq!(first.rssi, Some(-60.0));
+        // A second sample in the same minute only moves the averages
+        assert!(health
+            .observe("dev1", &serde_json::json!({"rssi": -70}), t0 + 30)
+            .is_none());
+        assert_eq!(health.snapshot("dev1").unwrap().point.rssi, Some(-61.0));
+
+        // Leaking 500 B a minute for 40 minutes with a steady signal
+        for m in 1..=40 {
+            let payload = serde_json::json!({"rssi": -61, "freeHeap": 180000 - 500 * m});
+            let point = health.observe("dev1", &payload, t0 + 60 * m).unwrap();
+            health.record("dev1", &point).await.unwrap();
+        }
+        let snapshot = health.snapshot("dev1").unwrap();
+        assert_eq!(snapshot.issues, [HealthIssue::HeapLeak]);
+        assert!(snapshot.heap_trend.unwrap() < -400.0);
+        assert!(snapshot.rssi_trend.unwrap().abs() < 0.05);
+
+        let q = DeviceHealthQuery {
+            since: Some(t0 + 60 * 31),
+            until: None,
+        };
+        let points = health.history("dev1", &q, t0 + 60 * 40).await.unwrap();
+        assert_eq!(points.len(), 10);
+        assert!(points[0].free_heap > points[9].free_heap);
+    }
+}
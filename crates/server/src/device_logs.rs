@@ -0,0 +1,264 @@
+//! Firmware debug logs published on `roaster/{device_id}/log`.
+//!
+//! The newest lines of every device are kept in an in-memory ring buffer
+//! (`RUSTROAST_DEVICE_LOG_LINES`, default 500) and broadcast to
+//! `/ws/logs/{device_id}` clients. Devices flagged for troubleshooting
+//! also get their lines written to `device_logs`, so a misbehaving board
+//! can be left running and its logs read back later.
+//!
+//! A payload is either plain text (one line per `\n`) or a JSON object
+//! with `msg`/`message` and an optional `level`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tokio::sync::{broadcast, OnceCell};
+
+use crate::models::{DeviceLogHistoryQuery, DeviceLogLine, LogCapture};
+
+const DEFAULT_BUFFER_LINES: usize = 500;
+const DEFAULT_HISTORY_LIMIT: i64 = 1000;
+const MAX_HISTORY_LIMIT: i64 = 10_000;
+
+#[derive(Clone)]
+pub struct DeviceLogs {
+    db: SqlitePool,
+    capacity: usize,
+    buffers: Arc<Mutex<HashMap<String, VecDeque<DeviceLogLine>>>>,
+    /// Devices flagged for troubleshooting, loaded on first use.
+    captured: Arc<OnceCell<RwLock<HashSet<String>>>>,
+    lines_tx: broadcast::Sender<DeviceLogLine>,
+}
+
+impl DeviceLogs {
+    pub fn new(db: SqlitePool, capacity: usize) -> Self {
+        let (lines_tx, _) = broadcast::channel(256);
+        Self {
+            db,
+            capacity,
+            buffers: Arc::new(Mutex::new(HashMap::new())),
+            captured: Arc::new(OnceCell::new()),
+            lines_tx,
+        }
+    }
+
+    /// Buffer size from `RUSTROAST_DEVICE_LOG_LINES` (default 500).
+    pub fn from_env(db: SqlitePool) -> Self {
+        let capacity = std::env::var("RUSTROAST_DEVICE_LOG_LINES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_BUFFER_LINES);
+        Self::new(db, capacity)
+    }
+
+    /// Buffer, broadcast and (for flagged devices) persist the lines of one
+    /// publish received at unix time `now`.
+    pub async fn record(&self, device_id: &str, payload: &[u8], now: u64) -> Result<()> {
+        let lines: Vec<DeviceLogLine> = parse_lines(payload)
+            .into_iter()
+            .map(|(level, message)| DeviceLogLine {
+                device_id: device_id.to_string(),
+                ts: now as i64,
+                level,
+                message,
+            })
+            .collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        {
+            let mut buffers = self.buffers.lock().unwrap();
+            let buffer = buffers.entry(device_id.to_string()).or_default();
+            for line in &lines {
+                if buffer.len() == self.capacity {
+                    buffer.pop_front();
+                }
+                buffer.push_back(line.clone());
+                let _ = self.lines_tx.send(line.clone());
+            }
+        }
+
+        if self.is_captured(device_id).await? {
+            let mut tx = self.db.begin().await?;
+            for line in &lines {
+                sqlx::query(
+                    "INSERT INTO device_logs (device_id, ts, level, message) VALUES (?, ?, ?, ?)",
+                )
+                .bind(&line.device_id)
+                .bind(line.ts)
+                .bind(&line.level)
+                .bind(&line.message)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// The newest buffered lines of a device, oldest first.
+    pub fn recent(&self, device_id: &str, limit: Option<usize>) -> Vec<DeviceLogLine> {
+        let buffers = self.buffers.lock().unwrap();
+        let Some(buffer) = buffers.get(device_id) else {
+            return Vec::new();
+        };
+        let skip = limit.map_or(0, |limit| buffer.len().saturating_sub(limit));
+        buffer.iter().skip(skip).cloned().collect()
+    }
+
+    /// The buffered lines of a device plus a subscription to the lines
+    /// after them, with no gap or overlap in between.
+    pub fn follow(
+        &self,
+        device_id: &str,
+    ) -> (Vec<DeviceLogLine>, broadcast::Receiver<DeviceLogLine>) {
+        // Lines are sent while the buffers are locked
+        let buffers = self.buffers.lock().unwrap();
+        let backlog = buffers
+            .get(device_id)
+            .map(|b| b.iter().cloned().collect())
+            .unwrap_or_default();
+        (backlog, self.lines_tx.subscribe())
+    }
+
+    /// Persisted lines of a device, oldest first.
+    pub async fn history(
+        &self,
+        device_id: &str,
+        q: &DeviceLogHistoryQuery,
+    ) -> Result<Vec<DeviceLogLine>> {
+        let lines = sqlx::query_as::<_, DeviceLogLine>(
+            r#"
+            SELECT device_id, ts, level, message FROM (
+                SELECT * FROM device_logs
+                WHERE device_id = ? AND ts >= ? AND ts <= ?
+                ORDER BY id DESC LIMIT ?
+            ) ORDER BY id
+            "#,
+        )
+        .bind(device_id)
+        .bind(q.since.unwrap_or(0))
+        .bind(q.until.unwrap_or(i64::MAX))
+        .bind(
+            q.limit
+                .unwrap_or(DEFAULT_HISTORY_LIMIT)
+                .clamp(1, MAX_HISTORY_LIMIT),
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(lines)
+    }
+
+    async fn captured_set(&self) -> Result<&RwLock<HashSet<String>>> {
+        self.captured
+            .get_or_try_init(|| async {
+                let ids: Vec<String> =
+                    sqlx::query_scalar("SELECT device_id FROM device_log_capture")
+                        .fetch_all(&self.db)
+                        .await?;
+                Ok(RwLock::new(ids.into_iter().collect()))
+            })
+            .await
+    }
+
+    pub async fn is_captured(&self, device_id: &str) -> Result<bool> {
+        Ok(self
+            .captured_set()
+            .await?
+            .read()
+            .unwrap()
+            .contains(device_id))
+    }
+
+    /// Devices currently flagged for troubleshooting.
+    pub async fn captures(&self) -> Result<Vec<LogCapture>> {
+        let captures =
+            sqlx::query_as::<_, LogCapture>("SELECT * FROM device_log_capture ORDER BY enabled_at")
+                .fetch_all(&self.db)
+                .await?;
+        Ok(captures)
+    }
+
+    /// Flag a device for troubleshooting. Flagging it again keeps the
+    /// original `enabled_at`.
+    pub async fn start_capture(&self, device_id: &str) -> Result<LogCapture> {
+        let captured = self.captured_set().await?;
+        sqlx::query(
+            "INSERT INTO device_log_capture (device_id, enabled_at) VALUES (?, ?) ON CONFLICT(device_id) DO NOTHING",
+        )
+        .bind(device_id)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await?;
+        captured.write().unwrap().insert(device_id.to_string());
+        tracing::info!(%device_id, "Persisting firmware logs for troubleshooting");
+
+        let capture =
+            sqlx::query_as::<_, LogCapture>("SELECT * FROM device_log_capture WHERE device_id = ?")
+                .bind(device_id)
+                .fetch_one(&self.db)
+                .await?;
+        Ok(capture)
+    }
+
+    /// Remove the flag. Persisted lines are kept. Returns whether the
+    /// device was flagged.
+    pub async fn stop_capture(&self, device_id: &str) -> Result<bool> {
+        let captured = self.captured_set().await?;
+        let result = sqlx::query("DELETE FROM device_log_capture WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&self.db)
+            .await?;
+        captured.write().unwrap().remove(device_id);
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Split a log publish into `(level, message)` lines, skipping blank ones.
+fn parse_lines(payload: &[u8]) -> Vec<(Option<String>, String)> {
+    if let Ok(serde_json::Value::Object(obj)) = serde_json::from_slice(payload) {
+        let text = |key: &str| obj.get(key).and_then(|v| v.as_str());
+        let level = text("level").map(str::to_string);
+        return text("msg")
+            .or_else(|| text("message"))
+            .into_iter()
+            .flat_map(str::lines)
+            .map(str::trim_end)
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| (level.clone(), l.to_string()))
+            .collect();
+    }
+    String::from_utf8_lossy(payload)
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| (None, l.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_lines() {
+        assert_eq!(
+            parse_lines(b"[PID] kp=2.0\r\n\n[WIFI] rssi=-61\n"),
+            [
+                (None, "[PID] kp=2.0".to_string()),
+                (None, "[WIFI] rssi=-61".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_lines(br#"{"level": "warn", "msg": "thermocouple open"}"#),
+            [(Some("warn".to_string()), "thermocouple open".to_string())]
+        );
+        assert!(parse_lines(br#"{"uptime": 12}"#).is_empty());
+        assert!(parse_lines(b"  \n").is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! A device's state at a past moment, for looking into a ruined batch after
+//! the fact: the telemetry it last reported (raw or compacted), the control
+//! commands sent to it, its last status snapshot and the session it was
+//! running.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+
+use crate::models::{
+    ControlAuditEntry, DeviceStateAt, RoastSession, SessionStatus, StateTelemetry, StatusSnapshot,
+};
+
+/// Reconstruct `device_id` at unix time `ts`, or `None` when nothing was
+/// stored about it by then.
+pub async fn state_at(db: &SqlitePool, device_id: &str, ts: i64) -> Result<Option<DeviceStateAt>> {
+    let at = DateTime::<Utc>::from_timestamp(ts, 0)
+        .ok_or_else(|| anyhow::anyhow!("timestamp {ts} out of range"))?;
+
+    let telemetry = sqlx::query_as::<_, (i64, String, Option<i64>)>(
+        "SELECT ts, payload, NULL AS samples FROM telemetry WHERE device_id = ? AND ts <= ?
+         UNION ALL
+         SELECT ts, payload, samples FROM telemetry_rollup WHERE device_id = ? AND ts <= ?
+         ORDER BY ts DESC LIMIT 1",
+    )
+    .bind(device_id)
+    .bind(ts)
+    .bind(device_id)
+    .bind(ts)
+    .fetch_optional(db)
+    .await?
+    .and_then(|(reported, payload, samples)| {
+        Some(StateTelemetry {
+            ts: reported,
+            age_secs: ts - reported,
+            samples,
+            values: serde_json::from_str(&payload).ok()?,
+        })
+    });
+
+    let last_commands = sqlx::query_as::<_, ControlAuditEntry>(
+        r#"
+        SELECT * FROM control_audit c
+        WHERE device_id = ? AND created_at <= ? AND created_at = (
+            SELECT MAX(created_at) FROM control_audit
+            WHERE device_id = c.device_id AND op = c.op AND created_at <= ?
+        )
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(device_id)
+    .bind(at)
+    .bind(at)
+    .fetch_all(db)
+    .await?;
+
+    let status = sqlx::query_as::<_, StatusSnapshot>(
+        "SELECT * FROM status_history WHERE device_id = ? AND ts <= ? ORDER BY ts DESC, id DESC LIMIT 1",
+    )
+    .bind(device_id)
+    .bind(ts)
+    .fetch_optional(db)
+    .await?;
+    // Not every status carries the version
+    let firmware_version = sqlx::query_scalar::<_, String>(
+        "SELECT version FROM status_history WHERE device_id = ? AND ts <= ? AND version IS NOT NULL \
+         ORDER BY ts DESC, id DESC LIMIT 1",
+    )
+    .bind(device_id)
+    .bind(ts)
+    .fetch_optional(db)
+    .await?;
+
+    // A session without an end time only counts while it is still open
+    let session = sqlx::query_as::<_, RoastSession>(
+        r#"
+        SELECT * FROM roast_sessions
+        WHERE device_id = ? AND start_time <= ?
+          AND (end_time >= ? OR (end_time IS NULL AND status IN (?, ?)))
+        ORDER BY start_time DESC LIMIT 1
+        "#,
+    )
+    .bind(device_id)
+    .bind(at)
+    .bind(at)
+    .bind(SessionStatus::Active.to_string())
+    .bind(SessionStatus::Paused.to_string())
+    .fetch_optional(db)
+    .await?;
+
+    if telemetry.is_none() && last_commands.is_empty() && status.is_none() && session.is_none() {
+        return Ok(None);
+    }
+    let values = telemetry.as_ref().map(|t| &t.values);
+    let mode = values
+        .and_then(|v| v.get("controlMode"))
+        .and_then(|m| match m {
+            serde_json::Value::String(s) => Some(s.clone()),
+            other => other
+                .as_f64()
+                .map(|m| if m == 0.0 { "manual" } else { "auto" }.to_string()),
+        });
+    let setpoint = values
+        .and_then(|v| v.get("setpoint"))
+        .and_then(|v| v.as_f64());
+    Ok(Some(DeviceStateAt {
+        device_id: device_id.to_string(),
+        ts,
+        telemetry,
+        mode,
+        setpoint,
+        last_commands,
+        status,
+        firmware_version,
+        session,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateSessionRequest;
+    use crate::services::RoastSessionService;
+    use crate::status_history::StatusHistory;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_state_at_picks_the_latest_of_everything() {
+        let db = crate::init_memory_db().await.unwrap();
+        let now = Utc::now().timestamp();
+        for (ts, bt, setpoint, mode) in [(now - 120, 150.0, 200.0, 1), (now - 60, 160.0, 210.0, 0)]
+        {
+            sqlx::query("INSERT INTO telemetry (device_id, ts, payload) VALUES (?, ?, ?)")
+                .bind("dev1")
+                .bind(ts)
+                .bind(
+                    json!({"beanTemp": bt, "setpoint": setpoint, "controlMode": mode}).to_string(),
+                )
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        let history = StatusHistory::new(db.clone());
+        history
+            .record(
+                "dev1",
+                &json!({"status": "online", "version": "1.2.0"}),
+                now - 300,
+                false,
+            )
+            .await
+            .unwrap();
+        history
+            .record("dev1", &json!({"status": "offline"}), now - 90, false)
+            .await
+            .unwrap();
+
+        let sessions = RoastSessionService::new(db.clone());
+        let session = sessions
+            .create_session(CreateSessionRequest {
+                name: "batch".to_string(),
+                device_id: "dev1".to_string(),
+                profile_id: None,
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: Default::default(),
+                bean_id: None,
+            })
+            .await
+            .unwrap();
+        sessions.start_session(&session.id).await.unwrap();
+        sessions
+            .record_control("dev1", "setpoint", "200", None)
+            .await
+            .unwrap();
+        sessions
+            .record_control("dev1", "setpoint", "210", None)
+            .await
+            .unwrap();
+
+        let state = state_at(&db, "dev1", now - 100).await.unwrap().unwrap();
+        let telemetry = state.telemetry.unwrap();
+        assert_eq!((telemetry.ts, telemetry.age_secs), (now - 120, 20));
+        assert_eq!(state.mode.as_deref(), Some("auto"));
+        assert_eq!(state.setpoint, Some(200.0));
+        assert_eq!(state.status.unwrap().status.as_deref(), Some("online"));
+        assert_eq!(state.firmware_version.as_deref(), Some("1.2.0"));
+        // Before the session and the commands
+        assert!(state.session.is_none() && state.last_commands.is_empty());
+
+        let state = state_at(&db, "dev1", now + 5).await.unwrap().unwrap();
+        assert_eq!(state.mode.as_deref(), Some("manual"));
+        assert_eq!(state.status.unwrap().status.as_deref(), Some("offline"));
+        assert_eq!(state.firmware_version.as_deref(), Some("1.2.0"));
+        assert_eq!(state.session.unwrap().id, session.id);
+        assert_eq!(state.last_commands.len(), 1);
+        assert_eq!(state.last_commands[0].value, "210");
+
+        assert!(state_at(&db, "dev1", now - 1000).await.unwrap().is_none());
+    }
+}
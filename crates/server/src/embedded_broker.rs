@@ -0,0 +1,141 @@
+//! In-process MQTT broker for running rustRoast as a single binary without
+//! installing Mosquitto (`MQTT_EMBEDDED=1`, in builds with the
+//! `embedded-broker` feature).
+//!
+//! The broker listens on `MQTT_EMBEDDED_ADDR` (default `0.0.0.0:1883`) for
+//! the roaster's ESP32, and the server's own client connects to the same
+//! listener over loopback. With `MQTT_USERNAME`/`MQTT_PASSWORD` set, those
+//! are the only credentials the broker accepts.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use anyhow::Result;
+use rustroast_mqtt::{MqttConfig, MqttProtocol, MqttTransportKind};
+
+const DEFAULT_ADDR: &str = "0.0.0.0:1883";
+
+pub fn enabled_from_env() -> bool {
+    std::env::var("MQTT_EMBEDDED")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
+pub fn addr_from_env() -> Result<SocketAddr> {
+    let addr = std::env::var("MQTT_EMBEDDED_ADDR")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_ADDR.to_string());
+    addr.parse()
+        .map_err(|e| anyhow::anyhow!("invalid MQTT_EMBEDDED_ADDR {addr:?}: {e}"))
+}
+
+/// Point the server's client at the embedded broker listening on `addr`,
+/// in place of any configured brokers.
+pub fn connect_locally(cfg: &mut MqttConfig, addr: SocketAddr) {
+    let ip = match addr.ip() {
+        ip if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        ip => ip,
+    };
+    cfg.host = ip.to_string();
+    cfg.port = addr.port();
+    cfg.brokers.clear();
+    cfg.tls = None;
+    cfg.transport = MqttTransportKind::Tcp;
+    // The listener speaks MQTT 3.1.1 only
+    cfg.protocol = MqttProtocol::V311;
+}
+
+/// Start the broker on its own thread. Fails when `addr` can't be bound,
+/// e.g. because another broker already listens there.
+#[cfg(feature = "embedded-broker")]
+pub fn start(addr: SocketAddr, credentials: Option<(String, String)>) -> Result<()> {
+    drop(
+        std::net::TcpListener::bind(addr)
+            .map_err(|e| anyhow::anyhow!("can't listen on {addr}: {e}"))?,
+    );
+    let config = broker_config(addr, credentials)?;
+    std::thread::Builder::new()
+        .name("mqtt-broker".to_string())
+        .spawn(move || {
+            let mut broker = rumqttd::Broker::new(config);
+            if let Err(e) = broker.start() {
+                tracing::error!(error = %e, "Embedded MQTT broker stopped");
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(not(feature = "embedded-broker"))]
+pub fn start(_addr: SocketAddr, _credentials: Option<(String, String)>) -> Result<()> {
+    anyhow::bail!("MQTT_EMBEDDED needs a server built with the `embedded-broker` feature")
+}
+
+/// A single MQTT 3.1.1 listener sized for a handful of roasters and
+/// dashboards.
+#[cfg(feature = "embedded-broker")]
+fn broker_config(
+    addr: SocketAddr,
+    credentials: Option<(String, String)>,
+) -> Result<rumqttd::Config> {
+    let auth = credentials.map(|(username, password)| {
+        serde_json::Map::from_iter([(username, serde_json::Value::String(password))])
+    });
+    let config = serde_json::json!({
+        "id": 0,
+        "router": {
+            "max_connections": 1000,
+            "max_outgoing_packet_count": 200,
+            "max_segment_size": 104_857_600,
+            "max_segment_count": 10,
+        },
+        "v4": {
+            "1": {
+                "name": "v4-1",
+                "listen": addr.to_string(),
+                "next_connection_delay_ms": 1,
+                "connections": {
+                    "connection_timeout_ms": 60_000,
+                    "max_payload_size": 1_048_576,
+                    "max_inflight_count": 100,
+                    "auth": auth,
+                    "dynamic_filters": true,
+                },
+            },
+        },
+    });
+    Ok(serde_json::from_value(config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_locally_replaces_the_configured_brokers() {
+        let mut cfg = MqttConfig {
+            host: "mosquitto".to_string(),
+            port: 8883,
+            brokers: vec![rustroast_mqtt::MqttBroker {
+                host: "mosquitto".to_string(),
+                port: 8883,
+            }],
+            tls: Some(Default::default()),
+            protocol: MqttProtocol::V5,
+            ..MqttConfig::default()
+        };
+        connect_locally(&mut cfg, "0.0.0.0:1884".parse().unwrap());
+        assert_eq!((cfg.host.as_str(), cfg.port), ("127.0.0.1", 1884));
+        assert!(cfg.brokers.is_empty() && cfg.tls.is_none());
+        assert_eq!(cfg.protocol, MqttProtocol::V311);
+
+        connect_locally(&mut cfg, "192.168.1.10:1883".parse().unwrap());
+        assert_eq!(cfg.host, "192.168.1.10");
+
+        #[cfg(feature = "embedded-broker")]
+        broker_config(
+            "0.0.0.0:1883".parse().unwrap(),
+            Some(("roaster".to_string(), "secret".to_string())),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+//! Integrity signatures for session exports.
+//!
+//! Every export carries the SHA-256 of its content and an HMAC-SHA256
+//! signature by the server, so whoever receives a roast log (e.g. a
+//! competition collecting them) can ask `POST /api/exports/verify` whether
+//! it is unchanged since export. CSV exports send the [`ExportIntegrity`]
+//! in the `X-Rustroast-Integrity` header. Artisan JSON embeds it under
+//! `rustroast_integrity`, and its hash covers the compact JSON of the
+//! document without that key.
+//!
+//! The key is `RUSTROAST_EXPORT_SIGNING_KEY`, or one generated on first
+//! use and stored in `export_signing_key` so signatures survive restarts.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use tokio::sync::OnceCell;
+
+use crate::models::{ExportFormat, ExportIntegrity, ExportVerification};
+
+/// Key of the integrity block in Artisan exports.
+pub const ARTISAN_INTEGRITY_KEY: &str = "rustroast_integrity";
+
+#[derive(Clone)]
+pub struct ExportSigner {
+    db: SqlitePool,
+    key: Arc<OnceCell<Vec<u8>>>,
+}
+
+impl ExportSigner {
+    /// Uses `RUSTROAST_EXPORT_SIGNING_KEY` when set, otherwise the stored key.
+    pub fn from_env(db: SqlitePool) -> Self {
+        let configured = std::env::var("RUSTROAST_EXPORT_SIGNING_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+            .map(String::into_bytes);
+        Self {
+            db,
+            key: Arc::new(OnceCell::new_with(configured)),
+        }
+    }
+
+    async fn key(&self) -> Result<&[u8]> {
+        let key = self
+            .key
+            .get_or_try_init(|| async {
+                let mut secret = [0u8; 32];
+                rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut secret);
+                // Keep the first key if another request stored one meanwhile
+                sqlx::query(
+                    "INSERT OR IGNORE INTO export_signing_key (id, secret, created_at) VALUES (1, ?, ?)",
+                )
+                .bind(to_hex(&secret))
+                .bind(Utc::now())
+                .execute(&self.db)
+                .await?;
+                let stored: String =
+                    sqlx::query_scalar("SELECT secret FROM export_signing_key WHERE id = 1")
+                        .fetch_one(&self.db)
+                        .await?;
+                from_hex(&stored)
+                    .ok_or_else(|| anyhow::anyhow!("export_signing_key holds an invalid key"))
+            })
+            .await?;
+        Ok(key)
+    }
+
+    /// Hash and sign an export's content.
+    pub async fn sign(
+        &self,
+        session_id: &str,
+        format: ExportFormat,
+        content: &[u8],
+    ) -> Result<ExportIntegrity> {
+        let key = self.key().await?;
+        // Whole seconds, so the time survives a round trip through JSON
+        let signed_at = Utc
+            .timestamp_opt(Utc::now().timestamp(), 0)
+            .single()
+            .unwrap_or_else(Utc::now);
+        let sha256 = sha256_hex(content);
+        let signature = to_hex(
+            &mac(key, session_id, format, &sha256, signed_at)
+                .finalize()
+                .into_bytes(),
+        );
+        Ok(ExportIntegrity {
+            session_id: session_id.to_string(),
+            format,
+            sha256,
+            signed_at,
+            key_id: key_id(key),
+            signature,
+        })
+    }
+
+    /// Sign an Artisan document and embed the integrity block in it.
+    pub async fn sign_artisan(
+        &self,
+        session_id: &str,
+        doc: &mut serde_json::Value,
+    ) -> Result<ExportIntegrity> {
+        let content = serde_json::to_vec(doc)?;
+        let integrity = self
+            .sign(session_id, ExportFormat::Artisan, &content)
+            .await?;
+        if let Some(obj) = doc.as_object_mut() {
+            obj.insert(
+                ARTISAN_INTEGRITY_KEY.to_string(),
+                serde_json::to_value(&integrity)?,
+            );
+        }
+        Ok(integrity)
+    }
+
+    /// Check content (as hashed at export) against its integrity block.
+    pub async fn verify(
+        &self,
+        content: &[u8],
+        integrity: ExportIntegrity,
+    ) -> Result<ExportVerification> {
+        let key = self.key().await?;
+        let sha256 = sha256_hex(content);
+        let signature_ok = from_hex(&integrity.signature).is_some_and(|sig| {
+            mac(
+                key,
+                &integrity.session_id,
+                integrity.format,
+                &integrity.sha256,
+                integrity.signed_at,
+            )
+            .verify_slice(&sig)
+            .is_ok()
+        });
+        let reason = if sha256 != integrity.sha256 {
+            Some("Content does not match its hash; the export was modified")
+        } else if integrity.key_id != key_id(key) {
+            Some("Export was signed with a different server key")
+        } else if !signature_ok {
+            Some("Signature is not valid; the integrity block was modified")
+        } else {
+            None
+        };
+        Ok(ExportVerification {
+            valid: reason.is_none(),
+            reason: reason.map(str::to_string),
+            sha256,
+            integrity,
+        })
+    }
+}
+
+/// Split an Artisan export into the bytes its hash covers and its
+/// integrity block.
+pub fn split_artisan(content: &str) -> Result<(Vec<u8>, Option<ExportIntegrity>), String> {
+    let mut doc: serde_json::Value =
+        serde_json::from_str(content).map_err(|e| format!("Invalid Artisan JSON: {}", e))?;
+    let integrity = doc
+        .as_object_mut()
+        .and_then(|obj| obj.remove(ARTISAN_INTEGRITY_KEY))
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| format!("Invalid {}: {}", ARTISAN_INTEGRITY_KEY, e))?;
+    let bytes = serde_json::to_vec(&doc).map_err(|e| e.to_string())?;
+    Ok((bytes, integrity))
+}
+
+fn mac(
+    key: &[u8],
+    session_id: &str,
+    format: ExportFormat,
+    sha256: &str,
+    signed_at: DateTime<Utc>,
+) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    let message = format!(
+        "rustroast-export-v1\n{}\n{}\n{}\n{}",
+        session_id,
+        format,
+        sha256,
+        signed_at.timestamp()
+    );
+    mac.update(message.as_bytes());
+    mac
+}
+
+fn key_id(key: &[u8]) -> String {
+    sha256_hex(key)[..8].to_string()
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    // An odd trailing digit fails the `get`
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
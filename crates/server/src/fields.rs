@@ -0,0 +1,93 @@
+//! Sparse fieldsets for list endpoints: `?fields=id,name,status` keeps only
+//! those keys of each record, so list views don't download note blobs and
+//! summary stats they never show.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Parse a `fields` parameter into its names, in the order given. Blank
+/// entries are skipped; an empty list means every field.
+pub fn parse(fields: Option<&str>) -> Vec<&str> {
+    fields
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+/// Serialize `records`, keeping only `fields` of each. Names that aren't a
+/// field of the records are an error naming them, so a typo doesn't quietly
+/// return empty objects.
+pub fn select<T: Serialize>(records: &[T], fields: &[&str]) -> Result<Value, String> {
+    let value = serde_json::to_value(records).map_err(|e| e.to_string())?;
+    if fields.is_empty() {
+        return Ok(value);
+    }
+    let Value::Array(records) = value else {
+        return Ok(value);
+    };
+    if let Some(Value::Object(first)) = records.first() {
+        let unknown: Vec<&str> = fields
+            .iter()
+            .copied()
+            .filter(|f| !first.contains_key(*f))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!("unknown fields: {}", unknown.join(", ")));
+        }
+    }
+    let selected = records
+        .into_iter()
+        .map(|record| match record {
+            Value::Object(mut record) => Value::Object(
+                fields
+                    .iter()
+                    .filter_map(|f| record.remove(*f).map(|v| (f.to_string(), v)))
+                    .collect::<Map<_, _>>(),
+            ),
+            other => other,
+        })
+        .collect();
+    Ok(Value::Array(selected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record {
+        id: &'static str,
+        name: &'static str,
+        notes: Option<&'static str>,
+    }
+
+    #[test]
+    fn test_select_keeps_only_the_requested_fields() {
+        let records = [Record {
+            id: "s1",
+            name: "Ethiopia",
+            notes: Some("long tasting notes"),
+        }];
+        let fields = parse(Some(" id, ,notes"));
+        assert_eq!(fields, ["id", "notes"]);
+        assert_eq!(
+            select(&records, &fields).unwrap(),
+            serde_json::json!([{"id": "s1", "notes": "long tasting notes"}])
+        );
+        assert_eq!(
+            select(&records, &parse(None)).unwrap()[0]["name"],
+            "Ethiopia"
+        );
+        assert_eq!(
+            select(&records, &["id", "colour"]).unwrap_err(),
+            "unknown fields: colour"
+        );
+        // Nothing to check names against without a record
+        assert_eq!(
+            select::<Record>(&[], &["colour"]).unwrap(),
+            serde_json::json!([])
+        );
+    }
+}
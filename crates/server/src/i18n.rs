@@ -0,0 +1,269 @@
+//! Translations for generated artifacts (CSV export labels, event names,
+//! notification titles).
+//!
+//! Requests pick a locale with `?lang=` or the `Accept-Language` header;
+//! background output (notifications) uses `RUSTROAST_LOCALE`. Missing
+//! catalog keys fall back to the key itself.
+
+use std::convert::Infallible;
+
+use axum::extract::FromRequestParts;
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::request::Parts;
+use serde::{Deserialize, Serialize};
+
+use crate::models::RoastEventType;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Es,
+}
+
+/// Key -> [en, de, es].
+const CATALOG: &[(&str, [&str; 3])] = &[
+    ("session", ["Session", "Röstung", "Tueste"]),
+    ("date", ["Date", "Datum", "Fecha"]),
+    ("bean", ["Bean", "Bohne", "Grano"]),
+    ("profile", ["Profile", "Profil", "Perfil"]),
+    (
+        "green_weight",
+        ["Green Weight", "Rohkaffeegewicht", "Peso verde"],
+    ),
+    (
+        "roasted_weight",
+        ["Roasted Weight", "Röstgewicht", "Peso tostado"],
+    ),
+    ("roaster", ["Roaster", "Röster", "Tostador"]),
+    ("unit", ["Unit", "Einheit", "Unidad"]),
+    ("event", ["Event", "Ereignis", "Evento"]),
+    (
+        "roast_cue",
+        ["Roast cue", "Röst-Hinweis", "Aviso de tueste"],
+    ),
+    (
+        "preheat_ready",
+        [
+            "Ready for the next charge",
+            "Bereit für die nächste Charge",
+            "Listo para la siguiente carga",
+        ],
+    ),
+    (
+        "series.bean_temp",
+        ["Bean Temp", "Bohnentemperatur", "Temperatura del grano"],
+    ),
+    (
+        "series.env_temp",
+        ["Env Temp", "Umgebungstemperatur", "Temperatura ambiente"],
+    ),
+    (
+        "series.rate_of_rise",
+        ["Rate of Rise", "Anstiegsrate", "Tasa de aumento"],
+    ),
+    ("series.setpoint", ["Setpoint", "Sollwert", "Consigna"]),
+    ("series.heater_pwm", ["Heater", "Heizung", "Calentador"]),
+    ("series.fan_pwm", ["Fan", "Lüfter", "Ventilador"]),
+    ("series.airflow", ["Airflow", "Luftstrom", "Flujo de aire"]),
+    ("series.profile_target", ["Profile", "Profil", "Perfil"]),
+    ("phase.drying", ["Drying", "Trocknung", "Secado"]),
+    ("phase.maillard", ["Maillard", "Maillard", "Maillard"]),
+    (
+        "phase.development",
+        ["Development", "Entwicklung", "Desarrollo"],
+    ),
+    ("event.charge", ["Charge", "Einfüllen", "Carga"]),
+    ("event.drop", ["Drop", "Auswurf", "Descarga"]),
+    (
+        "event.drying_end",
+        ["Drying End", "Ende Trocknung", "Fin del secado"],
+    ),
+    (
+        "event.first_crack_start",
+        [
+            "First Crack Start",
+            "Beginn First Crack",
+            "Inicio del primer crack",
+        ],
+    ),
+    (
+        "event.first_crack_end",
+        [
+            "First Crack End",
+            "Ende First Crack",
+            "Fin del primer crack",
+        ],
+    ),
+    (
+        "event.second_crack_start",
+        [
+            "Second Crack Start",
+            "Beginn Second Crack",
+            "Inicio del segundo crack",
+        ],
+    ),
+    (
+        "event.second_crack_end",
+        [
+            "Second Crack End",
+            "Ende Second Crack",
+            "Fin del segundo crack",
+        ],
+    ),
+    (
+        "event.development_start",
+        [
+            "Development Start",
+            "Beginn Entwicklung",
+            "Inicio del desarrollo",
+        ],
+    ),
+    ("event.drop_out", ["Drop Out", "Entleerung", "Vaciado"]),
+    (
+        "event.heater_change",
+        ["Heater Change", "Heizleistung geändert", "Cambio de calor"],
+    ),
+    (
+        "event.fan_change",
+        ["Fan Change", "Lüfter geändert", "Cambio de ventilador"],
+    ),
+    (
+        "event.custom",
+        ["Custom", "Benutzerdefiniert", "Personalizado"],
+    ),
+];
+
+impl Locale {
+    fn index(self) -> usize {
+        match self {
+            Locale::En => 0,
+            Locale::De => 1,
+            Locale::Es => 2,
+        }
+    }
+
+    /// Locale for a language tag such as `de`, `de-AT` or `ES`.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Locale::En),
+            "de" => Some(Locale::De),
+            "es" => Some(Locale::Es),
+            _ => None,
+        }
+    }
+
+    /// Best supported locale in an `Accept-Language` header, by q-value.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for entry in header.split(',') {
+            let mut parts = entry.split(';');
+            let Some(locale) = parts.next().and_then(Self::from_tag) else {
+                continue;
+            };
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((locale, q));
+            }
+        }
+        best.map(|(locale, _)| locale)
+    }
+
+    /// Server default from `RUSTROAST_LOCALE` (English if unset).
+    pub fn server_default() -> Self {
+        std::env::var("RUSTROAST_LOCALE")
+            .ok()
+            .and_then(|v| Self::from_tag(&v))
+            .unwrap_or_default()
+    }
+
+    pub fn t(self, key: &str) -> &str {
+        CATALOG
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map_or(key, |(_, strings)| strings[self.index()])
+    }
+
+    pub fn event_name(self, event_type: &RoastEventType) -> &'static str {
+        let key = format!("event.{}", event_type);
+        CATALOG
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map_or("", |(_, strings)| strings[self.index()])
+    }
+}
+
+/// Locale requested by the client: `?lang=` first, then `Accept-Language`.
+pub struct RequestLocale(pub Locale);
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestLocale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let from_query = parts.uri.query().and_then(|q| {
+            q.split('&')
+                .find_map(|kv| kv.strip_prefix("lang="))
+                .and_then(Locale::from_tag)
+        });
+        let from_header = || {
+            parts
+                .headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(Locale::from_accept_language)
+        };
+        Ok(RequestLocale(
+            from_query.or_else(from_header).unwrap_or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_language_and_catalog() {
+        assert_eq!(
+            Locale::from_accept_language("fr-FR, de-AT;q=0.8, es;q=0.9"),
+            Some(Locale::Es)
+        );
+        assert_eq!(
+            Locale::from_accept_language("de-DE,de;q=0.9,en;q=0.8"),
+            Some(Locale::De)
+        );
+        assert_eq!(Locale::from_accept_language("fr, *;q=0.5"), None);
+        assert_eq!(Locale::from_accept_language("es;q=0"), None);
+
+        assert_eq!(Locale::De.t("green_weight"), "Rohkaffeegewicht");
+        assert_eq!(Locale::Es.t("no_such_key"), "no_such_key");
+        assert_eq!(
+            Locale::Es.event_name(&RoastEventType::FirstCrackStart),
+            "Inicio del primer crack"
+        );
+        // Every event type has a catalog entry
+        for event_type in [
+            RoastEventType::Charge,
+            RoastEventType::Drop,
+            RoastEventType::DryingEnd,
+            RoastEventType::FirstCrackStart,
+            RoastEventType::FirstCrackEnd,
+            RoastEventType::SecondCrackStart,
+            RoastEventType::SecondCrackEnd,
+            RoastEventType::DevelopmentStart,
+            RoastEventType::DropOut,
+            RoastEventType::HeaterChange,
+            RoastEventType::FanChange,
+            RoastEventType::Custom,
+        ] {
+            assert!(!Locale::De.event_name(&event_type).is_empty());
+        }
+    }
+}
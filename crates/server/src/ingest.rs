@@ -0,0 +1,322 @@
+//! Limits applied to inbound MQTT publishes before they are parsed, and
+//! the sharding of accepted publishes onto per-device ingest workers.
+//!
+//! The broker may be shared with other clients, so oversized payloads and
+//! deeply nested topics are dropped up front. Drops are counted per reason
+//! and logged at most once per [`LOG_SAMPLE_INTERVAL`] per reason.
+//!
+//! Each device is hashed onto one of N workers, so a burst from one device
+//! only queues behind that device's worker while messages from a single
+//! device are still processed in order. Queuing never blocks the consumer,
+//! so one stalled worker cannot hold up the devices of the others. While
+//! telemetry is journaled (see `telemetry_wal`) nothing is shed: a worker
+//! stalled by a locked database keeps its backlog until it catches up.
+//! Without the journal a worker holding [`WORKER_QUEUE_CAPACITY`] jobs
+//! sheds new telemetry. Status, autotune and other control-plane messages
+//! are rare and not resent, so they are always queued.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustroast_core::{ParsedTopic, TopicLayout};
+use tokio::sync::mpsc;
+
+const DEFAULT_MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+const DEFAULT_MAX_TOPIC_LEVELS: usize = 8;
+const DEFAULT_WORKERS: usize = 4;
+const LOG_SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Jobs past which an unjournaled worker sheds telemetry.
+pub const WORKER_QUEUE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DropReason {
+    PayloadTooLarge,
+    TopicTooDeep,
+    WorkerQueueFull,
+    /// The consumer's own event queue overflowed (see `MQTT_EVENT_OVERFLOW`).
+    SubscriberOverflow,
+    /// Dropped by the MQTT client itself for exceeding `MQTT_MAX_PAYLOAD_SIZE`.
+    Oversized,
+    /// A `roaster/{device_id}/...` topic whose device id fails
+    /// `rustroast_core::is_valid_device_id`.
+    InvalidDeviceId,
+}
+
+impl DropReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DropReason::PayloadTooLarge => "payload_too_large",
+            DropReason::TopicTooDeep => "topic_too_deep",
+            DropReason::WorkerQueueFull => "worker_queue_full",
+            DropReason::SubscriberOverflow => "subscriber_overflow",
+            DropReason::Oversized => "oversized",
+            DropReason::InvalidDeviceId => "invalid_device_id",
+        }
+    }
+}
+
+/// An accepted `roaster/{device_id}/{path}` publish queued for a worker.
+pub struct IngestJob {
+    pub parsed: ParsedTopic,
+    /// Layout the topic was published in.
+    pub layout: TopicLayout,
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// Retained message delivered on subscribing.
+    pub retained: bool,
+    /// When the MQTT client received it, in epoch seconds.
+    pub received_at: u64,
+    pub queued_at: Instant,
+}
+
+/// Result of [`WorkerQueue::push`].
+pub enum Enqueued {
+    Queued,
+    /// Telemetry shed because the worker is too far behind.
+    Shed(IngestJob),
+    /// The worker has stopped.
+    Closed,
+}
+
+/// Consumer side of an ingest worker's queue.
+pub struct WorkerQueue {
+    jobs: mpsc::UnboundedSender<IngestJob>,
+    depth: Arc<AtomicUsize>,
+    /// Depth at which telemetry is shed, `None` to never shed.
+    shed_at: Option<usize>,
+}
+
+/// Worker side of the queue.
+pub struct WorkerJobs {
+    jobs: mpsc::UnboundedReceiver<IngestJob>,
+    depth: Arc<AtomicUsize>,
+}
+
+impl WorkerQueue {
+    pub fn channel(shed_at: Option<usize>) -> (Self, WorkerJobs) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let depth = Arc::new(AtomicUsize::new(0));
+        let queue = Self {
+            jobs: tx,
+            depth: depth.clone(),
+            shed_at,
+        };
+        (queue, WorkerJobs { jobs: rx, depth })
+    }
+
+    /// Queue a job without waiting.
+    pub fn push(&self, job: IngestJob) -> Enqueued {
+        let is_telemetry = matches!(job.parsed, ParsedTopic::Telemetry { .. });
+        if let Some(limit) = self.shed_at {
+            if is_telemetry && self.depth.load(Ordering::Relaxed) >= limit {
+                return Enqueued::Shed(job);
+            }
+        }
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        match self.jobs.send(job) {
+            Ok(()) => Enqueued::Queued,
+            Err(_) => {
+                self.depth.fetch_sub(1, Ordering::Relaxed);
+                Enqueued::Closed
+            }
+        }
+    }
+}
+
+impl WorkerJobs {
+    pub async fn recv(&mut self) -> Option<IngestJob> {
+        let job = self.jobs.recv().await?;
+        self.depth.fetch_sub(1, Ordering::Relaxed);
+        Some(job)
+    }
+}
+
+/// Number of ingest workers from `RUSTROAST_INGEST_WORKERS` (default 4).
+pub fn workers_from_env() -> usize {
+    std::env::var("RUSTROAST_INGEST_WORKERS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_WORKERS)
+}
+
+/// Worker index for a device, stable for the life of the process.
+pub fn shard(device_id: &str, workers: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    device_id.hash(&mut hasher);
+    (hasher.finish() % workers.max(1) as u64) as usize
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IngestLimits {
+    pub max_payload_bytes: usize,
+    pub max_topic_levels: usize,
+}
+
+impl Default for IngestLimits {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            max_topic_levels: DEFAULT_MAX_TOPIC_LEVELS,
+        }
+    }
+}
+
+impl IngestLimits {
+    /// Limits from `RUSTROAST_INGEST_MAX_PAYLOAD_BYTES` and
+    /// `RUSTROAST_INGEST_MAX_TOPIC_LEVELS`, falling back to the defaults.
+    pub fn from_env() -> Self {
+        let env = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|v| *v > 0)
+        };
+        let defaults = Self::default();
+        Self {
+            max_payload_bytes: env("RUSTROAST_INGEST_MAX_PAYLOAD_BYTES")
+                .unwrap_or(defaults.max_payload_bytes),
+            max_topic_levels: env("RUSTROAST_INGEST_MAX_TOPIC_LEVELS")
+                .unwrap_or(defaults.max_topic_levels),
+        }
+    }
+
+    pub fn check(&self, topic: &str, payload: &[u8]) -> Result<(), DropReason> {
+        if payload.len() > self.max_payload_bytes {
+            return Err(DropReason::PayloadTooLarge);
+        }
+        if topic.split('/').count() > self.max_topic_levels {
+            return Err(DropReason::TopicTooDeep);
+        }
+        Ok(())
+    }
+}
+
+/// Rate-limits drop warnings so a flood of bad publishes can't flood the log.
+#[derive(Default)]
+pub struct DropLog {
+    last: HashMap<DropReason, (Instant, u64)>,
+}
+
+impl DropLog {
+    pub fn record(&mut self, reason: DropReason, topic: &str, payload_len: usize) {
+        let now = Instant::now();
+        match self.last.get_mut(&reason) {
+            Some((logged_at, suppressed)) if now - *logged_at < LOG_SAMPLE_INTERVAL => {
+                *suppressed += 1;
+            }
+            entry => {
+                let suppressed = entry.map_or(0, |(_, s)| *s);
+                let topic: String = topic.chars().take(128).collect();
+                tracing::warn!(
+                    reason = reason.as_str(),
+                    %topic,
+                    payload_len,
+                    suppressed,
+                    "Dropped inbound MQTT message"
+                );
+                self.last.insert(reason, (now, 0));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ingest_limits() {
+        let limits = IngestLimits {
+            max_payload_bytes: 16,
+            max_topic_levels: 4,
+        };
+        assert_eq!(limits.check("roaster/dev1/telemetry", b"{}"), Ok(()));
+        assert_eq!(
+            limits.check("roaster/dev1/autotune/status", &[b' '; 16]),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check("roaster/dev1/telemetry", &[b' '; 17]),
+            Err(DropReason::PayloadTooLarge)
+        );
+        assert_eq!(
+            limits.check("roaster/dev1/a/b/c", b"{}"),
+            Err(DropReason::TopicTooDeep)
+        );
+    }
+
+    fn job(topic: &str) -> IngestJob {
+        let (layout, parsed) = ParsedTopic::parse_layout(topic).unwrap();
+        IngestJob {
+            parsed,
+            layout,
+            topic: topic.to_string(),
+            payload: b"{}".to_vec(),
+            retained: false,
+            received_at: 0,
+            queued_at: Instant::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_sheds_only_unjournaled_telemetry() {
+        let (queue, mut jobs) = WorkerQueue::channel(Some(2));
+        assert!(matches!(
+            queue.push(job("roaster/dev1/telemetry")),
+            Enqueued::Queued
+        ));
+        assert!(matches!(
+            queue.push(job("roaster/dev1/telemetry")),
+            Enqueued::Queued
+        ));
+        assert!(matches!(
+            queue.push(job("roaster/dev1/telemetry")),
+            Enqueued::Shed(_)
+        ));
+        // Control-plane messages queue past the limit, in order
+        assert!(matches!(
+            queue.push(job("roaster/dev1/status")),
+            Enqueued::Queued
+        ));
+        jobs.recv().await.unwrap();
+        jobs.recv().await.unwrap();
+        assert_eq!(jobs.recv().await.unwrap().topic, "roaster/dev1/status");
+        assert!(matches!(
+            queue.push(job("roaster/dev1/telemetry")),
+            Enqueued::Queued
+        ));
+
+        // Journaled telemetry is never shed
+        let (queue, jobs) = WorkerQueue::channel(None);
+        for _ in 0..WORKER_QUEUE_CAPACITY * 2 {
+            assert!(matches!(
+                queue.push(job("roaster/dev1/telemetry")),
+                Enqueued::Queued
+            ));
+        }
+        drop(jobs);
+        assert!(matches!(
+            queue.push(job("roaster/dev1/status")),
+            Enqueued::Closed
+        ));
+    }
+
+    #[test]
+    fn test_shard_is_stable_and_in_range() {
+        for device in ["dev1", "dev2", "roaster-kitchen", ""] {
+            let worker = shard(device, 4);
+            assert!(worker < 4);
+            assert_eq!(shard(device, 4), worker);
+            assert_eq!(shard(device, 1), 0);
+        }
+        let used: std::collections::HashSet<usize> =
+            (0..64).map(|i| shard(&format!("dev{}", i), 4)).collect();
+        assert_eq!(used.len(), 4);
+    }
+}
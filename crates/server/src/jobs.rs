@@ -0,0 +1,125 @@
+//! In-memory registry for long-running admin jobs.
+//!
+//! A job is started with the number of items it will process and reports
+//! progress as it goes; clients poll `GET /api/admin/jobs/{id}`. Jobs do
+//! not survive a restart, and only the most recent ones are kept.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const MAX_RETAINED_JOBS: usize = 50;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub status: JobStatus,
+    pub total: usize,
+    pub processed: usize,
+    pub failed: usize,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<RwLock<VecDeque<Job>>>,
+}
+
+impl JobRegistry {
+    /// Register a running job over `total` items.
+    pub async fn start(&self, kind: &str, total: usize) -> JobHandle {
+        let job = Job {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            status: JobStatus::Running,
+            total,
+            processed: 0,
+            failed: 0,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        let id = job.id.clone();
+        let mut jobs = self.jobs.write().await;
+        jobs.push_front(job);
+        // Drop the oldest finished jobs beyond the limit
+        while jobs.len() > MAX_RETAINED_JOBS {
+            match jobs.iter().rposition(|j| j.status != JobStatus::Running) {
+                Some(idx) => {
+                    jobs.remove(idx);
+                }
+                None => break,
+            }
+        }
+        JobHandle {
+            id,
+            registry: self.clone(),
+        }
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.read().await.iter().find(|j| j.id == id).cloned()
+    }
+
+    /// Retained jobs, newest first.
+    pub async fn list(&self) -> Vec<Job> {
+        self.jobs.read().await.iter().cloned().collect()
+    }
+
+    async fn update(&self, id: &str, f: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.jobs.write().await.iter_mut().find(|j| j.id == id) {
+            f(job);
+        }
+    }
+}
+
+/// Progress reporting for one running job.
+pub struct JobHandle {
+    id: String,
+    registry: JobRegistry,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Count one processed item, noting whether it failed.
+    pub async fn advance(&self, ok: bool) {
+        self.registry
+            .update(&self.id, |job| {
+                job.processed += 1;
+                if !ok {
+                    job.failed += 1;
+                }
+            })
+            .await;
+    }
+
+    /// Mark the job finished; it counts as failed if any item failed.
+    pub async fn finish(self) {
+        self.registry
+            .update(&self.id, |job| {
+                job.status = if job.failed == 0 {
+                    JobStatus::Completed
+                } else {
+                    JobStatus::Failed
+                };
+                job.finished_at = Some(Utc::now());
+            })
+            .await;
+    }
+}
@@ -0,0 +1,5631 @@
+use std::net::SocketAddr;
+
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use dotenvy::dotenv;
+use prometheus::{
+    Encoder, Gauge, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+use rumqttc::QoS;
+use rustroast_core::{
+    cluster_heartbeat_topic, roaster_wildcard_all, AutotuneTopic, Command, DeviceError,
+    DeviceErrorInfo, ParsedTopic, TopicError, TopicNegotiator,
+};
+use rustroast_mqtt::{
+    EventReceiver, EventRecvError, MqttConfig, MqttMetricsSnapshot, MqttService, PublishAckError,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::signal;
+use tokio::sync::{broadcast, RwLock};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+// (Static docs in /docs for now; utoipa can be reintroduced later)
+use axum::extract::Query;
+use axum::http::header::CONTENT_TYPE;
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tower_http::services::{ServeDir, ServeFile};
+
+mod alerts;
+mod archive;
+mod auth;
+mod automations;
+mod autotune;
+mod charge_correction;
+mod chart;
+mod cluster;
+mod compaction;
+mod config_bundle;
+mod control_debounce;
+mod control_policy;
+mod cues;
+mod dashboard_qr;
+mod demo;
+mod derived;
+mod device_conflict;
+mod device_health;
+mod device_logs;
+mod device_poller;
+mod device_state;
+mod embedded_broker;
+mod export_signing;
+mod fields;
+mod i18n;
+mod ingest;
+mod jobs;
+mod migrate;
+mod modbus;
+mod models;
+mod object_storage;
+mod oidc;
+mod preheat;
+mod probes;
+mod profile_curve;
+mod report_email;
+mod roast_queue;
+mod roastlog;
+mod routes;
+mod services;
+mod session_export;
+mod session_notes;
+mod session_recovery;
+mod shared_subs;
+mod smoothing;
+mod status_history;
+mod summaries;
+mod telemetry;
+mod telemetry_wal;
+mod tolerance;
+mod units;
+mod webhooks;
+
+use alerts::{AlertMonitor, AlertService};
+use archive::SessionArchiver;
+use auth::{Caller, DashboardToken};
+use automations::AutomationEngine;
+use cluster::Cluster;
+use config_bundle::ConfigBundles;
+use control_debounce::{ControlDebouncer, Debounce};
+use control_policy::ControlGuard;
+use cues::CueEngine;
+use derived::DerivedSpec;
+use device_conflict::{ConflictDetector, DeviceConflict};
+use device_health::{DeviceHealth, DeviceHealthMonitor};
+use device_logs::DeviceLogs;
+use export_signing::ExportSigner;
+use i18n::RequestLocale;
+use ingest::{DropLog, DropReason, Enqueued, IngestJob, IngestLimits, WorkerJobs, WorkerQueue};
+use jobs::JobRegistry;
+use models::*;
+use preheat::{BatchPreheat, PreheatMonitor};
+use probes::ProbeMonitor;
+use report_email::ReportMailer;
+use roast_queue::{QueueMonitor, RoastQueue};
+use routes::{
+    admin_routes, alert_routes, analytics_routes, archive_routes, auth_routes, automation_routes,
+    autotune_routes, batch_scaling_routes, bean_routes, charge_correction_routes, chart_routes,
+    checklist_routes, config_routes, cost_routes, cue_routes, device_health_routes,
+    device_log_routes, device_routes, export_routes, preheat_routes, probe_routes, qr_routes,
+    queue_routes, roastlog_routes, session_note_routes, smoothing_routes, status_history_routes,
+    sync_routes, tolerance_routes, webhook_routes,
+};
+use services::{decimate_telemetry, DeviceService, RoastSessionService, UserService};
+use session_export::SessionExporter;
+use shared_subs::SharedSubscriptions;
+use status_history::StatusHistory;
+use telemetry::TelemetryService;
+use tolerance::ToleranceMonitor;
+use units::{ConvertUnits, Units};
+use webhooks::WebhookService;
+
+#[derive(Clone)]
+pub struct AppState {
+    mqtt: MqttService,
+    pub(crate) telemetry_cache: Arc<RwLock<HashMap<String, (serde_json::Value, u64)>>>,
+    autotune_status_cache: Arc<RwLock<HashMap<String, (serde_json::Value, u64)>>>,
+    autotune_results_cache: Arc<RwLock<HashMap<String, (serde_json::Value, u64)>>>,
+    device_registry: Arc<RwLock<HashMap<String, DeviceInfo>>>,
+    metrics: Arc<Metrics>,
+    db: SqlitePool,
+    /// Read-only pool for history/export queries (may be the same as `db`).
+    read_db: SqlitePool,
+    session_service: RoastSessionService,
+    pub(crate) device_service: DeviceService,
+    pub(crate) telemetry_service: TelemetryService,
+    cue_engine: CueEngine,
+    conflicts: ConflictDetector,
+    alerts: AlertService,
+    /// Between-batches preheat runs.
+    preheat: BatchPreheat,
+    /// Production queue board of planned batches.
+    roast_queue: RoastQueue,
+    /// Export and import of automations, alert limits and preheat limits.
+    config_bundles: ConfigBundles,
+    /// Firmware log ring buffers and troubleshooting capture.
+    device_logs: DeviceLogs,
+    /// RSSI and free heap averages and health scores per device.
+    device_health: DeviceHealth,
+    /// Stored device status snapshots.
+    status_history: StatusHistory,
+    /// Coalesces rapid setpoint commands per device.
+    setpoint_debounce: ControlDebouncer,
+    /// Recent setpoint and heater moves of API keys with a control policy.
+    control_guard: ControlGuard,
+    /// Signs session exports and verifies them later.
+    export_signer: ExportSigner,
+    /// Completed-session archival to object storage.
+    archiver: SessionArchiver,
+    /// Daily report emails over SMTP.
+    report_mailer: ReportMailer,
+    /// Long-running admin jobs and their progress.
+    jobs: JobRegistry,
+    /// Outbound webhooks and their delivery log.
+    webhooks: WebhookService,
+    /// Demo mode: simulated roaster, seeded data, device control refused.
+    demo: bool,
+    /// Leader election between instances sharing the broker.
+    pub(crate) cluster: Cluster,
+    ws_keepalive: WsKeepalive,
+    pub(crate) user_service: UserService,
+    pub(crate) auth: Arc<auth::AuthState>,
+    /// WebSocket control channels for devices connected via WS instead of MQTT.
+    /// Key: device_id, Value: sender for outgoing control commands.
+    device_ws_senders: Arc<RwLock<HashMap<String, tokio::sync::mpsc::UnboundedSender<String>>>>,
+    /// Topic layout (v1 or v2) each device is addressed in.
+    topic_layouts: Arc<TopicNegotiator>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DeviceInfo {
+    device_id: String,
+    /// When the server last received a status. For a retained status
+    /// replayed by the broker that is the last status received live, as
+    /// the replay may be arbitrarily old.
+    last_seen: u64,
+    /// The status is the broker's retained copy: no live status has
+    /// arrived since the server started.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    retained: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rssi: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_raw: Option<serde_json::Value>,
+    /// Set while several boards appear to publish under this device_id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflict: Option<DeviceConflict>,
+    /// Decoded `systemStatus` of the latest telemetry (or status), when
+    /// the device reports an error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_error: Option<DeviceErrorInfo>,
+}
+
+#[derive(Serialize)]
+struct DevicesResponse {
+    devices: Vec<DeviceInfo>,
+}
+
+struct Metrics {
+    mqtt_connected: IntGauge,
+    mqtt_rx_total: IntCounter,
+    mqtt_dropped_total: IntCounterVec, // label: reason
+    mqtt_tx_total: IntCounter,
+    mqtt_publish_failures_total: IntCounter,
+    mqtt_reconnects_total: IntCounter,
+    mqtt_backoff: Gauge,
+    mqtt_offline_queued: IntGauge,
+    mqtt_offline_dropped_total: IntCounterVec, // label: reason
+    mqtt_topic_messages_total: IntCounterVec,  // labels: topic, direction
+    mqtt_topic_bytes_total: IntCounterVec,     // labels: topic, direction
+    ws_clients: IntGauge,
+    telemetry_last_seen: IntGaugeVec,       // label: device_id
+    status_last_seen: IntGaugeVec,          // label: device_id
+    db_pool_size: IntGaugeVec,              // label: pool
+    db_pool_in_use: IntGaugeVec,            // label: pool
+    mqtt_publish_latency: HistogramVec,     // label: lane
+    ingest_queue_depth: IntGaugeVec,        // label: worker
+    ingest_lag: HistogramVec,               // label: worker
+    control_coalesced_total: IntCounterVec, // label: device_id
+    device_errors_total: IntCounterVec,     // labels: device_id, error
+}
+
+impl Metrics {
+    fn new() -> Arc<Self> {
+        let mqtt_connected = IntGauge::new(
+            "rustroast_mqtt_connected",
+            "MQTT connection status (1 connected, 0 otherwise)",
+        )
+        .unwrap();
+        let mqtt_rx_total = IntCounter::new(
+            "rustroast_mqtt_messages_received_total",
+            "Total MQTT messages received",
+        )
+        .unwrap();
+        let mqtt_dropped_total = IntCounterVec::new(
+            prometheus::Opts::new(
+                "rustroast_mqtt_messages_dropped_total",
+                "Inbound MQTT messages dropped by ingest limits",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+        let mqtt_tx_total = IntCounter::new(
+            "rustroast_mqtt_messages_published_total",
+            "Total MQTT messages published",
+        )
+        .unwrap();
+        let mqtt_publish_failures_total = IntCounter::new(
+            "rustroast_mqtt_publish_failures_total",
+            "MQTT publishes the client refused",
+        )
+        .unwrap();
+        let mqtt_reconnects_total = IntCounter::new(
+            "rustroast_mqtt_reconnects_total",
+            "Times the MQTT client reconnected after an error or credential change",
+        )
+        .unwrap();
+        let mqtt_backoff = Gauge::new(
+            "rustroast_mqtt_reconnect_backoff_seconds",
+            "Current wait before the next MQTT connection attempt",
+        )
+        .unwrap();
+        let mqtt_offline_queued = IntGauge::new(
+            "rustroast_mqtt_offline_queued",
+            "MQTT publishes held while the broker is unreachable",
+        )
+        .unwrap();
+        let mqtt_offline_dropped_total = IntCounterVec::new(
+            prometheus::Opts::new(
+                "rustroast_mqtt_offline_dropped_total",
+                "Held MQTT publishes dropped before reconnecting (overflow, expired)",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+        let mqtt_topic_messages_total = IntCounterVec::new(
+            prometheus::Opts::new(
+                "rustroast_mqtt_topic_messages_total",
+                "MQTT messages per topic and direction (received, published)",
+            ),
+            &["topic", "direction"],
+        )
+        .unwrap();
+        let mqtt_topic_bytes_total = IntCounterVec::new(
+            prometheus::Opts::new(
+                "rustroast_mqtt_topic_bytes_total",
+                "MQTT payload bytes per topic and direction (received, published)",
+            ),
+            &["topic", "direction"],
+        )
+        .unwrap();
+        let ws_clients = IntGauge::new(
+            "rustroast_ws_clients",
+            "Number of connected WebSocket clients",
+        )
+        .unwrap();
+        let telemetry_last_seen = IntGaugeVec::new(
+            prometheus::Opts::new(
+                "rustroast_device_telemetry_last_seen",
+                "Last seen telemetry epoch seconds",
+            ),
+            &["device_id"],
+        )
+        .unwrap();
+        let status_last_seen = IntGaugeVec::new(
+            prometheus::Opts::new(
+                "rustroast_device_status_last_seen",
+                "Last seen status epoch seconds",
+            ),
+            &["device_id"],
+        )
+        .unwrap();
+        let db_pool_size = IntGaugeVec::new(
+            prometheus::Opts::new(
+                "rustroast_db_pool_connections",
+                "Open connections per database pool",
+            ),
+            &["pool"],
+        )
+        .unwrap();
+        let db_pool_in_use = IntGaugeVec::new(
+            prometheus::Opts::new(
+                "rustroast_db_pool_in_use",
+                "Connections currently checked out per database pool",
+            ),
+            &["pool"],
+        )
+        .unwrap();
+
+        let mqtt_publish_latency = HistogramVec::new(
+            prometheus::HistogramOpts::new(
+                "rustroast_mqtt_publish_latency_seconds",
+                "Time from publish request until the MQTT client accepted it, per queue lane",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+            ]),
+            &["lane"],
+        )
+        .unwrap();
+        let ingest_queue_depth = IntGaugeVec::new(
+            prometheus::Opts::new(
+                "rustroast_ingest_worker_queue_depth",
+                "Inbound MQTT messages waiting per ingest worker",
+            ),
+            &["worker"],
+        )
+        .unwrap();
+        let ingest_lag = HistogramVec::new(
+            prometheus::HistogramOpts::new(
+                "rustroast_ingest_worker_lag_seconds",
+                "Time inbound MQTT messages waited before their ingest worker picked them up",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+            ]),
+            &["worker"],
+        )
+        .unwrap();
+        let control_coalesced_total = IntCounterVec::new(
+            prometheus::Opts::new(
+                "rustroast_control_coalesced_total",
+                "Setpoint commands replaced by a newer one before they were published",
+            ),
+            &["device_id"],
+        )
+        .unwrap();
+
+        let device_errors_total = IntCounterVec::new(
+            prometheus::Opts::new(
+                "rustroast_device_errors_total",
+                "Times a device entered an error systemStatus, by error name",
+            ),
+            &["device_id", "error"],
+        )
+        .unwrap();
+
+        let registry = prometheus::default_registry();
+        let _ = registry.register(Box::new(mqtt_connected.clone()));
+        let _ = registry.register(Box::new(mqtt_rx_total.clone()));
+        let _ = registry.register(Box::new(mqtt_dropped_total.clone()));
+        let _ = registry.register(Box::new(mqtt_tx_total.clone()));
+        let _ = registry.register(Box::new(mqtt_publish_failures_total.clone()));
+        let _ = registry.register(Box::new(mqtt_reconnects_total.clone()));
+        let _ = registry.register(Box::new(mqtt_backoff.clone()));
+        let _ = registry.register(Box::new(mqtt_offline_queued.clone()));
+        let _ = registry.register(Box::new(mqtt_offline_dropped_total.clone()));
+        let _ = registry.register(Box::new(mqtt_topic_messages_total.clone()));
+        let _ = registry.register(Box::new(mqtt_topic_bytes_total.clone()));
+        let _ = registry.register(Box::new(ws_clients.clone()));
+        let _ = registry.register(Box::new(telemetry_last_seen.clone()));
+        let _ = registry.register(Box::new(status_last_seen.clone()));
+        let _ = registry.register(Box::new(db_pool_size.clone()));
+        let _ = registry.register(Box::new(db_pool_in_use.clone()));
+        let _ = registry.register(Box::new(mqtt_publish_latency.clone()));
+        let _ = registry.register(Box::new(ingest_queue_depth.clone()));
+        let _ = registry.register(Box::new(ingest_lag.clone()));
+        let _ = registry.register(Box::new(control_coalesced_total.clone()));
+        let _ = registry.register(Box::new(device_errors_total.clone()));
+
+        Arc::new(Self {
+            mqtt_connected,
+            mqtt_rx_total,
+            mqtt_dropped_total,
+            mqtt_tx_total,
+            mqtt_publish_failures_total,
+            mqtt_reconnects_total,
+            mqtt_backoff,
+            mqtt_offline_queued,
+            mqtt_offline_dropped_total,
+            mqtt_topic_messages_total,
+            mqtt_topic_bytes_total,
+            ws_clients,
+            telemetry_last_seen,
+            status_last_seen,
+            db_pool_size,
+            db_pool_in_use,
+            mqtt_publish_latency,
+            ingest_queue_depth,
+            ingest_lag,
+            control_coalesced_total,
+            device_errors_total,
+        })
+    }
+
+    fn observe_pool(&self, name: &str, pool: &SqlitePool) {
+        let size = pool.size() as i64;
+        self.db_pool_size.with_label_values(&[name]).set(size);
+        self.db_pool_in_use
+            .with_label_values(&[name])
+            .set(size - pool.num_idle() as i64);
+    }
+
+    /// Bring the MQTT metrics up to the client's own counters.
+    fn observe_mqtt(&self, mqtt: &MqttMetricsSnapshot) {
+        fn advance(counter: &IntCounter, total: u64) {
+            counter.inc_by(total.saturating_sub(counter.get()));
+        }
+        self.mqtt_connected.set(mqtt.connected as i64);
+        advance(&self.mqtt_rx_total, mqtt.messages_received);
+        advance(&self.mqtt_tx_total, mqtt.messages_published);
+        advance(&self.mqtt_publish_failures_total, mqtt.publish_failures);
+        advance(&self.mqtt_reconnects_total, mqtt.reconnects);
+        self.mqtt_backoff.set(mqtt.backoff.as_secs_f64());
+        self.mqtt_offline_queued.set(mqtt.offline_queued as i64);
+        for (reason, total) in [
+            ("overflow", mqtt.offline_overflowed),
+            ("expired", mqtt.offline_expired),
+        ] {
+            advance(
+                &self.mqtt_offline_dropped_total.with_label_values(&[reason]),
+                total,
+            );
+        }
+        for (topic, traffic) in &mqtt.topics {
+            for (direction, messages, bytes) in [
+                (
+                    "received",
+                    traffic.messages_received,
+                    traffic.bytes_received,
+                ),
+                (
+                    "published",
+                    traffic.messages_published,
+                    traffic.bytes_published,
+                ),
+            ] {
+                let labels = [topic.as_str(), direction];
+                advance(
+                    &self.mqtt_topic_messages_total.with_label_values(&labels),
+                    messages,
+                );
+                advance(
+                    &self.mqtt_topic_bytes_total.with_label_values(&labels),
+                    bytes,
+                );
+            }
+        }
+    }
+}
+
+/// Run the server with configuration from the environment. Used by the binary.
+pub async fn run() {
+    dotenv().ok();
+    init_tracing();
+
+    // MQTT setup
+    let demo = demo::enabled_from_env();
+    let mqtt = if demo {
+        // Demo mode runs without a broker; nothing published leaves the process
+        tracing::info!("Demo mode: using the in-process simulator instead of a broker");
+        let (mqtt, mut published) = MqttService::mock();
+        tokio::spawn(async move { while published.recv().await.is_some() {} });
+        mqtt
+    } else {
+        let mut mqtt_cfg =
+            MqttConfig::from_env().unwrap_or_else(|e| panic!("Invalid MQTT configuration: {e}"));
+        if embedded_broker::enabled_from_env() {
+            let addr = embedded_broker::addr_from_env().expect("Invalid MQTT_EMBEDDED_ADDR");
+            let credentials = mqtt_cfg.username.clone().zip(mqtt_cfg.password.clone());
+            let auth = credentials.is_some();
+            embedded_broker::start(addr, credentials)
+                .expect("Failed to start the embedded MQTT broker");
+            tracing::info!(%addr, auth, "Started embedded MQTT broker");
+            embedded_broker::connect_locally(&mut mqtt_cfg, addr);
+        }
+        tracing::info!(host = %mqtt_cfg.host, port = mqtt_cfg.port, failover = mqtt_cfg.brokers.len().saturating_sub(1), tls = mqtt_cfg.tls.is_some(), transport = ?mqtt_cfg.transport, "Configuring MQTT client");
+        MqttService::connect(mqtt_cfg)
+            .await
+            .expect("Failed to initialize MQTT")
+    };
+
+    let db = init_db().await.expect("failed to init db");
+    let read_db = init_read_pool(&db).await;
+    let mut state = build_state(mqtt.clone(), db.clone(), read_db);
+    // Telemetry is journaled to disk ahead of batched inserts. Samples a
+    // crash left in the journal are stored before anything else runs.
+    if let Some(path) = telemetry_wal::TelemetryWal::path_from_env(&db_path()) {
+        let committed = state
+            .telemetry_service
+            .committed_wal_seq()
+            .await
+            .expect("failed to read telemetry journal state");
+        let (wal, replay) = telemetry_wal::TelemetryWal::open(&path, committed)
+            .expect("failed to open telemetry journal");
+        if !replay.is_empty() {
+            state
+                .telemetry_service
+                .store(&replay)
+                .await
+                .expect("failed to replay telemetry journal");
+            let seq = replay.iter().map(|r| r.seq).max().unwrap_or(0);
+            wal.committed(seq)
+                .await
+                .expect("failed to truncate telemetry journal");
+            info!(records = replay.len(), path = %path.display(), "Replayed telemetry journal");
+        }
+        state.telemetry_service = state.telemetry_service.clone().with_wal(wal);
+    }
+    let app = build_router(state.clone());
+
+    let addr: SocketAddr = std::env::var("RUSTROAST_HTTP_ADDR")
+        .unwrap_or_else(|_| "0.0.0.0:8080".to_string())
+        .parse()
+        .expect("Invalid RUSTROAST_HTTP_ADDR");
+
+    info!(%addr, "Starting HTTP server");
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    // Modbus TCP server (disabled unless RUSTROAST_MODBUS_ADDR is set)
+    let _modbus_handle = modbus::start_modbus_server(
+        state.telemetry_cache.clone(),
+        mqtt.clone(),
+        state.topic_layouts.clone(),
+    )
+    .await;
+    // Sessions left active by a restart: resumed after a short outage,
+    // failed with a gap annotation after a long one
+    if let Err(e) = session_recovery::recover_sessions(
+        &state.session_service,
+        session_recovery::max_gap_from_env(),
+        chrono::Utc::now(),
+    )
+    .await
+    {
+        tracing::error!(error = %e, "Failed to recover interrupted sessions");
+    }
+    // Background consumer for MQTT events -> caches + metrics + persistence
+    spawn_mqtt_consumer(&state);
+    // Subscribed only once the consumer listens, so retained status and
+    // autotune messages delivered on subscribing are adopted before serving
+    let subscriptions = subscribe_topics(&mqtt).await;
+    // Leader election with other instances on the broker (opt-in)
+    if state.cluster.enabled() {
+        if subscriptions.is_sharing("telemetry") {
+            tracing::warn!(
+                "Telemetry is a shared subscription: leader-only work only sees the telemetry shared to the leader"
+            );
+        }
+        spawn_cluster_heartbeat(&state);
+    }
+    // Roast cues evaluated against incoming telemetry
+    spawn_cue_engine(&state);
+    // Automation rules (control commands on triggers) for active sessions
+    spawn_automation_engine(&state);
+    // Condition alerts (device id conflicts, over-temperature)
+    spawn_alert_monitor(&state);
+    // Profile tolerance breaches: alert, then pause or a safe setpoint
+    spawn_tolerance_monitor(&state);
+    // Between-batches preheat readiness, safety limits and timeouts
+    spawn_preheat_monitor(&state);
+    // Production queue states from sessions, events and preheats
+    spawn_queue_monitor(&state);
+    // Device RSSI/heap health history and degradation alerts
+    spawn_device_health_monitor(&state);
+    // Stuck or disagreeing temperature probes during roasts
+    spawn_probe_monitor(&state);
+    // Session lifecycle, roast event and alert webhooks
+    spawn_webhook_dispatcher(&state);
+    // Session-aligned telemetry republished for external loggers (opt-in)
+    if session_export::enabled_from_env() {
+        spawn_session_exporter(&state);
+    }
+    // Background pollers for Modbus TCP and WebSocket device connections
+    tokio::spawn(device_poller::start_device_pollers(
+        state.device_service.clone(),
+        state.telemetry_service.clone(),
+    ));
+    // Retention cleanup task
+    tokio::spawn(retention_cleanup_loop(db, state.cluster.clone()));
+    // Per-day, bean and profile aggregates behind the analytics endpoints
+    tokio::spawn(summaries::run(
+        state.db.clone(),
+        state.cluster.clone(),
+        state.session_service.subscribe(),
+        summaries::refresh_interval_from_env(),
+    ));
+    // Completed sessions archived to object storage (opt-in)
+    if state.archiver.is_configured() {
+        tokio::spawn(session_archive_loop(state.clone()));
+    }
+    // Daily report emailed at the configured time (opt-in)
+    if state.report_mailer.is_configured() {
+        tokio::spawn(report_email::run(
+            state.report_mailer.clone(),
+            state.session_service.clone(),
+            state.db.clone(),
+            state.cluster.clone(),
+        ));
+    }
+    if demo {
+        let device_id = demo::device_id_from_env();
+        match demo::seed(&state, &device_id).await {
+            Ok(true) => tracing::info!("Demo mode: seeded sample beans, profiles and sessions"),
+            Ok(false) => tracing::info!("Demo mode: database already has sessions, not seeding"),
+            Err(e) => tracing::error!(error = %e, "Demo mode: failed to seed sample data"),
+        }
+        tokio::spawn(demo::run_simulator(state.clone(), device_id));
+    }
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+    // Leave the broker cleanly so the presence topic reads offline
+    if let Err(e) = mqtt.shutdown(std::time::Duration::from_secs(2)).await {
+        tracing::warn!(?e, "Failed to disconnect from MQTT cleanly");
+    }
+}
+
+/// Subscribe to telemetry/status/autotune wildcards to receive updates
+/// early, shared with other instances as configured (see [`shared_subs`]).
+pub async fn subscribe_topics(mqtt: &MqttService) -> SharedSubscriptions {
+    let subscriptions = SharedSubscriptions::from_env();
+    for (name, filter) in subscriptions.filters() {
+        if let Err(e) = mqtt.subscribe(&filter, rumqttc::QoS::AtMostOnce).await {
+            tracing::warn!(?e, %filter, "Failed to subscribe to {} wildcard", name);
+        }
+    }
+    subscriptions
+}
+
+/// Wire services and caches around an MQTT connection and database pools.
+pub fn build_state(mqtt: MqttService, db: SqlitePool, read_db: SqlitePool) -> AppState {
+    let telemetry_cache = Arc::new(RwLock::new(HashMap::new()));
+    let metrics = Metrics::new();
+    let publish_latency = metrics.mqtt_publish_latency.clone();
+    mqtt.set_publish_observer(Arc::new(move |lane, elapsed| {
+        publish_latency
+            .with_label_values(&[lane.as_str()])
+            .observe(elapsed.as_secs_f64());
+    }));
+    let session_service = RoastSessionService::new(db.clone()).with_read_pool(read_db.clone());
+    let device_service = DeviceService::new(db.clone());
+    let conflicts = ConflictDetector::from_env();
+    let telemetry_service = TelemetryService::new(
+        telemetry_cache.clone(),
+        db.clone(),
+        device_service.clone(),
+        metrics.telemetry_last_seen.clone(),
+        metrics.device_errors_total.clone(),
+        conflicts.clone(),
+    );
+    let cue_engine = CueEngine::new(session_service.clone());
+    let alerts = AlertService::new(db.clone());
+    let webhooks = WebhookService::from_env(db.clone());
+    let device_logs = DeviceLogs::from_env(db.clone());
+    let device_health = DeviceHealth::new(db.clone());
+    let status_history = StatusHistory::new(db.clone());
+    let roast_queue = RoastQueue::from_env(db.clone());
+    let config_bundles = ConfigBundles::new(db.clone());
+    let export_signer = ExportSigner::from_env(db.clone());
+    let user_service = UserService::new(db.clone());
+    let oidc = oidc::OidcConfig::from_env().map(|cfg| {
+        tracing::info!(issuer = %cfg.issuer_url, "OIDC login enabled");
+        oidc::OidcClient::new(cfg)
+    });
+    let auth = Arc::new(auth::AuthState {
+        signer: auth::SessionSigner::from_env(),
+        oidc,
+    });
+    AppState {
+        mqtt,
+        telemetry_cache,
+        device_registry: Arc::new(RwLock::new(HashMap::new())),
+        metrics,
+        db,
+        read_db,
+        autotune_status_cache: Arc::new(RwLock::new(HashMap::new())),
+        autotune_results_cache: Arc::new(RwLock::new(HashMap::new())),
+        session_service,
+        device_service,
+        telemetry_service,
+        cue_engine,
+        conflicts,
+        alerts,
+        preheat: BatchPreheat::from_env(),
+        roast_queue,
+        config_bundles,
+        device_logs,
+        device_health,
+        status_history,
+        setpoint_debounce: ControlDebouncer::from_env(),
+        control_guard: ControlGuard::default(),
+        export_signer,
+        archiver: SessionArchiver::from_env(),
+        report_mailer: ReportMailer::from_env(),
+        jobs: JobRegistry::default(),
+        webhooks,
+        demo: demo::enabled_from_env(),
+        cluster: Cluster::from_env(),
+        ws_keepalive: WsKeepalive::from_env(),
+        user_service,
+        auth,
+        device_ws_senders: Arc::new(RwLock::new(HashMap::new())),
+        topic_layouts: Arc::new(TopicNegotiator::new()),
+    }
+}
+
+/// Background consumer for MQTT events -> caches + metrics + persistence.
+pub fn spawn_mqtt_consumer(state: &AppState) -> tokio::task::JoinHandle<()> {
+    // Subscribe before spawning so publishes right after startup aren't missed
+    let events = state.mqtt.events();
+    tokio::spawn(mqtt_consumer_loop(
+        state.clone(),
+        events,
+        IngestLimits::from_env(),
+        ingest::workers_from_env(),
+    ))
+}
+
+/// Background task publishing cluster heartbeats and electing the leader.
+pub fn spawn_cluster_heartbeat(state: &AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(state.cluster.clone().run(state.mqtt.clone()))
+}
+
+/// Background task firing roast cues from the telemetry stream.
+pub fn spawn_cue_engine(state: &AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(
+        state
+            .cue_engine
+            .clone()
+            .run(state.telemetry_service.subscribe()),
+    )
+}
+
+/// Background task running automation rules from the telemetry stream.
+pub fn spawn_automation_engine(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let engine = AutomationEngine::new(state.clone());
+    tokio::spawn(engine.run(state.telemetry_service.subscribe()))
+}
+
+/// Background task raising and clearing condition alerts from telemetry.
+pub fn spawn_alert_monitor(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let monitor = AlertMonitor::new(state.clone());
+    tokio::spawn(monitor.run(state.telemetry_service.subscribe()))
+}
+
+/// Background task watching active sessions against their profile's
+/// tolerance band.
+pub fn spawn_tolerance_monitor(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let monitor = ToleranceMonitor::new(state.clone());
+    tokio::spawn(monitor.run(state.telemetry_service.subscribe()))
+}
+
+/// Background task following between-batches preheat runs.
+pub fn spawn_preheat_monitor(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let monitor = PreheatMonitor::new(state.clone());
+    tokio::spawn(monitor.run(state.telemetry_service.subscribe()))
+}
+
+/// Background task moving queued batches across the production board.
+pub fn spawn_queue_monitor(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let monitor = QueueMonitor::new(state.clone());
+    tokio::spawn(monitor.run(state.session_service.subscribe()))
+}
+
+/// Background task tracking device health and alerting on its decline.
+pub fn spawn_device_health_monitor(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let monitor = DeviceHealthMonitor::new(state.clone());
+    tokio::spawn(monitor.run(state.telemetry_service.subscribe()))
+}
+
+/// Background task checking active sessions' temperature probes.
+pub fn spawn_probe_monitor(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let monitor = ProbeMonitor::new(state.clone());
+    tokio::spawn(monitor.run(state.telemetry_service.subscribe()))
+}
+
+/// Background task delivering session activity and alerts to webhooks.
+pub fn spawn_webhook_dispatcher(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let dispatcher = state.webhooks.clone();
+    tokio::spawn(dispatcher.run(state.session_service.subscribe(), state.alerts.subscribe()))
+}
+
+/// Background task republishing active-session telemetry to
+/// `rustroast/sessions/{session_id}/telemetry`.
+pub fn spawn_session_exporter(state: &AppState) -> tokio::task::JoinHandle<()> {
+    let exporter = SessionExporter::new(
+        state.session_service.clone(),
+        state.mqtt.clone(),
+        state.cluster.clone(),
+    );
+    tokio::spawn(exporter.run(state.telemetry_service.subscribe()))
+}
+
+/// The HTTP API, WebSocket endpoints and static frontend.
+pub fn build_router(state: AppState) -> Router {
+    // Static frontend (SPA fallback)
+    let server_crate_dir = env!("CARGO_MANIFEST_DIR");
+    let default_app_dir = PathBuf::from(server_crate_dir).join("../../apps/dashboard/build");
+    let app_dir = std::env::var("RUSTROAST_APP_DIR")
+        .unwrap_or_else(|_| default_app_dir.to_string_lossy().to_string());
+    let spa_fallback =
+        ServeDir::new(app_dir.clone()).fallback(ServeFile::new(format!("{}/index.html", app_dir)));
+
+    let router = Router::new()
+        // Undo cached 301 from old /app/ mount point
+        .route(
+            "/app",
+            get(|| async { axum::response::Redirect::temporary("/") }),
+        )
+        .route(
+            "/app/*rest",
+            get(|| async { axum::response::Redirect::temporary("/") }),
+        )
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/api/health/detail", get(health_detail))
+        .route("/version", get(version))
+        .route("/metrics", get(metrics_handler))
+        // Static OpenAPI
+        .route("/api-docs/openapi.json", get(serve_openapi_json))
+        .route("/api-docs/asyncapi.json", get(serve_asyncapi_json))
+        .route("/docs", get(serve_swagger_ui_html))
+        // Swagger UI can be added here when OpenAPI spec is generated
+        // Control API
+        .route(
+            "/api/roaster/:device_id/control/setpoint",
+            post(api_set_setpoint),
+        )
+        .route(
+            "/api/roaster/:device_id/control/fan_pwm",
+            post(api_set_fan_pwm),
+        )
+        .route(
+            "/api/roaster/:device_id/control/heater_pwm",
+            post(api_set_heater_pwm),
+        )
+        .route("/api/roaster/:device_id/control/mode", post(api_set_mode))
+        .route(
+            "/api/roaster/:device_id/control/heater_enable",
+            post(api_set_heater_enable),
+        )
+        .route("/api/roaster/:device_id/control/pid", post(api_set_pid))
+        .route(
+            "/api/roaster/:device_id/control/batch",
+            post(api_control_batch),
+        )
+        .route(
+            "/api/roaster/:device_id/pid/history",
+            get(api_get_pid_history),
+        )
+        .route(
+            "/api/roaster/:device_id/pid/copy-from/:source",
+            post(api_copy_pid),
+        )
+        .route(
+            "/api/roaster/:device_id/control/emergency_stop",
+            post(api_emergency_stop),
+        )
+        .route(
+            "/api/roaster/:device_id/control/audit",
+            get(api_control_audit),
+        )
+        // Control surface discovery for generic UIs
+        .route("/api/roaster/:device_id/controls", get(api_get_controls))
+        // MQTT admin endpoint
+        .route("/api/admin/mqtt/reset", post(api_mqtt_reset))
+        // WebSocket endpoints
+        .route("/ws/telemetry", get(ws_telemetry))
+        .route("/ws/debug", get(ws_debug))
+        .route("/ws/logs/:device_id", get(ws_device_logs))
+        // Device-to-server WebSocket (DEV-017): devices push telemetry, receive control commands
+        .route("/ws/device/:device_id/telemetry", get(ws_device_telemetry))
+        // Read APIs
+        .route(
+            "/api/roaster/:device_id/telemetry/latest",
+            get(api_get_latest_telemetry),
+        )
+        .route(
+            "/api/roaster/:device_id/telemetry",
+            get(api_get_telemetry_history),
+        )
+        .route("/api/devices/registry", get(api_get_devices))
+        .route("/api/devices/conflicts", get(api_list_device_conflicts))
+        .route("/api/devices/error-codes", get(api_list_device_error_codes))
+        .route(
+            "/api/roaster/:device_id/conflict",
+            delete(api_clear_device_conflict),
+        )
+        // Auto-tune APIs
+        .route(
+            "/api/roaster/:device_id/autotune/start",
+            post(api_autotune_start),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/stop",
+            post(api_autotune_stop),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/apply",
+            post(api_autotune_apply),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/status/latest",
+            get(api_get_autotune_status_latest),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/results/latest",
+            get(api_get_autotune_results_latest),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/status",
+            get(api_get_autotune_status_history),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/results",
+            get(api_get_autotune_results_history),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/runs",
+            get(api_list_autotune_runs),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/runs/active",
+            get(api_get_active_autotune_run),
+        )
+        // Roast Session Management API
+        .route("/api/sessions", post(api_create_session))
+        .route("/api/sessions", get(api_list_sessions))
+        .route("/api/sessions/:id", get(api_get_session))
+        .route("/api/sessions/:id", put(api_update_session))
+        .route("/api/sessions/:id", delete(api_delete_session))
+        .route("/api/sessions/:id/start", post(api_start_session))
+        .route("/api/sessions/:id/pause", post(api_pause_session))
+        .route("/api/sessions/:id/resume", post(api_resume_session))
+        .route("/api/sessions/:id/complete", post(api_complete_session))
+        .route("/api/sessions/:id/sign-off", post(api_sign_off_session))
+        .route("/api/sessions/:id/reopen", post(api_reopen_session))
+        .route(
+            "/api/sessions/:id/telemetry",
+            get(api_get_session_telemetry),
+        )
+        .route("/api/sessions/:id/telemetry", post(api_add_telemetry_point))
+        // Data Export API (AP-014)
+        .route(
+            "/api/sessions/:id/scoreboard",
+            get(api_get_session_scoreboard),
+        )
+        .route("/api/sessions/:id/manual", get(api_get_manual_status))
+        .route("/api/sessions/:id/export/csv", get(api_export_csv))
+        .route("/api/sessions/:id/export/artisan", get(api_export_artisan))
+        // Roast defect tags
+        .route(
+            "/api/sessions/:session_id/defects",
+            get(api_list_defects).post(api_create_defect),
+        )
+        .route(
+            "/api/sessions/:session_id/defects/:defect_id",
+            delete(api_delete_defect),
+        )
+        // Cupping Notes API (AP-012)
+        .route("/api/sessions/:session_id/cupping", get(api_get_cupping))
+        .route(
+            "/api/sessions/:session_id/cupping",
+            post(api_create_cupping),
+        )
+        .route(
+            "/api/sessions/:session_id/cupping",
+            delete(api_delete_cupping),
+        )
+        // Roast Events API
+        .route(
+            "/api/sessions/:session_id/events",
+            get(api_get_roast_events),
+        )
+        .route(
+            "/api/sessions/:session_id/events",
+            post(api_create_roast_event),
+        )
+        .route(
+            "/api/sessions/:session_id/events/bulk",
+            post(api_create_roast_events_bulk),
+        )
+        .route(
+            "/api/sessions/:session_id/events/now",
+            post(api_tap_roast_event),
+        )
+        .route(
+            "/api/sessions/:session_id/events/:event_id",
+            put(api_update_roast_event),
+        )
+        .route(
+            "/api/sessions/:session_id/events/:event_id",
+            delete(api_delete_roast_event),
+        )
+        // Roast Profile Management API
+        .route("/api/profiles", post(api_create_profile))
+        .route("/api/profiles", get(api_list_profiles))
+        .route("/api/profiles/:id", get(api_get_profile))
+        .route("/api/profiles/:id", put(api_update_profile))
+        .route("/api/profiles/:id", delete(api_delete_profile))
+        .route(
+            "/api/profiles/import/artisan",
+            post(api_import_artisan_profile),
+        )
+        // Settings API
+        .route("/api/settings", get(api_get_settings))
+        .route("/api/settings/:key", put(api_set_setting))
+        // Device Configuration API (DEV-004)
+        .merge(device_routes())
+        // Authentication (OIDC login, API keys)
+        .merge(auth_routes())
+        // Cross-session analytics
+        .merge(analytics_routes())
+        // Roast cues on profiles and sessions
+        .merge(cue_routes())
+        // Automation rules on profiles and roasters, plus their run log
+        .merge(automation_routes())
+        // Autotune result checks and per-roaster gain limits
+        .merge(autotune_routes())
+        // Batch size scaling rules and scaled profile versions
+        .merge(batch_scaling_routes())
+        // Between-batches preheat settings and runs
+        .merge(preheat_routes())
+        // Pre-roast checklists and their checks per session
+        .merge(checklist_routes())
+        // Voice memos and photos taken during a session
+        .merge(session_note_routes())
+        // Production queue board of planned batches
+        .merge(queue_routes())
+        // Device RSSI/heap health scores and their history
+        .merge(device_health_routes())
+        // Temperature probe faults and session data quality
+        .merge(probe_routes())
+        // Sessions pre-shaped for charting
+        .merge(chart_routes())
+        // Green bean lots and profile recommendations
+        .merge(bean_routes())
+        // Bean moisture/density charge corrections and what sessions used
+        .merge(charge_correction_routes())
+        // Profile tolerance bands watched during roasts
+        .merge(tolerance_routes())
+        // Session cost accounting and the daily cost report
+        .merge(cost_routes())
+        // Alert history and acknowledgement, per-roaster alert limits
+        .merge(alert_routes())
+        // Config export/import (automations, alert and preheat limits)
+        .merge(config_routes())
+        // Firmware debug logs and troubleshooting capture
+        .merge(device_log_routes())
+        // Device status snapshots over time
+        .merge(status_history_routes())
+        // QR codes linking to a roaster's live dashboard
+        .merge(qr_routes())
+        // Curve smoothing presets and smoothed session series
+        .merge(smoothing_routes())
+        // Verification of signed session exports
+        .merge(export_routes())
+        // Session archives in object storage and restores from them
+        .merge(archive_routes())
+        // Single-file .rroastlog session export and import
+        .merge(roastlog_routes())
+        // Outbound webhooks and their delivery log
+        .merge(webhook_routes())
+        // Offline sync for mobile logging clients
+        .merge(sync_routes())
+        // Admin maintenance jobs (derived data rebuilds)
+        .merge(admin_routes());
+    // Test utility: inject fake device messages to exercise WS and ingestion
+    // without a broker
+    #[cfg(feature = "test-endpoints")]
+    let router = router
+        .route(
+            "/api/test/emit-telemetry/:device_id",
+            post(api_test_emit_telemetry),
+        )
+        .route(
+            "/api/test/emit-status/:device_id",
+            post(api_test_emit_status),
+        );
+    router.with_state(state).fallback_service(spa_fallback)
+}
+
+fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new("info,axum=info,hyper=info,rumqttc=warn"))
+        .unwrap();
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(false)
+        .compact()
+        .init();
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut term = signal(SignalKind::terminate()).expect("failed to install signal handler");
+        term.recv().await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<AppState>) -> axum::http::StatusCode {
+    // MQTT must be ready and DB reachable
+    let mqtt_ok = state.mqtt.is_ready();
+    let db_ok = sqlx::query_scalar::<_, i64>("SELECT 1")
+        .fetch_one(&state.db)
+        .await
+        .is_ok();
+    if mqtt_ok && db_ok {
+        axum::http::StatusCode::OK
+    } else {
+        axum::http::StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Readiness plus this instance's cluster role, for operators running
+/// several instances.
+async fn health_detail(State(state): State<AppState>) -> Response {
+    let mqtt_ok = state.mqtt.is_ready();
+    let db_ok = sqlx::query_scalar::<_, i64>("SELECT 1")
+        .fetch_one(&state.db)
+        .await
+        .is_ok();
+    let status = if mqtt_ok && db_ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let cluster = state.cluster.status(Instant::now());
+    let body = serde_json::json!({
+        "status": if status == StatusCode::OK { "ok" } else { "degraded" },
+        "version": env!("CARGO_PKG_VERSION"),
+        "mqtt_connected": mqtt_ok,
+        "mqtt_broker": state.mqtt.broker(),
+        "db_ok": db_ok,
+        "instance_id": cluster.instance_id,
+        "role": cluster.role,
+        "cluster_enabled": state.cluster.enabled(),
+        "leader_id": cluster.leader_id,
+        "peers": cluster.peers,
+        "demo": state.demo,
+    });
+    (status, Json(body)).into_response()
+}
+
+async fn version() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    state.metrics.observe_pool("write", &state.db);
+    state.metrics.observe_pool("read", &state.read_db);
+    state.metrics.observe_mqtt(&state.mqtt.metrics());
+    let encoder = TextEncoder::new();
+    let metric_families = prometheus::gather();
+    let mut buf = Vec::new();
+    encoder.encode(&metric_families, &mut buf).unwrap();
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(axum::http::header::CONTENT_TYPE, encoder.format_type())
+        .body(axum::body::Body::from(buf))
+        .unwrap()
+}
+
+async fn serve_openapi_json() -> Response {
+    let body = include_str!("static/openapi.json");
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(axum::body::Body::from(body))
+        .unwrap()
+}
+
+/// MQTT topics and WebSocket messages, the streaming counterpart of the
+/// OpenAPI document.
+async fn serve_asyncapi_json() -> Response {
+    let body = include_str!("static/asyncapi.json");
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(axum::body::Body::from(body))
+        .unwrap()
+}
+
+async fn serve_swagger_ui_html() -> Response {
+    let body = include_str!("static/docs.html");
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(axum::body::Body::from(body))
+        .unwrap()
+}
+
+// ----- Control API payloads -----
+
+#[derive(Deserialize, Serialize)]
+struct SetpointPayload {
+    value: f64,
+}
+#[derive(Deserialize, Serialize)]
+struct FanPwmPayload {
+    value: u16,
+}
+#[derive(Deserialize, Serialize)]
+struct HeaterPwmPayload {
+    value: u8,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ModePayload {
+    mode: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct EnablePayload {
+    enabled: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct PidPayload {
+    kp: f64,
+    ki: f64,
+    kd: f64,
+}
+
+#[derive(Deserialize, Default)]
+struct PublishOpts {
+    wait_ack: Option<bool>,
+    timeout_ms: Option<u64>,
+    /// Refuse setpoint/heater enable with 409 when the device can't act on
+    /// it (see [`control_precondition`]).
+    strict: Option<bool>,
+    /// How recent the last telemetry must be for `strict` (default 10s).
+    max_age_secs: Option<u64>,
+}
+
+/// Default telemetry age within which a device counts as online for
+/// strict control commands.
+const STRICT_CONTROL_MAX_AGE_SECS: u64 = 10;
+
+/// A single control command, tagged by `op` with the same body as the
+/// matching `/control/*` endpoint, e.g. `{"op": "fan_pwm", "value": 200}`.
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ControlOp {
+    Setpoint(SetpointPayload),
+    FanPwm(FanPwmPayload),
+    HeaterPwm(HeaterPwmPayload),
+    Mode(ModePayload),
+    HeaterEnable(EnablePayload),
+    Pid(PidPayload),
+}
+
+impl ControlOp {
+    fn name(&self) -> &'static str {
+        match self {
+            ControlOp::Setpoint(_) => "setpoint",
+            ControlOp::FanPwm(_) => "fan_pwm",
+            ControlOp::HeaterPwm(_) => "heater_pwm",
+            ControlOp::Mode(_) => "mode",
+            ControlOp::HeaterEnable(_) => "heater_enable",
+            ControlOp::Pid(_) => "pid",
+        }
+    }
+
+    /// The setpoint or heater PWM being sent, with the telemetry field
+    /// reporting the device's current one.
+    fn level(&self) -> Option<(f64, &'static str)> {
+        match self {
+            ControlOp::Setpoint(body) => Some((body.value, "setpoint")),
+            ControlOp::HeaterPwm(body) => Some((f64::from(body.value), "heaterPWM")),
+            _ => None,
+        }
+    }
+
+    /// The firmware command this operation sends.
+    fn command(&self) -> Result<Command, &'static str> {
+        Ok(match self {
+            ControlOp::Setpoint(body) => Command::Setpoint(body.value),
+            ControlOp::FanPwm(body) => Command::FanPwm(body.value),
+            ControlOp::HeaterPwm(body) => Command::HeaterPwm(body.value),
+            ControlOp::Mode(body) => Command::Mode(body.mode.parse()?),
+            ControlOp::HeaterEnable(body) => Command::HeaterEnable(body.enabled),
+            ControlOp::Pid(body) => Command::Pid {
+                kp: body.kp,
+                ki: body.ki,
+                kd: body.kd,
+            },
+        })
+    }
+
+    /// Validate the command and build its MQTT topic and payload.
+    fn message(&self, device_id: &str) -> Result<(String, String), &'static str> {
+        let command = self.command()?;
+        command.validate()?;
+        Ok((command.topic(device_id), command.payload()))
+    }
+}
+
+#[derive(Deserialize)]
+struct ControlBatchRequest {
+    operations: Vec<ControlOp>,
+    /// Skip the remaining operations after the first failure.
+    #[serde(default)]
+    abort_on_failure: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ControlBatchStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize)]
+struct ControlBatchItemResult {
+    index: usize,
+    op: &'static str,
+    status: ControlBatchStatus,
+    /// HTTP status the single-command endpoint would have returned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ControlBatchResponse {
+    results: Vec<ControlBatchItemResult>,
+    aborted: bool,
+}
+
+/// Upper bound on operations per batch.
+const MAX_CONTROL_BATCH: usize = 32;
+
+#[derive(Serialize)]
+struct LatestTelemetryResponse {
+    device_id: String,
+    timestamp: u64,
+    telemetry: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct TelemetryItem {
+    ts: i64,
+    telemetry: serde_json::Value,
+    /// Set on per-minute rollups of compacted telemetry: how many raw
+    /// samples were averaged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    samples: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct TelemetryHistoryResponse {
+    device_id: String,
+    count: usize,
+    items: Vec<TelemetryItem>,
+}
+
+// ----- Control API handlers -----
+
+// OpenAPI annotations omitted in static docs mode
+async fn api_set_setpoint(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(body): Json<SetpointPayload>,
+) -> impl IntoResponse {
+    let op = ControlOp::Setpoint(body);
+    publish_control_as(&state, &caller, &device_id, &op, &opts).await
+}
+
+// OpenAPI annotations omitted in static docs mode
+async fn api_set_fan_pwm(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(body): Json<FanPwmPayload>,
+) -> impl IntoResponse {
+    let op = ControlOp::FanPwm(body);
+    publish_control_as(&state, &caller, &device_id, &op, &opts).await
+}
+
+// OpenAPI annotations omitted in static docs mode
+async fn api_set_heater_pwm(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(body): Json<HeaterPwmPayload>,
+) -> impl IntoResponse {
+    let op = ControlOp::HeaterPwm(body);
+    publish_control_as(&state, &caller, &device_id, &op, &opts).await
+}
+
+// OpenAPI annotations omitted
+async fn api_set_mode(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(body): Json<ModePayload>,
+) -> impl IntoResponse {
+    let op = ControlOp::Mode(body);
+    publish_control_as(&state, &caller, &device_id, &op, &opts).await
+}
+
+// OpenAPI annotations omitted
+async fn api_set_heater_enable(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(body): Json<EnablePayload>,
+) -> impl IntoResponse {
+    let op = ControlOp::HeaterEnable(body);
+    publish_control_as(&state, &caller, &device_id, &op, &opts).await
+}
+
+// OpenAPI annotations omitted in static docs mode
+async fn api_set_pid(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(body): Json<PidPayload>,
+) -> impl IntoResponse {
+    let op = ControlOp::Pid(body);
+    let resp = publish_control_as(&state, &caller, &device_id, &op, &opts).await;
+    if resp.status().is_success() {
+        record_manual_pid(&state, &device_id, &op).await;
+    }
+    resp
+}
+
+/// Record gains sent through the control API in the PID history.
+async fn record_manual_pid(state: &AppState, device_id: &str, op: &ControlOp) {
+    if let ControlOp::Pid(pid) = op {
+        record_pid_gains(
+            state,
+            device_id,
+            RecordPidGains {
+                kp: pid.kp,
+                ki: pid.ki,
+                kd: pid.kd,
+                source: "manual",
+                source_device_id: None,
+                scale: None,
+            },
+        )
+        .await;
+    }
+}
+
+#[derive(Deserialize)]
+struct CopyPidQuery {
+    scale: Option<f64>,
+    timeout_ms: Option<u64>,
+    force: Option<bool>,
+}
+
+/// Copy the latest applied PID gains from `source` to `target`, optionally scaled.
+/// Both roasters must be registered with the same device profile (machine
+/// model); `force=true` skips the check when either model is unknown. The
+/// copy is only recorded in the PID history once the broker acknowledged it.
+async fn api_copy_pid(
+    Path((target, source)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Query(q): Query<CopyPidQuery>,
+) -> Response {
+    if target == source {
+        return (StatusCode::BAD_REQUEST, "source and target must differ").into_response();
+    }
+    let scale = q.scale.unwrap_or(1.0);
+    if !scale.is_finite() || scale <= 0.0 || scale > 10.0 {
+        return (StatusCode::BAD_REQUEST, "scale must be in (0, 10]").into_response();
+    }
+
+    let (src_dev, dst_dev) = match tokio::try_join!(
+        state.device_service.get_device_by_device_id(&source),
+        state.device_service.get_device_by_device_id(&target),
+    ) {
+        Ok(pair) => pair,
+        Err(e) => {
+            tracing::error!(?e, "Failed to load devices for PID copy");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load devices").into_response();
+        }
+    };
+    let model = |dev: &Option<DeviceWithConnections>| {
+        dev.as_ref().and_then(|d| d.device.profile_id.clone())
+    };
+    match (model(&src_dev), model(&dst_dev)) {
+        (Some(src), Some(dst)) if src != dst => {
+            return (
+                StatusCode::CONFLICT,
+                "source and target roasters use different device profiles",
+            )
+                .into_response();
+        }
+        (Some(_), Some(_)) => {}
+        _ if q.force.unwrap_or(false) => {}
+        _ => {
+            return (
+                StatusCode::CONFLICT,
+                "Device profile of the source or target roaster is unknown. Pass force=true to copy anyway",
+            )
+                .into_response();
+        }
+    }
+
+    let latest = match state.device_service.latest_pid_gains(&source).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                "No applied PID gains recorded for source roaster",
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to load PID history");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load PID history",
+            )
+                .into_response();
+        }
+    };
+
+    let (kp, ki, kd) = (latest.kp * scale, latest.ki * scale, latest.kd * scale);
+    let command = Command::Pid { kp, ki, kd };
+    let resp = publish_qos1_and_maybe_wait_ack(
+        &state,
+        &command.topic(&target),
+        command.payload(),
+        true,
+        q.timeout_ms.unwrap_or(1000),
+    )
+    .await;
+    if !resp.status().is_success() {
+        return resp;
+    }
+
+    match state
+        .device_service
+        .record_pid_gains(
+            &target,
+            RecordPidGains {
+                kp,
+                ki,
+                kd,
+                source: "copy",
+                source_device_id: Some(&source),
+                scale: Some(scale),
+            },
+        )
+        .await
+    {
+        Ok(entry) => Json(entry).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to record PID history");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "PID gains sent but history could not be recorded",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PidHistoryQuery {
+    limit: Option<i64>,
+}
+
+async fn api_get_pid_history(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(q): Query<PidHistoryQuery>,
+) -> Response {
+    let limit = q.limit.unwrap_or(50).clamp(1, 500);
+    match state
+        .device_service
+        .list_pid_history(&device_id, limit)
+        .await
+    {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to list PID history");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list PID history",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Best-effort history write; control commands are not failed on DB errors.
+async fn record_pid_gains(state: &AppState, device_id: &str, gains: RecordPidGains<'_>) {
+    if let Err(e) = state
+        .device_service
+        .record_pid_gains(device_id, gains)
+        .await
+    {
+        tracing::warn!(?e, device_id, "Failed to record PID history");
+    }
+}
+
+/// [`publish_control`] on behalf of an API caller, refused with 403 when
+/// the caller's API key policy doesn't allow it.
+async fn publish_control_as(
+    state: &AppState,
+    caller: &Caller,
+    device_id: &str,
+    op: &ControlOp,
+    opts: &PublishOpts,
+) -> Response {
+    let (value, current) = match op.level() {
+        Some((value, field)) => {
+            let current = state
+                .telemetry_cache
+                .read()
+                .await
+                .get(device_id)
+                .and_then(|(t, _)| t.get(field).and_then(|v| v.as_f64()));
+            (Some(value), current)
+        }
+        None => (None, None),
+    };
+    if let Err(resp) =
+        check_control_policy(state, caller, device_id, op.name(), value, current).await
+    {
+        return resp;
+    }
+    publish_control(state, device_id, op, opts).await
+}
+
+/// Check a control command against the caller's API key policy (see
+/// [`control_policy`]). Callers without one pass.
+async fn check_control_policy(
+    state: &AppState,
+    caller: &Caller,
+    device_id: &str,
+    op: &'static str,
+    value: Option<f64>,
+    current: Option<f64>,
+) -> Result<(), Response> {
+    let Some(subject) = caller.subject.as_deref() else {
+        return Ok(());
+    };
+    let policy = match control_policy::get(&state.db, subject).await {
+        Ok(Some(policy)) => policy,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            tracing::error!(?e, subject, "Failed to load control policy");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load control policy",
+            )
+                .into_response());
+        }
+    };
+    state
+        .control_guard
+        .check(&policy, device_id, op, value, current, Instant::now())
+        .map_err(|reason| {
+            tracing::warn!(
+                subject,
+                device_id,
+                op,
+                %reason,
+                "Control command refused by API key policy"
+            );
+            (StatusCode::FORBIDDEN, reason).into_response()
+        })
+}
+
+/// Validate and publish one control command.
+async fn publish_control(
+    state: &AppState,
+    device_id: &str,
+    op: &ControlOp,
+    opts: &PublishOpts,
+) -> Response {
+    let (topic, payload) = match op.message(device_id) {
+        Ok(msg) => msg,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if opts.strict.unwrap_or(false) {
+        let telemetry = state.telemetry_cache.read().await.get(device_id).cloned();
+        let status = state
+            .device_registry
+            .read()
+            .await
+            .get(device_id)
+            .and_then(|d| Some((d.status_raw.clone()?, d.last_seen)));
+        let max_age = opts.max_age_secs.unwrap_or(STRICT_CONTROL_MAX_AGE_SECS);
+        if let Err(reason) = control_precondition(
+            op,
+            telemetry.as_ref(),
+            status.as_ref(),
+            epoch_secs(),
+            max_age,
+        ) {
+            return (StatusCode::CONFLICT, reason).into_response();
+        }
+    }
+    if let ControlOp::Setpoint(_) = op {
+        let immediate = opts.wait_ack.unwrap_or(false);
+        match state
+            .setpoint_debounce
+            .submit(&topic, &payload, Instant::now(), immediate)
+        {
+            Debounce::Publish => {}
+            Debounce::Deferred {
+                flush_in,
+                coalesced,
+            } => {
+                match flush_in {
+                    Some(delay) => spawn_setpoint_flush(state, device_id, topic, delay),
+                    None => state
+                        .metrics
+                        .control_coalesced_total
+                        .with_label_values(&[device_id])
+                        .inc(),
+                }
+                let body = serde_json::json!({"deferred": true, "coalesced": coalesced});
+                return (StatusCode::ACCEPTED, Json(body)).into_response();
+            }
+        }
+    }
+    let resp = publish_qos1_and_maybe_wait_ack(
+        state,
+        &topic,
+        payload.clone(),
+        opts.wait_ack.unwrap_or(false),
+        opts.timeout_ms.unwrap_or(1000),
+    )
+    .await;
+    if resp.status().is_success() {
+        record_control(state, device_id, op.name(), &payload).await;
+    }
+    resp
+}
+
+/// Publish the latest deferred setpoint of `topic` once its window ends.
+fn spawn_setpoint_flush(state: &AppState, device_id: &str, topic: String, delay: Duration) {
+    let state = state.clone();
+    let device_id = device_id.to_string();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let Some((payload, coalesced)) = state.setpoint_debounce.flush(&topic, Instant::now())
+        else {
+            return;
+        };
+        let resp =
+            publish_qos1_and_maybe_wait_ack(&state, &topic, payload.clone(), false, 1000).await;
+        if resp.status().is_success() {
+            tracing::debug!(%device_id, %payload, coalesced, "Published coalesced setpoint");
+            record_control(&state, &device_id, "setpoint", &payload).await;
+        }
+    });
+}
+
+/// Whether the device can act on a setpoint or heater enable right now,
+/// from its last telemetry and status (each with the unix time received).
+/// It must have sent telemetry within `max_age` seconds without a later
+/// `offline` status, report `systemStatus` 0 and, for a setpoint, be in
+/// auto mode. Turning the heater off and other commands always pass.
+fn control_precondition(
+    op: &ControlOp,
+    telemetry: Option<&(serde_json::Value, u64)>,
+    status: Option<&(serde_json::Value, u64)>,
+    now: u64,
+    max_age: u64,
+) -> Result<(), String> {
+    match op {
+        ControlOp::Setpoint(_) | ControlOp::HeaterEnable(EnablePayload { enabled: true }) => {}
+        _ => return Ok(()),
+    }
+    let Some((telemetry, seen)) = telemetry else {
+        return Err("Device has not sent any telemetry".to_string());
+    };
+    let age = now.saturating_sub(*seen);
+    if age > max_age {
+        return Err(format!(
+            "Device is offline: last telemetry {}s ago (limit {}s)",
+            age, max_age
+        ));
+    }
+    if let Some((status, status_seen)) = status {
+        if status_seen >= seen && status.get("status").and_then(|v| v.as_str()) == Some("offline") {
+            return Err("Device reported itself offline".to_string());
+        }
+    }
+    match telemetry.get("systemStatus").and_then(|v| v.as_i64()) {
+        Some(0) | None => {}
+        Some(code) => {
+            let error = DeviceError::from_code(code).map(|e| e.description());
+            return Err(format!(
+                "Device is in error state (systemStatus {}: {})",
+                code,
+                error.unwrap_or_default()
+            ));
+        }
+    }
+    if matches!(op, ControlOp::Setpoint(_))
+        && telemetry.get("controlMode").and_then(|v| v.as_i64()) == Some(0)
+    {
+        return Err("Device is in manual mode; switch to auto for setpoint control".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ControlValueKind {
+    Number,
+    Integer,
+    Boolean,
+    Choice,
+    /// `kp`, `ki` and `kd` together.
+    Gains,
+    /// No value, e.g. emergency stop.
+    Action,
+}
+
+/// One control a device accepts, as listed by `GET /controls`.
+#[derive(Serialize, Debug)]
+struct DeviceControl {
+    op: &'static str,
+    /// `POST` endpoint taking the command body.
+    endpoint: String,
+    kind: ControlValueKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Vec<&'static str>>,
+    /// Value from the latest telemetry.
+    current: Option<serde_json::Value>,
+    /// Default from the device's profile (machine preset).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: Option<serde_json::Value>,
+    /// Why a strict command would be refused right now.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocked_reason: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct DeviceControlSurface {
+    device_id: String,
+    /// Telemetry received within the strict control window.
+    online: bool,
+    /// Whether the controls were derived from the device's telemetry. Until
+    /// it reports, the full firmware control set is assumed.
+    reported: bool,
+    /// Device profile whose limits and defaults were applied.
+    profile_id: Option<String>,
+    controls: Vec<DeviceControl>,
+}
+
+/// The controls a device offers: each firmware control whose value the
+/// device reports in telemetry (all of them before it reports), with the
+/// endpoint range narrowed by its profile's `max_temp` and `min_fan_pwm`.
+fn control_surface(
+    device_id: &str,
+    telemetry: Option<&(serde_json::Value, u64)>,
+    status: Option<&(serde_json::Value, u64)>,
+    preset: Option<&DeviceProfile>,
+    now: u64,
+    demo: bool,
+) -> DeviceControlSurface {
+    let latest = telemetry.map(|(t, _)| t);
+    let field = |name: &str| latest.and_then(|t| t.get(name)).cloned();
+    let reports = |name: &str| latest.is_none_or(|t| t.get(name).is_some());
+    let blocked = |op: &ControlOp| {
+        control_precondition(op, telemetry, status, now, STRICT_CONTROL_MAX_AGE_SECS).err()
+    };
+    let control = |op: &'static str, kind: ControlValueKind| DeviceControl {
+        op,
+        endpoint: format!("/api/roaster/{}/control/{}", device_id, op),
+        kind,
+        min: None,
+        max: None,
+        unit: None,
+        options: None,
+        current: None,
+        default: None,
+        blocked_reason: None,
+    };
+
+    let mut controls = Vec::new();
+    if reports("setpoint") {
+        let (min, max) = AutomationCommand::Setpoint.range();
+        controls.push(DeviceControl {
+            min: Some(min),
+            max: Some(preset.and_then(|p| p.max_temp).map_or(max, |t| t.min(max))),
+            unit: Some("°C"),
+            current: field("setpoint"),
+            default: preset.and_then(|p| p.default_setpoint).map(Into::into),
+            blocked_reason: blocked(&ControlOp::Setpoint(SetpointPayload { value: 0.0 })),
+            ..control("setpoint", ControlValueKind::Number)
+        });
+    }
+    if reports("fanPWM") {
+        let (min, max) = AutomationCommand::FanPwm.range();
+        let preset_min = preset.and_then(|p| p.min_fan_pwm).map(f64::from);
+        controls.push(DeviceControl {
+            min: Some(preset_min.map_or(min, |m| m.clamp(min, max))),
+            max: Some(max),
+            unit: Some("pwm"),
+            current: field("fanPWM"),
+            default: preset.and_then(|p| p.default_fan_pwm).map(Into::into),
+            ..control("fan_pwm", ControlValueKind::Integer)
+        });
+    }
+    if reports("heaterPWM") {
+        let (min, max) = AutomationCommand::HeaterPwm.range();
+        controls.push(DeviceControl {
+            min: Some(min),
+            max: Some(max),
+            unit: Some("%"),
+            current: field("heaterPWM"),
+            ..control("heater_pwm", ControlValueKind::Integer)
+        });
+    }
+    if reports("controlMode") {
+        let mode = field("controlMode")
+            .and_then(|v| v.as_i64())
+            .map(|m| if m == 0 { "manual" } else { "auto" }.into());
+        controls.push(DeviceControl {
+            options: Some(vec!["auto", "manual"]),
+            current: mode,
+            default: preset
+                .and_then(|p| p.default_control_mode.clone())
+                .map(Into::into),
+            ..control("mode", ControlValueKind::Choice)
+        });
+    }
+    if reports("heaterEnable") {
+        controls.push(DeviceControl {
+            current: field("heaterEnable")
+                .and_then(|v| v.as_i64())
+                .map(|v| (v != 0).into()),
+            blocked_reason: blocked(&ControlOp::HeaterEnable(EnablePayload { enabled: true })),
+            ..control("heater_enable", ControlValueKind::Boolean)
+        });
+    }
+    if reports("Kp") {
+        controls.push(DeviceControl {
+            current: latest.map(
+                |_| serde_json::json!({"kp": field("Kp"), "ki": field("Ki"), "kd": field("Kd")}),
+            ),
+            default: preset.filter(|p| p.default_kp.is_some()).map(
+                |p| serde_json::json!({"kp": p.default_kp, "ki": p.default_ki, "kd": p.default_kd}),
+            ),
+            ..control("pid", ControlValueKind::Gains)
+        });
+    }
+    controls.push(control("emergency_stop", ControlValueKind::Action));
+    if demo {
+        for c in &mut controls {
+            c.blocked_reason = Some("Demo mode: device control is disabled".to_string());
+        }
+    }
+
+    DeviceControlSurface {
+        device_id: device_id.to_string(),
+        online: telemetry
+            .is_some_and(|(_, seen)| now.saturating_sub(*seen) <= STRICT_CONTROL_MAX_AGE_SECS),
+        reported: latest.is_some(),
+        profile_id: preset.map(|p| p.id.clone()),
+        controls,
+    }
+}
+
+/// Control surface of a device for generic UIs, see [`control_surface`].
+async fn api_get_controls(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    let telemetry = state.telemetry_cache.read().await.get(&device_id).cloned();
+    let status = state
+        .device_registry
+        .read()
+        .await
+        .get(&device_id)
+        .and_then(|d| Some((d.status_raw.clone()?, d.last_seen)));
+    let device = match state
+        .device_service
+        .get_device_by_device_id(&device_id)
+        .await
+    {
+        Ok(device) => device,
+        Err(e) => {
+            tracing::error!(?e, "Failed to load device");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load device").into_response();
+        }
+    };
+    if device.is_none() && telemetry.is_none() {
+        return (StatusCode::NOT_FOUND, "Unknown device").into_response();
+    }
+    let preset = match device.and_then(|d| d.device.profile_id) {
+        Some(profile_id) => match state.device_service.get_profile(&profile_id).await {
+            Ok(profile) => profile,
+            Err(e) => {
+                tracing::error!(?e, "Failed to load device profile");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to load device profile",
+                )
+                    .into_response();
+            }
+        },
+        None => None,
+    };
+    Json(control_surface(
+        &device_id,
+        telemetry.as_ref(),
+        status.as_ref(),
+        preset.as_ref(),
+        epoch_secs(),
+        state.demo,
+    ))
+    .into_response()
+}
+
+/// Best-effort control audit write (and manual-session event); control
+/// commands are not failed on DB errors.
+async fn record_control(state: &AppState, device_id: &str, op: &str, value: &str) {
+    let bean_temp = state
+        .telemetry_cache
+        .read()
+        .await
+        .get(device_id)
+        .and_then(|(t, _)| t.get("beanTemp").and_then(|v| v.as_f64()))
+        .map(|v| v as f32);
+    if let Err(e) = state
+        .session_service
+        .record_control(device_id, op, value, bean_temp)
+        .await
+    {
+        tracing::warn!(?e, device_id, op, "Failed to record control audit");
+    }
+}
+
+async fn api_control_audit(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(q): Query<ControlAuditQuery>,
+) -> Response {
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+    match state
+        .session_service
+        .list_control_audit(&device_id, limit)
+        .await
+    {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to list control audit");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list control audit",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Run control operations in order, e.g. mode, heater enable, fan and setpoint
+/// when preparing to roast. Always 200 with per-item results; with
+/// `abort_on_failure` the operations after the first failure are skipped.
+async fn api_control_batch(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(req): Json<ControlBatchRequest>,
+) -> Response {
+    if req.operations.is_empty() {
+        return (StatusCode::BAD_REQUEST, "operations must not be empty").into_response();
+    }
+    if req.operations.len() > MAX_CONTROL_BATCH {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("at most {} operations per batch", MAX_CONTROL_BATCH),
+        )
+            .into_response();
+    }
+
+    let mut results = Vec::with_capacity(req.operations.len());
+    let mut aborted = false;
+    for (index, op) in req.operations.iter().enumerate() {
+        if aborted {
+            results.push(ControlBatchItemResult {
+                index,
+                op: op.name(),
+                status: ControlBatchStatus::Skipped,
+                http_status: None,
+                error: None,
+            });
+            continue;
+        }
+        let resp = publish_control_as(&state, &caller, &device_id, op, &opts).await;
+        let code = resp.status();
+        if code.is_success() {
+            record_manual_pid(&state, &device_id, op).await;
+        }
+        let error = if code.is_success() {
+            None
+        } else {
+            let body = axum::body::to_bytes(resp.into_body(), 1024)
+                .await
+                .unwrap_or_default();
+            aborted = req.abort_on_failure;
+            Some(String::from_utf8_lossy(&body).into_owned())
+        };
+        results.push(ControlBatchItemResult {
+            index,
+            op: op.name(),
+            status: if error.is_none() {
+                ControlBatchStatus::Ok
+            } else {
+                ControlBatchStatus::Failed
+            },
+            http_status: Some(code.as_u16()),
+            error,
+        });
+    }
+
+    Json(ControlBatchResponse { results, aborted }).into_response()
+}
+
+async fn api_emergency_stop(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+) -> Response {
+    if let Err(resp) =
+        check_control_policy(&state, &caller, &device_id, "emergency_stop", None, None).await
+    {
+        return resp;
+    }
+    let command = Command::EmergencyStop;
+    let resp = publish_qos1_and_maybe_wait_ack(
+        &state,
+        &command.topic(&device_id),
+        command.payload(),
+        opts.wait_ack.unwrap_or(false),
+        opts.timeout_ms.unwrap_or(1000),
+    )
+    .await;
+    if resp.status().is_success() {
+        record_control(&state, &device_id, "emergency_stop", &command.payload()).await;
+    }
+    resp
+}
+
+async fn api_mqtt_reset(State(state): State<AppState>) -> impl IntoResponse {
+    tracing::info!("MQTT reset requested via API");
+
+    // Instead of forcing a disconnect which can fail, try to reestablish subscriptions
+    // This is more robust and addresses the actual issue users experience
+    match state.mqtt.resubscribe_tracked().await {
+        Ok(_) => {
+            tracing::info!("MQTT subscriptions reestablished successfully");
+            (
+                StatusCode::OK,
+                "MQTT reset completed - subscriptions restored",
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::warn!(
+                ?e,
+                "Failed to reestablish MQTT subscriptions, attempting disconnect/reconnect"
+            );
+
+            // If resubscribe fails, then try the disconnect approach as fallback
+            match state.mqtt.disconnect().await {
+                Ok(_) => {
+                    tracing::info!(
+                        "MQTT disconnect successful - automatic reconnection will begin"
+                    );
+                    // Give the MQTT client a moment to reconnect, then restore subscriptions
+                    let state_clone = state.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+                        if let Err(e) = state_clone.mqtt.resubscribe_tracked().await {
+                            tracing::warn!(?e, "Failed to resubscribe after MQTT reset");
+                        } else {
+                            tracing::info!("MQTT subscriptions restored after reconnection");
+                        }
+                    });
+                    (
+                        StatusCode::OK,
+                        "MQTT reset initiated - reconnection will happen automatically",
+                    )
+                        .into_response()
+                }
+                Err(disconnect_err) => {
+                    tracing::error!(
+                        ?e,
+                        ?disconnect_err,
+                        "Both resubscribe and disconnect failed"
+                    );
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to reset MQTT connection",
+                    )
+                        .into_response()
+                }
+            }
+        }
+    }
+}
+
+async fn publish_qos1_and_maybe_wait_ack(
+    state: &AppState,
+    topic: &str,
+    payload: impl Into<Vec<u8>>,
+    wait_ack: bool,
+    timeout_ms: u64,
+) -> Response {
+    if state.demo {
+        return (
+            StatusCode::FORBIDDEN,
+            "Demo mode: device control is disabled",
+        )
+            .into_response();
+    }
+    let payload_bytes: Vec<u8> = payload.into();
+
+    // Check if this is a control command for a WebSocket-connected device (DEV-017)
+    if let Ok(parsed) = ParsedTopic::parse(topic) {
+        let senders = state.device_ws_senders.read().await;
+        if let Some(tx) = senders.get(parsed.device_id()) {
+            let payload_str = String::from_utf8_lossy(&payload_bytes).to_string();
+            let ws_msg = serde_json::json!({
+                "type": "control",
+                "topic": topic,
+                "payload": payload_str,
+            })
+            .to_string();
+
+            if tx.send(ws_msg).is_ok() {
+                return StatusCode::NO_CONTENT.into_response();
+            }
+            // Fall through to MQTT if WS send fails (channel closed)
+        }
+    }
+
+    // Into the device's negotiated topic layout
+    let routed = state.topic_layouts.route(topic);
+    let topic = routed.as_str();
+
+    // Tagged so broker logs can be matched to this request (MQTT 5 only)
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let user_properties = vec![("request-id".to_string(), request_id.clone())];
+    let mut response = if wait_ack {
+        // Only the ack for this publish's packet id ends the wait
+        match state
+            .mqtt
+            .publish_and_wait(
+                topic,
+                QoS::AtLeastOnce,
+                false,
+                payload_bytes,
+                user_properties,
+                Duration::from_millis(timeout_ms),
+            )
+            .await
+        {
+            Ok(_) => StatusCode::NO_CONTENT.into_response(),
+            Err(PublishAckError::Timeout { packet_id }) => {
+                tracing::warn!(?packet_id, topic, request_id, "MQTT ack timeout");
+                (StatusCode::GATEWAY_TIMEOUT, "MQTT ack timeout").into_response()
+            }
+            Err(PublishAckError::Rejected {
+                packet_id,
+                rejection,
+            }) => {
+                tracing::warn!(packet_id, %rejection, topic, request_id, "MQTT publish rejected");
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("MQTT publish rejected: {rejection}"),
+                )
+                    .into_response()
+            }
+            Err(e) => {
+                tracing::warn!(?e, topic, request_id, "MQTT publish failed");
+                (StatusCode::BAD_GATEWAY, "MQTT publish failed").into_response()
+            }
+        }
+    } else {
+        match state
+            .mqtt
+            .publish_with_properties(
+                topic,
+                QoS::AtLeastOnce,
+                false,
+                payload_bytes,
+                user_properties,
+            )
+            .await
+        {
+            Ok(_) => StatusCode::NO_CONTENT.into_response(),
+            Err(e) => {
+                tracing::warn!(?e, topic, request_id, "MQTT publish failed");
+                (StatusCode::BAD_GATEWAY, "MQTT publish failed").into_response()
+            }
+        }
+    };
+    if let Ok(value) = axum::http::HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert("x-request-id", value);
+    }
+    response
+}
+
+// ----- WebSocket telemetry -----
+
+impl AppState {
+    /// Override the `/ws/telemetry` keepalive read from the environment,
+    /// e.g. to shorten it in tests.
+    pub fn set_ws_keepalive(&mut self, ping_interval: Duration, idle_timeout: Duration) {
+        self.ws_keepalive = WsKeepalive {
+            ping_interval,
+            idle_timeout,
+        };
+    }
+}
+
+/// Keepalive for `/ws/telemetry`: the server pings every `ping_interval`
+/// and closes sockets that sent nothing (not even a pong) for `idle_timeout`.
+#[derive(Debug, Clone, Copy)]
+struct WsKeepalive {
+    ping_interval: Duration,
+    idle_timeout: Duration,
+}
+
+impl WsKeepalive {
+    /// From `RUSTROAST_WS_PING_INTERVAL_SECS` (default 20) and
+    /// `RUSTROAST_WS_IDLE_TIMEOUT_SECS` (default 60).
+    fn from_env() -> Self {
+        let secs = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .map_or(Duration::from_secs(default), Duration::from_secs)
+        };
+        Self {
+            ping_interval: secs("RUSTROAST_WS_PING_INTERVAL_SECS", 20),
+            idle_timeout: secs("RUSTROAST_WS_IDLE_TIMEOUT_SECS", 60),
+        }
+    }
+}
+
+async fn ws_telemetry(
+    State(state): State<AppState>,
+    caller: Caller,
+    DashboardToken(pinned): DashboardToken,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    // Signed-in users start with their saved device subscriptions
+    let mut subscriptions = HashSet::new();
+    if let Some(subject) = caller.subject.as_deref() {
+        match state.user_service.get_preferences(subject).await {
+            Ok(Some(prefs)) => subscriptions.extend(prefs.ws_subscriptions.0),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(?e, "Failed to load WS subscription preferences"),
+        }
+    }
+    let smoothing = state
+        .user_service
+        .resolve_smoothing(caller.subject.as_deref(), DEFAULT_SMOOTHING_VIEW)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!(?e, "Failed to resolve curve smoothing");
+            SmoothingConfig::default()
+        });
+    ws.on_upgrade(move |socket| telemetry_ws_loop(state, socket, subscriptions, smoothing, pinned))
+}
+
+async fn ws_debug(
+    State(state): State<AppState>,
+    caller: Caller,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| debug_ws_loop(state, caller, socket))
+}
+
+async fn debug_ws_publish(
+    state: &AppState,
+    caller: &Caller,
+    command: serde_json::Value,
+) -> serde_json::Value {
+    let result = match serde_json::from_value::<MqttPublishRequest>(command) {
+        Ok(req) => routes::admin::publish_raw(state, caller, req, "websocket")
+            .await
+            .map_err(|e| e.message().to_string()),
+        Err(e) => Err(format!("Invalid publish command: {}", e)),
+    };
+    match result {
+        Ok(audit) => serde_json::json!({"type": "publish_result", "ok": true, "audit": audit}),
+        Err(error) => serde_json::json!({"type": "publish_result", "ok": false, "error": error}),
+    }
+}
+
+async fn ws_device_logs(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    DashboardToken(pinned): DashboardToken,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if pinned.is_some_and(|pinned| pinned != device_id) {
+        return (StatusCode::FORBIDDEN, "Token is for another device").into_response();
+    }
+    ws.on_upgrade(move |socket| device_logs_ws_loop(state, device_id, socket))
+        .into_response()
+}
+
+/// Streams telemetry, roast cue, device conflict and autotune events, after
+/// a first `{"smoothing": {...}}` message with the caller's live-view curve
+/// smoothing. `subscriptions` limits which devices are forwarded
+/// (empty = all); clients can change it by sending
+/// `{"type": "subscribe", "device_ids": [...]}`. Device status updates and
+/// device registry changes are opt-in: the subscribe command's `events`
+/// (`"status"`, `"devices"`) turns them on, omitting it keeps the current
+/// choice. Its `derived` ([`DerivedSpec`]) adds a `derived` object to each
+/// telemetry message, computed the client's way; an invalid one is
+/// answered with `{"subscribe_error": ...}`. A socket opened with a
+/// dashboard token is `pinned` to its device whatever it subscribes to.
+/// Idle sockets are closed per [`WsKeepalive`].
+async fn telemetry_ws_loop(
+    state: AppState,
+    mut socket: WebSocket,
+    mut subscriptions: HashSet<String>,
+    smoothing: SmoothingConfig,
+    pinned: Option<String>,
+) {
+    if let Some(device_id) = &pinned {
+        subscriptions = HashSet::from([device_id.clone()]);
+    }
+    // Count WS client
+    state.metrics.ws_clients.inc();
+    tracing::info!(
+        "WebSocket telemetry client connected, total: {}",
+        state.metrics.ws_clients.get()
+    );
+
+    // Subscribe to unified telemetry broadcast (covers MQTT, device WS, Modbus)
+    let mut telemetry_rx = state.telemetry_service.subscribe();
+    // Status and autotune messages come straight from MQTT, in either
+    // topic layout
+    let mut roaster_rx = state.mqtt.topic_messages(roaster_wildcard_all());
+    let mut cue_rx = state.cue_engine.subscribe();
+    let mut conflict_rx = state.conflicts.subscribe();
+    let mut alert_rx = state.alerts.subscribe();
+    let mut preheat_rx = state.preheat.subscribe();
+    let mut queue_rx = state.roast_queue.subscribe();
+    let mut device_rx = state.device_service.subscribe();
+    let mut opt_ins: HashSet<WsOptIn> = HashSet::new();
+    let mut derived: Option<DerivedSpec> = None;
+
+    let hint = serde_json::json!({ "smoothing": smoothing });
+    let _ = socket.send(Message::Text(hint.to_string())).await;
+
+    let keepalive = state.ws_keepalive;
+    let mut ping = tokio::time::interval_at(
+        tokio::time::Instant::now() + keepalive.ping_interval,
+        keepalive.ping_interval,
+    );
+    ping.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last_heard = tokio::time::Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = ping.tick() => {
+                if last_heard.elapsed() >= keepalive.idle_timeout {
+                    tracing::info!("Closing idle WebSocket telemetry client");
+                    let _ = socket
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::AWAY,
+                            reason: "idle timeout".into(),
+                        })))
+                        .await;
+                    break;
+                }
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            ws_msg = socket.recv() => {
+                if matches!(ws_msg, Some(Ok(_))) {
+                    last_heard = tokio::time::Instant::now();
+                }
+                match ws_msg {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Ok(cmd) = serde_json::from_str::<WsSubscribeCommand>(&text) {
+                            if cmd.kind == "subscribe" {
+                                subscriptions = match &pinned {
+                                    Some(device_id) => HashSet::from([device_id.clone()]),
+                                    None => cmd.device_ids.into_iter().collect(),
+                                };
+                                if let Some(events) = cmd.events {
+                                    opt_ins = events.into_iter().collect();
+                                }
+                                if let Some(spec) = cmd.derived {
+                                    if let Err(error) = spec.validate() {
+                                        let msg_text = serde_json::json!({
+                                            "subscribe_error": error,
+                                        }).to_string();
+                                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                                            break;
+                                        }
+                                    } else {
+                                        derived = Some(spec);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    _ => {}
+                }
+            }
+            evt = telemetry_rx.recv() => {
+                match evt {
+                    Ok(te) if !subscriptions.is_empty() && !subscriptions.contains(&te.device_id) => {}
+                    Ok(te) => {
+                        let mut msg = serde_json::json!({
+                            "device_id": te.device_id,
+                            "telemetry": te.payload,
+                        });
+                        if let Some(spec) = &derived {
+                            let values = state.telemetry_service.derived().get(&te.device_id, spec);
+                            msg["derived"] = serde_json::json!(values);
+                        }
+                        let msg_text = msg.to_string();
+                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "Dashboard WS client lagged, dropped messages");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            cue = cue_rx.recv() => {
+                match cue {
+                    Ok(cue) if !subscriptions.is_empty() && !subscriptions.contains(&cue.device_id) => {}
+                    Ok(cue) => {
+                        let msg_text = serde_json::json!({
+                            "device_id": cue.device_id,
+                            "cue": cue,
+                        }).to_string();
+                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            alert = alert_rx.recv() => {
+                match alert {
+                    Ok(alert) if alert.device_id.as_ref().is_some_and(|d| !subscriptions.is_empty() && !subscriptions.contains(d)) => {}
+                    Ok(alert) => {
+                        let msg_text = serde_json::json!({
+                            "device_id": alert.device_id,
+                            "alert": alert,
+                        }).to_string();
+                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            preheat = preheat_rx.recv() => {
+                match preheat {
+                    Ok(run) if !subscriptions.is_empty() && !subscriptions.contains(&run.device_id) => {}
+                    Ok(run) => {
+                        let msg_text = serde_json::json!({
+                            "device_id": run.device_id,
+                            "preheat": run,
+                        }).to_string();
+                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            entry = queue_rx.recv() => {
+                match entry {
+                    Ok(entry) if !subscriptions.is_empty() && !subscriptions.contains(&entry.device_id) => {}
+                    Ok(entry) => {
+                        let msg_text = serde_json::json!({
+                            "device_id": entry.device_id,
+                            "queue": entry,
+                        }).to_string();
+                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            change = device_rx.recv() => {
+                match change {
+                    Ok(_) if !opt_ins.contains(&WsOptIn::Devices) => {}
+                    Ok(change) if !subscriptions.is_empty() && !subscriptions.contains(&change.device.device_id) => {}
+                    Ok(change) => {
+                        let msg_text = serde_json::json!({
+                            "device_id": change.device.device_id,
+                            "device": change,
+                        }).to_string();
+                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            conflict = conflict_rx.recv() => {
+                match conflict {
+                    Ok(conflict) if !subscriptions.is_empty() && !subscriptions.contains(&conflict.device_id) => {}
+                    Ok(conflict) => {
+                        let msg_text = serde_json::json!({
+                            "device_id": conflict.device_id,
+                            "conflict": conflict,
+                        }).to_string();
+                        if socket.send(Message::Text(msg_text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            // Telemetry is handled via the unified broadcast above
+            message = roaster_rx.recv() => {
+                let Some(message) = message else { break };
+                let Ok(parsed) = ParsedTopic::parse(&message.topic) else { continue };
+                let device_id = parsed.device_id();
+                if !subscriptions.is_empty() && !subscriptions.contains(device_id) {
+                    continue;
+                }
+                let msg_text = match &parsed {
+                    ParsedTopic::Status { .. } if opt_ins.contains(&WsOptIn::Status) => {
+                        let value = serde_json::from_slice::<serde_json::Value>(&message.payload)
+                            .unwrap_or_else(|_| String::from_utf8_lossy(&message.payload).into());
+                        serde_json::json!({
+                            "device_id": device_id,
+                            "status": value,
+                        }).to_string()
+                    }
+                    ParsedTopic::Autotune { topic, .. } => {
+                        let sub = topic.as_str();
+                        match serde_json::from_slice::<serde_json::Value>(&message.payload) {
+                            Ok(val) => serde_json::json!({
+                                "device_id": device_id,
+                                "autotune": {"type": sub, "data": val}
+                            }).to_string(),
+                            Err(_) => serde_json::json!({
+                                "device_id": device_id,
+                                "autotune_raw": {"type": sub, "data": String::from_utf8_lossy(&message.payload)}
+                            }).to_string(),
+                        }
+                    }
+                    _ => continue,
+                };
+                if socket.send(Message::Text(msg_text)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    let _ = socket.close().await;
+    state.metrics.ws_clients.dec();
+}
+
+#[derive(Deserialize)]
+struct WsSubscribeCommand {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    device_ids: Vec<String>,
+    #[serde(default)]
+    events: Option<Vec<WsOptIn>>,
+    #[serde(default)]
+    derived: Option<DerivedSpec>,
+}
+
+/// Opt-in `/ws/telemetry` message types; unknown ones are ignored.
+#[derive(Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+enum WsOptIn {
+    Status,
+    Devices,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Mirrors all MQTT traffic. Clients can send `{"type": "ping"}`, and admins
+/// `{"type": "publish", "topic", "payload", "qos", "retain"}`, answered with
+/// `{"type": "publish_result", "ok", "audit" | "error"}`.
+async fn debug_ws_loop(state: AppState, caller: Caller, mut socket: WebSocket) {
+    use axum::extract::ws::Message;
+    use tokio::select;
+
+    // Count WS client
+    state.metrics.ws_clients.inc();
+    tracing::info!("Debug WebSocket client connected");
+
+    let mut rx = state.mqtt.events();
+
+    loop {
+        select! {
+            // Handle incoming WebSocket messages from client
+            ws_msg = socket.recv() => {
+                match ws_msg {
+                    Some(Ok(Message::Text(text))) => {
+                        // Handle ping and publish messages from client
+                        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&text) {
+                            let reply = match parsed.get("type").and_then(|v| v.as_str()) {
+                                Some("ping") => Some(serde_json::json!({"type": "pong"})),
+                                Some("publish") => Some(debug_ws_publish(&state, &caller, parsed).await),
+                                _ => None,
+                            };
+                            if let Some(reply) = reply {
+                                if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        tracing::info!("Debug WebSocket client disconnected gracefully");
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        tracing::warn!(?e, "Debug WebSocket error reading message");
+                        break;
+                    }
+                    _ => {
+                        // Ignore other message types like binary, ping, pong
+                    }
+                }
+            }
+            // Handle MQTT events and forward to client
+            mqtt_evt = rx.recv() => {
+                let evt = match mqtt_evt {
+                    Ok(evt) => evt,
+                    Err(EventRecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "Debug WebSocket lagged behind MQTT traffic");
+                        continue;
+                    }
+                    Err(EventRecvError::Closed) => {
+                        tracing::warn!("Debug WebSocket: MQTT event channel closed");
+                        break;
+                    }
+                };
+
+                let msg = match evt {
+                    rustroast_mqtt::MqttEvent::Publish { topic, payload, .. } => {
+                        // Parse device ID from topic if it's a roaster topic
+                        let device_id = ParsedTopic::parse(&topic)
+                            .ok()
+                            .map(|parsed| parsed.device_id().to_string());
+
+                        // Try to parse payload as JSON, otherwise use raw string
+                        let payload_value = match serde_json::from_slice::<serde_json::Value>(&payload) {
+                            Ok(json) => json,
+                            Err(_) => serde_json::Value::String(String::from_utf8_lossy(&payload).to_string())
+                        };
+
+                        serde_json::json!({
+                            "mqtt": {
+                                "topic": topic,
+                                "payload": payload_value,
+                                "direction": "incoming",
+                                "device_id": device_id
+                            }
+                        })
+                    }
+                    rustroast_mqtt::MqttEvent::Connected => {
+                        serde_json::json!({
+                            "mqtt": {
+                                "topic": "system/connected",
+                                "payload": "MQTT connected",
+                                "direction": "incoming"
+                            }
+                        })
+                    }
+                    rustroast_mqtt::MqttEvent::Disconnected => {
+                        serde_json::json!({
+                            "mqtt": {
+                                "topic": "system/disconnected",
+                                "payload": "MQTT disconnected",
+                                "direction": "incoming"
+                            }
+                        })
+                    }
+                    rustroast_mqtt::MqttEvent::PubAck(packet_id) => {
+                        serde_json::json!({
+                            "mqtt": {
+                                "topic": "system/puback",
+                                "payload": format!("PubAck received for packet {}", packet_id),
+                                "direction": "incoming"
+                            }
+                        })
+                    }
+                    rustroast_mqtt::MqttEvent::Oversized { topic, size } => {
+                        serde_json::json!({
+                            "mqtt": {
+                                "topic": topic,
+                                "payload": format!("Dropped oversized publish of {} bytes", size),
+                                "direction": "incoming"
+                            }
+                        })
+                    }
+                    rustroast_mqtt::MqttEvent::Stale { silent_for } => {
+                        serde_json::json!({
+                            "mqtt": {
+                                "topic": "system/stale",
+                                "payload": format!("Nothing heard from the broker for {:.1}s", silent_for.as_secs_f64()),
+                                "direction": "incoming"
+                            }
+                        })
+                    }
+                };
+
+                if socket.send(Message::Text(msg.to_string())).await.is_err() {
+                    tracing::info!("Debug WebSocket client disconnected during send");
+                    break;
+                }
+            }
+        }
+    }
+
+    let _ = socket.close().await;
+    state.metrics.ws_clients.dec();
+    tracing::info!("Debug WebSocket connection closed");
+}
+
+/// Streams one device's firmware log lines: the buffered ones first, then
+/// each line as it arrives.
+async fn device_logs_ws_loop(state: AppState, device_id: String, mut socket: WebSocket) {
+    state.metrics.ws_clients.inc();
+    tracing::info!(%device_id, "Device log WebSocket client connected");
+
+    let (backlog, mut lines_rx) = state.device_logs.follow(&device_id);
+    'stream: {
+        for line in &backlog {
+            let text = serde_json::to_string(line).unwrap_or_default();
+            if socket.send(Message::Text(text)).await.is_err() {
+                break 'stream;
+            }
+        }
+        loop {
+            tokio::select! {
+                ws_msg = socket.recv() => {
+                    match ws_msg {
+                        Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                        _ => {}
+                    }
+                }
+                line = lines_rx.recv() => {
+                    match line {
+                        Ok(line) if line.device_id != device_id => {}
+                        Ok(line) => {
+                            let text = serde_json::to_string(&line).unwrap_or_default();
+                            if socket.send(Message::Text(text)).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            tracing::warn!(%device_id, skipped = n, "Device log WebSocket lagged behind");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            }
+        }
+    }
+
+    let _ = socket.close().await;
+    state.metrics.ws_clients.dec();
+    tracing::info!(%device_id, "Device log WebSocket connection closed");
+}
+
+// OpenAPI generation deferred
+
+// OpenAPI generator removed for now to keep build stable; can be re-added
+
+// ----- Background MQTT consumer to fill caches + metrics -----
+/// Reads inbound MQTT events, applies ingest limits and hands each roaster
+/// publish to the ingest worker owning its device.
+async fn mqtt_consumer_loop(
+    state: AppState,
+    mut rx: EventReceiver,
+    limits: IngestLimits,
+    workers: usize,
+) {
+    let mut drop_log = DropLog::default();
+    let metrics = state.metrics.clone();
+    // Journaled telemetry survives a stalled worker, so it is never shed
+    let shed_at =
+        (!state.telemetry_service.is_journaled()).then_some(ingest::WORKER_QUEUE_CAPACITY);
+    let queues: Vec<WorkerQueue> = (0..workers)
+        .map(|index| {
+            let (queue, jobs) = WorkerQueue::channel(shed_at);
+            tokio::spawn(ingest_worker(state.clone(), index, jobs));
+            queue
+        })
+        .collect();
+
+    loop {
+        match rx.recv().await {
+            Ok(rustroast_mqtt::MqttEvent::Publish {
+                topic,
+                payload,
+                retain,
+                received_at,
+                ..
+            }) => {
+                if let Err(reason) = limits.check(&topic, &payload) {
+                    metrics
+                        .mqtt_dropped_total
+                        .with_label_values(&[reason.as_str()])
+                        .inc();
+                    drop_log.record(reason, &topic, payload.len());
+                    continue;
+                }
+                if topic == cluster_heartbeat_topic() {
+                    state.cluster.observe(&payload, Instant::now());
+                    continue;
+                }
+                let (layout, parsed) = match ParsedTopic::parse_layout(&topic) {
+                    Ok(parsed) => parsed,
+                    Err(TopicError::InvalidDeviceId) => {
+                        let reason = DropReason::InvalidDeviceId;
+                        metrics
+                            .mqtt_dropped_total
+                            .with_label_values(&[reason.as_str()])
+                            .inc();
+                        drop_log.record(reason, &topic, payload.len());
+                        continue;
+                    }
+                    Err(TopicError::NotRoaster) => continue,
+                };
+                let worker = ingest::shard(parsed.device_id(), queues.len());
+                let job = IngestJob {
+                    parsed,
+                    layout,
+                    topic,
+                    payload,
+                    retained: retain,
+                    received_at: received_at
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    queued_at: Instant::now(),
+                };
+                match queues[worker].push(job) {
+                    Enqueued::Queued => metrics
+                        .ingest_queue_depth
+                        .with_label_values(&[&worker.to_string()])
+                        .inc(),
+                    Enqueued::Shed(job) => {
+                        let reason = DropReason::WorkerQueueFull;
+                        metrics
+                            .mqtt_dropped_total
+                            .with_label_values(&[reason.as_str()])
+                            .inc();
+                        drop_log.record(reason, &job.topic, job.payload.len());
+                    }
+                    Enqueued::Closed => {
+                        tracing::error!(worker, "Ingest worker stopped, dropping message")
+                    }
+                }
+            }
+            Ok(rustroast_mqtt::MqttEvent::Oversized { topic, size }) => {
+                let reason = DropReason::Oversized;
+                metrics
+                    .mqtt_dropped_total
+                    .with_label_values(&[reason.as_str()])
+                    .inc();
+                drop_log.record(reason, &topic, size);
+            }
+            // Connection state and traffic are counted by the client itself
+            Ok(_) => {}
+            Err(EventRecvError::Lagged(n)) => {
+                metrics
+                    .mqtt_dropped_total
+                    .with_label_values(&[DropReason::SubscriberOverflow.as_str()])
+                    .inc_by(n);
+                tracing::warn!(skipped = n, "MQTT consumer lagged behind, events dropped");
+            }
+            Err(EventRecvError::Closed) => break,
+        }
+    }
+}
+
+/// Processes one shard of devices in arrival order.
+async fn ingest_worker(state: AppState, index: usize, mut jobs: WorkerJobs) {
+    let label = index.to_string();
+    let queue_depth = state
+        .metrics
+        .ingest_queue_depth
+        .with_label_values(&[&label]);
+    let lag = state.metrics.ingest_lag.with_label_values(&[&label]);
+    while let Some(job) = jobs.recv().await {
+        queue_depth.dec();
+        lag.observe(job.queued_at.elapsed().as_secs_f64());
+        process_roaster_message(&state, job).await;
+    }
+}
+
+/// Status of a device in the devices table, auto-creating unknown ones as
+/// `pending` with a default MQTT connection.
+async fn discover_device(state: &AppState, device_id: &str) -> Option<DeviceStatus> {
+    match state
+        .device_service
+        .get_device_by_device_id(device_id)
+        .await
+    {
+        Ok(Some(dev)) => Some(dev.device.status),
+        Ok(None) => {
+            // Auto-create the device
+            let req = CreateDeviceRequest {
+                name: device_id.to_string(),
+                device_id: device_id.to_string(),
+                profile_id: None,
+                description: Some("Auto-discovered via MQTT".to_string()),
+                location: None,
+            };
+            match state.device_service.create_device(req).await {
+                Ok(dev) => {
+                    // Add a default MQTT connection config derived from the topic
+                    let mqtt_config = serde_json::json!({
+                        "topic_prefix": format!("roaster/{}", device_id),
+                        "qos": 0
+                    });
+                    let conn_req = CreateConnectionRequest {
+                        protocol: Protocol::Mqtt,
+                        enabled: Some(true),
+                        priority: Some(0),
+                        config: mqtt_config,
+                    };
+                    if let Err(e) = state.device_service.add_connection(&dev.id, conn_req).await {
+                        tracing::warn!(%device_id, error = %e, "Failed to add default MQTT connection for auto-discovered device");
+                    }
+                    tracing::info!(%device_id, "Auto-discovered new device via MQTT");
+                    Some(DeviceStatus::Pending)
+                }
+                Err(e) => {
+                    tracing::warn!(%device_id, error = %e, "Failed to auto-create device");
+                    None
+                }
+            }
+        }
+        Err(e) => {
+            tracing::warn!(%device_id, error = %e, "Failed to look up device");
+            None
+        }
+    }
+}
+
+/// Caches, metrics and persistence for one `roaster/{device_id}/...` publish.
+async fn process_roaster_message(state: &AppState, job: IngestJob) {
+    let IngestJob {
+        parsed,
+        layout,
+        payload,
+        retained,
+        received_at,
+        ..
+    } = job;
+    let device_id = parsed.device_id().to_string();
+
+    // Controls and autotune commands are the server's own publishes
+    let from_device = !matches!(
+        parsed,
+        ParsedTopic::Control { .. }
+            | ParsedTopic::Autotune {
+                topic: AutotuneTopic::Start | AutotuneTopic::Stop | AutotuneTopic::Apply,
+                ..
+            }
+    );
+    if from_device && !retained {
+        state.topic_layouts.observe(&device_id, layout);
+    }
+
+    // Stamp with arrival rather than processing time, which lags behind
+    // under load
+    let now = received_at;
+    if let ParsedTopic::Telemetry { .. } = parsed {
+        // A retained reading is an old one, replayed on every subscribe
+        // (including retained scans). Recording it would store it again
+        // and pass it off as a sign of life.
+        if retained {
+            return;
+        }
+        if let Ok(val) = serde_json::from_slice::<serde_json::Value>(&payload) {
+            let device_status = discover_device(state, &device_id).await;
+
+            // Shared telemetry processing (cache, persist, session recording, last-seen)
+            state
+                .telemetry_service
+                .process_telemetry_at(&device_id, &val, device_status.as_ref(), now)
+                .await;
+        }
+    } else if let ParsedTopic::Status { .. } = parsed {
+        if let Ok(val) = serde_json::from_slice::<serde_json::Value>(&payload) {
+            // Retained statuses replayed on subscribing rebuild the registry
+            // right after a restart, without counting as a sign of life
+            let last_seen = if retained {
+                match state.status_history.last_ts(&device_id).await {
+                    Ok(ts) => ts.map_or(now, |ts| ts as u64),
+                    Err(e) => {
+                        tracing::warn!(%device_id, error = %e, "Failed to look up last status");
+                        now
+                    }
+                }
+            } else {
+                state
+                    .metrics
+                    .status_last_seen
+                    .with_label_values(&[&device_id])
+                    .set(now as i64);
+                now
+            };
+            discover_device(state, &device_id).await;
+            let mut reg = state.device_registry.write().await;
+            if retained && reg.get(&device_id).is_some_and(|d| !d.retained) {
+                // Already heard live; the replay is older
+                return;
+            }
+            let entry = reg.entry(device_id.clone()).or_insert(DeviceInfo {
+                device_id: device_id.clone(),
+                last_seen,
+                retained,
+                id: None,
+                ip: None,
+                version: None,
+                rssi: None,
+                status_raw: None,
+                conflict: None,
+                system_error: None,
+            });
+            entry.last_seen = last_seen;
+            entry.retained = retained;
+            entry.status_raw = Some(val.clone());
+            let status = rustroast_core::DeviceStatus::from_payload(&val);
+            if !status.invalid.is_empty() {
+                tracing::debug!(%device_id, fields = ?status.invalid, "Ignoring malformed status fields");
+            }
+            entry.id = status.id;
+            entry.ip = status.ip;
+            entry.version = status.version;
+            entry.rssi = status.rssi;
+            if let Some(schema) = status.topic_schema {
+                if state.topic_layouts.advertise(&device_id, schema).is_none() {
+                    tracing::warn!(%device_id, schema, "Unknown topic schema, keeping the observed layout");
+                }
+            }
+            entry.conflict = state.conflicts.observe(&device_id, &val, now);
+            drop(reg);
+            if let Err(e) = state
+                .status_history
+                .record(&device_id, &val, now as i64, retained)
+                .await
+            {
+                tracing::warn!(%device_id, error = %e, "Failed to store status snapshot");
+            }
+        }
+    } else if let ParsedTopic::Log { .. } = parsed {
+        if let Err(e) = state.device_logs.record(&device_id, &payload, now).await {
+            tracing::warn!(%device_id, error = %e, "Failed to persist device log lines");
+        }
+    } else if let ParsedTopic::Autotune { topic, .. } = parsed {
+        // roaster/{device_id}/autotune/{status|results}
+        if let Ok(val) = serde_json::from_slice::<serde_json::Value>(&payload) {
+            let payload_str = String::from_utf8_lossy(&payload).to_string();
+            // Retained messages are replayed on every (re)subscribe. Only those
+            // published while the server was down are new; the rest just seed
+            // the cache.
+            if retained {
+                let table = match topic {
+                    AutotuneTopic::Status => "autotune_status",
+                    AutotuneTopic::Results => "autotune_results",
+                    _ => return,
+                };
+                if let Some(ts) =
+                    recorded_autotune_ts(&state.db, table, &device_id, &payload_str).await
+                {
+                    let cache = match topic {
+                        AutotuneTopic::Status => &state.autotune_status_cache,
+                        _ => &state.autotune_results_cache,
+                    };
+                    cache
+                        .write()
+                        .await
+                        .entry(device_id.clone())
+                        .or_insert((val, ts as u64));
+                    return;
+                }
+                tracing::info!(%device_id, ?topic, "Adopting retained autotune message");
+            }
+            match topic {
+                AutotuneTopic::Status => {
+                    if let Some(phase) = val
+                        .get("phase")
+                        .or_else(|| val.get("state"))
+                        .and_then(|v| v.as_str())
+                    {
+                        let phase = phase.to_uppercase();
+                        if autotune_phase_is_failure(&phase) {
+                            finish_autotune_run(
+                                &state.device_service,
+                                &device_id,
+                                AutotuneRunState::Failed,
+                                None,
+                            )
+                            .await;
+                        } else if let Err(e) = state
+                            .device_service
+                            .set_autotune_phase(&device_id, &phase)
+                            .await
+                        {
+                            tracing::warn!(%device_id, error = %e, "Failed to update autotune run phase");
+                        }
+                    }
+                    state
+                        .autotune_status_cache
+                        .write()
+                        .await
+                        .insert(device_id.clone(), (val.clone(), now));
+                    let _ = sqlx::query(
+                        "INSERT INTO autotune_status (device_id, ts, payload) VALUES (?, ?, ?)",
+                    )
+                    .bind(&device_id)
+                    .bind(now as i64)
+                    .bind(payload_str)
+                    .execute(&state.db)
+                    .await;
+                }
+                AutotuneTopic::Results => {
+                    finish_autotune_run(
+                        &state.device_service,
+                        &device_id,
+                        AutotuneRunState::Completed,
+                        Some(&val),
+                    )
+                    .await;
+                    state
+                        .autotune_results_cache
+                        .write()
+                        .await
+                        .insert(device_id.clone(), (val.clone(), now));
+                    let _ = sqlx::query(
+                        "INSERT INTO autotune_results (device_id, ts, payload) VALUES (?, ?, ?)",
+                    )
+                    .bind(&device_id)
+                    .bind(now as i64)
+                    .bind(payload_str)
+                    .execute(&state.db)
+                    .await;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Timestamp of the latest row in `table` (`autotune_status` or
+/// `autotune_results`) holding exactly `payload` for the device.
+async fn recorded_autotune_ts(
+    db: &SqlitePool,
+    table: &str,
+    device_id: &str,
+    payload: &str,
+) -> Option<i64> {
+    let query = format!("SELECT MAX(ts) FROM {table} WHERE device_id = ? AND payload = ?");
+    sqlx::query_scalar::<_, Option<i64>>(&query)
+        .bind(device_id)
+        .bind(payload)
+        .fetch_one(db)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!(%device_id, error = %e, "Failed to look up recorded autotune message");
+            None
+        })
+}
+
+fn epoch_secs() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// ----- Device WebSocket endpoint (DEV-017) -----
+
+async fn ws_device_telemetry(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    // Validate device exists and is active
+    match state
+        .device_service
+        .get_device_by_device_id(&device_id)
+        .await
+    {
+        Ok(Some(dev)) => {
+            if dev.device.status != DeviceStatus::Active {
+                return (
+                    StatusCode::FORBIDDEN,
+                    format!(
+                        "Device '{}' is not active (status: {})",
+                        device_id, dev.device.status
+                    ),
+                )
+                    .into_response();
+            }
+            ws.on_upgrade(move |socket| device_ws_loop(state, device_id, socket))
+                .into_response()
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            format!("Device '{}' not found", device_id),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(%device_id, error = %e, "Failed to look up device for WebSocket");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response()
+        }
+    }
+}
+
+async fn device_ws_loop(state: AppState, device_id: String, mut socket: WebSocket) {
+    tracing::info!(%device_id, "Device WebSocket connected");
+
+    // Create control command channel and register sender
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    state
+        .device_ws_senders
+        .write()
+        .await
+        .insert(device_id.clone(), tx);
+
+    loop {
+        tokio::select! {
+            // Incoming telemetry from device
+            ws_msg = socket.recv() => {
+                match ws_msg {
+                    Some(Ok(Message::Text(text))) => {
+                        match serde_json::from_str::<serde_json::Value>(&text) {
+                            Ok(val) => {
+                                // Look up device status
+                                let device_status = state.device_service
+                                    .get_device_by_device_id(&device_id).await
+                                    .ok()
+                                    .flatten()
+                                    .map(|d| d.device.status);
+
+                                state.telemetry_service.process_telemetry(
+                                    &device_id,
+                                    &val,
+                                    device_status.as_ref(),
+                                ).await;
+                            }
+                            Err(_) => {
+                                tracing::warn!(%device_id, "Invalid JSON from device WebSocket");
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        tracing::info!(%device_id, "Device WebSocket disconnected");
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        tracing::warn!(%device_id, error = %e, "Device WebSocket error");
+                        break;
+                    }
+                    _ => {} // Ignore binary, ping, pong
+                }
+            }
+
+            // Outgoing control commands to device
+            Some(cmd) = rx.recv() => {
+                if socket.send(Message::Text(cmd)).await.is_err() {
+                    tracing::warn!(%device_id, "Failed to send control command via device WebSocket");
+                    break;
+                }
+            }
+        }
+    }
+
+    // Cleanup: remove sender and close socket
+    state.device_ws_senders.write().await.remove(&device_id);
+    let _ = socket.close().await;
+    tracing::info!(%device_id, "Device WebSocket cleaned up");
+}
+
+// ----- Read endpoints -----
+//#[utoipa::path(get, path = "/api/roaster/{device_id}/telemetry/latest", params(("device_id" = Path<String>)), responses((status = 200, body = LatestTelemetryResponse), (status = 404)))]
+async fn api_get_latest_telemetry(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Units(units): Units,
+) -> Response {
+    let map = state.telemetry_cache.read().await;
+    if let Some((val, ts)) = map.get(&device_id) {
+        let mut telemetry = val.clone();
+        telemetry.convert_units(units);
+        Json(LatestTelemetryResponse {
+            device_id,
+            timestamp: *ts,
+            telemetry,
+        })
+        .into_response()
+    } else {
+        (StatusCode::NOT_FOUND, "No telemetry").into_response()
+    }
+}
+
+//#[utoipa::path(get, path = "/api/devices", responses((status = 200, body = DevicesResponse)))]
+async fn api_get_devices(State(state): State<AppState>) -> Response {
+    let telemetry = state.telemetry_cache.read().await;
+    let reg = state.device_registry.read().await;
+    let list: Vec<_> = reg
+        .values()
+        .cloned()
+        .map(|mut device| {
+            let latest = telemetry
+                .get(&device.device_id)
+                .map(|(t, _)| t)
+                .or(device.status_raw.as_ref());
+            device.system_error = latest.and_then(DeviceError::from_payload).map(|e| e.info());
+            device
+        })
+        .collect();
+    Json(DevicesResponse { devices: list }).into_response()
+}
+
+/// The `systemStatus` error codes the server knows how to decode.
+async fn api_list_device_error_codes() -> Response {
+    let codes: Vec<DeviceErrorInfo> = DeviceError::ALL.iter().map(|e| e.info()).collect();
+    Json(codes).into_response()
+}
+
+/// Device ids currently flagged as shared by several boards.
+async fn api_list_device_conflicts(State(state): State<AppState>) -> Response {
+    Json(state.conflicts.conflicts()).into_response()
+}
+
+/// Clear a device id conflict by hand, e.g. after reflashing one board.
+async fn api_clear_device_conflict(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    if !state.conflicts.clear(&device_id) {
+        return (StatusCode::NOT_FOUND, "Device is not flagged as conflicted").into_response();
+    }
+    if let Some(entry) = state.device_registry.write().await.get_mut(&device_id) {
+        entry.conflict = None;
+    }
+    if let Err(e) = state
+        .alerts
+        .resolve_cleared(AlertKind::DeviceConflict, &device_id)
+        .await
+    {
+        tracing::warn!(%device_id, error = %e, "Failed to resolve device conflict alert");
+    }
+    tracing::info!(%device_id, "Device id conflict cleared by request");
+    StatusCode::NO_CONTENT.into_response()
+}
+
+// ----- Auto-tune control & read endpoints -----
+
+#[derive(Deserialize, Serialize)]
+struct AutoTuneStartPayload {
+    target_temperature: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tuning_method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bias: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    amplitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hysteresis: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aggressiveness: Option<f64>,
+}
+
+//#[utoipa::path(post, path = "/api/roaster/{device_id}/autotune/start", request_body = AutoTuneStartPayload,
+//    params(("device_id" = Path<String>), ("wait_ack" = Option<bool>, Query), ("timeout_ms" = Option<u64>, Query)),
+//    responses((status = 204), (status = 400), (status = 504))
+//)]
+async fn api_autotune_start(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    caller: Caller,
+    Json(body): Json<AutoTuneStartPayload>,
+) -> Response {
+    // ESP32 expects 150..=250 C according to firmware docs
+    if !(150.0..=250.0).contains(&body.target_temperature) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "target_temperature must be between 150 and 250 C",
+        )
+            .into_response();
+    }
+    if let Some(ref m) = body.mode {
+        if m != "relay" && m != "step_response" {
+            return (
+                StatusCode::BAD_REQUEST,
+                "mode must be 'relay' or 'step_response'",
+            )
+                .into_response();
+        }
+    }
+    if let Some(ref tm) = body.tuning_method {
+        if ![
+            "zn_classic",
+            "tyreus_luyben",
+            "zn_some_overshoot",
+            "zn_no_overshoot",
+        ]
+        .contains(&tm.as_str())
+        {
+            return (
+                StatusCode::BAD_REQUEST,
+                "tuning_method must be one of: zn_classic, tyreus_luyben, zn_some_overshoot, zn_no_overshoot",
+            )
+                .into_response();
+        }
+    }
+    if let Some(b) = body.bias {
+        if !(10.0..=90.0).contains(&b) {
+            return (
+                StatusCode::BAD_REQUEST,
+                "bias must be between 10.0 and 90.0",
+            )
+                .into_response();
+        }
+    }
+    if let Some(a) = body.amplitude {
+        if !(5.0..=45.0).contains(&a) {
+            return (
+                StatusCode::BAD_REQUEST,
+                "amplitude must be between 5.0 and 45.0",
+            )
+                .into_response();
+        }
+    }
+    if let Some(h) = body.hysteresis {
+        if !(0.1..=5.0).contains(&h) {
+            return (
+                StatusCode::BAD_REQUEST,
+                "hysteresis must be between 0.1 and 5.0",
+            )
+                .into_response();
+        }
+    }
+    if let Some(ag) = body.aggressiveness {
+        if !(0.1..=2.0).contains(&ag) {
+            return (
+                StatusCode::BAD_REQUEST,
+                "aggressiveness must be between 0.1 and 2.0",
+            )
+                .into_response();
+        }
+    }
+    // Only one run per device may be in flight, so results can be paired with this request
+    let run = state
+        .device_service
+        .start_autotune_run(
+            &device_id,
+            StartAutotuneRun {
+                target_temperature: body.target_temperature,
+                mode: body.mode.as_deref(),
+                tuning_method: body.tuning_method.as_deref(),
+                requested_by: caller.name.as_deref().or(caller.subject.as_deref()),
+            },
+        )
+        .await;
+    match run {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::CONFLICT,
+                "An autotune run is already in progress for this device",
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to record autotune run");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to record autotune run",
+            )
+                .into_response();
+        }
+    }
+
+    let topic = rustroast_core::autotune_start(&device_id);
+    let payload = serde_json::to_string(&body).unwrap_or_default();
+    let resp = publish_qos1_and_maybe_wait_ack(
+        &state,
+        &topic,
+        payload,
+        opts.wait_ack.unwrap_or(false),
+        opts.timeout_ms.unwrap_or(1000),
+    )
+    .await;
+    if !resp.status().is_success() {
+        finish_autotune_run(
+            &state.device_service,
+            &device_id,
+            AutotuneRunState::Failed,
+            None,
+        )
+        .await;
+    }
+    resp
+}
+
+//#[utoipa::path(post, path = "/api/roaster/{device_id}/autotune/stop",
+//    params(("device_id" = Path<String>), ("wait_ack" = Option<bool>, Query), ("timeout_ms" = Option<u64>, Query)),
+//    responses((status = 204), (status = 504))
+//)]
+async fn api_autotune_stop(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+) -> Response {
+    let topic = rustroast_core::autotune_stop(&device_id);
+    let resp = publish_qos1_and_maybe_wait_ack(
+        &state,
+        &topic,
+        "1",
+        opts.wait_ack.unwrap_or(false),
+        opts.timeout_ms.unwrap_or(1000),
+    )
+    .await;
+    if resp.status().is_success() {
+        finish_autotune_run(
+            &state.device_service,
+            &device_id,
+            AutotuneRunState::Stopped,
+            None,
+        )
+        .await;
+    }
+    resp
+}
+
+/// Close the device's in-flight autotune run, if any. Failures are logged only.
+async fn finish_autotune_run(
+    device_service: &DeviceService,
+    device_id: &str,
+    run_state: AutotuneRunState,
+    results: Option<&serde_json::Value>,
+) {
+    match device_service
+        .finish_autotune_run(device_id, run_state, results)
+        .await
+    {
+        Ok(Some(run)) => {
+            tracing::info!(%device_id, run_id = %run.id, state = %run_state, "Autotune run finished")
+        }
+        Ok(None) if results.is_some() => {
+            tracing::warn!(%device_id, "Autotune results received with no run in progress")
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!(%device_id, error = %e, "Failed to update autotune run"),
+    }
+}
+
+/// Phases reported on the status topic that end a run without results.
+fn autotune_phase_is_failure(phase: &str) -> bool {
+    matches!(phase, "ERROR" | "FAILED")
+}
+
+#[derive(Deserialize)]
+struct AutotuneRunsQuery {
+    limit: Option<i64>,
+}
+
+async fn api_list_autotune_runs(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(q): Query<AutotuneRunsQuery>,
+) -> Response {
+    let limit = q.limit.unwrap_or(50).clamp(1, 500);
+    match state
+        .device_service
+        .list_autotune_runs(&device_id, limit)
+        .await
+    {
+        Ok(runs) => Json(runs).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to list autotune runs");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list autotune runs",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_get_active_autotune_run(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    match state.device_service.active_autotune_run(&device_id).await {
+        Ok(Some(run)) => Json(run).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "No autotune run in progress").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to get active autotune run");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to get active autotune run",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AutotuneApplyOpts {
+    /// Apply results that fail the sanity check (see [`autotune::check`]).
+    force: Option<bool>,
+}
+
+//#[utoipa::path(post, path = "/api/roaster/{device_id}/autotune/apply",
+//    params(("device_id" = Path<String>), ("wait_ack" = Option<bool>, Query), ("timeout_ms" = Option<u64>, Query), ("force" = Option<bool>, Query)),
+//    responses((status = 204), (status = 409), (status = 504))
+//)]
+async fn api_autotune_apply(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(opts): Query<PublishOpts>,
+    Query(apply): Query<AutotuneApplyOpts>,
+) -> Response {
+    let results = state
+        .autotune_results_cache
+        .read()
+        .await
+        .get(&device_id)
+        .map(|(val, _)| val.clone());
+    let checked = match autotune::check(&state.db, &device_id, results.as_ref()).await {
+        Ok(checked) => checked,
+        Err(e) => {
+            tracing::error!(?e, "Failed to check autotune results");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check autotune results",
+            )
+                .into_response();
+        }
+    };
+    if !checked.problems.is_empty() {
+        let problems = checked.problems.join("; ");
+        if !apply.force.unwrap_or(false) {
+            return (
+                StatusCode::CONFLICT,
+                format!(
+                    "Autotune results look wrong: {problems}. Pass force=true to apply them anyway"
+                ),
+            )
+                .into_response();
+        }
+        tracing::warn!(%device_id, %problems, "Applying autotune results despite failed checks");
+    }
+
+    let topic = rustroast_core::autotune_apply(&device_id);
+    let resp = publish_qos1_and_maybe_wait_ack(
+        &state,
+        &topic,
+        "1",
+        opts.wait_ack.unwrap_or(false),
+        opts.timeout_ms.unwrap_or(1000),
+    )
+    .await;
+    if resp.status().is_success() {
+        if let Some(results) = checked.results {
+            let PidGains { kp, ki, kd } = results.recommended;
+            record_pid_gains(
+                &state,
+                &device_id,
+                RecordPidGains {
+                    kp,
+                    ki,
+                    kd,
+                    source: "autotune",
+                    source_device_id: None,
+                    scale: None,
+                },
+            )
+            .await;
+        }
+    }
+    resp
+}
+
+//#[utoipa::path(get, path = "/api/roaster/{device_id}/autotune/status/latest", params(("device_id" = Path<String>)), responses((status = 200), (status = 404)))]
+async fn api_get_autotune_status_latest(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    let map = state.autotune_status_cache.read().await;
+    match map.get(&device_id) {
+        Some((val, ts)) => {
+            Json(serde_json::json!({"device_id": device_id, "timestamp": ts, "status": val}))
+                .into_response()
+        }
+        None => Json(serde_json::json!(null)).into_response(),
+    }
+}
+
+//#[utoipa::path(get, path = "/api/roaster/{device_id}/autotune/results/latest", params(("device_id" = Path<String>)), responses((status = 200)))]
+async fn api_get_autotune_results_latest(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    let map = state.autotune_results_cache.read().await;
+    match map.get(&device_id) {
+        Some((val, ts)) => {
+            Json(serde_json::json!({"device_id": device_id, "timestamp": ts, "results": val}))
+                .into_response()
+        }
+        None => Json(serde_json::json!(null)).into_response(),
+    }
+}
+
+// Auto-tune history
+//#[utoipa::path(get, path = "/api/roaster/{device_id}/autotune/status", params(("device_id" = Path<String>), HistoryQuery), responses((status = 200)))]
+async fn api_get_autotune_status_history(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(q): Query<HistoryQuery>,
+) -> Response {
+    let now = epoch_secs();
+    let since = q.since_secs.unwrap_or(3600);
+    let limit = q.limit.unwrap_or(200).min(1000) as i64;
+    let since_ts = (now.saturating_sub(since)) as i64;
+    let rows = sqlx::query_as::<_, (i64, String)>("SELECT ts, payload FROM autotune_status WHERE device_id = ? AND ts >= ? ORDER BY ts DESC LIMIT ?")
+        .bind(&device_id)
+        .bind(since_ts)
+        .bind(limit)
+        .fetch_all(&state.read_db)
+        .await;
+    match rows {
+        Ok(items) => {
+            let mut out: Vec<TelemetryItem> = Vec::with_capacity(items.len());
+            for (ts, payload) in items {
+                if let Ok(val) = serde_json::from_str::<serde_json::Value>(&payload) {
+                    out.push(TelemetryItem {
+                        ts,
+                        telemetry: val,
+                        samples: None,
+                    });
+                }
+            }
+            Json(serde_json::json!({"device_id": device_id, "count": out.len(), "items": out}))
+                .into_response()
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "query failed").into_response(),
+    }
+}
+
+//#[utoipa::path(get, path = "/api/roaster/{device_id}/autotune/results", params(("device_id" = Path<String>), HistoryQuery), responses((status = 200)))]
+async fn api_get_autotune_results_history(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Query(q): Query<HistoryQuery>,
+) -> Response {
+    let now = epoch_secs();
+    let since = q.since_secs.unwrap_or(3600);
+    let limit = q.limit.unwrap_or(200).min(1000) as i64;
+    let since_ts = (now.saturating_sub(since)) as i64;
+    let rows = sqlx::query_as::<_, (i64, String)>("SELECT ts, payload FROM autotune_results WHERE device_id = ? AND ts >= ? ORDER BY ts DESC LIMIT ?")
+        .bind(&device_id)
+        .bind(since_ts)
+        .bind(limit)
+        .fetch_all(&state.read_db)
+        .await;
+    match rows {
+        Ok(items) => {
+            let mut out: Vec<TelemetryItem> = Vec::with_capacity(items.len());
+            for (ts, payload) in items {
+                if let Ok(val) = serde_json::from_str::<serde_json::Value>(&payload) {
+                    out.push(TelemetryItem {
+                        ts,
+                        telemetry: val,
+                        samples: None,
+                    });
+                }
+            }
+            Json(serde_json::json!({"device_id": device_id, "count": out.len(), "items": out}))
+                .into_response()
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "query failed").into_response(),
+    }
+}
+
+// Query telemetry history
+#[derive(Deserialize)]
+struct HistoryQuery {
+    since_secs: Option<u64>,
+    limit: Option<u32>,
+}
+
+//#[utoipa::path(get, path = "/api/roaster/{device_id}/telemetry", params(("device_id" = Path<String>), HistoryQuery), responses((status = 200, body = TelemetryHistoryResponse)))]
+async fn api_get_telemetry_history(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    Units(units): Units,
+    Query(q): Query<HistoryQuery>,
+) -> Response {
+    let now = epoch_secs();
+    let since = q.since_secs.unwrap_or(3600); // default last hour
+    let limit = q.limit.unwrap_or(200).min(1000) as i64; // cap limit
+    let since_ts = (now.saturating_sub(since)) as i64;
+    // Compacted history continues in per-minute rollups
+    let rows = sqlx::query_as::<_, (i64, String, Option<i64>)>(
+        "SELECT ts, payload, NULL AS samples FROM telemetry WHERE device_id = ? AND ts >= ?
+         UNION ALL
+         SELECT ts, payload, samples FROM telemetry_rollup WHERE device_id = ? AND ts >= ?
+         ORDER BY ts DESC LIMIT ?",
+    )
+    .bind(&device_id)
+    .bind(since_ts)
+    .bind(&device_id)
+    .bind(since_ts)
+    .bind(limit)
+    .fetch_all(&state.read_db)
+    .await;
+    match rows {
+        Ok(items) => {
+            let mut out: Vec<TelemetryItem> = Vec::with_capacity(items.len());
+            for (ts, payload, samples) in items {
+                if let Ok(mut val) = serde_json::from_str::<serde_json::Value>(&payload) {
+                    val.convert_units(units);
+                    out.push(TelemetryItem {
+                        ts,
+                        telemetry: val,
+                        samples,
+                    });
+                }
+            }
+            Json(TelemetryHistoryResponse {
+                device_id,
+                count: out.len(),
+                items: out,
+            })
+            .into_response()
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "query failed").into_response(),
+    }
+}
+
+// ----- DB init and retention -----
+fn db_path() -> String {
+    std::env::var("RUSTROAST_DB_PATH").unwrap_or_else(|_| "./data/rustroast.db".to_string())
+}
+
+/// Open the pool used for history/export reads so large queries don't compete
+/// with live ingestion on the write pool. `RUSTROAST_DB_READ_URL` may point at a
+/// SQLite replica; otherwise the primary file is reopened read-only. Falls back
+/// to the write pool when disabled (`RUSTROAST_DB_READ_POOL_SIZE=0`) or unavailable.
+async fn init_read_pool(db: &SqlitePool) -> SqlitePool {
+    let size = std::env::var("RUSTROAST_DB_READ_POOL_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(4);
+    if size == 0 {
+        return db.clone();
+    }
+    let url = match std::env::var("RUSTROAST_DB_READ_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            let path = db_path();
+            if !std::path::Path::new(&path).exists() {
+                return db.clone();
+            }
+            format!("sqlite://{}?mode=ro", path)
+        }
+    };
+    match SqlitePoolOptions::new()
+        .max_connections(size)
+        .connect(&url)
+        .await
+    {
+        Ok(pool) => {
+            tracing::info!(max_connections = size, "Opened read-only database pool");
+            pool
+        }
+        Err(e) => {
+            tracing::warn!(error = ?e, "Failed to open read pool; using write pool for reads");
+            db.clone()
+        }
+    }
+}
+
+async fn init_db() -> Result<SqlitePool, sqlx::Error> {
+    let path = db_path();
+    // Ensure parent directory exists
+    if let Some(parent) = std::path::Path::new(&path).parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let url = format!("sqlite://{}?mode=rwc", path);
+    let pool = match SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect(&url)
+        .await
+    {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::warn!(error = ?e, "Failed to open SQLite at path; falling back to in-memory DB");
+            SqlitePoolOptions::new()
+                .max_connections(5)
+                .connect("sqlite::memory:")
+                .await?
+        }
+    };
+    // WAL for better concurrency
+    let _ = sqlx::query("PRAGMA journal_mode=WAL;").execute(&pool).await;
+    init_schema(&pool).await?;
+    Ok(pool)
+}
+
+/// Fresh in-memory database with the full schema, for tests. A single
+/// connection is kept open for the pool's lifetime since each SQLite memory
+/// connection is its own database.
+pub async fn init_memory_db() -> Result<SqlitePool, sqlx::Error> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await?;
+    init_schema(&pool).await?;
+    Ok(pool)
+}
+
+/// Create tables, run migrations and seed default settings.
+async fn init_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    // Enforce foreign key constraints
+    sqlx::query("PRAGMA foreign_keys = ON;")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS telemetry (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_id TEXT NOT NULL,
+            ts INTEGER NOT NULL,
+            payload TEXT NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_telemetry_device_ts ON telemetry(device_id, ts DESC);",
+    )
+    .execute(pool)
+    .await?;
+    // Auto-tune tables for status and results
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS autotune_status (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_id TEXT NOT NULL,
+            ts INTEGER NOT NULL,
+            payload TEXT NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_autotune_status_device_ts ON autotune_status(device_id, ts DESC);")
+        .execute(pool).await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS autotune_results (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_id TEXT NOT NULL,
+            ts INTEGER NOT NULL,
+            payload TEXT NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_autotune_results_device_ts ON autotune_results(device_id, ts DESC);")
+        .execute(pool).await?;
+    // Settings key-value table
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('profile_lookahead_seconds', '20');",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('auc_base_temp', '0');")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('auc_start_event', 'charge');",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('ror_window_seconds', '30');")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('ror_smoothing_algorithm', 'moving_average');",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES ('auto_dry_temp', '150');")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('auto_event_detection', 'true');",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('alarm_sound_enabled', 'true');",
+    )
+    .execute(pool)
+    .await?;
+    // Session cost accounting rates (heater power at 100 % PWM in watts)
+    for (key, value) in [
+        ("cost_currency", "USD"),
+        ("cost_heater_watts", "1500"),
+        ("cost_energy_per_kwh", "0"),
+        ("cost_labor_per_hour", "0"),
+        ("cost_overhead_per_batch", "0"),
+    ] {
+        sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES (?, ?)")
+            .bind(key)
+            .bind(value)
+            .execute(pool)
+            .await?;
+    }
+    sqlx::query(
+        r#"INSERT OR IGNORE INTO settings (key, value) VALUES ('roast_alarms', '[{"name":"High Temp Warning","condition_type":"temp_above","threshold":230,"enabled":true},{"name":"FC Approaching","condition_type":"temp_above","threshold":195,"enabled":true},{"name":"Low RoR Warning","condition_type":"ror_below","threshold":5.0,"reference_event":"first_crack_start","enabled":true}]');"#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Run migrations
+    let migrations: &[&str] = &[
+        include_str!("../migrations/001_roast_sessions.sql"),
+        include_str!("../migrations/002_roast_events.sql"),
+        include_str!("../migrations/003_device_configuration.sql"),
+        include_str!("../migrations/004_session_statistics.sql"),
+        include_str!("../migrations/005_auc_value.sql"),
+        include_str!("../migrations/006_cupping_scores.sql"),
+        include_str!("../migrations/007_profile_env_temp.sql"),
+        include_str!("../migrations/008_session_signoff.sql"),
+        include_str!("../migrations/009_pid_history.sql"),
+        include_str!("../migrations/010_fan_calibration.sql"),
+        include_str!("../migrations/011_users_auth.sql"),
+        include_str!("../migrations/012_user_preferences.sql"),
+        include_str!("../migrations/013_autotune_runs.sql"),
+        include_str!("../migrations/014_profile_scoreboard.sql"),
+        include_str!("../migrations/015_roast_cues.sql"),
+        include_str!("../migrations/016_sync_journal.sql"),
+        include_str!("../migrations/017_session_defects.sql"),
+        include_str!("../migrations/018_manual_sessions.sql"),
+        include_str!("../migrations/019_automations.sql"),
+        include_str!("../migrations/020_profile_batch_scaling.sql"),
+        include_str!("../migrations/021_alerts.sql"),
+        include_str!("../migrations/022_device_logs.sql"),
+        include_str!("../migrations/023_smoothing_presets.sql"),
+        include_str!("../migrations/024_export_signing_key.sql"),
+        include_str!("../migrations/025_green_beans.sql"),
+        include_str!("../migrations/026_mqtt_publish_audit.sql"),
+        include_str!("../migrations/027_session_costs.sql"),
+        include_str!("../migrations/028_telemetry_rollup.sql"),
+        include_str!("../migrations/029_batch_preheat.sql"),
+        include_str!("../migrations/030_sample_roasts.sql"),
+        include_str!("../migrations/031_device_health.sql"),
+        include_str!("../migrations/032_session_archives.sql"),
+        include_str!("../migrations/033_webhooks.sql"),
+        include_str!("../migrations/034_roaster_ror_settings.sql"),
+        include_str!("../migrations/035_status_history.sql"),
+        include_str!("../migrations/036_roast_queue.sql"),
+        include_str!("../migrations/037_alert_limits.sql"),
+        include_str!("../migrations/038_autotune_gain_limits.sql"),
+        include_str!("../migrations/039_charge_corrections.sql"),
+        include_str!("../migrations/040_profile_tolerances.sql"),
+        include_str!("../migrations/041_session_probe_issues.sql"),
+        include_str!("../migrations/042_api_key_control_policies.sql"),
+        include_str!("../migrations/043_session_summaries.sql"),
+        include_str!("../migrations/044_report_emails.sql"),
+        include_str!("../migrations/045_preroast_checklists.sql"),
+        include_str!("../migrations/046_session_attachments.sql"),
+        include_str!("../migrations/047_telemetry_wal_state.sql"),
+    ];
+    for migration_sql in migrations {
+        apply_migration(pool, migration_sql).await?;
+    }
+
+    Ok(())
+}
+
+/// Run a migration statement by statement. Migrations run again on every
+/// start, so statements creating something that already exists, and seed
+/// `INSERT`s of rows already present, are skipped; any other error fails
+/// startup instead of leaving the schema incomplete.
+/// See [`migrate::split_statements`] for how statements are separated.
+pub(crate) async fn apply_migration(pool: &SqlitePool, sql: &str) -> Result<(), sqlx::Error> {
+    for statement in migrate::split_statements(sql) {
+        if let Err(e) = sqlx::query(statement).execute(pool).await {
+            let message = e.to_string();
+            let reseeded =
+                message.contains("UNIQUE constraint failed") && migrate::is_insert(statement);
+            if message.contains("already exists")
+                || message.contains("duplicate column name")
+                || reseeded
+            {
+                tracing::debug!("Migration statement result: {:?}", e);
+            } else {
+                tracing::error!(error = %e, statement, "Migration statement failed");
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prunes device logs and compacts (or, with compaction off, deletes) old
+/// raw telemetry, see [`compaction`].
+async fn retention_cleanup_loop(db: SqlitePool, cluster: Cluster) {
+    let ttl = std::env::var("RUSTROAST_DB_RETENTION_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(7 * 24 * 3600);
+    let interval = std::env::var("RUSTROAST_DB_CLEAN_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(300);
+    let compaction = compaction::CompactionConfig::from_env();
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        ticker.tick().await;
+        if !cluster.is_leader() {
+            continue;
+        }
+        let now = epoch_secs() as i64;
+        let cutoff = (epoch_secs().saturating_sub(ttl)) as i64;
+        match compaction.compact_after_secs {
+            Some(after) => match compaction::compact_telemetry(&db, now - after).await {
+                Ok(stats) if stats.raw_rows > 0 => {
+                    tracing::info!(
+                        raw_rows = stats.raw_rows,
+                        rollups = stats.rollups,
+                        "Compacted old telemetry"
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "Telemetry compaction failed"),
+            },
+            None => {
+                let _ = sqlx::query("DELETE FROM telemetry WHERE ts < ?")
+                    .bind(cutoff)
+                    .execute(&db)
+                    .await;
+            }
+        }
+        if let Some(keep) = compaction.rollup_retention_secs {
+            let _ = sqlx::query("DELETE FROM telemetry_rollup WHERE ts < ?")
+                .bind(now - keep)
+                .execute(&db)
+                .await;
+            let _ = sqlx::query("DELETE FROM device_health WHERE ts < ?")
+                .bind(now - keep)
+                .execute(&db)
+                .await;
+            let _ = sqlx::query("DELETE FROM status_history WHERE ts < ?")
+                .bind(now - keep)
+                .execute(&db)
+                .await;
+        }
+        let _ = sqlx::query("DELETE FROM device_logs WHERE ts < ?")
+            .bind(cutoff)
+            .execute(&db)
+            .await;
+    }
+}
+
+/// Archives completed sessions once they are `archive_after_secs` old, see
+/// [`archive`].
+async fn session_archive_loop(state: AppState) {
+    let Some(after) = state.archiver.archive_after_secs else {
+        return;
+    };
+    let interval = std::env::var("RUSTROAST_ARCHIVE_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(3600);
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        ticker.tick().await;
+        if !state.cluster.is_leader() {
+            continue;
+        }
+        let cutoff = epoch_secs() as i64 - after;
+        match archive::archive_due(&state.archiver, &state.session_service, cutoff).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!(sessions = n, "Archived completed sessions"),
+            Err(e) => tracing::warn!(error = %e, "Session archival failed"),
+        }
+    }
+}
+
+// ----- Roast Session Management API Handlers -----
+
+// Session Management
+async fn api_create_session(
+    State(state): State<AppState>,
+    Json(req): Json<CreateSessionRequest>,
+) -> Response {
+    if req.session_type == SessionType::Manual && req.profile_id.is_some() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Manual sessions cannot be linked to a profile",
+        )
+            .into_response();
+    }
+    if req.session_type == SessionType::Sample {
+        if req.bean_id.is_none() || req.profile_id.is_none() {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Sample sessions need a bean_id and a profile_id",
+            )
+                .into_response();
+        }
+    } else if req.name.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "name is required").into_response();
+    }
+    if let Some(bean_id) = &req.bean_id {
+        match state.session_service.get_bean(bean_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return (StatusCode::BAD_REQUEST, "Unknown bean_id").into_response(),
+            Err(e) => {
+                tracing::error!(?e, "Failed to look up bean");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to create session",
+                )
+                    .into_response();
+            }
+        }
+    }
+    match state.session_service.create_session(req).await {
+        Ok(session) => Json(session).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to create session");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create session",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SessionListQuery {
+    device_id: Option<String>,
+    roaster: Option<String>,
+    limit: Option<i32>,
+    /// Comma-separated fields to return per session, e.g.
+    /// `id,name,status,start_time`; all of them when absent.
+    fields: Option<String>,
+}
+
+async fn api_list_sessions(
+    State(state): State<AppState>,
+    Units(units): Units,
+    Query(q): Query<SessionListQuery>,
+) -> Response {
+    match state
+        .session_service
+        .list_sessions(q.device_id.as_deref(), q.roaster.as_deref(), q.limit)
+        .await
+    {
+        Ok(mut sessions) => {
+            sessions.convert_units(units);
+            match fields::select(&sessions, &fields::parse(q.fields.as_deref())) {
+                Ok(sessions) => Json(sessions).into_response(),
+                Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+            }
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to list sessions");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list sessions").into_response()
+        }
+    }
+}
+
+async fn api_get_session(
+    State(state): State<AppState>,
+    Units(units): Units,
+    Path(id): Path<String>,
+) -> Response {
+    match state.session_service.get_session_with_telemetry(&id).await {
+        Ok(Some(mut session)) => {
+            session.convert_units(units);
+            Json(session).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to get session");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get session").into_response()
+        }
+    }
+}
+
+async fn api_get_session_scoreboard(
+    State(state): State<AppState>,
+    Units(units): Units,
+    Path(id): Path<String>,
+) -> Response {
+    let session = match state.session_service.get_session(&id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to get session");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get session").into_response();
+        }
+    };
+    match state.session_service.session_scoreboard(&session).await {
+        Ok(Some(mut scoreboard)) => {
+            scoreboard.convert_units(units);
+            Json(scoreboard).into_response()
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            "Session has no profile or no telemetry yet",
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to compute session scoreboard");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to compute session scoreboard",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_get_manual_status(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    let session = match state.session_service.get_session(&id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to get session");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get session").into_response();
+        }
+    };
+    if session.session_type != SessionType::Manual {
+        return (StatusCode::BAD_REQUEST, "Session is not a manual roast").into_response();
+    }
+    match state.session_service.manual_status(&session).await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to compute manual roast status");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to compute manual roast status",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_update_session(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateSessionRequest>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &id, &caller).await {
+        return resp;
+    }
+    match state.session_service.update_session(&id, req).await {
+        Ok(Some(session)) => Json(session).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to update session");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update session",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_delete_session(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &id, &caller).await {
+        return resp;
+    }
+    match state.session_service.delete_session(&id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to delete session");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete session",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_start_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<StartSessionQuery>,
+    caller: Caller,
+) -> Response {
+    // A planned session only starts with its pre-roast checklist complete,
+    // unless overridden with a reason
+    let checklist = match state.session_service.get_session(&id).await {
+        Ok(Some(session)) if session.status == SessionStatus::Planning => {
+            state.session_service.session_checklist(&session).await
+        }
+        Ok(_) => Ok(None),
+        Err(e) => Err(e),
+    };
+    let checklist = match checklist {
+        Ok(checklist) => checklist,
+        Err(e) => {
+            tracing::error!(?e, "Failed to load the pre-roast checklist");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to start session").into_response();
+        }
+    };
+    let override_reason = query
+        .override_checklist
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    let overridden = match &checklist {
+        Some(checklist) if !checklist.complete => {
+            if override_reason.is_none() {
+                return (
+                    StatusCode::CONFLICT,
+                    format!(
+                        "Pre-roast checklist incomplete: {}",
+                        checklist.unchecked().join(", ")
+                    ),
+                )
+                    .into_response();
+            }
+            true
+        }
+        _ => false,
+    };
+
+    match state.session_service.start_session(&id).await {
+        Ok(Some(session)) => {
+            if let Some(checklist) = &checklist {
+                let (by, reason) = if overridden {
+                    (Some(caller.label().unwrap_or("anonymous")), override_reason)
+                } else {
+                    (None, None)
+                };
+                if let Err(e) = state
+                    .session_service
+                    .record_session_checklist(&session.id, checklist, by, reason)
+                    .await
+                {
+                    tracing::error!(?e, session_id = %session.id, "Failed to record the pre-roast checklist");
+                }
+            }
+            Json(session).into_response()
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            "Session not found or not in planning state",
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to start session");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to start session").into_response()
+        }
+    }
+}
+
+async fn api_pause_session(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.session_service.pause_session(&id).await {
+        Ok(Some(session)) => Json(session).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found or not active").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to pause session");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to pause session").into_response()
+        }
+    }
+}
+
+async fn api_resume_session(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.session_service.resume_session(&id).await {
+        Ok(Some(session)) => Json(session).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found or not paused").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to resume session");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to resume session",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_complete_session(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.session_service.complete_session(&id).await {
+        Ok(Some(session)) => {
+            preheat::start_after_session(&state, &session).await;
+            Json(session).into_response()
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            "Session not found or not active/paused",
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to complete session");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to complete session",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Sign-off vouches for a roast, so it is recorded under the signed-in
+/// operator or admin rather than a name from the request.
+async fn api_sign_off_session(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Response {
+    let signed_off_by = match (caller.role, caller.label()) {
+        (Some(UserRole::Operator | UserRole::Admin), Some(label)) => label.to_string(),
+        (Some(_), _) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "Only operators and admins can sign off a session",
+            )
+                .into_response()
+        }
+        (None, _) => return (StatusCode::UNAUTHORIZED, "Authentication required").into_response(),
+    };
+    match state
+        .session_service
+        .sign_off_session(&id, &signed_off_by)
+        .await
+    {
+        Ok(Some(session)) => Json(session).into_response(),
+        Ok(None) => (
+            StatusCode::CONFLICT,
+            "Session not found, not completed, or already signed off",
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to sign off session");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to sign off session",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_reopen_session(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Response {
+    if !caller.is_admin() {
+        return (StatusCode::FORBIDDEN, "Only admins can reopen a session").into_response();
+    }
+    match state.session_service.reopen_session(&id).await {
+        Ok(Some(session)) => Json(session).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to reopen session");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to reopen session",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Signed-off sessions are locked; only admins may modify them.
+async fn ensure_session_editable(
+    state: &AppState,
+    session_id: &str,
+    caller: &Caller,
+) -> Result<(), Response> {
+    if caller.is_admin() {
+        return Ok(());
+    }
+    match state.session_service.get_session(session_id).await {
+        Ok(Some(session)) if session.is_signed_off() => Err((
+            StatusCode::FORBIDDEN,
+            "Session is signed off and locked; only admins can modify it",
+        )
+            .into_response()),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            tracing::error!(?e, "Failed to load session");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session").into_response())
+        }
+    }
+}
+
+async fn api_get_session_telemetry(
+    State(state): State<AppState>,
+    caller: Caller,
+    Units(units): Units,
+    Path(id): Path<String>,
+    Query(range): Query<TelemetryRangeQuery>,
+) -> Response {
+    if let (Some(from), Some(to)) = (range.from_secs, range.to_secs) {
+        if from > to {
+            return (StatusCode::BAD_REQUEST, "from_secs must not exceed to_secs").into_response();
+        }
+    }
+    if range.max_points.is_some_and(|m| m < 3) {
+        return (StatusCode::BAD_REQUEST, "max_points must be at least 3").into_response();
+    }
+    // The scoreboard is computed from the full curve before narrowing it
+    match state.session_service.get_session_with_telemetry(&id).await {
+        Ok(Some(mut session_with_telemetry)) => {
+            let mut telemetry = std::mem::take(&mut session_with_telemetry.telemetry);
+            telemetry.retain(|p| {
+                range.from_secs.is_none_or(|from| p.elapsed_seconds >= from)
+                    && range.to_secs.is_none_or(|to| p.elapsed_seconds <= to)
+            });
+            if let Some(max_points) = range.max_points {
+                telemetry = decimate_telemetry(telemetry, max_points);
+            }
+            session_with_telemetry.telemetry = telemetry;
+            // Hint so every client draws the curves the same way
+            let view = range.view.as_deref().unwrap_or(DEFAULT_SMOOTHING_VIEW);
+            let device_id = &session_with_telemetry.session.device_id;
+            let smoothing = match state
+                .user_service
+                .resolve_smoothing(caller.subject.as_deref(), view)
+                .await
+            {
+                Ok(config) => {
+                    state
+                        .session_service
+                        .with_ror_settings(device_id, config)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match smoothing {
+                Ok(smoothing) => session_with_telemetry.smoothing = Some(smoothing),
+                Err(e) => tracing::warn!(?e, "Failed to resolve curve smoothing"),
+            }
+            session_with_telemetry.convert_units(units);
+            Json(session_with_telemetry).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to get session with telemetry");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to get session with telemetry",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TelemetryPointRequest {
+    elapsed_seconds: f32,
+    bean_temp: Option<f32>,
+    env_temp: Option<f32>,
+    rate_of_rise: Option<f32>,
+    heater_pwm: Option<i32>,
+    fan_pwm: Option<i32>,
+    setpoint: Option<f32>,
+}
+
+async fn api_add_telemetry_point(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Json(req): Json<TelemetryPointRequest>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &id, &caller).await {
+        return resp;
+    }
+    match state
+        .session_service
+        .add_telemetry_point(
+            &id,
+            req.elapsed_seconds,
+            req.bean_temp,
+            req.env_temp,
+            req.rate_of_rise,
+            req.heater_pwm,
+            req.fan_pwm,
+            req.setpoint,
+        )
+        .await
+    {
+        Ok(_) => StatusCode::CREATED.into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to add telemetry point");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to add telemetry point",
+            )
+                .into_response()
+        }
+    }
+}
+
+// Profile Management
+async fn api_create_profile(
+    State(state): State<AppState>,
+    Json(req): Json<CreateProfileRequest>,
+) -> Response {
+    match state.session_service.create_profile(req).await {
+        Ok(profile) => Json(profile).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to create profile");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create profile",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProfileListQuery {
+    include_private: Option<bool>,
+    /// Comma-separated fields to return per profile; all of them when
+    /// absent.
+    fields: Option<String>,
+}
+
+async fn api_list_profiles(
+    State(state): State<AppState>,
+    Units(units): Units,
+    Query(q): Query<ProfileListQuery>,
+) -> Response {
+    match state
+        .session_service
+        .list_profiles(q.include_private.unwrap_or(false))
+        .await
+    {
+        Ok(mut profiles) => {
+            profiles.convert_units(units);
+            match fields::select(&profiles, &fields::parse(q.fields.as_deref())) {
+                Ok(profiles) => Json(profiles).into_response(),
+                Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+            }
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to list profiles");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list profiles").into_response()
+        }
+    }
+}
+
+async fn api_get_profile(
+    State(state): State<AppState>,
+    Units(units): Units,
+    Path(id): Path<String>,
+) -> Response {
+    match state.session_service.get_profile_with_points(&id).await {
+        Ok(Some(mut profile)) => {
+            profile.convert_units(units);
+            Json(profile).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Profile not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to get profile");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get profile").into_response()
+        }
+    }
+}
+
+async fn api_update_profile(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<CreateProfileRequest>,
+) -> Response {
+    match state.session_service.update_profile(&id, req).await {
+        Ok(Some(profile)) => Json(profile).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Profile not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to update profile");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update profile",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_delete_profile(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.session_service.delete_profile(&id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Profile not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to delete profile");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete profile",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_import_artisan_profile(
+    State(state): State<AppState>,
+    Query(q): Query<ImportQuery>,
+    Json(req): Json<ImportArtisanProfileRequest>,
+) -> Response {
+    match state
+        .session_service
+        .import_artisan_profile(req, q.partial)
+        .await
+    {
+        Ok(ImportOutcome::Imported(imported)) => Json(imported).into_response(),
+        Ok(ImportOutcome::Rejected(report)) => {
+            let error = if report.points_accepted == 0 {
+                "The Artisan profile has no usable curve points"
+            } else {
+                "The Artisan profile has problems, import again with ?partial=true to accept the valid subset"
+            };
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({
+                    "error": error,
+                    "status": StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                    "import_report": report,
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to import Artisan profile");
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to import Artisan profile: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
+// ----- Settings API -----
+
+async fn api_get_settings(State(state): State<AppState>) -> Response {
+    let rows = sqlx::query_as::<_, (String, String)>("SELECT key, value FROM settings")
+        .fetch_all(&state.db)
+        .await;
+    match rows {
+        Ok(rows) => {
+            let map: serde_json::Map<String, serde_json::Value> = rows
+                .into_iter()
+                .map(|(k, v)| (k, serde_json::Value::String(v)))
+                .collect();
+            Json(serde_json::Value::Object(map)).into_response()
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to get settings");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get settings").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SetSettingRequest {
+    value: String,
+}
+
+async fn api_set_setting(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Json(req): Json<SetSettingRequest>,
+) -> Response {
+    let result = sqlx::query(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, datetime('now'))
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+    )
+    .bind(&key)
+    .bind(&req.value)
+    .execute(&state.db)
+    .await;
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to set setting");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to set setting").into_response()
+        }
+    }
+}
+
+// ----- Test-only helper endpoint -----
+
+#[cfg(feature = "test-endpoints")]
+async fn api_test_emit_telemetry(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    maybe_body: Option<Json<serde_json::Value>>,
+) -> Response {
+    let topic = rustroast_core::telemetry_topic(&device_id);
+    let payload = if let Some(Json(v)) = maybe_body {
+        v.to_string()
+    } else {
+        serde_json::json!({
+            "timestamp": 0,
+            "beanTemp": 100.0,
+            "envTemp": 90.0,
+            "rateOfRise": 10.0,
+            "heaterPWM": 50,
+            "fanPWM": 180,
+            "setpoint": 200.0,
+            "controlMode": 1,
+            "heaterEnable": 1,
+            "uptime": 1,
+            "Kp": 15.0,
+            "Ki": 1.0,
+            "Kd": 25.0,
+            "freeHeap": 0,
+            "rssi": -40,
+            "systemStatus": 0
+        })
+        .to_string()
+    };
+    // Delivered in-process as if the device had published it
+    state.mqtt.inject(&topic, payload);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[cfg(feature = "test-endpoints")]
+async fn api_test_emit_status(
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+    maybe_body: Option<Json<serde_json::Value>>,
+) -> Response {
+    let topic = rustroast_core::status_topic(&device_id);
+    let payload = if let Some(Json(v)) = maybe_body {
+        v.to_string()
+    } else {
+        serde_json::json!({
+            "status": "online",
+            "id": format!("{}-TEST", device_id),
+            "ip": "127.0.0.1",
+            "rssi": -40,
+            "freeHeap": 123456,
+            "version": "test"
+        })
+        .to_string()
+    };
+    // Delivered in-process as if the device had published it
+    state.mqtt.inject(&topic, payload);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+// Roast Events API Handlers
+async fn api_get_roast_events(
+    State(state): State<AppState>,
+    Units(units): Units,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_service.get_roast_events(&session_id).await {
+        Ok(mut events) => {
+            events.convert_units(units);
+            Json(events).into_response()
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to get roast events");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to get roast events",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_create_roast_event(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    Json(req): Json<CreateRoastEventRequest>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    match state
+        .session_service
+        .create_roast_event(&session_id, req)
+        .await
+    {
+        Ok(event) => (StatusCode::CREATED, Json(event)).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to create roast event");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create roast event",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Upper bound on events accepted by one bulk request.
+const MAX_BULK_EVENTS: usize = 500;
+
+async fn api_create_roast_events_bulk(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    Json(req): Json<BulkCreateRoastEventsRequest>,
+) -> Response {
+    if req.events.is_empty() || req.events.len() > MAX_BULK_EVENTS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("events must contain 1 to {} entries", MAX_BULK_EVENTS),
+        )
+            .into_response();
+    }
+    if let Some(i) = req
+        .events
+        .iter()
+        .position(|e| !e.elapsed_seconds.is_finite() || e.elapsed_seconds < 0.0)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "events[{}].elapsed_seconds must be a non-negative number",
+                i
+            ),
+        )
+            .into_response();
+    }
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    match state.session_service.get_session(&session_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to load session");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session").into_response();
+        }
+    }
+    match state
+        .session_service
+        .create_roast_events(&session_id, req.events)
+        .await
+    {
+        Ok(events) => (StatusCode::CREATED, Json(events)).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to create roast events");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create roast events",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Bean temperature readings older than this are not attached to tapped events.
+const TAP_MAX_READING_AGE_SECS: u64 = 10;
+
+/// Record an event at the current elapsed time with the latest bean temp.
+async fn api_tap_roast_event(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    Query(q): Query<TapRoastEventQuery>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    let session = match state.session_service.get_session(&session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to load session");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session").into_response();
+        }
+    };
+    let Some(start) = session.start_time else {
+        return (StatusCode::CONFLICT, "Session has not been started").into_response();
+    };
+    let elapsed_seconds = (chrono::Utc::now() - start).num_milliseconds().max(0) as f32 / 1000.0;
+    let temperature = state
+        .telemetry_cache
+        .read()
+        .await
+        .get(&session.device_id)
+        .filter(|(_, seen)| epoch_secs().saturating_sub(*seen) <= TAP_MAX_READING_AGE_SECS)
+        .and_then(|(payload, _)| payload.get("beanTemp").and_then(|v| v.as_f64()))
+        .map(|t| t as f32);
+    let req = CreateRoastEventRequest {
+        event_type: q.event_type,
+        elapsed_seconds,
+        temperature,
+        notes: q.notes,
+    };
+    match state
+        .session_service
+        .create_roast_event(&session_id, req)
+        .await
+    {
+        Ok(event) => (StatusCode::CREATED, Json(event)).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to create roast event");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create roast event",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_update_roast_event(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path((session_id, event_id)): Path<(String, String)>,
+    Json(req): Json<UpdateRoastEventRequest>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    match state
+        .session_service
+        .update_roast_event(&event_id, req)
+        .await
+    {
+        Ok(event) => Json(event).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to update roast event");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update roast event",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_delete_roast_event(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path((session_id, event_id)): Path<(String, String)>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    match state.session_service.delete_roast_event(&event_id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to delete roast event");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete roast event",
+            )
+                .into_response()
+        }
+    }
+}
+
+// ---- Cupping Notes API (AP-012) ----
+
+async fn api_get_cupping(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_service.get_cupping(&session_id).await {
+        Ok(Some(cupping)) => Json(serde_json::to_value(cupping).unwrap()).into_response(),
+        Ok(None) => Json(serde_json::Value::Null).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to get cupping");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get cupping").into_response()
+        }
+    }
+}
+
+async fn api_create_cupping(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    Json(req): Json<CreateCuppingRequest>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    match state.session_service.create_cupping(&session_id, req).await {
+        Ok(cupping) => (StatusCode::CREATED, Json(cupping)).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to create cupping");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create cupping",
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn api_delete_cupping(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    match state.session_service.delete_cupping(&session_id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to delete cupping");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete cupping",
+            )
+                .into_response()
+        }
+    }
+}
+
+// ---- Roast defect tags ----
+
+async fn api_list_defects(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_service.list_defects(&session_id).await {
+        Ok(defects) => Json(defects).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to list defects");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list defects").into_response()
+        }
+    }
+}
+
+async fn api_create_defect(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    Json(req): Json<CreateDefectRequest>,
+) -> Response {
+    let range = [req.start_seconds, req.end_seconds];
+    if range.iter().flatten().any(|t| !t.is_finite() || *t < 0.0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "start_seconds and end_seconds must be non-negative",
+        )
+            .into_response();
+    }
+    if let (Some(start), Some(end)) = (req.start_seconds, req.end_seconds) {
+        if start > end {
+            return (
+                StatusCode::BAD_REQUEST,
+                "start_seconds must not be after end_seconds",
+            )
+                .into_response();
+        }
+    }
+    match state.session_service.get_session(&session_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to load session");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session").into_response();
+        }
+    }
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    let tagged_by = caller.name.as_deref().or(caller.subject.as_deref());
+    match state
+        .session_service
+        .create_defect(&session_id, req, tagged_by)
+        .await
+    {
+        Ok(defect) => (StatusCode::CREATED, Json(defect)).into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to create defect");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create defect").into_response()
+        }
+    }
+}
+
+async fn api_delete_defect(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path((session_id, defect_id)): Path<(String, String)>,
+) -> Response {
+    if let Err(resp) = ensure_session_editable(&state, &session_id, &caller).await {
+        return resp;
+    }
+    match state
+        .session_service
+        .delete_defect(&session_id, &defect_id)
+        .await
+    {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Defect not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to delete defect");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete defect").into_response()
+        }
+    }
+}
+
+// ---- Data Export API (AP-014) ----
+
+async fn api_export_csv(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(opts): Query<CsvExportOptions>,
+    RequestLocale(locale): RequestLocale,
+) -> Response {
+    match state.session_service.export_csv(&id, opts, locale).await {
+        Ok(Some((csv, filename))) => {
+            let integrity = match state
+                .export_signer
+                .sign(&id, ExportFormat::Csv, csv.as_bytes())
+                .await
+            {
+                Ok(integrity) => integrity,
+                Err(e) => {
+                    tracing::error!(?e, "Failed to sign CSV export");
+                    return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to export CSV")
+                        .into_response();
+                }
+            };
+            let headers = [
+                (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    axum::http::header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+                (
+                    axum::http::HeaderName::from_static("x-content-sha256"),
+                    integrity.sha256.clone(),
+                ),
+                (
+                    axum::http::HeaderName::from_static("x-rustroast-integrity"),
+                    serde_json::to_string(&integrity).unwrap_or_default(),
+                ),
+            ];
+            (headers, csv).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to export CSV");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to export CSV").into_response()
+        }
+    }
+}
+
+async fn api_export_artisan(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.session_service.export_artisan_json(&id).await {
+        Ok(Some((mut json, filename))) => {
+            if let Err(e) = state.export_signer.sign_artisan(&id, &mut json).await {
+                tracing::error!(?e, "Failed to sign Artisan export");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to export Artisan JSON",
+                )
+                    .into_response();
+            }
+            let body = serde_json::to_string_pretty(&json).unwrap_or_default();
+            let headers = [
+                (CONTENT_TYPE, "application/json; charset=utf-8"),
+                (
+                    axum::http::header::CONTENT_DISPOSITION,
+                    &format!("attachment; filename=\"{}\"", filename),
+                ),
+            ];
+            (headers, body).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            tracing::error!(?e, "Failed to export Artisan JSON");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to export Artisan JSON",
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Splitting migration files into the statements `apply_migration` runs.
+//!
+//! A statement ends at a `;` outside comments, quoted strings and quoted
+//! identifiers, and outside the `BEGIN ... END` body of a `CREATE TRIGGER`
+//! (`CASE ... END` inside such a body is balanced too). Chunks holding only
+//! comments and whitespace are dropped.
+
+/// The statements of `sql` in order, trimmed and without the closing `;`.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    // Whether the current chunk has anything besides comments
+    let mut has_code = false;
+    // Leading keywords of the current statement, enough to spot a trigger
+    let mut lead: Vec<&str> = Vec::new();
+    let mut trigger = false;
+    // Open BEGIN/CASE blocks
+    let mut depth = 0usize;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = find(bytes, i + 2, b"\n").map_or(bytes.len(), |end| end + 1);
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = find(bytes, i + 2, b"*/").map_or(bytes.len(), |end| end + 2);
+            }
+            quote @ (b'\'' | b'"' | b'`' | b'[') => {
+                let close = if quote == b'[' { b']' } else { quote };
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == close {
+                        // A doubled quote is an escaped one
+                        if close != b']' && bytes.get(i + 1) == Some(&close) {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+                i += 1;
+                has_code = true;
+            }
+            b';' if depth == 0 => {
+                if has_code {
+                    statements.push(sql[start..i].trim());
+                }
+                i += 1;
+                start = i;
+                has_code = false;
+                lead.clear();
+                trigger = false;
+            }
+            c if c.is_ascii_alphanumeric() || c == b'_' => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|c| !(c.is_ascii_alphanumeric() || *c == b'_'))
+                    .map_or(bytes.len(), |n| i + n);
+                let word = &sql[i..end];
+                has_code = true;
+                if lead.len() < 3 {
+                    lead.push(word);
+                    trigger = is_create_trigger(&lead);
+                }
+                if word.eq_ignore_ascii_case("CASE")
+                    || (trigger && word.eq_ignore_ascii_case("BEGIN"))
+                {
+                    depth += 1;
+                } else if word.eq_ignore_ascii_case("END") {
+                    depth = depth.saturating_sub(1);
+                }
+                i = end;
+            }
+            c => {
+                if !c.is_ascii_whitespace() {
+                    has_code = true;
+                }
+                i += 1;
+            }
+        }
+    }
+    if has_code {
+        statements.push(sql[start..].trim());
+    }
+    statements
+}
+
+/// Whether a statement from [`split_statements`] is an `INSERT`, looking
+/// past leading comments.
+pub fn is_insert(statement: &str) -> bool {
+    let mut rest = statement.trim_start();
+    loop {
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, r)| r).trim_start();
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, r)| r).trim_start();
+        } else {
+            break;
+        }
+    }
+    rest.get(..6)
+        .is_some_and(|word| word.eq_ignore_ascii_case("INSERT"))
+}
+
+/// `CREATE [TEMP|TEMPORARY] TRIGGER`
+fn is_create_trigger(lead: &[&str]) -> bool {
+    let is = |n: usize, keyword: &str| lead.get(n).is_some_and(|w| w.eq_ignore_ascii_case(keyword));
+    is(0, "CREATE")
+        && (is(1, "TRIGGER") || ((is(1, "TEMP") || is(1, "TEMPORARY")) && is(2, "TRIGGER")))
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|n| from + n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semicolons_in_comments_strings_and_triggers_do_not_split() {
+        let sql = r#"
+-- Migration: header; with a semicolon
+CREATE TABLE t (id TEXT PRIMARY KEY, note TEXT DEFAULT 'a;b', updated_at TEXT);
+/* block; comment */
+INSERT INTO t (id, note) VALUES ('it''s;', "x;y");
+
+CREATE TRIGGER IF NOT EXISTS t_touch
+    AFTER UPDATE ON t
+    FOR EACH ROW
+BEGIN
+    UPDATE t SET note = CASE WHEN NEW.note IS NULL THEN 'none' ELSE NEW.note END WHERE id = NEW.id;
+    UPDATE t SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+BEGIN TRANSACTION;
+COMMIT
+-- trailing comment; nothing to run
+"#;
+        let statements = split_statements(sql);
+        assert_eq!(statements.len(), 5, "{statements:#?}");
+        assert!(statements[0].starts_with("-- Migration: header; with a semicolon\nCREATE TABLE t"));
+        assert!(statements[0].ends_with("updated_at TEXT)"));
+        assert_eq!(
+            statements[1],
+            "/* block; comment */\nINSERT INTO t (id, note) VALUES ('it''s;', \"x;y\")"
+        );
+        assert!(statements[2].starts_with("CREATE TRIGGER IF NOT EXISTS t_touch"));
+        assert!(statements[2].ends_with("WHERE id = NEW.id;\nEND"));
+        // BEGIN only opens a block inside a trigger
+        assert_eq!(statements[3], "BEGIN TRANSACTION");
+        assert_eq!(statements[4], "COMMIT\n-- trailing comment; nothing to run");
+    }
+
+    #[test]
+    fn test_comment_only_and_empty_chunks_are_dropped() {
+        assert!(split_statements("").is_empty());
+        assert!(split_statements(" ;\n-- only a comment;\n/* and; this */ ;").is_empty());
+        assert_eq!(
+            split_statements("SELECT 1;;SELECT 2"),
+            vec!["SELECT 1", "SELECT 2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrations_rerun_cleanly_with_triggers_and_seeds() {
+        let pool = crate::init_memory_db().await.unwrap();
+        // A restart runs every migration again
+        crate::init_schema(&pool).await.unwrap();
+        let triggers: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'trigger' AND name LIKE 'update_%_updated_at'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(triggers, 5);
+        let profiles: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM roast_profiles WHERE id LIKE 'default-%'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(profiles, 3);
+    }
+
+    #[test]
+    fn test_insert_is_recognized_past_comments() {
+        assert!(is_insert("insert into t VALUES (1)"));
+        assert!(is_insert(
+            "-- Seed rows\n/* defaults */ INSERT INTO t VALUES (1)"
+        ));
+        assert!(!is_insert(
+            "-- INSERT INTO t\nCREATE UNIQUE INDEX i ON t(id)"
+        ));
+        assert!(!is_insert("UPDATE t SET id = 1"));
+    }
+}
@@ -12,6 +12,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use rumqttc::QoS;
+use rustroast_core::{Command, ControlMode, TopicNegotiator};
 use rustroast_mqtt::MqttService;
 use tokio::net::TcpListener;
 use tokio::sync::RwLock;
@@ -92,6 +93,8 @@ struct ModbusSharedState {
     telemetry_cache: Arc<RwLock<HashMap<String, (serde_json::Value, u64)>>>,
     /// MQTT service for publishing control commands on holding-register writes.
     mqtt: MqttService,
+    /// Topic layout each device is addressed in.
+    topic_layouts: Arc<TopicNegotiator>,
     /// Default device_id whose telemetry is served via input registers.
     device_id: String,
     /// Current holding-register values (0x0000 .. holding_reg::COUNT).
@@ -247,77 +250,49 @@ async fn publish_control_commands(state: &ModbusSharedState, start: u16, end: u1
     let mut setpoint_published = false;
 
     for reg_addr in start..end {
-        let result = match reg_addr {
+        let command = match reg_addr {
             holding_reg::SETPOINT_HI | holding_reg::SETPOINT_LO if !setpoint_published => {
                 setpoint_published = true;
                 let setpoint = registers_to_f32(regs[0], regs[1]);
-                let topic = rustroast_core::control_setpoint(device_id);
-                state
-                    .mqtt
-                    .publish(&topic, QoS::AtMostOnce, false, format!("{setpoint}"))
-                    .await
-            }
-            holding_reg::FAN_PWM => {
-                let topic = rustroast_core::control_fan_pwm(device_id);
-                state
-                    .mqtt
-                    .publish(
-                        &topic,
-                        QoS::AtMostOnce,
-                        false,
-                        regs[holding_reg::FAN_PWM as usize].to_string(),
-                    )
-                    .await
-            }
-            holding_reg::HEATER_PWM => {
-                let topic = rustroast_core::control_heater_pwm(device_id);
-                state
-                    .mqtt
-                    .publish(
-                        &topic,
-                        QoS::AtMostOnce,
-                        false,
-                        regs[holding_reg::HEATER_PWM as usize].to_string(),
-                    )
-                    .await
+                // Hundredths, so the f32 isn't sent with its widening noise
+                Command::Setpoint((f64::from(setpoint) * 100.0).round() / 100.0)
             }
+            holding_reg::FAN_PWM => Command::FanPwm(regs[holding_reg::FAN_PWM as usize]),
+            holding_reg::HEATER_PWM => Command::HeaterPwm(
+                u8::try_from(regs[holding_reg::HEATER_PWM as usize]).unwrap_or(u8::MAX),
+            ),
             holding_reg::CONTROL_MODE => {
-                let mode = if regs[holding_reg::CONTROL_MODE as usize] == 0 {
-                    "manual"
+                Command::Mode(if regs[holding_reg::CONTROL_MODE as usize] == 0 {
+                    ControlMode::Manual
                 } else {
-                    "auto"
-                };
-                let topic = rustroast_core::control_mode(device_id);
-                state
-                    .mqtt
-                    .publish(&topic, QoS::AtMostOnce, false, mode)
-                    .await
+                    ControlMode::Auto
+                })
             }
             holding_reg::HEATER_ENABLE => {
-                let val = if regs[holding_reg::HEATER_ENABLE as usize] == 0 {
-                    "0"
-                } else {
-                    "1"
-                };
-                let topic = rustroast_core::control_heater_enable(device_id);
-                state
-                    .mqtt
-                    .publish(&topic, QoS::AtMostOnce, false, val)
-                    .await
+                Command::HeaterEnable(regs[holding_reg::HEATER_ENABLE as usize] != 0)
             }
             holding_reg::EMERGENCY_STOP => {
                 if regs[holding_reg::EMERGENCY_STOP as usize] == 1 {
-                    let topic = rustroast_core::control_emergency_stop(device_id);
-                    state
-                        .mqtt
-                        .publish(&topic, QoS::AtMostOnce, false, "1")
-                        .await
+                    Command::EmergencyStop
                 } else {
                     continue;
                 }
             }
             _ => continue, // Gap registers (0x0006..0x000B) — no MQTT action.
         };
+        if let Err(e) = command.validate() {
+            tracing::warn!(reg_addr, "Ignoring Modbus write: {}", e);
+            continue;
+        }
+        let result = state
+            .mqtt
+            .publish(
+                &state.topic_layouts.route(&command.topic(device_id)),
+                QoS::AtMostOnce,
+                false,
+                command.payload(),
+            )
+            .await;
 
         if let Err(e) = result {
             tracing::warn!(
@@ -339,6 +314,7 @@ async fn publish_control_commands(state: &ModbusSharedState, start: u16, end: u1
 pub async fn start_modbus_server(
     telemetry_cache: Arc<RwLock<HashMap<String, (serde_json::Value, u64)>>>,
     mqtt: MqttService,
+    topic_layouts: Arc<TopicNegotiator>,
 ) -> Option<tokio::task::JoinHandle<()>> {
     let addr_str = match std::env::var("RUSTROAST_MODBUS_ADDR") {
         Ok(addr) => addr,
@@ -362,6 +338,7 @@ pub async fn start_modbus_server(
     let shared_state = ModbusSharedState {
         telemetry_cache,
         mqtt,
+        topic_layouts,
         device_id: device_id.clone(),
         holding_registers: Arc::new(tokio::sync::Mutex::new(vec![
             0u16;
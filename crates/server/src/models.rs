@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::sqlite::{SqliteTypeInfo, SqliteValueRef};
 use sqlx::{Decode, Encode, FromRow, Type};
@@ -11,6 +11,10 @@ pub struct RoastSession {
     pub name: String,
     pub device_id: String,
     pub profile_id: Option<String>, // Optional linked profile
+    pub bean_id: Option<String>,    // Optional green bean lot
+    pub session_type: SessionType,
+    /// Triplicate group of a sample session, see `/api/sample-groups`.
+    pub sample_group_id: Option<String>,
     pub status: SessionStatus,
     pub start_time: Option<DateTime<Utc>>,
     pub end_time: Option<DateTime<Utc>>,
@@ -44,6 +48,24 @@ pub struct RoastSession {
 
     // AUC (AP-002)
     pub auc_value: Option<f32>,
+
+    // Attribution and QA sign-off
+    pub roaster: Option<String>,
+    pub signed_off_by: Option<String>,
+    pub signed_off_at: Option<DateTime<Utc>>,
+
+    // Profile-following results (actual minus profile target)
+    pub profile_fc_delta: Option<f32>,
+    pub profile_drop_time_delta: Option<f32>,
+    pub profile_drop_temp_delta: Option<f32>,
+    pub profile_deviation_integral: Option<f32>,
+}
+
+impl RoastSession {
+    /// Signed-off sessions are locked against edits except by admins.
+    pub fn is_signed_off(&self) -> bool {
+        self.signed_off_at.is_some()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -62,6 +84,13 @@ pub struct RoastProfile {
     pub target_end_temp: Option<f32>,
     pub preheat_temp: Option<f32>,
     pub charge_temp: Option<f32>,
+    /// Green batch size the profile was tuned for (grams).
+    pub batch_size_g: Option<f32>,
+    /// Heater output ceiling for the roast (percent).
+    pub heater_cap: Option<i32>,
+    /// Original profile this one is a version of.
+    pub parent_profile_id: Option<String>,
+    pub version: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -74,6 +103,7 @@ pub struct ProfilePoint {
     pub notes: Option<String>,
     pub created_at: DateTime<Utc>,
     pub target_env_temp: Option<f32>,
+    pub target_airflow: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -88,6 +118,7 @@ pub struct SessionTelemetry {
     pub heater_pwm: Option<i32>,
     pub fan_pwm: Option<i32>,
     pub setpoint: Option<f32>,
+    pub airflow: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -154,9 +185,70 @@ impl std::str::FromStr for SessionStatus {
     }
 }
 
+/// `profile` sessions may follow a roast profile; `manual` sessions have no
+/// profile and record heater/fan changes as events. `sample` sessions are
+/// small green-buying sample roasts of a bean lot on a profile, grouped in
+/// threes for side-by-side evaluation.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionType {
+    #[default]
+    Profile,
+    Manual,
+    Sample,
+}
+
+impl Type<sqlx::Sqlite> for SessionType {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for SessionType {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for SessionType {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for SessionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SessionType::Profile => "profile",
+            SessionType::Manual => "manual",
+            SessionType::Sample => "sample",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for SessionType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "profile" => Ok(SessionType::Profile),
+            "manual" => Ok(SessionType::Manual),
+            "sample" => Ok(SessionType::Sample),
+            _ => Err(format!("Invalid session type: {}", s)),
+        }
+    }
+}
+
 // Request/Response DTOs
 #[derive(Debug, Deserialize)]
 pub struct CreateSessionRequest {
+    /// Optional for sample sessions, which are named after their bean.
+    #[serde(default)]
     pub name: String,
     pub device_id: String,
     pub profile_id: Option<String>,
@@ -167,17 +259,31 @@ pub struct CreateSessionRequest {
     pub notes: Option<String>,
     pub ambient_temp: Option<f32>,
     pub humidity: Option<f32>,
+    pub roaster: Option<String>,
+    #[serde(default)]
+    pub session_type: SessionType,
+    /// Green bean lot. Its origin and variety fill in `bean_origin` and
+    /// `bean_variety` when those are not given.
+    #[serde(default)]
+    pub bean_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateSessionRequest {
     pub name: Option<String>,
+    pub roaster: Option<String>,
     pub roasted_weight: Option<f32>,
     pub notes: Option<String>,
     pub first_crack_time: Option<i32>,
     pub development_time_ratio: Option<f32>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct StartSessionQuery {
+    /// Why the session starts without a complete pre-roast checklist.
+    pub override_checklist: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateProfileRequest {
     pub name: String,
@@ -187,6 +293,10 @@ pub struct CreateProfileRequest {
     pub target_end_temp: Option<f32>,
     pub preheat_temp: Option<f32>,
     pub charge_temp: Option<f32>,
+    #[serde(default)]
+    pub batch_size_g: Option<f32>,
+    #[serde(default)]
+    pub heater_cap: Option<i32>,
     pub points: Vec<CreateProfilePointRequest>,
 }
 
@@ -197,6 +307,7 @@ pub struct CreateProfilePointRequest {
     pub fan_speed: Option<i32>,
     pub notes: Option<String>,
     pub target_env_temp: Option<f32>,
+    pub target_airflow: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -205,6 +316,57 @@ pub struct ImportArtisanProfileRequest {
     pub name: Option<String>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct ImportQuery {
+    /// Import the valid subset instead of rejecting a file with problems.
+    #[serde(default)]
+    pub partial: bool,
+}
+
+/// What an import accepted and what it had to leave out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub points_accepted: usize,
+    pub rows_skipped: Vec<SkippedImportRow>,
+    pub events_unmatched: Vec<UnmatchedImportEvent>,
+}
+
+impl ImportReport {
+    pub fn is_clean(&self) -> bool {
+        self.rows_skipped.is_empty() && self.events_unmatched.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedImportRow {
+    /// Zero-based index of the row in the file's curve.
+    pub row: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnmatchedImportEvent {
+    pub event: String,
+    pub time_seconds: f64,
+    pub reason: String,
+}
+
+/// An imported profile and the report of its import.
+#[derive(Debug, Serialize)]
+pub struct ImportedProfile {
+    #[serde(flatten)]
+    pub profile: ProfileWithPoints,
+    pub import_report: ImportReport,
+}
+
+#[derive(Debug)]
+pub enum ImportOutcome {
+    Imported(Box<ImportedProfile>),
+    /// Nothing was created: the file had problems and `partial` wasn't set,
+    /// or no point was usable.
+    Rejected(ImportReport),
+}
+
 #[derive(Debug, Serialize)]
 pub struct SessionWithTelemetry {
     #[serde(flatten)]
@@ -213,6 +375,49 @@ pub struct SessionWithTelemetry {
     pub profile: Option<ProfileWithPoints>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cupping: Option<CuppingWithAttributes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scoreboard: Option<ProfileScoreboard>,
+    pub defects: Vec<SessionDefect>,
+    pub cost: SessionCost,
+    /// The caller's curve smoothing, set by the session telemetry endpoint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothing: Option<SmoothingConfig>,
+}
+
+/// How a profile-following roast is tracking against its profile.
+/// Times are elapsed seconds of the session, temperatures °C unless the
+/// request asks for °F (see [`crate::units`]); profile times are shifted
+/// by `profile_zero`.
+#[derive(Debug, Clone, Serialize, Default, PartialEq)]
+pub struct ProfileScoreboard {
+    /// Elapsed seconds at which the profile starts: the charge event plus
+    /// the configured offset, or 0 without a charge event.
+    pub profile_zero: f32,
+    pub elapsed_seconds: f32,
+    pub bean_temp: f32,
+    /// Profile target temperature at `elapsed_seconds`.
+    pub target_temp: Option<f32>,
+    /// `bean_temp - target_temp`.
+    pub deviation: Option<f32>,
+    /// Signed area between bean temp and profile so far (°C·min).
+    pub deviation_integral: f32,
+    /// Unsigned area between bean temp and profile so far (°C·min).
+    pub abs_deviation_integral: f32,
+    /// Bean temperature rise (°C/min) over the roaster's RoR window, 30 s
+    /// unless configured, used for projections.
+    pub ror: Option<f32>,
+    pub target_first_crack: Option<f32>,
+    /// Marked first crack, or projected from the current RoR until it is marked.
+    pub projected_first_crack: Option<f32>,
+    pub first_crack_delta: Option<f32>,
+    pub target_drop_time: Option<f32>,
+    pub target_drop_temp: Option<f32>,
+    /// When the bean temp reaches `target_drop_temp` at the current RoR.
+    pub projected_drop_time: Option<f32>,
+    /// Bean temp expected at `target_drop_time` at the current RoR.
+    pub projected_drop_temp: Option<f32>,
+    pub drop_time_delta: Option<f32>,
+    pub drop_temp_delta: Option<f32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -236,6 +441,8 @@ pub struct RoastEvent {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum RoastEventType {
+    /// Beans in; the time zero profiles are aligned to.
+    Charge,
     Drop,
     DryingEnd,
     FirstCrackStart,
@@ -244,6 +451,8 @@ pub enum RoastEventType {
     SecondCrackEnd,
     DevelopmentStart,
     DropOut,
+    HeaterChange,
+    FanChange,
     Custom,
 }
 
@@ -273,6 +482,7 @@ impl<'q> Encode<'q, sqlx::Sqlite> for RoastEventType {
 impl std::fmt::Display for RoastEventType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
+            RoastEventType::Charge => "charge",
             RoastEventType::Drop => "drop",
             RoastEventType::DryingEnd => "drying_end",
             RoastEventType::FirstCrackStart => "first_crack_start",
@@ -281,6 +491,8 @@ impl std::fmt::Display for RoastEventType {
             RoastEventType::SecondCrackEnd => "second_crack_end",
             RoastEventType::DevelopmentStart => "development_start",
             RoastEventType::DropOut => "drop_out",
+            RoastEventType::HeaterChange => "heater_change",
+            RoastEventType::FanChange => "fan_change",
             RoastEventType::Custom => "custom",
         };
         write!(f, "{}", s)
@@ -292,6 +504,7 @@ impl std::str::FromStr for RoastEventType {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "charge" => Ok(RoastEventType::Charge),
             "drop" => Ok(RoastEventType::Drop),
             "drying_end" => Ok(RoastEventType::DryingEnd),
             "first_crack_start" => Ok(RoastEventType::FirstCrackStart),
@@ -300,6 +513,8 @@ impl std::str::FromStr for RoastEventType {
             "second_crack_end" => Ok(RoastEventType::SecondCrackEnd),
             "development_start" => Ok(RoastEventType::DevelopmentStart),
             "drop_out" => Ok(RoastEventType::DropOut),
+            "heater_change" => Ok(RoastEventType::HeaterChange),
+            "fan_change" => Ok(RoastEventType::FanChange),
             "custom" => Ok(RoastEventType::Custom),
             _ => Err(format!("Invalid roast event type: {}", s)),
         }
@@ -314,6 +529,19 @@ pub struct CreateRoastEventRequest {
     pub notes: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BulkCreateRoastEventsRequest {
+    pub events: Vec<CreateRoastEventRequest>,
+}
+
+/// `?type=first_crack_start` for marking an event at the current moment.
+#[derive(Debug, Deserialize)]
+pub struct TapRoastEventQuery {
+    #[serde(rename = "type")]
+    pub event_type: RoastEventType,
+    pub notes: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateRoastEventRequest {
     pub elapsed_seconds: Option<f32>,
@@ -321,34 +549,30 @@ pub struct UpdateRoastEventRequest {
     pub notes: Option<String>,
 }
 
-// ============================================================================
-// Device Configuration Models
-// ============================================================================
-
-/// Device status enum matching the `devices.status` column constraint.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// What a roast cue waits for before it fires.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
-pub enum DeviceStatus {
-    Pending,
-    Active,
-    Disabled,
-    Error,
+pub enum CueTrigger {
+    /// Seconds since the session started.
+    Elapsed,
+    /// Bean temperature (°C) reached.
+    Temperature,
 }
 
-impl Type<sqlx::Sqlite> for DeviceStatus {
+impl Type<sqlx::Sqlite> for CueTrigger {
     fn type_info() -> SqliteTypeInfo {
         <String as Type<sqlx::Sqlite>>::type_info()
     }
 }
 
-impl<'r> Decode<'r, sqlx::Sqlite> for DeviceStatus {
+impl<'r> Decode<'r, sqlx::Sqlite> for CueTrigger {
     fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
         let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
         s.parse().map_err(Into::into)
     }
 }
 
-impl<'q> Encode<'q, sqlx::Sqlite> for DeviceStatus {
+impl<'q> Encode<'q, sqlx::Sqlite> for CueTrigger {
     fn encode_by_ref(
         &self,
         buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
@@ -357,56 +581,102 @@ impl<'q> Encode<'q, sqlx::Sqlite> for DeviceStatus {
     }
 }
 
-impl std::fmt::Display for DeviceStatus {
+impl std::fmt::Display for CueTrigger {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            DeviceStatus::Pending => "pending",
-            DeviceStatus::Active => "active",
-            DeviceStatus::Disabled => "disabled",
-            DeviceStatus::Error => "error",
+            CueTrigger::Elapsed => "elapsed",
+            CueTrigger::Temperature => "temperature",
         };
         write!(f, "{}", s)
     }
 }
 
-impl std::str::FromStr for DeviceStatus {
+impl std::str::FromStr for CueTrigger {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "pending" => Ok(DeviceStatus::Pending),
-            "active" => Ok(DeviceStatus::Active),
-            "disabled" => Ok(DeviceStatus::Disabled),
-            "error" => Ok(DeviceStatus::Error),
-            _ => Err(format!("Invalid device status: {}", s)),
+            "elapsed" => Ok(CueTrigger::Elapsed),
+            "temperature" => Ok(CueTrigger::Temperature),
+            _ => Err(format!("Invalid cue trigger: {}", s)),
         }
     }
 }
 
-/// Connection protocol enum matching the `device_connections.protocol` column.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// A bench reminder attached to a profile or to a single session.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RoastCue {
+    pub id: String,
+    pub profile_id: Option<String>,
+    pub session_id: Option<String>,
+    pub message: String,
+    pub trigger_type: CueTrigger,
+    pub trigger_value: f64,
+    /// Also push a notification when the cue fires.
+    pub notify: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RoastCue {
+    /// Whether the cue should fire at this point of the roast.
+    pub fn is_due(&self, elapsed_seconds: f64, bean_temp: Option<f64>) -> bool {
+        match self.trigger_type {
+            CueTrigger::Elapsed => elapsed_seconds >= self.trigger_value,
+            CueTrigger::Temperature => bean_temp.is_some_and(|t| t >= self.trigger_value),
+        }
+    }
+}
+
+/// A cue as seen from one session, with when it fired (if it has).
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SessionCue {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub cue: RoastCue,
+    pub fired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCueRequest {
+    pub message: String,
+    pub trigger_type: CueTrigger,
+    pub trigger_value: f64,
+    #[serde(default)]
+    pub notify: bool,
+}
+
+// ============================================================================
+// Automation Rules
+// ============================================================================
+
+/// What an automation rule waits for before it runs.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
-pub enum Protocol {
-    Mqtt,
-    #[serde(rename = "websocket")]
-    WebSocket,
-    ModbusTcp,
+pub enum AutomationTrigger {
+    /// Bean temperature crosses `trigger_value` (°C) going up.
+    BeanTempRising,
+    /// Bean temperature crosses `trigger_value` (°C) going down.
+    BeanTempFalling,
+    /// Seconds since the session started.
+    Elapsed,
+    /// A roast event of type `trigger_event` is logged.
+    Event,
 }
 
-impl Type<sqlx::Sqlite> for Protocol {
+impl Type<sqlx::Sqlite> for AutomationTrigger {
     fn type_info() -> SqliteTypeInfo {
         <String as Type<sqlx::Sqlite>>::type_info()
     }
 }
 
-impl<'r> Decode<'r, sqlx::Sqlite> for Protocol {
+impl<'r> Decode<'r, sqlx::Sqlite> for AutomationTrigger {
     fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
         let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
         s.parse().map_err(Into::into)
     }
 }
 
-impl<'q> Encode<'q, sqlx::Sqlite> for Protocol {
+impl<'q> Encode<'q, sqlx::Sqlite> for AutomationTrigger {
     fn encode_by_ref(
         &self,
         buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
@@ -415,54 +685,75 @@ impl<'q> Encode<'q, sqlx::Sqlite> for Protocol {
     }
 }
 
-impl std::fmt::Display for Protocol {
+impl std::fmt::Display for AutomationTrigger {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            Protocol::Mqtt => "mqtt",
-            Protocol::WebSocket => "websocket",
-            Protocol::ModbusTcp => "modbus_tcp",
+            AutomationTrigger::BeanTempRising => "bean_temp_rising",
+            AutomationTrigger::BeanTempFalling => "bean_temp_falling",
+            AutomationTrigger::Elapsed => "elapsed",
+            AutomationTrigger::Event => "event",
         };
         write!(f, "{}", s)
     }
 }
 
-impl std::str::FromStr for Protocol {
+impl std::str::FromStr for AutomationTrigger {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "mqtt" => Ok(Protocol::Mqtt),
-            "websocket" => Ok(Protocol::WebSocket),
-            "modbus_tcp" => Ok(Protocol::ModbusTcp),
-            _ => Err(format!("Invalid protocol: {}", s)),
+            "bean_temp_rising" => Ok(AutomationTrigger::BeanTempRising),
+            "bean_temp_falling" => Ok(AutomationTrigger::BeanTempFalling),
+            "elapsed" => Ok(AutomationTrigger::Elapsed),
+            "event" => Ok(AutomationTrigger::Event),
+            _ => Err(format!("Invalid automation trigger: {}", s)),
         }
     }
 }
 
-/// Modbus register type enum.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Control command an automation rule sends.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
-pub enum ModbusRegisterType {
-    Input,
-    Holding,
-    Coil,
-    Discrete,
+pub enum AutomationCommand {
+    FanPwm,
+    HeaterPwm,
+    Setpoint,
 }
 
-impl Type<sqlx::Sqlite> for ModbusRegisterType {
+impl AutomationCommand {
+    /// Telemetry field holding the current value, for relative rules.
+    pub fn telemetry_key(self) -> &'static str {
+        match self {
+            AutomationCommand::FanPwm => "fanPWM",
+            AutomationCommand::HeaterPwm => "heaterPWM",
+            AutomationCommand::Setpoint => "setpoint",
+        }
+    }
+
+    /// Range accepted by the matching control endpoint.
+    pub fn range(self) -> (f64, f64) {
+        match self {
+            AutomationCommand::FanPwm => (0.0, 255.0),
+            AutomationCommand::HeaterPwm => (0.0, 100.0),
+            AutomationCommand::Setpoint => (0.0, 300.0),
+        }
+    }
+}
+
+impl Type<sqlx::Sqlite> for AutomationCommand {
     fn type_info() -> SqliteTypeInfo {
         <String as Type<sqlx::Sqlite>>::type_info()
     }
 }
 
-impl<'r> Decode<'r, sqlx::Sqlite> for ModbusRegisterType {
+impl<'r> Decode<'r, sqlx::Sqlite> for AutomationCommand {
     fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
         let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
         s.parse().map_err(Into::into)
     }
 }
 
-impl<'q> Encode<'q, sqlx::Sqlite> for ModbusRegisterType {
+impl<'q> Encode<'q, sqlx::Sqlite> for AutomationCommand {
     fn encode_by_ref(
         &self,
         buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
@@ -471,58 +762,243 @@ impl<'q> Encode<'q, sqlx::Sqlite> for ModbusRegisterType {
     }
 }
 
-impl std::fmt::Display for ModbusRegisterType {
+impl std::fmt::Display for AutomationCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            ModbusRegisterType::Input => "input",
-            ModbusRegisterType::Holding => "holding",
-            ModbusRegisterType::Coil => "coil",
-            ModbusRegisterType::Discrete => "discrete",
+            AutomationCommand::FanPwm => "fan_pwm",
+            AutomationCommand::HeaterPwm => "heater_pwm",
+            AutomationCommand::Setpoint => "setpoint",
         };
         write!(f, "{}", s)
     }
 }
 
-impl std::str::FromStr for ModbusRegisterType {
+impl std::str::FromStr for AutomationCommand {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "input" => Ok(ModbusRegisterType::Input),
-            "holding" => Ok(ModbusRegisterType::Holding),
-            "coil" => Ok(ModbusRegisterType::Coil),
-            "discrete" => Ok(ModbusRegisterType::Discrete),
-            _ => Err(format!("Invalid modbus register type: {}", s)),
+            "fan_pwm" => Ok(AutomationCommand::FanPwm),
+            "heater_pwm" => Ok(AutomationCommand::HeaterPwm),
+            "setpoint" => Ok(AutomationCommand::Setpoint),
+            _ => Err(format!("Invalid automation command: {}", s)),
         }
     }
 }
 
-/// Modbus data type enum.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// A rule attached to a profile (every session using it) or to a roaster
+/// (every session on it). Runs at most once per session.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AutomationRule {
+    pub id: String,
+    pub profile_id: Option<String>,
+    pub device_id: Option<String>,
+    pub name: String,
+    pub trigger_type: AutomationTrigger,
+    pub trigger_value: Option<f64>,
+    pub trigger_event: Option<RoastEventType>,
+    pub command: AutomationCommand,
+    pub value: f64,
+    /// Add `value` to the current reading instead of setting it.
+    pub relative: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AutomationRule {
+    /// Whether the rule should run at this sample. `previous_bean_temp` is
+    /// the session's prior sample, so crossings need two readings.
+    pub fn is_due(
+        &self,
+        elapsed_seconds: f64,
+        previous_bean_temp: Option<f64>,
+        bean_temp: Option<f64>,
+        events: &[RoastEventType],
+    ) -> bool {
+        let threshold = self.trigger_value.unwrap_or(f64::NAN);
+        match self.trigger_type {
+            AutomationTrigger::BeanTempRising => previous_bean_temp
+                .zip(bean_temp)
+                .is_some_and(|(prev, t)| prev < threshold && t >= threshold),
+            AutomationTrigger::BeanTempFalling => previous_bean_temp
+                .zip(bean_temp)
+                .is_some_and(|(prev, t)| prev > threshold && t <= threshold),
+            AutomationTrigger::Elapsed => elapsed_seconds >= threshold,
+            AutomationTrigger::Event => self
+                .trigger_event
+                .as_ref()
+                .is_some_and(|e| events.contains(e)),
+        }
+    }
+
+    /// Value to send given the current telemetry, clamped to the command's
+    /// range. `None` when a relative rule has no current reading.
+    pub fn target_value(&self, telemetry: &serde_json::Value) -> Option<f64> {
+        let base = if self.relative {
+            telemetry.get(self.command.telemetry_key())?.as_f64()?
+        } else {
+            0.0
+        };
+        let (min, max) = self.command.range();
+        Some((base + self.value).clamp(min, max))
+    }
+}
+
+/// A rule as seen from one session, with when it ran (if it has).
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SessionAutomationRule {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub rule: AutomationRule,
+    pub executed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAutomationRequest {
+    pub name: String,
+    pub trigger_type: AutomationTrigger,
+    pub trigger_value: Option<f64>,
+    pub trigger_event: Option<RoastEventType>,
+    pub command: AutomationCommand,
+    pub value: f64,
+    #[serde(default)]
+    pub relative: bool,
+}
+
+/// One run of a rule during a session.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AutomationExecution {
+    pub id: String,
+    pub rule_id: String,
+    pub session_id: String,
+    pub device_id: String,
+    pub elapsed_seconds: f64,
+    pub bean_temp: Option<f64>,
+    pub command: AutomationCommand,
+    /// Payload sent to the roaster, absent when nothing could be sent.
+    pub sent_value: Option<String>,
+    /// `None` while the command is still being sent.
+    pub success: Option<bool>,
+    pub error: Option<String>,
+    pub executed_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// Batch Size Scaling
+// ============================================================================
+
+/// How a roaster's charge temp and heater cap follow the batch size.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct BatchScalingRule {
+    pub device_id: String,
+    /// Charge temperature change per 100 g more green coffee (°C).
+    pub charge_temp_per_100g: f32,
+    /// Heater cap change per 100 g more green coffee (percent points).
+    pub heater_cap_per_100g: f32,
+    pub min_heater_cap: i32,
+    pub max_heater_cap: i32,
+    pub max_charge_temp: Option<f32>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertBatchScalingRuleRequest {
+    pub charge_temp_per_100g: f32,
+    pub heater_cap_per_100g: f32,
+    #[serde(default)]
+    pub min_heater_cap: Option<i32>,
+    #[serde(default)]
+    pub max_heater_cap: Option<i32>,
+    #[serde(default)]
+    pub max_charge_temp: Option<f32>,
+}
+
+/// `?device_id=&batch_size_g=` for a suggestion, or the body that also saves
+/// it as a new profile version.
+#[derive(Debug, Deserialize)]
+pub struct ScaleProfileRequest {
+    pub device_id: String,
+    pub batch_size_g: f32,
+    /// Batch size the profile was tuned for, when the profile doesn't say.
+    #[serde(default)]
+    pub reference_batch_g: Option<f32>,
+    /// Name of the saved version (defaults to "<profile> (<batch> g)").
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BatchScalingSuggestion {
+    pub profile_id: String,
+    pub device_id: String,
+    pub reference_batch_g: f32,
+    pub batch_size_g: f32,
+    /// Absent when the profile has no charge temp to scale.
+    pub charge_temp: Option<f32>,
+    pub heater_cap: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScaledProfile {
+    pub suggestion: BatchScalingSuggestion,
+    pub profile: ProfileWithPoints,
+}
+
+impl BatchScalingRule {
+    /// Charge temp and heater cap for running `profile` with
+    /// `batch_size_g` instead of `reference_batch_g`. Without a heater cap on
+    /// the profile, scaling starts from `max_heater_cap`.
+    pub fn suggest(
+        &self,
+        profile: &RoastProfile,
+        reference_batch_g: f32,
+        batch_size_g: f32,
+    ) -> BatchScalingSuggestion {
+        let hundreds = (batch_size_g - reference_batch_g) / 100.0;
+        let charge_temp = profile.charge_temp.map(|t| {
+            let scaled = t + self.charge_temp_per_100g * hundreds;
+            self.max_charge_temp.map_or(scaled, |max| scaled.min(max))
+        });
+        let base_cap = profile.heater_cap.unwrap_or(self.max_heater_cap) as f32;
+        let heater_cap = (base_cap + self.heater_cap_per_100g * hundreds).round() as i32;
+        BatchScalingSuggestion {
+            profile_id: profile.id.clone(),
+            device_id: self.device_id.clone(),
+            reference_batch_g,
+            batch_size_g,
+            charge_temp,
+            heater_cap: heater_cap.clamp(self.min_heater_cap, self.max_heater_cap),
+        }
+    }
+}
+
+// ============================================================================
+// Charge corrections
+// ============================================================================
+
+/// Measured green bean property a correction table is keyed on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-pub enum ModbusDataType {
-    Uint16,
-    Int16,
-    Float32,
-    Uint32,
-    Int32,
-    Bool,
+pub enum CorrectionFactor {
+    /// `moisture_pct`
+    Moisture,
+    /// g/L
+    Density,
 }
 
-impl Type<sqlx::Sqlite> for ModbusDataType {
+impl Type<sqlx::Sqlite> for CorrectionFactor {
     fn type_info() -> SqliteTypeInfo {
         <String as Type<sqlx::Sqlite>>::type_info()
     }
 }
 
-impl<'r> Decode<'r, sqlx::Sqlite> for ModbusDataType {
+impl<'r> Decode<'r, sqlx::Sqlite> for CorrectionFactor {
     fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
         let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
         s.parse().map_err(Into::into)
     }
 }
 
-impl<'q> Encode<'q, sqlx::Sqlite> for ModbusDataType {
+impl<'q> Encode<'q, sqlx::Sqlite> for CorrectionFactor {
     fn encode_by_ref(
         &self,
         buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
@@ -531,272 +1007,3555 @@ impl<'q> Encode<'q, sqlx::Sqlite> for ModbusDataType {
     }
 }
 
-impl std::fmt::Display for ModbusDataType {
+impl std::fmt::Display for CorrectionFactor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            ModbusDataType::Uint16 => "uint16",
-            ModbusDataType::Int16 => "int16",
-            ModbusDataType::Float32 => "float32",
-            ModbusDataType::Uint32 => "uint32",
-            ModbusDataType::Int32 => "int32",
-            ModbusDataType::Bool => "bool",
+            CorrectionFactor::Moisture => "moisture",
+            CorrectionFactor::Density => "density",
         };
         write!(f, "{}", s)
     }
 }
 
-impl std::str::FromStr for ModbusDataType {
+impl std::str::FromStr for CorrectionFactor {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "uint16" => Ok(ModbusDataType::Uint16),
-            "int16" => Ok(ModbusDataType::Int16),
-            "float32" => Ok(ModbusDataType::Float32),
-            "uint32" => Ok(ModbusDataType::Uint32),
-            "int32" => Ok(ModbusDataType::Int32),
-            "bool" => Ok(ModbusDataType::Bool),
-            _ => Err(format!("Invalid modbus data type: {}", s)),
+            "moisture" => Ok(CorrectionFactor::Moisture),
+            "density" => Ok(CorrectionFactor::Density),
+            _ => Err(format!("Invalid correction factor: {}", s)),
         }
     }
 }
 
-// ---- Database row structs ----
+/// One band of a correction table, for beans with
+/// `min_value <= value < max_value` (open at an absent end).
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, PartialEq)]
+pub struct ChargeCorrection {
+    pub factor: CorrectionFactor,
+    #[serde(default)]
+    pub min_value: Option<f32>,
+    #[serde(default)]
+    pub max_value: Option<f32>,
+    /// Added to the profile's charge temp (°C).
+    #[serde(default)]
+    pub charge_temp_delta: f32,
+    /// Added to the profile's heater cap (percent points).
+    #[serde(default)]
+    pub heater_cap_delta: f32,
+}
+
+impl ChargeCorrection {
+    pub fn contains(&self, value: f32) -> bool {
+        self.min_value.is_none_or(|min| value >= min)
+            && self.max_value.is_none_or(|max| value < max)
+    }
+}
+
+/// Body of `PUT /api/charge-corrections`, replacing every table.
+#[derive(Debug, Deserialize)]
+pub struct ReplaceChargeCorrectionsRequest {
+    pub corrections: Vec<ChargeCorrection>,
+}
+
+/// Bean measurements for a charge adjustment: given directly, or taken from
+/// the green bean lot for whichever of them is not given.
+#[derive(Debug, Default, Deserialize)]
+pub struct ChargeAdjustmentRequest {
+    #[serde(default)]
+    pub bean_id: Option<String>,
+    #[serde(default)]
+    pub moisture_pct: Option<f32>,
+    #[serde(default)]
+    pub density: Option<f32>,
+    /// Profile to adjust, for a session without one.
+    #[serde(default)]
+    pub profile_id: Option<String>,
+}
+
+/// A profile's charge temp and heater cap corrected for the bean.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChargeAdjustment {
+    pub profile_id: String,
+    pub moisture_pct: Option<f32>,
+    pub density: Option<f32>,
+    pub base_charge_temp: Option<f32>,
+    pub base_heater_cap: Option<i32>,
+    /// Absent when the profile has no charge temp to adjust.
+    pub charge_temp: Option<f32>,
+    /// Absent when the profile has no heater cap to adjust.
+    pub heater_cap: Option<i32>,
+    pub charge_temp_delta: f32,
+    pub heater_cap_delta: f32,
+    /// The band the bean fell into in each table.
+    pub applied: Vec<ChargeCorrection>,
+}
+
+/// The adjustment a session was roasted with.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SessionChargeAdjustment {
+    pub session_id: String,
+    #[sqlx(json)]
+    pub adjustment: ChargeAdjustment,
+    pub created_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// Profile tolerance bands
+// ============================================================================
+
+/// What happens when a roast stays outside its profile's tolerance band.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToleranceAction {
+    /// Raise an alert and record the breach only.
+    #[default]
+    Alert,
+    /// Also pause the session.
+    Pause,
+    /// Also send the profile's `safe_setpoint`.
+    SafeSetpoint,
+}
+
+impl Type<sqlx::Sqlite> for ToleranceAction {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for ToleranceAction {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for ToleranceAction {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for ToleranceAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ToleranceAction::Alert => "alert",
+            ToleranceAction::Pause => "pause",
+            ToleranceAction::SafeSetpoint => "safe_setpoint",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for ToleranceAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alert" => Ok(ToleranceAction::Alert),
+            "pause" => Ok(ToleranceAction::Pause),
+            "safe_setpoint" => Ok(ToleranceAction::SafeSetpoint),
+            _ => Err(format!("Invalid tolerance action: {}", s)),
+        }
+    }
+}
+
+/// How far a roast may stray from its profile's curve before it is off
+/// profile.
+#[derive(Debug, Clone, Serialize, FromRow, PartialEq)]
+pub struct ProfileTolerance {
+    pub profile_id: String,
+    /// Largest bean temp deviation (°C) from the profile target, either way.
+    pub max_deviation: f32,
+    /// How long the bean temp may stay outside the band before it is a
+    /// breach (seconds).
+    pub max_outside_secs: f32,
+    pub action: ToleranceAction,
+    /// Setpoint (°C) sent on a breach with the `safe_setpoint` action.
+    pub safe_setpoint: Option<f32>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetProfileToleranceRequest {
+    pub max_deviation: f32,
+    pub max_outside_secs: f32,
+    #[serde(default)]
+    pub action: ToleranceAction,
+    #[serde(default)]
+    pub safe_setpoint: Option<f32>,
+}
+
+// ============================================================================
+// Probe faults
+// ============================================================================
+
+/// How a temperature probe looks broken.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeIssueKind {
+    /// The reading hasn't changed at all for a while, as a detached or
+    /// shorted thermocouple reads.
+    Flatline,
+    /// Two probes are further apart than they can physically be.
+    Divergence,
+}
+
+impl Type<sqlx::Sqlite> for ProbeIssueKind {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for ProbeIssueKind {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for ProbeIssueKind {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for ProbeIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ProbeIssueKind::Flatline => "flatline",
+            ProbeIssueKind::Divergence => "divergence",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for ProbeIssueKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flatline" => Ok(ProbeIssueKind::Flatline),
+            "divergence" => Ok(ProbeIssueKind::Divergence),
+            _ => Err(format!("Invalid probe issue kind: {}", s)),
+        }
+    }
+}
+
+/// A probe fault as currently seen on a device.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProbeIssue {
+    pub kind: ProbeIssueKind,
+    /// Telemetry keys of the probes involved, e.g. `beanTemp`.
+    pub probes: Vec<String>,
+    /// The stuck reading, or how far apart the probes are (°C).
+    pub value: f64,
+    /// How long the fault has lasted.
+    pub duration_secs: f64,
+}
+
+/// A probe fault recorded on a session when it was first seen.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SessionProbeIssue {
+    pub id: String,
+    pub session_id: String,
+    pub device_id: String,
+    pub kind: ProbeIssueKind,
+    /// Comma-separated telemetry keys.
+    pub probes: String,
+    pub value: f64,
+    pub elapsed_seconds: Option<f64>,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Whether a session's temperature data can be trusted.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionDataQuality {
+    pub session_id: String,
+    /// No probe faults were seen during the session.
+    pub ok: bool,
+    pub issues: Vec<SessionProbeIssue>,
+}
+
+// ============================================================================
+// Pre-roast checklists
+// ============================================================================
+
+/// Items to check on a roaster before each session on it starts.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PreroastChecklist {
+    pub device_id: String,
+    pub items: sqlx::types::Json<Vec<String>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertPreroastChecklistRequest {
+    pub items: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckChecklistItemRequest {
+    pub item: String,
+    /// Defaults to the authenticated caller.
+    pub checked_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChecklistItemStatus {
+    pub item: String,
+    pub checked_by: Option<String>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// A session's pre-roast checklist. Until the session starts the items are
+/// the roaster's current checklist; after, the ones it started with.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionChecklist {
+    pub session_id: String,
+    pub items: Vec<ChecklistItemStatus>,
+    /// Every item is checked.
+    pub complete: bool,
+    pub overridden_by: Option<String>,
+    pub override_reason: Option<String>,
+    /// When the session started with it.
+    pub recorded_at: Option<DateTime<Utc>>,
+}
+
+impl SessionChecklist {
+    pub fn unchecked(&self) -> Vec<&str> {
+        self.items
+            .iter()
+            .filter(|i| i.checked_at.is_none())
+            .map(|i| i.item.as_str())
+            .collect()
+    }
+}
+
+/// A voice memo or photo taken during a session, without its bytes.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SessionAttachment {
+    pub id: String,
+    pub session_id: String,
+    /// `audio` or `image`.
+    pub kind: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    /// Seconds since the session started when it arrived.
+    pub elapsed_seconds: f64,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// Batch preheat
+// ============================================================================
+
+/// Between-batches preheat settings of a roaster.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct BatchPreheatSettings {
+    pub device_id: String,
+    pub enabled: bool,
+    /// How long to heat and hold before giving up and turning the heater off.
+    pub timeout_secs: i64,
+    /// Upper bound for the preheat target; exceeding it aborts the preheat.
+    pub max_temp: f64,
+    /// How close to the target (°C) the bean temp must be to be ready.
+    pub ready_band: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertBatchPreheatRequest {
+    pub enabled: bool,
+    pub timeout_secs: Option<i64>,
+    pub max_temp: Option<f64>,
+    pub ready_band: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreheatState {
+    /// Driving towards the charge temp.
+    Heating,
+    /// At the charge temp and holding, waiting for the next charge.
+    Ready,
+    /// The next session started.
+    Charged,
+    TimedOut,
+    /// Stopped for safety: over `max_temp` or a device error.
+    Aborted,
+    Cancelled,
+}
+
+impl PreheatState {
+    pub fn is_running(self) -> bool {
+        matches!(self, PreheatState::Heating | PreheatState::Ready)
+    }
+}
+
+/// A roaster's latest preheat, kept in memory.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreheatRun {
+    pub device_id: String,
+    /// The completed session that started the preheat.
+    pub session_id: String,
+    pub profile_id: String,
+    pub target_temp: f64,
+    pub max_temp: f64,
+    pub ready_band: f64,
+    pub state: PreheatState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+    pub ready_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchPreheatStatus {
+    pub settings: Option<BatchPreheatSettings>,
+    pub run: Option<PreheatRun>,
+}
+
+// ============================================================================
+// Roast queue
+// ============================================================================
+
+/// Column of a planned batch on the production board, in the order batches
+/// move through them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueState {
+    Queued,
+    /// Its roaster is preheating for it; only the roaster's next batch.
+    Preheating,
+    Roasting,
+    /// Dropped into the cooling tray.
+    Cooling,
+    Done,
+}
+
+impl QueueState {
+    /// The state to move to when the session data says `target`. Batches
+    /// only move forward, except back to queued when a preheat stops.
+    pub fn advance(self, target: QueueState) -> Option<QueueState> {
+        let back_to_queue = self == QueueState::Preheating && target == QueueState::Queued;
+        (target > self || back_to_queue).then_some(target)
+    }
+}
+
+impl Type<sqlx::Sqlite> for QueueState {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for QueueState {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for QueueState {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for QueueState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            QueueState::Queued => "queued",
+            QueueState::Preheating => "preheating",
+            QueueState::Roasting => "roasting",
+            QueueState::Cooling => "cooling",
+            QueueState::Done => "done",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for QueueState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(QueueState::Queued),
+            "preheating" => Ok(QueueState::Preheating),
+            "roasting" => Ok(QueueState::Roasting),
+            "cooling" => Ok(QueueState::Cooling),
+            "done" => Ok(QueueState::Done),
+            _ => Err(format!("Invalid queue state: {}", s)),
+        }
+    }
+}
+
+/// A planned batch on the production board.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct QueueEntry {
+    pub session_id: String,
+    pub session_name: String,
+    pub device_id: String,
+    pub profile_id: Option<String>,
+    pub position: i64,
+    pub state: QueueState,
+    pub state_changed_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnqueueBatchRequest {
+    /// A session still in planning.
+    pub session_id: String,
+}
+
+/// `GET /api/queue`: open batches plus those done in the last `done_hours`
+/// (default 12).
+#[derive(Debug, Default, Deserialize)]
+pub struct QueueQuery {
+    pub done_hours: Option<i64>,
+}
+
+// ============================================================================
+// Alerts
+// ============================================================================
+
+/// What raised an alert.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    DeviceConflict,
+    OverTemperature,
+    AutomationFailed,
+    PreheatFailed,
+    DeviceHealth,
+    ToleranceBreach,
+    ProbeFault,
+}
+
+impl Type<sqlx::Sqlite> for AlertKind {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for AlertKind {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for AlertKind {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AlertKind::DeviceConflict => "device_conflict",
+            AlertKind::OverTemperature => "over_temperature",
+            AlertKind::AutomationFailed => "automation_failed",
+            AlertKind::PreheatFailed => "preheat_failed",
+            AlertKind::DeviceHealth => "device_health",
+            AlertKind::ToleranceBreach => "tolerance_breach",
+            AlertKind::ProbeFault => "probe_fault",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for AlertKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "device_conflict" => Ok(AlertKind::DeviceConflict),
+            "over_temperature" => Ok(AlertKind::OverTemperature),
+            "automation_failed" => Ok(AlertKind::AutomationFailed),
+            "preheat_failed" => Ok(AlertKind::PreheatFailed),
+            "device_health" => Ok(AlertKind::DeviceHealth),
+            "tolerance_breach" => Ok(AlertKind::ToleranceBreach),
+            "probe_fault" => Ok(AlertKind::ProbeFault),
+            _ => Err(format!("Invalid alert kind: {}", s)),
+        }
+    }
+}
+
+/// How urgently an alert needs attention.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertSeverity {
+    Warning,
+    Critical,
+}
+
+impl Type<sqlx::Sqlite> for AlertSeverity {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for AlertSeverity {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for AlertSeverity {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for AlertSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AlertSeverity::Warning => "warning",
+            AlertSeverity::Critical => "critical",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for AlertSeverity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warning" => Ok(AlertSeverity::Warning),
+            "critical" => Ok(AlertSeverity::Critical),
+            _ => Err(format!("Invalid alert severity: {}", s)),
+        }
+    }
+}
+
+/// Firing until someone acknowledges it, resolved when the condition
+/// clears or by hand.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Acknowledged,
+    Resolved,
+}
+
+impl Type<sqlx::Sqlite> for AlertState {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for AlertState {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for AlertState {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for AlertState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AlertState::Firing => "firing",
+            AlertState::Acknowledged => "acknowledged",
+            AlertState::Resolved => "resolved",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for AlertState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "firing" => Ok(AlertState::Firing),
+            "acknowledged" => Ok(AlertState::Acknowledged),
+            "resolved" => Ok(AlertState::Resolved),
+            _ => Err(format!("Invalid alert state: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Alert {
+    pub id: String,
+    pub kind: AlertKind,
+    pub severity: AlertSeverity,
+    pub device_id: Option<String>,
+    pub session_id: Option<String>,
+    pub message: String,
+    pub details: Option<sqlx::types::Json<serde_json::Value>>,
+    pub state: AlertState,
+    pub fired_at: DateTime<Utc>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    /// `None` on a resolved alert means the condition cleared by itself.
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// Filters for `GET /api/alerts`. Times bound `fired_at`.
+#[derive(Debug, Default, Deserialize)]
+pub struct AlertListQuery {
+    pub state: Option<AlertState>,
+    pub kind: Option<AlertKind>,
+    pub device_id: Option<String>,
+    pub session_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+/// Body of acknowledge/resolve. `by` defaults to the signed-in caller.
+#[derive(Debug, Default, Deserialize)]
+pub struct AlertActionRequest {
+    pub by: Option<String>,
+}
+
+/// A roaster's own over-temperature limit, in place of
+/// `RUSTROAST_ALERT_MAX_BEAN_TEMP`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AlertLimit {
+    pub device_id: String,
+    pub max_bean_temp: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertAlertLimitRequest {
+    pub max_bean_temp: f64,
+}
+
+// ============================================================================
+// Autotune results
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PidGains {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+}
+
+/// Gains a classic tuning rule derives from the run's `ku` and `tu`.
+#[derive(Debug, Clone, Serialize)]
+pub struct GainCandidate {
+    pub method: &'static str,
+    #[serde(flatten)]
+    pub gains: PidGains,
+}
+
+/// An autotune results payload as published by the firmware.
+#[derive(Debug, Clone, Serialize)]
+pub struct AutotuneResults {
+    /// `tuning_method` of the run, e.g. `zn_classic`.
+    pub method: Option<String>,
+    /// The firmware's own rating: good, acceptable, fallback or poor.
+    pub quality: Option<String>,
+    /// Ultimate gain (`ultimate_gain`).
+    pub ku: Option<f64>,
+    /// Ultimate period (`oscillation_period`).
+    pub tu: Option<f64>,
+    /// What `apply` makes the firmware use.
+    pub recommended: PidGains,
+    /// The gains in use before the run.
+    pub original: Option<PidGains>,
+    pub candidates: Vec<GainCandidate>,
+}
+
+/// A roaster's own bounds on autotuned gains.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AutotuneGainLimits {
+    pub device_id: String,
+    pub max_kp: f64,
+    pub max_ki: f64,
+    pub max_kd: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertAutotuneGainLimitsRequest {
+    pub max_kp: f64,
+    pub max_ki: f64,
+    pub max_kd: f64,
+}
+
+/// Whether a device's latest autotune results are safe to apply.
+#[derive(Debug, Clone, Serialize)]
+pub struct AutotuneCheck {
+    pub device_id: String,
+    /// `None` when there are no results, or they carry no gains.
+    pub results: Option<AutotuneResults>,
+    pub max_gains: PidGains,
+    /// `max_gains` come from the roaster's limits, not the defaults.
+    pub custom_limits: bool,
+    /// Why `apply` refuses the results; empty when they look sane.
+    pub problems: Vec<String>,
+}
+
+// ============================================================================
+// Config bundles
+// ============================================================================
+
+pub const CONFIG_BUNDLE_VERSION: u32 = 1;
+
+/// Automations, alert limits and preheat safety limits as one YAML or JSON
+/// document. A section that is present is the complete set: importing it
+/// adds, changes and removes entries to match. Absent sections are left
+/// alone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automations: Option<Vec<AutomationConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert_limits: Option<Vec<AlertLimitConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preheat: Option<Vec<PreheatConfig>>,
+}
+
+/// An automation rule without its id, identified by its profile or roaster
+/// and its name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AutomationConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    pub name: String,
+    pub trigger_type: AutomationTrigger,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_event: Option<RoastEventType>,
+    pub command: AutomationCommand,
+    pub value: f64,
+    #[serde(default)]
+    pub relative: bool,
+}
+
+impl AutomationConfig {
+    /// `profile:<id>/<name>` or `roaster:<device_id>/<name>`.
+    pub fn key(&self) -> String {
+        match (&self.profile_id, &self.device_id) {
+            (Some(profile_id), _) => format!("profile:{}/{}", profile_id, self.name),
+            (None, Some(device_id)) => format!("roaster:{}/{}", device_id, self.name),
+            (None, None) => self.name.clone(),
+        }
+    }
+
+    pub fn request(&self) -> CreateAutomationRequest {
+        CreateAutomationRequest {
+            name: self.name.clone(),
+            trigger_type: self.trigger_type,
+            trigger_value: self.trigger_value,
+            trigger_event: self.trigger_event.clone(),
+            command: self.command,
+            value: self.value,
+            relative: self.relative,
+        }
+    }
+}
+
+impl From<AutomationRule> for AutomationConfig {
+    fn from(rule: AutomationRule) -> Self {
+        Self {
+            profile_id: rule.profile_id,
+            device_id: rule.device_id,
+            name: rule.name,
+            trigger_type: rule.trigger_type,
+            trigger_value: rule.trigger_value,
+            trigger_event: rule.trigger_event,
+            command: rule.command,
+            value: rule.value,
+            relative: rule.relative,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertLimitConfig {
+    pub device_id: String,
+    pub max_bean_temp: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreheatConfig {
+    pub device_id: String,
+    pub enabled: bool,
+    pub timeout_secs: i64,
+    pub max_temp: f64,
+    pub ready_band: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Json,
+}
+
+/// `GET /api/config/export`, YAML unless `format=json`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfigExportQuery {
+    #[serde(default)]
+    pub format: ConfigFormat,
+}
+
+/// `?dry_run=true` of `POST /api/config/import` only reports the changes.
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfigImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigChangeAction {
+    Add,
+    Change,
+    Remove,
+}
+
+/// One entry an import adds, changes or removes, with its values before
+/// and after.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigChange {
+    pub section: String,
+    pub key: String,
+    pub action: ConfigChangeAction,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigImportReport {
+    pub dry_run: bool,
+    pub changes: Vec<ConfigChange>,
+}
+
+// ============================================================================
+// Device Logs
+// ============================================================================
+
+/// One firmware log line from `roaster/{device_id}/log`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DeviceLogLine {
+    pub device_id: String,
+    /// Unix seconds when the server received the line.
+    pub ts: i64,
+    pub level: Option<String>,
+    pub message: String,
+}
+
+/// A device flagged for troubleshooting, whose log lines are persisted.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct LogCapture {
+    pub device_id: String,
+    pub enabled_at: DateTime<Utc>,
+}
+
+/// `GET /api/roaster/:device_id/logs`: the newest `limit` buffered lines.
+#[derive(Debug, Default, Deserialize)]
+pub struct DeviceLogQuery {
+    pub limit: Option<usize>,
+}
+
+/// Filters for persisted lines. Times are unix seconds.
+#[derive(Debug, Default, Deserialize)]
+pub struct DeviceLogHistoryQuery {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+// ============================================================================
+// Device status history
+// ============================================================================
+
+/// A stored `roaster/{device_id}/status` payload.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct StatusSnapshot {
+    pub id: i64,
+    pub device_id: String,
+    /// Unix seconds when the server received the status.
+    pub ts: i64,
+    pub status: Option<String>,
+    pub ip: Option<String>,
+    pub version: Option<String>,
+    pub rssi: Option<i64>,
+    /// Status, chip id, IP or firmware version differ from the previous
+    /// snapshot; otherwise this is a once-a-minute sample.
+    pub changed: bool,
+    pub payload: sqlx::types::Json<serde_json::Value>,
+}
+
+/// `GET /api/roaster/:device_id/status/history`. Times are unix seconds;
+/// `limit` keeps the newest snapshots.
+#[derive(Debug, Default, Deserialize)]
+pub struct StatusHistoryQuery {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub changes_only: bool,
+}
+
+/// `GET /api/roaster/:device_id/state_at`. `ts` is unix seconds.
+#[derive(Debug, Deserialize)]
+pub struct DeviceStateQuery {
+    pub ts: i64,
+}
+
+/// The telemetry a device last reported before a point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateTelemetry {
+    pub ts: i64,
+    /// Seconds between this telemetry and the requested time.
+    pub age_secs: i64,
+    /// Set on per-minute rollups of compacted telemetry: how many raw
+    /// samples were averaged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub samples: Option<i64>,
+    pub values: serde_json::Value,
+}
+
+/// A device as it was at `ts`, pieced together from stored telemetry,
+/// status snapshots, control commands and sessions. Every part is the
+/// latest one at or before `ts`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceStateAt {
+    pub device_id: String,
+    pub ts: i64,
+    pub telemetry: Option<StateTelemetry>,
+    /// `auto` or `manual`, as reported in the telemetry.
+    pub mode: Option<String>,
+    /// Setpoint reported in the telemetry.
+    pub setpoint: Option<f64>,
+    /// The last command of each kind sent to the device, newest first.
+    pub last_commands: Vec<ControlAuditEntry>,
+    pub status: Option<StatusSnapshot>,
+    pub firmware_version: Option<String>,
+    /// The session running on the device at `ts`.
+    pub session: Option<RoastSession>,
+}
+
+// ============================================================================
+// Dashboard QR codes
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+/// `GET /api/roaster/:device_id/qr`. `scale` is pixels (or SVG units) per
+/// module; `token=true` embeds a read-only token valid for `token_ttl_days`.
+#[derive(Debug, Default, Deserialize)]
+pub struct DashboardQrQuery {
+    #[serde(default)]
+    pub format: QrFormat,
+    pub scale: Option<u32>,
+    #[serde(default)]
+    pub token: bool,
+    pub token_ttl_days: Option<u64>,
+}
+
+// ============================================================================
+// Device health
+// ============================================================================
+
+/// A minute of a device's health: its RSSI and free heap moving averages
+/// and the score they give.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, PartialEq)]
+pub struct DeviceHealthPoint {
+    /// Unix seconds, a whole minute.
+    pub ts: i64,
+    /// dBm.
+    pub rssi: Option<f64>,
+    /// Bytes.
+    pub free_heap: Option<f64>,
+    /// 0 (failing) to 100 (healthy).
+    pub score: f64,
+}
+
+/// A degradation trend or limit a device's health shows.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthIssue {
+    /// Free heap keeps falling, e.g. a memory leak.
+    HeapLeak,
+    LowHeap,
+    WeakSignal,
+    /// RSSI keeps falling, e.g. a failing antenna or connector.
+    FallingSignal,
+}
+
+impl std::fmt::Display for HealthIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            HealthIssue::HeapLeak => "free heap is steadily falling",
+            HealthIssue::LowHeap => "free heap is low",
+            HealthIssue::WeakSignal => "WiFi signal is weak",
+            HealthIssue::FallingSignal => "WiFi signal is steadily falling",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A device's current health with its trends over the last hour.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceHealthSnapshot {
+    pub device_id: String,
+    #[serde(flatten)]
+    pub point: DeviceHealthPoint,
+    /// Bytes per minute, once there is enough history.
+    pub heap_trend: Option<f64>,
+    /// dB per minute, once there is enough history.
+    pub rssi_trend: Option<f64>,
+    pub issues: Vec<HealthIssue>,
+}
+
+/// `GET /api/roaster/:device_id/health`: the current health and its chart.
+#[derive(Debug, Serialize)]
+pub struct DeviceHealthHistory {
+    pub current: Option<DeviceHealthSnapshot>,
+    pub points: Vec<DeviceHealthPoint>,
+}
+
+/// Chart range in unix seconds, the last 24 hours by default.
+#[derive(Debug, Default, Deserialize)]
+pub struct DeviceHealthQuery {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+// ============================================================================
+// Curve Smoothing
+// ============================================================================
+
+/// How rate of rise is derived from the bean temperatures in its window.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RorAlgorithm {
+    /// Temperature difference between the window's first and last sample.
+    #[default]
+    MovingAverage,
+    /// Least-squares slope with newer samples weighted higher.
+    WeightedMovingAverage,
+    /// Slope of a quadratic fit at the window's center.
+    SavitzkyGolay,
+}
+
+impl Type<sqlx::Sqlite> for RorAlgorithm {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for RorAlgorithm {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for RorAlgorithm {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for RorAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RorAlgorithm::MovingAverage => "moving_average",
+            RorAlgorithm::WeightedMovingAverage => "weighted_moving_average",
+            RorAlgorithm::SavitzkyGolay => "savitzky_golay",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for RorAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "moving_average" => Ok(RorAlgorithm::MovingAverage),
+            "weighted_moving_average" => Ok(RorAlgorithm::WeightedMovingAverage),
+            "savitzky_golay" => Ok(RorAlgorithm::SavitzkyGolay),
+            _ => Err(format!("Invalid RoR algorithm: {}", s)),
+        }
+    }
+}
+
+/// Unit RoR curves are reported in.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum RorUnit {
+    #[default]
+    #[serde(rename = "per_minute")]
+    PerMinute,
+    /// °C per 30 s, common on fast fluid-bed roasters.
+    #[serde(rename = "per_30s")]
+    Per30Secs,
+}
+
+impl RorUnit {
+    /// Multiplier from °C/min to this unit.
+    pub fn factor(&self) -> f32 {
+        match self {
+            RorUnit::PerMinute => 1.0,
+            RorUnit::Per30Secs => 0.5,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RorUnit::PerMinute => "°C/min",
+            RorUnit::Per30Secs => "°C/30s",
+        }
+    }
+}
+
+impl Type<sqlx::Sqlite> for RorUnit {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for RorUnit {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for RorUnit {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for RorUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RorUnit::PerMinute => "per_minute",
+            RorUnit::Per30Secs => "per_30s",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for RorUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "per_minute" => Ok(RorUnit::PerMinute),
+            "per_30s" => Ok(RorUnit::Per30Secs),
+            _ => Err(format!("Invalid RoR unit: {}", s)),
+        }
+    }
+}
+
+pub const DEFAULT_SMOOTHING_VIEW: &str = "live";
+
+/// Trailing windows (seconds) clients use to draw curves. A BT/ET window
+/// of 0 leaves that curve unsmoothed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, FromRow, PartialEq)]
+pub struct SmoothingConfig {
+    pub bt_window_secs: f32,
+    pub et_window_secs: f32,
+    pub ror_window_secs: f32,
+    pub ror_algorithm: RorAlgorithm,
+    /// Set per roaster with its RoR settings rather than stored in presets.
+    #[serde(default)]
+    #[sqlx(default)]
+    pub ror_unit: RorUnit,
+}
+
+impl Default for SmoothingConfig {
+    fn default() -> Self {
+        Self {
+            bt_window_secs: 0.0,
+            et_window_secs: 0.0,
+            ror_window_secs: 30.0,
+            ror_algorithm: RorAlgorithm::MovingAverage,
+            ror_unit: RorUnit::PerMinute,
+        }
+    }
+}
+
+impl SmoothingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("bt_window_secs", self.bt_window_secs),
+            ("et_window_secs", self.et_window_secs),
+        ] {
+            if !(0.0..=300.0).contains(&value) {
+                return Err(format!("{} must be between 0 and 300", name));
+            }
+        }
+        if !(1.0..=300.0).contains(&self.ror_window_secs) {
+            return Err("ror_window_secs must be between 1 and 300".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A user's smoothing for one view (e.g. `live`, `replay`, `compare`).
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SmoothingPreset {
+    pub subject: String,
+    pub view: String,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub config: SmoothingConfig,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Which view's smoothing to resolve. Without one, [`DEFAULT_SMOOTHING_VIEW`]
+/// is used. With a roaster, its RoR settings apply on top.
+#[derive(Debug, Default, Deserialize)]
+pub struct SmoothingQuery {
+    pub view: Option<String>,
+    pub device_id: Option<String>,
+}
+
+/// `GET /api/sessions/:id/telemetry/smoothed`: the caller's smoothing for
+/// `view` and the roaster's RoR settings, with any field overridden for
+/// comparing settings.
+#[derive(Debug, Default, Deserialize)]
+pub struct SmoothedTelemetryQuery {
+    pub view: Option<String>,
+    pub bt_window_secs: Option<f32>,
+    pub et_window_secs: Option<f32>,
+    pub ror_window_secs: Option<f32>,
+    pub ror_algorithm: Option<RorAlgorithm>,
+    pub ror_unit: Option<RorUnit>,
+}
+
+/// A telemetry sample after smoothing. RoR is in the config's `ror_unit`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SmoothedPoint {
+    pub elapsed_seconds: f32,
+    pub bean_temp: Option<f32>,
+    pub env_temp: Option<f32>,
+    pub rate_of_rise: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmoothedSessionTelemetry {
+    pub session_id: String,
+    pub smoothing: SmoothingConfig,
+    pub raw: Vec<SessionTelemetry>,
+    pub smoothed: Vec<SmoothedPoint>,
+}
+
+/// RoR window and unit of a roaster. They take precedence over the
+/// caller's smoothing for that roaster's curves, charts and scoreboard, as
+/// fluid-bed and drum roasters need very different windows to be readable.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RoasterRorSettings {
+    pub device_id: String,
+    pub window_secs: f32,
+    pub unit: RorUnit,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RoasterRorSettings {
+    pub fn apply(&self, config: SmoothingConfig) -> SmoothingConfig {
+        SmoothingConfig {
+            ror_window_secs: self.window_secs,
+            ror_unit: self.unit,
+            ..config
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertRorSettingsRequest {
+    pub window_secs: f32,
+    #[serde(default)]
+    pub unit: RorUnit,
+}
+
+// ============================================================================
+// Session charts
+// ============================================================================
+
+/// `GET /api/sessions/:id/chart`. Curves are smoothed with the caller's
+/// smoothing for `view` unless `raw`, then decimated to `max_points`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ChartQuery {
+    pub max_points: Option<usize>,
+    pub view: Option<String>,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+/// The y axis a series belongs on.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChartAxis {
+    Temperature,
+    RateOfRise,
+    /// Heater and fan outputs.
+    Output,
+    Airflow,
+}
+
+/// One channel, a y value per `x` of the chart.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChartSeries {
+    pub key: &'static str,
+    pub label: String,
+    /// `None` for airflow, which is in the fan calibration's unit.
+    pub unit: Option<&'static str>,
+    pub axis: ChartAxis,
+    pub y: Vec<Option<f32>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChartMarker {
+    pub event_type: RoastEventType,
+    pub label: String,
+    pub x: f32,
+    /// The event's temperature, else the bean temp at its time.
+    pub y: Option<f32>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoastPhase {
+    Drying,
+    Maillard,
+    Development,
+}
+
+/// A phase band between two elapsed times.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChartPhase {
+    pub phase: RoastPhase,
+    pub label: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// A session laid out for charting: a shared `x` array of elapsed seconds
+/// and a `y` array per channel, plus event markers and phase bands.
+#[derive(Debug, Serialize)]
+pub struct SessionChart {
+    pub session_id: String,
+    /// `None` for raw curves.
+    pub smoothing: Option<SmoothingConfig>,
+    /// Samples before decimation.
+    pub total_points: usize,
+    pub x: Vec<f32>,
+    pub series: Vec<ChartSeries>,
+    pub markers: Vec<ChartMarker>,
+    pub phases: Vec<ChartPhase>,
+    /// Where the `profile_target` series starts, for sessions with a
+    /// profile. See [`ProfileScoreboard::profile_zero`].
+    pub profile_zero: Option<f32>,
+}
+
+// ============================================================================
+// Session archives
+// ============================================================================
+
+/// A completed session exported to object storage, see `archive.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionArchive {
+    pub session_id: String,
+    /// Key prefix of the session's `session.json`, `telemetry.parquet`
+    /// and `report.md`.
+    pub object_prefix: String,
+    pub telemetry_points: i64,
+    pub archived_at: DateTime<Utc>,
+    /// When local telemetry was deleted, cleared again by a restore.
+    pub pruned_at: Option<DateTime<Utc>>,
+    pub restored_at: Option<DateTime<Utc>>,
+}
+
+/// `session.json` in an archive: everything needed to re-hydrate the
+/// session's events and telemetry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionArchiveBundle {
+    pub format_version: u32,
+    pub session: RoastSession,
+    pub events: Vec<RoastEvent>,
+    pub telemetry: Vec<SessionTelemetry>,
+}
+
+/// Archive completed sessions older than `older_than_days` now, defaulting
+/// to `RUSTROAST_ARCHIVE_AFTER_DAYS`.
+#[derive(Debug, Deserialize)]
+pub struct ArchiveSessionsQuery {
+    pub older_than_days: Option<i64>,
+}
+
+/// `manifest.json` of a `.rroastlog` file, see `roastlog.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoastlogManifest {
+    /// Always `rroastlog`.
+    pub format: String,
+    pub format_version: u32,
+    pub session_id: String,
+    pub exported_at: DateTime<Utc>,
+    pub exported_by: String,
+    /// Every other file in the zip.
+    pub files: Vec<RoastlogFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoastlogFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Result of importing a `.rroastlog` file.
+#[derive(Debug, Serialize)]
+pub struct RoastlogImport {
+    pub session: RoastSession,
+    pub events: usize,
+    pub telemetry_points: usize,
+    /// Paths of the attachments restored along with the session.
+    pub attachments: Vec<String>,
+    /// Links dropped because this instance has no such profile, bean lot
+    /// or sample group (`profile_id`, `bean_id`, `sample_group_id`).
+    pub unlinked: Vec<&'static str>,
+}
+
+// ============================================================================
+// Webhooks
+// ============================================================================
+
+/// Something a webhook can subscribe to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum WebhookEvent {
+    #[serde(rename = "session.started")]
+    SessionStarted,
+    #[serde(rename = "session.completed")]
+    SessionCompleted,
+    #[serde(rename = "roast_event.created")]
+    RoastEventCreated,
+    #[serde(rename = "alert.fired")]
+    AlertFired,
+    #[serde(rename = "alert.acknowledged")]
+    AlertAcknowledged,
+    #[serde(rename = "alert.resolved")]
+    AlertResolved,
+}
+
+impl WebhookEvent {
+    /// The event an alert state change is delivered as.
+    pub fn for_alert(state: AlertState) -> Self {
+        match state {
+            AlertState::Firing => WebhookEvent::AlertFired,
+            AlertState::Acknowledged => WebhookEvent::AlertAcknowledged,
+            AlertState::Resolved => WebhookEvent::AlertResolved,
+        }
+    }
+}
+
+impl Type<sqlx::Sqlite> for WebhookEvent {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for WebhookEvent {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for WebhookEvent {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            WebhookEvent::SessionStarted => "session.started",
+            WebhookEvent::SessionCompleted => "session.completed",
+            WebhookEvent::RoastEventCreated => "roast_event.created",
+            WebhookEvent::AlertFired => "alert.fired",
+            WebhookEvent::AlertAcknowledged => "alert.acknowledged",
+            WebhookEvent::AlertResolved => "alert.resolved",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "session.started" => Ok(WebhookEvent::SessionStarted),
+            "session.completed" => Ok(WebhookEvent::SessionCompleted),
+            "roast_event.created" => Ok(WebhookEvent::RoastEventCreated),
+            "alert.fired" => Ok(WebhookEvent::AlertFired),
+            "alert.acknowledged" => Ok(WebhookEvent::AlertAcknowledged),
+            "alert.resolved" => Ok(WebhookEvent::AlertResolved),
+            _ => Err(format!("Invalid webhook event: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl Type<sqlx::Sqlite> for WebhookDeliveryStatus {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for WebhookDeliveryStatus {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for WebhookDeliveryStatus {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for WebhookDeliveryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for WebhookDeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(WebhookDeliveryStatus::Pending),
+            "delivered" => Ok(WebhookDeliveryStatus::Delivered),
+            "failed" => Ok(WebhookDeliveryStatus::Failed),
+            _ => Err(format!("Invalid webhook delivery status: {}", s)),
+        }
+    }
+}
+
+/// An outbound webhook. The secret signs deliveries and is never returned.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Webhook {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Events delivered to this webhook, all of them when empty.
+    #[sqlx(json)]
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub name: String,
+    pub url: String,
+    pub secret: String,
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<WebhookEvent>>,
+    pub enabled: Option<bool>,
+}
+
+/// One event sent to one webhook, retried until delivered or out of attempts.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    #[sqlx(json)]
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i64,
+    /// HTTP status of the last attempt, if the receiver answered.
+    pub response_status: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookDeliveryQuery {
+    pub limit: Option<i64>,
+}
+
+/// A session lifecycle step or new roast event, broadcast by
+/// `RoastSessionService` for webhooks.
+#[derive(Debug, Clone)]
+pub enum SessionActivity {
+    Started(RoastSession),
+    Completed(RoastSession),
+    EventCreated(RoastEvent),
+}
+
+// ============================================================================
+// Export Integrity
+// ============================================================================
+
+/// Export formats that carry an integrity signature.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Artisan,
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::Artisan => write!(f, "artisan"),
+        }
+    }
+}
+
+/// Content hash and server signature of one session export.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportIntegrity {
+    pub session_id: String,
+    pub format: ExportFormat,
+    /// Hex SHA-256 of the export content.
+    pub sha256: String,
+    pub signed_at: DateTime<Utc>,
+    /// Identifies the signing key, so a rotated key is told apart from an
+    /// edited export.
+    pub key_id: String,
+    /// Hex HMAC-SHA256 over the session, format, hash and signing time.
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyExportRequest {
+    pub format: ExportFormat,
+    /// The export exactly as received.
+    pub content: String,
+    /// The `X-Rustroast-Integrity` header of a CSV export. Artisan exports
+    /// carry it in the document.
+    pub integrity: Option<ExportIntegrity>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportVerification {
+    pub valid: bool,
+    /// Why verification failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// SHA-256 of the content as received.
+    pub sha256: String,
+    pub integrity: ExportIntegrity,
+}
+
+// ============================================================================
+// Device Configuration Models
+// ============================================================================
+
+/// Device status enum matching the `devices.status` column constraint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceStatus {
+    Pending,
+    Active,
+    Disabled,
+    Error,
+}
+
+impl Type<sqlx::Sqlite> for DeviceStatus {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for DeviceStatus {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for DeviceStatus {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for DeviceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            DeviceStatus::Pending => "pending",
+            DeviceStatus::Active => "active",
+            DeviceStatus::Disabled => "disabled",
+            DeviceStatus::Error => "error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for DeviceStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeviceStatus::Pending),
+            "active" => Ok(DeviceStatus::Active),
+            "disabled" => Ok(DeviceStatus::Disabled),
+            "error" => Ok(DeviceStatus::Error),
+            _ => Err(format!("Invalid device status: {}", s)),
+        }
+    }
+}
+
+/// Connection protocol enum matching the `device_connections.protocol` column.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    Mqtt,
+    #[serde(rename = "websocket")]
+    WebSocket,
+    ModbusTcp,
+}
+
+impl Type<sqlx::Sqlite> for Protocol {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for Protocol {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for Protocol {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Protocol::Mqtt => "mqtt",
+            Protocol::WebSocket => "websocket",
+            Protocol::ModbusTcp => "modbus_tcp",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mqtt" => Ok(Protocol::Mqtt),
+            "websocket" => Ok(Protocol::WebSocket),
+            "modbus_tcp" => Ok(Protocol::ModbusTcp),
+            _ => Err(format!("Invalid protocol: {}", s)),
+        }
+    }
+}
+
+/// Modbus register type enum.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModbusRegisterType {
+    Input,
+    Holding,
+    Coil,
+    Discrete,
+}
+
+impl Type<sqlx::Sqlite> for ModbusRegisterType {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for ModbusRegisterType {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for ModbusRegisterType {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for ModbusRegisterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ModbusRegisterType::Input => "input",
+            ModbusRegisterType::Holding => "holding",
+            ModbusRegisterType::Coil => "coil",
+            ModbusRegisterType::Discrete => "discrete",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for ModbusRegisterType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "input" => Ok(ModbusRegisterType::Input),
+            "holding" => Ok(ModbusRegisterType::Holding),
+            "coil" => Ok(ModbusRegisterType::Coil),
+            "discrete" => Ok(ModbusRegisterType::Discrete),
+            _ => Err(format!("Invalid modbus register type: {}", s)),
+        }
+    }
+}
+
+/// Modbus data type enum.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModbusDataType {
+    Uint16,
+    Int16,
+    Float32,
+    Uint32,
+    Int32,
+    Bool,
+}
+
+impl Type<sqlx::Sqlite> for ModbusDataType {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for ModbusDataType {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for ModbusDataType {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for ModbusDataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ModbusDataType::Uint16 => "uint16",
+            ModbusDataType::Int16 => "int16",
+            ModbusDataType::Float32 => "float32",
+            ModbusDataType::Uint32 => "uint32",
+            ModbusDataType::Int32 => "int32",
+            ModbusDataType::Bool => "bool",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for ModbusDataType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uint16" => Ok(ModbusDataType::Uint16),
+            "int16" => Ok(ModbusDataType::Int16),
+            "float32" => Ok(ModbusDataType::Float32),
+            "uint32" => Ok(ModbusDataType::Uint32),
+            "int32" => Ok(ModbusDataType::Int32),
+            "bool" => Ok(ModbusDataType::Bool),
+            _ => Err(format!("Invalid modbus data type: {}", s)),
+        }
+    }
+}
+
+// ---- Database row structs ----
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    pub device_id: String,
+    pub profile_id: Option<String>,
+    pub status: DeviceStatus,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceChangeOp {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A device registry change, broadcast to `/ws/telemetry` clients that
+/// opted in to `devices` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceChange {
+    pub op: DeviceChangeOp,
+    #[serde(flatten)]
+    pub device: Device,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceProfile {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub default_control_mode: Option<String>,
+    pub default_setpoint: Option<f64>,
+    pub default_fan_pwm: Option<i32>,
+    pub default_kp: Option<f64>,
+    pub default_ki: Option<f64>,
+    pub default_kd: Option<f64>,
+    pub max_temp: Option<f64>,
+    pub min_fan_pwm: Option<i32>,
+    pub telemetry_interval_ms: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceConnection {
+    pub id: String,
+    pub device_id: String,
+    pub protocol: Protocol,
+    pub enabled: bool,
+    pub priority: i32,
+    #[sqlx(json)]
+    pub config: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ModbusRegisterMap {
+    pub id: String,
+    pub device_id: String,
+    pub register_type: ModbusRegisterType,
+    pub address: i32,
+    pub name: String,
+    pub data_type: ModbusDataType,
+    pub byte_order: Option<String>,
+    pub scale_factor: Option<f64>,
+    pub offset: Option<f64>,
+    pub unit: Option<String>,
+    pub description: Option<String>,
+    pub writable: bool,
+}
+
+// ---- API response structs ----
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceWithConnections {
+    #[serde(flatten)]
+    pub device: Device,
+    pub connections: Vec<DeviceConnection>,
+}
+
+// ---- API request structs ----
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDeviceRequest {
+    pub name: String,
+    pub device_id: String,
+    pub profile_id: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDeviceRequest {
+    pub name: Option<String>,
+    pub profile_id: Option<String>,
+    pub status: Option<DeviceStatus>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDeviceProfileRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub default_control_mode: Option<String>,
+    pub default_setpoint: Option<f64>,
+    pub default_fan_pwm: Option<i32>,
+    pub default_kp: Option<f64>,
+    pub default_ki: Option<f64>,
+    pub default_kd: Option<f64>,
+    pub max_temp: Option<f64>,
+    pub min_fan_pwm: Option<i32>,
+    pub telemetry_interval_ms: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)] // Used by device profile edit UI (DEV-013)
+pub struct UpdateDeviceProfileRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub default_control_mode: Option<String>,
+    pub default_setpoint: Option<f64>,
+    pub default_fan_pwm: Option<i32>,
+    pub default_kp: Option<f64>,
+    pub default_ki: Option<f64>,
+    pub default_kd: Option<f64>,
+    pub max_temp: Option<f64>,
+    pub min_fan_pwm: Option<i32>,
+    pub telemetry_interval_ms: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateConnectionRequest {
+    pub protocol: Protocol,
+    pub enabled: Option<bool>,
+    pub priority: Option<i32>,
+    pub config: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateConnectionRequest {
+    pub enabled: Option<bool>,
+    pub priority: Option<i32>,
+    pub config: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRegisterMapEntry {
+    pub register_type: ModbusRegisterType,
+    pub address: i32,
+    pub name: String,
+    pub data_type: ModbusDataType,
+    pub byte_order: Option<String>,
+    pub scale_factor: Option<f64>,
+    pub offset: Option<f64>,
+    pub unit: Option<String>,
+    pub description: Option<String>,
+    pub writable: Option<bool>,
+}
+
+// ---- Connection test request/response ----
+
+#[derive(Debug, Deserialize)]
+pub struct TestConnectionRequest {
+    pub protocol: Protocol,
+    pub config: serde_json::Value,
+    pub device_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestConnectionResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+// ---- Export formatting ----
+
+pub use rustroast_core::TemperatureUnit;
+
+/// How the time column is written in CSV exports.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportTimestamp {
+    /// Seconds since session start
+    #[default]
+    Seconds,
+    /// `m:ss` since session start
+    Mmss,
+    /// Absolute RFC 3339 timestamp
+    Iso8601,
+    /// Absolute Unix epoch seconds
+    Epoch,
+}
+
+/// Column naming conventions of common roasting tools.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportColumnPreset {
+    #[default]
+    Default,
+    Artisan,
+    Cropster,
+}
+
+/// CSV export options. Unset `units`/`decimals` fall back to the
+/// `export_temperature_unit` / `export_decimal_places` settings; with
+/// neither set, values are written unrounded in Celsius without a unit line.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CsvExportOptions {
+    pub units: Option<TemperatureUnit>,
+    pub decimals: Option<u8>,
+    #[serde(default)]
+    pub timestamp: ExportTimestamp,
+    #[serde(default)]
+    pub preset: ExportColumnPreset,
+}
+
+// ---- Fan airflow calibration ----
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FanCalibrationPoint {
+    pub id: String,
+    pub device_id: String,
+    pub fan_pwm: i32,
+    pub airflow: f64,
+    pub static_pressure: Option<f64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateFanCalibrationPoint {
+    pub fan_pwm: i32,
+    pub airflow: f64,
+    pub static_pressure: Option<f64>,
+}
+
+// ---- PID gain history ----
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PidHistoryEntry {
+    pub id: String,
+    pub device_id: String,
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub source: String,
+    pub source_device_id: Option<String>,
+    pub scale: Option<f64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordPidGains<'a> {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub source: &'a str,
+    pub source_device_id: Option<&'a str>,
+    pub scale: Option<f64>,
+}
+
+// ---- Green beans ----
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Bean {
+    pub id: String,
+    pub name: String,
+    pub origin: Option<String>,
+    pub variety: Option<String>,
+    /// e.g. washed, natural, honey
+    pub process: Option<String>,
+    /// g/L
+    pub density: Option<f32>,
+    pub moisture_pct: Option<f32>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Purchase price per green kg, in the cost currency.
+    pub cost_per_kg: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBeanRequest {
+    pub name: String,
+    pub origin: Option<String>,
+    pub variety: Option<String>,
+    pub process: Option<String>,
+    pub density: Option<f32>,
+    pub moisture_pct: Option<f32>,
+    pub notes: Option<String>,
+    pub cost_per_kg: Option<f64>,
+}
+
+/// Fields left out keep their value.
+#[derive(Debug, Deserialize)]
+pub struct UpdateBeanRequest {
+    pub name: Option<String>,
+    pub origin: Option<String>,
+    pub variety: Option<String>,
+    pub process: Option<String>,
+    pub density: Option<f32>,
+    pub moisture_pct: Option<f32>,
+    pub notes: Option<String>,
+    pub cost_per_kg: Option<f64>,
+}
+
+/// Check measured bean values are physically plausible and the price
+/// is not negative.
+pub fn validate_bean_values(
+    density: Option<f32>,
+    moisture_pct: Option<f32>,
+    cost_per_kg: Option<f64>,
+) -> Result<(), &'static str> {
+    if density.is_some_and(|d| !(100.0..=1500.0).contains(&d)) {
+        return Err("density must be between 100 and 1500 g/L");
+    }
+    if moisture_pct.is_some_and(|m| !(0.0..=100.0).contains(&m)) {
+        return Err("moisture_pct must be between 0 and 100");
+    }
+    if cost_per_kg.is_some_and(|c| !c.is_finite() || c < 0.0) {
+        return Err("cost_per_kg must not be negative");
+    }
+    Ok(())
+}
+
+/// Least similarity for a past session's bean to count towards a profile
+/// recommendation.
+pub const MIN_BEAN_SIMILARITY: f64 = 0.5;
+
+/// The bean attributes profile recommendations match on.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct BeanAttributes {
+    pub origin: Option<String>,
+    pub variety: Option<String>,
+    pub process: Option<String>,
+    pub density: Option<f32>,
+}
+
+impl From<&Bean> for BeanAttributes {
+    fn from(bean: &Bean) -> Self {
+        Self {
+            origin: bean.origin.clone(),
+            variety: bean.variety.clone(),
+            process: bean.process.clone(),
+            density: bean.density,
+        }
+    }
+}
+
+impl BeanAttributes {
+    pub fn is_empty(&self) -> bool {
+        self.origin.is_none()
+            && self.variety.is_none()
+            && self.process.is_none()
+            && self.density.is_none()
+    }
+
+    /// How closely `other` matches these attributes, 0..1. Origin weighs
+    /// most, then process, density (linear, nothing at 100 g/L apart) and
+    /// variety. Only attributes set here count, and text compares
+    /// case-insensitively.
+    pub fn similarity(&self, other: &BeanAttributes) -> f64 {
+        fn text(a: &Option<String>, b: &Option<String>) -> Option<f64> {
+            let a = a.as_deref()?.trim();
+            let same = b
+                .as_deref()
+                .is_some_and(|b| b.trim().eq_ignore_ascii_case(a));
+            Some(if same { 1.0 } else { 0.0 })
+        }
+        let density = self.density.map(|a| {
+            other
+                .density
+                .map_or(0.0, |b| (1.0 - (a - b).abs() as f64 / 100.0).max(0.0))
+        });
+        let parts = [
+            (0.4, text(&self.origin, &other.origin)),
+            (0.3, text(&self.process, &other.process)),
+            (0.2, density),
+            (0.1, text(&self.variety, &other.variety)),
+        ];
+        let weight: f64 = parts
+            .iter()
+            .filter(|(_, v)| v.is_some())
+            .map(|(w, _)| w)
+            .sum();
+        if weight == 0.0 {
+            return 0.0;
+        }
+        parts.iter().map(|(w, v)| w * v.unwrap_or(0.0)).sum::<f64>() / weight
+    }
+}
+
+/// A bean by id, or attributes given directly.
+#[derive(Debug, Deserialize)]
+pub struct ProfileRecommendQuery {
+    pub bean_id: Option<String>,
+    pub origin: Option<String>,
+    pub variety: Option<String>,
+    pub process: Option<String>,
+    pub density: Option<f32>,
+    pub limit: Option<usize>,
+}
+
+/// A profile ranked by how it did on beans like the requested one.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileRecommendation {
+    pub profile_id: String,
+    pub profile_name: String,
+    /// Ranking score, 0..1.
+    pub score: f64,
+    /// Completed sessions of the profile with a similar bean.
+    pub similar_sessions: i64,
+    /// Mean similarity of those beans to the requested one, 0..1.
+    pub similarity: f64,
+    /// Similarity-weighted mean cupping score of the cupped sessions.
+    pub avg_cupping_score: Option<f64>,
+    pub cupped_sessions: i64,
+    pub avg_development_time_ratio: Option<f64>,
+    pub avg_weight_loss_pct: Option<f64>,
+    pub last_roasted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileRecommendations {
+    pub bean: BeanAttributes,
+    pub recommendations: Vec<ProfileRecommendation>,
+}
+
+// ---- Sample roasts ----
+
+/// Sample sessions of one bean, each on a different profile.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SampleGroup {
+    pub id: String,
+    pub bean_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SampleGroupSummary {
+    #[serde(flatten)]
+    pub group: SampleGroup,
+    /// Sessions in the group, not counting cancelled ones.
+    pub sample_count: i64,
+    pub completed_count: i64,
+    /// All samples roasted, ready for the evaluation sheet.
+    pub complete: bool,
+}
+
+/// One sample of an evaluation sheet.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SampleEvaluationRow {
+    pub session_id: String,
+    pub name: String,
+    pub status: SessionStatus,
+    pub profile_id: Option<String>,
+    pub profile_name: Option<String>,
+    pub green_weight: Option<f32>,
+    pub roasted_weight: Option<f32>,
+    pub weight_loss_pct: Option<f32>,
+    pub total_time_seconds: Option<i32>,
+    pub first_crack_time: Option<i32>,
+    pub development_time_ratio: Option<f32>,
+    /// Bean temp of the marked drop.
+    pub drop_temp: Option<f32>,
+    /// Mean overall cupping score.
+    pub cupping_score: Option<f64>,
+    pub cupping_notes: Option<String>,
+}
+
+/// Side-by-side evaluation sheet of a sample group.
+#[derive(Debug, Serialize)]
+pub struct SampleEvaluation {
+    pub group: SampleGroup,
+    pub bean: Option<Bean>,
+    pub complete: bool,
+    /// The best cupped sample, once any is cupped.
+    pub best_session_id: Option<String>,
+    pub samples: Vec<SampleEvaluationRow>,
+}
+
+// ---- Session costs ----
+
+/// Rates for session cost accounting, from the `cost_*` settings.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CostRates {
+    pub currency: String,
+    /// Heater power at 100 % PWM.
+    pub heater_watts: f64,
+    pub energy_per_kwh: f64,
+    pub labor_per_hour: f64,
+    pub overhead_per_batch: f64,
+}
+
+impl Default for CostRates {
+    fn default() -> Self {
+        Self {
+            currency: "USD".to_string(),
+            heater_watts: 1500.0,
+            energy_per_kwh: 0.0,
+            labor_per_hour: 0.0,
+            overhead_per_batch: 0.0,
+        }
+    }
+}
+
+/// What a session cost, in `currency`. Weights are kg.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SessionCost {
+    pub session_id: String,
+    pub currency: String,
+    pub green_kg: Option<f64>,
+    pub roasted_kg: Option<f64>,
+    /// `None` without a green weight or a priced bean lot.
+    pub bean_cost: Option<f64>,
+    /// Estimated from the heater PWM in the session's telemetry.
+    pub energy_kwh: f64,
+    pub energy_cost: f64,
+    pub roast_hours: f64,
+    pub labor_cost: f64,
+    pub overhead_cost: f64,
+    /// Sum of the known parts.
+    pub total_cost: f64,
+    /// `None` without a roasted weight.
+    pub cost_per_roasted_kg: Option<f64>,
+}
+
+/// `?date=YYYY-MM-DD`, today (UTC) when left out.
+#[derive(Debug, Deserialize)]
+pub struct DailyReportQuery {
+    pub date: Option<NaiveDate>,
+}
+
+/// Completed sessions started on a day, with their costs.
+#[derive(Debug, Serialize)]
+pub struct DailyReport {
+    pub date: NaiveDate,
+    pub currency: String,
+    pub session_count: usize,
+    pub green_kg: f64,
+    pub roasted_kg: f64,
+    pub total_cost: f64,
+    /// Cost of the sessions with a roasted weight over that weight.
+    pub cost_per_roasted_kg: Option<f64>,
+    pub sessions: Vec<SessionCost>,
+}
+
+/// `POST /api/admin/reports/daily/email`; the date defaults to today (UTC)
+/// and the recipients to the `report_email_recipients` setting.
+#[derive(Debug, Default, Deserialize)]
+pub struct SendReportEmailRequest {
+    pub date: Option<NaiveDate>,
+    pub recipients: Option<Vec<String>>,
+}
+
+/// A daily report email that was sent.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ReportEmail {
+    pub id: String,
+    pub date: NaiveDate,
+    pub sent_at: DateTime<Utc>,
+    pub recipients: sqlx::types::Json<Vec<String>>,
+    pub session_count: i64,
+    /// Who sent it on request, `None` when scheduled.
+    pub sent_by: Option<String>,
+}
+
+// ---- Analytics ----
+
+/// Session statistic tracked by the trends API.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendMetric {
+    DevRatio,
+    LossPct,
+    FcTime,
+}
+
+impl TrendMetric {
+    /// The `roast_sessions` column holding this metric.
+    pub fn column(self) -> &'static str {
+        match self {
+            TrendMetric::DevRatio => "development_time_ratio",
+            TrendMetric::LossPct => "weight_loss_pct",
+            TrendMetric::FcTime => "first_crack_time",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendGroupBy {
+    Bean,
+    Profile,
+    /// A single series over all sessions.
+    #[default]
+    Week,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrendQuery {
+    pub metric: TrendMetric,
+    #[serde(default)]
+    pub group_by: TrendGroupBy,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Aggregate of one metric over the completed sessions in a week.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TrendPoint {
+    /// Monday of the week (YYYY-MM-DD).
+    pub week: String,
+    pub count: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendSeries {
+    /// Bean origin or profile id. `None` groups sessions without one.
+    pub key: Option<String>,
+    pub label: Option<String>,
+    pub points: Vec<TrendPoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendsResponse {
+    pub metric: TrendMetric,
+    pub group_by: TrendGroupBy,
+    pub series: Vec<TrendSeries>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryGroupBy {
+    #[default]
+    Day,
+    Bean,
+    Profile,
+}
+
+/// `from` and `to` select whole days (UTC).
+#[derive(Debug, Clone, Deserialize)]
+pub struct SummaryQuery {
+    #[serde(default)]
+    pub group_by: SummaryGroupBy,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Totals and averages over the completed sessions of a day, bean origin
+/// or profile.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SummaryRow {
+    /// Day (YYYY-MM-DD), bean origin or profile id. `None` groups sessions
+    /// without one.
+    pub key: Option<String>,
+    pub label: Option<String>,
+    pub sessions: i64,
+    pub roast_seconds: Option<f64>,
+    pub green_weight: Option<f64>,
+    pub roasted_weight: Option<f64>,
+    pub avg_dev_ratio: Option<f64>,
+    pub avg_loss_pct: Option<f64>,
+    pub avg_fc_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryResponse {
+    pub group_by: SummaryGroupBy,
+    pub rows: Vec<SummaryRow>,
+}
+
+// ---- Autotune runs ----
+
+/// Lifecycle of an autotune run. Only `Running` accepts new results.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutotuneRunState {
+    Running,
+    Completed,
+    Failed,
+    Stopped,
+    /// No results arrived within the run timeout.
+    Expired,
+}
+
+impl Type<sqlx::Sqlite> for AutotuneRunState {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for AutotuneRunState {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for AutotuneRunState {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for AutotuneRunState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AutotuneRunState::Running => "running",
+            AutotuneRunState::Completed => "completed",
+            AutotuneRunState::Failed => "failed",
+            AutotuneRunState::Stopped => "stopped",
+            AutotuneRunState::Expired => "expired",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for AutotuneRunState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "running" => Ok(AutotuneRunState::Running),
+            "completed" => Ok(AutotuneRunState::Completed),
+            "failed" => Ok(AutotuneRunState::Failed),
+            "stopped" => Ok(AutotuneRunState::Stopped),
+            "expired" => Ok(AutotuneRunState::Expired),
+            _ => Err(format!("Invalid autotune run state: {}", s)),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct Device {
+pub struct AutotuneRun {
     pub id: String,
-    pub name: String,
     pub device_id: String,
-    pub profile_id: Option<String>,
-    pub status: DeviceStatus,
-    pub description: Option<String>,
-    pub location: Option<String>,
+    pub target_temperature: f64,
+    pub mode: Option<String>,
+    pub tuning_method: Option<String>,
+    pub requested_by: Option<String>,
+    pub state: AutotuneRunState,
+    /// Most recent phase reported on the status topic.
+    pub last_phase: Option<String>,
+    /// The paired `results` payload once the run completes.
+    pub results: Option<sqlx::types::Json<serde_json::Value>>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StartAutotuneRun<'a> {
+    pub target_temperature: f64,
+    pub mode: Option<&'a str>,
+    pub tuning_method: Option<&'a str>,
+    pub requested_by: Option<&'a str>,
+}
+
+// ---- Typed protocol config structs ----
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttConnectionConfig {
+    pub topic_prefix: String,
+    pub qos: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketConnectionConfig {
+    pub url: String,
+    pub reconnect_interval_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModbusTcpConnectionConfig {
+    pub host: String,
+    pub port: u16,
+    pub unit_id: u8,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_ms: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    1000
+}
+
+// ============================================================================
+// Cupping Notes Models (AP-012)
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CuppingScore {
+    pub id: String,
+    pub session_id: String,
+    pub scoring_framework: String,
+    pub overall_score: Option<f32>,
+    pub notes: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
-    pub last_seen_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct DeviceProfile {
-    pub id: String,
-    pub name: String,
-    pub description: Option<String>,
-    pub default_control_mode: Option<String>,
-    pub default_setpoint: Option<f64>,
-    pub default_fan_pwm: Option<i32>,
-    pub default_kp: Option<f64>,
-    pub default_ki: Option<f64>,
-    pub default_kd: Option<f64>,
-    pub max_temp: Option<f64>,
-    pub min_fan_pwm: Option<i32>,
-    pub telemetry_interval_ms: Option<i32>,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CuppingAttribute {
+    pub id: String,
+    pub cupping_id: String,
+    pub attribute_name: String,
+    pub score: f32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CuppingWithAttributes {
+    #[serde(flatten)]
+    pub cupping: CuppingScore,
+    pub attributes: Vec<CuppingAttribute>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCuppingAttributeRequest {
+    pub name: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCuppingRequest {
+    pub scoring_framework: Option<String>,
+    pub notes: Option<String>,
+    pub attributes: Vec<CreateCuppingAttributeRequest>,
+}
+
+// ---- Roast defects ----
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DefectType {
+    Scorching,
+    Tipping,
+    Underdevelopment,
+    Baked,
+    Other,
+}
+
+impl Type<sqlx::Sqlite> for DefectType {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for DefectType {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for DefectType {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for DefectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            DefectType::Scorching => "scorching",
+            DefectType::Tipping => "tipping",
+            DefectType::Underdevelopment => "underdevelopment",
+            DefectType::Baked => "baked",
+            DefectType::Other => "other",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for DefectType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scorching" => Ok(DefectType::Scorching),
+            "tipping" => Ok(DefectType::Tipping),
+            "underdevelopment" => Ok(DefectType::Underdevelopment),
+            "baked" => Ok(DefectType::Baked),
+            "other" => Ok(DefectType::Other),
+            _ => Err(format!("Invalid defect type: {}", s)),
+        }
+    }
+}
+
+/// A defect tagged on a session. The optional range is elapsed seconds on
+/// the session curve where the defect shows.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionDefect {
+    pub id: String,
+    pub session_id: String,
+    pub defect: DefectType,
+    pub start_seconds: Option<f32>,
+    pub end_seconds: Option<f32>,
+    pub notes: Option<String>,
+    pub tagged_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDefectRequest {
+    pub defect: DefectType,
+    pub start_seconds: Option<f32>,
+    pub end_seconds: Option<f32>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DefectGroupBy {
+    #[default]
+    Profile,
+    Bean,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DefectRateQuery {
+    #[serde(default)]
+    pub group_by: DefectGroupBy,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DefectRate {
+    pub defect: DefectType,
+    /// Completed sessions in the group tagged with this defect.
+    pub sessions: i64,
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DefectRateGroup {
+    /// Profile id or bean origin. `None` groups sessions without one.
+    pub key: Option<String>,
+    pub label: Option<String>,
+    pub sessions: i64,
+    /// Sessions with at least one defect.
+    pub defective_sessions: i64,
+    pub defect_rate: f64,
+    pub defects: Vec<DefectRate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DefectRatesResponse {
+    pub group_by: DefectGroupBy,
+    pub groups: Vec<DefectRateGroup>,
+}
+
+// ---- Offline sync ----
+
+/// Record kinds tracked by the sync change journal.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncEntity {
+    Session,
+    Event,
+    /// Keyed by session id (one cupping per session).
+    Cupping,
+}
+
+impl Type<sqlx::Sqlite> for SyncEntity {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for SyncEntity {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for SyncEntity {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for SyncEntity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SyncEntity::Session => "session",
+            SyncEntity::Event => "event",
+            SyncEntity::Cupping => "cupping",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for SyncEntity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "session" => Ok(SyncEntity::Session),
+            "event" => Ok(SyncEntity::Event),
+            "cupping" => Ok(SyncEntity::Cupping),
+            _ => Err(format!("Invalid sync entity: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncOp {
+    Upsert,
+    Delete,
+}
+
+impl Type<sqlx::Sqlite> for SyncOp {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, sqlx::Sqlite> for SyncOp {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
+}
+
+impl<'q> Encode<'q, sqlx::Sqlite> for SyncOp {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
+impl std::fmt::Display for SyncOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SyncOp::Upsert => "upsert",
+            SyncOp::Delete => "delete",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for SyncOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upsert" => Ok(SyncOp::Upsert),
+            "delete" => Ok(SyncOp::Delete),
+            _ => Err(format!("Invalid sync op: {}", s)),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct DeviceConnection {
-    pub id: String,
-    pub device_id: String,
-    pub protocol: Protocol,
-    pub enabled: bool,
-    pub priority: i32,
-    #[sqlx(json)]
-    pub config: serde_json::Value,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
+/// One row of the change journal.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SyncChange {
+    pub seq: i64,
+    pub entity: SyncEntity,
+    pub entity_id: String,
+    pub op: SyncOp,
+    pub origin: Option<String>,
+    pub changed_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct ModbusRegisterMap {
-    pub id: String,
-    pub device_id: String,
-    pub register_type: ModbusRegisterType,
-    pub address: i32,
-    pub name: String,
-    pub data_type: ModbusDataType,
-    pub byte_order: Option<String>,
-    pub scale_factor: Option<f64>,
-    pub offset: Option<f64>,
-    pub unit: Option<String>,
-    pub description: Option<String>,
-    pub writable: bool,
+#[derive(Debug, Deserialize)]
+pub struct SyncPullQuery {
+    /// Last `cursor` the client received; 0 for a full sync.
+    #[serde(default)]
+    pub since: i64,
+    pub limit: Option<i64>,
 }
 
-// ---- API response structs ----
+/// Latest state of a changed record. `data` is absent for deletes.
+#[derive(Debug, Serialize)]
+pub struct SyncPullItem {
+    pub seq: i64,
+    pub entity: SyncEntity,
+    pub entity_id: String,
+    pub op: SyncOp,
+    pub changed_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
 
-#[derive(Debug, Clone, Serialize)]
-pub struct DeviceWithConnections {
-    #[serde(flatten)]
-    pub device: Device,
-    pub connections: Vec<DeviceConnection>,
+#[derive(Debug, Serialize)]
+pub struct SyncPullResponse {
+    pub changes: Vec<SyncPullItem>,
+    /// Pass as `since` on the next pull.
+    pub cursor: i64,
+    pub has_more: bool,
 }
 
-// ---- API request structs ----
+#[derive(Debug, Deserialize)]
+pub struct SyncPushRequest {
+    /// Stable id of the pushing device, used to tell its own changes apart.
+    pub client_id: String,
+    /// Cursor of the client's last pull. Server changes after it conflict.
+    #[serde(default)]
+    pub base_seq: i64,
+    pub changes: Vec<SyncPushChange>,
+}
 
 #[derive(Debug, Deserialize)]
-pub struct CreateDeviceRequest {
-    pub name: String,
+pub struct SyncPushChange {
+    pub entity: SyncEntity,
+    pub entity_id: String,
+    pub op: SyncOp,
+    /// Create/update request body for the entity (events also need `session_id`).
+    #[serde(default)]
+    pub data: serde_json::Value,
+    /// Overwrite the server copy even if it changed since `base_seq`.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncPushStatus {
+    Applied,
+    /// Changed on the server since `base_seq`; `server` holds the current copy.
+    Conflict,
+    Rejected,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncPushResult {
+    pub entity: SyncEntity,
+    pub entity_id: String,
+    pub status: SyncPushStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncPushResponse {
+    pub results: Vec<SyncPushResult>,
+}
+
+// ---- Manual roasting ----
+
+/// Phase of a manual roast, derived from its status and marked events.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManualPhase {
+    Preheat,
+    Drying,
+    Maillard,
+    Development,
+    Finished,
+}
+
+/// Simplified phase tracker with the next guided prompt for a manual roast.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManualRoastStatus {
+    pub session_id: String,
+    pub phase: ManualPhase,
+    pub elapsed_seconds: Option<f32>,
+    /// Seconds since first crack, while developing.
+    pub development_seconds: Option<f32>,
+    pub development_ratio: Option<f32>,
+    pub prompt: Option<String>,
+    /// Latest heater/fan settings recorded during the session.
+    pub heater_pwm: Option<String>,
+    pub fan_pwm: Option<String>,
+}
+
+/// A control command sent to a roaster.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ControlAuditEntry {
+    pub id: String,
     pub device_id: String,
-    pub profile_id: Option<String>,
-    pub description: Option<String>,
-    pub location: Option<String>,
+    pub op: String,
+    pub value: String,
+    /// Session active on the device when the command was sent.
+    pub session_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Window and resolution for `GET /api/sessions/{id}/telemetry`. Times are
+/// elapsed seconds, both ends inclusive.
+#[derive(Debug, Default, Deserialize)]
+pub struct TelemetryRangeQuery {
+    pub from_secs: Option<f32>,
+    pub to_secs: Option<f32>,
+    pub max_points: Option<usize>,
+    /// View whose smoothing is returned as `smoothing` (default `live`).
+    pub view: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct UpdateDeviceRequest {
-    pub name: Option<String>,
-    pub profile_id: Option<String>,
-    pub status: Option<DeviceStatus>,
-    pub description: Option<String>,
-    pub location: Option<String>,
+pub struct ControlAuditQuery {
+    pub limit: Option<i64>,
 }
 
+// ---- Admin recompute ----
+
+/// `scope` is `all` or `session:<id>`.
 #[derive(Debug, Deserialize)]
-pub struct CreateDeviceProfileRequest {
-    pub name: String,
-    pub description: Option<String>,
-    pub default_control_mode: Option<String>,
-    pub default_setpoint: Option<f64>,
-    pub default_fan_pwm: Option<i32>,
-    pub default_kp: Option<f64>,
-    pub default_ki: Option<f64>,
-    pub default_kd: Option<f64>,
-    pub max_temp: Option<f64>,
-    pub min_fan_pwm: Option<i32>,
-    pub telemetry_interval_ms: Option<i32>,
+pub struct RecomputeQuery {
+    pub scope: String,
 }
 
+/// Compact raw telemetry older than `older_than_days` now, defaulting to
+/// `RUSTROAST_TELEMETRY_COMPACT_AFTER_DAYS`.
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)] // Used by device profile edit UI (DEV-013)
-pub struct UpdateDeviceProfileRequest {
-    pub name: Option<String>,
-    pub description: Option<String>,
-    pub default_control_mode: Option<String>,
-    pub default_setpoint: Option<f64>,
-    pub default_fan_pwm: Option<i32>,
-    pub default_kp: Option<f64>,
-    pub default_ki: Option<f64>,
-    pub default_kd: Option<f64>,
-    pub max_temp: Option<f64>,
-    pub min_fan_pwm: Option<i32>,
-    pub telemetry_interval_ms: Option<i32>,
+pub struct CompactTelemetryQuery {
+    pub older_than_days: Option<i64>,
 }
 
+/// Runtime MQTT credential update. Exactly one of `password` or `token`
+/// (sent as the password); `username` defaults to the configured one.
 #[derive(Debug, Deserialize)]
-pub struct CreateConnectionRequest {
-    pub protocol: Protocol,
-    pub enabled: Option<bool>,
-    pub priority: Option<i32>,
-    pub config: serde_json::Value,
+pub struct MqttCredentialsRequest {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub token: Option<String>,
+    /// How long to wait for the broker to accept the new credentials.
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MqttCredentialsResponse {
+    pub connected: bool,
 }
 
+/// Runtime MQTT connection change. Fields left out keep their current
+/// value; `brokers` (`host:port` entries, tried in order) replaces the
+/// broker list and `host`, and `username: ""` removes the username.
 #[derive(Debug, Deserialize)]
-pub struct UpdateConnectionRequest {
-    pub enabled: Option<bool>,
-    pub priority: Option<i32>,
-    pub config: Option<serde_json::Value>,
+pub struct MqttConfigRequest {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub brokers: Option<Vec<String>>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub token: Option<String>,
+    pub keep_alive_secs: Option<u16>,
+    /// How long to wait for the broker to accept the connection.
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MqttConfigResponse {
+    pub connected: bool,
+    /// `host:port` of the broker in use.
+    pub broker: Option<String>,
 }
 
+/// Raw MQTT message for troubleshooting. A string `payload` is sent as is,
+/// anything else as its JSON text.
 #[derive(Debug, Deserialize)]
-pub struct CreateRegisterMapEntry {
-    pub register_type: ModbusRegisterType,
-    pub address: i32,
-    pub name: String,
-    pub data_type: ModbusDataType,
-    pub byte_order: Option<String>,
-    pub scale_factor: Option<f64>,
-    pub offset: Option<f64>,
-    pub unit: Option<String>,
-    pub description: Option<String>,
-    pub writable: Option<bool>,
+pub struct MqttPublishRequest {
+    pub topic: String,
+    pub payload: serde_json::Value,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
 }
 
-// ---- Connection test request/response ----
+/// An audited raw MQTT publish.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MqttPublishAudit {
+    pub id: String,
+    pub topic: String,
+    pub payload: String,
+    pub qos: u8,
+    pub retain: bool,
+    /// Admin who sent it (user, API key or `admin-token`).
+    pub subject: Option<String>,
+    /// `rest`, `websocket` or `retained_clear`.
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
 
 #[derive(Debug, Deserialize)]
-pub struct TestConnectionRequest {
-    pub protocol: Protocol,
-    pub config: serde_json::Value,
+pub struct MqttPublishAuditQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RetainedMessagesQuery {
+    /// How long to collect what the broker replays, in ms (default 2000).
+    pub window_ms: Option<u64>,
+    /// A device not heard from for this long is stale (default 7 days).
+    pub stale_after_secs: Option<u64>,
+}
+
+/// A retained message under `roaster/#`, as the broker replays it to new
+/// subscribers.
+#[derive(Debug, Clone, Serialize)]
+pub struct RetainedMessageInfo {
+    pub topic: String,
     pub device_id: Option<String>,
+    /// Decoded as UTF-8, invalid bytes replaced.
+    pub payload: String,
+    pub bytes: usize,
+    /// When the device last sent telemetry or a live status.
+    pub last_seen_at: Option<DateTime<Utc>>,
+    /// The device is unknown, or hasn't been heard from within
+    /// `stale_after_secs`.
+    pub stale: bool,
 }
 
-#[derive(Debug, Serialize)]
-pub struct TestConnectionResponse {
-    pub success: bool,
-    pub message: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub latency_ms: Option<u64>,
+/// Retained messages to clear: the given `topics`, or with `stale: true`
+/// every stale one a fresh scan finds.
+#[derive(Debug, Default, Deserialize)]
+pub struct ClearRetainedRequest {
+    #[serde(default)]
+    pub topics: Vec<String>,
+    #[serde(default)]
+    pub stale: bool,
+    pub window_ms: Option<u64>,
+    pub stale_after_secs: Option<u64>,
 }
 
-// ---- Typed protocol config structs ----
+// ---- Users and API keys ----
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MqttConnectionConfig {
-    pub topic_prefix: String,
-    pub qos: u8,
+/// Access level for users and API keys, ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum UserRole {
+    Viewer,
+    Operator,
+    Admin,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WebSocketConnectionConfig {
-    pub url: String,
-    pub reconnect_interval_ms: u64,
+impl Type<sqlx::Sqlite> for UserRole {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ModbusTcpConnectionConfig {
-    pub host: String,
-    pub port: u16,
-    pub unit_id: u8,
-    #[serde(default = "default_poll_interval")]
-    pub poll_interval_ms: u64,
+impl<'r> Decode<'r, sqlx::Sqlite> for UserRole {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        s.parse().map_err(Into::into)
+    }
 }
 
-fn default_poll_interval() -> u64 {
-    1000
+impl<'q> Encode<'q, sqlx::Sqlite> for UserRole {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        <String as Encode<sqlx::Sqlite>>::encode_by_ref(&self.to_string(), buf)
+    }
 }
 
-// ============================================================================
-// Cupping Notes Models (AP-012)
-// ============================================================================
+impl std::fmt::Display for UserRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            UserRole::Viewer => "viewer",
+            UserRole::Operator => "operator",
+            UserRole::Admin => "admin",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for UserRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(UserRole::Viewer),
+            "operator" => Ok(UserRole::Operator),
+            "admin" => Ok(UserRole::Admin),
+            _ => Err(format!("Invalid user role: {}", s)),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct CuppingScore {
+pub struct User {
     pub id: String,
-    pub session_id: String,
-    pub scoring_framework: String,
-    pub overall_score: Option<f32>,
-    pub notes: Option<String>,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub role: UserRole,
     pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct CuppingAttribute {
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ApiKey {
     pub id: String,
-    pub cupping_id: String,
-    pub attribute_name: String,
-    pub score: f32,
+    pub name: String,
+    /// First characters of the key, shown so users can tell keys apart.
+    pub key_prefix: String,
+    pub role: UserRole,
+    pub created_by: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub role: Option<UserRole>,
 }
 
+/// Returned once on creation; the plaintext key is never stored.
 #[derive(Debug, Serialize)]
-pub struct CuppingWithAttributes {
+pub struct CreatedApiKey {
     #[serde(flatten)]
-    pub cupping: CuppingScore,
-    pub attributes: Vec<CuppingAttribute>,
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// Limits on the control commands one API key may send, so a third-party
+/// automation gets a narrower blast radius than the dashboard.
+#[derive(Debug, Clone, Serialize, FromRow, PartialEq)]
+pub struct ApiKeyControlPolicy {
+    pub api_key_id: String,
+    /// Largest setpoint movement (°C) the key may make within any minute.
+    pub max_setpoint_delta_per_min: Option<f64>,
+    /// Largest heater PWM movement (%) the key may make within any minute.
+    pub max_heater_pwm_delta_per_min: Option<f64>,
+    /// Control ops the key may not send at all, e.g. `emergency_stop`.
+    pub banned_ops: sqlx::types::Json<Vec<String>>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct CreateCuppingAttributeRequest {
-    pub name: String,
-    pub score: f32,
+pub struct SetApiKeyControlPolicyRequest {
+    #[serde(default)]
+    pub max_setpoint_delta_per_min: Option<f64>,
+    #[serde(default)]
+    pub max_heater_pwm_delta_per_min: Option<f64>,
+    #[serde(default)]
+    pub banned_ops: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserPreferences {
+    pub subject: String,
+    /// Device ids streamed on `/ws/telemetry` by default; empty means all.
+    pub ws_subscriptions: sqlx::types::Json<Vec<String>>,
+    pub dashboard_layout: Option<serde_json::Value>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct CreateCuppingRequest {
-    pub scoring_framework: Option<String>,
-    pub notes: Option<String>,
-    pub attributes: Vec<CreateCuppingAttributeRequest>,
+pub struct UpdatePreferencesRequest {
+    pub ws_subscriptions: Option<Vec<String>>,
+    pub dashboard_layout: Option<serde_json::Value>,
 }
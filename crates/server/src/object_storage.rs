@@ -0,0 +1,226 @@
+//! Minimal client for S3-compatible object storage (AWS S3, MinIO, R2, ...).
+//!
+//! Only what session archival needs: PUT and GET of whole objects, with
+//! path-style URLs (`{endpoint}/{bucket}/{key}`) signed with AWS Signature
+//! Version 4. Without credentials requests go out unsigned.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Method, Url};
+use sha2::Sha256;
+
+use crate::export_signing::{sha256_hex, to_hex};
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+#[derive(Clone)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+#[derive(Clone)]
+pub struct S3Store {
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    credentials: Option<S3Credentials>,
+    http: reqwest::Client,
+}
+
+impl S3Store {
+    pub fn new(
+        endpoint: &str,
+        bucket: &str,
+        region: &str,
+        credentials: Option<S3Credentials>,
+    ) -> Result<Self> {
+        let endpoint = Url::parse(endpoint).context("invalid object storage endpoint")?;
+        Ok(Self {
+            endpoint,
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            credentials,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// `RUSTROAST_ARCHIVE_S3_ENDPOINT` and `RUSTROAST_ARCHIVE_S3_BUCKET`, with
+    /// `RUSTROAST_ARCHIVE_S3_REGION` (default `us-east-1`) and the
+    /// `RUSTROAST_ARCHIVE_S3_ACCESS_KEY_ID` / `_SECRET_ACCESS_KEY` pair.
+    /// `None` unless endpoint and bucket are set.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let endpoint = var("RUSTROAST_ARCHIVE_S3_ENDPOINT")?;
+        let bucket = var("RUSTROAST_ARCHIVE_S3_BUCKET")?;
+        let region = var("RUSTROAST_ARCHIVE_S3_REGION").unwrap_or_else(|| DEFAULT_REGION.into());
+        let credentials = var("RUSTROAST_ARCHIVE_S3_ACCESS_KEY_ID")
+            .zip(var("RUSTROAST_ARCHIVE_S3_SECRET_ACCESS_KEY"))
+            .map(|(access_key_id, secret_access_key)| S3Credentials {
+                access_key_id,
+                secret_access_key,
+            });
+        match Self::new(&endpoint, &bucket, &region, credentials) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                tracing::error!(error = %e, "Object storage is misconfigured, archival disabled");
+                None
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> Result<()> {
+        self.request(Method::PUT, key, &body, Utc::now())?
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("failed to upload {}", key))?;
+        Ok(())
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let resp = self
+            .request(Method::GET, key, &[], Utc::now())?
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("failed to download {}", key))?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+
+    fn url(&self, key: &str) -> Result<Url> {
+        let base = self.endpoint.as_str().trim_end_matches('/');
+        let url = format!("{}/{}/{}", base, uri_encode(&self.bucket), uri_encode(key));
+        Ok(Url::parse(&url)?)
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        key: &str,
+        body: &[u8],
+        now: DateTime<Utc>,
+    ) -> Result<reqwest::RequestBuilder> {
+        let url = self.url(key)?;
+        let mut req = self.http.request(method.clone(), url.clone());
+        if let Some(credentials) = &self.credentials {
+            let payload_hash = sha256_hex(body);
+            let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+            let authorization =
+                self.authorization(credentials, &method, &url, &payload_hash, &amz_date);
+            req = req
+                .header("x-amz-date", amz_date)
+                .header("x-amz-content-sha256", payload_hash)
+                .header(reqwest::header::AUTHORIZATION, authorization);
+        }
+        Ok(req)
+    }
+
+    /// SigV4 `Authorization` header over the host and `x-amz-*` headers.
+    fn authorization(
+        &self,
+        credentials: &S3Credentials,
+        method: &Method,
+        url: &Url,
+        payload_hash: &str,
+        amz_date: &str,
+    ) -> String {
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method.as_str(),
+            url.path(),
+            host,
+            payload_hash,
+            amz_date,
+            signed_headers,
+            payload_hash
+        );
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+        let secret = format!("AWS4{}", credentials.secret_access_key);
+        let key = [date, self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(secret.into_bytes(), |key, part| hmac_sha256(&key, part));
+        let signature = to_hex(&hmac_sha256(&key, &string_to_sign));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        )
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode all but unreserved characters and `/`, as SigV4 expects.
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_sigv4_matches_reference_signature() {
+        let credentials = S3Credentials {
+            access_key_id: "AKIDEXAMPLE".into(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+        };
+        let store = S3Store::new(
+            "http://127.0.0.1:9000",
+            "roasts",
+            "eu-central-1",
+            Some(credentials),
+        )
+        .unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        let req = store
+            .request(
+                Method::PUT,
+                "rr/sessions/a b/session.json",
+                br#"{"ok":true}"#,
+                now,
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            req.url().as_str(),
+            "http://127.0.0.1:9000/roasts/rr/sessions/a%20b/session.json"
+        );
+        assert_eq!(req.headers()["x-amz-date"], "20261016T120000Z");
+        // Computed with botocore's S3SigV4Auth for the same request
+        assert_eq!(
+            req.headers()["authorization"],
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20261016/eu-central-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=00ced21cedc62f2c2d228e2519cc433584e5a27d3fc6c245813ef4a8acc38d60"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! OpenID Connect login (authorization code flow with PKCE).
+//!
+//! The provider is discovered from `OIDC_ISSUER_URL`; ID tokens are verified
+//! against the provider's JWKS. Roles are mapped from a groups claim.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Validation};
+use rand::RngCore;
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::models::UserRole;
+
+/// Pending logins older than this are discarded.
+const LOGIN_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone)]
+pub(crate) struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+    pub groups_claim: String,
+    /// Group name -> role. The highest role among matching groups wins.
+    pub role_map: Vec<(String, UserRole)>,
+    pub default_role: UserRole,
+    /// Where the browser is sent after a successful login.
+    pub post_login_redirect: String,
+}
+
+impl OidcConfig {
+    /// Returns `None` unless `OIDC_ISSUER_URL`, `OIDC_CLIENT_ID` and
+    /// `OIDC_REDIRECT_URL` are all set.
+    pub fn from_env() -> Option<Self> {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        Some(Self {
+            issuer_url: var("OIDC_ISSUER_URL")?,
+            client_id: var("OIDC_CLIENT_ID")?,
+            client_secret: var("OIDC_CLIENT_SECRET"),
+            redirect_url: var("OIDC_REDIRECT_URL")?,
+            scopes: var("OIDC_SCOPES").unwrap_or_else(|| "openid email profile".to_string()),
+            groups_claim: var("OIDC_GROUPS_CLAIM").unwrap_or_else(|| "groups".to_string()),
+            role_map: var("OIDC_ROLE_MAP")
+                .map(|v| parse_role_map(&v))
+                .unwrap_or_default(),
+            default_role: var("OIDC_DEFAULT_ROLE")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(UserRole::Viewer),
+            post_login_redirect: var("OIDC_POST_LOGIN_REDIRECT").unwrap_or_else(|| "/".to_string()),
+        })
+    }
+
+    /// Highest role granted by any of the user's groups, or the default role.
+    pub fn map_role(&self, groups: &[String]) -> UserRole {
+        self.role_map
+            .iter()
+            .filter(|(group, _)| groups.iter().any(|g| g == group))
+            .map(|(_, role)| *role)
+            .max()
+            .unwrap_or(self.default_role)
+    }
+}
+
+/// Parse `OIDC_ROLE_MAP`, e.g. `roast-admins=admin,roasters=operator`.
+/// Malformed entries are skipped with a warning.
+pub(crate) fn parse_role_map(value: &str) -> Vec<(String, UserRole)> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .and_then(|(g, r)| Some((g.trim().to_string(), r.trim().parse().ok()?)));
+            if parsed.is_none() {
+                tracing::warn!(entry, "Ignoring malformed OIDC_ROLE_MAP entry");
+            }
+            parsed
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    email: Option<String>,
+    name: Option<String>,
+    preferred_username: Option<String>,
+    nonce: Option<String>,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+struct PendingLogin {
+    nonce: String,
+    pkce_verifier: String,
+    created: Instant,
+}
+
+/// Identity asserted by the provider for a completed login.
+#[derive(Debug, Clone)]
+pub(crate) struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub groups: Vec<String>,
+}
+
+pub(crate) struct OidcClient {
+    pub config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+fn random_token() -> String {
+    let mut buf = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut buf);
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            metadata: OnceCell::new(),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Provider discovery, fetched on first use and cached.
+    async fn metadata(&self) -> Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer_url.trim_end_matches('/')
+                );
+                let meta = self
+                    .http
+                    .get(&url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<ProviderMetadata>()
+                    .await
+                    .context("invalid OIDC discovery document")?;
+                Ok(meta)
+            })
+            .await
+    }
+
+    /// Build the provider authorization URL for a new login attempt.
+    pub async fn authorization_url(&self) -> Result<String> {
+        let meta = self.metadata().await?;
+        let state = random_token();
+        let nonce = random_token();
+        let pkce_verifier = random_token();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pkce_verifier.as_bytes()));
+
+        let url = Url::parse_with_params(
+            &meta.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.created.elapsed() < LOGIN_TTL);
+        pending.insert(
+            state,
+            PendingLogin {
+                nonce,
+                pkce_verifier,
+                created: Instant::now(),
+            },
+        );
+        Ok(url.into())
+    }
+
+    /// Exchange an authorization code and verify the returned ID token.
+    pub async fn complete_login(&self, code: &str, state: &str) -> Result<OidcIdentity> {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(state)
+            .filter(|p| p.created.elapsed() < LOGIN_TTL)
+            .ok_or_else(|| anyhow!("unknown or expired login state"))?;
+        let meta = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", pending.pkce_verifier.as_str()),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let tokens = self
+            .http
+            .post(&meta.token_endpoint)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()
+            .context("token exchange failed")?
+            .json::<TokenResponse>()
+            .await?;
+
+        let claims = self.verify_id_token(meta, &tokens.id_token).await?;
+        if claims.nonce.as_deref() != Some(pending.nonce.as_str()) {
+            bail!("ID token nonce mismatch");
+        }
+
+        let groups = match claims.extra.get(&self.config.groups_claim) {
+            Some(serde_json::Value::Array(items)) => items
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            Some(serde_json::Value::String(g)) => vec![g.clone()],
+            _ => Vec::new(),
+        };
+
+        Ok(OidcIdentity {
+            issuer: meta.issuer.clone(),
+            subject: claims.sub,
+            email: claims.email,
+            name: claims.name.or(claims.preferred_username),
+            groups,
+        })
+    }
+
+    async fn verify_id_token(
+        &self,
+        meta: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+        let jwks = self
+            .http
+            .get(&meta.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or_else(|| anyhow!("no matching JWK for ID token"))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&meta.issuer]);
+        let data = jsonwebtoken::decode::<IdTokenClaims>(
+            id_token,
+            &DecodingKey::from_jwk(jwk)?,
+            &validation,
+        )?;
+        Ok(data.claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_role_map_and_mapping() {
+        let map = parse_role_map("roast-admins=admin, roasters = operator,bogus,x=superuser");
+        assert_eq!(
+            map,
+            vec![
+                ("roast-admins".to_string(), UserRole::Admin),
+                ("roasters".to_string(), UserRole::Operator),
+            ]
+        );
+
+        let config = OidcConfig {
+            issuer_url: "https://idp".to_string(),
+            client_id: "rustroast".to_string(),
+            client_secret: None,
+            redirect_url: "http://localhost/cb".to_string(),
+            scopes: "openid".to_string(),
+            groups_claim: "groups".to_string(),
+            role_map: map,
+            default_role: UserRole::Viewer,
+            post_login_redirect: "/".to_string(),
+        };
+        let groups = |gs: &[&str]| gs.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        assert_eq!(config.map_role(&groups(&[])), UserRole::Viewer);
+        assert_eq!(config.map_role(&groups(&["roasters"])), UserRole::Operator);
+        assert_eq!(
+            config.map_role(&groups(&["roasters", "roast-admins"])),
+            UserRole::Admin
+        );
+    }
+}
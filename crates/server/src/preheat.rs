@@ -0,0 +1,374 @@
+//! Between-batches preheat: back to the charge temp for the next roast.
+//!
+//! With preheat enabled for a roaster (`PUT /api/roaster/{device_id}/preheat`),
+//! completing a session on it switches the roaster to auto, enables the
+//! heater and sets the profile's charge temp, capped at `max_temp`, as the
+//! setpoint, which the firmware PID then holds. [`PreheatMonitor`] follows
+//! the telemetry: once the bean temp is within `ready_band` of the target
+//! the run is ready, which is broadcast to `/ws/telemetry` clients and
+//! POSTed to `RUSTROAST_NOTIFY_WEBHOOK_URL`. The run ends when the next
+//! session starts. The heater is turned off, and a `preheat_failed` alert
+//! raised, when the bean temp exceeds `max_temp` or the device reports an
+//! error. It is also turned off when `timeout_secs` pass without a new
+//! session, alerting only if the roaster never got ready.
+//!
+//! Runs live in memory on the instance that started them, which also
+//! monitors them, so clustered instances never act on the same run.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rustroast_core::DeviceError;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::i18n::Locale;
+use crate::models::{
+    AlertKind, AlertSeverity, PreheatRun, PreheatState, RoastSession, SessionStatus,
+};
+use crate::telemetry::TelemetryEvent;
+use crate::{AppState, ControlOp, EnablePayload, ModePayload, PublishOpts, SetpointPayload};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Webhook body: the run plus a localized title.
+#[derive(Serialize)]
+struct PreheatNotification<'a> {
+    title: &'a str,
+    #[serde(flatten)]
+    run: &'a PreheatRun,
+}
+
+#[derive(Clone)]
+pub struct BatchPreheat {
+    runs: Arc<Mutex<HashMap<String, PreheatRun>>>,
+    changes_tx: broadcast::Sender<PreheatRun>,
+    webhook_url: Option<String>,
+    locale: Locale,
+    http: reqwest::Client,
+}
+
+impl BatchPreheat {
+    pub fn from_env() -> Self {
+        let (changes_tx, _) = broadcast::channel(64);
+        Self {
+            runs: Arc::new(Mutex::new(HashMap::new())),
+            changes_tx,
+            webhook_url: std::env::var("RUSTROAST_NOTIFY_WEBHOOK_URL")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            locale: Locale::server_default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Subscribe to preheat runs as they start, get ready and end.
+    pub fn subscribe(&self) -> broadcast::Receiver<PreheatRun> {
+        self.changes_tx.subscribe()
+    }
+
+    /// The device's latest run, running or not.
+    pub fn run(&self, device_id: &str) -> Option<PreheatRun> {
+        self.runs.lock().unwrap().get(device_id).cloned()
+    }
+
+    fn running(&self) -> Vec<PreheatRun> {
+        self.runs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|r| r.state.is_running())
+            .cloned()
+            .collect()
+    }
+
+    fn insert(&self, run: PreheatRun) {
+        self.runs
+            .lock()
+            .unwrap()
+            .insert(run.device_id.clone(), run.clone());
+        let _ = self.changes_tx.send(run);
+    }
+
+    /// Move the run started at `started_at` to `state`, unless it already
+    /// ended (e.g. cancelled meanwhile). Returns the updated run.
+    fn transition(
+        &self,
+        device_id: &str,
+        started_at: DateTime<Utc>,
+        state: PreheatState,
+        reason: Option<String>,
+    ) -> Option<PreheatRun> {
+        let mut runs = self.runs.lock().unwrap();
+        let run = runs
+            .get_mut(device_id)
+            .filter(|r| r.started_at == started_at && r.state.is_running())?;
+        let now = Utc::now();
+        run.state = state;
+        run.reason = reason;
+        match state {
+            PreheatState::Ready => run.ready_at = Some(now),
+            PreheatState::Heating => {}
+            _ => run.ended_at = Some(now),
+        }
+        let run = run.clone();
+        drop(runs);
+        let _ = self.changes_tx.send(run.clone());
+        Some(run)
+    }
+
+    /// Fire-and-forget webhook POST so a slow receiver never stalls the monitor.
+    fn push_notification(&self, run: &PreheatRun) {
+        let Some(url) = self.webhook_url.clone() else {
+            return;
+        };
+        let request = self.http.post(url).json(&PreheatNotification {
+            title: self.locale.t("preheat_ready"),
+            run,
+        });
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "Preheat notification webhook failed"),
+            }
+        });
+    }
+}
+
+/// Start preheating for the next batch after `session` completed, when its
+/// roaster has preheat enabled and its profile a charge temp.
+pub async fn start_after_session(state: &AppState, session: &RoastSession) {
+    let device_id = session.device_id.as_str();
+    let settings = match state.session_service.get_batch_preheat(device_id).await {
+        Ok(Some(settings)) if settings.enabled => settings,
+        Ok(_) => return,
+        Err(e) => {
+            tracing::warn!(%device_id, error = %e, "Failed to load preheat settings");
+            return;
+        }
+    };
+    let Some(profile_id) = session.profile_id.clone() else {
+        tracing::debug!(session_id = %session.id, "No profile to preheat for");
+        return;
+    };
+    let charge_temp = match state
+        .session_service
+        .get_profile_with_points(&profile_id)
+        .await
+    {
+        Ok(Some(profile)) => profile.profile.charge_temp,
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!(%profile_id, error = %e, "Failed to load profile for preheat");
+            return;
+        }
+    };
+    let Some(charge_temp) = charge_temp else {
+        tracing::debug!(%profile_id, "Profile has no charge temp to preheat to");
+        return;
+    };
+
+    let now = Utc::now();
+    let target_temp = (charge_temp as f64).min(settings.max_temp);
+    let mut run = PreheatRun {
+        device_id: device_id.to_string(),
+        session_id: session.id.clone(),
+        profile_id,
+        target_temp,
+        max_temp: settings.max_temp,
+        ready_band: settings.ready_band,
+        state: PreheatState::Heating,
+        reason: None,
+        started_at: now,
+        deadline: now + chrono::Duration::seconds(settings.timeout_secs),
+        ready_at: None,
+        ended_at: None,
+    };
+    let commands = [
+        ControlOp::Mode(ModePayload {
+            mode: "auto".to_string(),
+        }),
+        ControlOp::HeaterEnable(EnablePayload { enabled: true }),
+        ControlOp::Setpoint(SetpointPayload { value: target_temp }),
+    ];
+    for op in &commands {
+        let resp = crate::publish_control(state, device_id, op, &PublishOpts::default()).await;
+        if !resp.status().is_success() {
+            let reason = format!("{} command failed with status {}", op.name(), resp.status());
+            heater_off(state, device_id).await;
+            run.state = PreheatState::Aborted;
+            run.ended_at = Some(Utc::now());
+            run.reason = Some(reason.clone());
+            state.preheat.insert(run.clone());
+            raise_alert(state, &run, AlertSeverity::Warning, &reason).await;
+            return;
+        }
+    }
+    tracing::info!(%device_id, target_temp, "Preheating for the next batch");
+    state.preheat.insert(run);
+}
+
+/// Stop the device's running preheat and turn the heater off.
+pub async fn cancel(state: &AppState, device_id: &str) -> Option<PreheatRun> {
+    let started_at = state.preheat.run(device_id)?.started_at;
+    let run = state
+        .preheat
+        .transition(device_id, started_at, PreheatState::Cancelled, None)?;
+    heater_off(state, device_id).await;
+    Some(run)
+}
+
+async fn heater_off(state: &AppState, device_id: &str) {
+    let op = ControlOp::HeaterEnable(EnablePayload { enabled: false });
+    let resp = crate::publish_control(state, device_id, &op, &PublishOpts::default()).await;
+    if !resp.status().is_success() {
+        tracing::error!(%device_id, status = %resp.status(), "Failed to turn the heater off after preheat");
+    }
+}
+
+async fn raise_alert(state: &AppState, run: &PreheatRun, severity: AlertSeverity, reason: &str) {
+    let message = format!("Preheat for the next batch stopped: {}", reason);
+    let details = serde_json::json!({
+        "session_id": run.session_id,
+        "target_temp": run.target_temp,
+        "state": run.state,
+    });
+    if let Err(e) = state
+        .alerts
+        .raise(
+            AlertKind::PreheatFailed,
+            severity,
+            Some(&run.device_id),
+            Some(&run.session_id),
+            &message,
+            Some(details),
+        )
+        .await
+    {
+        tracing::warn!(device_id = %run.device_id, error = %e, "Failed to record preheat alert");
+    }
+}
+
+pub struct PreheatMonitor {
+    state: AppState,
+}
+
+impl PreheatMonitor {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                evt = telemetry_rx.recv() => match evt {
+                    Ok(evt) => self.check_telemetry(&evt.device_id, &evt.payload).await,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "Preheat monitor lagged behind telemetry");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ticker.tick() => self.check_deadlines().await,
+            }
+        }
+    }
+
+    async fn check_telemetry(&self, device_id: &str, payload: &serde_json::Value) {
+        let Some(run) = self
+            .state
+            .preheat
+            .run(device_id)
+            .filter(|r| r.state.is_running())
+        else {
+            return;
+        };
+        if let Some(error) = DeviceError::from_payload(payload) {
+            let reason = format!("device reported {}", error);
+            self.abort(&run, reason).await;
+            return;
+        }
+        let Some(bean_temp) = payload.get("beanTemp").and_then(|v| v.as_f64()) else {
+            return;
+        };
+        if bean_temp > run.max_temp {
+            let reason = format!(
+                "bean temp {:.1} °C is above the {:.1} °C limit",
+                bean_temp, run.max_temp
+            );
+            self.abort(&run, reason).await;
+            return;
+        }
+        match self
+            .state
+            .session_service
+            .get_active_session(device_id)
+            .await
+        {
+            Ok(Some(s)) if s.status == SessionStatus::Active && s.id != run.session_id => {
+                self.state.preheat.transition(
+                    device_id,
+                    run.started_at,
+                    PreheatState::Charged,
+                    None,
+                );
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(%device_id, error = %e, "Failed to look up active session for preheat");
+            }
+        }
+        if run.state == PreheatState::Heating
+            && (bean_temp - run.target_temp).abs() <= run.ready_band
+        {
+            if let Some(run) =
+                self.state
+                    .preheat
+                    .transition(device_id, run.started_at, PreheatState::Ready, None)
+            {
+                tracing::info!(%device_id, bean_temp, "Ready for the next charge");
+                self.state.preheat.push_notification(&run);
+            }
+        }
+    }
+
+    async fn check_deadlines(&self) {
+        let now = Utc::now();
+        for run in self.state.preheat.running() {
+            if now < run.deadline {
+                continue;
+            }
+            let reason = "no new session started before the timeout".to_string();
+            let Some(ended) = self.state.preheat.transition(
+                &run.device_id,
+                run.started_at,
+                PreheatState::TimedOut,
+                Some(reason.clone()),
+            ) else {
+                continue;
+            };
+            heater_off(&self.state, &run.device_id).await;
+            if ended.ready_at.is_none() {
+                raise_alert(&self.state, &ended, AlertSeverity::Warning, &reason).await;
+            } else {
+                tracing::info!(device_id = %run.device_id, "Preheat hold timed out, heater off");
+            }
+        }
+    }
+
+    async fn abort(&self, run: &PreheatRun, reason: String) {
+        let Some(ended) = self.state.preheat.transition(
+            &run.device_id,
+            run.started_at,
+            PreheatState::Aborted,
+            Some(reason.clone()),
+        ) else {
+            return;
+        };
+        heater_off(&self.state, &run.device_id).await;
+        raise_alert(&self.state, &ended, AlertSeverity::Critical, &reason).await;
+    }
+}
@@ -0,0 +1,399 @@
+//! Temperature probe fault detection during roasts.
+//!
+//! A failed thermocouple rarely reads as an error: a detached probe keeps
+//! reporting the last value and a shorted one drifts away from the others.
+//! While a session is active, [`ProbeMonitor`] follows `beanTemp`, `envTemp`
+//! and extra bean probes (`beanTemp2`, `beanTemp3`, ...) and finds:
+//!
+//! - a flatline: a reading that hasn't changed at all for
+//!   `RUSTROAST_PROBE_FLATLINE_SECS` (default 60 s);
+//! - a divergence: bean probes more than `RUSTROAST_PROBE_MAX_SPREAD`
+//!   (default 15 °C) apart, or bean and environment probes more than
+//!   [`MAX_BT_ET_SPREAD`] apart, for [`DIVERGENCE_SECS`].
+//!
+//! Each fault is recorded on the session once, marking its data as suspect,
+//! and a `probe_fault` alert is raised until the probes look sane again
+//! during a roast.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::{
+    AlertKind, AlertSeverity, ProbeIssue, ProbeIssueKind, SessionProbeIssue, SessionStatus,
+};
+use crate::telemetry::TelemetryEvent;
+use crate::AppState;
+
+const DEFAULT_FLATLINE_SECS: f64 = 60.0;
+const DEFAULT_MAX_SPREAD: f64 = 15.0;
+/// Largest plausible gap (°C) between a bean and the environment probe.
+pub const MAX_BT_ET_SPREAD: f64 = 150.0;
+/// How long probes must disagree before it counts.
+pub const DIVERGENCE_SECS: f64 = 10.0;
+const BEAN_PROBE: &str = "beanTemp";
+const ENV_PROBE: &str = "envTemp";
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeLimits {
+    pub flatline_secs: f64,
+    /// Largest plausible gap (°C) between two bean probes.
+    pub max_spread: f64,
+}
+
+impl Default for ProbeLimits {
+    fn default() -> Self {
+        Self {
+            flatline_secs: DEFAULT_FLATLINE_SECS,
+            max_spread: DEFAULT_MAX_SPREAD,
+        }
+    }
+}
+
+impl ProbeLimits {
+    pub fn from_env() -> Self {
+        let env = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v > 0.0)
+        };
+        let defaults = Self::default();
+        Self {
+            flatline_secs: env("RUSTROAST_PROBE_FLATLINE_SECS").unwrap_or(defaults.flatline_secs),
+            max_spread: env("RUSTROAST_PROBE_MAX_SPREAD").unwrap_or(defaults.max_spread),
+        }
+    }
+}
+
+/// Probe readings of a telemetry payload, by key.
+fn probe_readings(payload: &serde_json::Value) -> BTreeMap<String, f64> {
+    let Some(fields) = payload.as_object() else {
+        return BTreeMap::new();
+    };
+    fields
+        .iter()
+        .filter(|(key, _)| *key == ENV_PROBE || is_bean_probe(key))
+        .filter_map(|(key, v)| Some((key.clone(), v.as_f64()?)))
+        .collect()
+}
+
+fn is_bean_probe(key: &str) -> bool {
+    key.strip_prefix(BEAN_PROBE)
+        .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+}
+
+struct Probe {
+    value: f64,
+    unchanged_since: f64,
+}
+
+#[derive(Default)]
+struct DeviceProbes {
+    probes: BTreeMap<String, Probe>,
+    /// When each pair of probes started disagreeing.
+    diverging_since: HashMap<(String, String), f64>,
+}
+
+/// Per-device probe tracking, fed one payload at a time.
+pub struct ProbeChecker {
+    limits: ProbeLimits,
+    devices: HashMap<String, DeviceProbes>,
+}
+
+impl ProbeChecker {
+    pub fn new(limits: ProbeLimits) -> Self {
+        Self {
+            limits,
+            devices: HashMap::new(),
+        }
+    }
+
+    /// Update the device's probes with a payload received at `now` (unix
+    /// seconds) and return its current faults.
+    pub fn observe(
+        &mut self,
+        device_id: &str,
+        payload: &serde_json::Value,
+        now: f64,
+    ) -> Vec<ProbeIssue> {
+        let readings = probe_readings(payload);
+        let device = self.devices.entry(device_id.to_string()).or_default();
+        let mut issues = Vec::new();
+        for (key, &value) in &readings {
+            let probe = device.probes.entry(key.clone()).or_insert(Probe {
+                value,
+                unchanged_since: now,
+            });
+            if probe.value != value {
+                probe.value = value;
+                probe.unchanged_since = now;
+            }
+            let stuck = now - probe.unchanged_since;
+            if stuck >= self.limits.flatline_secs {
+                issues.push(ProbeIssue {
+                    kind: ProbeIssueKind::Flatline,
+                    probes: vec![key.clone()],
+                    value,
+                    duration_secs: stuck,
+                });
+            }
+        }
+        let keys: Vec<&String> = readings.keys().collect();
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                let max_spread = if *a == ENV_PROBE || *b == ENV_PROBE {
+                    MAX_BT_ET_SPREAD
+                } else {
+                    self.limits.max_spread
+                };
+                let spread = (readings[*a] - readings[*b]).abs();
+                let pair = (a.to_string(), b.to_string());
+                if spread <= max_spread {
+                    device.diverging_since.remove(&pair);
+                    continue;
+                }
+                let since = *device.diverging_since.entry(pair).or_insert(now);
+                if now - since >= DIVERGENCE_SECS {
+                    issues.push(ProbeIssue {
+                        kind: ProbeIssueKind::Divergence,
+                        probes: vec![a.to_string(), b.to_string()],
+                        value: spread,
+                        duration_secs: now - since,
+                    });
+                }
+            }
+        }
+        issues
+    }
+
+    /// Drop what is known about the device's probes, e.g. between roasts.
+    pub fn forget(&mut self, device_id: &str) {
+        self.devices.remove(device_id);
+    }
+}
+
+pub async fn record(
+    db: &SqlitePool,
+    session_id: &str,
+    device_id: &str,
+    issue: &ProbeIssue,
+    elapsed_seconds: Option<f64>,
+) -> Result<SessionProbeIssue> {
+    let recorded = sqlx::query_as::<_, SessionProbeIssue>(
+        r#"
+        INSERT INTO session_probe_issues
+            (id, session_id, device_id, kind, probes, value, elapsed_seconds, detected_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(session_id)
+    .bind(device_id)
+    .bind(issue.kind)
+    .bind(issue.probes.join(","))
+    .bind(issue.value)
+    .bind(elapsed_seconds)
+    .bind(Utc::now())
+    .fetch_one(db)
+    .await?;
+    Ok(recorded)
+}
+
+pub async fn for_session(db: &SqlitePool, session_id: &str) -> Result<Vec<SessionProbeIssue>> {
+    let issues = sqlx::query_as::<_, SessionProbeIssue>(
+        "SELECT * FROM session_probe_issues WHERE session_id = ? ORDER BY detected_at",
+    )
+    .bind(session_id)
+    .fetch_all(db)
+    .await?;
+    Ok(issues)
+}
+
+/// Faults already recorded on a device's current session.
+struct RecordedFaults {
+    session_id: String,
+    faults: HashSet<(ProbeIssueKind, Vec<String>)>,
+}
+
+/// Checks active sessions' probes and alerts on their faults.
+pub struct ProbeMonitor {
+    state: AppState,
+    checker: ProbeChecker,
+    alerted: HashSet<String>,
+    /// By device.
+    recorded: HashMap<String, RecordedFaults>,
+}
+
+impl ProbeMonitor {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            checker: ProbeChecker::new(ProbeLimits::from_env()),
+            alerted: HashSet::new(),
+            recorded: HashMap::new(),
+        }
+    }
+
+    pub async fn run(mut self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        match self.state.alerts.open_devices(AlertKind::ProbeFault).await {
+            Ok(alerted) => self.alerted = alerted,
+            Err(e) => tracing::warn!(error = %e, "Failed to load open probe fault alerts"),
+        }
+        loop {
+            match telemetry_rx.recv().await {
+                // Only the cluster leader records faults and alerts
+                Ok(evt) if self.state.cluster.is_leader() => {
+                    self.observe(&evt.device_id, &evt.payload).await
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "Probe monitor lagged behind telemetry");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn observe(&mut self, device_id: &str, payload: &serde_json::Value) {
+        // Idle probes may sit still; only roasts are checked
+        let session = match self
+            .state
+            .session_service
+            .get_active_session(device_id)
+            .await
+        {
+            Ok(Some(s)) if s.status == SessionStatus::Active => s,
+            Ok(_) => {
+                self.checker.forget(device_id);
+                self.recorded.remove(device_id);
+                return;
+            }
+            Err(e) => {
+                tracing::warn!(%device_id, error = %e, "Failed to look up active session for probe checks");
+                return;
+            }
+        };
+        let now = Utc::now();
+        let issues =
+            self.checker
+                .observe(device_id, payload, now.timestamp_millis() as f64 / 1000.0);
+        let elapsed = session
+            .start_time
+            .map(|start| (now - start).num_milliseconds() as f64 / 1000.0);
+
+        let fresh = || RecordedFaults {
+            session_id: session.id.clone(),
+            faults: HashSet::new(),
+        };
+        let recorded = self
+            .recorded
+            .entry(device_id.to_string())
+            .or_insert_with(fresh);
+        if recorded.session_id != session.id {
+            *recorded = fresh();
+        }
+        for issue in &issues {
+            if !recorded.faults.insert((issue.kind, issue.probes.clone())) {
+                continue;
+            }
+            if let Err(e) = record(&self.state.db, &session.id, device_id, issue, elapsed).await {
+                tracing::warn!(session_id = %session.id, error = %e, "Failed to record probe fault");
+            }
+        }
+
+        let alerted = self.alerted.contains(device_id);
+        if !issues.is_empty() && !alerted {
+            let faults: Vec<String> = issues
+                .iter()
+                .map(|i| format!("{} {}", i.probes.join("/"), i.kind))
+                .collect();
+            let message = format!(
+                "Temperature probe fault on {}: {}",
+                device_id,
+                faults.join(", ")
+            );
+            let details = serde_json::json!({ "issues": issues });
+            if let Err(e) = self
+                .state
+                .alerts
+                .raise(
+                    AlertKind::ProbeFault,
+                    AlertSeverity::Critical,
+                    Some(device_id),
+                    Some(&session.id),
+                    &message,
+                    Some(details),
+                )
+                .await
+            {
+                tracing::warn!(%device_id, error = %e, "Failed to record probe fault alert");
+            }
+            self.alerted.insert(device_id.to_string());
+        } else if issues.is_empty() && alerted {
+            if let Err(e) = self
+                .state
+                .alerts
+                .resolve_cleared(AlertKind::ProbeFault, device_id)
+                .await
+            {
+                tracing::warn!(%device_id, error = %e, "Failed to resolve probe fault alert");
+            }
+            self.alerted.remove(device_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_flatlined_and_diverging_probes() {
+        let mut checker = ProbeChecker::new(ProbeLimits::default());
+        let t0 = 1_700_000_000.0;
+        let sample = |bt: f64, bt2: f64, et: f64| serde_json::json!({"beanTemp": bt, "beanTemp2": bt2, "envTemp": et, "heaterPWM": 80});
+
+        for s in 0..30 {
+            let t = s as f64;
+            let issues = checker.observe("dev1", &sample(100.0 + t, 101.0 + t, 180.0 + t), t0 + t);
+            assert!(issues.is_empty());
+        }
+        // The second bean probe comes loose and stays at 130, more than
+        // 15 °C off from t = 46
+        for s in 30..56 {
+            let t = s as f64;
+            checker.observe("dev1", &sample(100.0 + t, 130.0, 180.0 + t), t0 + t);
+        }
+        let issues = checker.observe("dev1", &sample(156.0, 130.0, 236.0), t0 + 56.0);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, ProbeIssueKind::Divergence);
+        assert_eq!(issues[0].probes, ["beanTemp", "beanTemp2"]);
+        assert_eq!(issues[0].duration_secs, 10.0);
+
+        let issues = checker.observe("dev1", &sample(190.0, 130.0, 260.0), t0 + 90.0);
+        let kinds: Vec<_> = issues.iter().map(|i| (i.kind, i.probes.len())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (ProbeIssueKind::Flatline, 1),
+                (ProbeIssueKind::Divergence, 2)
+            ]
+        );
+        assert_eq!(issues[0].value, 130.0);
+
+        checker.forget("dev1");
+        assert!(checker
+            .observe("dev1", &sample(190.0, 130.0, 260.0), t0 + 91.0)
+            .is_empty());
+        // Bean and environment probes may be far apart, within reason
+        let wild = serde_json::json!({"beanTemp": 20.0, "envTemp": 400.0});
+        checker.observe("dev2", &wild, t0);
+        assert_eq!(checker.observe("dev2", &wild, t0 + 10.0).len(), 1);
+    }
+}
@@ -0,0 +1,184 @@
+//! Interpolated profile curves, cached per profile version.
+//!
+//! The scoreboard, the tolerance monitor and the chart endpoint all look up
+//! a profile's target temperature many times a second during a roast. A
+//! [`ProfileCurve`] samples the curve once at a fixed step, and
+//! [`ProfileCurveCache`] keeps it until the profile changes: entries are
+//! keyed by the profile's `updated_at`, so an edit made by another instance
+//! is picked up too, and local edits and deletes drop them right away.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{ProfilePoint, ProfileWithPoints};
+use crate::services::profile_target_temp;
+
+/// Step curves are sampled at. Profile points lie on whole seconds, so at
+/// one second sampling loses nothing.
+pub const DEFAULT_STEP_SECS: f32 = 1.0;
+
+/// A profile's target temperature sampled every `step` seconds from its
+/// first point to its last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileCurve {
+    start: f32,
+    end: f32,
+    step: f32,
+    samples: Vec<f32>,
+}
+
+impl ProfileCurve {
+    /// Sample points sorted by `time_seconds`; `None` without points.
+    pub fn sample(points: &[ProfilePoint], step: f32) -> Option<Self> {
+        let start = points.first()?.time_seconds as f32;
+        let end = points.last()?.time_seconds as f32;
+        let step = step.max(0.001);
+        let count = ((end - start) / step).ceil() as usize + 1;
+        let samples = (0..count)
+            .map(|i| (start + i as f32 * step).min(end))
+            .map(|t| profile_target_temp(points, t))
+            .collect::<Option<Vec<f32>>>()?;
+        Some(Self {
+            start,
+            end,
+            step,
+            samples,
+        })
+    }
+
+    /// Target temperature at `t` seconds into the profile, linear between
+    /// samples and clamped to the end points like [`profile_target_temp`].
+    pub fn target_at(&self, t: f32) -> f32 {
+        let last = self.samples.len() - 1;
+        if t <= self.start || last == 0 {
+            return self.samples[0];
+        }
+        if t >= self.end {
+            return self.samples[last];
+        }
+        let i = (((t - self.start) / self.step) as usize).min(last - 1);
+        let t0 = self.start + i as f32 * self.step;
+        let t1 = (t0 + self.step).min(self.end);
+        let f = ((t - t0) / (t1 - t0)).clamp(0.0, 1.0);
+        self.samples[i] + f * (self.samples[i + 1] - self.samples[i])
+    }
+}
+
+#[derive(Default)]
+struct CachedCurves {
+    updated_at: DateTime<Utc>,
+    /// By step in milliseconds.
+    by_step: HashMap<u32, Arc<ProfileCurve>>,
+}
+
+/// Read-through cache of [`ProfileCurve`]s by profile version and step.
+#[derive(Default)]
+pub struct ProfileCurveCache {
+    profiles: RwLock<HashMap<String, CachedCurves>>,
+}
+
+impl ProfileCurveCache {
+    /// The curve of `profile` at `step` seconds, sampled on first use.
+    pub fn get(&self, profile: &ProfileWithPoints, step: f32) -> Option<Arc<ProfileCurve>> {
+        let id = &profile.profile.id;
+        let updated_at = profile.profile.updated_at;
+        let key = (step * 1000.0).round() as u32;
+        if let Some(curve) = self
+            .profiles
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|cached| cached.updated_at == updated_at)
+            .and_then(|cached| cached.by_step.get(&key))
+        {
+            return Some(curve.clone());
+        }
+        let curve = Arc::new(ProfileCurve::sample(&profile.points, step)?);
+        let mut profiles = self.profiles.write().unwrap();
+        let cached = profiles.entry(id.clone()).or_default();
+        if cached.updated_at != updated_at {
+            *cached = CachedCurves {
+                updated_at,
+                by_step: HashMap::new(),
+            };
+        }
+        cached.by_step.insert(key, curve.clone());
+        Some(curve)
+    }
+
+    /// Drop the curves of a profile that was edited or deleted.
+    pub fn invalidate(&self, profile_id: &str) {
+        self.profiles.write().unwrap().remove(profile_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RoastProfile;
+
+    fn profile(updated_at: DateTime<Utc>, points: &[(i32, f32)]) -> ProfileWithPoints {
+        ProfileWithPoints {
+            profile: RoastProfile {
+                id: "p1".to_string(),
+                name: "Curve".to_string(),
+                description: None,
+                created_by: None,
+                created_at: updated_at,
+                updated_at,
+                is_public: false,
+                target_total_time: None,
+                target_first_crack: None,
+                target_end_temp: None,
+                preheat_temp: None,
+                charge_temp: None,
+                batch_size_g: None,
+                heater_cap: None,
+                parent_profile_id: None,
+                version: 1,
+            },
+            points: points
+                .iter()
+                .map(|&(time_seconds, target_temp)| ProfilePoint {
+                    id: time_seconds.to_string(),
+                    profile_id: "p1".to_string(),
+                    time_seconds,
+                    target_temp,
+                    fan_speed: None,
+                    notes: None,
+                    created_at: updated_at,
+                    target_env_temp: None,
+                    target_airflow: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_curve_matches_interpolation_and_follows_versions() {
+        let v1 = profile(Utc::now(), &[(30, 100.0), (90, 160.0), (601, 220.0)]);
+        let curve = ProfileCurve::sample(&v1.points, DEFAULT_STEP_SECS).unwrap();
+        for t in [0.0, 30.0, 45.5, 90.0, 123.25, 600.5, 601.0, 900.0] {
+            let exact = profile_target_temp(&v1.points, t).unwrap();
+            assert!((curve.target_at(t) - exact).abs() < 1e-3, "at {t}");
+        }
+        let coarse = ProfileCurve::sample(&v1.points, 7.0).unwrap();
+        assert_eq!(coarse.target_at(601.0), 220.0);
+        assert!(ProfileCurve::sample(&[], 1.0).is_none());
+
+        let cache = ProfileCurveCache::default();
+        let first = cache.get(&v1, 1.0).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.get(&v1, 1.0).unwrap()));
+        assert!(!Arc::ptr_eq(&first, &cache.get(&v1, 5.0).unwrap()));
+
+        let v2 = profile(
+            v1.profile.updated_at + chrono::Duration::seconds(1),
+            &[(0, 50.0)],
+        );
+        assert_eq!(cache.get(&v2, 1.0).unwrap().target_at(100.0), 50.0);
+        cache.invalidate("p1");
+        assert!(!Arc::ptr_eq(&first, &cache.get(&v1, 1.0).unwrap()));
+    }
+}
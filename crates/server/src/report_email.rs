@@ -0,0 +1,402 @@
+//! Daily production report by email.
+//!
+//! With SMTP configured (see [`ReportMailer::from_env`]) and the
+//! `report_email_enabled` setting `true`, the leader emails the day's
+//! [`DailyReport`] to the `report_email_recipients` setting (comma
+//! separated) once it is past `report_email_time` (`HH:MM` UTC, default
+//! 18:00). The mail sums up the day, or says there were no roasts, with the
+//! per-session costs attached as CSV. Sends are recorded in `report_emails`
+//! so each day's scheduled mail goes out once.
+//! `POST /api/admin/reports/daily/email` sends one right away.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::cluster::Cluster;
+use crate::models::{DailyReport, ReportEmail};
+use crate::services::RoastSessionService;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_SEND_TIME: (u32, u32) = (18, 0);
+
+struct Smtp {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+#[derive(Clone, Default)]
+pub struct ReportMailer {
+    smtp: Option<Arc<Smtp>>,
+}
+
+impl ReportMailer {
+    /// SMTP from `SMTP_HOST`, `SMTP_PORT`, `SMTP_SECURITY` (`starttls`,
+    /// the default, `tls` or `none`), `SMTP_USERNAME` / `SMTP_PASSWORD` and
+    /// `SMTP_FROM`. Unconfigured without a host; a configuration that can't
+    /// be used is logged and left unconfigured.
+    pub fn from_env() -> Self {
+        match smtp_from_env() {
+            Ok(smtp) => Self {
+                smtp: smtp.map(Arc::new),
+            },
+            Err(e) => {
+                tracing::error!(error = %e, "Invalid SMTP configuration; report emails are off");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.smtp.is_some()
+    }
+
+    async fn send(&self, report: &DailyReport, recipients: &[Mailbox]) -> Result<()> {
+        let smtp = self
+            .smtp
+            .as_deref()
+            .ok_or_else(|| anyhow!("SMTP is not configured"))?;
+        let mut message = Message::builder()
+            .from(smtp.from.clone())
+            .subject(subject(report));
+        for recipient in recipients {
+            message = message.to(recipient.clone());
+        }
+        let csv = Attachment::new(format!("daily-report-{}.csv", report.date))
+            .body(report_csv(report), ContentType::parse("text/csv")?);
+        let message = message.multipart(
+            MultiPart::mixed()
+                .singlepart(SinglePart::plain(body(report)))
+                .singlepart(csv),
+        )?;
+        smtp.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+fn smtp_from_env() -> Result<Option<Smtp>> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let Some(host) = var("SMTP_HOST") else {
+        return Ok(None);
+    };
+    let from: Mailbox = var("SMTP_FROM")
+        .ok_or_else(|| anyhow!("SMTP_FROM is required with SMTP_HOST"))?
+        .parse()?;
+    let mut builder = match var("SMTP_SECURITY").as_deref() {
+        None | Some("starttls") => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+        Some("tls") => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+        Some("none") => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+        Some(other) => bail!("SMTP_SECURITY must be starttls, tls or none, not {other}"),
+    };
+    if let Some(port) = var("SMTP_PORT").and_then(|p| p.parse::<u16>().ok()) {
+        builder = builder.port(port);
+    }
+    if let Some(username) = var("SMTP_USERNAME") {
+        let password = var("SMTP_PASSWORD").unwrap_or_default();
+        builder = builder.credentials(Credentials::new(username, password));
+    }
+    Ok(Some(Smtp {
+        transport: builder.build(),
+        from,
+    }))
+}
+
+/// The `report_email_*` settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportEmailSettings {
+    pub enabled: bool,
+    pub recipients: Vec<String>,
+    pub send_at: NaiveTime,
+}
+
+impl Default for ReportEmailSettings {
+    fn default() -> Self {
+        let (hour, minute) = DEFAULT_SEND_TIME;
+        Self {
+            enabled: false,
+            recipients: Vec::new(),
+            send_at: NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+        }
+    }
+}
+
+pub async fn settings(db: &SqlitePool) -> Result<ReportEmailSettings> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT key, value FROM settings WHERE key IN ('report_email_enabled', 'report_email_recipients', 'report_email_time')",
+    )
+    .fetch_all(db)
+    .await?;
+    let mut settings = ReportEmailSettings::default();
+    for (key, value) in rows {
+        match key.as_str() {
+            "report_email_enabled" => settings.enabled = value == "true" || value == "1",
+            "report_email_recipients" => settings.recipients = parse_recipients(&value),
+            "report_email_time" => {
+                if let Ok(time) = NaiveTime::parse_from_str(value.trim(), "%H:%M") {
+                    settings.send_at = time;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(settings)
+}
+
+fn parse_recipients(list: &str) -> Vec<String> {
+    list.split([',', ';'])
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse recipient addresses, naming the ones that aren't valid.
+pub fn mailboxes(recipients: &[String]) -> Result<Vec<Mailbox>> {
+    let mut parsed = Vec::with_capacity(recipients.len());
+    let mut invalid = Vec::new();
+    for recipient in recipients {
+        match recipient.parse::<Mailbox>() {
+            Ok(mailbox) => parsed.push(mailbox),
+            Err(_) => invalid.push(recipient.as_str()),
+        }
+    }
+    if !invalid.is_empty() {
+        bail!("invalid recipient address: {}", invalid.join(", "));
+    }
+    Ok(parsed)
+}
+
+fn subject(report: &DailyReport) -> String {
+    match report.session_count {
+        0 => format!("rustRoast daily report {}: no roasts", report.date),
+        1 => format!("rustRoast daily report {}: 1 roast", report.date),
+        n => format!("rustRoast daily report {}: {} roasts", report.date, n),
+    }
+}
+
+fn body(report: &DailyReport) -> String {
+    if report.session_count == 0 {
+        return format!("No roasts were completed on {}.\n", report.date);
+    }
+    let mut body = format!(
+        "Roasts completed on {}: {}\nGreen: {:.2} kg\nRoasted: {:.2} kg\nTotal cost: {:.2} {}\n",
+        report.date,
+        report.session_count,
+        report.green_kg,
+        report.roasted_kg,
+        report.total_cost,
+        report.currency,
+    );
+    if let Some(per_kg) = report.cost_per_roasted_kg {
+        body.push_str(&format!(
+            "Cost per roasted kg: {:.2} {}\n",
+            per_kg, report.currency
+        ));
+    }
+    body.push_str("\nThe attached CSV has the costs per session.\n");
+    body
+}
+
+/// One row per session of the report, with its cost breakdown.
+fn report_csv(report: &DailyReport) -> String {
+    let opt = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+    let mut csv = String::from(
+        "session_id,currency,green_kg,roasted_kg,bean_cost,energy_kwh,energy_cost,roast_hours,labor_cost,overhead_cost,total_cost,cost_per_roasted_kg\n",
+    );
+    for cost in &report.sessions {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            cost.session_id,
+            cost.currency,
+            opt(cost.green_kg),
+            opt(cost.roasted_kg),
+            opt(cost.bean_cost),
+            cost.energy_kwh,
+            cost.energy_cost,
+            cost.roast_hours,
+            cost.labor_cost,
+            cost.overhead_cost,
+            cost.total_cost,
+            opt(cost.cost_per_roasted_kg),
+        ));
+    }
+    csv
+}
+
+/// Email the report for `date` and record the send.
+pub async fn send_report(
+    mailer: &ReportMailer,
+    sessions: &RoastSessionService,
+    db: &SqlitePool,
+    date: NaiveDate,
+    recipients: &[Mailbox],
+    sent_by: Option<&str>,
+) -> Result<ReportEmail> {
+    let report = sessions.daily_report(date).await?;
+    mailer.send(&report, recipients).await?;
+    let recipients: Vec<String> = recipients.iter().map(|r| r.email.to_string()).collect();
+    let sent = sqlx::query_as::<_, ReportEmail>(
+        r#"
+        INSERT INTO report_emails (id, date, sent_at, recipients, session_count, sent_by)
+        VALUES (?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(date)
+    .bind(Utc::now())
+    .bind(sqlx::types::Json(&recipients))
+    .bind(report.session_count as i64)
+    .bind(sent_by)
+    .fetch_one(db)
+    .await?;
+    Ok(sent)
+}
+
+/// The most recent sends, newest first.
+pub async fn list_sent(db: &SqlitePool, limit: i64) -> Result<Vec<ReportEmail>> {
+    Ok(sqlx::query_as::<_, ReportEmail>(
+        "SELECT * FROM report_emails ORDER BY sent_at DESC LIMIT ?",
+    )
+    .bind(limit)
+    .fetch_all(db)
+    .await?)
+}
+
+/// Send today's report if it is enabled, past the send time and not sent
+/// on schedule yet. Returns the send, if any.
+async fn send_due(
+    mailer: &ReportMailer,
+    sessions: &RoastSessionService,
+    db: &SqlitePool,
+    now: DateTime<Utc>,
+) -> Result<Option<ReportEmail>> {
+    let settings = settings(db).await?;
+    let date = now.date_naive();
+    if !settings.enabled || settings.recipients.is_empty() || now.time() < settings.send_at {
+        return Ok(None);
+    }
+    let sent: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM report_emails WHERE date = ? AND sent_by IS NULL)",
+    )
+    .bind(date)
+    .fetch_one(db)
+    .await?;
+    if sent {
+        return Ok(None);
+    }
+    let recipients = mailboxes(&settings.recipients)?;
+    Ok(Some(
+        send_report(mailer, sessions, db, date, &recipients, None).await?,
+    ))
+}
+
+/// Check every minute for a report due, on the cluster leader.
+pub async fn run(
+    mailer: ReportMailer,
+    sessions: RoastSessionService,
+    db: SqlitePool,
+    cluster: Cluster,
+) {
+    let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        ticker.tick().await;
+        if !cluster.is_leader() {
+            continue;
+        }
+        match send_due(&mailer, &sessions, &db, Utc::now()).await {
+            Ok(Some(sent)) => tracing::info!(
+                date = %sent.date,
+                recipients = sent.recipients.len(),
+                "Emailed the daily report"
+            ),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "Daily report email failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SessionCost;
+
+    fn report(sessions: Vec<SessionCost>) -> DailyReport {
+        DailyReport {
+            date: NaiveDate::from_ymd_opt(2026, 3, 2).unwrap(),
+            currency: "EUR".to_string(),
+            session_count: sessions.len(),
+            green_kg: 1.0,
+            roasted_kg: 0.85,
+            total_cost: 12.5,
+            cost_per_roasted_kg: Some(14.71),
+            sessions,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_settings_and_report_contents() {
+        let db = crate::init_memory_db().await.unwrap();
+        assert_eq!(settings(&db).await.unwrap(), ReportEmailSettings::default());
+        for (key, value) in [
+            ("report_email_enabled", "true"),
+            (
+                "report_email_recipients",
+                "roastery@example.com; Ana <ana@example.com>,",
+            ),
+            ("report_email_time", "07:30"),
+        ] {
+            sqlx::query(
+                "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, datetime('now'))",
+            )
+            .bind(key)
+            .bind(value)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        let s = settings(&db).await.unwrap();
+        assert!(s.enabled);
+        assert_eq!(s.send_at, NaiveTime::from_hms_opt(7, 30, 0).unwrap());
+        assert_eq!(mailboxes(&s.recipients).unwrap().len(), 2);
+        assert!(mailboxes(&["not an address".to_string()]).is_err());
+
+        let empty = report(Vec::new());
+        assert_eq!(
+            subject(&empty),
+            "rustRoast daily report 2026-03-02: no roasts"
+        );
+        assert!(body(&empty).starts_with("No roasts"));
+        assert_eq!(report_csv(&empty).lines().count(), 1);
+
+        let one = report(vec![SessionCost {
+            session_id: "s1".to_string(),
+            currency: "EUR".to_string(),
+            green_kg: Some(1.0),
+            roasted_kg: Some(0.85),
+            bean_cost: None,
+            energy_kwh: 0.5,
+            energy_cost: 0.15,
+            roast_hours: 0.2,
+            labor_cost: 4.0,
+            overhead_cost: 8.35,
+            total_cost: 12.5,
+            cost_per_roasted_kg: Some(14.71),
+        }]);
+        assert_eq!(subject(&one), "rustRoast daily report 2026-03-02: 1 roast");
+        assert!(body(&one).contains("Total cost: 12.50 EUR"));
+        let csv = report_csv(&one);
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("s1,EUR,1,0.85,,0.5,0.15,0.2,4,8.35,12.5,14.71")
+        );
+    }
+}
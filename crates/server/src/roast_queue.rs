@@ -0,0 +1,323 @@
+//! Production queue: planned batches on a "what's next" board.
+//!
+//! Sessions still in planning are queued with `POST /api/queue` and then
+//! move across the board on their own as [`QueueMonitor`] follows their
+//! session and events: `preheating` while their roaster preheats and they
+//! are its next batch, `roasting` once the session starts, `cooling` from
+//! the drop (or the end of a session without one) and `done` once the
+//! session is completed and `RUSTROAST_QUEUE_COOLING_SECS` (default 240)
+//! have passed since. Failed and cancelled sessions are done right away.
+//! Every change is broadcast to `/ws/telemetry` clients.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, SqlitePool};
+use tokio::sync::broadcast;
+
+use crate::models::{QueueEntry, QueueState, SessionActivity, SessionStatus};
+use crate::AppState;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+const DEFAULT_COOLING_SECS: i64 = 240;
+pub const DEFAULT_DONE_HOURS: i64 = 12;
+
+const ENTRY_COLUMNS: &str = "q.session_id, s.name AS session_name, s.device_id, s.profile_id, \
+     q.position, q.state, q.state_changed_at, q.created_at";
+
+#[derive(Clone)]
+pub struct RoastQueue {
+    db: SqlitePool,
+    changes_tx: broadcast::Sender<QueueEntry>,
+    /// How long a dropped batch cools before it is done.
+    cooling: chrono::Duration,
+}
+
+/// An entry not done yet, with what its next state is derived from.
+#[derive(FromRow)]
+struct OpenEntry {
+    #[sqlx(flatten)]
+    entry: QueueEntry,
+    status: SessionStatus,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    /// Elapsed seconds of the first drop or drop out event.
+    drop_secs: Option<f64>,
+}
+
+impl RoastQueue {
+    pub fn from_env(db: SqlitePool) -> Self {
+        let cooling_secs = std::env::var("RUSTROAST_QUEUE_COOLING_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_COOLING_SECS);
+        let (changes_tx, _) = broadcast::channel(64);
+        Self {
+            db,
+            changes_tx,
+            cooling: chrono::Duration::seconds(cooling_secs),
+        }
+    }
+
+    /// Subscribe to entries as they are queued and change state.
+    pub fn subscribe(&self) -> broadcast::Receiver<QueueEntry> {
+        self.changes_tx.subscribe()
+    }
+
+    /// Queue a planned session at the end of the board.
+    pub async fn enqueue(&self, session_id: &str, now: DateTime<Utc>) -> Result<QueueEntry> {
+        sqlx::query(
+            r#"
+            INSERT INTO roast_queue (session_id, position, state, state_changed_at, created_at)
+            SELECT ?, COALESCE(MAX(position), 0) + 1, ?, ?, ? FROM roast_queue
+            "#,
+        )
+        .bind(session_id)
+        .bind(QueueState::Queued)
+        .bind(now)
+        .bind(now)
+        .execute(&self.db)
+        .await?;
+        let entry = self
+            .get(session_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("queued session {session_id} has no session"))?;
+        let _ = self.changes_tx.send(entry.clone());
+        Ok(entry)
+    }
+
+    pub async fn get(&self, session_id: &str) -> Result<Option<QueueEntry>> {
+        let entry = sqlx::query_as::<_, QueueEntry>(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM roast_queue q \
+             JOIN roast_sessions s ON s.id = q.session_id WHERE q.session_id = ?"
+        ))
+        .bind(session_id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(entry)
+    }
+
+    /// Take a batch off the board. Returns whether it was on it.
+    pub async fn remove(&self, session_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM roast_queue WHERE session_id = ?")
+            .bind(session_id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Open batches and those done since `done_since`, in board order.
+    pub async fn board(&self, done_since: DateTime<Utc>) -> Result<Vec<QueueEntry>> {
+        let entries = sqlx::query_as::<_, QueueEntry>(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM roast_queue q \
+             JOIN roast_sessions s ON s.id = q.session_id \
+             WHERE q.state != ? OR q.state_changed_at >= ? ORDER BY q.position"
+        ))
+        .bind(QueueState::Done)
+        .bind(done_since)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(entries)
+    }
+
+    /// Move open batches to the state their session data says.
+    /// `preheating(device_id)` tells whether a roaster is preheating.
+    /// Returns the entries that changed.
+    pub async fn refresh(
+        &self,
+        preheating: impl Fn(&str) -> bool,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<QueueEntry>> {
+        let open = sqlx::query_as::<_, OpenEntry>(&format!(
+            r#"
+            SELECT {ENTRY_COLUMNS}, s.status, s.start_time, s.end_time,
+                (SELECT MIN(e.elapsed_seconds) FROM roast_events e
+                 WHERE e.session_id = s.id AND e.event_type IN ('drop', 'drop_out')) AS drop_secs
+            FROM roast_queue q JOIN roast_sessions s ON s.id = q.session_id
+            WHERE q.state != ? ORDER BY q.position
+            "#
+        ))
+        .bind(QueueState::Done)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut next_up = HashSet::new();
+        let mut changed = Vec::new();
+        for open in open {
+            // A roaster preheats for its first planned batch only
+            let next_batch = open.status == SessionStatus::Planning
+                && next_up.insert(open.entry.device_id.clone());
+            let preheating = next_batch && preheating(&open.entry.device_id);
+            let target = target_state(&open, preheating, now, self.cooling);
+            let Some(state) = open.entry.state.advance(target) else {
+                continue;
+            };
+            sqlx::query(
+                "UPDATE roast_queue SET state = ?, state_changed_at = ? WHERE session_id = ?",
+            )
+            .bind(state)
+            .bind(now)
+            .bind(&open.entry.session_id)
+            .execute(&self.db)
+            .await?;
+            let entry = QueueEntry {
+                state,
+                state_changed_at: now,
+                ..open.entry
+            };
+            let _ = self.changes_tx.send(entry.clone());
+            changed.push(entry);
+        }
+        Ok(changed)
+    }
+}
+
+/// The state a batch's session data puts it in.
+fn target_state(
+    open: &OpenEntry,
+    preheating: bool,
+    now: DateTime<Utc>,
+    cooling: chrono::Duration,
+) -> QueueState {
+    let dropped_at = open
+        .start_time
+        .zip(open.drop_secs)
+        .map(|(start, secs)| start + chrono::Duration::milliseconds((secs * 1000.0) as i64));
+    match open.status {
+        SessionStatus::Planning if preheating => QueueState::Preheating,
+        SessionStatus::Planning => QueueState::Queued,
+        SessionStatus::Active | SessionStatus::Paused if dropped_at.is_some() => {
+            QueueState::Cooling
+        }
+        SessionStatus::Active | SessionStatus::Paused => QueueState::Roasting,
+        SessionStatus::Completed => match dropped_at.or(open.end_time) {
+            Some(since) if now - since < cooling => QueueState::Cooling,
+            _ => QueueState::Done,
+        },
+        SessionStatus::Failed | SessionStatus::Cancelled => QueueState::Done,
+    }
+}
+
+pub struct QueueMonitor {
+    state: AppState,
+}
+
+impl QueueMonitor {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Refresh the board on session activity, and periodically for
+    /// preheats and cooling times.
+    pub async fn run(self, mut activity_rx: broadcast::Receiver<SessionActivity>) {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                activity = activity_rx.recv() => {
+                    if let Err(broadcast::error::RecvError::Closed) = activity {
+                        break;
+                    }
+                }
+                _ = ticker.tick() => {}
+            }
+            if !self.state.cluster.is_leader() {
+                continue;
+            }
+            let preheat = &self.state.preheat;
+            let preheating =
+                |device_id: &str| preheat.run(device_id).is_some_and(|r| r.state.is_running());
+            if let Err(e) = self.state.roast_queue.refresh(preheating, Utc::now()).await {
+                tracing::warn!(error = %e, "Failed to refresh the roast queue");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateRoastEventRequest, CreateSessionRequest, RoastEventType};
+    use crate::services::RoastSessionService;
+
+    #[tokio::test]
+    async fn test_batches_move_across_the_board() {
+        let db = crate::init_memory_db().await.unwrap();
+        let sessions = RoastSessionService::new(db.clone());
+        let queue = RoastQueue::from_env(db);
+        let plan = |name: &str| CreateSessionRequest {
+            name: name.to_string(),
+            device_id: "dev1".to_string(),
+            profile_id: None,
+            bean_origin: None,
+            bean_variety: None,
+            green_weight: None,
+            target_roast_level: None,
+            notes: None,
+            ambient_temp: None,
+            humidity: None,
+            roaster: None,
+            session_type: Default::default(),
+            bean_id: None,
+        };
+        let first = sessions.create_session(plan("first")).await.unwrap();
+        let second = sessions.create_session(plan("second")).await.unwrap();
+        let now = Utc::now();
+        queue.enqueue(&first.id, now).await.unwrap();
+        let entry = queue.enqueue(&second.id, now).await.unwrap();
+        assert_eq!(entry.position, 2);
+        assert_eq!(entry.state, QueueState::Queued);
+
+        let state_of = |id: &str| {
+            let queue = queue.clone();
+            let id = id.to_string();
+            async move { queue.get(&id).await.unwrap().unwrap().state }
+        };
+        // Only the roaster's next batch preheats
+        let changed = queue.refresh(|_| true, now).await.unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(state_of(&first.id).await, QueueState::Preheating);
+        assert_eq!(state_of(&second.id).await, QueueState::Queued);
+        // The preheat stopped
+        queue.refresh(|_| false, now).await.unwrap();
+        assert_eq!(state_of(&first.id).await, QueueState::Queued);
+
+        sessions.start_session(&first.id).await.unwrap();
+        queue.refresh(|_| true, now).await.unwrap();
+        assert_eq!(state_of(&first.id).await, QueueState::Roasting);
+        assert_eq!(state_of(&second.id).await, QueueState::Preheating);
+
+        sessions
+            .create_roast_event(
+                &first.id,
+                CreateRoastEventRequest {
+                    event_type: RoastEventType::Drop,
+                    elapsed_seconds: 0.0,
+                    temperature: None,
+                    notes: None,
+                },
+            )
+            .await
+            .unwrap();
+        queue.refresh(|_| true, now).await.unwrap();
+        assert_eq!(state_of(&first.id).await, QueueState::Cooling);
+        sessions.complete_session(&first.id).await.unwrap();
+        queue.refresh(|_| true, Utc::now()).await.unwrap();
+        assert_eq!(state_of(&first.id).await, QueueState::Cooling);
+        let later = Utc::now() + chrono::Duration::seconds(DEFAULT_COOLING_SECS + 1);
+        queue.refresh(|_| true, later).await.unwrap();
+        assert_eq!(state_of(&first.id).await, QueueState::Done);
+
+        // Done batches drop off the board after a while
+        assert_eq!(queue.board(now).await.unwrap().len(), 2);
+        assert_eq!(
+            queue
+                .board(later + chrono::Duration::seconds(1))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
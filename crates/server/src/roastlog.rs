@@ -0,0 +1,471 @@
+//! `.rroastlog` files: one completed roast in a single zip, for long-term
+//! archiving and moving sessions between instances.
+//!
+//! The zip holds `manifest.json` (format version and the size and SHA-256
+//! of every other file), `session.json`, `events.json`,
+//! `telemetry.parquet`, a standalone `report.html`, and under
+//! `attachments/` the session's cupping and charge adjustment when it has
+//! them. Import checks every file against the manifest and refuses files it
+//! doesn't list, then inserts the session under its original id.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{Cursor, Read, Write};
+
+use anyhow::Result;
+use axum::body::Bytes;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::archive::{format_secs, telemetry_from_parquet, telemetry_parquet};
+use crate::charge_correction;
+use crate::export_signing::sha256_hex;
+use crate::i18n::Locale;
+use crate::models::{
+    ChargeAdjustment, CuppingWithAttributes, RoastEvent, RoastSession, RoastlogFile,
+    RoastlogImport, RoastlogManifest, SessionArchiveBundle, SessionStatus,
+};
+use crate::services::RoastSessionService;
+
+pub const FORMAT: &str = "rroastlog";
+pub const FORMAT_VERSION: u32 = 1;
+/// Largest file (uncompressed) accepted inside an import.
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+const MANIFEST: &str = "manifest.json";
+const SESSION: &str = "session.json";
+const EVENTS: &str = "events.json";
+const TELEMETRY: &str = "telemetry.parquet";
+const REPORT: &str = "report.html";
+const CUPPING: &str = "attachments/cupping.json";
+const CHARGE_ADJUSTMENT: &str = "attachments/charge-adjustment.json";
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// Not a `.rroastlog` file this version can read, or one that was
+    /// altered after export.
+    Invalid(String),
+    /// The session already exists here.
+    Exists(String),
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ImportError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Other(e)
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> ImportError {
+    ImportError::Invalid(msg.into())
+}
+
+/// Build the `.rroastlog` of a completed session.
+pub async fn export(
+    sessions: &RoastSessionService,
+    db: &SqlitePool,
+    bundle: &SessionArchiveBundle,
+) -> Result<Vec<u8>> {
+    let session = &bundle.session;
+    let mut files: Vec<(&str, Vec<u8>)> = vec![
+        (SESSION, serde_json::to_vec_pretty(session)?),
+        (EVENTS, serde_json::to_vec_pretty(&bundle.events)?),
+        (TELEMETRY, telemetry_parquet(&bundle.telemetry)?),
+        (
+            REPORT,
+            session_report_html(bundle, Locale::server_default()).into_bytes(),
+        ),
+    ];
+    if let Some(cupping) = sessions.get_cupping(&session.id).await? {
+        files.push((CUPPING, serde_json::to_vec_pretty(&cupping)?));
+    }
+    if let Some(recorded) = charge_correction::for_session(db, &session.id).await? {
+        files.push((
+            CHARGE_ADJUSTMENT,
+            serde_json::to_vec_pretty(&recorded.adjustment)?,
+        ));
+    }
+    let manifest = RoastlogManifest {
+        format: FORMAT.to_string(),
+        format_version: FORMAT_VERSION,
+        session_id: session.id.clone(),
+        exported_at: Utc::now(),
+        exported_by: format!("rustroast {}", env!("CARGO_PKG_VERSION")),
+        files: files
+            .iter()
+            .map(|(path, bytes)| RoastlogFile {
+                path: path.to_string(),
+                size: bytes.len() as u64,
+                sha256: sha256_hex(bytes),
+            })
+            .collect(),
+    };
+
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file(MANIFEST, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    for (path, bytes) in &files {
+        zip.start_file(*path, options)?;
+        zip.write_all(bytes)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Download name, `{session name}_{date}.rroastlog`.
+pub fn filename(session: &RoastSession) -> String {
+    let date = session.start_time.unwrap_or(session.created_at);
+    format!(
+        "{}_{}.{FORMAT}",
+        session.name.replace([' ', '"'], "_"),
+        date.format("%Y-%m-%d")
+    )
+}
+
+/// Check a `.rroastlog` file against its manifest and insert the session it
+/// holds.
+pub async fn import(
+    sessions: &RoastSessionService,
+    db: &SqlitePool,
+    zip: &[u8],
+) -> Result<RoastlogImport, ImportError> {
+    let mut files = read_files(zip)?;
+    let manifest: RoastlogManifest = parse(&files, MANIFEST)?
+        .ok_or_else(|| invalid(format!("{MANIFEST} is missing, not a {FORMAT} file")))?;
+    if manifest.format != FORMAT {
+        return Err(invalid(format!("unknown format {:?}", manifest.format)));
+    }
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(invalid(format!(
+            "format version {} is newer than this server reads ({FORMAT_VERSION})",
+            manifest.format_version
+        )));
+    }
+    files.remove(MANIFEST);
+    for listed in &manifest.files {
+        let bytes = files
+            .get(&listed.path)
+            .ok_or_else(|| invalid(format!("{} is listed but missing", listed.path)))?;
+        if bytes.len() as u64 != listed.size || sha256_hex(bytes) != listed.sha256 {
+            return Err(invalid(format!(
+                "{} doesn't match the manifest",
+                listed.path
+            )));
+        }
+    }
+    if let Some(extra) = files
+        .keys()
+        .find(|path| !manifest.files.iter().any(|f| &f.path == *path))
+    {
+        return Err(invalid(format!("{extra} is not listed in the manifest")));
+    }
+
+    let session: RoastSession =
+        parse(&files, SESSION)?.ok_or_else(|| invalid(format!("{SESSION} is missing")))?;
+    if session.id != manifest.session_id {
+        return Err(invalid(format!("{SESSION} holds another session")));
+    }
+    if session.status != SessionStatus::Completed {
+        return Err(invalid("only completed sessions can be imported"));
+    }
+    let events: Vec<RoastEvent> =
+        parse(&files, EVENTS)?.ok_or_else(|| invalid(format!("{EVENTS} is missing")))?;
+    let parquet = files
+        .remove(TELEMETRY)
+        .ok_or_else(|| invalid(format!("{TELEMETRY} is missing")))?;
+    let telemetry = telemetry_from_parquet(Bytes::from(parquet), &session.id)
+        .map_err(|e| invalid(format!("{TELEMETRY}: {e}")))?;
+    let cupping: Option<CuppingWithAttributes> = parse(&files, CUPPING)?;
+    let adjustment: Option<ChargeAdjustment> = parse(&files, CHARGE_ADJUSTMENT)?;
+
+    let bundle = SessionArchiveBundle {
+        format_version: FORMAT_VERSION,
+        session,
+        events,
+        telemetry,
+    };
+    let (session, unlinked) = sessions
+        .import_session_bundle(&bundle, cupping.as_ref())
+        .await?
+        .ok_or_else(|| ImportError::Exists(bundle.session.id.clone()))?;
+    if let Some(adjustment) = &adjustment {
+        charge_correction::record(db, &session.id, adjustment).await?;
+    }
+    let attachments = [
+        (CUPPING, cupping.is_some()),
+        (CHARGE_ADJUSTMENT, adjustment.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, present)| *present)
+    .map(|(path, _)| path.to_string())
+    .collect();
+    Ok(RoastlogImport {
+        session,
+        events: bundle.events.len(),
+        telemetry_points: bundle.telemetry.len(),
+        attachments,
+        unlinked,
+    })
+}
+
+/// Every file in the zip by path, refusing oversized ones.
+fn read_files(zip: &[u8]) -> Result<HashMap<String, Vec<u8>>, ImportError> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(zip)).map_err(|e| invalid(format!("not a zip file: {e}")))?;
+    let mut files = HashMap::new();
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| invalid(format!("unreadable zip entry: {e}")))?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = file.name().to_string();
+        let mut bytes = Vec::new();
+        file.take(MAX_FILE_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| invalid(format!("{path}: {e}")))?;
+        if bytes.len() as u64 > MAX_FILE_BYTES {
+            return Err(invalid(format!(
+                "{path} is larger than {MAX_FILE_BYTES} bytes"
+            )));
+        }
+        files.insert(path, bytes);
+    }
+    Ok(files)
+}
+
+fn parse<T: serde::de::DeserializeOwned>(
+    files: &HashMap<String, Vec<u8>>,
+    path: &str,
+) -> Result<Option<T>, ImportError> {
+    files
+        .get(path)
+        .map(|bytes| serde_json::from_slice(bytes).map_err(|e| invalid(format!("{path}: {e}"))))
+        .transpose()
+}
+
+/// Standalone summary page: key figures, the bean temp curve, events and
+/// notes, readable without rustRoast.
+fn session_report_html(bundle: &SessionArchiveBundle, locale: Locale) -> String {
+    let s = &bundle.session;
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{name}</title>\n\
+         <style>body{{font-family:sans-serif;max-width:48rem;margin:2rem auto}}\
+         td,th{{padding:.2rem .8rem;text-align:left}}</style>\n</head>\n<body>\n<h1>{name}</h1>\n<table>\n",
+        name = escape_html(&s.name)
+    );
+    let mut row = |label: &str, value: Option<String>| {
+        if let Some(value) = value {
+            let _ = writeln!(
+                out,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                label,
+                escape_html(&value)
+            );
+        }
+    };
+    row("Session", Some(s.id.clone()));
+    row("Device", Some(s.device_id.clone()));
+    row("Roaster", s.roaster.clone());
+    row("Started", s.start_time.map(|t| t.to_rfc3339()));
+    row("Ended", s.end_time.map(|t| t.to_rfc3339()));
+    row("Bean origin", s.bean_origin.clone());
+    row("Bean variety", s.bean_variety.clone());
+    row(
+        "Green weight",
+        s.green_weight.map(|w| format!("{:.0} g", w)),
+    );
+    row(
+        "Roasted weight",
+        s.roasted_weight.map(|w| format!("{:.0} g", w)),
+    );
+    row(
+        "Weight loss",
+        s.weight_loss_pct.map(|p| format!("{:.1} %", p)),
+    );
+    row("Total time", s.total_time_seconds.map(format_secs));
+    row("First crack", s.first_crack_time.map(format_secs));
+    row(
+        "Development ratio",
+        s.development_time_ratio
+            .map(|r| format!("{:.1} %", r * 100.0)),
+    );
+    row("Max temp", s.max_temp.map(|t| format!("{:.1} °C", t)));
+    row("Max RoR", s.max_ror.map(|r| format!("{:.1} °C/min", r)));
+    out.push_str("</table>\n");
+    out.push_str(&bean_temp_svg(bundle));
+    if !bundle.events.is_empty() {
+        out.push_str(
+            "<h2>Events</h2>\n<table>\n<tr><th>Time</th><th>Event</th><th>Temp</th></tr>\n",
+        );
+        for e in &bundle.events {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                format_secs(e.elapsed_seconds as i32),
+                escape_html(locale.event_name(&e.event_type)),
+                e.temperature
+                    .map(|t| format!("{:.1} °C", t))
+                    .unwrap_or_default(),
+            );
+        }
+        out.push_str("</table>\n");
+    }
+    if let Some(notes) = s.notes.as_deref().filter(|n| !n.is_empty()) {
+        let _ = writeln!(out, "<h2>Notes</h2>\n<p>{}</p>", escape_html(notes));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// The bean temp curve as an inline SVG polyline, empty without readings.
+fn bean_temp_svg(bundle: &SessionArchiveBundle) -> String {
+    const WIDTH: f32 = 640.0;
+    const HEIGHT: f32 = 240.0;
+    let points: Vec<(f32, f32)> = bundle
+        .telemetry
+        .iter()
+        .filter_map(|p| Some((p.elapsed_seconds, p.bean_temp?)))
+        .collect();
+    if points.len() < 2 {
+        return String::new();
+    }
+    let (mut t_max, mut lo, mut hi) = (f32::MIN, f32::MAX, f32::MIN);
+    for &(t, bt) in &points {
+        t_max = t_max.max(t);
+        lo = lo.min(bt);
+        hi = hi.max(bt);
+    }
+    let t_max = t_max.max(1.0);
+    let span = (hi - lo).max(1.0);
+    let mut polyline = String::new();
+    for (t, bt) in points {
+        let _ = write!(
+            polyline,
+            "{:.1},{:.1} ",
+            t / t_max * WIDTH,
+            HEIGHT - (bt - lo) / span * HEIGHT
+        );
+    }
+    format!(
+        "<h2>Bean temp</h2>\n<p>{lo:.0}–{hi:.0} °C over {}</p>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {WIDTH} {HEIGHT}\" width=\"100%\">\
+         <polyline fill=\"none\" stroke=\"#c0392b\" stroke-width=\"2\" points=\"{}\"/></svg>\n",
+        format_secs(t_max as i32),
+        polyline.trim_end()
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateCuppingRequest;
+
+    #[tokio::test]
+    async fn test_export_and_import_round_trip() {
+        let source_db = crate::init_memory_db().await.unwrap();
+        let source = RoastSessionService::new(source_db.clone());
+        let req = serde_json::json!({"name": "Kenya <AA>", "device_id": "dev1", "notes": "bright"});
+        let session = source
+            .create_session(serde_json::from_value(req).unwrap())
+            .await
+            .unwrap();
+        source.start_session(&session.id).await.unwrap();
+        for t in 0..5 {
+            source
+                .add_telemetry_point(
+                    &session.id,
+                    t as f32,
+                    Some(150.0 + t as f32),
+                    None,
+                    None,
+                    Some(80),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        source.complete_session(&session.id).await.unwrap();
+        let cupping: CreateCuppingRequest = serde_json::from_value(serde_json::json!({
+            "attributes": [{"name": "acidity", "score": 8.5}],
+        }))
+        .unwrap();
+        source.create_cupping(&session.id, cupping).await.unwrap();
+
+        let bundle = source
+            .session_archive_bundle(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let zip = export(&source, &source_db, &bundle).await.unwrap();
+        let files = read_files(&zip).unwrap();
+        let report = String::from_utf8(files[REPORT].clone()).unwrap();
+        assert!(report.contains("<h1>Kenya &lt;AA&gt;</h1>") && report.contains("<polyline"));
+
+        let db = crate::init_memory_db().await.unwrap();
+        let target = RoastSessionService::new(db.clone());
+        let imported = import(&target, &db, &zip).await.unwrap();
+        assert_eq!(imported.session.id, session.id);
+        assert_eq!(imported.session.notes.as_deref(), Some("bright"));
+        assert_eq!(
+            (imported.events, imported.telemetry_points),
+            (bundle.events.len(), 5)
+        );
+        assert_eq!(imported.attachments, [CUPPING]);
+        let telemetry = target.get_session_telemetry(&session.id).await.unwrap();
+        assert_eq!(telemetry[4].bean_temp, Some(154.0));
+        assert_eq!(telemetry[4].heater_pwm, Some(80));
+        let cupped = target.get_cupping(&session.id).await.unwrap().unwrap();
+        assert_eq!(cupped.attributes.len(), 1);
+        assert!(matches!(
+            import(&target, &db, &zip).await,
+            Err(ImportError::Exists(_))
+        ));
+
+        // A file changed after export
+        let mut tampered = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, bytes) in &files {
+            tampered
+                .start_file(path.as_str(), SimpleFileOptions::default())
+                .unwrap();
+            let bytes = match path.as_str() {
+                SESSION => String::from_utf8(bytes.clone())
+                    .unwrap()
+                    .replace("bright", "flat")
+                    .into_bytes(),
+                _ => bytes.clone(),
+            };
+            tampered.write_all(&bytes).unwrap();
+        }
+        let tampered = tampered.finish().unwrap().into_inner();
+        let fresh = crate::init_memory_db().await.unwrap();
+        let err = import(&RoastSessionService::new(fresh.clone()), &fresh, &tampered)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ImportError::Invalid(msg) if msg == "session.json doesn't match the manifest"),
+            "{err:?}"
+        );
+    }
+}
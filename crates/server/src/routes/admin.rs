@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use rumqttc::QoS;
+use rustroast_core::ParsedTopic;
+use rustroast_mqtt::{topic_matches, MqttBroker, MqttCredentials};
+
+use super::auth::require_admin;
+use super::AppError;
+use crate::auth::Caller;
+use crate::compaction::{self, CompactionConfig};
+use crate::jobs::Job;
+use crate::models::*;
+use crate::report_email;
+use crate::shared_subs::CATCH_ALL;
+use crate::AppState;
+
+const DEFAULT_CREDENTIALS_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_RETAINED_SCAN_MS: u64 = 2000;
+const DEFAULT_RETAINED_STALE_SECS: u64 = 7 * 86_400;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Admin maintenance: rebuild derived session data, compact telemetry and
+/// archive sessions (as jobs with progress), rotate broker credentials or
+/// move to another broker, publish raw MQTT messages, clear stale retained
+/// ones and email the daily report.
+pub fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/mqtt/credentials", post(update_mqtt_credentials))
+        .route("/api/admin/mqtt/config", put(update_mqtt_config))
+        .route("/api/admin/mqtt/publish", post(publish_mqtt))
+        .route("/api/admin/mqtt/audit", get(list_mqtt_publishes))
+        .route("/api/admin/mqtt/retained", get(list_retained))
+        .route("/api/admin/mqtt/retained/clear", post(clear_retained))
+        .route("/api/admin/recompute", post(recompute))
+        .route("/api/admin/telemetry/compact", post(compact_telemetry))
+        .route("/api/admin/sessions/archive", post(archive_sessions))
+        .route(
+            "/api/admin/reports/daily/email",
+            get(list_report_emails).post(send_report_email),
+        )
+        .route("/api/admin/jobs", get(list_jobs))
+        .route("/api/admin/jobs/:id", get(get_job))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn recompute(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<RecomputeQuery>,
+) -> Result<(StatusCode, Json<Job>), AppError> {
+    require_admin(&caller)?;
+    let session_ids = if q.scope == "all" {
+        state.session_service.completed_session_ids().await?
+    } else if let Some(id) = q.scope.strip_prefix("session:") {
+        let session = state
+            .session_service
+            .get_session(id)
+            .await?
+            .ok_or_else(|| AppError::not_found("Session"))?;
+        if session.status != SessionStatus::Completed {
+            return Err(AppError::bad_request(
+                "only completed sessions have derived data",
+            ));
+        }
+        vec![session.id]
+    } else {
+        return Err(AppError::bad_request(
+            "scope must be 'all' or 'session:<id>'",
+        ));
+    };
+
+    let handle = state.jobs.start("recompute", session_ids.len()).await;
+    let job = state
+        .jobs
+        .get(handle.id())
+        .await
+        .ok_or_else(|| AppError::internal("job was not registered"))?;
+    let service = state.session_service.clone();
+    tokio::spawn(async move {
+        for id in session_ids {
+            let ok = match service.recompute_session_stats(&id).await {
+                Ok(_) => true,
+                Err(e) => {
+                    tracing::warn!(session_id = %id, error = %e, "Failed to recompute session");
+                    false
+                }
+            };
+            handle.advance(ok).await;
+        }
+        handle.finish().await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn compact_telemetry(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<CompactTelemetryQuery>,
+) -> Result<(StatusCode, Json<Job>), AppError> {
+    require_admin(&caller)?;
+    let older_than_secs = match q.older_than_days {
+        Some(days) if days > 0 => days * 86_400,
+        Some(_) => return Err(AppError::bad_request("older_than_days must be positive")),
+        None => CompactionConfig::from_env()
+            .compact_after_secs
+            .ok_or_else(|| AppError::bad_request("compaction is disabled, pass older_than_days"))?,
+    };
+    let cutoff = chrono::Utc::now().timestamp() - older_than_secs;
+    let devices = compaction::devices_to_compact(&state.db, cutoff).await?;
+
+    let handle = state.jobs.start("compact_telemetry", devices.len()).await;
+    let job = state
+        .jobs
+        .get(handle.id())
+        .await
+        .ok_or_else(|| AppError::internal("job was not registered"))?;
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        for device_id in devices {
+            let ok = match compaction::compact_device(&db, &device_id, cutoff).await {
+                Ok(_) => true,
+                Err(e) => {
+                    tracing::warn!(device_id = %device_id, error = %e, "Failed to compact telemetry");
+                    false
+                }
+            };
+            handle.advance(ok).await;
+        }
+        handle.finish().await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn archive_sessions(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<ArchiveSessionsQuery>,
+) -> Result<(StatusCode, Json<Job>), AppError> {
+    require_admin(&caller)?;
+    if !state.archiver.is_configured() {
+        return Err(AppError::bad_request("object storage is not configured"));
+    }
+    let older_than_secs = match q.older_than_days {
+        Some(days) if days >= 0 => days * 86_400,
+        Some(_) => {
+            return Err(AppError::bad_request(
+                "older_than_days must not be negative",
+            ))
+        }
+        None => state.archiver.archive_after_secs.ok_or_else(|| {
+            AppError::bad_request("automatic archival is off, pass older_than_days")
+        })?,
+    };
+    let cutoff = chrono::Utc::now() - chrono::Duration::seconds(older_than_secs);
+    let session_ids = state.session_service.sessions_to_archive(cutoff).await?;
+
+    let handle = state
+        .jobs
+        .start("archive_sessions", session_ids.len())
+        .await;
+    let job = state
+        .jobs
+        .get(handle.id())
+        .await
+        .ok_or_else(|| AppError::internal("job was not registered"))?;
+    let (archiver, sessions) = (state.archiver.clone(), state.session_service.clone());
+    tokio::spawn(async move {
+        for id in session_ids {
+            let ok = match archiver.archive(&sessions, &id).await {
+                Ok(_) => true,
+                Err(e) => {
+                    tracing::warn!(session_id = %id, error = %e, "Failed to archive session");
+                    false
+                }
+            };
+            handle.advance(ok).await;
+        }
+        handle.finish().await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn send_report_email(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<SendReportEmailRequest>,
+) -> Result<Json<ReportEmail>, AppError> {
+    require_admin(&caller)?;
+    if !state.report_mailer.is_configured() {
+        return Err(AppError::bad_request("SMTP is not configured"));
+    }
+    let recipients = match req.recipients {
+        Some(recipients) => recipients,
+        None => report_email::settings(&state.db).await?.recipients,
+    };
+    if recipients.is_empty() {
+        return Err(AppError::bad_request("no recipients"));
+    }
+    let recipients = report_email::mailboxes(&recipients).map_err(AppError::bad_request)?;
+    let date = req.date.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let sent = report_email::send_report(
+        &state.report_mailer,
+        &state.session_service,
+        &state.db,
+        date,
+        &recipients,
+        Some(caller.label().unwrap_or("admin")),
+    )
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to send the report: {}", e)))?;
+    Ok(Json(sent))
+}
+
+async fn list_report_emails(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<ReportEmail>>, AppError> {
+    require_admin(&caller)?;
+    Ok(Json(report_email::list_sent(&state.db, 50).await?))
+}
+
+async fn list_jobs(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<Job>>, AppError> {
+    require_admin(&caller)?;
+    Ok(Json(state.jobs.list().await))
+}
+
+async fn get_job(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<Json<Job>, AppError> {
+    require_admin(&caller)?;
+    state
+        .jobs
+        .get(&id)
+        .await
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("Job"))
+}
+
+async fn update_mqtt_credentials(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<MqttCredentialsRequest>,
+) -> Result<Json<MqttCredentialsResponse>, AppError> {
+    require_admin(&caller)?;
+    let password = match (req.password, req.token) {
+        (Some(secret), None) | (None, Some(secret)) if !secret.is_empty() => secret,
+        _ => {
+            return Err(AppError::bad_request(
+                "provide exactly one of password or token",
+            ))
+        }
+    };
+    let timeout_ms = req
+        .timeout_ms
+        .unwrap_or(DEFAULT_CREDENTIALS_TIMEOUT_MS)
+        .clamp(100, 60_000);
+    tracing::info!(subject = ?caller.subject, "MQTT credentials update requested");
+    let connected = state
+        .mqtt
+        .update_credentials(
+            MqttCredentials {
+                username: req.username.filter(|u| !u.is_empty()),
+                password,
+            },
+            Duration::from_millis(timeout_ms),
+        )
+        .await;
+    if !connected {
+        return Err(AppError::gateway_timeout(format!(
+            "Broker did not accept the new credentials within {} ms; still retrying",
+            timeout_ms
+        )));
+    }
+    Ok(Json(MqttCredentialsResponse { connected }))
+}
+
+async fn update_mqtt_config(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<MqttConfigRequest>,
+) -> Result<Json<MqttConfigResponse>, AppError> {
+    require_admin(&caller)?;
+    if req.password.is_some() && req.token.is_some() {
+        return Err(AppError::bad_request(
+            "provide at most one of password or token",
+        ));
+    }
+    let mut config = state.mqtt.config().unwrap_or_default();
+    if let Some(port) = req.port {
+        config.port = port;
+    }
+    if let Some(host) = req.host.filter(|h| !h.is_empty()) {
+        config.host = host;
+        config.brokers.clear();
+    }
+    if let Some(brokers) = req.brokers {
+        let brokers: Vec<MqttBroker> = brokers
+            .iter()
+            .filter(|b| !b.trim().is_empty())
+            .map(|b| MqttBroker::parse(b, config.port))
+            .collect();
+        let Some(first) = brokers.first() else {
+            return Err(AppError::bad_request("brokers must not be empty"));
+        };
+        config.host = first.host.clone();
+        config.port = first.port;
+        config.brokers = brokers;
+    }
+    if let Some(username) = req.username {
+        config.username = Some(username).filter(|u| !u.is_empty());
+    }
+    if let Some(secret) = req.password.or(req.token) {
+        config.password = Some(secret).filter(|p| !p.is_empty());
+    }
+    if let Some(secs) = req.keep_alive_secs {
+        config.keep_alive_secs = secs;
+    }
+    let timeout_ms = req
+        .timeout_ms
+        .unwrap_or(DEFAULT_CREDENTIALS_TIMEOUT_MS)
+        .clamp(100, 60_000);
+    tracing::info!(
+        subject = ?caller.subject,
+        host = %config.host,
+        port = config.port,
+        "MQTT configuration update requested"
+    );
+    let connected = state
+        .mqtt
+        .reconfigure(config, Duration::from_millis(timeout_ms))
+        .await
+        .map_err(AppError::bad_request)?;
+    let broker = state.mqtt.broker();
+    if !connected {
+        return Err(AppError::gateway_timeout(format!(
+            "Broker {} did not accept the connection within {} ms; still retrying",
+            broker.as_deref().unwrap_or("(none)"),
+            timeout_ms
+        )));
+    }
+    Ok(Json(MqttConfigResponse { connected, broker }))
+}
+
+async fn publish_mqtt(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<MqttPublishRequest>,
+) -> Result<Json<MqttPublishAudit>, AppError> {
+    publish_raw(&state, &caller, req, "rest").await.map(Json)
+}
+
+async fn list_mqtt_publishes(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<MqttPublishAuditQuery>,
+) -> Result<Json<Vec<MqttPublishAudit>>, AppError> {
+    require_admin(&caller)?;
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+    Ok(Json(
+        state.session_service.list_mqtt_publishes(limit).await?,
+    ))
+}
+
+async fn list_retained(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<RetainedMessagesQuery>,
+) -> Result<Json<Vec<RetainedMessageInfo>>, AppError> {
+    require_admin(&caller)?;
+    Ok(Json(
+        scan_retained(&state, q.window_ms, q.stale_after_secs).await?,
+    ))
+}
+
+/// Clear retained messages by publishing an empty retained message to each
+/// topic, audited like any raw publish.
+async fn clear_retained(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<ClearRetainedRequest>,
+) -> Result<Json<Vec<MqttPublishAudit>>, AppError> {
+    require_admin(&caller)?;
+    let topics = match (req.stale, req.topics.is_empty()) {
+        (true, true) => scan_retained(&state, req.window_ms, req.stale_after_secs)
+            .await?
+            .into_iter()
+            .filter(|m| m.stale)
+            .map(|m| m.topic)
+            .collect(),
+        (false, false) => {
+            if let Some(topic) = req.topics.iter().find(|t| !topic_matches(CATCH_ALL, t)) {
+                return Err(AppError::bad_request(format!(
+                    "not a roaster topic: {}",
+                    topic
+                )));
+            }
+            req.topics
+        }
+        _ => {
+            return Err(AppError::bad_request(
+                "provide either topics or stale: true",
+            ))
+        }
+    };
+    let mut cleared = Vec::with_capacity(topics.len());
+    for topic in topics {
+        let req = MqttPublishRequest {
+            topic,
+            payload: serde_json::Value::String(String::new()),
+            qos: 1,
+            retain: true,
+        };
+        cleared.push(publish_raw(&state, &caller, req, "retained_clear").await?);
+    }
+    Ok(Json(cleared))
+}
+
+/// Retained messages under `roaster/#`, each with its device's last sign
+/// of life: telemetry, or a status received live.
+async fn scan_retained(
+    state: &AppState,
+    window_ms: Option<u64>,
+    stale_after_secs: Option<u64>,
+) -> Result<Vec<RetainedMessageInfo>, AppError> {
+    let window = window_ms
+        .unwrap_or(DEFAULT_RETAINED_SCAN_MS)
+        .clamp(100, 30_000);
+    let stale_after = stale_after_secs.unwrap_or(DEFAULT_RETAINED_STALE_SECS);
+    let messages = state
+        .mqtt
+        .scan_retained(CATCH_ALL, Duration::from_millis(window))
+        .await
+        .map_err(|e| AppError::internal(format!("MQTT subscribe failed: {}", e)))?;
+
+    let mut last_seen: HashMap<String, DateTime<Utc>> = state
+        .device_service
+        .list_devices(None)
+        .await?
+        .into_iter()
+        .filter_map(|d| Some((d.device_id, d.last_seen_at?)))
+        .collect();
+    for device in state.device_registry.read().await.values() {
+        if device.retained {
+            continue;
+        }
+        if let Some(seen) = DateTime::from_timestamp(device.last_seen as i64, 0) {
+            let entry = last_seen.entry(device.device_id.clone()).or_insert(seen);
+            *entry = (*entry).max(seen);
+        }
+    }
+
+    let now = Utc::now();
+    Ok(messages
+        .into_iter()
+        .map(|m| {
+            let device_id = ParsedTopic::parse(&m.topic)
+                .ok()
+                .map(|t| t.device_id().to_string());
+            let last_seen_at = device_id.as_ref().and_then(|id| last_seen.get(id).copied());
+            let stale =
+                last_seen_at.is_none_or(|seen| (now - seen).num_seconds() > stale_after as i64);
+            RetainedMessageInfo {
+                topic: m.topic,
+                device_id,
+                payload: String::from_utf8_lossy(&m.payload).into_owned(),
+                bytes: m.payload.len(),
+                last_seen_at,
+                stale,
+            }
+        })
+        .collect())
+}
+
+/// Publish an arbitrary message for an admin and audit it. Shared by the
+/// REST endpoint and the debug WebSocket's `publish` command.
+pub(crate) async fn publish_raw(
+    state: &AppState,
+    caller: &Caller,
+    req: MqttPublishRequest,
+    source: &str,
+) -> Result<MqttPublishAudit, AppError> {
+    require_admin(caller)?;
+    if state.demo {
+        return Err(AppError::forbidden("Demo mode: publishing is disabled"));
+    }
+    if req.topic.is_empty() || req.topic.contains(['+', '#']) {
+        return Err(AppError::bad_request(
+            "topic must be non-empty and must not contain wildcards",
+        ));
+    }
+    let qos = match req.qos {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        2 => QoS::ExactlyOnce,
+        _ => return Err(AppError::bad_request("qos must be 0, 1 or 2")),
+    };
+    let payload = match &req.payload {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    tracing::info!(subject = ?caller.subject, topic = %req.topic, source, "Raw MQTT publish");
+    state
+        .mqtt
+        .publish(&req.topic, qos, req.retain, payload.clone())
+        .await
+        .map_err(|e| AppError::internal(format!("MQTT publish failed: {}", e)))?;
+    let entry = state
+        .session_service
+        .record_mqtt_publish(&req, &payload, caller.subject.as_deref(), source)
+        .await?;
+    Ok(entry)
+}
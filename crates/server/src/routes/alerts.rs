@@ -0,0 +1,143 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::auth::Caller;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Alert history, the acknowledge/resolve workflow and per-roaster limits.
+pub fn alert_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/alerts", get(list_alerts))
+        .route("/api/alerts/:id", get(get_alert))
+        .route("/api/alerts/:id/acknowledge", post(acknowledge_alert))
+        .route("/api/alerts/:id/resolve", post(resolve_alert))
+        .route(
+            "/api/roaster/:device_id/alert-limits",
+            get(get_alert_limit)
+                .put(put_alert_limit)
+                .delete(delete_alert_limit),
+        )
+}
+
+pub(super) fn validate_max_bean_temp(max_bean_temp: f64) -> Result<(), AppError> {
+    if !(max_bean_temp.is_finite() && (0.0..=300.0).contains(&max_bean_temp)) {
+        return Err(AppError::bad_request(
+            "max_bean_temp must be between 0 and 300 C",
+        ));
+    }
+    Ok(())
+}
+
+/// Who is acting: the request's `by`, else the signed-in caller.
+fn actor(caller: &Caller, req: Option<Json<AlertActionRequest>>) -> Result<String, AppError> {
+    req.and_then(|Json(r)| r.by)
+        .filter(|by| !by.trim().is_empty())
+        .or_else(|| caller.name.clone())
+        .or_else(|| caller.subject.clone())
+        .ok_or_else(|| AppError::bad_request("by is required when not signed in"))
+}
+
+/// Why a state change matched nothing: missing alert or wrong state.
+async fn transition_error(state: &AppState, id: &str) -> AppError {
+    match state.alerts.get(id).await {
+        Ok(Some(alert)) => AppError::conflict(format!("Alert is already {}", alert.state)),
+        Ok(None) => AppError::not_found("Alert"),
+        Err(e) => e.into(),
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn list_alerts(
+    State(state): State<AppState>,
+    Query(q): Query<AlertListQuery>,
+) -> Result<Json<Vec<Alert>>, AppError> {
+    let alerts = state.alerts.list(&q).await?;
+    Ok(Json(alerts))
+}
+
+async fn get_alert(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Alert>, AppError> {
+    let alert = state
+        .alerts
+        .get(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Alert"))?;
+    Ok(Json(alert))
+}
+
+async fn acknowledge_alert(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    req: Option<Json<AlertActionRequest>>,
+) -> Result<Json<Alert>, AppError> {
+    let by = actor(&caller, req)?;
+    match state.alerts.acknowledge(&id, &by).await? {
+        Some(alert) => Ok(Json(alert)),
+        None => Err(transition_error(&state, &id).await),
+    }
+}
+
+async fn resolve_alert(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    req: Option<Json<AlertActionRequest>>,
+) -> Result<Json<Alert>, AppError> {
+    let by = actor(&caller, req)?;
+    match state.alerts.resolve(&id, &by).await? {
+        Some(alert) => Ok(Json(alert)),
+        None => Err(transition_error(&state, &id).await),
+    }
+}
+
+async fn get_alert_limit(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<AlertLimit>, AppError> {
+    let limit = state
+        .alerts
+        .limit(&device_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Alert limit"))?;
+    Ok(Json(limit))
+}
+
+async fn put_alert_limit(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(req): Json<UpsertAlertLimitRequest>,
+) -> Result<Json<AlertLimit>, AppError> {
+    validate_max_bean_temp(req.max_bean_temp)?;
+    let limit = state
+        .alerts
+        .set_limit(&device_id, req.max_bean_temp)
+        .await?;
+    Ok(Json(limit))
+}
+
+async fn delete_alert_limit(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.alerts.remove_limit(&device_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Alert limit"))
+    }
+}
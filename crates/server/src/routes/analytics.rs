@@ -0,0 +1,64 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Cross-session analytics for trend charts.
+pub fn analytics_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/analytics/trends", get(get_trends))
+        .route("/api/analytics/defects", get(get_defect_rates))
+        .route("/api/analytics/summary", get(get_summary))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_trends(
+    State(state): State<AppState>,
+    Query(q): Query<TrendQuery>,
+) -> Result<Json<TrendsResponse>, AppError> {
+    if let (Some(from), Some(to)) = (q.from, q.to) {
+        if from >= to {
+            return Err(AppError::bad_request("from must be before to"));
+        }
+    }
+    let trends = state.session_service.session_trends(&q).await?;
+    Ok(Json(trends))
+}
+
+async fn get_defect_rates(
+    State(state): State<AppState>,
+    Query(q): Query<DefectRateQuery>,
+) -> Result<Json<DefectRatesResponse>, AppError> {
+    if let (Some(from), Some(to)) = (q.from, q.to) {
+        if from >= to {
+            return Err(AppError::bad_request("from must be before to"));
+        }
+    }
+    let rates = state.session_service.defect_rates(&q).await?;
+    Ok(Json(rates))
+}
+
+async fn get_summary(
+    State(state): State<AppState>,
+    Query(q): Query<SummaryQuery>,
+) -> Result<Json<SummaryResponse>, AppError> {
+    if let (Some(from), Some(to)) = (q.from, q.to) {
+        if from >= to {
+            return Err(AppError::bad_request("from must be before to"));
+        }
+    }
+    let summary = state.session_service.session_summary(&q).await?;
+    Ok(Json(summary))
+}
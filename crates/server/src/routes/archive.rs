@@ -0,0 +1,52 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// A session's archive in object storage, and re-hydrating its telemetry
+/// from there.
+pub fn archive_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/sessions/:id/archive", get(get_archive))
+        .route("/api/sessions/:id/restore", post(restore_session))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_archive(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionArchive>, AppError> {
+    let archive = state
+        .session_service
+        .get_session_archive(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session archive"))?;
+    Ok(Json(archive))
+}
+
+async fn restore_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionArchive>, AppError> {
+    if !state.archiver.is_configured() {
+        return Err(AppError::bad_request("object storage is not configured"));
+    }
+    let archive = state
+        .archiver
+        .restore(&state.session_service, &id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session archive"))?;
+    Ok(Json(archive))
+}
@@ -0,0 +1,258 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::SET_COOKIE, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::warn;
+
+use super::AppError;
+use crate::auth::{Caller, SESSION_COOKIE};
+use crate::control_policy;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// OIDC login/logout, current-identity lookup and API key management.
+pub fn auth_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/auth/oidc/login", get(oidc_login))
+        .route("/api/auth/oidc/callback", get(oidc_callback))
+        .route("/api/auth/logout", post(logout))
+        .route("/api/auth/me", get(me))
+        .route("/api/auth/api-keys", get(list_api_keys))
+        .route("/api/auth/api-keys", post(create_api_key))
+        .route("/api/auth/api-keys/:id", delete(revoke_api_key))
+        .route(
+            "/api/auth/api-keys/:id/control-policy",
+            get(get_control_policy)
+                .put(set_control_policy)
+                .delete(remove_control_policy),
+        )
+        .route("/api/me/preferences", get(get_preferences))
+        .route("/api/me/preferences", put(update_preferences))
+}
+
+pub(super) fn require_subject(caller: &Caller) -> Result<&str, AppError> {
+    caller
+        .subject
+        .as_deref()
+        .ok_or_else(|| AppError::unauthorized("Authentication required"))
+}
+
+pub(super) fn require_admin(caller: &Caller) -> Result<(), AppError> {
+    match caller.role {
+        Some(UserRole::Admin) => Ok(()),
+        Some(_) => Err(AppError::forbidden("Admin role required")),
+        None => Err(AppError::unauthorized("Authentication required")),
+    }
+}
+
+// ============================================================================
+// OIDC handlers
+// ============================================================================
+
+async fn oidc_login(State(state): State<AppState>) -> Result<Redirect, AppError> {
+    let oidc = state
+        .auth
+        .oidc
+        .as_ref()
+        .ok_or_else(|| AppError::not_found("OIDC login"))?;
+    let url = oidc.authorization_url().await?;
+    Ok(Redirect::to(&url))
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+async fn oidc_callback(
+    State(state): State<AppState>,
+    Query(q): Query<CallbackQuery>,
+) -> Result<Response, AppError> {
+    let oidc = state
+        .auth
+        .oidc
+        .as_ref()
+        .ok_or_else(|| AppError::not_found("OIDC login"))?;
+    if let Some(err) = q.error {
+        return Err(AppError::unauthorized(format!(
+            "OIDC login failed: {}",
+            err
+        )));
+    }
+    let (Some(code), Some(login_state)) = (q.code, q.state) else {
+        return Err(AppError::bad_request("missing code or state"));
+    };
+
+    let identity = oidc
+        .complete_login(&code, &login_state)
+        .await
+        .map_err(|e| {
+            warn!(error = %e, "OIDC login rejected");
+            AppError::unauthorized("OIDC login could not be verified")
+        })?;
+    let role = oidc.config.map_role(&identity.groups);
+    let user = state
+        .user_service
+        .upsert_oidc_user(
+            &identity.issuer,
+            &identity.subject,
+            identity.email.as_deref(),
+            identity.name.as_deref(),
+            role,
+        )
+        .await?;
+    let token = state.auth.signer.issue(&user)?;
+
+    let secure = if oidc.config.redirect_url.starts_with("https://") {
+        "; Secure"
+    } else {
+        ""
+    };
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{}",
+        SESSION_COOKIE, token, state.auth.signer.ttl_secs, secure
+    );
+    Ok((
+        [(SET_COOKIE, cookie)],
+        Redirect::to(&oidc.config.post_login_redirect),
+    )
+        .into_response())
+}
+
+async fn logout() -> Response {
+    let cookie = format!(
+        "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0",
+        SESSION_COOKIE
+    );
+    ([(SET_COOKIE, cookie)], StatusCode::NO_CONTENT).into_response()
+}
+
+async fn me(caller: Caller) -> Result<Json<Caller>, AppError> {
+    if !caller.is_authenticated() {
+        return Err(AppError::unauthorized("Not signed in"));
+    }
+    Ok(Json(caller))
+}
+
+// ============================================================================
+// API key handlers (admin only)
+// ============================================================================
+
+async fn list_api_keys(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<ApiKey>>, AppError> {
+    require_admin(&caller)?;
+    let keys = state.user_service.list_api_keys().await?;
+    Ok(Json(keys))
+}
+
+async fn create_api_key(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), AppError> {
+    require_admin(&caller)?;
+    if req.name.trim().is_empty() {
+        return Err(AppError::bad_request("name is required"));
+    }
+    let created = state
+        .user_service
+        .create_api_key(req, caller.subject.as_deref())
+        .await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn revoke_api_key(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&caller)?;
+    if state.user_service.revoke_api_key(&id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("API key"))
+    }
+}
+
+async fn get_control_policy(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<Json<ApiKeyControlPolicy>, AppError> {
+    require_admin(&caller)?;
+    control_policy::get(&state.db, &id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("Control policy"))
+}
+
+async fn set_control_policy(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Json(req): Json<SetApiKeyControlPolicyRequest>,
+) -> Result<Json<ApiKeyControlPolicy>, AppError> {
+    require_admin(&caller)?;
+    control_policy::validate(&req).map_err(AppError::bad_request)?;
+    let keys = state.user_service.list_api_keys().await?;
+    if !keys.iter().any(|k| k.id == id) {
+        return Err(AppError::not_found("API key"));
+    }
+    Ok(Json(control_policy::set(&state.db, &id, &req).await?))
+}
+
+async fn remove_control_policy(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&caller)?;
+    if control_policy::remove(&state.db, &id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Control policy"))
+    }
+}
+
+// ============================================================================
+// Current user preferences
+// ============================================================================
+
+async fn get_preferences(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<UserPreferences>, AppError> {
+    let subject = require_subject(&caller)?;
+    let prefs = match state.user_service.get_preferences(subject).await? {
+        Some(prefs) => prefs,
+        None => UserPreferences {
+            subject: subject.to_string(),
+            ws_subscriptions: sqlx::types::Json(Vec::new()),
+            dashboard_layout: None,
+            updated_at: chrono::Utc::now(),
+        },
+    };
+    Ok(Json(prefs))
+}
+
+async fn update_preferences(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<UpdatePreferencesRequest>,
+) -> Result<Json<UserPreferences>, AppError> {
+    let subject = require_subject(&caller)?;
+    let prefs = state.user_service.update_preferences(subject, req).await?;
+    Ok(Json(prefs))
+}
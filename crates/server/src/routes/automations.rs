@@ -0,0 +1,183 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Automation rules on profiles and roasters, and their per-session runs.
+pub fn automation_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/profiles/:id/automations",
+            get(list_profile_automations).post(create_profile_automation),
+        )
+        .route(
+            "/api/roaster/:device_id/automations",
+            get(list_device_automations).post(create_device_automation),
+        )
+        .route(
+            "/api/sessions/:id/automations",
+            get(list_session_automations),
+        )
+        .route(
+            "/api/sessions/:id/automations/log",
+            get(list_automation_executions),
+        )
+        .route("/api/automations/:id", delete(delete_automation))
+}
+
+pub(super) fn validate_automation(req: &CreateAutomationRequest) -> Result<(), AppError> {
+    if req.name.trim().is_empty() {
+        return Err(AppError::bad_request("name is required"));
+    }
+    match req.trigger_type {
+        AutomationTrigger::Event => {
+            if req.trigger_event.is_none() {
+                return Err(AppError::bad_request(
+                    "trigger_event is required for event triggers",
+                ));
+            }
+        }
+        AutomationTrigger::Elapsed => match req.trigger_value {
+            Some(v) if v.is_finite() && v >= 0.0 => {}
+            _ => {
+                return Err(AppError::bad_request(
+                    "elapsed trigger needs a non-negative trigger_value",
+                ))
+            }
+        },
+        AutomationTrigger::BeanTempRising | AutomationTrigger::BeanTempFalling => {
+            if !req.trigger_value.is_some_and(f64::is_finite) {
+                return Err(AppError::bad_request(
+                    "temperature trigger needs a trigger_value",
+                ));
+            }
+        }
+    }
+    if !req.value.is_finite() {
+        return Err(AppError::bad_request("value must be a number"));
+    }
+    let (min, max) = req.command.range();
+    if !req.relative && !(min..=max).contains(&req.value) {
+        return Err(AppError::bad_request(format!(
+            "{} must be between {} and {}",
+            req.command, min, max
+        )));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn list_profile_automations(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<AutomationRule>>, AppError> {
+    if state
+        .session_service
+        .get_profile_with_points(&id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::not_found("Profile"));
+    }
+    let rules = state.session_service.list_profile_automations(&id).await?;
+    Ok(Json(rules))
+}
+
+async fn create_profile_automation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<CreateAutomationRequest>,
+) -> Result<(StatusCode, Json<AutomationRule>), AppError> {
+    validate_automation(&req)?;
+    if state
+        .session_service
+        .get_profile_with_points(&id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::not_found("Profile"));
+    }
+    let rule = state
+        .session_service
+        .create_profile_automation(&id, req)
+        .await?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+async fn list_device_automations(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Vec<AutomationRule>>, AppError> {
+    let rules = state
+        .session_service
+        .list_device_automations(&device_id)
+        .await?;
+    Ok(Json(rules))
+}
+
+async fn create_device_automation(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(req): Json<CreateAutomationRequest>,
+) -> Result<(StatusCode, Json<AutomationRule>), AppError> {
+    validate_automation(&req)?;
+    let rule = state
+        .session_service
+        .create_device_automation(&device_id, req)
+        .await?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+async fn list_session_automations(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<SessionAutomationRule>>, AppError> {
+    let session = state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let rules = state
+        .session_service
+        .list_session_automations(&session)
+        .await?;
+    Ok(Json(rules))
+}
+
+async fn list_automation_executions(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<AutomationExecution>>, AppError> {
+    if state.session_service.get_session(&id).await?.is_none() {
+        return Err(AppError::not_found("Session"));
+    }
+    let executions = state
+        .session_service
+        .list_automation_executions(&id)
+        .await?;
+    Ok(Json(executions))
+}
+
+async fn delete_automation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.session_service.delete_automation(&id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Automation rule"))
+    }
+}
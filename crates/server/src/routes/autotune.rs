@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::autotune;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// The sanity check `autotune/apply` runs on a roaster's latest results, and
+/// the per-roaster gain limits it checks against.
+pub fn autotune_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/roaster/:device_id/autotune/results/latest/check",
+            get(check_latest_results),
+        )
+        .route(
+            "/api/roaster/:device_id/autotune/gain-limits",
+            get(get_gain_limits)
+                .put(put_gain_limits)
+                .delete(delete_gain_limits),
+        )
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn check_latest_results(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<AutotuneCheck>, AppError> {
+    let results = state
+        .autotune_results_cache
+        .read()
+        .await
+        .get(&device_id)
+        .map(|(val, _)| val.clone());
+    let checked = autotune::check(&state.db, &device_id, results.as_ref()).await?;
+    Ok(Json(checked))
+}
+
+async fn get_gain_limits(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<AutotuneGainLimits>, AppError> {
+    let limits = autotune::gain_limits(&state.db, &device_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Autotune gain limits"))?;
+    Ok(Json(limits))
+}
+
+async fn put_gain_limits(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(req): Json<UpsertAutotuneGainLimitsRequest>,
+) -> Result<Json<AutotuneGainLimits>, AppError> {
+    if [req.max_kp, req.max_ki, req.max_kd]
+        .iter()
+        .any(|max| !(max.is_finite() && *max > 0.0))
+    {
+        return Err(AppError::bad_request(
+            "max_kp, max_ki and max_kd must be positive",
+        ));
+    }
+    let max = PidGains {
+        kp: req.max_kp,
+        ki: req.max_ki,
+        kd: req.max_kd,
+    };
+    let limits = autotune::set_gain_limits(&state.db, &device_id, max).await?;
+    Ok(Json(limits))
+}
+
+async fn delete_gain_limits(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if autotune::remove_gain_limits(&state.db, &device_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Autotune gain limits"))
+    }
+}
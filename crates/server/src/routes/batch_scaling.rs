@@ -0,0 +1,152 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Per-roaster batch scaling rules, scaled profile suggestions and the
+/// profile versions they are saved as.
+pub fn batch_scaling_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/roaster/:device_id/batch-scaling",
+            get(get_batch_scaling_rule).put(put_batch_scaling_rule),
+        )
+        .route(
+            "/api/profiles/:id/batch-scale",
+            get(suggest_batch_scale).post(create_batch_scaled_version),
+        )
+        .route("/api/profiles/:id/versions", get(list_profile_versions))
+}
+
+fn validate_rule(req: &UpsertBatchScalingRuleRequest) -> Result<(), AppError> {
+    if !req.charge_temp_per_100g.is_finite() || !req.heater_cap_per_100g.is_finite() {
+        return Err(AppError::bad_request("scaling factors must be numbers"));
+    }
+    let min = req.min_heater_cap.unwrap_or(0);
+    let max = req.max_heater_cap.unwrap_or(100);
+    if !(0..=100).contains(&min) || !(0..=100).contains(&max) || min > max {
+        return Err(AppError::bad_request(
+            "heater caps must satisfy 0 <= min_heater_cap <= max_heater_cap <= 100",
+        ));
+    }
+    if req.max_charge_temp.is_some_and(|t| !t.is_finite()) {
+        return Err(AppError::bad_request("max_charge_temp must be a number"));
+    }
+    Ok(())
+}
+
+/// Look up the profile and the roaster's rule and compute the suggestion.
+async fn suggestion_for(
+    state: &AppState,
+    profile_id: &str,
+    req: &ScaleProfileRequest,
+) -> Result<(ProfileWithPoints, BatchScalingSuggestion), AppError> {
+    if !(req.batch_size_g.is_finite() && req.batch_size_g > 0.0) {
+        return Err(AppError::bad_request("batch_size_g must be positive"));
+    }
+    let profile = state
+        .session_service
+        .get_profile_with_points(profile_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Profile"))?;
+    let reference_batch_g = req
+        .reference_batch_g
+        .or(profile.profile.batch_size_g)
+        .ok_or_else(|| {
+            AppError::bad_request(
+                "profile has no batch_size_g, pass reference_batch_g for the batch it was tuned for",
+            )
+        })?;
+    if !(reference_batch_g.is_finite() && reference_batch_g > 0.0) {
+        return Err(AppError::bad_request("reference_batch_g must be positive"));
+    }
+    let rule = state
+        .session_service
+        .get_batch_scaling_rule(&req.device_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Batch scaling rule"))?;
+    let suggestion = rule.suggest(&profile.profile, reference_batch_g, req.batch_size_g);
+    Ok((profile, suggestion))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_batch_scaling_rule(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<BatchScalingRule>, AppError> {
+    let rule = state
+        .session_service
+        .get_batch_scaling_rule(&device_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Batch scaling rule"))?;
+    Ok(Json(rule))
+}
+
+async fn put_batch_scaling_rule(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(req): Json<UpsertBatchScalingRuleRequest>,
+) -> Result<Json<BatchScalingRule>, AppError> {
+    validate_rule(&req)?;
+    let rule = state
+        .session_service
+        .upsert_batch_scaling_rule(&device_id, req)
+        .await?;
+    Ok(Json(rule))
+}
+
+async fn suggest_batch_scale(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(req): Query<ScaleProfileRequest>,
+) -> Result<Json<BatchScalingSuggestion>, AppError> {
+    let (_, suggestion) = suggestion_for(&state, &id, &req).await?;
+    Ok(Json(suggestion))
+}
+
+async fn create_batch_scaled_version(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<ScaleProfileRequest>,
+) -> Result<(StatusCode, Json<ScaledProfile>), AppError> {
+    let (source, suggestion) = suggestion_for(&state, &id, &req).await?;
+    let name = match req.name.as_deref().map(str::trim) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => format!("{} ({} g)", source.profile.name, req.batch_size_g),
+    };
+    let profile = state
+        .session_service
+        .create_scaled_profile_version(&source, &suggestion, &name)
+        .await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ScaledProfile {
+            suggestion,
+            profile,
+        }),
+    ))
+}
+
+async fn list_profile_versions(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<RoastProfile>>, AppError> {
+    let versions = state.session_service.list_profile_versions(&id).await?;
+    if versions.is_empty() {
+        return Err(AppError::not_found("Profile"));
+    }
+    Ok(Json(versions))
+}
@@ -0,0 +1,163 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+const DEFAULT_RECOMMENDATIONS: usize = 5;
+const MAX_RECOMMENDATIONS: usize = 50;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Green bean lots, their sample roast groups, and profile recommendations
+/// for a bean.
+pub fn bean_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/beans", get(list_beans).post(create_bean))
+        .route(
+            "/api/beans/:id",
+            get(get_bean).put(update_bean).delete(delete_bean),
+        )
+        .route("/api/beans/:id/sample-groups", get(list_sample_groups))
+        .route(
+            "/api/sample-groups/:id/evaluation",
+            get(get_sample_evaluation),
+        )
+        .route("/api/profiles/recommend", get(recommend_profiles))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn list_beans(State(state): State<AppState>) -> Result<Json<Vec<Bean>>, AppError> {
+    let beans = state.session_service.list_beans().await?;
+    Ok(Json(beans))
+}
+
+async fn create_bean(
+    State(state): State<AppState>,
+    Json(req): Json<CreateBeanRequest>,
+) -> Result<Json<Bean>, AppError> {
+    if req.name.trim().is_empty() {
+        return Err(AppError::bad_request("name is required"));
+    }
+    validate_bean_values(req.density, req.moisture_pct, req.cost_per_kg)
+        .map_err(AppError::bad_request)?;
+    let bean = state.session_service.create_bean(req).await?;
+    Ok(Json(bean))
+}
+
+async fn get_bean(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Bean>, AppError> {
+    let bean = state
+        .session_service
+        .get_bean(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Bean"))?;
+    Ok(Json(bean))
+}
+
+async fn update_bean(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateBeanRequest>,
+) -> Result<Json<Bean>, AppError> {
+    if req.name.as_deref().is_some_and(|n| n.trim().is_empty()) {
+        return Err(AppError::bad_request("name cannot be empty"));
+    }
+    validate_bean_values(req.density, req.moisture_pct, req.cost_per_kg)
+        .map_err(AppError::bad_request)?;
+    let bean = state
+        .session_service
+        .update_bean(&id, req)
+        .await?
+        .ok_or_else(|| AppError::not_found("Bean"))?;
+    Ok(Json(bean))
+}
+
+async fn delete_bean(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.session_service.delete_bean(&id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Bean"))
+    }
+}
+
+async fn list_sample_groups(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<SampleGroupSummary>>, AppError> {
+    if state.session_service.get_bean(&id).await?.is_none() {
+        return Err(AppError::not_found("Bean"));
+    }
+    let groups = state.session_service.list_sample_groups(&id).await?;
+    Ok(Json(groups))
+}
+
+/// Side-by-side evaluation sheet of a sample group's roasts and cuppings.
+async fn get_sample_evaluation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SampleEvaluation>, AppError> {
+    let evaluation = state
+        .session_service
+        .get_sample_evaluation(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Sample group"))?;
+    Ok(Json(evaluation))
+}
+
+/// Profiles ranked by past results on beans like the given one. Attributes
+/// given next to `bean_id` override the bean's.
+async fn recommend_profiles(
+    State(state): State<AppState>,
+    Query(q): Query<ProfileRecommendQuery>,
+) -> Result<Json<ProfileRecommendations>, AppError> {
+    let base = match &q.bean_id {
+        Some(id) => {
+            let bean = state
+                .session_service
+                .get_bean(id)
+                .await?
+                .ok_or_else(|| AppError::not_found("Bean"))?;
+            BeanAttributes::from(&bean)
+        }
+        None => BeanAttributes::default(),
+    };
+    let bean = BeanAttributes {
+        origin: q.origin.or(base.origin),
+        variety: q.variety.or(base.variety),
+        process: q.process.or(base.process),
+        density: q.density.or(base.density),
+    };
+    if bean.is_empty() {
+        return Err(AppError::bad_request(
+            "Give a bean_id with known attributes, or origin, variety, process or density",
+        ));
+    }
+    let limit = q
+        .limit
+        .unwrap_or(DEFAULT_RECOMMENDATIONS)
+        .clamp(1, MAX_RECOMMENDATIONS);
+    let recommendations = state
+        .session_service
+        .recommend_profiles(&bean, limit)
+        .await?;
+    Ok(Json(ProfileRecommendations {
+        bean,
+        recommendations,
+    }))
+}
@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::charge_correction;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Moisture and density correction tables, charge adjustments suggested
+/// from them and the adjustment each session was roasted with.
+pub fn charge_correction_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/charge-corrections",
+            get(list_corrections).put(replace_corrections),
+        )
+        .route(
+            "/api/profiles/:id/charge-adjustment",
+            get(suggest_adjustment),
+        )
+        .route(
+            "/api/sessions/:id/charge-adjustment",
+            get(get_session_adjustment).post(record_session_adjustment),
+        )
+}
+
+/// The bean's moisture and density: as given, else from its lot.
+async fn measurements(
+    state: &AppState,
+    bean_id: Option<&str>,
+    req: &ChargeAdjustmentRequest,
+) -> Result<(Option<f32>, Option<f32>), AppError> {
+    let bean = match bean_id {
+        Some(id) => Some(
+            state
+                .session_service
+                .get_bean(id)
+                .await?
+                .ok_or_else(|| AppError::not_found("Bean"))?,
+        ),
+        None => None,
+    };
+    let moisture_pct = req
+        .moisture_pct
+        .or(bean.as_ref().and_then(|b| b.moisture_pct));
+    let density = req.density.or(bean.as_ref().and_then(|b| b.density));
+    if moisture_pct.is_none() && density.is_none() {
+        return Err(AppError::bad_request(
+            "moisture_pct or density is required, or a bean_id of a lot with measurements",
+        ));
+    }
+    validate_bean_values(density, moisture_pct, None).map_err(AppError::bad_request)?;
+    Ok((moisture_pct, density))
+}
+
+async fn adjustment_for(
+    state: &AppState,
+    profile_id: &str,
+    bean_id: Option<&str>,
+    req: &ChargeAdjustmentRequest,
+) -> Result<ChargeAdjustment, AppError> {
+    let profile = state
+        .session_service
+        .get_profile_with_points(profile_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Profile"))?;
+    let (moisture_pct, density) = measurements(state, bean_id, req).await?;
+    let corrections = charge_correction::corrections(&state.db).await?;
+    Ok(charge_correction::adjust(
+        &profile.profile,
+        moisture_pct,
+        density,
+        &corrections,
+    ))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn list_corrections(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ChargeCorrection>>, AppError> {
+    let corrections = charge_correction::corrections(&state.db).await?;
+    Ok(Json(corrections))
+}
+
+async fn replace_corrections(
+    State(state): State<AppState>,
+    Json(req): Json<ReplaceChargeCorrectionsRequest>,
+) -> Result<Json<Vec<ChargeCorrection>>, AppError> {
+    charge_correction::validate(&req.corrections).map_err(AppError::bad_request)?;
+    let corrections = charge_correction::replace_corrections(&state.db, &req.corrections).await?;
+    Ok(Json(corrections))
+}
+
+async fn suggest_adjustment(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(req): Query<ChargeAdjustmentRequest>,
+) -> Result<Json<ChargeAdjustment>, AppError> {
+    let adjustment = adjustment_for(&state, &id, req.bean_id.as_deref(), &req).await?;
+    Ok(Json(adjustment))
+}
+
+async fn get_session_adjustment(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionChargeAdjustment>, AppError> {
+    let recorded = charge_correction::for_session(&state.db, &id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Charge adjustment"))?;
+    Ok(Json(recorded))
+}
+
+/// Compute the adjustment for the session's profile and bean (unless given
+/// in the body) and record it on the session.
+async fn record_session_adjustment(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<ChargeAdjustmentRequest>,
+) -> Result<Json<SessionChargeAdjustment>, AppError> {
+    let session = state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let profile_id = req
+        .profile_id
+        .clone()
+        .or(session.profile_id)
+        .ok_or_else(|| AppError::bad_request("session has no profile, pass profile_id"))?;
+    let bean_id = req.bean_id.clone().or(session.bean_id);
+    let adjustment = adjustment_for(&state, &profile_id, bean_id.as_deref(), &req).await?;
+    let recorded = charge_correction::record(&state.db, &id, &adjustment).await?;
+    Ok(Json(recorded))
+}
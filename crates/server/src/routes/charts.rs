@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::auth::Caller;
+use crate::chart::build_chart;
+use crate::i18n::RequestLocale;
+use crate::models::*;
+use crate::services::profile_zero;
+use crate::AppState;
+
+const DEFAULT_CHART_POINTS: usize = 1000;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Sessions pre-shaped for charting.
+pub fn chart_routes() -> Router<AppState> {
+    Router::new().route("/api/sessions/:id/chart", get(get_session_chart))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_session_chart(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Query(q): Query<ChartQuery>,
+    RequestLocale(locale): RequestLocale,
+) -> Result<Json<SessionChart>, AppError> {
+    let max_points = q.max_points.unwrap_or(DEFAULT_CHART_POINTS);
+    if max_points < 3 {
+        return Err(AppError::bad_request("max_points must be at least 3"));
+    }
+    let session = state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let smoothing = if q.raw {
+        None
+    } else {
+        let view = q.view.as_deref().unwrap_or(DEFAULT_SMOOTHING_VIEW);
+        let config = state
+            .user_service
+            .resolve_smoothing(caller.subject.as_deref(), view)
+            .await?;
+        Some(
+            state
+                .session_service
+                .with_ror_settings(&session.device_id, config)
+                .await?,
+        )
+    };
+    let telemetry = state.session_service.get_session_telemetry(&id).await?;
+    let events = state.session_service.get_roast_events(&id).await?;
+    let curve = match &session.profile_id {
+        Some(profile_id) => state
+            .session_service
+            .get_profile_with_points(profile_id)
+            .await?
+            .and_then(|p| state.session_service.profile_curve(&p)),
+        None => None,
+    };
+    let zero = profile_zero(
+        &events,
+        state.session_service.profile_charge_offset().await?,
+    );
+    Ok(Json(build_chart(
+        &id,
+        telemetry,
+        &events,
+        curve.as_deref().map(|curve| (curve, zero)),
+        smoothing,
+        max_points,
+        locale,
+    )))
+}
@@ -0,0 +1,157 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::auth::Caller;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Per-roaster pre-roast checklists and the checks made for each session.
+pub fn checklist_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/roaster/:device_id/checklist",
+            get(get_checklist)
+                .put(put_checklist)
+                .delete(delete_checklist),
+        )
+        .route("/api/sessions/:id/checklist", get(get_session_checklist))
+        .route(
+            "/api/sessions/:id/checklist/checks",
+            post(check_session_item),
+        )
+}
+
+fn validate_items(items: &[String]) -> Result<Vec<String>, AppError> {
+    let mut trimmed: Vec<String> = Vec::with_capacity(items.len());
+    for item in items.iter().map(|i| i.trim()) {
+        if item.is_empty() {
+            return Err(AppError::bad_request("checklist items must not be empty"));
+        }
+        if trimmed.iter().any(|t| t == item) {
+            return Err(AppError::bad_request(format!(
+                "duplicate checklist item: {}",
+                item
+            )));
+        }
+        trimmed.push(item.to_string());
+    }
+    Ok(trimmed)
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_checklist(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<PreroastChecklist>, AppError> {
+    let checklist = state
+        .session_service
+        .get_preroast_checklist(&device_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Checklist"))?;
+    Ok(Json(checklist))
+}
+
+async fn put_checklist(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(req): Json<UpsertPreroastChecklistRequest>,
+) -> Result<Json<PreroastChecklist>, AppError> {
+    let items = validate_items(&req.items)?;
+    let checklist = state
+        .session_service
+        .upsert_preroast_checklist(&device_id, items)
+        .await?;
+    Ok(Json(checklist))
+}
+
+async fn delete_checklist(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state
+        .session_service
+        .delete_preroast_checklist(&device_id)
+        .await?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Checklist"))
+    }
+}
+
+async fn get_session_checklist(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionChecklist>, AppError> {
+    let session = state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let checklist = state
+        .session_service
+        .session_checklist(&session)
+        .await?
+        .ok_or_else(|| AppError::not_found("Checklist"))?;
+    Ok(Json(checklist))
+}
+
+async fn check_session_item(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    caller: Caller,
+    Json(req): Json<CheckChecklistItemRequest>,
+) -> Result<Json<SessionChecklist>, AppError> {
+    let checked_by = req
+        .checked_by
+        .as_deref()
+        .map(str::trim)
+        .filter(|by| !by.is_empty())
+        .or_else(|| caller.label())
+        .ok_or_else(|| AppError::bad_request("checked_by is required"))?
+        .to_string();
+    let session = state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    if session.status != SessionStatus::Planning {
+        return Err(AppError::conflict(
+            "Checklist items can only be checked before the session starts",
+        ));
+    }
+    let checklist = state
+        .session_service
+        .session_checklist(&session)
+        .await?
+        .ok_or_else(|| AppError::not_found("Checklist"))?;
+    let item = req.item.trim();
+    if !checklist.items.iter().any(|i| i.item == item) {
+        return Err(AppError::bad_request(format!(
+            "not on the checklist: {}",
+            item
+        )));
+    }
+    state
+        .session_service
+        .check_checklist_item(&id, item, &checked_by)
+        .await?;
+    let checklist = state
+        .session_service
+        .session_checklist(&session)
+        .await?
+        .ok_or_else(|| AppError::not_found("Checklist"))?;
+    Ok(Json(checklist))
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+
+use super::alerts::validate_max_bean_temp;
+use super::auth::{require_admin, require_subject};
+use super::automations::validate_automation;
+use super::preheat::validate_settings;
+use super::AppError;
+use crate::auth::Caller;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Automations, alert limits and preheat safety limits as a YAML or JSON
+/// document, and importing one with an optional dry run.
+pub fn config_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/config/export", get(export_config))
+        .route("/api/config/import", post(import_config))
+}
+
+/// Parse a bundle as JSON when sent as such, else as YAML.
+fn parse_bundle(headers: &HeaderMap, body: &str) -> Result<ConfigBundle, AppError> {
+    let json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let bundle: ConfigBundle = if json {
+        serde_json::from_str(body)
+            .map_err(|e| AppError::bad_request(format!("Invalid JSON: {e}")))?
+    } else {
+        serde_yaml::from_str(body)
+            .map_err(|e| AppError::bad_request(format!("Invalid YAML: {e}")))?
+    };
+    if bundle.version != CONFIG_BUNDLE_VERSION {
+        return Err(AppError::bad_request(format!(
+            "Unsupported config version {} (expected {})",
+            bundle.version, CONFIG_BUNDLE_VERSION
+        )));
+    }
+    Ok(bundle)
+}
+
+/// Check every entry the way its own endpoint would, naming the entry.
+async fn validate_bundle(state: &AppState, bundle: &ConfigBundle) -> Result<(), AppError> {
+    let named = |key: &str, e: AppError| AppError::bad_request(format!("{key}: {}", e.message()));
+    let mut keys = HashSet::new();
+    for automation in bundle.automations.iter().flatten() {
+        let key = automation.key();
+        if automation.profile_id.is_some() == automation.device_id.is_some() {
+            return Err(AppError::bad_request(format!(
+                "{key}: set either profile_id or device_id"
+            )));
+        }
+        if !keys.insert(key.clone()) {
+            return Err(AppError::bad_request(format!("{key}: listed twice")));
+        }
+        validate_automation(&automation.request()).map_err(|e| named(&key, e))?;
+        if let Some(profile_id) = &automation.profile_id {
+            if state
+                .session_service
+                .get_profile_with_points(profile_id)
+                .await?
+                .is_none()
+            {
+                return Err(AppError::bad_request(format!("{key}: unknown profile")));
+            }
+        }
+    }
+
+    let mut devices = HashSet::new();
+    for limit in bundle.alert_limits.iter().flatten() {
+        if !devices.insert(&limit.device_id) {
+            return Err(AppError::bad_request(format!(
+                "{}: alert limit listed twice",
+                limit.device_id
+            )));
+        }
+        validate_max_bean_temp(limit.max_bean_temp).map_err(|e| named(&limit.device_id, e))?;
+    }
+
+    let mut devices = HashSet::new();
+    for preheat in bundle.preheat.iter().flatten() {
+        if !devices.insert(&preheat.device_id) {
+            return Err(AppError::bad_request(format!(
+                "{}: preheat listed twice",
+                preheat.device_id
+            )));
+        }
+        validate_settings(&UpsertBatchPreheatRequest {
+            enabled: preheat.enabled,
+            timeout_secs: Some(preheat.timeout_secs),
+            max_temp: Some(preheat.max_temp),
+            ready_band: Some(preheat.ready_band),
+        })
+        .map_err(|e| named(&preheat.device_id, e))?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn export_config(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<ConfigExportQuery>,
+) -> Result<Response, AppError> {
+    require_subject(&caller)?;
+    let bundle = state.config_bundles.export().await?;
+    Ok(match q.format {
+        ConfigFormat::Yaml => {
+            let yaml = serde_yaml::to_string(&bundle).map_err(AppError::internal)?;
+            ([(CONTENT_TYPE, "application/yaml")], yaml).into_response()
+        }
+        ConfigFormat::Json => Json(bundle).into_response(),
+    })
+}
+
+/// Admin only, dry runs included: automations drive the heater and fan.
+async fn import_config(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<ConfigImportQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<ConfigImportReport>, AppError> {
+    require_admin(&caller)?;
+    let bundle = parse_bundle(&headers, &body)?;
+    validate_bundle(&state, &bundle).await?;
+    let report = state.config_bundles.import(&bundle, q.dry_run).await?;
+    if !report.dry_run && !report.changes.is_empty() {
+        tracing::info!(changes = report.changes.len(), "Imported configuration");
+    }
+    Ok(Json(report))
+}
@@ -0,0 +1,47 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::Utc;
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Session cost accounting. Rates are the `cost_*` keys of `/api/settings`
+/// and bean prices the `cost_per_kg` of bean lots.
+pub fn cost_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/sessions/:id/cost", get(get_session_cost))
+        .route("/api/reports/daily", get(daily_report))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_session_cost(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionCost>, AppError> {
+    let cost = state
+        .session_service
+        .get_session_cost(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    Ok(Json(cost))
+}
+
+async fn daily_report(
+    State(state): State<AppState>,
+    Query(q): Query<DailyReportQuery>,
+) -> Result<Json<DailyReport>, AppError> {
+    let date = q.date.unwrap_or_else(|| Utc::now().date_naive());
+    let report = state.session_service.daily_report(date).await?;
+    Ok(Json(report))
+}
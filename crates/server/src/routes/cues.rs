@@ -0,0 +1,118 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Roast cue definitions on profiles and sessions.
+pub fn cue_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/profiles/:id/cues",
+            get(list_profile_cues).post(create_profile_cue),
+        )
+        .route(
+            "/api/sessions/:id/cues",
+            get(list_session_cues).post(create_session_cue),
+        )
+        .route("/api/cues/:id", delete(delete_cue))
+}
+
+fn validate_cue(req: &CreateCueRequest) -> Result<(), AppError> {
+    if req.message.trim().is_empty() {
+        return Err(AppError::bad_request("message is required"));
+    }
+    if !req.trigger_value.is_finite() {
+        return Err(AppError::bad_request("trigger_value must be a number"));
+    }
+    if req.trigger_type == CueTrigger::Elapsed && req.trigger_value < 0.0 {
+        return Err(AppError::bad_request(
+            "elapsed trigger must not be negative",
+        ));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn list_profile_cues(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<RoastCue>>, AppError> {
+    if state
+        .session_service
+        .get_profile_with_points(&id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::not_found("Profile"));
+    }
+    let cues = state.session_service.list_profile_cues(&id).await?;
+    Ok(Json(cues))
+}
+
+async fn create_profile_cue(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<CreateCueRequest>,
+) -> Result<(StatusCode, Json<RoastCue>), AppError> {
+    validate_cue(&req)?;
+    if state
+        .session_service
+        .get_profile_with_points(&id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::not_found("Profile"));
+    }
+    let cue = state.session_service.create_profile_cue(&id, req).await?;
+    Ok((StatusCode::CREATED, Json(cue)))
+}
+
+async fn list_session_cues(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<SessionCue>>, AppError> {
+    let session = state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let cues = state.session_service.list_session_cues(&session).await?;
+    Ok(Json(cues))
+}
+
+async fn create_session_cue(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<CreateCueRequest>,
+) -> Result<(StatusCode, Json<RoastCue>), AppError> {
+    validate_cue(&req)?;
+    if state.session_service.get_session(&id).await?.is_none() {
+        return Err(AppError::not_found("Session"));
+    }
+    let cue = state.session_service.create_session_cue(&id, req).await?;
+    Ok((StatusCode::CREATED, Json(cue)))
+}
+
+async fn delete_cue(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.session_service.delete_cue(&id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Cue"))
+    }
+}
@@ -0,0 +1,46 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Device health scores from RSSI and free heap, and their history.
+pub fn device_health_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/devices/health", get(list_device_health))
+        .route("/api/roaster/:device_id/health", get(get_device_health))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn list_device_health(State(state): State<AppState>) -> Json<Vec<DeviceHealthSnapshot>> {
+    Json(state.device_health.snapshots())
+}
+
+async fn get_device_health(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Query(q): Query<DeviceHealthQuery>,
+) -> Result<Json<DeviceHealthHistory>, AppError> {
+    if let (Some(since), Some(until)) = (q.since, q.until) {
+        if since > until {
+            return Err(AppError::bad_request("since must not be after until"));
+        }
+    }
+    let now = chrono::Utc::now().timestamp();
+    let points = state.device_health.history(&device_id, &q, now).await?;
+    Ok(Json(DeviceHealthHistory {
+        current: state.device_health.snapshot(&device_id),
+        points,
+    }))
+}
@@ -0,0 +1,90 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Firmware debug logs and the troubleshooting flag that persists them.
+/// The live stream is `/ws/logs/:device_id`.
+pub fn device_log_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/roaster/:device_id/logs", get(recent_logs))
+        .route("/api/roaster/:device_id/logs/history", get(log_history))
+        .route(
+            "/api/roaster/:device_id/troubleshooting",
+            get(get_troubleshooting)
+                .put(start_troubleshooting)
+                .delete(stop_troubleshooting),
+        )
+        .route("/api/devices/troubleshooting", get(list_troubleshooting))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn recent_logs(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Query(q): Query<DeviceLogQuery>,
+) -> Json<Vec<DeviceLogLine>> {
+    Json(state.device_logs.recent(&device_id, q.limit))
+}
+
+async fn log_history(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Query(q): Query<DeviceLogHistoryQuery>,
+) -> Result<Json<Vec<DeviceLogLine>>, AppError> {
+    let lines = state.device_logs.history(&device_id, &q).await?;
+    Ok(Json(lines))
+}
+
+async fn list_troubleshooting(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<LogCapture>>, AppError> {
+    let captures = state.device_logs.captures().await?;
+    Ok(Json(captures))
+}
+
+async fn get_troubleshooting(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<LogCapture>, AppError> {
+    let capture = state
+        .device_logs
+        .captures()
+        .await?
+        .into_iter()
+        .find(|c| c.device_id == device_id)
+        .ok_or_else(|| AppError::not_found("Troubleshooting flag"))?;
+    Ok(Json(capture))
+}
+
+async fn start_troubleshooting(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<LogCapture>, AppError> {
+    let capture = state.device_logs.start_capture(&device_id).await?;
+    Ok(Json(capture))
+}
+
+async fn stop_troubleshooting(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.device_logs.stop_capture(&device_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Troubleshooting flag"))
+    }
+}
@@ -4,65 +4,16 @@ use std::time::Instant;
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
     routing::{delete, get, post, put},
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
-use tracing::{error, info, warn};
+use serde::Deserialize;
+use tracing::{info, warn};
 
+use super::AppError;
 use crate::models::*;
 use crate::AppState;
 
-// ============================================================================
-// AppError — consistent JSON error responses
-// ============================================================================
-
-#[derive(Debug)]
-pub struct AppError {
-    status: StatusCode,
-    message: String,
-}
-
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
-    status: u16,
-}
-
-impl AppError {
-    fn not_found(entity: &str) -> Self {
-        Self {
-            status: StatusCode::NOT_FOUND,
-            message: format!("{} not found", entity),
-        }
-    }
-
-    fn internal(msg: impl std::fmt::Display) -> Self {
-        Self {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: msg.to_string(),
-        }
-    }
-}
-
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let body = ErrorResponse {
-            error: self.message,
-            status: self.status.as_u16(),
-        };
-        (self.status, Json(body)).into_response()
-    }
-}
-
-impl From<anyhow::Error> for AppError {
-    fn from(err: anyhow::Error) -> Self {
-        error!(?err, "Internal error");
-        Self::internal(err)
-    }
-}
-
 // ============================================================================
 // Query parameters
 // ============================================================================
@@ -107,6 +58,9 @@ pub fn device_routes() -> Router<AppState> {
         // Modbus Register Map
         .route("/api/devices/:id/register-map", get(get_register_map))
         .route("/api/devices/:id/register-map", put(set_register_map))
+        // Fan airflow calibration
+        .route("/api/devices/:id/fan-calibration", get(get_fan_calibration))
+        .route("/api/devices/:id/fan-calibration", put(set_fan_calibration))
         // Connection testing
         .route("/api/devices/test-connection", post(test_connection))
 }
@@ -320,6 +274,59 @@ async fn set_register_map(
     Ok(Json(updated))
 }
 
+// ============================================================================
+// Fan calibration handlers
+// ============================================================================
+
+async fn get_fan_calibration(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Vec<FanCalibrationPoint>>, AppError> {
+    state
+        .device_service
+        .get_device(&device_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Device"))?;
+
+    let points = state.device_service.get_fan_calibration(&device_id).await?;
+    Ok(Json(points))
+}
+
+async fn set_fan_calibration(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(points): Json<Vec<CreateFanCalibrationPoint>>,
+) -> Result<Json<Vec<FanCalibrationPoint>>, AppError> {
+    state
+        .device_service
+        .get_device(&device_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Device"))?;
+
+    if let Some(p) = points
+        .iter()
+        .find(|p| !(0..=255).contains(&p.fan_pwm) || !p.airflow.is_finite() || p.airflow < 0.0)
+    {
+        return Err(AppError::bad_request(format!(
+            "invalid calibration point at fan_pwm {}: fan_pwm must be 0..255 and airflow >= 0",
+            p.fan_pwm
+        )));
+    }
+
+    let mut pwms: Vec<i32> = points.iter().map(|p| p.fan_pwm).collect();
+    pwms.sort_unstable();
+    if pwms.windows(2).any(|w| w[0] == w[1]) {
+        return Err(AppError::bad_request("duplicate fan_pwm in calibration"));
+    }
+
+    let updated = state
+        .device_service
+        .set_fan_calibration(&device_id, points)
+        .await?;
+    state.telemetry_service.invalidate_fan_curves().await;
+    Ok(Json(updated))
+}
+
 // ============================================================================
 // Connection test handler
 // ============================================================================
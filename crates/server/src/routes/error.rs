@@ -0,0 +1,96 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use tracing::error;
+
+// ============================================================================
+// AppError — consistent JSON error responses
+// ============================================================================
+
+#[derive(Debug)]
+pub struct AppError {
+    status: StatusCode,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+    status: u16,
+}
+
+impl AppError {
+    pub(crate) fn not_found(entity: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: format!("{} not found", entity),
+        }
+    }
+
+    pub(crate) fn bad_request(msg: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: msg.to_string(),
+        }
+    }
+
+    pub(crate) fn unauthorized(msg: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            message: msg.to_string(),
+        }
+    }
+
+    pub(crate) fn forbidden(msg: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: msg.to_string(),
+        }
+    }
+
+    pub(crate) fn conflict(msg: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::CONFLICT,
+            message: msg.to_string(),
+        }
+    }
+
+    pub(crate) fn gateway_timeout(msg: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::GATEWAY_TIMEOUT,
+            message: msg.to_string(),
+        }
+    }
+
+    pub(crate) fn internal(msg: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: msg.to_string(),
+        }
+    }
+
+    /// The message alone, for replies outside HTTP (e.g. WebSocket commands).
+    pub(crate) fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let body = ErrorResponse {
+            error: self.message,
+            status: self.status.as_u16(),
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        error!(?err, "Internal error");
+        Self::internal(err)
+    }
+}
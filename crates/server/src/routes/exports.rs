@@ -0,0 +1,44 @@
+use axum::{extract::State, routing::post, Json, Router};
+
+use super::AppError;
+use crate::export_signing::split_artisan;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Verification of signed session exports. Open to anyone holding an
+/// export, signed in or not.
+pub fn export_routes() -> Router<AppState> {
+    Router::new().route("/api/exports/verify", post(verify_export))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// Whether an export is unchanged since the server signed it. A modified
+/// export is a `valid: false` result, malformed input a 400.
+async fn verify_export(
+    State(state): State<AppState>,
+    Json(req): Json<VerifyExportRequest>,
+) -> Result<Json<ExportVerification>, AppError> {
+    let (content, embedded) = match req.format {
+        ExportFormat::Csv => (req.content.into_bytes(), None),
+        ExportFormat::Artisan => split_artisan(&req.content).map_err(AppError::bad_request)?,
+    };
+    let integrity = req
+        .integrity
+        .or(embedded)
+        .ok_or_else(|| AppError::bad_request("Export has no integrity block to verify"))?;
+    if integrity.format != req.format {
+        return Err(AppError::bad_request(format!(
+            "Integrity block is for a {} export",
+            integrity.format
+        )));
+    }
+    let verification = state.export_signer.verify(&content, integrity).await?;
+    Ok(Json(verification))
+}
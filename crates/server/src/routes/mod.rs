@@ -1,3 +1,63 @@
+pub mod admin;
+pub mod alerts;
+pub mod analytics;
+pub mod archive;
+pub mod auth;
+pub mod automations;
+pub mod autotune;
+pub mod batch_scaling;
+pub mod beans;
+pub mod charge_corrections;
+pub mod charts;
+pub mod checklists;
+pub mod config;
+pub mod costs;
+pub mod cues;
+pub mod device_health;
+pub mod device_logs;
 pub mod devices;
+mod error;
+pub mod exports;
+pub mod preheat;
+pub mod probes;
+pub mod qr;
+pub mod queue;
+pub mod roastlog;
+pub mod session_notes;
+pub mod smoothing;
+pub mod status_history;
+pub mod sync;
+pub mod tolerance;
+pub mod webhooks;
 
+pub use admin::admin_routes;
+pub use alerts::alert_routes;
+pub use analytics::analytics_routes;
+pub use archive::archive_routes;
+pub use auth::auth_routes;
+pub use automations::automation_routes;
+pub use autotune::autotune_routes;
+pub use batch_scaling::batch_scaling_routes;
+pub use beans::bean_routes;
+pub use charge_corrections::charge_correction_routes;
+pub use charts::chart_routes;
+pub use checklists::checklist_routes;
+pub use config::config_routes;
+pub use costs::cost_routes;
+pub use cues::cue_routes;
+pub use device_health::device_health_routes;
+pub use device_logs::device_log_routes;
 pub use devices::device_routes;
+pub(crate) use error::AppError;
+pub use exports::export_routes;
+pub use preheat::preheat_routes;
+pub use probes::probe_routes;
+pub use qr::qr_routes;
+pub use queue::queue_routes;
+pub use roastlog::roastlog_routes;
+pub use session_notes::session_note_routes;
+pub use smoothing::smoothing_routes;
+pub use status_history::status_history_routes;
+pub use sync::sync_routes;
+pub use tolerance::tolerance_routes;
+pub use webhooks::webhook_routes;
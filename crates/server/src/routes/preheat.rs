@@ -0,0 +1,82 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::{preheat, AppState};
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Per-roaster between-batches preheat settings and the current run.
+pub fn preheat_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/roaster/:device_id/preheat",
+            get(get_preheat).put(put_preheat),
+        )
+        .route(
+            "/api/roaster/:device_id/preheat/cancel",
+            post(cancel_preheat),
+        )
+}
+
+pub(super) fn validate_settings(req: &UpsertBatchPreheatRequest) -> Result<(), AppError> {
+    if req.timeout_secs.is_some_and(|t| t <= 0) {
+        return Err(AppError::bad_request("timeout_secs must be positive"));
+    }
+    if req
+        .max_temp
+        .is_some_and(|t| !(t.is_finite() && (0.0..=300.0).contains(&t)))
+    {
+        return Err(AppError::bad_request(
+            "max_temp must be between 0 and 300 C",
+        ));
+    }
+    if req.ready_band.is_some_and(|b| !(b.is_finite() && b > 0.0)) {
+        return Err(AppError::bad_request("ready_band must be positive"));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_preheat(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<BatchPreheatStatus>, AppError> {
+    let settings = state.session_service.get_batch_preheat(&device_id).await?;
+    Ok(Json(BatchPreheatStatus {
+        settings,
+        run: state.preheat.run(&device_id),
+    }))
+}
+
+async fn put_preheat(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(req): Json<UpsertBatchPreheatRequest>,
+) -> Result<Json<BatchPreheatSettings>, AppError> {
+    validate_settings(&req)?;
+    let settings = state
+        .session_service
+        .upsert_batch_preheat(&device_id, req)
+        .await?;
+    Ok(Json(settings))
+}
+
+async fn cancel_preheat(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<PreheatRun>, AppError> {
+    let run = preheat::cancel(&state, &device_id)
+        .await
+        .ok_or_else(|| AppError::not_found("Running preheat"))?;
+    Ok(Json(run))
+}
@@ -0,0 +1,40 @@
+use axum::{
+    extract::{Path, State},
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::probes;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Temperature probe faults seen during a session.
+pub fn probe_routes() -> Router<AppState> {
+    Router::new().route("/api/sessions/:id/data-quality", get(get_data_quality))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_data_quality(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionDataQuality>, AppError> {
+    state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let issues = probes::for_session(&state.db, &id).await?;
+    Ok(Json(SessionDataQuality {
+        session_id: id,
+        ok: issues.is_empty(),
+        issues,
+    }))
+}
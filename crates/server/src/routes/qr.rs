@@ -0,0 +1,118 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, HOST},
+        HeaderMap,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use super::auth::require_admin;
+use super::AppError;
+use crate::auth::Caller;
+use crate::dashboard_qr::{
+    self, DEFAULT_SCALE, DEFAULT_TOKEN_TTL_DAYS, MAX_SCALE, MAX_TOKEN_TTL_DAYS,
+};
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// QR codes opening a roaster's live dashboard. Embedding a read-only
+/// token takes an admin.
+pub fn qr_routes() -> Router<AppState> {
+    Router::new().route("/api/roaster/:device_id/qr", get(dashboard_qr))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn dashboard_qr(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(device_id): Path<String>,
+    Query(q): Query<DashboardQrQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let known = state.device_registry.read().await.contains_key(&device_id)
+        || state
+            .device_service
+            .get_device_by_device_id(&device_id)
+            .await?
+            .is_some();
+    if !known {
+        return Err(AppError::not_found("Device"));
+    }
+    let scale = q.scale.unwrap_or(DEFAULT_SCALE);
+    if !(1..=MAX_SCALE).contains(&scale) {
+        return Err(AppError::bad_request(format!(
+            "scale must be between 1 and {MAX_SCALE}"
+        )));
+    }
+
+    let token = if q.token {
+        require_admin(&caller)?;
+        let days = q.token_ttl_days.unwrap_or(DEFAULT_TOKEN_TTL_DAYS);
+        let ttl_secs = (1..=MAX_TOKEN_TTL_DAYS)
+            .contains(&days)
+            .then(|| days.checked_mul(86_400))
+            .flatten()
+            .ok_or_else(|| {
+                AppError::bad_request(format!(
+                    "token_ttl_days must be between 1 and {MAX_TOKEN_TTL_DAYS}"
+                ))
+            })?;
+        let token = state
+            .auth
+            .signer
+            .issue_dashboard(&device_id, ttl_secs)
+            .map_err(|e| AppError::bad_request(e.to_string()))?;
+        Some(token)
+    } else {
+        None
+    };
+
+    let base = match dashboard_qr::public_url_from_env() {
+        Some(base) => base,
+        None => request_origin(&headers).ok_or_else(|| {
+            AppError::bad_request("Set RUSTROAST_PUBLIC_URL or send a Host header")
+        })?,
+    };
+    let link = dashboard_qr::dashboard_link(&base, &device_id, token.as_deref())
+        .map_err(AppError::bad_request)?;
+
+    // A code carrying a token is a credential; keep it out of caches.
+    let cache = if token.is_some() {
+        "no-store"
+    } else {
+        "public, max-age=300"
+    };
+    Ok(match q.format {
+        QrFormat::Png => (
+            [(CONTENT_TYPE, "image/png"), (CACHE_CONTROL, cache)],
+            dashboard_qr::render_png(&link, scale)?,
+        )
+            .into_response(),
+        QrFormat::Svg => (
+            [(CONTENT_TYPE, "image/svg+xml"), (CACHE_CONTROL, cache)],
+            dashboard_qr::render_svg(&link, scale)?,
+        )
+            .into_response(),
+    })
+}
+
+/// `scheme://host` the request was sent to, honouring `X-Forwarded-Proto`
+/// from a reverse proxy.
+fn request_origin(headers: &HeaderMap) -> Option<String> {
+    let host = headers.get(HOST)?.to_str().ok()?;
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    Some(format!("{scheme}://{host}"))
+}
@@ -0,0 +1,70 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::Utc;
+
+use super::AppError;
+use crate::models::*;
+use crate::roast_queue::DEFAULT_DONE_HOURS;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// The production queue board of planned batches.
+pub fn queue_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/queue", get(get_queue).post(enqueue_batch))
+        .route("/api/queue/:session_id", delete(dequeue_batch))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_queue(
+    State(state): State<AppState>,
+    Query(q): Query<QueueQuery>,
+) -> Result<Json<Vec<QueueEntry>>, AppError> {
+    let done_hours = q.done_hours.unwrap_or(DEFAULT_DONE_HOURS);
+    if done_hours < 0 {
+        return Err(AppError::bad_request("done_hours must not be negative"));
+    }
+    let done_since = Utc::now() - chrono::Duration::hours(done_hours);
+    Ok(Json(state.roast_queue.board(done_since).await?))
+}
+
+async fn enqueue_batch(
+    State(state): State<AppState>,
+    Json(req): Json<EnqueueBatchRequest>,
+) -> Result<(StatusCode, Json<QueueEntry>), AppError> {
+    let session = state
+        .session_service
+        .get_session(&req.session_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    if session.status != SessionStatus::Planning {
+        return Err(AppError::conflict(
+            "Only sessions still in planning can be queued",
+        ));
+    }
+    if state.roast_queue.get(&session.id).await?.is_some() {
+        return Err(AppError::conflict("Session is already queued"));
+    }
+    let entry = state.roast_queue.enqueue(&session.id, Utc::now()).await?;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+async fn dequeue_batch(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if !state.roast_queue.remove(&session_id).await? {
+        return Err(AppError::not_found("Queued session"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
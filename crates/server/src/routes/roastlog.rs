@@ -0,0 +1,81 @@
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::roastlog::{self, ImportError};
+use crate::AppState;
+
+/// Largest `.rroastlog` upload accepted.
+const MAX_UPLOAD_BYTES: usize = 256 * 1024 * 1024;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Single-file `.rroastlog` export of a completed session, and importing
+/// one from this or another instance.
+pub fn roastlog_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/sessions/:id/export/rroastlog", get(export_roastlog))
+        .route(
+            "/api/sessions/import/rroastlog",
+            post(import_roastlog).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn export_roastlog(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let bundle = state
+        .session_service
+        .session_archive_bundle(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    if bundle.session.status != SessionStatus::Completed {
+        return Err(AppError::conflict(
+            "only completed sessions can be exported as a .rroastlog",
+        ));
+    }
+    let zip = roastlog::export(&state.session_service, &state.db, &bundle).await?;
+    let headers = [
+        (CONTENT_TYPE, "application/zip".to_string()),
+        (
+            CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}\"",
+                roastlog::filename(&bundle.session)
+            ),
+        ),
+    ];
+    Ok((headers, zip).into_response())
+}
+
+/// Import the raw `.rroastlog` request body.
+async fn import_roastlog(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<RoastlogImport>), AppError> {
+    match roastlog::import(&state.session_service, &state.db, &body).await {
+        Ok(imported) => Ok((StatusCode::CREATED, Json(imported))),
+        Err(ImportError::Invalid(msg)) => Err(AppError::bad_request(msg)),
+        Err(ImportError::Exists(id)) => {
+            Err(AppError::conflict(format!("Session {id} already exists")))
+        }
+        Err(ImportError::Other(e)) => Err(e.into()),
+    }
+}
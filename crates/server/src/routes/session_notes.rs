@@ -0,0 +1,180 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::Utc;
+
+use super::AppError;
+use crate::auth::Caller;
+use crate::models::*;
+use crate::session_notes;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Voice memos and photos taken during a session.
+pub fn session_note_routes() -> Router<AppState> {
+    Router::new()
+        .route("/ws/sessions/:id/notes", get(ws_session_notes))
+        .route("/api/sessions/:id/attachments", get(list_attachments))
+        .route(
+            "/api/sessions/:id/attachments/:attachment_id",
+            get(get_attachment).delete(delete_attachment),
+        )
+}
+
+async fn active_session(state: &AppState, id: &str) -> Result<RoastSession, AppError> {
+    let session = state
+        .session_service
+        .get_session(id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    if session.status != SessionStatus::Active {
+        return Err(AppError::conflict(
+            "Notes can only be added while the session is active",
+        ));
+    }
+    Ok(session)
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// Each binary frame is stored as one attachment, marked with the seconds
+/// since the session started, and answered with
+/// `{"type": "attachment_saved", "attachment": {...}}` or
+/// `{"type": "attachment_error", "error": ...}`. Needs an operator or
+/// admin.
+async fn ws_session_notes(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    caller: Caller,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    match caller.role {
+        None => return Err(AppError::unauthorized("Authentication required")),
+        Some(UserRole::Viewer) => {
+            return Err(AppError::forbidden("Viewers can't add session notes"))
+        }
+        Some(_) => {}
+    }
+    let session = active_session(&state, &id).await?;
+    let max_bytes = session_notes::max_bytes();
+    Ok(ws
+        .max_message_size(max_bytes)
+        .max_frame_size(max_bytes)
+        .on_upgrade(move |socket| notes_ws_loop(state, session.id, caller, socket)))
+}
+
+async fn notes_ws_loop(state: AppState, session_id: String, caller: Caller, mut socket: WebSocket) {
+    state.metrics.ws_clients.inc();
+    while let Some(Ok(message)) = socket.recv().await {
+        let reply = match message {
+            Message::Binary(data) => {
+                match save_attachment(&state, &session_id, &caller, &data).await {
+                    Ok(attachment) => serde_json::json!({
+                        "type": "attachment_saved",
+                        "attachment": attachment,
+                    }),
+                    Err(e) => serde_json::json!({
+                        "type": "attachment_error",
+                        "error": e.message(),
+                    }),
+                }
+            }
+            Message::Text(text)
+                if serde_json::from_str::<serde_json::Value>(&text)
+                    .is_ok_and(|v| v["type"] == "ping") =>
+            {
+                serde_json::json!({"type": "pong"})
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            break;
+        }
+    }
+    state.metrics.ws_clients.dec();
+}
+
+async fn save_attachment(
+    state: &AppState,
+    session_id: &str,
+    caller: &Caller,
+    data: &[u8],
+) -> Result<SessionAttachment, AppError> {
+    let (kind, content_type) = session_notes::sniff(data).ok_or_else(|| {
+        AppError::bad_request(format!(
+            "Unsupported attachment, expected {}",
+            session_notes::ACCEPTED
+        ))
+    })?;
+    // The session may have ended since the socket opened
+    let session = active_session(state, session_id).await?;
+    let elapsed = session
+        .start_time
+        .map(|start| (Utc::now() - start).num_milliseconds().max(0) as f64 / 1000.0)
+        .unwrap_or(0.0);
+    let attachment = state
+        .session_service
+        .add_session_attachment(
+            session_id,
+            kind,
+            content_type,
+            elapsed,
+            data,
+            caller.label(),
+        )
+        .await?;
+    Ok(attachment)
+}
+
+async fn list_attachments(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<SessionAttachment>>, AppError> {
+    state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let attachments = state.session_service.list_session_attachments(&id).await?;
+    Ok(Json(attachments))
+}
+
+async fn get_attachment(
+    State(state): State<AppState>,
+    Path((id, attachment_id)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    let (content_type, data) = state
+        .session_service
+        .get_session_attachment_data(&id, &attachment_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Attachment"))?;
+    Ok(([(CONTENT_TYPE, content_type)], data).into_response())
+}
+
+async fn delete_attachment(
+    State(state): State<AppState>,
+    Path((id, attachment_id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    if state
+        .session_service
+        .delete_session_attachment(&id, &attachment_id)
+        .await?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Attachment"))
+    }
+}
@@ -0,0 +1,203 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+
+use super::auth::require_subject;
+use super::AppError;
+use crate::auth::Caller;
+use crate::models::*;
+use crate::smoothing::smooth_telemetry;
+use crate::AppState;
+
+const MAX_VIEW_LEN: usize = 64;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Curve smoothing presets per user and view, RoR settings per roaster, and
+/// raw vs smoothed series.
+pub fn smoothing_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/smoothing", get(get_smoothing))
+        .route("/api/me/smoothing", get(list_presets))
+        .route(
+            "/api/me/smoothing/:view",
+            put(upsert_preset).delete(delete_preset),
+        )
+        .route(
+            "/api/roaster/:device_id/ror",
+            get(get_ror_settings)
+                .put(put_ror_settings)
+                .delete(delete_ror_settings),
+        )
+        .route(
+            "/api/sessions/:id/telemetry/smoothed",
+            get(get_smoothed_telemetry),
+        )
+}
+
+fn view_or_default(view: Option<&str>) -> &str {
+    view.unwrap_or(DEFAULT_SMOOTHING_VIEW)
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// The caller's smoothing for a view; server settings when signed out. With
+/// a `device_id`, that roaster's RoR window and unit apply.
+async fn get_smoothing(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(q): Query<SmoothingQuery>,
+) -> Result<Json<SmoothingConfig>, AppError> {
+    let view = view_or_default(q.view.as_deref());
+    let mut config = state
+        .user_service
+        .resolve_smoothing(caller.subject.as_deref(), view)
+        .await?;
+    if let Some(device_id) = &q.device_id {
+        config = state
+            .session_service
+            .with_ror_settings(device_id, config)
+            .await?;
+    }
+    Ok(Json(config))
+}
+
+async fn list_presets(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<SmoothingPreset>>, AppError> {
+    let subject = require_subject(&caller)?;
+    let presets = state.user_service.list_smoothing_presets(subject).await?;
+    Ok(Json(presets))
+}
+
+async fn upsert_preset(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(view): Path<String>,
+    Json(config): Json<SmoothingConfig>,
+) -> Result<Json<SmoothingPreset>, AppError> {
+    let subject = require_subject(&caller)?;
+    if view.trim().is_empty() || view.len() > MAX_VIEW_LEN {
+        return Err(AppError::bad_request(format!(
+            "view must be 1 to {} characters",
+            MAX_VIEW_LEN
+        )));
+    }
+    config.validate().map_err(AppError::bad_request)?;
+    let preset = state
+        .user_service
+        .upsert_smoothing_preset(subject, &view, &config)
+        .await?;
+    Ok(Json(preset))
+}
+
+async fn delete_preset(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(view): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let subject = require_subject(&caller)?;
+    if state
+        .user_service
+        .delete_smoothing_preset(subject, &view)
+        .await?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Smoothing preset"))
+    }
+}
+
+async fn get_ror_settings(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<RoasterRorSettings>, AppError> {
+    state
+        .session_service
+        .get_ror_settings(&device_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("RoR settings"))
+}
+
+async fn put_ror_settings(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(req): Json<UpsertRorSettingsRequest>,
+) -> Result<Json<RoasterRorSettings>, AppError> {
+    if !(1.0..=300.0).contains(&req.window_secs) {
+        return Err(AppError::bad_request(
+            "window_secs must be between 1 and 300",
+        ));
+    }
+    let settings = state
+        .session_service
+        .upsert_ror_settings(&device_id, req)
+        .await?;
+    Ok(Json(settings))
+}
+
+/// Back to the caller's smoothing and the default scoreboard window.
+async fn delete_ror_settings(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state
+        .session_service
+        .delete_ror_settings(&device_id)
+        .await?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("RoR settings"))
+    }
+}
+
+/// A session's raw telemetry next to the smoothed series, for comparing
+/// smoothing settings on the same roast.
+async fn get_smoothed_telemetry(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Query(q): Query<SmoothedTelemetryQuery>,
+) -> Result<Json<SmoothedSessionTelemetry>, AppError> {
+    let session = state
+        .session_service
+        .get_session(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session"))?;
+    let view = view_or_default(q.view.as_deref());
+    let resolved = state
+        .user_service
+        .resolve_smoothing(caller.subject.as_deref(), view)
+        .await?;
+    let resolved = state
+        .session_service
+        .with_ror_settings(&session.device_id, resolved)
+        .await?;
+    let smoothing = SmoothingConfig {
+        bt_window_secs: q.bt_window_secs.unwrap_or(resolved.bt_window_secs),
+        et_window_secs: q.et_window_secs.unwrap_or(resolved.et_window_secs),
+        ror_window_secs: q.ror_window_secs.unwrap_or(resolved.ror_window_secs),
+        ror_algorithm: q.ror_algorithm.unwrap_or(resolved.ror_algorithm),
+        ror_unit: q.ror_unit.unwrap_or(resolved.ror_unit),
+    };
+    smoothing.validate().map_err(AppError::bad_request)?;
+
+    let raw = state.session_service.get_session_telemetry(&id).await?;
+    let smoothed = smooth_telemetry(&raw, &smoothing);
+    Ok(Json(SmoothedSessionTelemetry {
+        session_id: id,
+        smoothing,
+        raw,
+        smoothed,
+    }))
+}
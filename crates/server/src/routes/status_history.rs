@@ -0,0 +1,56 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Stored device status snapshots, for tracing WiFi drops, IP changes and
+/// firmware upgrades over time, and a device's whole state at a past moment.
+pub fn status_history_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/roaster/:device_id/status/history",
+            get(status_history),
+        )
+        .route("/api/roaster/:device_id/state_at", get(state_at))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn status_history(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Query(q): Query<StatusHistoryQuery>,
+) -> Result<Json<Vec<StatusSnapshot>>, AppError> {
+    if let (Some(since), Some(until)) = (q.since, q.until) {
+        if since > until {
+            return Err(AppError::bad_request("since must not be after until"));
+        }
+    }
+    let snapshots = state.status_history.history(&device_id, &q).await?;
+    Ok(Json(snapshots))
+}
+
+async fn state_at(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Query(q): Query<DeviceStateQuery>,
+) -> Result<Json<DeviceStateAt>, AppError> {
+    if q.ts < 0 {
+        return Err(AppError::bad_request("ts must be unix seconds"));
+    }
+    crate::device_state::state_at(&state.read_db, &device_id, q.ts)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("Device state"))
+}
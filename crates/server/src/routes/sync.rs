@@ -0,0 +1,66 @@
+use axum::{
+    extract::{Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+
+use super::AppError;
+use crate::auth::Caller;
+use crate::models::*;
+use crate::AppState;
+
+const DEFAULT_PULL_LIMIT: i64 = 500;
+const MAX_PULL_LIMIT: i64 = 1000;
+const MAX_PUSH_CHANGES: usize = 500;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Offline-first sync for mobile logging clients: pull the change journal
+/// for sessions, events and cuppings, and push changes recorded offline.
+pub fn sync_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/sync/pull", get(pull))
+        .route("/api/sync/push", post(push))
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn pull(
+    State(state): State<AppState>,
+    Query(q): Query<SyncPullQuery>,
+) -> Result<Json<SyncPullResponse>, AppError> {
+    if q.since < 0 {
+        return Err(AppError::bad_request("since must not be negative"));
+    }
+    let limit = q
+        .limit
+        .unwrap_or(DEFAULT_PULL_LIMIT)
+        .clamp(1, MAX_PULL_LIMIT);
+    let changes = state.session_service.sync_pull(q.since, limit).await?;
+    Ok(Json(changes))
+}
+
+async fn push(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<SyncPushRequest>,
+) -> Result<Json<SyncPushResponse>, AppError> {
+    if req.client_id.trim().is_empty() {
+        return Err(AppError::bad_request("client_id is required"));
+    }
+    if req.changes.len() > MAX_PUSH_CHANGES {
+        return Err(AppError::bad_request(format!(
+            "at most {} changes per push",
+            MAX_PUSH_CHANGES
+        )));
+    }
+    let results = state
+        .session_service
+        .sync_push(req, caller.is_admin())
+        .await?;
+    Ok(Json(results))
+}
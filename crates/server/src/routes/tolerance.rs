@@ -0,0 +1,64 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use super::AppError;
+use crate::models::*;
+use crate::tolerance;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Tolerance bands a roast on the profile must stay within.
+pub fn tolerance_routes() -> Router<AppState> {
+    Router::new().route(
+        "/api/profiles/:id/tolerance",
+        get(get_tolerance)
+            .put(set_tolerance)
+            .delete(remove_tolerance),
+    )
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn get_tolerance(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ProfileTolerance>, AppError> {
+    tolerance::get(&state.db, &id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("Tolerance band"))
+}
+
+async fn set_tolerance(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<SetProfileToleranceRequest>,
+) -> Result<Json<ProfileTolerance>, AppError> {
+    tolerance::validate(&req).map_err(AppError::bad_request)?;
+    state
+        .session_service
+        .get_profile_with_points(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Profile"))?;
+    Ok(Json(tolerance::set(&state.db, &id, &req).await?))
+}
+
+async fn remove_tolerance(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if tolerance::remove(&state.db, &id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::not_found("Tolerance band"))
+    }
+}
@@ -0,0 +1,132 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use super::auth::require_admin;
+use super::AppError;
+use crate::auth::Caller;
+use crate::models::*;
+use crate::AppState;
+
+// ============================================================================
+// Route builder
+// ============================================================================
+
+/// Outbound webhooks (admin only) and their delivery log.
+pub fn webhook_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/webhooks", get(list_webhooks).post(create_webhook))
+        .route(
+            "/api/webhooks/:id",
+            get(get_webhook).put(update_webhook).delete(delete_webhook),
+        )
+        .route("/api/webhooks/:id/deliveries", get(list_webhook_deliveries))
+}
+
+fn validate_url(url: &str) -> Result<(), AppError> {
+    match reqwest::Url::parse(url) {
+        Ok(u) if matches!(u.scheme(), "http" | "https") => Ok(()),
+        _ => Err(AppError::bad_request("url must be an http(s) URL")),
+    }
+}
+
+fn validate_secret(secret: &str) -> Result<(), AppError> {
+    if secret.is_empty() {
+        return Err(AppError::bad_request("secret must not be empty"));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+async fn list_webhooks(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<Webhook>>, AppError> {
+    require_admin(&caller)?;
+    Ok(Json(state.webhooks.list().await?))
+}
+
+async fn create_webhook(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), AppError> {
+    require_admin(&caller)?;
+    if req.name.trim().is_empty() {
+        return Err(AppError::bad_request("name is required"));
+    }
+    validate_url(&req.url)?;
+    validate_secret(&req.secret)?;
+    let webhook = state.webhooks.create(req).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+async fn get_webhook(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<Json<Webhook>, AppError> {
+    require_admin(&caller)?;
+    let webhook = state
+        .webhooks
+        .get(&id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook"))?;
+    Ok(Json(webhook))
+}
+
+async fn update_webhook(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>, AppError> {
+    require_admin(&caller)?;
+    if req.name.as_ref().is_some_and(|n| n.trim().is_empty()) {
+        return Err(AppError::bad_request("name must not be empty"));
+    }
+    if let Some(url) = &req.url {
+        validate_url(url)?;
+    }
+    if let Some(secret) = &req.secret {
+        validate_secret(secret)?;
+    }
+    let webhook = state
+        .webhooks
+        .update(&id, req)
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook"))?;
+    Ok(Json(webhook))
+}
+
+async fn delete_webhook(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&caller)?;
+    if !state.webhooks.delete(&id).await? {
+        return Err(AppError::not_found("Webhook"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+    Query(q): Query<WebhookDeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, AppError> {
+    require_admin(&caller)?;
+    if state.webhooks.get(&id).await?.is_none() {
+        return Err(AppError::not_found("Webhook"));
+    }
+    let deliveries = state.webhooks.list_deliveries(&id, q.limit).await?;
+    Ok(Json(deliveries))
+}
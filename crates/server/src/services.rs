@@ -1,36 +1,162 @@
+use crate::i18n::Locale;
 use crate::models::*;
+use crate::profile_curve::{ProfileCurve, ProfileCurveCache, DEFAULT_STEP_SECS};
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
+/// Derived statistics written to a session on completion (and by
+/// recompute).
+struct CompletionStats {
+    total_time_seconds: Option<i32>,
+    max_temp: Option<f32>,
+    max_ror: Option<f32>,
+    first_crack_time: Option<i32>,
+    development_time_ratio: Option<f32>,
+    weight_loss_pct: Option<f32>,
+    avg_ror_drying: Option<f32>,
+    avg_ror_maillard: Option<f32>,
+    avg_ror_development: Option<f32>,
+    drying_end_time: Option<i32>,
+    drying_end_temp: Option<f32>,
+    auc_value: Option<f32>,
+    scoreboard: Option<ProfileScoreboard>,
+}
+
+/// `SET` assignments for [`CompletionStats`], bound by
+/// [`CompletionStats::bind`].
+const COMPLETION_STATS_SET: &str = r#"
+    total_time_seconds = ?, max_temp = ?, max_ror = ?,
+    first_crack_time = COALESCE(?, first_crack_time),
+    development_time_ratio = COALESCE(?, development_time_ratio),
+    weight_loss_pct = ?,
+    avg_ror_drying = ?, avg_ror_maillard = ?, avg_ror_development = ?,
+    drying_end_time = ?, drying_end_temp = ?,
+    auc_value = ?,
+    profile_fc_delta = ?, profile_drop_time_delta = ?,
+    profile_drop_temp_delta = ?, profile_deviation_integral = ?"#;
+
+impl CompletionStats {
+    fn bind<'q, O>(
+        &self,
+        query: sqlx::query::QueryAs<'q, sqlx::Sqlite, O, sqlx::sqlite::SqliteArguments<'q>>,
+    ) -> sqlx::query::QueryAs<'q, sqlx::Sqlite, O, sqlx::sqlite::SqliteArguments<'q>> {
+        let scoreboard = self.scoreboard.as_ref();
+        query
+            .bind(self.total_time_seconds)
+            .bind(self.max_temp)
+            .bind(self.max_ror)
+            .bind(self.first_crack_time)
+            .bind(self.development_time_ratio)
+            .bind(self.weight_loss_pct)
+            .bind(self.avg_ror_drying)
+            .bind(self.avg_ror_maillard)
+            .bind(self.avg_ror_development)
+            .bind(self.drying_end_time)
+            .bind(self.drying_end_temp)
+            .bind(self.auc_value)
+            .bind(scoreboard.and_then(|s| s.first_crack_delta))
+            .bind(scoreboard.and_then(|s| s.drop_time_delta))
+            .bind(scoreboard.and_then(|s| s.drop_temp_delta))
+            .bind(scoreboard.map(|s| s.deviation_integral))
+    }
+}
+
+/// Samples of a bean evaluated side by side, each on a different profile.
+const SAMPLE_GROUP_SIZE: i64 = 3;
+/// Green weight of a sample roast when none is given.
+const SAMPLE_GREEN_WEIGHT_G: f32 = 100.0;
+
 #[derive(Clone)]
 pub struct RoastSessionService {
     db: SqlitePool,
+    /// Pool for heavy history/export reads; defaults to `db`.
+    read_db: SqlitePool,
+    /// Sync client whose pushed changes this instance is applying.
+    sync_origin: Option<String>,
+    activity_tx: broadcast::Sender<SessionActivity>,
+    /// Interpolated profile curves, dropped when a profile changes.
+    curves: Arc<ProfileCurveCache>,
 }
 
 impl RoastSessionService {
     pub fn new(db: SqlitePool) -> Self {
-        Self { db }
+        let (activity_tx, _) = broadcast::channel(64);
+        Self {
+            read_db: db.clone(),
+            db,
+            sync_origin: None,
+            activity_tx,
+            curves: Arc::default(),
+        }
+    }
+
+    /// Subscribe to sessions starting and completing and to new roast events.
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionActivity> {
+        self.activity_tx.subscribe()
+    }
+
+    fn publish(&self, activity: SessionActivity) {
+        let _ = self.activity_tx.send(activity);
+    }
+
+    pub fn with_read_pool(mut self, read_db: SqlitePool) -> Self {
+        self.read_db = read_db;
+        self
+    }
+
+    fn with_sync_origin(mut self, client_id: &str) -> Self {
+        self.sync_origin = Some(client_id.to_string());
+        self
     }
 
     // Session Management
     pub async fn create_session(&self, req: CreateSessionRequest) -> Result<RoastSession> {
-        let id = Uuid::new_v4().to_string();
+        self.insert_session(&Uuid::new_v4().to_string(), req).await
+    }
+
+    /// Insert a session under a caller-chosen id (sync clients create ids offline).
+    async fn insert_session(
+        &self,
+        id: &str,
+        mut req: CreateSessionRequest,
+    ) -> Result<RoastSession> {
         let now = Utc::now();
+        let mut bean_name = None;
+        if let Some(bean_id) = &req.bean_id {
+            if let Some(bean) = self.get_bean(bean_id).await? {
+                req.bean_origin = req.bean_origin.or(bean.origin);
+                req.bean_variety = req.bean_variety.or(bean.variety);
+                bean_name = Some(bean.name);
+            }
+        }
+        let mut sample_group_id = None;
+        if req.session_type == SessionType::Sample {
+            if req.name.trim().is_empty() {
+                req.name = format!("{} sample", bean_name.as_deref().unwrap_or("Bean"));
+            }
+            req.green_weight = req.green_weight.or(Some(SAMPLE_GREEN_WEIGHT_G));
+            if let (Some(bean_id), Some(profile_id)) = (&req.bean_id, &req.profile_id) {
+                sample_group_id = Some(self.sample_group_for(bean_id, profile_id).await?);
+            }
+        }
 
         let session = sqlx::query_as::<_, RoastSession>(
             r#"
             INSERT INTO roast_sessions (
                 id, name, device_id, profile_id, status, start_time, created_at, updated_at,
                 bean_origin, bean_variety, green_weight, target_roast_level, 
-                notes, ambient_temp, humidity
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                notes, ambient_temp, humidity, roaster, session_type, bean_id, sample_group_id
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             RETURNING *
             "#,
         )
-        .bind(&id)
+        .bind(id)
         .bind(&req.name)
         .bind(&req.device_id)
         .bind(&req.profile_id)
@@ -45,15 +171,22 @@ impl RoastSessionService {
         .bind(&req.notes)
         .bind(req.ambient_temp)
         .bind(req.humidity)
+        .bind(&req.roaster)
+        .bind(req.session_type)
+        .bind(&req.bean_id)
+        .bind(&sample_group_id)
         .fetch_one(&self.db)
         .await?;
 
+        self.journal(SyncEntity::Session, &session.id, SyncOp::Upsert)
+            .await?;
         Ok(session)
     }
 
     pub async fn list_sessions(
         &self,
         device_id: Option<&str>,
+        roaster: Option<&str>,
         limit: Option<i32>,
     ) -> Result<Vec<RoastSession>> {
         let mut query = "SELECT * FROM roast_sessions".to_string();
@@ -62,6 +195,9 @@ impl RoastSessionService {
         if device_id.is_some() {
             conditions.push("device_id = ?");
         }
+        if roaster.is_some() {
+            conditions.push("roaster = ?");
+        }
 
         if !conditions.is_empty() {
             query.push_str(" WHERE ");
@@ -79,11 +215,124 @@ impl RoastSessionService {
         if let Some(device_id) = device_id {
             query_builder = query_builder.bind(device_id);
         }
+        if let Some(roaster) = roaster {
+            query_builder = query_builder.bind(roaster);
+        }
 
-        let sessions = query_builder.fetch_all(&self.db).await?;
+        let sessions = query_builder.fetch_all(&self.read_db).await?;
         Ok(sessions)
     }
 
+    /// Weekly aggregates of a completion statistic across completed sessions,
+    /// one series per bean origin or profile (or a single series by week).
+    /// Read from the session summaries, so `from` and `to` select whole days.
+    pub async fn session_trends(&self, q: &TrendQuery) -> Result<TrendsResponse> {
+        let (key_expr, label_expr) = match q.group_by {
+            TrendGroupBy::Bean => ("NULLIF(ss.bean_origin, '')", "NULLIF(ss.bean_origin, '')"),
+            TrendGroupBy::Profile => ("NULLIF(ss.profile_id, '')", "p.name"),
+            TrendGroupBy::Week => ("NULL", "NULL"),
+        };
+        let column = q.metric.column();
+        let mut query = format!(
+            r#"
+            SELECT {key_expr} AS grp, MAX({label_expr}) AS label,
+                date(ss.day, '-6 days', 'weekday 1') AS week,
+                SUM(ss.{column}_n) AS n, SUM(ss.{column}_sum) / SUM(ss.{column}_n) AS avg_v,
+                MIN(ss.{column}_min) AS min_v, MAX(ss.{column}_max) AS max_v
+            FROM session_summaries ss
+            LEFT JOIN roast_profiles p ON p.id = ss.profile_id
+            WHERE ss.{column}_n > 0
+            "#
+        );
+        push_day_range(&mut query, q.from, q.to);
+        query.push_str(" GROUP BY grp, week ORDER BY grp, week");
+
+        let mut query_builder = sqlx::query(&query);
+        for day in [q.from, q.to].into_iter().flatten() {
+            query_builder = query_builder.bind(day.date_naive().to_string());
+        }
+        let rows = query_builder.fetch_all(&self.read_db).await?;
+
+        let mut series: Vec<TrendSeries> = Vec::new();
+        for row in rows {
+            let key: Option<String> = row.try_get("grp")?;
+            let point = TrendPoint {
+                week: row.try_get("week")?,
+                count: row.try_get("n")?,
+                avg: row.try_get("avg_v")?,
+                min: row.try_get("min_v")?,
+                max: row.try_get("max_v")?,
+            };
+            match series.last_mut() {
+                Some(last) if last.key == key => last.points.push(point),
+                _ => series.push(TrendSeries {
+                    key,
+                    label: row.try_get("label")?,
+                    points: vec![point],
+                }),
+            }
+        }
+
+        Ok(TrendsResponse {
+            metric: q.metric,
+            group_by: q.group_by,
+            series,
+        })
+    }
+
+    /// Completed-session totals and averages per day, bean origin or
+    /// profile, from the session summaries.
+    pub async fn session_summary(&self, q: &SummaryQuery) -> Result<SummaryResponse> {
+        let (key_expr, label_expr) = match q.group_by {
+            SummaryGroupBy::Day => ("ss.day", "ss.day"),
+            SummaryGroupBy::Bean => ("NULLIF(ss.bean_origin, '')", "NULLIF(ss.bean_origin, '')"),
+            SummaryGroupBy::Profile => ("NULLIF(ss.profile_id, '')", "p.name"),
+        };
+        let mut query = format!(
+            r#"
+            SELECT {key_expr} AS grp, MAX({label_expr}) AS label,
+                SUM(ss.sessions) AS sessions, SUM(ss.roast_seconds) AS roast_seconds,
+                SUM(ss.green_weight) AS green_weight, SUM(ss.roasted_weight) AS roasted_weight,
+                SUM(ss.development_time_ratio_sum) / SUM(ss.development_time_ratio_n) AS avg_dev_ratio,
+                SUM(ss.weight_loss_pct_sum) / SUM(ss.weight_loss_pct_n) AS avg_loss_pct,
+                SUM(ss.first_crack_time_sum) / SUM(ss.first_crack_time_n) AS avg_fc_time
+            FROM session_summaries ss
+            LEFT JOIN roast_profiles p ON p.id = ss.profile_id
+            WHERE 1 = 1
+            "#
+        );
+        push_day_range(&mut query, q.from, q.to);
+        query.push_str(" GROUP BY grp ORDER BY grp");
+
+        let mut query_builder = sqlx::query(&query);
+        for day in [q.from, q.to].into_iter().flatten() {
+            query_builder = query_builder.bind(day.date_naive().to_string());
+        }
+        let rows = query_builder
+            .fetch_all(&self.read_db)
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok(SummaryRow {
+                    key: row.try_get("grp")?,
+                    label: row.try_get("label")?,
+                    sessions: row.try_get("sessions")?,
+                    roast_seconds: row.try_get("roast_seconds")?,
+                    green_weight: row.try_get("green_weight")?,
+                    roasted_weight: row.try_get("roasted_weight")?,
+                    avg_dev_ratio: row.try_get("avg_dev_ratio")?,
+                    avg_loss_pct: row.try_get("avg_loss_pct")?,
+                    avg_fc_time: row.try_get("avg_fc_time")?,
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        Ok(SummaryResponse {
+            group_by: q.group_by,
+            rows,
+        })
+    }
+
     pub async fn get_session(&self, id: &str) -> Result<Option<RoastSession>> {
         let session =
             sqlx::query_as::<_, RoastSession>("SELECT * FROM roast_sessions WHERE id = ?")
@@ -111,12 +360,31 @@ impl RoastSessionService {
         };
 
         let cupping = self.get_cupping(id).await?;
+        let defects = self.list_defects(id).await?;
+
+        let curve = profile.as_ref().and_then(|p| self.profile_curve(p));
+        let scoreboard = match (&profile, curve) {
+            (Some(p), Some(curve)) => {
+                let events = self.get_roast_events(id).await?;
+                let finished = session.status == SessionStatus::Completed;
+                let window = self.scoreboard_ror_window(&session.device_id).await?;
+                let offset = self.profile_charge_offset().await?;
+                compute_profile_scoreboard(p, &curve, &telemetry, &events, finished, window, offset)
+            }
+            _ => None,
+        };
+        let rates = self.cost_rates().await?;
+        let cost = self.session_cost(&session, &telemetry, &rates).await?;
 
         Ok(Some(SessionWithTelemetry {
             session,
             telemetry,
             profile,
             cupping,
+            scoreboard,
+            defects,
+            cost,
+            smoothing: None,
         }))
     }
 
@@ -127,6 +395,7 @@ impl RoastSessionService {
     ) -> Result<Option<RoastSession>> {
         // Check if there are any fields to update
         if req.name.is_none()
+            && req.roaster.is_none()
             && req.roasted_weight.is_none()
             && req.notes.is_none()
             && req.first_crack_time.is_none()
@@ -143,6 +412,9 @@ impl RoastSessionService {
         if req.name.is_some() {
             query.push_str(", name = ?");
         }
+        if req.roaster.is_some() {
+            query.push_str(", roaster = ?");
+        }
         if req.roasted_weight.is_some() {
             query.push_str(", roasted_weight = ?");
         }
@@ -164,6 +436,9 @@ impl RoastSessionService {
         if let Some(ref name) = req.name {
             query_builder = query_builder.bind(name);
         }
+        if let Some(ref roaster) = req.roaster {
+            query_builder = query_builder.bind(roaster);
+        }
         if let Some(roasted_weight) = req.roasted_weight {
             query_builder = query_builder.bind(roasted_weight);
         }
@@ -180,7 +455,7 @@ impl RoastSessionService {
         query_builder = query_builder.bind(id);
 
         let session = query_builder.fetch_optional(&self.db).await?;
-        Ok(session)
+        self.journaled(session).await
     }
 
     pub async fn start_session(&self, id: &str) -> Result<Option<RoastSession>> {
@@ -200,6 +475,10 @@ impl RoastSessionService {
         .fetch_optional(&self.db)
         .await?;
 
+        let session = self.journaled(session).await?;
+        if let Some(s) = &session {
+            self.publish(SessionActivity::Started(s.clone()));
+        }
         Ok(session)
     }
 
@@ -219,7 +498,7 @@ impl RoastSessionService {
         .fetch_optional(&self.db)
         .await?;
 
-        Ok(session)
+        self.journaled(session).await
     }
 
     pub async fn resume_session(&self, id: &str) -> Result<Option<RoastSession>> {
@@ -238,17 +517,87 @@ impl RoastSessionService {
         .fetch_optional(&self.db)
         .await?;
 
-        Ok(session)
+        self.journaled(session).await
     }
 
     pub async fn complete_session(&self, id: &str) -> Result<Option<RoastSession>> {
-        let now = Utc::now();
-
         // Fetch the session first to get green_weight / roasted_weight
         let existing = match self.get_session(id).await? {
             Some(s) => s,
             None => return Ok(None),
         };
+        let stats = self.completion_stats(&existing).await?;
+
+        let now = Utc::now();
+        let sql = format!(
+            r#"
+            UPDATE roast_sessions
+            SET status = ?, end_time = ?, updated_at = ?, {COMPLETION_STATS_SET}
+            WHERE id = ? AND status IN (?, ?)
+            RETURNING *
+            "#
+        );
+        let session = stats
+            .bind(
+                sqlx::query_as::<_, RoastSession>(&sql)
+                    .bind(SessionStatus::Completed.to_string())
+                    .bind(now)
+                    .bind(now),
+            )
+            .bind(id)
+            .bind(SessionStatus::Active.to_string())
+            .bind(SessionStatus::Paused.to_string())
+            .fetch_optional(&self.db)
+            .await?;
+        let session = self.journaled(session).await?;
+        if let Some(s) = &session {
+            self.publish(SessionActivity::Completed(s.clone()));
+        }
+        Ok(session)
+    }
+
+    /// Re-run completion statistics (phase RoR averages, AUC, DTR, profile
+    /// scoreboard) over stored telemetry. Only completed sessions carry
+    /// these; returns `None` for a missing or unfinished session.
+    pub async fn recompute_session_stats(&self, id: &str) -> Result<Option<RoastSession>> {
+        let existing = match self.get_session(id).await? {
+            Some(s) if s.status == SessionStatus::Completed => s,
+            _ => return Ok(None),
+        };
+        let stats = self.completion_stats(&existing).await?;
+
+        // Status and end time stay as they are
+        let sql = format!(
+            r#"
+            UPDATE roast_sessions
+            SET updated_at = ?, {COMPLETION_STATS_SET}
+            WHERE id = ? AND status = ?
+            RETURNING *
+            "#
+        );
+        let session = stats
+            .bind(sqlx::query_as::<_, RoastSession>(&sql).bind(Utc::now()))
+            .bind(id)
+            .bind(SessionStatus::Completed.to_string())
+            .fetch_optional(&self.db)
+            .await?;
+        self.journaled(session).await
+    }
+
+    /// Ids of all completed sessions, oldest first.
+    pub async fn completed_session_ids(&self) -> Result<Vec<String>> {
+        let ids = sqlx::query_scalar::<_, String>(
+            "SELECT id FROM roast_sessions WHERE status = ? ORDER BY created_at",
+        )
+        .bind(SessionStatus::Completed.to_string())
+        .fetch_all(&self.db)
+        .await?;
+        Ok(ids)
+    }
+
+    /// Statistics stored on a session when it completes.
+    async fn completion_stats(&self, existing: &RoastSession) -> Result<CompletionStats> {
+        let id = existing.id.as_str();
 
         // Calculate total time, max temperature, and max RoR from telemetry
         let stats = sqlx::query(
@@ -336,43 +685,78 @@ impl RoastSessionService {
         // Compute AUC (Area Under the Curve) using trapezoidal rule
         let auc_value = self.compute_auc(id, &events).await?;
 
-        let session = sqlx::query_as::<_, RoastSession>(
-            r#"
-            UPDATE roast_sessions
-            SET status = ?, end_time = ?, updated_at = ?,
-                total_time_seconds = ?, max_temp = ?, max_ror = ?,
-                first_crack_time = COALESCE(?, first_crack_time),
-                development_time_ratio = COALESCE(?, development_time_ratio),
-                weight_loss_pct = ?,
-                avg_ror_drying = ?, avg_ror_maillard = ?, avg_ror_development = ?,
-                drying_end_time = ?, drying_end_temp = ?,
-                auc_value = ?
-            WHERE id = ? AND status IN (?, ?)
-            RETURNING *
-            "#,
-        )
-        .bind(SessionStatus::Completed.to_string())
-        .bind(now)
-        .bind(now)
-        .bind(total_time_seconds)
-        .bind(max_temp)
-        .bind(max_ror)
-        .bind(first_crack_time)
-        .bind(development_time_ratio)
-        .bind(weight_loss_pct)
-        .bind(avg_ror_drying)
-        .bind(avg_ror_maillard)
-        .bind(avg_ror_development)
-        .bind(drying_end_time)
-        .bind(drying_end_temp)
-        .bind(auc_value)
-        .bind(id)
-        .bind(SessionStatus::Active.to_string())
-        .bind(SessionStatus::Paused.to_string())
-        .fetch_optional(&self.db)
-        .await?;
+        // Final profile-following scoreboard for profile-linked roasts
+        let scoreboard = self.profile_scoreboard(existing, &events, true).await?;
+
+        Ok(CompletionStats {
+            total_time_seconds,
+            max_temp,
+            max_ror,
+            first_crack_time,
+            development_time_ratio,
+            weight_loss_pct,
+            avg_ror_drying,
+            avg_ror_maillard,
+            avg_ror_development,
+            drying_end_time,
+            drying_end_temp,
+            auc_value,
+            scoreboard,
+        })
+    }
 
-        Ok(session)
+    /// Scoreboard against the session's linked profile, if it has one.
+    async fn profile_scoreboard(
+        &self,
+        session: &RoastSession,
+        events: &[RoastEvent],
+        finished: bool,
+    ) -> Result<Option<ProfileScoreboard>> {
+        let Some(profile_id) = &session.profile_id else {
+            return Ok(None);
+        };
+        let Some(profile) = self.get_profile_with_points(profile_id).await? else {
+            return Ok(None);
+        };
+        let Some(curve) = self.profile_curve(&profile) else {
+            return Ok(None);
+        };
+        let telemetry = self.get_session_telemetry(&session.id).await?;
+        let window = self.scoreboard_ror_window(&session.device_id).await?;
+        let offset = self.profile_charge_offset().await?;
+        Ok(compute_profile_scoreboard(
+            &profile, &curve, &telemetry, events, finished, window, offset,
+        ))
+    }
+
+    /// Seconds after the charge event profiles start at, from the
+    /// `profile_charge_offset_secs` setting (default 0). Negative values
+    /// start the profile before the charge is marked.
+    pub async fn profile_charge_offset(&self) -> Result<f32> {
+        Ok(self
+            .get_setting("profile_charge_offset_secs")
+            .await?
+            .and_then(|v| v.trim().parse::<f32>().ok())
+            .filter(|v| v.is_finite())
+            .unwrap_or(0.0))
+    }
+
+    async fn scoreboard_ror_window(&self, device_id: &str) -> Result<f32> {
+        Ok(self
+            .get_ror_settings(device_id)
+            .await?
+            .map_or(SCOREBOARD_ROR_WINDOW_SECS, |s| s.window_secs))
+    }
+
+    /// Live scoreboard for a session. `None` if it has no profile or no
+    /// telemetry yet.
+    pub async fn session_scoreboard(
+        &self,
+        session: &RoastSession,
+    ) -> Result<Option<ProfileScoreboard>> {
+        let events = self.get_roast_events(&session.id).await?;
+        let finished = session.status == SessionStatus::Completed;
+        self.profile_scoreboard(session, &events, finished).await
     }
 
     /// Compute average rate_of_rise within a time range from session telemetry.
@@ -470,13 +854,63 @@ impl RoastSessionService {
         Ok(Some((auc_seconds / 60.0) as f32))
     }
 
+    /// Mark a completed session as reviewed. Returns `None` if the session does
+    /// not exist, is not completed, or has already been signed off.
+    pub async fn sign_off_session(
+        &self,
+        id: &str,
+        signed_off_by: &str,
+    ) -> Result<Option<RoastSession>> {
+        let now = Utc::now();
+        let session = sqlx::query_as::<_, RoastSession>(
+            r#"
+            UPDATE roast_sessions
+            SET signed_off_by = ?, signed_off_at = ?, updated_at = ?
+            WHERE id = ? AND status = ? AND signed_off_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(signed_off_by)
+        .bind(now)
+        .bind(now)
+        .bind(id)
+        .bind(SessionStatus::Completed.to_string())
+        .fetch_optional(&self.db)
+        .await?;
+
+        self.journaled(session).await
+    }
+
+    /// Clear a session's sign-off so it can be edited again.
+    pub async fn reopen_session(&self, id: &str) -> Result<Option<RoastSession>> {
+        let session = sqlx::query_as::<_, RoastSession>(
+            r#"
+            UPDATE roast_sessions
+            SET signed_off_by = NULL, signed_off_at = NULL, updated_at = ?
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        self.journaled(session).await
+    }
+
     pub async fn delete_session(&self, id: &str) -> Result<bool> {
         let result = sqlx::query("DELETE FROM roast_sessions WHERE id = ?")
             .bind(id)
             .execute(&self.db)
             .await?;
 
-        Ok(result.rows_affected() > 0)
+        let deleted = result.rows_affected() > 0;
+        if deleted {
+            self.journal(SyncEntity::Session, id, SyncOp::Delete)
+                .await?;
+        }
+        Ok(deleted)
     }
 
     // Telemetry Management
@@ -523,7 +957,7 @@ impl RoastSessionService {
             "SELECT * FROM session_telemetry WHERE session_id = ? ORDER BY elapsed_seconds",
         )
         .bind(session_id)
-        .fetch_all(&self.db)
+        .fetch_all(&self.read_db)
         .await?;
 
         Ok(telemetry)
@@ -540,8 +974,8 @@ impl RoastSessionService {
             INSERT INTO roast_profiles (
                 id, name, description, created_at, updated_at, is_public,
                 target_total_time, target_first_crack, target_end_temp,
-                preheat_temp, charge_temp
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                preheat_temp, charge_temp, batch_size_g, heater_cap
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             RETURNING *
             "#,
         )
@@ -556,6 +990,8 @@ impl RoastSessionService {
         .bind(req.target_end_temp)
         .bind(req.preheat_temp)
         .bind(req.charge_temp)
+        .bind(req.batch_size_g)
+        .bind(req.heater_cap)
         .fetch_one(&self.db)
         .await?;
 
@@ -566,8 +1002,9 @@ impl RoastSessionService {
             let point = sqlx::query_as::<_, ProfilePoint>(
                 r#"
                 INSERT INTO profile_points (
-                    id, profile_id, time_seconds, target_temp, fan_speed, notes, created_at, target_env_temp
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                    id, profile_id, time_seconds, target_temp, fan_speed, notes, created_at,
+                    target_env_temp, target_airflow
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
                 RETURNING *
                 "#,
             )
@@ -579,6 +1016,7 @@ impl RoastSessionService {
             .bind(&point_req.notes)
             .bind(now)
             .bind(point_req.target_env_temp)
+            .bind(point_req.target_airflow)
             .fetch_one(&self.db)
             .await?;
 
@@ -628,10 +1066,16 @@ impl RoastSessionService {
             .bind(id)
             .execute(&self.db)
             .await?;
+        self.curves.invalidate(id);
 
         Ok(result.rows_affected() > 0)
     }
 
+    /// The interpolated target curve of a profile, `None` without points.
+    pub fn profile_curve(&self, profile: &ProfileWithPoints) -> Option<Arc<ProfileCurve>> {
+        self.curves.get(profile, DEFAULT_STEP_SECS)
+    }
+
     pub async fn update_profile(
         &self,
         id: &str,
@@ -646,7 +1090,7 @@ impl RoastSessionService {
             UPDATE roast_profiles SET
                 name = ?, description = ?, updated_at = ?,
                 target_total_time = ?, target_first_crack = ?, target_end_temp = ?,
-                preheat_temp = ?, charge_temp = ?
+                preheat_temp = ?, charge_temp = ?, batch_size_g = ?, heater_cap = ?
             WHERE id = ?
             "#,
         )
@@ -658,6 +1102,8 @@ impl RoastSessionService {
         .bind(req.target_end_temp)
         .bind(req.preheat_temp)
         .bind(req.charge_temp)
+        .bind(req.batch_size_g)
+        .bind(req.heater_cap)
         .bind(id)
         .execute(&mut *tx)
         .await?;
@@ -677,8 +1123,9 @@ impl RoastSessionService {
             sqlx::query(
                 r#"
                 INSERT INTO profile_points (
-                    id, profile_id, time_seconds, target_temp, fan_speed, notes, created_at, target_env_temp
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                    id, profile_id, time_seconds, target_temp, fan_speed, notes, created_at,
+                    target_env_temp, target_airflow
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
                 "#,
             )
             .bind(&point_id)
@@ -689,21 +1136,31 @@ impl RoastSessionService {
             .bind(&point_req.notes)
             .bind(now)
             .bind(point_req.target_env_temp)
+            .bind(point_req.target_airflow)
             .execute(&mut *tx)
             .await?;
         }
 
         tx.commit().await?;
+        self.curves.invalidate(id);
 
         // Fetch and return updated profile with points
         self.get_profile_with_points(id).await
     }
 
+    /// Import an Artisan `.alog` as a profile. Unusable curve rows and
+    /// events that match no curve point reject the import unless `partial`,
+    /// in which case the rest is imported. Either way the report says what
+    /// was left out.
     pub async fn import_artisan_profile(
         &self,
         req: ImportArtisanProfileRequest,
-    ) -> Result<ProfileWithPoints> {
+        partial: bool,
+    ) -> Result<ImportOutcome> {
         let parsed = parse_artisan_alog(&req.alog_content)?;
+        if parsed.points.is_empty() || (!partial && !parsed.report.is_clean()) {
+            return Ok(ImportOutcome::Rejected(parsed.report));
+        }
 
         // Create profile from parsed data
         let profile_name = req.name.unwrap_or_else(|| {
@@ -715,20 +1172,18 @@ impl RoastSessionService {
         });
 
         // Convert parsed data to profile points
-        let mut points = Vec::new();
-        for (i, point) in parsed.points.iter().enumerate() {
-            points.push(CreateProfilePointRequest {
+        let points = parsed
+            .points
+            .iter()
+            .map(|point| CreateProfilePointRequest {
                 time_seconds: point.time as i32,
                 target_temp: point.bean_temp,
                 fan_speed: None, // Artisan doesn't provide fan speed in the curve
-                notes: if i < parsed.events.len() {
-                    Some(parsed.events[i].name.clone())
-                } else {
-                    None
-                },
+                notes: point.notes.clone(),
                 target_env_temp: None,
-            });
-        }
+                target_airflow: None,
+            })
+            .collect();
 
         let create_req = CreateProfileRequest {
             name: profile_name,
@@ -749,718 +1204,951 @@ impl RoastSessionService {
                 .iter()
                 .find(|e| e.event_type == "CHARGE")
                 .map(|e| e.bean_temp),
+            batch_size_g: None,
+            heater_cap: None,
             points,
         };
 
-        self.create_profile(create_req).await
+        let profile = self.create_profile(create_req).await?;
+        Ok(ImportOutcome::Imported(Box::new(ImportedProfile {
+            profile,
+            import_report: parsed.report,
+        })))
     }
 
-    // Utility functions
-    #[allow(dead_code)] // Used by MQTT consumer (DEV-007)
-    pub async fn get_active_session(&self, device_id: &str) -> Result<Option<RoastSession>> {
-        let session = sqlx::query_as::<_, RoastSession>(
+    /// Save `source` with a batch scaling suggestion applied as the next
+    /// version in its profile family.
+    pub async fn create_scaled_profile_version(
+        &self,
+        source: &ProfileWithPoints,
+        suggestion: &BatchScalingSuggestion,
+        name: &str,
+    ) -> Result<ProfileWithPoints> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let root_id = source
+            .profile
+            .parent_profile_id
+            .clone()
+            .unwrap_or_else(|| source.profile.id.clone());
+        let mut tx = self.db.begin().await?;
+
+        let (latest_version,): (i32,) = sqlx::query_as(
+            "SELECT COALESCE(MAX(version), 1) FROM roast_profiles WHERE id = ?1 OR parent_profile_id = ?1",
+        )
+        .bind(&root_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let p = &source.profile;
+        sqlx::query(
             r#"
-            SELECT * FROM roast_sessions 
-            WHERE device_id = ? AND status IN (?, ?)
-            ORDER BY start_time DESC
-            LIMIT 1
+            INSERT INTO roast_profiles (
+                id, name, description, created_by, created_at, updated_at, is_public,
+                target_total_time, target_first_crack, target_end_temp,
+                preheat_temp, charge_temp, batch_size_g, heater_cap,
+                parent_profile_id, version
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
-        .bind(device_id)
-        .bind(SessionStatus::Active.to_string())
-        .bind(SessionStatus::Paused.to_string())
-        .fetch_optional(&self.db)
+        .bind(&id)
+        .bind(name)
+        .bind(&p.description)
+        .bind(&p.created_by)
+        .bind(now)
+        .bind(now)
+        .bind(p.is_public)
+        .bind(p.target_total_time)
+        .bind(p.target_first_crack)
+        .bind(p.target_end_temp)
+        .bind(p.preheat_temp)
+        .bind(suggestion.charge_temp)
+        .bind(suggestion.batch_size_g)
+        .bind(suggestion.heater_cap)
+        .bind(&root_id)
+        .bind(latest_version + 1)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(session)
-    }
+        for point in &source.points {
+            sqlx::query(
+                r#"
+                INSERT INTO profile_points (
+                    id, profile_id, time_seconds, target_temp, fan_speed, notes, created_at,
+                    target_env_temp, target_airflow
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&id)
+            .bind(point.time_seconds)
+            .bind(point.target_temp)
+            .bind(point.fan_speed)
+            .bind(&point.notes)
+            .bind(now)
+            .bind(point.target_env_temp)
+            .bind(point.target_airflow)
+            .execute(&mut *tx)
+            .await?;
+        }
 
-    // Roast Events CRUD operations
-    pub async fn create_roast_event(
-        &self,
-        session_id: &str,
-        req: CreateRoastEventRequest,
-    ) -> Result<RoastEvent> {
-        let event_id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+        tx.commit().await?;
 
-        let event = sqlx::query_as::<_, RoastEvent>(
+        self.get_profile_with_points(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("scaled profile {} vanished after insert", id))
+    }
+
+    /// All versions in the family of profile `id` (the original first).
+    pub async fn list_profile_versions(&self, id: &str) -> Result<Vec<RoastProfile>> {
+        let profiles = sqlx::query_as::<_, RoastProfile>(
             r#"
-            INSERT INTO roast_events (id, session_id, event_type, elapsed_seconds, temperature, notes, created_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            RETURNING *
-            "#
+            SELECT * FROM roast_profiles
+            WHERE id = (SELECT COALESCE(parent_profile_id, id) FROM roast_profiles WHERE id = ?1)
+               OR parent_profile_id = (SELECT COALESCE(parent_profile_id, id) FROM roast_profiles WHERE id = ?1)
+            ORDER BY version, created_at
+            "#,
         )
-        .bind(&event_id)
-        .bind(session_id)
-        .bind(req.event_type.to_string())
-        .bind(req.elapsed_seconds)
-        .bind(req.temperature)
-        .bind(&req.notes)
-        .bind(now)
-        .fetch_one(&self.db)
+        .bind(id)
+        .fetch_all(&self.db)
         .await?;
 
-        Ok(event)
+        Ok(profiles)
     }
 
-    pub async fn get_roast_events(&self, session_id: &str) -> Result<Vec<RoastEvent>> {
-        let events = sqlx::query_as::<_, RoastEvent>(
-            "SELECT * FROM roast_events WHERE session_id = ?1 ORDER BY elapsed_seconds ASC",
+    pub async fn get_batch_scaling_rule(
+        &self,
+        device_id: &str,
+    ) -> Result<Option<BatchScalingRule>> {
+        let rule = sqlx::query_as::<_, BatchScalingRule>(
+            "SELECT * FROM batch_scaling_rules WHERE device_id = ?",
         )
-        .bind(session_id)
-        .fetch_all(&self.db)
+        .bind(device_id)
+        .fetch_optional(&self.db)
         .await?;
 
-        Ok(events)
+        Ok(rule)
     }
 
-    pub async fn update_roast_event(
+    pub async fn upsert_batch_scaling_rule(
         &self,
-        event_id: &str,
-        req: UpdateRoastEventRequest,
-    ) -> Result<RoastEvent> {
-        let mut updates = Vec::new();
-
-        if req.elapsed_seconds.is_some() {
-            updates.push("elapsed_seconds = ?".to_string());
-        }
-
-        if req.temperature.is_some() {
-            updates.push("temperature = ?".to_string());
-        }
+        device_id: &str,
+        req: UpsertBatchScalingRuleRequest,
+    ) -> Result<BatchScalingRule> {
+        let rule = sqlx::query_as::<_, BatchScalingRule>(
+            r#"
+            INSERT INTO batch_scaling_rules (
+                device_id, charge_temp_per_100g, heater_cap_per_100g,
+                min_heater_cap, max_heater_cap, max_charge_temp, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                charge_temp_per_100g = excluded.charge_temp_per_100g,
+                heater_cap_per_100g = excluded.heater_cap_per_100g,
+                min_heater_cap = excluded.min_heater_cap,
+                max_heater_cap = excluded.max_heater_cap,
+                max_charge_temp = excluded.max_charge_temp,
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(req.charge_temp_per_100g)
+        .bind(req.heater_cap_per_100g)
+        .bind(req.min_heater_cap.unwrap_or(0))
+        .bind(req.max_heater_cap.unwrap_or(100))
+        .bind(req.max_charge_temp)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
 
-        if req.notes.is_some() {
-            updates.push("notes = ?".to_string());
-        }
+        Ok(rule)
+    }
 
-        if updates.is_empty() {
-            return Err(anyhow::anyhow!("No fields to update"));
-        }
+    pub async fn get_batch_preheat(&self, device_id: &str) -> Result<Option<BatchPreheatSettings>> {
+        let settings = sqlx::query_as::<_, BatchPreheatSettings>(
+            "SELECT * FROM batch_preheat_settings WHERE device_id = ?",
+        )
+        .bind(device_id)
+        .fetch_optional(&self.db)
+        .await?;
 
-        updates.push("updated_at = CURRENT_TIMESTAMP".to_string());
+        Ok(settings)
+    }
 
-        let query = format!(
-            "UPDATE roast_events SET {} WHERE id = ? RETURNING *",
-            updates.join(", ")
-        );
+    pub async fn upsert_batch_preheat(
+        &self,
+        device_id: &str,
+        req: UpsertBatchPreheatRequest,
+    ) -> Result<BatchPreheatSettings> {
+        let settings = sqlx::query_as::<_, BatchPreheatSettings>(
+            r#"
+            INSERT INTO batch_preheat_settings (
+                device_id, enabled, timeout_secs, max_temp, ready_band, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                enabled = excluded.enabled,
+                timeout_secs = excluded.timeout_secs,
+                max_temp = excluded.max_temp,
+                ready_band = excluded.ready_band,
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(req.enabled)
+        .bind(req.timeout_secs.unwrap_or(1200))
+        .bind(req.max_temp.unwrap_or(230.0))
+        .bind(req.ready_band.unwrap_or(3.0))
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
 
-        let mut query_builder = sqlx::query_as::<_, RoastEvent>(&query);
+        Ok(settings)
+    }
 
-        // Bind parameters in the same order as they appear in the updates
-        if let Some(elapsed_seconds) = req.elapsed_seconds {
-            query_builder = query_builder.bind(elapsed_seconds);
-        }
-        if let Some(temperature) = req.temperature {
-            query_builder = query_builder.bind(temperature);
-        }
-        if let Some(notes) = req.notes {
-            query_builder = query_builder.bind(notes);
-        }
+    pub async fn get_preroast_checklist(
+        &self,
+        device_id: &str,
+    ) -> Result<Option<PreroastChecklist>> {
+        let checklist = sqlx::query_as::<_, PreroastChecklist>(
+            "SELECT * FROM preroast_checklists WHERE device_id = ?",
+        )
+        .bind(device_id)
+        .fetch_optional(&self.db)
+        .await?;
 
-        // Bind the event_id for the WHERE clause
-        query_builder = query_builder.bind(event_id);
+        Ok(checklist)
+    }
 
-        let event = query_builder.fetch_one(&self.db).await?;
+    pub async fn upsert_preroast_checklist(
+        &self,
+        device_id: &str,
+        items: Vec<String>,
+    ) -> Result<PreroastChecklist> {
+        let checklist = sqlx::query_as::<_, PreroastChecklist>(
+            r#"
+            INSERT INTO preroast_checklists (device_id, items, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                items = excluded.items,
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(sqlx::types::Json(items))
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
 
-        Ok(event)
+        Ok(checklist)
     }
 
-    pub async fn delete_roast_event(&self, event_id: &str) -> Result<()> {
-        let rows_affected = sqlx::query("DELETE FROM roast_events WHERE id = ?1")
-            .bind(event_id)
+    pub async fn delete_preroast_checklist(&self, device_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM preroast_checklists WHERE device_id = ?")
+            .bind(device_id)
             .execute(&self.db)
-            .await?
-            .rows_affected();
+            .await?;
 
-        if rows_affected == 0 {
-            return Err(anyhow::anyhow!("Roast event not found"));
-        }
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark a checklist item done for a session; checking it again updates
+    /// who and when.
+    pub async fn check_checklist_item(
+        &self,
+        session_id: &str,
+        item: &str,
+        checked_by: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO session_checklist_checks (session_id, item, checked_by, checked_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(session_id, item) DO UPDATE SET
+                checked_by = excluded.checked_by,
+                checked_at = excluded.checked_at
+            "#,
+        )
+        .bind(session_id)
+        .bind(item)
+        .bind(checked_by)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await?;
 
         Ok(())
     }
 
-    // ---- Cupping Notes CRUD (AP-012) ----
+    /// The checklist recorded when the session started or, for a planned
+    /// session, its roaster's checklist. `None` when there is neither.
+    pub async fn session_checklist(
+        &self,
+        session: &RoastSession,
+    ) -> Result<Option<SessionChecklist>> {
+        let recorded = sqlx::query_as::<
+            _,
+            (
+                sqlx::types::Json<Vec<String>>,
+                Option<String>,
+                Option<String>,
+                DateTime<Utc>,
+            ),
+        >(
+            "SELECT items, overridden_by, override_reason, recorded_at FROM session_checklists WHERE session_id = ?",
+        )
+        .bind(&session.id)
+        .fetch_optional(&self.db)
+        .await?;
+        let (items, overridden_by, override_reason, recorded_at) = match recorded {
+            Some((items, by, reason, at)) => (items.0, by, reason, Some(at)),
+            None if session.status == SessionStatus::Planning => {
+                match self.get_preroast_checklist(&session.device_id).await? {
+                    Some(checklist) => (checklist.items.0, None, None, None),
+                    None => return Ok(None),
+                }
+            }
+            None => return Ok(None),
+        };
 
-    pub async fn create_cupping(
+        let mut checks: HashMap<String, (String, DateTime<Utc>)> =
+            sqlx::query_as::<_, (String, String, DateTime<Utc>)>(
+                "SELECT item, checked_by, checked_at FROM session_checklist_checks WHERE session_id = ?",
+            )
+            .bind(&session.id)
+            .fetch_all(&self.db)
+            .await?
+            .into_iter()
+            .map(|(item, by, at)| (item, (by, at)))
+            .collect();
+        let items: Vec<ChecklistItemStatus> = items
+            .into_iter()
+            .map(|item| {
+                let check = checks.remove(&item);
+                ChecklistItemStatus {
+                    checked_by: check.as_ref().map(|(by, _)| by.clone()),
+                    checked_at: check.map(|(_, at)| at),
+                    item,
+                }
+            })
+            .collect();
+
+        Ok(Some(SessionChecklist {
+            session_id: session.id.clone(),
+            complete: items.iter().all(|i| i.checked_at.is_some()),
+            items,
+            overridden_by,
+            override_reason,
+            recorded_at,
+        }))
+    }
+
+    /// Keep the checklist a session started with, and who overrode it if it
+    /// wasn't complete.
+    pub async fn record_session_checklist(
         &self,
         session_id: &str,
-        req: CreateCuppingRequest,
-    ) -> Result<CuppingWithAttributes> {
-        // Delete any existing cupping for this session (upsert semantics)
-        // CASCADE will also delete associated cupping_attributes
-        sqlx::query("DELETE FROM cupping_scores WHERE session_id = ?")
-            .bind(session_id)
-            .execute(&self.db)
-            .await?;
-
-        let cupping_id = Uuid::new_v4().to_string();
-        let framework = req.scoring_framework.unwrap_or_else(|| "sca".to_string());
+        checklist: &SessionChecklist,
+        overridden_by: Option<&str>,
+        override_reason: Option<&str>,
+    ) -> Result<()> {
+        let items: Vec<&str> = checklist.items.iter().map(|i| i.item.as_str()).collect();
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO session_checklists (
+                session_id, items, overridden_by, override_reason, recorded_at
+            ) VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(session_id)
+        .bind(sqlx::types::Json(items))
+        .bind(overridden_by)
+        .bind(override_reason)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await?;
 
-        // Compute overall score as average of attribute scores
-        let overall: Option<f32> = if req.attributes.is_empty() {
-            None
-        } else {
-            let sum: f32 = req.attributes.iter().map(|a| a.score).sum();
-            Some(sum / req.attributes.len() as f32)
-        };
+        Ok(())
+    }
 
-        let cupping = sqlx::query_as::<_, CuppingScore>(
-            r#"INSERT INTO cupping_scores (id, session_id, scoring_framework, overall_score, notes)
-               VALUES (?, ?, ?, ?, ?)
-               RETURNING *"#,
+    pub async fn add_session_attachment(
+        &self,
+        session_id: &str,
+        kind: &str,
+        content_type: &str,
+        elapsed_seconds: f64,
+        data: &[u8],
+        created_by: Option<&str>,
+    ) -> Result<SessionAttachment> {
+        let attachment = sqlx::query_as::<_, SessionAttachment>(
+            r#"
+            INSERT INTO session_attachments (
+                id, session_id, kind, content_type, size_bytes, elapsed_seconds,
+                data, created_by, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id, session_id, kind, content_type, size_bytes, elapsed_seconds,
+                created_by, created_at
+            "#,
         )
-        .bind(&cupping_id)
+        .bind(Uuid::new_v4().to_string())
         .bind(session_id)
-        .bind(&framework)
-        .bind(overall)
-        .bind(&req.notes)
+        .bind(kind)
+        .bind(content_type)
+        .bind(data.len() as i64)
+        .bind(elapsed_seconds)
+        .bind(data)
+        .bind(created_by)
+        .bind(Utc::now())
         .fetch_one(&self.db)
         .await?;
 
-        let mut attributes = Vec::new();
-        for attr in &req.attributes {
-            let attr_id = Uuid::new_v4().to_string();
-            let row = sqlx::query_as::<_, CuppingAttribute>(
-                r#"INSERT INTO cupping_attributes (id, cupping_id, attribute_name, score)
-                   VALUES (?, ?, ?, ?)
-                   RETURNING *"#,
-            )
-            .bind(&attr_id)
-            .bind(&cupping_id)
-            .bind(&attr.name)
-            .bind(attr.score)
-            .fetch_one(&self.db)
-            .await?;
-            attributes.push(row);
-        }
-
-        Ok(CuppingWithAttributes {
-            cupping,
-            attributes,
-        })
+        Ok(attachment)
     }
 
-    pub async fn get_cupping(&self, session_id: &str) -> Result<Option<CuppingWithAttributes>> {
-        let cupping = sqlx::query_as::<_, CuppingScore>(
-            "SELECT * FROM cupping_scores WHERE session_id = ? ORDER BY created_at DESC LIMIT 1",
+    /// A session's attachments in the order they were taken.
+    pub async fn list_session_attachments(
+        &self,
+        session_id: &str,
+    ) -> Result<Vec<SessionAttachment>> {
+        let attachments = sqlx::query_as::<_, SessionAttachment>(
+            r#"
+            SELECT id, session_id, kind, content_type, size_bytes, elapsed_seconds,
+                created_by, created_at
+            FROM session_attachments
+            WHERE session_id = ?
+            ORDER BY elapsed_seconds, created_at
+            "#,
         )
         .bind(session_id)
-        .fetch_optional(&self.db)
+        .fetch_all(&self.db)
         .await?;
 
-        let cupping = match cupping {
-            Some(c) => c,
-            None => return Ok(None),
-        };
+        Ok(attachments)
+    }
 
-        let attributes = sqlx::query_as::<_, CuppingAttribute>(
-            "SELECT * FROM cupping_attributes WHERE cupping_id = ? ORDER BY attribute_name",
+    /// An attachment's content type and bytes.
+    pub async fn get_session_attachment_data(
+        &self,
+        session_id: &str,
+        attachment_id: &str,
+    ) -> Result<Option<(String, Vec<u8>)>> {
+        let data = sqlx::query_as::<_, (String, Vec<u8>)>(
+            "SELECT content_type, data FROM session_attachments WHERE session_id = ? AND id = ?",
         )
-        .bind(&cupping.id)
-        .fetch_all(&self.db)
+        .bind(session_id)
+        .bind(attachment_id)
+        .fetch_optional(&self.db)
         .await?;
 
-        Ok(Some(CuppingWithAttributes {
-            cupping,
-            attributes,
-        }))
+        Ok(data)
     }
 
-    pub async fn delete_cupping(&self, session_id: &str) -> Result<()> {
-        // CASCADE will delete cupping_attributes
-        sqlx::query("DELETE FROM cupping_scores WHERE session_id = ?")
+    pub async fn delete_session_attachment(
+        &self,
+        session_id: &str,
+        attachment_id: &str,
+    ) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM session_attachments WHERE session_id = ? AND id = ?")
             .bind(session_id)
+            .bind(attachment_id)
             .execute(&self.db)
             .await?;
-        Ok(())
+
+        Ok(result.rows_affected() > 0)
     }
 
-    // ---- Data Export (AP-014) ----
+    pub async fn get_ror_settings(&self, device_id: &str) -> Result<Option<RoasterRorSettings>> {
+        let settings = sqlx::query_as::<_, RoasterRorSettings>(
+            "SELECT * FROM roaster_ror_settings WHERE device_id = ?",
+        )
+        .bind(device_id)
+        .fetch_optional(&self.db)
+        .await?;
 
-    pub async fn export_csv(&self, id: &str) -> Result<Option<(String, String)>> {
-        let session = match self.get_session(id).await? {
-            Some(s) => s,
-            None => return Ok(None),
-        };
-        let telemetry = self.get_session_telemetry(id).await?;
-        let profile_name = if let Some(pid) = &session.profile_id {
-            self.get_profile_with_points(pid)
-                .await?
-                .map(|p| p.profile.name)
-                .unwrap_or_default()
-        } else {
-            String::new()
-        };
+        Ok(settings)
+    }
 
-        let mut csv = String::new();
-        csv.push_str(&format!("# Session: {}\n", session.name));
-        if let Some(ref st) = session.start_time {
-            csv.push_str(&format!("# Date: {}\n", st));
-        }
-        if let Some(ref origin) = session.bean_origin {
-            let variety = session.bean_variety.as_deref().unwrap_or("");
-            if variety.is_empty() {
-                csv.push_str(&format!("# Bean: {}\n", origin));
-            } else {
-                csv.push_str(&format!("# Bean: {} ({})\n", origin, variety));
-            }
-        }
-        if !profile_name.is_empty() {
-            csv.push_str(&format!("# Profile: {}\n", profile_name));
-        }
-        if let Some(gw) = session.green_weight {
-            csv.push_str(&format!("# Green Weight: {}g\n", gw));
-        }
-        if let Some(rw) = session.roasted_weight {
-            csv.push_str(&format!("# Roasted Weight: {}g\n", rw));
-        }
+    pub async fn upsert_ror_settings(
+        &self,
+        device_id: &str,
+        req: UpsertRorSettingsRequest,
+    ) -> Result<RoasterRorSettings> {
+        let settings = sqlx::query_as::<_, RoasterRorSettings>(
+            r#"
+            INSERT INTO roaster_ror_settings (device_id, window_secs, unit, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                window_secs = excluded.window_secs,
+                unit = excluded.unit,
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(req.window_secs)
+        .bind(req.unit)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
 
-        csv.push_str(
-            "elapsed_seconds,bean_temp,env_temp,rate_of_rise,heater_pwm,fan_pwm,setpoint\n",
-        );
-        for t in &telemetry {
-            csv.push_str(&format!(
-                "{},{},{},{},{},{},{}\n",
-                t.elapsed_seconds,
-                t.bean_temp.map(|v| v.to_string()).unwrap_or_default(),
-                t.env_temp.map(|v| v.to_string()).unwrap_or_default(),
-                t.rate_of_rise.map(|v| v.to_string()).unwrap_or_default(),
-                t.heater_pwm.map(|v| v.to_string()).unwrap_or_default(),
-                t.fan_pwm.map(|v| v.to_string()).unwrap_or_default(),
-                t.setpoint.map(|v| v.to_string()).unwrap_or_default(),
-            ));
-        }
+        Ok(settings)
+    }
 
-        let date_str = session
-            .start_time
-            .map(|dt| dt.format("%Y-%m-%d").to_string())
-            .unwrap_or_else(|| session.created_at.format("%Y-%m-%d").to_string());
-        let filename = format!("{}_{}.csv", session.name.replace(' ', "_"), date_str);
+    pub async fn delete_ror_settings(&self, device_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM roaster_ror_settings WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&self.db)
+            .await?;
 
-        Ok(Some((csv, filename)))
+        Ok(result.rows_affected() > 0)
     }
 
-    pub async fn export_artisan_json(
+    /// `config` with the roaster's RoR window and unit, when it has any.
+    pub async fn with_ror_settings(
         &self,
-        id: &str,
-    ) -> Result<Option<(serde_json::Value, String)>> {
-        let session = match self.get_session(id).await? {
-            Some(s) => s,
-            None => return Ok(None),
-        };
-        let telemetry = self.get_session_telemetry(id).await?;
-        let events = self.get_roast_events(id).await?;
+        device_id: &str,
+        config: SmoothingConfig,
+    ) -> Result<SmoothingConfig> {
+        Ok(match self.get_ror_settings(device_id).await? {
+            Some(settings) => settings.apply(config),
+            None => config,
+        })
+    }
 
-        let timex: Vec<f64> = telemetry.iter().map(|t| t.elapsed_seconds as f64).collect();
-        let temp1: Vec<f64> = telemetry
-            .iter()
-            .map(|t| t.env_temp.unwrap_or(-1.0) as f64)
-            .collect();
-        let temp2: Vec<f64> = telemetry
-            .iter()
-            .map(|t| t.bean_temp.unwrap_or(-1.0) as f64)
-            .collect();
+    // Utility functions
+    /// Active sessions with the elapsed seconds of their last telemetry
+    /// point (`None` before the first), for recovery after a restart.
+    pub async fn active_sessions_with_last_point(
+        &self,
+    ) -> Result<Vec<(RoastSession, Option<f64>)>> {
+        let sessions = sqlx::query_as::<_, RoastSession>(
+            "SELECT * FROM roast_sessions WHERE status = ? ORDER BY created_at",
+        )
+        .bind(SessionStatus::Active.to_string())
+        .fetch_all(&self.db)
+        .await?;
+        let mut result = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            let last: Option<f64> = sqlx::query_scalar(
+                "SELECT MAX(elapsed_seconds) FROM session_telemetry WHERE session_id = ?",
+            )
+            .bind(&session.id)
+            .fetch_one(&self.db)
+            .await?;
+            result.push((session, last));
+        }
+        Ok(result)
+    }
 
-        // Build timeindex: [CHARGE, DRY, FCs, FCe, SCs, SCe, DROP, COOL]
-        // CHARGE is handled separately (always index 0), so event_map starts at DRY
-        let event_map = [
-            "drying_end",         // DRY
-            "first_crack_start",  // FCs
-            "first_crack_end",    // FCe
-            "second_crack_start", // SCs
-            "second_crack_end",   // SCe
-            "drop_out",           // DROP
-        ];
+    /// End an active session as failed at `end_time`, e.g. when it was cut
+    /// off by a server outage. `None` if it is not active.
+    pub async fn fail_session(
+        &self,
+        id: &str,
+        end_time: DateTime<Utc>,
+        total_time_seconds: i32,
+    ) -> Result<Option<RoastSession>> {
+        let session = sqlx::query_as::<_, RoastSession>(
+            r#"
+            UPDATE roast_sessions
+            SET status = ?, end_time = ?, total_time_seconds = ?, updated_at = ?
+            WHERE id = ? AND status = ?
+            RETURNING *
+            "#,
+        )
+        .bind(SessionStatus::Failed.to_string())
+        .bind(end_time)
+        .bind(total_time_seconds)
+        .bind(Utc::now())
+        .bind(id)
+        .bind(SessionStatus::Active.to_string())
+        .fetch_optional(&self.db)
+        .await?;
 
-        // CHARGE is index 0 in telemetry (first reading)
-        let charge_idx: i64 = if !timex.is_empty() { 0 } else { -1 };
+        self.journaled(session).await
+    }
 
-        let mut timeindex: Vec<i64> = vec![charge_idx];
-        for event_type in &event_map {
-            let idx = events
-                .iter()
-                .find(|e| e.event_type.to_string() == *event_type)
-                .and_then(|e| {
-                    // Find nearest telemetry index
-                    timex
-                        .iter()
-                        .enumerate()
-                        .min_by_key(|(_, &t)| {
-                            ((t - e.elapsed_seconds as f64) * 1000.0).abs() as i64
-                        })
-                        .map(|(i, _)| i as i64)
-                })
-                .unwrap_or(-1);
-            timeindex.push(idx);
-        }
-        // COOL (index 7) — not tracked, use -1
-        timeindex.push(-1);
+    pub async fn get_active_session(&self, device_id: &str) -> Result<Option<RoastSession>> {
+        let session = sqlx::query_as::<_, RoastSession>(
+            r#"
+            SELECT * FROM roast_sessions 
+            WHERE device_id = ? AND status IN (?, ?)
+            ORDER BY start_time DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(device_id)
+        .bind(SessionStatus::Active.to_string())
+        .bind(SessionStatus::Paused.to_string())
+        .fetch_optional(&self.db)
+        .await?;
 
-        // Build specialevents
-        let specialevents: Vec<serde_json::Value> = events
-            .iter()
-            .map(|e| {
-                serde_json::json!({
-                    "type": e.event_type.to_string(),
-                    "time": e.elapsed_seconds,
-                    "temperature": e.temperature,
-                    "notes": e.notes,
-                })
-            })
-            .collect();
+        Ok(session)
+    }
 
-        let roastdate = session
-            .start_time
-            .map(|dt| dt.to_rfc3339())
-            .unwrap_or_else(|| session.created_at.to_rfc3339());
+    // ---- Manual roasting ----
 
-        let weight: Vec<serde_json::Value> = vec![
-            serde_json::json!(session.green_weight.unwrap_or(0.0)),
-            serde_json::json!(session.roasted_weight.unwrap_or(0.0)),
-            serde_json::json!("g"),
-        ];
+    /// Log a control command sent to `device_id`. During an active manual
+    /// session, heater and fan changes are also recorded as session events.
+    pub async fn record_control(
+        &self,
+        device_id: &str,
+        op: &str,
+        value: &str,
+        bean_temp: Option<f32>,
+    ) -> Result<Option<RoastEvent>> {
+        let session = self.get_active_session(device_id).await?;
+        sqlx::query(
+            "INSERT INTO control_audit (id, device_id, op, value, session_id, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(device_id)
+        .bind(op)
+        .bind(value)
+        .bind(session.as_ref().map(|s| &s.id))
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await?;
 
-        let alog = serde_json::json!({
-            "version": "2",
-            "title": session.name,
-            "roastdate": roastdate,
-            "beans": session.bean_origin.unwrap_or_default(),
-            "weight": weight,
-            "timex": timex,
-            "temp1": temp1,
-            "temp2": temp2,
-            "timeindex": timeindex,
-            "specialevents": specialevents,
-        });
+        let Some(session) = session
+            .filter(|s| s.session_type == SessionType::Manual && s.status == SessionStatus::Active)
+        else {
+            return Ok(None);
+        };
+        let event_type = match op {
+            "heater_pwm" => RoastEventType::HeaterChange,
+            "fan_pwm" => RoastEventType::FanChange,
+            _ => return Ok(None),
+        };
+        let Some(start) = session.start_time else {
+            return Ok(None);
+        };
+        let event = self
+            .create_roast_event(
+                &session.id,
+                CreateRoastEventRequest {
+                    event_type,
+                    elapsed_seconds: (Utc::now() - start).num_milliseconds() as f32 / 1000.0,
+                    temperature: bean_temp,
+                    notes: Some(value.to_string()),
+                },
+            )
+            .await?;
+        Ok(Some(event))
+    }
 
-        let date_str = session
-            .start_time
-            .map(|dt| dt.format("%Y-%m-%d").to_string())
-            .unwrap_or_else(|| session.created_at.format("%Y-%m-%d").to_string());
-        let filename = format!("{}_{}.alog", session.name.replace(' ', "_"), date_str);
+    /// Most recent control commands for a device, newest first.
+    pub async fn list_control_audit(
+        &self,
+        device_id: &str,
+        limit: i64,
+    ) -> Result<Vec<ControlAuditEntry>> {
+        let entries = sqlx::query_as::<_, ControlAuditEntry>(
+            "SELECT * FROM control_audit WHERE device_id = ? ORDER BY created_at DESC LIMIT ?",
+        )
+        .bind(device_id)
+        .bind(limit)
+        .fetch_all(&self.read_db)
+        .await?;
+        Ok(entries)
+    }
 
-        Ok(Some((alog, filename)))
+    /// Log a raw MQTT message published by an admin.
+    pub async fn record_mqtt_publish(
+        &self,
+        req: &MqttPublishRequest,
+        payload: &str,
+        subject: Option<&str>,
+        source: &str,
+    ) -> Result<MqttPublishAudit> {
+        let entry = MqttPublishAudit {
+            id: Uuid::new_v4().to_string(),
+            topic: req.topic.clone(),
+            payload: payload.to_string(),
+            qos: req.qos,
+            retain: req.retain,
+            subject: subject.map(str::to_string),
+            source: source.to_string(),
+            created_at: Utc::now(),
+        };
+        sqlx::query(
+            "INSERT INTO mqtt_publish_audit (id, topic, payload, qos, retain, subject, source, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&entry.id)
+        .bind(&entry.topic)
+        .bind(&entry.payload)
+        .bind(entry.qos)
+        .bind(entry.retain)
+        .bind(&entry.subject)
+        .bind(&entry.source)
+        .bind(entry.created_at)
+        .execute(&self.db)
+        .await?;
+        Ok(entry)
     }
-}
 
-// Artisan Profile Parser
-#[derive(Debug, Deserialize, Serialize)]
-struct ArtisanProfilePoint {
-    time: f64,
-    bean_temp: f32,
-    env_temp: f32,
-}
+    /// Most recent raw MQTT publishes, newest first.
+    pub async fn list_mqtt_publishes(&self, limit: i64) -> Result<Vec<MqttPublishAudit>> {
+        let entries = sqlx::query_as::<_, MqttPublishAudit>(
+            "SELECT * FROM mqtt_publish_audit ORDER BY created_at DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.read_db)
+        .await?;
+        Ok(entries)
+    }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct ArtisanRoastEvent {
-    name: String,
-    time: f64,
-    bean_temp: f32,
-    env_temp: f32,
-    event_type: String,
-}
+    pub async fn manual_status(&self, session: &RoastSession) -> Result<ManualRoastStatus> {
+        let events = self.get_roast_events(&session.id).await?;
+        Ok(manual_roast_status(session, &events, Utc::now()))
+    }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct ParsedArtisanProfile {
-    title: String,
-    roast_date: String,
-    total_time: f64,
-    points: Vec<ArtisanProfilePoint>,
-    events: Vec<ArtisanRoastEvent>,
-}
+    // Roast Events CRUD operations
+    pub async fn create_roast_event(
+        &self,
+        session_id: &str,
+        req: CreateRoastEventRequest,
+    ) -> Result<RoastEvent> {
+        self.insert_roast_event(&Uuid::new_v4().to_string(), session_id, req)
+            .await
+    }
 
-fn parse_artisan_alog(content: &str) -> Result<ParsedArtisanProfile> {
-    // First try to parse as JSON
-    let profile_data: serde_json::Value = if let Ok(json) = serde_json::from_str(content) {
-        json
-    } else {
-        // Try to convert Python literals to JSON with more comprehensive replacements
-        let json_content = content
-            // Basic Python to JSON conversions
-            .replace("True", "true")
-            .replace("False", "false")
-            .replace("None", "null")
-            // Handle Python tuples - convert to arrays
-            .replace("(", "[")
-            .replace(")", "]")
-            // Handle Python single quotes
-            .replace("'", "\"")
-            // Remove Python comments that might interfere
-            .lines()
-            .filter(|line| !line.trim_start().starts_with('#'))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        serde_json::from_str(&json_content)
-            .map_err(|e| anyhow!("Unable to parse Artisan profile file format: {}", e))?
-    };
-
-    // Extract time and temperature arrays
-    let timex = profile_data["timex"].as_array().unwrap_or(&vec![]).clone();
-    let temp1 = profile_data["temp1"].as_array().unwrap_or(&vec![]).clone(); // Environment temp
-    let temp2 = profile_data["temp2"].as_array().unwrap_or(&vec![]).clone(); // Bean temp
-    let default_computed = serde_json::Map::new();
-    let computed = profile_data["computed"]
-        .as_object()
-        .unwrap_or(&default_computed);
-
-    // Convert to profile points
-    let mut points = Vec::new();
-    for (i, time_val) in timex.iter().enumerate() {
-        if let Some(time) = time_val.as_f64() {
-            let bean_temp = temp2.get(i).and_then(|t| t.as_f64()).unwrap_or(0.0) as f32;
-            let env_temp = temp1.get(i).and_then(|t| t.as_f64()).unwrap_or(0.0) as f32;
-            points.push(ArtisanProfilePoint {
-                time,
-                bean_temp,
-                env_temp,
-            });
+    /// Insert several events for a session, all or none.
+    pub async fn create_roast_events(
+        &self,
+        session_id: &str,
+        reqs: Vec<CreateRoastEventRequest>,
+    ) -> Result<Vec<RoastEvent>> {
+        let now = Utc::now();
+        let mut tx = self.db.begin().await?;
+        let mut events = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            let event = sqlx::query_as::<_, RoastEvent>(
+                r#"
+                INSERT INTO roast_events (id, session_id, event_type, elapsed_seconds, temperature, notes, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                RETURNING *
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(session_id)
+            .bind(req.event_type.to_string())
+            .bind(req.elapsed_seconds)
+            .bind(req.temperature)
+            .bind(&req.notes)
+            .bind(now)
+            .fetch_one(&mut *tx)
+            .await?;
+            events.push(event);
         }
-    }
-
-    // Extract roast events
-    let mut events = Vec::new();
-
-    let event_types = vec![
-        ("CHARGE", "CHARGE"),
-        ("TP", "TP"),
-        ("DRY", "DRY"),
-        ("FCs", "FCs"),
-        ("FCe", "FCe"),
-        ("SCs", "SCs"),
-        ("DROP", "DROP"),
-    ];
-
-    for (key, event_type) in event_types {
-        if let Some(time) = computed
-            .get(&format!("{}_time", key))
-            .and_then(|t| t.as_f64())
-        {
-            let bean_temp = computed
-                .get(&format!("{}_BT", key))
-                .and_then(|t| t.as_f64())
-                .unwrap_or(0.0) as f32;
-            let env_temp = computed
-                .get(&format!("{}_ET", key))
-                .and_then(|t| t.as_f64())
-                .unwrap_or(0.0) as f32;
-            let name = match event_type {
-                "CHARGE" => "Charge",
-                "TP" => "Turning Point",
-                "DRY" => "Dry End",
-                "FCs" => "First Crack Start",
-                "FCe" => "First Crack End",
-                "SCs" => "Second Crack Start",
-                "DROP" => "Drop",
-                _ => event_type,
-            }
-            .to_string();
+        tx.commit().await?;
 
-            events.push(ArtisanRoastEvent {
-                name,
-                time,
-                bean_temp,
-                env_temp,
-                event_type: event_type.to_string(),
-            });
+        for event in &events {
+            self.journal(SyncEntity::Event, &event.id, SyncOp::Upsert)
+                .await?;
+            self.publish(SessionActivity::EventCreated(event.clone()));
         }
+        Ok(events)
     }
 
-    let title = profile_data["title"]
-        .as_str()
-        .unwrap_or("Imported Profile")
-        .to_string();
-    let roast_date = profile_data["roastdate"].as_str().unwrap_or("").to_string();
-    let total_time = computed
-        .get("totaltime")
-        .and_then(|t| t.as_f64())
-        .unwrap_or_else(|| timex.last().and_then(|t| t.as_f64()).unwrap_or(0.0));
-
-    Ok(ParsedArtisanProfile {
-        title,
-        roast_date,
-        total_time,
-        points,
-        events,
-    })
-}
-
-// ============================================================================
-// Device Service
-// ============================================================================
-
-#[derive(Clone)]
-pub struct DeviceService {
-    db: SqlitePool,
-}
-
-impl DeviceService {
-    pub fn new(db: SqlitePool) -> Self {
-        Self { db }
-    }
+    async fn insert_roast_event(
+        &self,
+        event_id: &str,
+        session_id: &str,
+        req: CreateRoastEventRequest,
+    ) -> Result<RoastEvent> {
+        let now = Utc::now();
 
-    // ---- Device CRUD ----
+        let event = sqlx::query_as::<_, RoastEvent>(
+            r#"
+            INSERT INTO roast_events (id, session_id, event_type, elapsed_seconds, temperature, notes, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            RETURNING *
+            "#
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .bind(req.event_type.to_string())
+        .bind(req.elapsed_seconds)
+        .bind(req.temperature)
+        .bind(&req.notes)
+        .bind(now)
+        .fetch_one(&self.db)
+        .await?;
 
-    pub async fn list_devices(&self, status_filter: Option<DeviceStatus>) -> Result<Vec<Device>> {
-        if let Some(status) = status_filter {
-            let devices = sqlx::query_as::<_, Device>(
-                "SELECT * FROM devices WHERE status = ? ORDER BY created_at DESC",
-            )
-            .bind(status.to_string())
-            .fetch_all(&self.db)
+        self.journal(SyncEntity::Event, &event.id, SyncOp::Upsert)
             .await?;
-            Ok(devices)
-        } else {
-            let devices =
-                sqlx::query_as::<_, Device>("SELECT * FROM devices ORDER BY created_at DESC")
-                    .fetch_all(&self.db)
-                    .await?;
-            Ok(devices)
-        }
+        self.publish(SessionActivity::EventCreated(event.clone()));
+        Ok(event)
     }
 
-    pub async fn get_device(&self, id: &str) -> Result<Option<DeviceWithConnections>> {
-        let device = sqlx::query_as::<_, Device>("SELECT * FROM devices WHERE id = ?")
-            .bind(id)
+    pub async fn get_roast_event(&self, event_id: &str) -> Result<Option<RoastEvent>> {
+        let event = sqlx::query_as::<_, RoastEvent>("SELECT * FROM roast_events WHERE id = ?1")
+            .bind(event_id)
             .fetch_optional(&self.db)
             .await?;
 
-        let Some(device) = device else {
-            return Ok(None);
-        };
+        Ok(event)
+    }
 
-        let connections = sqlx::query_as::<_, DeviceConnection>(
-            "SELECT * FROM device_connections WHERE device_id = ? ORDER BY priority DESC",
+    pub async fn get_roast_events(&self, session_id: &str) -> Result<Vec<RoastEvent>> {
+        let events = sqlx::query_as::<_, RoastEvent>(
+            "SELECT * FROM roast_events WHERE session_id = ?1 ORDER BY elapsed_seconds ASC",
         )
-        .bind(id)
+        .bind(session_id)
         .fetch_all(&self.db)
         .await?;
 
-        Ok(Some(DeviceWithConnections {
-            device,
-            connections,
-        }))
+        Ok(events)
     }
 
-    pub async fn get_device_by_device_id(
+    pub async fn update_roast_event(
         &self,
-        device_id: &str,
-    ) -> Result<Option<DeviceWithConnections>> {
-        let device = sqlx::query_as::<_, Device>("SELECT * FROM devices WHERE device_id = ?")
-            .bind(device_id)
-            .fetch_optional(&self.db)
-            .await?;
+        event_id: &str,
+        req: UpdateRoastEventRequest,
+    ) -> Result<RoastEvent> {
+        let mut updates = Vec::new();
 
-        let Some(device) = device else {
-            return Ok(None);
-        };
+        if req.elapsed_seconds.is_some() {
+            updates.push("elapsed_seconds = ?".to_string());
+        }
 
-        let connections = sqlx::query_as::<_, DeviceConnection>(
-            "SELECT * FROM device_connections WHERE device_id = ? ORDER BY priority DESC",
-        )
-        .bind(&device.id)
-        .fetch_all(&self.db)
-        .await?;
+        if req.temperature.is_some() {
+            updates.push("temperature = ?".to_string());
+        }
 
-        Ok(Some(DeviceWithConnections {
-            device,
-            connections,
-        }))
-    }
+        if req.notes.is_some() {
+            updates.push("notes = ?".to_string());
+        }
 
-    pub async fn create_device(&self, req: CreateDeviceRequest) -> Result<Device> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+        if updates.is_empty() {
+            return Err(anyhow::anyhow!("No fields to update"));
+        }
 
-        let device = sqlx::query_as::<_, Device>(
-            r#"
-            INSERT INTO devices (id, name, device_id, profile_id, status, description, location, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-            RETURNING *
-            "#
-        )
-        .bind(&id)
-        .bind(&req.name)
-        .bind(&req.device_id)
-        .bind(&req.profile_id)
-        .bind(DeviceStatus::Pending.to_string())
-        .bind(&req.description)
-        .bind(&req.location)
-        .bind(now)
-        .bind(now)
-        .fetch_one(&self.db)
-        .await?;
+        updates.push("updated_at = CURRENT_TIMESTAMP".to_string());
 
-        Ok(device)
-    }
+        let query = format!(
+            "UPDATE roast_events SET {} WHERE id = ? RETURNING *",
+            updates.join(", ")
+        );
 
-    pub async fn update_device(
-        &self,
-        id: &str,
-        req: UpdateDeviceRequest,
-    ) -> Result<Option<Device>> {
-        if req.name.is_none()
-            && req.profile_id.is_none()
-            && req.status.is_none()
-            && req.description.is_none()
-            && req.location.is_none()
-        {
-            // Nothing to update, just return the existing device
-            let device = sqlx::query_as::<_, Device>("SELECT * FROM devices WHERE id = ?")
-                .bind(id)
-                .fetch_optional(&self.db)
-                .await?;
-            return Ok(device);
-        }
-
-        let now = Utc::now();
-        let mut query = "UPDATE devices SET updated_at = ?".to_string();
+        let mut query_builder = sqlx::query_as::<_, RoastEvent>(&query);
 
-        if req.name.is_some() {
-            query.push_str(", name = ?");
-        }
-        if req.profile_id.is_some() {
-            query.push_str(", profile_id = ?");
-        }
-        if req.status.is_some() {
-            query.push_str(", status = ?");
+        // Bind parameters in the same order as they appear in the updates
+        if let Some(elapsed_seconds) = req.elapsed_seconds {
+            query_builder = query_builder.bind(elapsed_seconds);
         }
-        if req.description.is_some() {
-            query.push_str(", description = ?");
+        if let Some(temperature) = req.temperature {
+            query_builder = query_builder.bind(temperature);
         }
-        if req.location.is_some() {
-            query.push_str(", location = ?");
+        if let Some(notes) = req.notes {
+            query_builder = query_builder.bind(notes);
         }
 
-        query.push_str(" WHERE id = ? RETURNING *");
+        // Bind the event_id for the WHERE clause
+        query_builder = query_builder.bind(event_id);
 
-        let mut query_builder = sqlx::query_as::<_, Device>(&query).bind(now);
+        let event = query_builder.fetch_one(&self.db).await?;
 
-        if let Some(ref name) = req.name {
-            query_builder = query_builder.bind(name);
-        }
-        if let Some(ref profile_id) = req.profile_id {
-            query_builder = query_builder.bind(profile_id);
-        }
-        if let Some(ref status) = req.status {
-            query_builder = query_builder.bind(status.to_string());
-        }
-        if let Some(ref description) = req.description {
-            query_builder = query_builder.bind(description);
-        }
-        if let Some(ref location) = req.location {
-            query_builder = query_builder.bind(location);
+        self.journal(SyncEntity::Event, &event.id, SyncOp::Upsert)
+            .await?;
+        Ok(event)
+    }
+
+    pub async fn delete_roast_event(&self, event_id: &str) -> Result<()> {
+        let rows_affected = sqlx::query("DELETE FROM roast_events WHERE id = ?1")
+            .bind(event_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(anyhow::anyhow!("Roast event not found"));
         }
 
-        query_builder = query_builder.bind(id);
+        self.journal(SyncEntity::Event, event_id, SyncOp::Delete)
+            .await?;
+        Ok(())
+    }
 
-        let device = query_builder.fetch_optional(&self.db).await?;
-        Ok(device)
+    // ---- Roast cues ----
+
+    pub async fn create_profile_cue(
+        &self,
+        profile_id: &str,
+        req: CreateCueRequest,
+    ) -> Result<RoastCue> {
+        self.insert_cue(Some(profile_id), None, req).await
     }
 
-    pub async fn delete_device(&self, id: &str) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM devices WHERE id = ?")
+    pub async fn create_session_cue(
+        &self,
+        session_id: &str,
+        req: CreateCueRequest,
+    ) -> Result<RoastCue> {
+        self.insert_cue(None, Some(session_id), req).await
+    }
+
+    async fn insert_cue(
+        &self,
+        profile_id: Option<&str>,
+        session_id: Option<&str>,
+        req: CreateCueRequest,
+    ) -> Result<RoastCue> {
+        let cue = sqlx::query_as::<_, RoastCue>(
+            r#"
+            INSERT INTO roast_cues (id, profile_id, session_id, message, trigger_type, trigger_value, notify, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(profile_id)
+        .bind(session_id)
+        .bind(req.message.trim())
+        .bind(req.trigger_type)
+        .bind(req.trigger_value)
+        .bind(req.notify)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(cue)
+    }
+
+    pub async fn list_profile_cues(&self, profile_id: &str) -> Result<Vec<RoastCue>> {
+        let cues = sqlx::query_as::<_, RoastCue>(
+            "SELECT * FROM roast_cues WHERE profile_id = ?1 ORDER BY trigger_type, trigger_value",
+        )
+        .bind(profile_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(cues)
+    }
+
+    /// Cues that apply to a session: its own plus those of its profile,
+    /// with the time each one fired during this session.
+    pub async fn list_session_cues(&self, session: &RoastSession) -> Result<Vec<SessionCue>> {
+        let cues = sqlx::query_as::<_, SessionCue>(
+            r#"
+            SELECT c.*, f.fired_at
+            FROM roast_cues c
+            LEFT JOIN session_cue_firings f ON f.cue_id = c.id AND f.session_id = ?1
+            WHERE c.session_id = ?1 OR (?2 IS NOT NULL AND c.profile_id = ?2)
+            ORDER BY c.trigger_type, c.trigger_value
+            "#,
+        )
+        .bind(&session.id)
+        .bind(&session.profile_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(cues)
+    }
+
+    pub async fn delete_cue(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM roast_cues WHERE id = ?1")
             .bind(id)
             .execute(&self.db)
             .await?;
@@ -1468,1250 +2156,6357 @@ impl DeviceService {
         Ok(result.rows_affected() > 0)
     }
 
-    #[allow(dead_code)] // Will be used by device status transitions
-    pub async fn update_device_status(
+    /// Record that a cue fired in a session. Returns `false` when it had
+    /// already fired, so callers announce each cue once.
+    pub async fn record_cue_firing(
         &self,
-        id: &str,
-        status: DeviceStatus,
-    ) -> Result<Option<Device>> {
-        let device = sqlx::query_as::<_, Device>(
-            "UPDATE devices SET status = ?, updated_at = ? WHERE id = ? RETURNING *",
+        session_id: &str,
+        cue_id: &str,
+        elapsed_seconds: f64,
+        bean_temp: Option<f64>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO session_cue_firings (session_id, cue_id, elapsed_seconds, bean_temp, fired_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
         )
-        .bind(status.to_string())
+        .bind(session_id)
+        .bind(cue_id)
+        .bind(elapsed_seconds)
+        .bind(bean_temp)
         .bind(Utc::now())
-        .bind(id)
-        .fetch_optional(&self.db)
+        .execute(&self.db)
         .await?;
 
-        Ok(device)
+        Ok(result.rows_affected() > 0)
     }
 
-    pub async fn update_last_seen(&self, device_id: &str) -> Result<()> {
-        sqlx::query("UPDATE devices SET last_seen_at = ? WHERE device_id = ?")
-            .bind(Utc::now())
-            .bind(device_id)
-            .execute(&self.db)
-            .await?;
+    // ---- Automation rules ----
 
-        Ok(())
+    pub async fn create_profile_automation(
+        &self,
+        profile_id: &str,
+        req: CreateAutomationRequest,
+    ) -> Result<AutomationRule> {
+        self.insert_automation(Some(profile_id), None, req).await
     }
 
-    // ---- Device Profile CRUD ----
+    pub async fn create_device_automation(
+        &self,
+        device_id: &str,
+        req: CreateAutomationRequest,
+    ) -> Result<AutomationRule> {
+        self.insert_automation(None, Some(device_id), req).await
+    }
 
-    pub async fn list_profiles(&self) -> Result<Vec<DeviceProfile>> {
-        let profiles = sqlx::query_as::<_, DeviceProfile>(
-            "SELECT * FROM device_profiles ORDER BY created_at DESC",
+    async fn insert_automation(
+        &self,
+        profile_id: Option<&str>,
+        device_id: Option<&str>,
+        req: CreateAutomationRequest,
+    ) -> Result<AutomationRule> {
+        let rule = sqlx::query_as::<_, AutomationRule>(
+            r#"
+            INSERT INTO automation_rules (
+                id, profile_id, device_id, name, trigger_type, trigger_value,
+                trigger_event, command, value, relative, created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(profile_id)
+        .bind(device_id)
+        .bind(req.name.trim())
+        .bind(req.trigger_type)
+        .bind(req.trigger_value)
+        .bind(req.trigger_event)
+        .bind(req.command)
+        .bind(req.value)
+        .bind(req.relative)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(rule)
+    }
+
+    pub async fn list_profile_automations(&self, profile_id: &str) -> Result<Vec<AutomationRule>> {
+        let rules = sqlx::query_as::<_, AutomationRule>(
+            "SELECT * FROM automation_rules WHERE profile_id = ?1 ORDER BY created_at",
         )
+        .bind(profile_id)
         .fetch_all(&self.db)
         .await?;
 
-        Ok(profiles)
+        Ok(rules)
     }
 
-    pub async fn get_profile(&self, id: &str) -> Result<Option<DeviceProfile>> {
-        let profile =
-            sqlx::query_as::<_, DeviceProfile>("SELECT * FROM device_profiles WHERE id = ?")
-                .bind(id)
-                .fetch_optional(&self.db)
-                .await?;
+    pub async fn list_device_automations(&self, device_id: &str) -> Result<Vec<AutomationRule>> {
+        let rules = sqlx::query_as::<_, AutomationRule>(
+            "SELECT * FROM automation_rules WHERE device_id = ?1 ORDER BY created_at",
+        )
+        .bind(device_id)
+        .fetch_all(&self.db)
+        .await?;
 
-        Ok(profile)
+        Ok(rules)
     }
 
-    pub async fn create_profile(&self, req: CreateDeviceProfileRequest) -> Result<DeviceProfile> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    /// Rules that apply to a session: its roaster's plus those of its
+    /// profile, with the time each one ran during this session.
+    pub async fn list_session_automations(
+        &self,
+        session: &RoastSession,
+    ) -> Result<Vec<SessionAutomationRule>> {
+        let rules = sqlx::query_as::<_, SessionAutomationRule>(
+            r#"
+            SELECT r.*, x.executed_at
+            FROM automation_rules r
+            LEFT JOIN automation_executions x ON x.rule_id = r.id AND x.session_id = ?1
+            WHERE r.device_id = ?2 OR (?3 IS NOT NULL AND r.profile_id = ?3)
+            ORDER BY r.created_at
+            "#,
+        )
+        .bind(&session.id)
+        .bind(&session.device_id)
+        .bind(&session.profile_id)
+        .fetch_all(&self.db)
+        .await?;
 
-        let profile = sqlx::query_as::<_, DeviceProfile>(
+        Ok(rules)
+    }
+
+    pub async fn delete_automation(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM automation_rules WHERE id = ?1")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Claim a rule's single run in a session before sending its command.
+    /// Returns the execution id, or `None` when the rule already ran.
+    pub async fn claim_automation_execution(
+        &self,
+        session: &RoastSession,
+        rule: &AutomationRule,
+        elapsed_seconds: f64,
+        bean_temp: Option<f64>,
+    ) -> Result<Option<String>> {
+        let id = Uuid::new_v4().to_string();
+        let result = sqlx::query(
             r#"
-            INSERT INTO device_profiles (
-                id, name, description, default_control_mode, default_setpoint, default_fan_pwm,
-                default_kp, default_ki, default_kd, max_temp, min_fan_pwm, telemetry_interval_ms,
-                created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            RETURNING *
+            INSERT OR IGNORE INTO automation_executions (
+                id, rule_id, session_id, device_id, elapsed_seconds, bean_temp,
+                command, executed_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
             "#,
         )
         .bind(&id)
-        .bind(&req.name)
-        .bind(&req.description)
-        .bind(&req.default_control_mode)
-        .bind(req.default_setpoint)
-        .bind(req.default_fan_pwm)
-        .bind(req.default_kp)
-        .bind(req.default_ki)
-        .bind(req.default_kd)
-        .bind(req.max_temp)
-        .bind(req.min_fan_pwm)
-        .bind(req.telemetry_interval_ms)
-        .bind(now)
-        .bind(now)
-        .fetch_one(&self.db)
+        .bind(&rule.id)
+        .bind(&session.id)
+        .bind(&session.device_id)
+        .bind(elapsed_seconds)
+        .bind(bean_temp)
+        .bind(rule.command)
+        .bind(Utc::now())
+        .execute(&self.db)
         .await?;
 
-        Ok(profile)
+        Ok((result.rows_affected() > 0).then_some(id))
     }
 
-    pub async fn update_profile(
+    /// Record the outcome of a claimed execution.
+    pub async fn finish_automation_execution(
         &self,
         id: &str,
-        req: UpdateDeviceProfileRequest,
-    ) -> Result<Option<DeviceProfile>> {
-        let now = Utc::now();
-        let profile = sqlx::query_as::<_, DeviceProfile>(
-            r#"
-            UPDATE device_profiles SET
-                name = COALESCE(?, name),
-                description = COALESCE(?, description),
-                default_control_mode = COALESCE(?, default_control_mode),
-                default_setpoint = COALESCE(?, default_setpoint),
-                default_fan_pwm = COALESCE(?, default_fan_pwm),
-                default_kp = COALESCE(?, default_kp),
-                default_ki = COALESCE(?, default_ki),
-                default_kd = COALESCE(?, default_kd),
-                max_temp = COALESCE(?, max_temp),
-                min_fan_pwm = COALESCE(?, min_fan_pwm),
-                telemetry_interval_ms = COALESCE(?, telemetry_interval_ms),
-                updated_at = ?
-            WHERE id = ?
-            RETURNING *
-            "#,
+        sent_value: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE automation_executions SET sent_value = ?2, success = ?3, error = ?4 WHERE id = ?1",
         )
-        .bind(&req.name)
-        .bind(&req.description)
-        .bind(&req.default_control_mode)
-        .bind(req.default_setpoint)
-        .bind(req.default_fan_pwm)
-        .bind(req.default_kp)
-        .bind(req.default_ki)
-        .bind(req.default_kd)
-        .bind(req.max_temp)
-        .bind(req.min_fan_pwm)
-        .bind(req.telemetry_interval_ms)
-        .bind(now)
         .bind(id)
-        .fetch_optional(&self.db)
+        .bind(sent_value)
+        .bind(error.is_none())
+        .bind(error)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_automation_executions(
+        &self,
+        session_id: &str,
+    ) -> Result<Vec<AutomationExecution>> {
+        let executions = sqlx::query_as::<_, AutomationExecution>(
+            "SELECT * FROM automation_executions WHERE session_id = ?1 ORDER BY executed_at",
+        )
+        .bind(session_id)
+        .fetch_all(&self.db)
         .await?;
 
-        Ok(profile)
+        Ok(executions)
+    }
+
+    // ---- Cupping Notes CRUD (AP-012) ----
+
+    pub async fn create_cupping(
+        &self,
+        session_id: &str,
+        req: CreateCuppingRequest,
+    ) -> Result<CuppingWithAttributes> {
+        // Delete any existing cupping for this session (upsert semantics)
+        // CASCADE will also delete associated cupping_attributes
+        sqlx::query("DELETE FROM cupping_scores WHERE session_id = ?")
+            .bind(session_id)
+            .execute(&self.db)
+            .await?;
+
+        let cupping_id = Uuid::new_v4().to_string();
+        let framework = req.scoring_framework.unwrap_or_else(|| "sca".to_string());
+
+        // Compute overall score as average of attribute scores
+        let overall: Option<f32> = if req.attributes.is_empty() {
+            None
+        } else {
+            let sum: f32 = req.attributes.iter().map(|a| a.score).sum();
+            Some(sum / req.attributes.len() as f32)
+        };
+
+        let cupping = sqlx::query_as::<_, CuppingScore>(
+            r#"INSERT INTO cupping_scores (id, session_id, scoring_framework, overall_score, notes)
+               VALUES (?, ?, ?, ?, ?)
+               RETURNING *"#,
+        )
+        .bind(&cupping_id)
+        .bind(session_id)
+        .bind(&framework)
+        .bind(overall)
+        .bind(&req.notes)
+        .fetch_one(&self.db)
+        .await?;
+
+        let mut attributes = Vec::new();
+        for attr in &req.attributes {
+            let attr_id = Uuid::new_v4().to_string();
+            let row = sqlx::query_as::<_, CuppingAttribute>(
+                r#"INSERT INTO cupping_attributes (id, cupping_id, attribute_name, score)
+                   VALUES (?, ?, ?, ?)
+                   RETURNING *"#,
+            )
+            .bind(&attr_id)
+            .bind(&cupping_id)
+            .bind(&attr.name)
+            .bind(attr.score)
+            .fetch_one(&self.db)
+            .await?;
+            attributes.push(row);
+        }
+
+        self.journal(SyncEntity::Cupping, session_id, SyncOp::Upsert)
+            .await?;
+        Ok(CuppingWithAttributes {
+            cupping,
+            attributes,
+        })
+    }
+
+    pub async fn get_cupping(&self, session_id: &str) -> Result<Option<CuppingWithAttributes>> {
+        let cupping = sqlx::query_as::<_, CuppingScore>(
+            "SELECT * FROM cupping_scores WHERE session_id = ? ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(session_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let cupping = match cupping {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        let attributes = sqlx::query_as::<_, CuppingAttribute>(
+            "SELECT * FROM cupping_attributes WHERE cupping_id = ? ORDER BY attribute_name",
+        )
+        .bind(&cupping.id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(Some(CuppingWithAttributes {
+            cupping,
+            attributes,
+        }))
+    }
+
+    pub async fn delete_cupping(&self, session_id: &str) -> Result<()> {
+        // CASCADE will delete cupping_attributes
+        let result = sqlx::query("DELETE FROM cupping_scores WHERE session_id = ?")
+            .bind(session_id)
+            .execute(&self.db)
+            .await?;
+        if result.rows_affected() > 0 {
+            self.journal(SyncEntity::Cupping, session_id, SyncOp::Delete)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // ---- Roast defects ----
+
+    pub async fn create_defect(
+        &self,
+        session_id: &str,
+        req: CreateDefectRequest,
+        tagged_by: Option<&str>,
+    ) -> Result<SessionDefect> {
+        let defect = sqlx::query_as::<_, SessionDefect>(
+            r#"
+            INSERT INTO session_defects (id, session_id, defect, start_seconds, end_seconds, notes, tagged_by, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(session_id)
+        .bind(req.defect)
+        .bind(req.start_seconds)
+        .bind(req.end_seconds)
+        .bind(&req.notes)
+        .bind(tagged_by)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(defect)
+    }
+
+    pub async fn list_defects(&self, session_id: &str) -> Result<Vec<SessionDefect>> {
+        let defects = sqlx::query_as::<_, SessionDefect>(
+            "SELECT * FROM session_defects WHERE session_id = ?1 ORDER BY start_seconds, created_at",
+        )
+        .bind(session_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(defects)
+    }
+
+    pub async fn delete_defect(&self, session_id: &str, defect_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM session_defects WHERE id = ?1 AND session_id = ?2")
+            .bind(defect_id)
+            .bind(session_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Share of completed sessions tagged with each defect, per profile or bean.
+    pub async fn defect_rates(&self, q: &DefectRateQuery) -> Result<DefectRatesResponse> {
+        let (key_expr, label_expr) = match q.group_by {
+            DefectGroupBy::Bean => ("s.bean_origin", "s.bean_origin"),
+            DefectGroupBy::Profile => ("s.profile_id", "p.name"),
+        };
+        let mut query = format!(
+            r#"
+            SELECT DISTINCT {key_expr} AS grp, {label_expr} AS label, s.id AS session_id, d.defect AS defect
+            FROM roast_sessions s
+            LEFT JOIN roast_profiles p ON p.id = s.profile_id
+            LEFT JOIN session_defects d ON d.session_id = s.id
+            WHERE s.status = ?
+            "#
+        );
+        if q.from.is_some() {
+            query.push_str(" AND COALESCE(s.start_time, s.created_at) >= ?");
+        }
+        if q.to.is_some() {
+            query.push_str(" AND COALESCE(s.start_time, s.created_at) < ?");
+        }
+        query.push_str(" ORDER BY grp, s.id");
+
+        let mut query_builder = sqlx::query(&query).bind(SessionStatus::Completed.to_string());
+        if let Some(from) = q.from {
+            query_builder = query_builder.bind(from);
+        }
+        if let Some(to) = q.to {
+            query_builder = query_builder.bind(to);
+        }
+        let rows = query_builder.fetch_all(&self.read_db).await?;
+
+        struct Tally {
+            key: Option<String>,
+            label: Option<String>,
+            sessions: std::collections::HashSet<String>,
+            defective: std::collections::HashSet<String>,
+            by_defect: std::collections::BTreeMap<DefectType, i64>,
+        }
+        let mut tallies: Vec<Tally> = Vec::new();
+        for row in rows {
+            let key: Option<String> = row.try_get("grp")?;
+            let session_id: String = row.try_get("session_id")?;
+            let defect: Option<DefectType> = row.try_get("defect")?;
+            if tallies.last().is_none_or(|t| t.key != key) {
+                tallies.push(Tally {
+                    key,
+                    label: row.try_get("label")?,
+                    sessions: Default::default(),
+                    defective: Default::default(),
+                    by_defect: Default::default(),
+                });
+            }
+            let tally = tallies.last_mut().unwrap();
+            tally.sessions.insert(session_id.clone());
+            if let Some(defect) = defect {
+                tally.defective.insert(session_id);
+                *tally.by_defect.entry(defect).or_default() += 1;
+            }
+        }
+
+        let groups = tallies
+            .into_iter()
+            .map(|t| {
+                let sessions = t.sessions.len() as i64;
+                let rate = |n: i64| n as f64 / sessions as f64;
+                DefectRateGroup {
+                    key: t.key,
+                    label: t.label,
+                    sessions,
+                    defective_sessions: t.defective.len() as i64,
+                    defect_rate: rate(t.defective.len() as i64),
+                    defects: t
+                        .by_defect
+                        .into_iter()
+                        .map(|(defect, n)| DefectRate {
+                            defect,
+                            sessions: n,
+                            rate: rate(n),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Ok(DefectRatesResponse {
+            group_by: q.group_by,
+            groups,
+        })
+    }
+
+    // ---- Offline sync ----
+
+    /// Append a row to the sync change journal.
+    async fn journal(&self, entity: SyncEntity, entity_id: &str, op: SyncOp) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO sync_changes (entity, entity_id, op, origin, changed_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(entity)
+        .bind(entity_id)
+        .bind(op)
+        .bind(&self.sync_origin)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Journal a session update when it matched a row.
+    async fn journaled(&self, session: Option<RoastSession>) -> Result<Option<RoastSession>> {
+        if let Some(s) = &session {
+            self.journal(SyncEntity::Session, &s.id, SyncOp::Upsert)
+                .await?;
+        }
+        Ok(session)
+    }
+
+    /// Changes after `since`, collapsed to the latest change per record.
+    pub async fn sync_pull(&self, since: i64, limit: i64) -> Result<SyncPullResponse> {
+        let mut rows = sqlx::query_as::<_, SyncChange>(
+            r#"
+            SELECT * FROM sync_changes c
+            WHERE c.seq > ?1
+              AND c.seq = (
+                  SELECT MAX(seq) FROM sync_changes
+                  WHERE entity = c.entity AND entity_id = c.entity_id
+              )
+            ORDER BY c.seq
+            LIMIT ?2
+            "#,
+        )
+        .bind(since)
+        .bind(limit + 1)
+        .fetch_all(&self.db)
+        .await?;
+
+        let has_more = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+        let cursor = rows.last().map_or(since, |r| r.seq);
+
+        let mut changes = Vec::with_capacity(rows.len());
+        for row in rows {
+            let data = match row.op {
+                SyncOp::Upsert => self.sync_snapshot(row.entity, &row.entity_id).await?,
+                SyncOp::Delete => None,
+            };
+            changes.push(SyncPullItem {
+                seq: row.seq,
+                entity: row.entity,
+                entity_id: row.entity_id,
+                // Removed without a journal entry (e.g. cascaded from its session)
+                op: if data.is_some() {
+                    row.op
+                } else {
+                    SyncOp::Delete
+                },
+                changed_at: row.changed_at,
+                data,
+            });
+        }
+
+        Ok(SyncPullResponse {
+            changes,
+            cursor,
+            has_more,
+        })
+    }
+
+    async fn sync_snapshot(
+        &self,
+        entity: SyncEntity,
+        entity_id: &str,
+    ) -> Result<Option<serde_json::Value>> {
+        let value = match entity {
+            SyncEntity::Session => self.get_session(entity_id).await?.map(serde_json::to_value),
+            SyncEntity::Event => self
+                .get_roast_event(entity_id)
+                .await?
+                .map(serde_json::to_value),
+            SyncEntity::Cupping => self.get_cupping(entity_id).await?.map(serde_json::to_value),
+        };
+        Ok(value.transpose()?)
+    }
+
+    /// Apply changes recorded offline by a sync client. Each change is
+    /// applied independently; a record changed on the server after the
+    /// client's `base_seq` by anyone else is reported as a conflict (with the
+    /// server copy) unless the change sets `force`.
+    pub async fn sync_push(
+        &self,
+        req: SyncPushRequest,
+        can_edit_signed_off: bool,
+    ) -> Result<SyncPushResponse> {
+        let service = self.clone().with_sync_origin(&req.client_id);
+        let mut results = Vec::with_capacity(req.changes.len());
+        for change in req.changes {
+            let (entity, entity_id) = (change.entity, change.entity_id.clone());
+            let result = if !change.force
+                && service
+                    .changed_elsewhere(entity, &entity_id, req.base_seq)
+                    .await?
+            {
+                SyncPushResult {
+                    entity,
+                    entity_id: entity_id.clone(),
+                    status: SyncPushStatus::Conflict,
+                    error: None,
+                    server: service.sync_snapshot(entity, &entity_id).await?,
+                }
+            } else {
+                let outcome = service.apply_sync_change(change, can_edit_signed_off).await;
+                SyncPushResult {
+                    entity,
+                    entity_id,
+                    status: if outcome.is_ok() {
+                        SyncPushStatus::Applied
+                    } else {
+                        SyncPushStatus::Rejected
+                    },
+                    error: outcome.err().map(|e| e.to_string()),
+                    server: None,
+                }
+            };
+            results.push(result);
+        }
+        Ok(SyncPushResponse { results })
+    }
+
+    async fn changed_elsewhere(
+        &self,
+        entity: SyncEntity,
+        entity_id: &str,
+        base_seq: i64,
+    ) -> Result<bool> {
+        let changed: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM sync_changes
+                WHERE entity = ? AND entity_id = ? AND seq > ?
+                  AND (origin IS NULL OR origin != ?)
+            )
+            "#,
+        )
+        .bind(entity)
+        .bind(entity_id)
+        .bind(base_seq)
+        .bind(&self.sync_origin)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(changed)
+    }
+
+    /// Load a session a sync change touches, refusing signed-off sessions.
+    async fn editable_session(
+        &self,
+        session_id: &str,
+        can_edit_signed_off: bool,
+    ) -> Result<Option<RoastSession>> {
+        let session = self.get_session(session_id).await?;
+        if let Some(s) = &session {
+            if s.is_signed_off() && !can_edit_signed_off {
+                return Err(anyhow!("session {} is signed off", session_id));
+            }
+        }
+        Ok(session)
+    }
+
+    async fn apply_sync_change(
+        &self,
+        change: SyncPushChange,
+        can_edit_signed_off: bool,
+    ) -> Result<()> {
+        let id = change.entity_id.as_str();
+        match (change.entity, change.op) {
+            (SyncEntity::Session, SyncOp::Upsert) => {
+                if self
+                    .editable_session(id, can_edit_signed_off)
+                    .await?
+                    .is_some()
+                {
+                    let req: UpdateSessionRequest = serde_json::from_value(change.data)?;
+                    self.update_session(id, req).await?;
+                } else {
+                    let req: CreateSessionRequest = serde_json::from_value(change.data)?;
+                    self.insert_session(id, req).await?;
+                }
+            }
+            (SyncEntity::Session, SyncOp::Delete) => {
+                if self
+                    .editable_session(id, can_edit_signed_off)
+                    .await?
+                    .is_some()
+                {
+                    self.delete_session(id).await?;
+                }
+            }
+            (SyncEntity::Event, SyncOp::Upsert) => {
+                if let Some(event) = self.get_roast_event(id).await? {
+                    self.editable_session(&event.session_id, can_edit_signed_off)
+                        .await?;
+                    let req: UpdateRoastEventRequest = serde_json::from_value(change.data)?;
+                    self.update_roast_event(id, req).await?;
+                } else {
+                    #[derive(Deserialize)]
+                    struct NewEvent {
+                        session_id: String,
+                        #[serde(flatten)]
+                        event: CreateRoastEventRequest,
+                    }
+                    let req: NewEvent = serde_json::from_value(change.data)?;
+                    if self
+                        .editable_session(&req.session_id, can_edit_signed_off)
+                        .await?
+                        .is_none()
+                    {
+                        return Err(anyhow!("session {} not found", req.session_id));
+                    }
+                    self.insert_roast_event(id, &req.session_id, req.event)
+                        .await?;
+                }
+            }
+            (SyncEntity::Event, SyncOp::Delete) => {
+                if let Some(event) = self.get_roast_event(id).await? {
+                    self.editable_session(&event.session_id, can_edit_signed_off)
+                        .await?;
+                    self.delete_roast_event(id).await?;
+                }
+            }
+            (SyncEntity::Cupping, op) => {
+                if self
+                    .editable_session(id, can_edit_signed_off)
+                    .await?
+                    .is_none()
+                {
+                    return Err(anyhow!("session {} not found", id));
+                }
+                match op {
+                    SyncOp::Upsert => {
+                        let req: CreateCuppingRequest = serde_json::from_value(change.data)?;
+                        self.create_cupping(id, req).await?;
+                    }
+                    SyncOp::Delete => self.delete_cupping(id).await?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // ---- Green beans ----
+
+    pub async fn create_bean(&self, req: CreateBeanRequest) -> Result<Bean> {
+        let now = Utc::now();
+        let bean = sqlx::query_as::<_, Bean>(
+            r#"
+            INSERT INTO beans (id, name, origin, variety, process, density, moisture_pct, notes, created_at, updated_at, cost_per_kg)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&req.name)
+        .bind(&req.origin)
+        .bind(&req.variety)
+        .bind(&req.process)
+        .bind(req.density)
+        .bind(req.moisture_pct)
+        .bind(&req.notes)
+        .bind(now)
+        .bind(now)
+        .bind(req.cost_per_kg)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(bean)
+    }
+
+    pub async fn list_beans(&self) -> Result<Vec<Bean>> {
+        let beans = sqlx::query_as::<_, Bean>("SELECT * FROM beans ORDER BY name")
+            .fetch_all(&self.db)
+            .await?;
+        Ok(beans)
+    }
+
+    pub async fn get_bean(&self, id: &str) -> Result<Option<Bean>> {
+        let bean = sqlx::query_as::<_, Bean>("SELECT * FROM beans WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(bean)
+    }
+
+    pub async fn update_bean(&self, id: &str, req: UpdateBeanRequest) -> Result<Option<Bean>> {
+        let bean = sqlx::query_as::<_, Bean>(
+            r#"
+            UPDATE beans SET
+                name = COALESCE(?, name),
+                origin = COALESCE(?, origin),
+                variety = COALESCE(?, variety),
+                process = COALESCE(?, process),
+                density = COALESCE(?, density),
+                moisture_pct = COALESCE(?, moisture_pct),
+                notes = COALESCE(?, notes),
+                cost_per_kg = COALESCE(?, cost_per_kg),
+                updated_at = ?
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(&req.name)
+        .bind(&req.origin)
+        .bind(&req.variety)
+        .bind(&req.process)
+        .bind(req.density)
+        .bind(req.moisture_pct)
+        .bind(&req.notes)
+        .bind(req.cost_per_kg)
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(bean)
+    }
+
+    /// Delete a bean. Sessions that roasted it keep their origin/variety text.
+    pub async fn delete_bean(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM beans WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ---- Sample roasts ----
+
+    /// The group a new sample of `bean_id` on `profile_id` joins: the oldest
+    /// of the bean's groups that is not full and has no sample on that
+    /// profile yet, else a new one. Cancelled samples free their place.
+    async fn sample_group_for(&self, bean_id: &str, profile_id: &str) -> Result<String> {
+        let open = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT g.id FROM sample_groups g
+            WHERE g.bean_id = ?
+              AND (SELECT COUNT(*) FROM roast_sessions s
+                   WHERE s.sample_group_id = g.id AND s.status != ?) < ?
+              AND NOT EXISTS (SELECT 1 FROM roast_sessions s
+                   WHERE s.sample_group_id = g.id AND s.status != ? AND s.profile_id = ?)
+            ORDER BY g.created_at, g.id
+            LIMIT 1
+            "#,
+        )
+        .bind(bean_id)
+        .bind(SessionStatus::Cancelled.to_string())
+        .bind(SAMPLE_GROUP_SIZE)
+        .bind(SessionStatus::Cancelled.to_string())
+        .bind(profile_id)
+        .fetch_optional(&self.db)
+        .await?;
+        if let Some(id) = open {
+            return Ok(id);
+        }
+        let id = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO sample_groups (id, bean_id, created_at) VALUES (?, ?, ?)")
+            .bind(&id)
+            .bind(bean_id)
+            .bind(Utc::now())
+            .execute(&self.db)
+            .await?;
+        Ok(id)
+    }
+
+    pub async fn list_sample_groups(&self, bean_id: &str) -> Result<Vec<SampleGroupSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT g.id, g.bean_id, g.created_at,
+                (SELECT COUNT(*) FROM roast_sessions s
+                 WHERE s.sample_group_id = g.id AND s.status != ?) AS sample_count,
+                (SELECT COUNT(*) FROM roast_sessions s
+                 WHERE s.sample_group_id = g.id AND s.status = ?) AS completed_count
+            FROM sample_groups g
+            WHERE g.bean_id = ?
+            ORDER BY g.created_at DESC, g.id
+            "#,
+        )
+        .bind(SessionStatus::Cancelled.to_string())
+        .bind(SessionStatus::Completed.to_string())
+        .bind(bean_id)
+        .fetch_all(&self.read_db)
+        .await?;
+        let mut groups = Vec::with_capacity(rows.len());
+        for row in rows {
+            let completed_count: i64 = row.try_get("completed_count")?;
+            groups.push(SampleGroupSummary {
+                group: SampleGroup {
+                    id: row.try_get("id")?,
+                    bean_id: row.try_get("bean_id")?,
+                    created_at: row.try_get("created_at")?,
+                },
+                sample_count: row.try_get("sample_count")?,
+                completed_count,
+                complete: completed_count >= SAMPLE_GROUP_SIZE,
+            });
+        }
+        Ok(groups)
+    }
+
+    /// The group's samples side by side with their roast stats and cupping
+    /// scores. The group is complete once it has [`SAMPLE_GROUP_SIZE`]
+    /// completed samples.
+    pub async fn get_sample_evaluation(&self, group_id: &str) -> Result<Option<SampleEvaluation>> {
+        let Some(group) =
+            sqlx::query_as::<_, SampleGroup>("SELECT * FROM sample_groups WHERE id = ?")
+                .bind(group_id)
+                .fetch_optional(&self.read_db)
+                .await?
+        else {
+            return Ok(None);
+        };
+        let samples = sqlx::query_as::<_, SampleEvaluationRow>(
+            r#"
+            SELECT s.id AS session_id, s.name, s.status, s.profile_id,
+                p.name AS profile_name, s.green_weight, s.roasted_weight,
+                s.weight_loss_pct, s.total_time_seconds, s.first_crack_time,
+                s.development_time_ratio,
+                (SELECT e.temperature FROM roast_events e
+                    WHERE e.session_id = s.id AND e.event_type = ?
+                    ORDER BY e.elapsed_seconds DESC LIMIT 1) AS drop_temp,
+                (SELECT AVG(c.overall_score) FROM cupping_scores c
+                    WHERE c.session_id = s.id AND c.overall_score IS NOT NULL) AS cupping_score,
+                (SELECT c.notes FROM cupping_scores c
+                    WHERE c.session_id = s.id ORDER BY c.created_at DESC LIMIT 1) AS cupping_notes
+            FROM roast_sessions s
+            LEFT JOIN roast_profiles p ON p.id = s.profile_id
+            WHERE s.sample_group_id = ? AND s.status != ?
+            ORDER BY s.created_at, s.id
+            "#,
+        )
+        .bind(RoastEventType::Drop.to_string())
+        .bind(group_id)
+        .bind(SessionStatus::Cancelled.to_string())
+        .fetch_all(&self.read_db)
+        .await?;
+        let completed = samples
+            .iter()
+            .filter(|s| s.status == SessionStatus::Completed)
+            .count() as i64;
+        let best_session_id = samples
+            .iter()
+            .filter_map(|s| s.cupping_score.map(|score| (score, &s.session_id)))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, id)| id.clone());
+        let bean = self.get_bean(&group.bean_id).await?;
+        Ok(Some(SampleEvaluation {
+            group,
+            bean,
+            complete: completed >= SAMPLE_GROUP_SIZE,
+            best_session_id,
+            samples,
+        }))
+    }
+
+    // ---- Session archives ----
+
+    /// Completed sessions that ended before `cutoff` and are not archived
+    /// yet, oldest first.
+    pub async fn sessions_to_archive(&self, cutoff: DateTime<Utc>) -> Result<Vec<String>> {
+        let ids = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT s.id FROM roast_sessions s
+            WHERE s.status = ? AND COALESCE(s.end_time, s.updated_at) < ?
+              AND NOT EXISTS (SELECT 1 FROM session_archives a WHERE a.session_id = s.id)
+            ORDER BY COALESCE(s.end_time, s.updated_at)
+            "#,
+        )
+        .bind(SessionStatus::Completed.to_string())
+        .bind(cutoff)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(ids)
+    }
+
+    /// The session with its events and telemetry, as written to an archive.
+    pub async fn session_archive_bundle(&self, id: &str) -> Result<Option<SessionArchiveBundle>> {
+        let Some(session) = self.get_session(id).await? else {
+            return Ok(None);
+        };
+        Ok(Some(SessionArchiveBundle {
+            format_version: 1,
+            events: self.get_roast_events(id).await?,
+            telemetry: self.get_session_telemetry(id).await?,
+            session,
+        }))
+    }
+
+    pub async fn get_session_archive(&self, session_id: &str) -> Result<Option<SessionArchive>> {
+        let archive = sqlx::query_as::<_, SessionArchive>(
+            "SELECT * FROM session_archives WHERE session_id = ?",
+        )
+        .bind(session_id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(archive)
+    }
+
+    /// Record (or, when archived again, replace) a session's archive.
+    pub async fn record_session_archive(
+        &self,
+        session_id: &str,
+        object_prefix: &str,
+        telemetry_points: i64,
+    ) -> Result<SessionArchive> {
+        let archive = sqlx::query_as::<_, SessionArchive>(
+            r#"
+            INSERT OR REPLACE INTO session_archives
+                (session_id, object_prefix, telemetry_points, archived_at, pruned_at, restored_at)
+            VALUES (?, ?, ?, ?, NULL, NULL)
+            RETURNING *
+            "#,
+        )
+        .bind(session_id)
+        .bind(object_prefix)
+        .bind(telemetry_points)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+        Ok(archive)
+    }
+
+    /// Delete an archived session's local telemetry. Returns the updated
+    /// archive, `None` when the session is not archived.
+    pub async fn prune_session_telemetry(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<SessionArchive>> {
+        let mut tx = self.db.begin().await?;
+        let archive = sqlx::query_as::<_, SessionArchive>(
+            "UPDATE session_archives SET pruned_at = ? WHERE session_id = ? RETURNING *",
+        )
+        .bind(Utc::now())
+        .bind(session_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if archive.is_some() {
+            sqlx::query("DELETE FROM session_telemetry WHERE session_id = ?")
+                .bind(session_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(archive)
+    }
+
+    /// Put an archived session's telemetry back, keeping rows that are
+    /// still there, and mark the archive restored.
+    pub async fn restore_session_telemetry(
+        &self,
+        session_id: &str,
+        telemetry: &[SessionTelemetry],
+    ) -> Result<Option<SessionArchive>> {
+        let mut tx = self.db.begin().await?;
+        for point in telemetry {
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO session_telemetry (
+                    id, session_id, timestamp, elapsed_seconds, bean_temp, env_temp,
+                    rate_of_rise, heater_pwm, fan_pwm, setpoint, airflow
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&point.id)
+            .bind(session_id)
+            .bind(point.timestamp)
+            .bind(point.elapsed_seconds)
+            .bind(point.bean_temp)
+            .bind(point.env_temp)
+            .bind(point.rate_of_rise)
+            .bind(point.heater_pwm)
+            .bind(point.fan_pwm)
+            .bind(point.setpoint)
+            .bind(point.airflow)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let archive = sqlx::query_as::<_, SessionArchive>(
+            "UPDATE session_archives SET pruned_at = NULL, restored_at = ? WHERE session_id = ? RETURNING *",
+        )
+        .bind(Utc::now())
+        .bind(session_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(archive)
+    }
+
+    /// Insert a session from another instance (or an earlier life of this
+    /// one) with its original ids, events, telemetry and cupping. Links to
+    /// a profile, bean lot or sample group that doesn't exist here are
+    /// dropped and named in the result. `None` when the session exists.
+    pub async fn import_session_bundle(
+        &self,
+        bundle: &SessionArchiveBundle,
+        cupping: Option<&CuppingWithAttributes>,
+    ) -> Result<Option<(RoastSession, Vec<&'static str>)>> {
+        let s = &bundle.session;
+        let mut tx = self.db.begin().await?;
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM roast_sessions WHERE id = ?")
+            .bind(&s.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if exists.is_some() {
+            return Ok(None);
+        }
+        let mut unlinked = Vec::new();
+        let mut links = Vec::new();
+        for (table, id, name) in [
+            ("roast_profiles", &s.profile_id, "profile_id"),
+            ("beans", &s.bean_id, "bean_id"),
+            ("sample_groups", &s.sample_group_id, "sample_group_id"),
+        ] {
+            let mut id = id.clone();
+            if let Some(linked) = &id {
+                let found: Option<i64> =
+                    sqlx::query_scalar(&format!("SELECT 1 FROM {} WHERE id = ?", table))
+                        .bind(linked)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                if found.is_none() {
+                    unlinked.push(name);
+                    id = None;
+                }
+            }
+            links.push(id);
+        }
+        let [profile_id, bean_id, sample_group_id] =
+            <[Option<String>; 3]>::try_from(links).map_err(|_| anyhow!("unexpected link count"))?;
+
+        let session = sqlx::query_as::<_, RoastSession>(
+            r#"
+            INSERT INTO roast_sessions (
+                id, name, device_id, profile_id, bean_id, session_type, sample_group_id,
+                status, start_time, end_time, created_at, updated_at,
+                bean_origin, bean_variety, green_weight, roasted_weight, target_roast_level,
+                notes, ambient_temp, humidity,
+                max_temp, total_time_seconds, first_crack_time, development_time_ratio,
+                weight_loss_pct, max_ror, avg_ror_drying, avg_ror_maillard, avg_ror_development,
+                drying_end_time, drying_end_temp, auc_value,
+                roaster, signed_off_by, signed_off_at,
+                profile_fc_delta, profile_drop_time_delta, profile_drop_temp_delta,
+                profile_deviation_integral
+            ) VALUES (
+                ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+                ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(&s.id)
+        .bind(&s.name)
+        .bind(&s.device_id)
+        .bind(&profile_id)
+        .bind(&bean_id)
+        .bind(s.session_type)
+        .bind(&sample_group_id)
+        .bind(s.status.to_string())
+        .bind(s.start_time)
+        .bind(s.end_time)
+        .bind(s.created_at)
+        .bind(s.updated_at)
+        .bind(&s.bean_origin)
+        .bind(&s.bean_variety)
+        .bind(s.green_weight)
+        .bind(s.roasted_weight)
+        .bind(&s.target_roast_level)
+        .bind(&s.notes)
+        .bind(s.ambient_temp)
+        .bind(s.humidity)
+        .bind(s.max_temp)
+        .bind(s.total_time_seconds)
+        .bind(s.first_crack_time)
+        .bind(s.development_time_ratio)
+        .bind(s.weight_loss_pct)
+        .bind(s.max_ror)
+        .bind(s.avg_ror_drying)
+        .bind(s.avg_ror_maillard)
+        .bind(s.avg_ror_development)
+        .bind(s.drying_end_time)
+        .bind(s.drying_end_temp)
+        .bind(s.auc_value)
+        .bind(&s.roaster)
+        .bind(&s.signed_off_by)
+        .bind(s.signed_off_at)
+        .bind(s.profile_fc_delta)
+        .bind(s.profile_drop_time_delta)
+        .bind(s.profile_drop_temp_delta)
+        .bind(s.profile_deviation_integral)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for event in &bundle.events {
+            sqlx::query(
+                r#"
+                INSERT INTO roast_events (id, session_id, event_type, elapsed_seconds, temperature, notes, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&event.id)
+            .bind(&s.id)
+            .bind(event.event_type.to_string())
+            .bind(event.elapsed_seconds)
+            .bind(event.temperature)
+            .bind(&event.notes)
+            .bind(event.created_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        for point in &bundle.telemetry {
+            sqlx::query(
+                r#"
+                INSERT INTO session_telemetry (
+                    id, session_id, timestamp, elapsed_seconds, bean_temp, env_temp,
+                    rate_of_rise, heater_pwm, fan_pwm, setpoint, airflow
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&point.id)
+            .bind(&s.id)
+            .bind(point.timestamp)
+            .bind(point.elapsed_seconds)
+            .bind(point.bean_temp)
+            .bind(point.env_temp)
+            .bind(point.rate_of_rise)
+            .bind(point.heater_pwm)
+            .bind(point.fan_pwm)
+            .bind(point.setpoint)
+            .bind(point.airflow)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if let Some(c) = cupping {
+            sqlx::query(
+                r#"
+                INSERT INTO cupping_scores (id, session_id, scoring_framework, overall_score, notes, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&c.cupping.id)
+            .bind(&s.id)
+            .bind(&c.cupping.scoring_framework)
+            .bind(c.cupping.overall_score)
+            .bind(&c.cupping.notes)
+            .bind(c.cupping.created_at)
+            .bind(c.cupping.updated_at)
+            .execute(&mut *tx)
+            .await?;
+            for attr in &c.attributes {
+                sqlx::query(
+                    r#"
+                    INSERT INTO cupping_attributes (id, cupping_id, attribute_name, score, created_at)
+                    VALUES (?, ?, ?, ?, ?)
+                    "#,
+                )
+                .bind(&attr.id)
+                .bind(&c.cupping.id)
+                .bind(&attr.attribute_name)
+                .bind(attr.score)
+                .bind(attr.created_at)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+
+        self.journal(SyncEntity::Session, &session.id, SyncOp::Upsert)
+            .await?;
+        if cupping.is_some() {
+            self.journal(SyncEntity::Cupping, &session.id, SyncOp::Upsert)
+                .await?;
+        }
+        Ok(Some((session, unlinked)))
+    }
+
+    // ---- Session costs ----
+
+    /// Cost rates from the `cost_*` settings, defaults for any unset.
+    pub async fn cost_rates(&self) -> Result<CostRates> {
+        let settings = sqlx::query_as::<_, (String, String)>(
+            "SELECT key, value FROM settings WHERE key IN ('cost_currency', 'cost_heater_watts', 'cost_energy_per_kwh', 'cost_labor_per_hour', 'cost_overhead_per_batch')",
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let mut rates = CostRates::default();
+        for (key, value) in settings {
+            let amount = value
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite() && *v >= 0.0);
+            match (key.as_str(), amount) {
+                ("cost_currency", _) if !value.is_empty() => rates.currency = value,
+                ("cost_heater_watts", Some(v)) => rates.heater_watts = v,
+                ("cost_energy_per_kwh", Some(v)) => rates.energy_per_kwh = v,
+                ("cost_labor_per_hour", Some(v)) => rates.labor_per_hour = v,
+                ("cost_overhead_per_batch", Some(v)) => rates.overhead_per_batch = v,
+                _ => {}
+            }
+        }
+        Ok(rates)
+    }
+
+    async fn session_cost(
+        &self,
+        session: &RoastSession,
+        telemetry: &[SessionTelemetry],
+        rates: &CostRates,
+    ) -> Result<SessionCost> {
+        let bean_cost_per_kg = match &session.bean_id {
+            Some(bean_id) => self.get_bean(bean_id).await?.and_then(|b| b.cost_per_kg),
+            None => None,
+        };
+        Ok(compute_session_cost(
+            session,
+            bean_cost_per_kg,
+            telemetry,
+            rates,
+        ))
+    }
+
+    pub async fn get_session_cost(&self, id: &str) -> Result<Option<SessionCost>> {
+        let Some(session) = self.get_session(id).await? else {
+            return Ok(None);
+        };
+        let telemetry = self.get_session_telemetry(id).await?;
+        let rates = self.cost_rates().await?;
+        Ok(Some(self.session_cost(&session, &telemetry, &rates).await?))
+    }
+
+    /// Costs of the sessions completed that started on `date` (UTC).
+    pub async fn daily_report(&self, date: NaiveDate) -> Result<DailyReport> {
+        let sessions = sqlx::query_as::<_, RoastSession>(
+            r#"
+            SELECT * FROM roast_sessions
+            WHERE status = ? AND date(COALESCE(start_time, created_at)) = ?
+            ORDER BY COALESCE(start_time, created_at)
+            "#,
+        )
+        .bind(SessionStatus::Completed.to_string())
+        .bind(date.to_string())
+        .fetch_all(&self.read_db)
+        .await?;
+
+        let rates = self.cost_rates().await?;
+        let mut costs = Vec::with_capacity(sessions.len());
+        for session in &sessions {
+            let telemetry = self.get_session_telemetry(&session.id).await?;
+            costs.push(self.session_cost(session, &telemetry, &rates).await?);
+        }
+        let cents = |v: f64| (v * 100.0).round() / 100.0;
+        let (roasted_cost, roasted_kg) = costs
+            .iter()
+            .filter_map(|c| c.roasted_kg.map(|kg| (c.total_cost, kg)))
+            .fold((0.0, 0.0), |(cost, kg), (c, k)| (cost + c, kg + k));
+        Ok(DailyReport {
+            date,
+            currency: rates.currency,
+            session_count: costs.len(),
+            green_kg: costs
+                .iter()
+                .filter_map(|c| c.green_kg)
+                .fold(0.0, |a, b| a + b),
+            roasted_kg,
+            total_cost: cents(costs.iter().fold(0.0, |a, c| a + c.total_cost)),
+            cost_per_roasted_kg: (roasted_kg > 0.0).then(|| cents(roasted_cost / roasted_kg)),
+            sessions: costs,
+        })
+    }
+
+    /// Rank profiles by their completed sessions on beans similar to `bean`.
+    ///
+    /// A session counts when its bean (the linked lot, else the session's
+    /// origin/variety text) is at least [`MIN_BEAN_SIMILARITY`] similar.
+    /// Per profile, the score is the mean similarity times the outcome
+    /// (similarity-weighted cupping score out of 10, 0.5 while uncupped)
+    /// times a confidence of n/(n+2) for n sessions, so a profile proven on
+    /// several close beans beats a single lucky roast.
+    pub async fn recommend_profiles(
+        &self,
+        bean: &BeanAttributes,
+        limit: usize,
+    ) -> Result<Vec<ProfileRecommendation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT s.profile_id, p.name AS profile_name,
+                COALESCE(b.origin, s.bean_origin) AS origin,
+                COALESCE(b.variety, s.bean_variety) AS variety,
+                b.process, b.density,
+                s.development_time_ratio, s.weight_loss_pct,
+                COALESCE(s.end_time, s.start_time, s.created_at) AS roasted_at,
+                (SELECT AVG(c.overall_score) FROM cupping_scores c
+                    WHERE c.session_id = s.id AND c.overall_score IS NOT NULL) AS cupping_score
+            FROM roast_sessions s
+            JOIN roast_profiles p ON p.id = s.profile_id
+            LEFT JOIN beans b ON b.id = s.bean_id
+            WHERE s.status = ?
+            "#,
+        )
+        .bind(SessionStatus::Completed.to_string())
+        .fetch_all(&self.read_db)
+        .await?;
+
+        #[derive(Default)]
+        struct Acc {
+            name: String,
+            n: i64,
+            similarity: f64,
+            cupped: i64,
+            cupping: (f64, f64),
+            dtr: (f64, f64),
+            loss: (f64, f64),
+            last: Option<DateTime<Utc>>,
+        }
+        fn add(acc: &mut (f64, f64), weight: f64, value: Option<f64>) {
+            if let Some(v) = value {
+                acc.0 += weight * v;
+                acc.1 += weight;
+            }
+        }
+        fn mean(acc: (f64, f64)) -> Option<f64> {
+            (acc.1 > 0.0).then(|| acc.0 / acc.1)
+        }
+
+        let mut profiles: HashMap<String, Acc> = HashMap::new();
+        for row in rows {
+            let session_bean = BeanAttributes {
+                origin: row.try_get("origin")?,
+                variety: row.try_get("variety")?,
+                process: row.try_get("process")?,
+                density: row.try_get("density")?,
+            };
+            let similarity = bean.similarity(&session_bean);
+            if similarity < MIN_BEAN_SIMILARITY {
+                continue;
+            }
+            let acc = profiles.entry(row.try_get("profile_id")?).or_default();
+            acc.name = row.try_get("profile_name")?;
+            acc.n += 1;
+            acc.similarity += similarity;
+            let cupping: Option<f64> = row.try_get("cupping_score")?;
+            if cupping.is_some() {
+                acc.cupped += 1;
+            }
+            add(&mut acc.cupping, similarity, cupping);
+            add(
+                &mut acc.dtr,
+                similarity,
+                row.try_get("development_time_ratio")?,
+            );
+            add(&mut acc.loss, similarity, row.try_get("weight_loss_pct")?);
+            let roasted_at: Option<DateTime<Utc>> = row.try_get("roasted_at")?;
+            acc.last = acc.last.max(roasted_at);
+        }
+
+        let mut recommendations: Vec<ProfileRecommendation> = profiles
+            .into_iter()
+            .map(|(profile_id, acc)| {
+                let n = acc.n as f64;
+                let similarity = acc.similarity / n;
+                let avg_cupping_score = mean(acc.cupping);
+                let outcome = avg_cupping_score.map_or(0.5, |c| (c / 10.0).clamp(0.0, 1.0));
+                ProfileRecommendation {
+                    profile_id,
+                    profile_name: acc.name,
+                    score: similarity * outcome * n / (n + 2.0),
+                    similar_sessions: acc.n,
+                    similarity,
+                    avg_cupping_score,
+                    cupped_sessions: acc.cupped,
+                    avg_development_time_ratio: mean(acc.dtr),
+                    avg_weight_loss_pct: mean(acc.loss),
+                    last_roasted_at: acc.last,
+                }
+            })
+            .collect();
+        recommendations.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(b.last_roasted_at.cmp(&a.last_roasted_at))
+        });
+        recommendations.truncate(limit);
+        Ok(recommendations)
+    }
+
+    // ---- Data Export (AP-014) ----
+
+    pub async fn export_csv(
+        &self,
+        id: &str,
+        opts: CsvExportOptions,
+        locale: Locale,
+    ) -> Result<Option<(String, String)>> {
+        let session = match self.get_session(id).await? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let telemetry = self.get_session_telemetry(id).await?;
+        let profile_name = if let Some(pid) = &session.profile_id {
+            self.get_profile_with_points(pid)
+                .await?
+                .map(|p| p.profile.name)
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        let units = match opts.units {
+            Some(u) => Some(u),
+            None => self
+                .get_setting("export_temperature_unit")
+                .await?
+                .and_then(|v| v.parse().ok()),
+        };
+        let decimals = match opts.decimals {
+            Some(d) => Some(d.min(6) as usize),
+            None => self
+                .get_setting("export_decimal_places")
+                .await?
+                .and_then(|v| v.parse::<usize>().ok())
+                .map(|d| d.min(6)),
+        };
+        let num = |v: Option<f32>| match (v, decimals) {
+            (Some(v), Some(d)) => format!("{:.*}", d, v),
+            (Some(v), None) => v.to_string(),
+            (None, _) => String::new(),
+        };
+
+        let mut csv = String::new();
+        csv.push_str(&format!("# {}: {}\n", locale.t("session"), session.name));
+        if let Some(ref st) = session.start_time {
+            csv.push_str(&format!("# {}: {}\n", locale.t("date"), st));
+        }
+        if let Some(ref origin) = session.bean_origin {
+            let variety = session.bean_variety.as_deref().unwrap_or("");
+            if variety.is_empty() {
+                csv.push_str(&format!("# {}: {}\n", locale.t("bean"), origin));
+            } else {
+                csv.push_str(&format!(
+                    "# {}: {} ({})\n",
+                    locale.t("bean"),
+                    origin,
+                    variety
+                ));
+            }
+        }
+        if !profile_name.is_empty() {
+            csv.push_str(&format!("# {}: {}\n", locale.t("profile"), profile_name));
+        }
+        if let Some(gw) = session.green_weight {
+            csv.push_str(&format!("# {}: {}g\n", locale.t("green_weight"), gw));
+        }
+        if let Some(rw) = session.roasted_weight {
+            csv.push_str(&format!("# {}: {}g\n", locale.t("roasted_weight"), rw));
+        }
+        if let Some(roaster) = &session.roaster {
+            csv.push_str(&format!("# {}: {}\n", locale.t("roaster"), roaster));
+        }
+
+        // Without a configured unit the export stays in stored Celsius and
+        // carries no `# Unit` line, matching the pre-policy output.
+        if let Some(units) = units {
+            csv.push_str(&format!("# {}: {}\n", locale.t("unit"), units.as_str()));
+        }
+        let units = units.unwrap_or(TemperatureUnit::C);
+        for event in self.get_roast_events(id).await? {
+            let secs = event.elapsed_seconds.max(0.0).round() as u32;
+            let mut line = format!(
+                "# {}: {} @ {}:{:02}",
+                locale.t("event"),
+                locale.event_name(&event.event_type),
+                secs / 60,
+                secs % 60
+            );
+            if let Some(temp) = event.temperature {
+                line.push_str(&format!(", {}", num(Some(units.convert(temp)))));
+            }
+            csv.push_str(&line);
+            csv.push('\n');
+        }
+
+        let columns: [&str; 7] = match opts.preset {
+            ExportColumnPreset::Default => [
+                match opts.timestamp {
+                    ExportTimestamp::Seconds => "elapsed_seconds",
+                    _ => "time",
+                },
+                "bean_temp",
+                "env_temp",
+                "rate_of_rise",
+                "heater_pwm",
+                "fan_pwm",
+                "setpoint",
+            ],
+            ExportColumnPreset::Artisan => ["Time", "BT", "ET", "DeltaBT", "Burner", "Fan", "SV"],
+            ExportColumnPreset::Cropster => [
+                "Time",
+                "Bean temperature",
+                "Exhaust temperature",
+                "Rate of rise",
+                "Gas",
+                "Airflow",
+                "Setpoint",
+            ],
+        };
+        csv.push_str(&columns.join(","));
+        csv.push('\n');
+
+        for t in &telemetry {
+            let time = match opts.timestamp {
+                ExportTimestamp::Seconds => num(Some(t.elapsed_seconds)),
+                ExportTimestamp::Mmss => {
+                    let secs = t.elapsed_seconds.max(0.0).round() as u32;
+                    format!("{}:{:02}", secs / 60, secs % 60)
+                }
+                ExportTimestamp::Iso8601 => t.timestamp.to_rfc3339(),
+                ExportTimestamp::Epoch => t.timestamp.timestamp().to_string(),
+            };
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                time,
+                num(t.bean_temp.map(|v| units.convert(v))),
+                num(t.env_temp.map(|v| units.convert(v))),
+                num(t.rate_of_rise.map(|v| units.convert_delta(v))),
+                t.heater_pwm.map(|v| v.to_string()).unwrap_or_default(),
+                t.fan_pwm.map(|v| v.to_string()).unwrap_or_default(),
+                num(t.setpoint.map(|v| units.convert(v))),
+            ));
+        }
+
+        let date_str = session
+            .start_time
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| session.created_at.format("%Y-%m-%d").to_string());
+        let filename = format!("{}_{}.csv", session.name.replace(' ', "_"), date_str);
+
+        Ok(Some((csv, filename)))
+    }
+
+    async fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let value = sqlx::query_scalar::<_, String>("SELECT value FROM settings WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(value.filter(|v| !v.is_empty()))
+    }
+
+    pub async fn export_artisan_json(
+        &self,
+        id: &str,
+    ) -> Result<Option<(serde_json::Value, String)>> {
+        let session = match self.get_session(id).await? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let telemetry = self.get_session_telemetry(id).await?;
+        let events = self.get_roast_events(id).await?;
+
+        let timex: Vec<f64> = telemetry.iter().map(|t| t.elapsed_seconds as f64).collect();
+        let temp1: Vec<f64> = telemetry
+            .iter()
+            .map(|t| t.env_temp.unwrap_or(-1.0) as f64)
+            .collect();
+        let temp2: Vec<f64> = telemetry
+            .iter()
+            .map(|t| t.bean_temp.unwrap_or(-1.0) as f64)
+            .collect();
+
+        // Build timeindex: [CHARGE, DRY, FCs, FCe, SCs, SCe, DROP, COOL]
+        // CHARGE is handled separately (always index 0), so event_map starts at DRY
+        let event_map = [
+            "drying_end",         // DRY
+            "first_crack_start",  // FCs
+            "first_crack_end",    // FCe
+            "second_crack_start", // SCs
+            "second_crack_end",   // SCe
+            "drop_out",           // DROP
+        ];
+
+        // CHARGE is index 0 in telemetry (first reading)
+        let charge_idx: i64 = if !timex.is_empty() { 0 } else { -1 };
+
+        let mut timeindex: Vec<i64> = vec![charge_idx];
+        for event_type in &event_map {
+            let idx = events
+                .iter()
+                .find(|e| e.event_type.to_string() == *event_type)
+                .and_then(|e| {
+                    // Find nearest telemetry index
+                    timex
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, &t)| {
+                            ((t - e.elapsed_seconds as f64) * 1000.0).abs() as i64
+                        })
+                        .map(|(i, _)| i as i64)
+                })
+                .unwrap_or(-1);
+            timeindex.push(idx);
+        }
+        // COOL (index 7) — not tracked, use -1
+        timeindex.push(-1);
+
+        // Build specialevents
+        let specialevents: Vec<serde_json::Value> = events
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "type": e.event_type.to_string(),
+                    "time": e.elapsed_seconds,
+                    "temperature": e.temperature,
+                    "notes": e.notes,
+                })
+            })
+            .collect();
+
+        let roastdate = session
+            .start_time
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_else(|| session.created_at.to_rfc3339());
+
+        let weight: Vec<serde_json::Value> = vec![
+            serde_json::json!(session.green_weight.unwrap_or(0.0)),
+            serde_json::json!(session.roasted_weight.unwrap_or(0.0)),
+            serde_json::json!("g"),
+        ];
+
+        let alog = serde_json::json!({
+            "version": "2",
+            "title": session.name,
+            "roastdate": roastdate,
+            "beans": session.bean_origin.unwrap_or_default(),
+            "weight": weight,
+            "timex": timex,
+            "temp1": temp1,
+            "temp2": temp2,
+            "timeindex": timeindex,
+            "specialevents": specialevents,
+        });
+
+        let date_str = session
+            .start_time
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| session.created_at.format("%Y-%m-%d").to_string());
+        let filename = format!("{}_{}.alog", session.name.replace(' ', "_"), date_str);
+
+        Ok(Some((alog, filename)))
+    }
+}
+
+/// Linearly interpolate airflow for a fan PWM from a calibration curve sorted by
+/// `fan_pwm`. Values outside the calibrated range are clamped to the end points.
+pub fn interpolate_airflow(points: &[FanCalibrationPoint], fan_pwm: i32) -> Option<f64> {
+    let first = points.first()?;
+    let last = points.last()?;
+    if fan_pwm <= first.fan_pwm {
+        return Some(first.airflow);
+    }
+    if fan_pwm >= last.fan_pwm {
+        return Some(last.airflow);
+    }
+    points.windows(2).find_map(|w| {
+        let (lo, hi) = (&w[0], &w[1]);
+        if fan_pwm < lo.fan_pwm || fan_pwm > hi.fan_pwm || hi.fan_pwm == lo.fan_pwm {
+            return None;
+        }
+        let t = (fan_pwm - lo.fan_pwm) as f64 / (hi.fan_pwm - lo.fan_pwm) as f64;
+        Some(lo.airflow + t * (hi.airflow - lo.airflow))
+    })
+}
+
+/// Profile target temperature at `t` seconds, interpolated between points sorted
+/// by `time_seconds` and clamped to the end points.
+pub fn profile_target_temp(points: &[ProfilePoint], t: f32) -> Option<f32> {
+    let first = points.first()?;
+    let last = points.last()?;
+    if t <= first.time_seconds as f32 {
+        return Some(first.target_temp);
+    }
+    if t >= last.time_seconds as f32 {
+        return Some(last.target_temp);
+    }
+    points.windows(2).find_map(|w| {
+        let (lo, hi) = (&w[0], &w[1]);
+        let (t0, t1) = (lo.time_seconds as f32, hi.time_seconds as f32);
+        if t < t0 || t > t1 || t1 == t0 {
+            return None;
+        }
+        let f = (t - t0) / (t1 - t0);
+        Some(lo.target_temp + f * (hi.target_temp - lo.target_temp))
+    })
+}
+
+/// Elapsed seconds of a session at which its profile's time zero lies: the
+/// charge event plus `charge_offset_secs`, so a session started well before
+/// charging is still compared against the right part of the curve. Without
+/// a charge event the profile starts with the session.
+pub fn profile_zero(events: &[RoastEvent], charge_offset_secs: f32) -> f32 {
+    events
+        .iter()
+        .find(|e| e.event_type == RoastEventType::Charge)
+        .map_or(0.0, |e| (e.elapsed_seconds + charge_offset_secs).max(0.0))
+}
+
+/// Window over which the scoreboard estimates the current RoR, unless the
+/// roaster has RoR settings.
+const SCOREBOARD_ROR_WINDOW_SECS: f32 = 30.0;
+
+/// Phase and next prompt for a manual roast. Phases advance on the marked
+/// events: drying until Drying End, Maillard until First Crack Start, then
+/// development until Drop Out.
+pub fn manual_roast_status(
+    session: &RoastSession,
+    events: &[RoastEvent],
+    now: DateTime<Utc>,
+) -> ManualRoastStatus {
+    let find = |t: RoastEventType| events.iter().find(|e| e.event_type == t);
+    let last_note = |t: RoastEventType| {
+        events
+            .iter()
+            .rev()
+            .find(|e| e.event_type == t)
+            .and_then(|e| e.notes.clone())
+    };
+    let elapsed = session
+        .start_time
+        .map(|start| (session.end_time.unwrap_or(now) - start).num_milliseconds() as f32 / 1000.0);
+    let first_crack = find(RoastEventType::FirstCrackStart);
+    let drop_out = find(RoastEventType::DropOut);
+
+    let phase = match session.status {
+        SessionStatus::Planning => ManualPhase::Preheat,
+        SessionStatus::Active | SessionStatus::Paused => {
+            if drop_out.is_some() {
+                ManualPhase::Finished
+            } else if first_crack.is_some() {
+                ManualPhase::Development
+            } else if find(RoastEventType::DryingEnd).is_some() {
+                ManualPhase::Maillard
+            } else {
+                ManualPhase::Drying
+            }
+        }
+        SessionStatus::Completed | SessionStatus::Failed | SessionStatus::Cancelled => {
+            ManualPhase::Finished
+        }
+    };
+
+    // Development runs from first crack to drop out (or now while roasting)
+    let roast_end = drop_out.map(|e| e.elapsed_seconds).or(elapsed);
+    let (development_seconds, development_ratio) = match (first_crack, roast_end) {
+        (Some(fc), Some(end)) if end > fc.elapsed_seconds => {
+            let dev = end - fc.elapsed_seconds;
+            (Some(dev), Some(dev / end))
+        }
+        _ => (None, None),
+    };
+
+    let prompt = match phase {
+        ManualPhase::Preheat => {
+            Some("Preheat the roaster, then start the session when you charge the beans.")
+        }
+        ManualPhase::Drying => Some("Mark Drying End when the beans turn yellow."),
+        ManualPhase::Maillard => Some("Listen for first crack and mark it when it starts."),
+        ManualPhase::Development => {
+            Some("Mark Drop Out when the beans reach your target roast level.")
+        }
+        ManualPhase::Finished => None,
+    };
+
+    ManualRoastStatus {
+        session_id: session.id.clone(),
+        phase,
+        elapsed_seconds: elapsed,
+        development_seconds,
+        development_ratio,
+        prompt: prompt.map(str::to_string),
+        heater_pwm: last_note(RoastEventType::HeaterChange),
+        fan_pwm: last_note(RoastEventType::FanChange),
+    }
+}
+
+/// Downsample telemetry to at most `max_points` samples with
+/// Largest-Triangle-Three-Buckets on the bean temperature (env temp when
+/// there is none). The first and last samples are always kept.
+pub fn decimate_telemetry(
+    points: Vec<SessionTelemetry>,
+    max_points: usize,
+) -> Vec<SessionTelemetry> {
+    let n = points.len();
+    if max_points < 3 || n <= max_points {
+        return points;
+    }
+    let x = |i: usize| points[i].elapsed_seconds as f64;
+    let y = |i: usize| {
+        let p = &points[i];
+        p.bean_temp.or(p.env_temp).unwrap_or(0.0) as f64
+    };
+    // Interior points split into max_points - 2 buckets, the final sample
+    // acting as one more bucket of its own
+    let buckets = max_points - 2;
+    let bucket_size = (n - 2) as f64 / buckets as f64;
+    let bucket = |b: usize| {
+        if b >= buckets {
+            return n - 1..n;
+        }
+        let start = (b as f64 * bucket_size) as usize + 1;
+        let end = if b + 1 == buckets {
+            n - 1
+        } else {
+            ((b + 1) as f64 * bucket_size) as usize + 1
+        };
+        start..end
+    };
+
+    let mut keep = Vec::with_capacity(max_points);
+    keep.push(0);
+    let mut a = 0;
+    for b in 0..buckets {
+        let next = bucket(b + 1);
+        let len = next.len() as f64;
+        let avg_x = next.clone().map(x).sum::<f64>() / len;
+        let avg_y = next.map(y).sum::<f64>() / len;
+
+        let (ax, ay) = (x(a), y(a));
+        let current = bucket(b);
+        let mut best = current.start;
+        let mut best_area = -1.0;
+        for j in current {
+            let area = ((ax - avg_x) * (y(j) - ay) - (ax - x(j)) * (avg_y - ay)).abs();
+            if area > best_area {
+                best_area = area;
+                best = j;
+            }
+        }
+        keep.push(best);
+        a = best;
+    }
+    keep.push(n - 1);
+
+    let mut keep = keep.into_iter().peekable();
+    points
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, p)| {
+            if keep.peek() == Some(&i) {
+                keep.next();
+                Some(p)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Longest gap between telemetry samples counted towards heater energy, so
+/// pauses and dropouts don't bill the last heater level for their length.
+const MAX_ENERGY_SAMPLE_GAP_SECS: f64 = 5.0;
+
+/// Cost of a roast: the green beans at the lot's price, heater energy from
+/// the telemetry's heater PWM at `heater_watts` (each sample holding until
+/// the next), labor for the roast time and a fixed overhead per batch.
+/// Money is rounded to cents.
+pub fn compute_session_cost(
+    session: &RoastSession,
+    bean_cost_per_kg: Option<f64>,
+    telemetry: &[SessionTelemetry],
+    rates: &CostRates,
+) -> SessionCost {
+    let cents = |v: f64| (v * 100.0).round() / 100.0;
+    let green_kg = session.green_weight.map(|g| g as f64 / 1000.0);
+    let roasted_kg = session
+        .roasted_weight
+        .map(|g| g as f64 / 1000.0)
+        .filter(|kg| *kg > 0.0);
+    let bean_cost = green_kg.zip(bean_cost_per_kg).map(|(kg, price)| kg * price);
+
+    let watt_seconds: f64 = telemetry
+        .windows(2)
+        .map(|w| {
+            let dt = (w[1].elapsed_seconds - w[0].elapsed_seconds) as f64;
+            let pwm = w[0].heater_pwm.unwrap_or(0).clamp(0, 100) as f64;
+            pwm / 100.0 * rates.heater_watts * dt.clamp(0.0, MAX_ENERGY_SAMPLE_GAP_SECS)
+        })
+        .sum();
+    let energy_kwh = watt_seconds / 3_600_000.0;
+
+    let roast_secs = session
+        .total_time_seconds
+        .map(|s| s as f64)
+        .or_else(|| {
+            session
+                .start_time
+                .zip(session.end_time)
+                .map(|(start, end)| (end - start).num_milliseconds() as f64 / 1000.0)
+        })
+        .or_else(|| telemetry.last().map(|p| p.elapsed_seconds as f64))
+        .unwrap_or(0.0)
+        .max(0.0);
+    let roast_hours = roast_secs / 3600.0;
+
+    let energy_cost = energy_kwh * rates.energy_per_kwh;
+    let labor_cost = roast_hours * rates.labor_per_hour;
+    let total_cost = bean_cost.unwrap_or(0.0) + energy_cost + labor_cost + rates.overhead_per_batch;
+    SessionCost {
+        session_id: session.id.clone(),
+        currency: rates.currency.clone(),
+        green_kg,
+        roasted_kg,
+        bean_cost: bean_cost.map(cents),
+        energy_kwh: (energy_kwh * 1000.0).round() / 1000.0,
+        energy_cost: cents(energy_cost),
+        roast_hours: (roast_hours * 1000.0).round() / 1000.0,
+        labor_cost: cents(labor_cost),
+        overhead_cost: cents(rates.overhead_per_batch),
+        total_cost: cents(total_cost),
+        cost_per_roasted_kg: roasted_kg.map(|kg| cents(total_cost / kg)),
+    }
+}
+
+/// Compare a roast against its profile and its interpolated `curve`.
+/// Projections extrapolate the bean temp linearly at the RoR of the last
+/// `ror_window_secs`. With `finished` set, the last sample stands in for an
+/// unmarked drop and first crack is no longer projected.
+pub fn compute_profile_scoreboard(
+    profile: &ProfileWithPoints,
+    curve: &ProfileCurve,
+    telemetry: &[SessionTelemetry],
+    events: &[RoastEvent],
+    finished: bool,
+    ror_window_secs: f32,
+    charge_offset_secs: f32,
+) -> Option<ProfileScoreboard> {
+    let points = &profile.points;
+    let find_event = |kind: RoastEventType| events.iter().find(|e| e.event_type == kind);
+    let drop_event = find_event(RoastEventType::Drop);
+    let zero = profile_zero(events, charge_offset_secs);
+
+    // Cooling after the drop isn't part of the roast
+    let samples: Vec<(f32, f32)> = telemetry
+        .iter()
+        .filter(|t| drop_event.is_none_or(|d| t.elapsed_seconds <= d.elapsed_seconds))
+        .filter_map(|t| Some((t.elapsed_seconds, t.bean_temp?)))
+        .collect();
+    let &(now, bean_temp) = samples.last()?;
+    let target_at = |t: f32| curve.target_at(t - zero);
+    let target_temp = target_at(now);
+
+    // Nor is anything before the profile starts
+    let first = samples.partition_point(|(t, _)| *t < zero);
+    let (mut deviation_integral, mut abs_deviation_integral) = (0.0, 0.0);
+    for w in samples[first..].windows(2) {
+        let ((t0, b0), (t1, b1)) = (w[0], w[1]);
+        let d0 = b0 - target_at(t0);
+        let d1 = b1 - target_at(t1);
+        let dt_min = (t1 - t0) / 60.0;
+        deviation_integral += (d0 + d1) / 2.0 * dt_min;
+        abs_deviation_integral += (d0.abs() + d1.abs()) / 2.0 * dt_min;
+    }
+
+    let ror = samples
+        .iter()
+        .find(|(t, _)| *t >= now - ror_window_secs)
+        .filter(|(t, _)| now - t >= ror_window_secs.min(5.0))
+        .map(|&(t, b)| (bean_temp - b) / (now - t) * 60.0);
+    // Time at which the bean temp reaches `temp` at the current RoR
+    let time_to_reach = |temp: f32| {
+        if bean_temp >= temp {
+            Some(now)
+        } else {
+            ror.filter(|r| *r > 0.0)
+                .map(|r| now + (temp - bean_temp) / r * 60.0)
+        }
+    };
+
+    let target_first_crack = profile.profile.target_first_crack.map(|t| t as f32 + zero);
+    let projected_first_crack = match find_event(RoastEventType::FirstCrackStart) {
+        Some(fc) => Some(fc.elapsed_seconds),
+        None if finished => None,
+        None => target_first_crack.map(target_at).and_then(time_to_reach),
+    };
+
+    let last_point = points.last()?;
+    let target_drop_time = profile
+        .profile
+        .target_total_time
+        .unwrap_or(last_point.time_seconds) as f32
+        + zero;
+    let target_drop_temp = profile
+        .profile
+        .target_end_temp
+        .unwrap_or(last_point.target_temp);
+    let (projected_drop_time, projected_drop_temp) = if let Some(d) = drop_event {
+        (
+            Some(d.elapsed_seconds),
+            Some(d.temperature.unwrap_or(bean_temp)),
+        )
+    } else if finished {
+        (Some(now), Some(bean_temp))
+    } else {
+        let temp_at_target = if now >= target_drop_time {
+            Some(bean_temp)
+        } else {
+            ror.map(|r| bean_temp + r * (target_drop_time - now) / 60.0)
+        };
+        (time_to_reach(target_drop_temp), temp_at_target)
+    };
+
+    Some(ProfileScoreboard {
+        profile_zero: zero,
+        elapsed_seconds: now,
+        bean_temp,
+        target_temp: Some(target_temp),
+        deviation: Some(bean_temp - target_temp),
+        deviation_integral,
+        abs_deviation_integral,
+        ror,
+        target_first_crack,
+        projected_first_crack,
+        first_crack_delta: projected_first_crack
+            .zip(target_first_crack)
+            .map(|(p, t)| p - t),
+        target_drop_time: Some(target_drop_time),
+        target_drop_temp: Some(target_drop_temp),
+        projected_drop_time,
+        projected_drop_temp,
+        drop_time_delta: projected_drop_time.map(|p| p - target_drop_time),
+        drop_temp_delta: projected_drop_temp.map(|p| p - target_drop_temp),
+    })
+}
+
+// Artisan Profile Parser
+
+/// Events further than this from every accepted curve point are unmatched.
+const ARTISAN_EVENT_MATCH_SECS: f64 = 5.0;
+/// Bean temperatures above this are sensor garbage (°C or °F).
+const ARTISAN_MAX_TEMP: f64 = 600.0;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ArtisanProfilePoint {
+    time: f64,
+    bean_temp: f32,
+    env_temp: f32,
+    /// Names of the events that happened at this point.
+    notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ArtisanRoastEvent {
+    name: String,
+    time: f64,
+    bean_temp: f32,
+    env_temp: f32,
+    event_type: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ParsedArtisanProfile {
+    title: String,
+    roast_date: String,
+    total_time: f64,
+    points: Vec<ArtisanProfilePoint>,
+    events: Vec<ArtisanRoastEvent>,
+    report: ImportReport,
+}
+
+/// Limit a `session_summaries` query to the days from `from` up to, not
+/// including, `to`. The dates are bound in that order.
+fn push_day_range(query: &mut String, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) {
+    if from.is_some() {
+        query.push_str(" AND ss.day >= ?");
+    }
+    if to.is_some() {
+        query.push_str(" AND ss.day < ?");
+    }
+}
+
+/// A number, also when written as a string with a decimal comma (`"185,3"`)
+/// as Artisan does in some locales.
+fn artisan_number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().replace(',', ".").parse().ok(),
+        _ => None,
+    }
+}
+
+/// Drop JSON5-style trailing commas (`[1, 2,]`, `{"a": 1,}`) outside strings.
+fn strip_trailing_commas(content: &str) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut out = String::with_capacity(content.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if matches!(next, Some(']') | Some('}')) {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn parse_artisan_alog(content: &str) -> Result<ParsedArtisanProfile> {
+    // First try to parse as JSON
+    let profile_data: serde_json::Value = if let Ok(json) = serde_json::from_str(content) {
+        json
+    } else {
+        // Try to convert Python literals (or JSON5) to JSON with more
+        // comprehensive replacements
+        let json_content = content
+            // Basic Python to JSON conversions
+            .replace("True", "true")
+            .replace("False", "false")
+            .replace("None", "null")
+            // Handle Python tuples - convert to arrays
+            .replace("(", "[")
+            .replace(")", "]")
+            // Handle Python single quotes
+            .replace("'", "\"")
+            // Remove Python and JSON5 comments that might interfere
+            .lines()
+            .filter(|line| {
+                let line = line.trim_start();
+                !line.starts_with('#') && !line.starts_with("//")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        serde_json::from_str(&strip_trailing_commas(&json_content))
+            .map_err(|e| anyhow!("Unable to parse Artisan profile file format: {}", e))?
+    };
+
+    // Extract time and temperature arrays
+    let timex = profile_data["timex"].as_array().unwrap_or(&vec![]).clone();
+    let temp1 = profile_data["temp1"].as_array().unwrap_or(&vec![]).clone(); // Environment temp
+    let temp2 = profile_data["temp2"].as_array().unwrap_or(&vec![]).clone(); // Bean temp
+    let default_computed = serde_json::Map::new();
+    let computed = profile_data["computed"]
+        .as_object()
+        .unwrap_or(&default_computed);
+
+    // Convert to profile points, skipping rows that can't be one
+    let mut report = ImportReport::default();
+    let mut points: Vec<ArtisanProfilePoint> = Vec::new();
+    for (i, time_val) in timex.iter().enumerate() {
+        let mut skip = |reason: String| {
+            report
+                .rows_skipped
+                .push(SkippedImportRow { row: i, reason });
+        };
+        let Some(time) = artisan_number(time_val) else {
+            skip(format!("time {} is not a number", time_val));
+            continue;
+        };
+        let bean_temp = match temp2.get(i).and_then(artisan_number) {
+            None => {
+                skip("bean temperature is missing".to_string());
+                continue;
+            }
+            // Artisan records -1 when there was no reading
+            Some(t) if t < 0.0 => {
+                skip("no bean temperature reading".to_string());
+                continue;
+            }
+            Some(t) if t > ARTISAN_MAX_TEMP => {
+                skip(format!("bean temperature {} is out of range", t));
+                continue;
+            }
+            Some(t) => t as f32,
+        };
+        if let Some(last) = points.last() {
+            if time <= last.time {
+                skip(format!("time {}s does not follow {}s", time, last.time));
+                continue;
+            }
+        }
+        let env_temp = temp1.get(i).and_then(artisan_number).unwrap_or(0.0) as f32;
+        points.push(ArtisanProfilePoint {
+            time,
+            bean_temp,
+            env_temp,
+            notes: None,
+        });
+    }
+    report.points_accepted = points.len();
+
+    // Extract roast events
+    let mut events = Vec::new();
+
+    let event_types = vec![
+        ("CHARGE", "CHARGE"),
+        ("TP", "TP"),
+        ("DRY", "DRY"),
+        ("FCs", "FCs"),
+        ("FCe", "FCe"),
+        ("SCs", "SCs"),
+        ("DROP", "DROP"),
+    ];
+
+    for (key, event_type) in event_types {
+        if let Some(time) = computed
+            .get(&format!("{}_time", key))
+            .and_then(artisan_number)
+        {
+            let bean_temp = computed
+                .get(&format!("{}_BT", key))
+                .and_then(artisan_number)
+                .unwrap_or(0.0) as f32;
+            let env_temp = computed
+                .get(&format!("{}_ET", key))
+                .and_then(artisan_number)
+                .unwrap_or(0.0) as f32;
+            let name = match event_type {
+                "CHARGE" => "Charge",
+                "TP" => "Turning Point",
+                "DRY" => "Dry End",
+                "FCs" => "First Crack Start",
+                "FCe" => "First Crack End",
+                "SCs" => "Second Crack Start",
+                "DROP" => "Drop",
+                _ => event_type,
+            }
+            .to_string();
+
+            // Noted on the closest curve point
+            let closest = points
+                .iter_mut()
+                .filter(|p| (p.time - time).abs() <= ARTISAN_EVENT_MATCH_SECS)
+                .min_by(|a, b| (a.time - time).abs().total_cmp(&(b.time - time).abs()));
+            match closest {
+                Some(point) => {
+                    point.notes = Some(match point.notes.take() {
+                        Some(notes) => format!("{}, {}", notes, name),
+                        None => name.clone(),
+                    });
+                }
+                None => report.events_unmatched.push(UnmatchedImportEvent {
+                    event: name.clone(),
+                    time_seconds: time,
+                    reason: format!("no curve point within {}s", ARTISAN_EVENT_MATCH_SECS),
+                }),
+            }
+
+            events.push(ArtisanRoastEvent {
+                name,
+                time,
+                bean_temp,
+                env_temp,
+                event_type: event_type.to_string(),
+            });
+        }
+    }
+
+    let title = profile_data["title"]
+        .as_str()
+        .unwrap_or("Imported Profile")
+        .to_string();
+    let roast_date = profile_data["roastdate"].as_str().unwrap_or("").to_string();
+    let total_time = computed
+        .get("totaltime")
+        .and_then(artisan_number)
+        .unwrap_or_else(|| points.last().map(|p| p.time).unwrap_or(0.0));
+
+    Ok(ParsedArtisanProfile {
+        title,
+        roast_date,
+        total_time,
+        points,
+        events,
+        report,
+    })
+}
+
+// ============================================================================
+// Device Service
+// ============================================================================
+
+/// An autotune run with no results after this long no longer blocks a new start.
+const AUTOTUNE_RUN_TIMEOUT_SECS: i64 = 30 * 60;
+
+#[derive(Clone)]
+pub struct DeviceService {
+    db: SqlitePool,
+    changes_tx: broadcast::Sender<DeviceChange>,
+}
+
+impl DeviceService {
+    pub fn new(db: SqlitePool) -> Self {
+        let (changes_tx, _) = broadcast::channel(64);
+        Self { db, changes_tx }
+    }
+
+    /// Subscribe to devices as they are created, updated and deleted.
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceChange> {
+        self.changes_tx.subscribe()
+    }
+
+    fn publish(&self, op: DeviceChangeOp, device: &Device) {
+        let _ = self.changes_tx.send(DeviceChange {
+            op,
+            device: device.clone(),
+        });
+    }
+
+    // ---- Device CRUD ----
+
+    pub async fn list_devices(&self, status_filter: Option<DeviceStatus>) -> Result<Vec<Device>> {
+        if let Some(status) = status_filter {
+            let devices = sqlx::query_as::<_, Device>(
+                "SELECT * FROM devices WHERE status = ? ORDER BY created_at DESC",
+            )
+            .bind(status.to_string())
+            .fetch_all(&self.db)
+            .await?;
+            Ok(devices)
+        } else {
+            let devices =
+                sqlx::query_as::<_, Device>("SELECT * FROM devices ORDER BY created_at DESC")
+                    .fetch_all(&self.db)
+                    .await?;
+            Ok(devices)
+        }
+    }
+
+    pub async fn get_device(&self, id: &str) -> Result<Option<DeviceWithConnections>> {
+        let device = sqlx::query_as::<_, Device>("SELECT * FROM devices WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?;
+
+        let Some(device) = device else {
+            return Ok(None);
+        };
+
+        let connections = sqlx::query_as::<_, DeviceConnection>(
+            "SELECT * FROM device_connections WHERE device_id = ? ORDER BY priority DESC",
+        )
+        .bind(id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(Some(DeviceWithConnections {
+            device,
+            connections,
+        }))
+    }
+
+    pub async fn get_device_by_device_id(
+        &self,
+        device_id: &str,
+    ) -> Result<Option<DeviceWithConnections>> {
+        let device = sqlx::query_as::<_, Device>("SELECT * FROM devices WHERE device_id = ?")
+            .bind(device_id)
+            .fetch_optional(&self.db)
+            .await?;
+
+        let Some(device) = device else {
+            return Ok(None);
+        };
+
+        let connections = sqlx::query_as::<_, DeviceConnection>(
+            "SELECT * FROM device_connections WHERE device_id = ? ORDER BY priority DESC",
+        )
+        .bind(&device.id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(Some(DeviceWithConnections {
+            device,
+            connections,
+        }))
+    }
+
+    pub async fn create_device(&self, req: CreateDeviceRequest) -> Result<Device> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let device = sqlx::query_as::<_, Device>(
+            r#"
+            INSERT INTO devices (id, name, device_id, profile_id, status, description, location, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#
+        )
+        .bind(&id)
+        .bind(&req.name)
+        .bind(&req.device_id)
+        .bind(&req.profile_id)
+        .bind(DeviceStatus::Pending.to_string())
+        .bind(&req.description)
+        .bind(&req.location)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.db)
+        .await?;
+
+        self.publish(DeviceChangeOp::Created, &device);
+        Ok(device)
+    }
+
+    pub async fn update_device(
+        &self,
+        id: &str,
+        req: UpdateDeviceRequest,
+    ) -> Result<Option<Device>> {
+        if req.name.is_none()
+            && req.profile_id.is_none()
+            && req.status.is_none()
+            && req.description.is_none()
+            && req.location.is_none()
+        {
+            // Nothing to update, just return the existing device
+            let device = sqlx::query_as::<_, Device>("SELECT * FROM devices WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.db)
+                .await?;
+            return Ok(device);
+        }
+
+        let now = Utc::now();
+        let mut query = "UPDATE devices SET updated_at = ?".to_string();
+
+        if req.name.is_some() {
+            query.push_str(", name = ?");
+        }
+        if req.profile_id.is_some() {
+            query.push_str(", profile_id = ?");
+        }
+        if req.status.is_some() {
+            query.push_str(", status = ?");
+        }
+        if req.description.is_some() {
+            query.push_str(", description = ?");
+        }
+        if req.location.is_some() {
+            query.push_str(", location = ?");
+        }
+
+        query.push_str(" WHERE id = ? RETURNING *");
+
+        let mut query_builder = sqlx::query_as::<_, Device>(&query).bind(now);
+
+        if let Some(ref name) = req.name {
+            query_builder = query_builder.bind(name);
+        }
+        if let Some(ref profile_id) = req.profile_id {
+            query_builder = query_builder.bind(profile_id);
+        }
+        if let Some(ref status) = req.status {
+            query_builder = query_builder.bind(status.to_string());
+        }
+        if let Some(ref description) = req.description {
+            query_builder = query_builder.bind(description);
+        }
+        if let Some(ref location) = req.location {
+            query_builder = query_builder.bind(location);
+        }
+
+        query_builder = query_builder.bind(id);
+
+        let device = query_builder.fetch_optional(&self.db).await?;
+        if let Some(device) = &device {
+            self.publish(DeviceChangeOp::Updated, device);
+        }
+        Ok(device)
+    }
+
+    pub async fn delete_device(&self, id: &str) -> Result<bool> {
+        let device = sqlx::query_as::<_, Device>("DELETE FROM devices WHERE id = ? RETURNING *")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?;
+
+        if let Some(device) = &device {
+            self.publish(DeviceChangeOp::Deleted, device);
+        }
+        Ok(device.is_some())
+    }
+
+    #[allow(dead_code)] // Will be used by device status transitions
+    pub async fn update_device_status(
+        &self,
+        id: &str,
+        status: DeviceStatus,
+    ) -> Result<Option<Device>> {
+        let device = sqlx::query_as::<_, Device>(
+            "UPDATE devices SET status = ?, updated_at = ? WHERE id = ? RETURNING *",
+        )
+        .bind(status.to_string())
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        if let Some(device) = &device {
+            self.publish(DeviceChangeOp::Updated, device);
+        }
+        Ok(device)
+    }
+
+    pub async fn update_last_seen(&self, device_id: &str) -> Result<()> {
+        sqlx::query("UPDATE devices SET last_seen_at = ? WHERE device_id = ?")
+            .bind(Utc::now())
+            .bind(device_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    // ---- Device Profile CRUD ----
+
+    pub async fn list_profiles(&self) -> Result<Vec<DeviceProfile>> {
+        let profiles = sqlx::query_as::<_, DeviceProfile>(
+            "SELECT * FROM device_profiles ORDER BY created_at DESC",
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(profiles)
+    }
+
+    pub async fn get_profile(&self, id: &str) -> Result<Option<DeviceProfile>> {
+        let profile =
+            sqlx::query_as::<_, DeviceProfile>("SELECT * FROM device_profiles WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.db)
+                .await?;
+
+        Ok(profile)
+    }
+
+    pub async fn create_profile(&self, req: CreateDeviceProfileRequest) -> Result<DeviceProfile> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let profile = sqlx::query_as::<_, DeviceProfile>(
+            r#"
+            INSERT INTO device_profiles (
+                id, name, description, default_control_mode, default_setpoint, default_fan_pwm,
+                default_kp, default_ki, default_kd, max_temp, min_fan_pwm, telemetry_interval_ms,
+                created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(&req.name)
+        .bind(&req.description)
+        .bind(&req.default_control_mode)
+        .bind(req.default_setpoint)
+        .bind(req.default_fan_pwm)
+        .bind(req.default_kp)
+        .bind(req.default_ki)
+        .bind(req.default_kd)
+        .bind(req.max_temp)
+        .bind(req.min_fan_pwm)
+        .bind(req.telemetry_interval_ms)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(profile)
+    }
+
+    pub async fn update_profile(
+        &self,
+        id: &str,
+        req: UpdateDeviceProfileRequest,
+    ) -> Result<Option<DeviceProfile>> {
+        let now = Utc::now();
+        let profile = sqlx::query_as::<_, DeviceProfile>(
+            r#"
+            UPDATE device_profiles SET
+                name = COALESCE(?, name),
+                description = COALESCE(?, description),
+                default_control_mode = COALESCE(?, default_control_mode),
+                default_setpoint = COALESCE(?, default_setpoint),
+                default_fan_pwm = COALESCE(?, default_fan_pwm),
+                default_kp = COALESCE(?, default_kp),
+                default_ki = COALESCE(?, default_ki),
+                default_kd = COALESCE(?, default_kd),
+                max_temp = COALESCE(?, max_temp),
+                min_fan_pwm = COALESCE(?, min_fan_pwm),
+                telemetry_interval_ms = COALESCE(?, telemetry_interval_ms),
+                updated_at = ?
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(&req.name)
+        .bind(&req.description)
+        .bind(&req.default_control_mode)
+        .bind(req.default_setpoint)
+        .bind(req.default_fan_pwm)
+        .bind(req.default_kp)
+        .bind(req.default_ki)
+        .bind(req.default_kd)
+        .bind(req.max_temp)
+        .bind(req.min_fan_pwm)
+        .bind(req.telemetry_interval_ms)
+        .bind(now)
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(profile)
+    }
+
+    pub async fn delete_profile(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM device_profiles WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ---- Device Connection CRUD ----
+
+    pub async fn add_connection(
+        &self,
+        device_id: &str,
+        req: CreateConnectionRequest,
+    ) -> Result<DeviceConnection> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let connection = sqlx::query_as::<_, DeviceConnection>(
+            r#"
+            INSERT INTO device_connections (id, device_id, protocol, enabled, priority, config, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#
+        )
+        .bind(&id)
+        .bind(device_id)
+        .bind(req.protocol.to_string())
+        .bind(req.enabled.unwrap_or(true))
+        .bind(req.priority.unwrap_or(0))
+        .bind(serde_json::to_string(&req.config)?)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(connection)
+    }
+
+    pub async fn update_connection(
+        &self,
+        connection_id: &str,
+        req: UpdateConnectionRequest,
+    ) -> Result<Option<DeviceConnection>> {
+        if req.enabled.is_none() && req.priority.is_none() && req.config.is_none() {
+            let conn = sqlx::query_as::<_, DeviceConnection>(
+                "SELECT * FROM device_connections WHERE id = ?",
+            )
+            .bind(connection_id)
+            .fetch_optional(&self.db)
+            .await?;
+            return Ok(conn);
+        }
+
+        let now = Utc::now();
+        let mut query = "UPDATE device_connections SET updated_at = ?".to_string();
+
+        if req.enabled.is_some() {
+            query.push_str(", enabled = ?");
+        }
+        if req.priority.is_some() {
+            query.push_str(", priority = ?");
+        }
+        if req.config.is_some() {
+            query.push_str(", config = ?");
+        }
+
+        query.push_str(" WHERE id = ? RETURNING *");
+
+        let mut query_builder = sqlx::query_as::<_, DeviceConnection>(&query).bind(now);
+
+        if let Some(enabled) = req.enabled {
+            query_builder = query_builder.bind(enabled);
+        }
+        if let Some(priority) = req.priority {
+            query_builder = query_builder.bind(priority);
+        }
+        if let Some(ref config) = req.config {
+            query_builder = query_builder.bind(serde_json::to_string(config)?);
+        }
+
+        query_builder = query_builder.bind(connection_id);
+
+        let connection = query_builder.fetch_optional(&self.db).await?;
+        Ok(connection)
+    }
+
+    pub async fn remove_connection(&self, connection_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM device_connections WHERE id = ?")
+            .bind(connection_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ---- Register Map CRUD ----
+
+    pub async fn get_register_map(&self, device_id: &str) -> Result<Vec<ModbusRegisterMap>> {
+        let registers = sqlx::query_as::<_, ModbusRegisterMap>(
+            "SELECT * FROM modbus_register_maps WHERE device_id = ? ORDER BY register_type, address"
+        )
+        .bind(device_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(registers)
+    }
+
+    pub async fn set_register_map(
+        &self,
+        device_id: &str,
+        registers: Vec<CreateRegisterMapEntry>,
+    ) -> Result<Vec<ModbusRegisterMap>> {
+        // Delete existing register map and insert new entries in a transaction
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("DELETE FROM modbus_register_maps WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut result = Vec::new();
+        for entry in registers {
+            let id = Uuid::new_v4().to_string();
+            let register = sqlx::query_as::<_, ModbusRegisterMap>(
+                r#"
+                INSERT INTO modbus_register_maps (
+                    id, device_id, register_type, address, name, data_type,
+                    byte_order, scale_factor, offset, unit, description, writable
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                RETURNING *
+                "#,
+            )
+            .bind(&id)
+            .bind(device_id)
+            .bind(entry.register_type.to_string())
+            .bind(entry.address)
+            .bind(&entry.name)
+            .bind(entry.data_type.to_string())
+            .bind(&entry.byte_order)
+            .bind(entry.scale_factor)
+            .bind(entry.offset)
+            .bind(&entry.unit)
+            .bind(&entry.description)
+            .bind(entry.writable.unwrap_or(false))
+            .fetch_one(&mut *tx)
+            .await?;
+
+            result.push(register);
+        }
+
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    // ---- Fan Airflow Calibration ----
+
+    pub async fn get_fan_calibration(&self, device_id: &str) -> Result<Vec<FanCalibrationPoint>> {
+        let points = sqlx::query_as::<_, FanCalibrationPoint>(
+            "SELECT * FROM fan_calibration_points WHERE device_id = ? ORDER BY fan_pwm",
+        )
+        .bind(device_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(points)
+    }
+
+    /// Calibration curve looked up by the roaster's protocol-level device_id.
+    pub async fn get_fan_calibration_by_device_id(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<FanCalibrationPoint>> {
+        let points = sqlx::query_as::<_, FanCalibrationPoint>(
+            r#"
+            SELECT f.* FROM fan_calibration_points f
+            JOIN devices d ON d.id = f.device_id
+            WHERE d.device_id = ?
+            ORDER BY f.fan_pwm
+            "#,
+        )
+        .bind(device_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(points)
+    }
+
+    pub async fn set_fan_calibration(
+        &self,
+        device_id: &str,
+        points: Vec<CreateFanCalibrationPoint>,
+    ) -> Result<Vec<FanCalibrationPoint>> {
+        // Replace the whole curve in one transaction
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("DELETE FROM fan_calibration_points WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let now = Utc::now();
+        let mut result = Vec::new();
+        for point in points {
+            let row = sqlx::query_as::<_, FanCalibrationPoint>(
+                r#"
+                INSERT INTO fan_calibration_points (
+                    id, device_id, fan_pwm, airflow, static_pressure, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?)
+                RETURNING *
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(device_id)
+            .bind(point.fan_pwm)
+            .bind(point.airflow)
+            .bind(point.static_pressure)
+            .bind(now)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            result.push(row);
+        }
+
+        tx.commit().await?;
+        result.sort_by_key(|p| p.fan_pwm);
+        Ok(result)
+    }
+
+    // ---- PID Gain History ----
+
+    pub async fn record_pid_gains(
+        &self,
+        device_id: &str,
+        gains: RecordPidGains<'_>,
+    ) -> Result<PidHistoryEntry> {
+        let entry = sqlx::query_as::<_, PidHistoryEntry>(
+            r#"
+            INSERT INTO pid_history (
+                id, device_id, kp, ki, kd, source, source_device_id, scale, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(device_id)
+        .bind(gains.kp)
+        .bind(gains.ki)
+        .bind(gains.kd)
+        .bind(gains.source)
+        .bind(gains.source_device_id)
+        .bind(gains.scale)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(entry)
+    }
+
+    pub async fn latest_pid_gains(&self, device_id: &str) -> Result<Option<PidHistoryEntry>> {
+        let entry = sqlx::query_as::<_, PidHistoryEntry>(
+            "SELECT * FROM pid_history WHERE device_id = ? ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(device_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(entry)
+    }
+
+    pub async fn list_pid_history(
+        &self,
+        device_id: &str,
+        limit: i64,
+    ) -> Result<Vec<PidHistoryEntry>> {
+        let entries = sqlx::query_as::<_, PidHistoryEntry>(
+            "SELECT * FROM pid_history WHERE device_id = ? ORDER BY created_at DESC LIMIT ?",
+        )
+        .bind(device_id)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(entries)
+    }
+
+    // ========================================================================
+    // Autotune runs
+    // ========================================================================
+
+    /// Mark running autotune runs that never reported results as expired, so a
+    /// lost `results` message doesn't block new runs forever.
+    async fn expire_stale_autotune_runs(&self, device_id: &str) -> Result<()> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(AUTOTUNE_RUN_TIMEOUT_SECS);
+        sqlx::query(
+            "UPDATE autotune_runs SET state = ?, finished_at = ? WHERE device_id = ? AND state = ? AND started_at < ?",
+        )
+        .bind(AutotuneRunState::Expired)
+        .bind(Utc::now())
+        .bind(device_id)
+        .bind(AutotuneRunState::Running)
+        .bind(cutoff)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Record a new in-flight autotune run. Returns `None` if the device
+    /// already has one running.
+    pub async fn start_autotune_run(
+        &self,
+        device_id: &str,
+        req: StartAutotuneRun<'_>,
+    ) -> Result<Option<AutotuneRun>> {
+        self.expire_stale_autotune_runs(device_id).await?;
+
+        let inserted = sqlx::query_as::<_, AutotuneRun>(
+            r#"
+            INSERT INTO autotune_runs (
+                id, device_id, target_temperature, mode, tuning_method, requested_by, state, started_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(device_id)
+        .bind(req.target_temperature)
+        .bind(req.mode)
+        .bind(req.tuning_method)
+        .bind(req.requested_by)
+        .bind(AutotuneRunState::Running)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await;
+
+        match inserted {
+            Ok(run) => Ok(Some(run)),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn active_autotune_run(&self, device_id: &str) -> Result<Option<AutotuneRun>> {
+        self.expire_stale_autotune_runs(device_id).await?;
+
+        let run = sqlx::query_as::<_, AutotuneRun>(
+            "SELECT * FROM autotune_runs WHERE device_id = ? AND state = ?",
+        )
+        .bind(device_id)
+        .bind(AutotuneRunState::Running)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(run)
+    }
+
+    /// Record the latest reported phase on the running run, if any.
+    pub async fn set_autotune_phase(&self, device_id: &str, phase: &str) -> Result<()> {
+        sqlx::query("UPDATE autotune_runs SET last_phase = ? WHERE device_id = ? AND state = ?")
+            .bind(phase)
+            .bind(device_id)
+            .bind(AutotuneRunState::Running)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Move the device's running run to a terminal state, attaching `results`
+    /// when given. Returns `None` if no run was in flight.
+    pub async fn finish_autotune_run(
+        &self,
+        device_id: &str,
+        state: AutotuneRunState,
+        results: Option<&serde_json::Value>,
+    ) -> Result<Option<AutotuneRun>> {
+        let run = sqlx::query_as::<_, AutotuneRun>(
+            r#"
+            UPDATE autotune_runs
+            SET state = ?, results = COALESCE(?, results), finished_at = ?
+            WHERE device_id = ? AND state = ?
+            RETURNING *
+            "#,
+        )
+        .bind(state)
+        .bind(results.map(sqlx::types::Json))
+        .bind(Utc::now())
+        .bind(device_id)
+        .bind(AutotuneRunState::Running)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(run)
+    }
+
+    pub async fn list_autotune_runs(
+        &self,
+        device_id: &str,
+        limit: i64,
+    ) -> Result<Vec<AutotuneRun>> {
+        let runs = sqlx::query_as::<_, AutotuneRun>(
+            "SELECT * FROM autotune_runs WHERE device_id = ? ORDER BY started_at DESC LIMIT ?",
+        )
+        .bind(device_id)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(runs)
+    }
+}
+
+// ============================================================================
+// User Service
+// ============================================================================
+
+#[derive(Clone)]
+pub struct UserService {
+    db: SqlitePool,
+}
+
+/// Prefix for locally issued API keys, used to tell them apart from JWTs.
+pub const API_KEY_PREFIX: &str = "rr_";
+
+fn hash_api_key(key: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl UserService {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    // ---- Users ----
+
+    /// Create or refresh a user after an OIDC login. Role is re-derived from
+    /// the provider's groups on every login.
+    pub async fn upsert_oidc_user(
+        &self,
+        issuer: &str,
+        subject: &str,
+        email: Option<&str>,
+        display_name: Option<&str>,
+        role: UserRole,
+    ) -> Result<User> {
+        let now = Utc::now();
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            INSERT INTO users (
+                id, issuer, subject, email, display_name, role, created_at, last_login_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (issuer, subject) DO UPDATE SET
+                email = excluded.email,
+                display_name = excluded.display_name,
+                role = excluded.role,
+                last_login_at = excluded.last_login_at
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(issuer)
+        .bind(subject)
+        .bind(email)
+        .bind(display_name)
+        .bind(role)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(user)
+    }
+
+    // ---- API Keys ----
+
+    /// Create an API key. The plaintext key is only returned here; the
+    /// database stores its SHA-256 hash.
+    pub async fn create_api_key(
+        &self,
+        req: CreateApiKeyRequest,
+        created_by: Option<&str>,
+    ) -> Result<CreatedApiKey> {
+        use base64::Engine;
+        use rand::RngCore;
+
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let key = format!(
+            "{}{}",
+            API_KEY_PREFIX,
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(secret)
+        );
+
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+            INSERT INTO api_keys (id, name, key_prefix, key_hash, role, created_by, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&req.name)
+        .bind(&key[..API_KEY_PREFIX.len() + 6])
+        .bind(hash_api_key(&key))
+        .bind(req.role.unwrap_or(UserRole::Operator))
+        .bind(created_by)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(CreatedApiKey { api_key, key })
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys ORDER BY created_at DESC")
+            .fetch_all(&self.db)
+            .await?;
+
+        Ok(keys)
+    }
+
+    pub async fn revoke_api_key(&self, id: &str) -> Result<bool> {
+        let result =
+            sqlx::query("UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+                .bind(Utc::now())
+                .bind(id)
+                .execute(&self.db)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Look up an active API key by its plaintext value and record its use.
+    pub async fn authenticate_api_key(&self, key: &str) -> Result<Option<ApiKey>> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+            UPDATE api_keys SET last_used_at = ?
+            WHERE key_hash = ? AND revoked_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(hash_api_key(key))
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(api_key)
+    }
+
+    // ---- Preferences ----
+
+    pub async fn get_preferences(&self, subject: &str) -> Result<Option<UserPreferences>> {
+        let prefs = sqlx::query_as::<_, UserPreferences>(
+            "SELECT * FROM user_preferences WHERE subject = ?",
+        )
+        .bind(subject)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(prefs)
+    }
+
+    /// Create or partially update preferences; omitted fields keep their value.
+    pub async fn update_preferences(
+        &self,
+        subject: &str,
+        req: UpdatePreferencesRequest,
+    ) -> Result<UserPreferences> {
+        let subscriptions = req
+            .ws_subscriptions
+            .map(|s| serde_json::to_string(&s))
+            .transpose()?;
+        let layout = req
+            .dashboard_layout
+            .map(|l| serde_json::to_string(&l))
+            .transpose()?;
+
+        let prefs = sqlx::query_as::<_, UserPreferences>(
+            r#"
+            INSERT INTO user_preferences (subject, ws_subscriptions, dashboard_layout, updated_at)
+            VALUES (?, COALESCE(?, '[]'), ?, ?)
+            ON CONFLICT (subject) DO UPDATE SET
+                ws_subscriptions = COALESCE(?, ws_subscriptions),
+                dashboard_layout = COALESCE(?, dashboard_layout),
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(subject)
+        .bind(&subscriptions)
+        .bind(&layout)
+        .bind(Utc::now())
+        .bind(&subscriptions)
+        .bind(&layout)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(prefs)
+    }
+
+    // ---- Curve smoothing ----
+
+    pub async fn list_smoothing_presets(&self, subject: &str) -> Result<Vec<SmoothingPreset>> {
+        let presets = sqlx::query_as::<_, SmoothingPreset>(
+            "SELECT * FROM smoothing_presets WHERE subject = ? ORDER BY view",
+        )
+        .bind(subject)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(presets)
+    }
+
+    pub async fn upsert_smoothing_preset(
+        &self,
+        subject: &str,
+        view: &str,
+        config: &SmoothingConfig,
+    ) -> Result<SmoothingPreset> {
+        let preset = sqlx::query_as::<_, SmoothingPreset>(
+            r#"
+            INSERT INTO smoothing_presets
+                (subject, view, bt_window_secs, et_window_secs, ror_window_secs, ror_algorithm, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (subject, view) DO UPDATE SET
+                bt_window_secs = excluded.bt_window_secs,
+                et_window_secs = excluded.et_window_secs,
+                ror_window_secs = excluded.ror_window_secs,
+                ror_algorithm = excluded.ror_algorithm,
+                updated_at = excluded.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(subject)
+        .bind(view)
+        .bind(config.bt_window_secs)
+        .bind(config.et_window_secs)
+        .bind(config.ror_window_secs)
+        .bind(config.ror_algorithm)
+        .bind(Utc::now())
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(preset)
+    }
+
+    pub async fn delete_smoothing_preset(&self, subject: &str, view: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM smoothing_presets WHERE subject = ? AND view = ?")
+            .bind(subject)
+            .bind(view)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Smoothing for a view: the user's preset for it, else the server's
+    /// RoR settings (`ror_window_seconds`, `ror_smoothing_algorithm`) with
+    /// unsmoothed BT/ET.
+    pub async fn resolve_smoothing(
+        &self,
+        subject: Option<&str>,
+        view: &str,
+    ) -> Result<SmoothingConfig> {
+        if let Some(subject) = subject {
+            let preset = sqlx::query_as::<_, SmoothingConfig>(
+                "SELECT bt_window_secs, et_window_secs, ror_window_secs, ror_algorithm FROM smoothing_presets WHERE subject = ? AND view = ?",
+            )
+            .bind(subject)
+            .bind(view)
+            .fetch_optional(&self.db)
+            .await?;
+            if let Some(config) = preset {
+                return Ok(config);
+            }
+        }
+
+        let settings = sqlx::query_as::<_, (String, String)>(
+            "SELECT key, value FROM settings WHERE key IN ('ror_window_seconds', 'ror_smoothing_algorithm')",
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let mut config = SmoothingConfig::default();
+        for (key, value) in settings {
+            match key.as_str() {
+                "ror_window_seconds" => {
+                    if let Ok(secs) = value.parse::<f32>() {
+                        config.ror_window_secs = secs;
+                    }
+                }
+                _ => {
+                    if let Ok(algorithm) = value.parse() {
+                        config.ror_algorithm = algorithm;
+                    }
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("Failed to create test db");
+
+        // Enable foreign keys for tests
+        sqlx::query("PRAGMA foreign_keys = ON;")
+            .execute(&pool)
+            .await
+            .expect("Failed to enable foreign keys");
+
+        // Run migrations
+        let migrations: &[&str] = &[
+            include_str!("../migrations/001_roast_sessions.sql"),
+            include_str!("../migrations/002_roast_events.sql"),
+            include_str!("../migrations/003_device_configuration.sql"),
+            include_str!("../migrations/004_session_statistics.sql"),
+            include_str!("../migrations/005_auc_value.sql"),
+            include_str!("../migrations/006_cupping_scores.sql"),
+            include_str!("../migrations/007_profile_env_temp.sql"),
+            include_str!("../migrations/008_session_signoff.sql"),
+            include_str!("../migrations/009_pid_history.sql"),
+            include_str!("../migrations/010_fan_calibration.sql"),
+            include_str!("../migrations/011_users_auth.sql"),
+            include_str!("../migrations/012_user_preferences.sql"),
+            include_str!("../migrations/013_autotune_runs.sql"),
+            include_str!("../migrations/014_profile_scoreboard.sql"),
+            include_str!("../migrations/015_roast_cues.sql"),
+            include_str!("../migrations/016_sync_journal.sql"),
+            include_str!("../migrations/017_session_defects.sql"),
+            include_str!("../migrations/018_manual_sessions.sql"),
+            include_str!("../migrations/019_automations.sql"),
+            include_str!("../migrations/020_profile_batch_scaling.sql"),
+            include_str!("../migrations/021_alerts.sql"),
+            include_str!("../migrations/022_device_logs.sql"),
+            include_str!("../migrations/023_smoothing_presets.sql"),
+            include_str!("../migrations/024_export_signing_key.sql"),
+            include_str!("../migrations/025_green_beans.sql"),
+            include_str!("../migrations/026_mqtt_publish_audit.sql"),
+            include_str!("../migrations/027_session_costs.sql"),
+            include_str!("../migrations/028_telemetry_rollup.sql"),
+            include_str!("../migrations/029_batch_preheat.sql"),
+            include_str!("../migrations/030_sample_roasts.sql"),
+            include_str!("../migrations/031_device_health.sql"),
+            include_str!("../migrations/032_session_archives.sql"),
+            include_str!("../migrations/033_webhooks.sql"),
+            include_str!("../migrations/034_roaster_ror_settings.sql"),
+            include_str!("../migrations/035_status_history.sql"),
+            include_str!("../migrations/036_roast_queue.sql"),
+            include_str!("../migrations/037_alert_limits.sql"),
+            include_str!("../migrations/038_autotune_gain_limits.sql"),
+            include_str!("../migrations/039_charge_corrections.sql"),
+            include_str!("../migrations/040_profile_tolerances.sql"),
+            include_str!("../migrations/041_session_probe_issues.sql"),
+            include_str!("../migrations/042_api_key_control_policies.sql"),
+            include_str!("../migrations/043_session_summaries.sql"),
+            include_str!("../migrations/044_report_emails.sql"),
+            include_str!("../migrations/045_preroast_checklists.sql"),
+            include_str!("../migrations/046_session_attachments.sql"),
+            include_str!("../migrations/047_telemetry_wal_state.sql"),
+        ];
+        for migration_sql in migrations {
+            crate::apply_migration(&pool, migration_sql)
+                .await
+                .expect("Failed to apply migration");
+        }
+
+        // Create settings table and seed defaults (mirrors init_db)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );",
+        )
+        .execute(&pool)
+        .await
+        .expect("Failed to create settings table");
+        for (key, value) in [
+            ("profile_lookahead_seconds", "20"),
+            ("auc_base_temp", "0"),
+            ("auc_start_event", "charge"),
+            ("ror_window_seconds", "30"),
+            ("ror_smoothing_algorithm", "moving_average"),
+            ("auto_dry_temp", "150"),
+            ("auto_event_detection", "true"),
+            ("alarm_sound_enabled", "true"),
+            ("cost_currency", "USD"),
+            ("cost_heater_watts", "1500"),
+            ("cost_energy_per_kwh", "0"),
+            ("cost_labor_per_hour", "0"),
+            ("cost_overhead_per_batch", "0"),
+            (
+                "roast_alarms",
+                r#"[{"name":"High Temp Warning","condition_type":"temp_above","threshold":230,"enabled":true},{"name":"FC Approaching","condition_type":"temp_above","threshold":195,"enabled":true},{"name":"Low RoR Warning","condition_type":"ror_below","threshold":5.0,"reference_event":"first_crack_start","enabled":true}]"#,
+            ),
+        ] {
+            let _ = sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES (?, ?)")
+                .bind(key)
+                .bind(value)
+                .execute(&pool)
+                .await;
+        }
+
+        pool
+    }
+
+    // ---- Device CRUD Tests ----
+
+    #[tokio::test]
+    async fn test_create_and_get_device() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let req = CreateDeviceRequest {
+            name: "Test Roaster".to_string(),
+            device_id: "esp32-001".to_string(),
+            profile_id: None,
+            description: Some("A test device".to_string()),
+            location: Some("Kitchen".to_string()),
+        };
+
+        let device = service
+            .create_device(req)
+            .await
+            .expect("create_device failed");
+        assert_eq!(device.name, "Test Roaster");
+        assert_eq!(device.device_id, "esp32-001");
+        assert_eq!(device.status, DeviceStatus::Pending);
+        assert_eq!(device.description, Some("A test device".to_string()));
+        assert_eq!(device.location, Some("Kitchen".to_string()));
+
+        // Get by id
+        let fetched = service
+            .get_device(&device.id)
+            .await
+            .expect("get_device failed");
+        assert!(fetched.is_some());
+        let fetched = fetched.unwrap();
+        assert_eq!(fetched.device.name, "Test Roaster");
+        assert!(fetched.connections.is_empty());
+
+        // Get by device_id
+        let fetched = service
+            .get_device_by_device_id("esp32-001")
+            .await
+            .expect("get_device_by_device_id failed");
+        assert!(fetched.is_some());
+        assert_eq!(fetched.unwrap().device.id, device.id);
+    }
+
+    #[tokio::test]
+    async fn test_list_devices_with_status_filter() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        // Create two devices
+        service
+            .create_device(CreateDeviceRequest {
+                name: "Device 1".to_string(),
+                device_id: "d1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        let device2 = service
+            .create_device(CreateDeviceRequest {
+                name: "Device 2".to_string(),
+                device_id: "d2".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        // Activate device 2
+        service
+            .update_device_status(&device2.id, DeviceStatus::Active)
+            .await
+            .unwrap();
+
+        // List all
+        let all = service.list_devices(None).await.unwrap();
+        assert_eq!(all.len(), 2);
+
+        // List only pending
+        let pending = service
+            .list_devices(Some(DeviceStatus::Pending))
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].device_id, "d1");
+
+        // List only active
+        let active = service
+            .list_devices(Some(DeviceStatus::Active))
+            .await
+            .unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].device_id, "d2");
+    }
+
+    #[tokio::test]
+    async fn test_update_device() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Original".to_string(),
+                device_id: "dev1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        let updated = service
+            .update_device(
+                &device.id,
+                UpdateDeviceRequest {
+                    name: Some("Updated Name".to_string()),
+                    profile_id: None,
+                    status: Some(DeviceStatus::Active),
+                    description: Some("Now with description".to_string()),
+                    location: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(updated.is_some());
+        let updated = updated.unwrap();
+        assert_eq!(updated.name, "Updated Name");
+        assert_eq!(updated.status, DeviceStatus::Active);
+        assert_eq!(
+            updated.description,
+            Some("Now with description".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_device() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "To Delete".to_string(),
+                device_id: "del1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(service.delete_device(&device.id).await.unwrap());
+        assert!(!service.delete_device(&device.id).await.unwrap()); // Already deleted
+
+        let fetched = service.get_device(&device.id).await.unwrap();
+        assert!(fetched.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_last_seen() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Seen Device".to_string(),
+                device_id: "seen1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(device.last_seen_at.is_none());
+
+        service.update_last_seen("seen1").await.unwrap();
+
+        let fetched = service.get_device(&device.id).await.unwrap().unwrap();
+        assert!(fetched.device.last_seen_at.is_some());
+    }
+
+    // ---- Device Profile Tests ----
+
+    #[tokio::test]
+    async fn test_create_and_list_profiles() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let profile = service
+            .create_profile(CreateDeviceProfileRequest {
+                name: "Default Profile".to_string(),
+                description: Some("Standard roasting config".to_string()),
+                default_control_mode: Some("auto".to_string()),
+                default_setpoint: Some(200.0),
+                default_fan_pwm: Some(180),
+                default_kp: Some(15.0),
+                default_ki: Some(1.0),
+                default_kd: Some(25.0),
+                max_temp: Some(240.0),
+                min_fan_pwm: Some(100),
+                telemetry_interval_ms: Some(1000),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(profile.name, "Default Profile");
+        assert_eq!(profile.default_setpoint, Some(200.0));
+
+        let profiles = service.list_profiles().await.unwrap();
+        assert_eq!(profiles.len(), 1);
+
+        let fetched = service.get_profile(&profile.id).await.unwrap();
+        assert!(fetched.is_some());
+        assert_eq!(fetched.unwrap().name, "Default Profile");
+    }
+
+    #[tokio::test]
+    async fn test_delete_profile() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let profile = service
+            .create_profile(CreateDeviceProfileRequest {
+                name: "Temp Profile".to_string(),
+                description: None,
+                default_control_mode: None,
+                default_setpoint: None,
+                default_fan_pwm: None,
+                default_kp: None,
+                default_ki: None,
+                default_kd: None,
+                max_temp: None,
+                min_fan_pwm: None,
+                telemetry_interval_ms: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(service.delete_profile(&profile.id).await.unwrap());
+        assert!(!service.delete_profile(&profile.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_device_with_profile() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let profile = service
+            .create_profile(CreateDeviceProfileRequest {
+                name: "Roast Profile".to_string(),
+                description: None,
+                default_control_mode: None,
+                default_setpoint: Some(200.0),
+                default_fan_pwm: None,
+                default_kp: None,
+                default_ki: None,
+                default_kd: None,
+                max_temp: None,
+                min_fan_pwm: None,
+                telemetry_interval_ms: None,
+            })
+            .await
+            .unwrap();
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Profiled Device".to_string(),
+                device_id: "prof1".to_string(),
+                profile_id: Some(profile.id.clone()),
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(device.profile_id, Some(profile.id));
+    }
+
+    // ---- Connection Tests ----
+
+    #[tokio::test]
+    async fn test_add_and_remove_connection() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Connected Device".to_string(),
+                device_id: "conn1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        let mqtt_config = serde_json::json!({
+            "topic_prefix": "roaster/conn1",
+            "qos": 1
+        });
+
+        let conn = service
+            .add_connection(
+                &device.id,
+                CreateConnectionRequest {
+                    protocol: Protocol::Mqtt,
+                    enabled: Some(true),
+                    priority: Some(10),
+                    config: mqtt_config.clone(),
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(conn.protocol, Protocol::Mqtt);
+        assert!(conn.enabled);
+        assert_eq!(conn.priority, 10);
+        assert_eq!(conn.config, mqtt_config);
+
+        // Verify connection shows up in device fetch
+        let fetched = service.get_device(&device.id).await.unwrap().unwrap();
+        assert_eq!(fetched.connections.len(), 1);
+
+        // Remove connection
+        assert!(service.remove_connection(&conn.id).await.unwrap());
+        assert!(!service.remove_connection(&conn.id).await.unwrap());
+
+        let fetched = service.get_device(&device.id).await.unwrap().unwrap();
+        assert!(fetched.connections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_connection() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Update Conn Device".to_string(),
+                device_id: "uconn1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        let conn = service.add_connection(&device.id, CreateConnectionRequest {
+            protocol: Protocol::WebSocket,
+            enabled: Some(true),
+            priority: Some(0),
+            config: serde_json::json!({"url": "ws://10.0.0.1:8080/ws", "reconnect_interval_ms": 5000}),
+        }).await.unwrap();
+
+        let updated = service
+            .update_connection(
+                &conn.id,
+                UpdateConnectionRequest {
+                    enabled: Some(false),
+                    priority: Some(5),
+                    config: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(updated.is_some());
+        let updated = updated.unwrap();
+        assert!(!updated.enabled);
+        assert_eq!(updated.priority, 5);
+    }
+
+    #[tokio::test]
+    async fn test_cascade_delete_connections() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Cascade Device".to_string(),
+                device_id: "casc1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        service
+            .add_connection(
+                &device.id,
+                CreateConnectionRequest {
+                    protocol: Protocol::Mqtt,
+                    enabled: None,
+                    priority: None,
+                    config: serde_json::json!({"topic_prefix": "roaster/casc1", "qos": 0}),
+                },
+            )
+            .await
+            .unwrap();
+
+        service.add_connection(&device.id, CreateConnectionRequest {
+            protocol: Protocol::ModbusTcp,
+            enabled: None,
+            priority: None,
+            config: serde_json::json!({"host": "10.0.0.1", "port": 502, "unit_id": 1, "poll_interval_ms": 1000}),
+        }).await.unwrap();
+
+        // Delete device should cascade delete connections
+        assert!(service.delete_device(&device.id).await.unwrap());
+
+        // Connections should be gone (we can verify by trying to get the device)
+        let fetched = service.get_device(&device.id).await.unwrap();
+        assert!(fetched.is_none());
+    }
+
+    // ---- Register Map Tests ----
+
+    #[tokio::test]
+    async fn test_set_and_get_register_map() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Modbus Device".to_string(),
+                device_id: "modbus1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        let registers = vec![
+            CreateRegisterMapEntry {
+                register_type: ModbusRegisterType::Input,
+                address: 0,
+                name: "bean_temp_hi".to_string(),
+                data_type: ModbusDataType::Float32,
+                byte_order: Some("ABCD".to_string()),
+                scale_factor: Some(1.0),
+                offset: Some(0.0),
+                unit: Some("C".to_string()),
+                description: Some("Bean temperature (high word)".to_string()),
+                writable: Some(false),
+            },
+            CreateRegisterMapEntry {
+                register_type: ModbusRegisterType::Holding,
+                address: 0,
+                name: "setpoint".to_string(),
+                data_type: ModbusDataType::Float32,
+                byte_order: Some("ABCD".to_string()),
+                scale_factor: Some(1.0),
+                offset: Some(0.0),
+                unit: Some("C".to_string()),
+                description: Some("Target setpoint".to_string()),
+                writable: Some(true),
+            },
+        ];
+
+        let result = service
+            .set_register_map(&device.id, registers)
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+
+        let fetched = service.get_register_map(&device.id).await.unwrap();
+        assert_eq!(fetched.len(), 2);
+        // Ordered by register_type, address
+        assert_eq!(fetched[0].name, "setpoint"); // holding comes before input alphabetically
+        assert_eq!(fetched[1].name, "bean_temp_hi");
+    }
+
+    #[tokio::test]
+    async fn test_set_register_map_replaces_existing() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Replace Map Device".to_string(),
+                device_id: "rmap1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        // Set initial map
+        service
+            .set_register_map(
+                &device.id,
+                vec![CreateRegisterMapEntry {
+                    register_type: ModbusRegisterType::Input,
+                    address: 0,
+                    name: "old_register".to_string(),
+                    data_type: ModbusDataType::Uint16,
+                    byte_order: None,
+                    scale_factor: None,
+                    offset: None,
+                    unit: None,
+                    description: None,
+                    writable: None,
+                }],
+            )
+            .await
+            .unwrap();
+
+        // Replace with new map
+        service
+            .set_register_map(
+                &device.id,
+                vec![
+                    CreateRegisterMapEntry {
+                        register_type: ModbusRegisterType::Holding,
+                        address: 0,
+                        name: "new_register_1".to_string(),
+                        data_type: ModbusDataType::Float32,
+                        byte_order: None,
+                        scale_factor: None,
+                        offset: None,
+                        unit: None,
+                        description: None,
+                        writable: None,
+                    },
+                    CreateRegisterMapEntry {
+                        register_type: ModbusRegisterType::Holding,
+                        address: 2,
+                        name: "new_register_2".to_string(),
+                        data_type: ModbusDataType::Uint16,
+                        byte_order: None,
+                        scale_factor: None,
+                        offset: None,
+                        unit: None,
+                        description: None,
+                        writable: None,
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+
+        let fetched = service.get_register_map(&device.id).await.unwrap();
+        assert_eq!(fetched.len(), 2);
+        // Old register should be gone
+        assert!(fetched.iter().all(|r| r.name != "old_register"));
+        assert!(fetched.iter().any(|r| r.name == "new_register_1"));
+        assert!(fetched.iter().any(|r| r.name == "new_register_2"));
+    }
+
+    #[tokio::test]
+    async fn test_cascade_delete_register_maps() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Cascade Reg Device".to_string(),
+                device_id: "creg1".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        service
+            .set_register_map(
+                &device.id,
+                vec![CreateRegisterMapEntry {
+                    register_type: ModbusRegisterType::Input,
+                    address: 0,
+                    name: "temp".to_string(),
+                    data_type: ModbusDataType::Float32,
+                    byte_order: None,
+                    scale_factor: None,
+                    offset: None,
+                    unit: None,
+                    description: None,
+                    writable: None,
+                }],
+            )
+            .await
+            .unwrap();
+
+        // Delete device should cascade delete register maps
+        service.delete_device(&device.id).await.unwrap();
+
+        // Verify register maps are gone
+        let registers = service.get_register_map(&device.id).await.unwrap();
+        assert!(registers.is_empty());
     }
 
-    pub async fn delete_profile(&self, id: &str) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM device_profiles WHERE id = ?")
-            .bind(id)
-            .execute(&self.db)
-            .await?;
+    // ---- Fan Calibration Tests ----
 
-        Ok(result.rows_affected() > 0)
+    #[tokio::test]
+    async fn test_fan_calibration_and_interpolation() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+
+        let device = service
+            .create_device(CreateDeviceRequest {
+                name: "Roaster".to_string(),
+                device_id: "esp32-fan".to_string(),
+                profile_id: None,
+                description: None,
+                location: None,
+            })
+            .await
+            .unwrap();
+
+        let points = service
+            .set_fan_calibration(
+                &device.id,
+                vec![
+                    CreateFanCalibrationPoint {
+                        fan_pwm: 200,
+                        airflow: 60.0,
+                        static_pressure: Some(120.0),
+                    },
+                    CreateFanCalibrationPoint {
+                        fan_pwm: 100,
+                        airflow: 20.0,
+                        static_pressure: None,
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(points[0].fan_pwm, 100);
+
+        let curve = service
+            .get_fan_calibration_by_device_id("esp32-fan")
+            .await
+            .unwrap();
+        assert_eq!(curve.len(), 2);
+        assert_eq!(interpolate_airflow(&curve, 150), Some(40.0));
+        assert_eq!(interpolate_airflow(&curve, 50), Some(20.0));
+        assert_eq!(interpolate_airflow(&curve, 255), Some(60.0));
+        assert_eq!(interpolate_airflow(&[], 150), None);
     }
 
-    // ---- Device Connection CRUD ----
+    // ---- PID History Tests ----
 
-    pub async fn add_connection(
-        &self,
-        device_id: &str,
-        req: CreateConnectionRequest,
-    ) -> Result<DeviceConnection> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    #[tokio::test]
+    async fn test_pid_history_latest() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
 
-        let connection = sqlx::query_as::<_, DeviceConnection>(
-            r#"
-            INSERT INTO device_connections (id, device_id, protocol, enabled, priority, config, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-            RETURNING *
-            "#
-        )
-        .bind(&id)
-        .bind(device_id)
-        .bind(req.protocol.to_string())
-        .bind(req.enabled.unwrap_or(true))
-        .bind(req.priority.unwrap_or(0))
-        .bind(serde_json::to_string(&req.config)?)
-        .bind(now)
-        .bind(now)
-        .fetch_one(&self.db)
-        .await?;
+        assert!(service.latest_pid_gains("dev1").await.unwrap().is_none());
 
-        Ok(connection)
+        service
+            .record_pid_gains(
+                "dev1",
+                RecordPidGains {
+                    kp: 2.0,
+                    ki: 0.01,
+                    kd: 1.0,
+                    source: "manual",
+                    source_device_id: None,
+                    scale: None,
+                },
+            )
+            .await
+            .unwrap();
+        let copied = service
+            .record_pid_gains(
+                "dev2",
+                RecordPidGains {
+                    kp: 1.8,
+                    ki: 0.009,
+                    kd: 0.9,
+                    source: "copy",
+                    source_device_id: Some("dev1"),
+                    scale: Some(0.9),
+                },
+            )
+            .await
+            .unwrap();
+
+        let latest = service.latest_pid_gains("dev2").await.unwrap().unwrap();
+        assert_eq!(latest.id, copied.id);
+        assert_eq!(latest.source, "copy");
+        assert_eq!(latest.source_device_id.as_deref(), Some("dev1"));
+        assert_eq!(latest.scale, Some(0.9));
+
+        let history = service.list_pid_history("dev1", 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].source, "manual");
     }
 
-    pub async fn update_connection(
-        &self,
-        connection_id: &str,
-        req: UpdateConnectionRequest,
-    ) -> Result<Option<DeviceConnection>> {
-        if req.enabled.is_none() && req.priority.is_none() && req.config.is_none() {
-            let conn = sqlx::query_as::<_, DeviceConnection>(
-                "SELECT * FROM device_connections WHERE id = ?",
-            )
-            .bind(connection_id)
-            .fetch_optional(&self.db)
-            .await?;
-            return Ok(conn);
-        }
+    #[tokio::test]
+    async fn test_autotune_run_pairing() {
+        let pool = setup_test_db().await;
+        let service = DeviceService::new(pool);
+        let start = |target| StartAutotuneRun {
+            target_temperature: target,
+            mode: Some("relay"),
+            tuning_method: None,
+            requested_by: Some("alice"),
+        };
 
-        let now = Utc::now();
-        let mut query = "UPDATE device_connections SET updated_at = ?".to_string();
+        let run = service
+            .start_autotune_run("dev1", start(200.0))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.state, AutotuneRunState::Running);
 
-        if req.enabled.is_some() {
-            query.push_str(", enabled = ?");
-        }
-        if req.priority.is_some() {
-            query.push_str(", priority = ?");
-        }
-        if req.config.is_some() {
-            query.push_str(", config = ?");
-        }
+        // A second start while running is rejected, other devices are unaffected
+        assert!(service
+            .start_autotune_run("dev1", start(210.0))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service
+            .start_autotune_run("dev2", start(210.0))
+            .await
+            .unwrap()
+            .is_some());
 
-        query.push_str(" WHERE id = ? RETURNING *");
+        service.set_autotune_phase("dev1", "RUNNING").await.unwrap();
+        let results = serde_json::json!({"recommended_kp": 2.5});
+        let done = service
+            .finish_autotune_run("dev1", AutotuneRunState::Completed, Some(&results))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(done.id, run.id);
+        assert_eq!(done.target_temperature, 200.0);
+        assert_eq!(done.requested_by.as_deref(), Some("alice"));
+        assert_eq!(done.last_phase.as_deref(), Some("RUNNING"));
+        assert_eq!(done.results.map(|r| r.0), Some(results.clone()));
+        assert!(done.finished_at.is_some());
+
+        // Late results with nothing in flight are not paired
+        assert!(service
+            .finish_autotune_run("dev1", AutotuneRunState::Completed, Some(&results))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service.active_autotune_run("dev1").await.unwrap().is_none());
+        assert!(service
+            .start_autotune_run("dev1", start(220.0))
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            service.list_autotune_runs("dev1", 10).await.unwrap().len(),
+            2
+        );
+    }
 
-        let mut query_builder = sqlx::query_as::<_, DeviceConnection>(&query).bind(now);
+    // ---- Roast Profile CRUD Tests ----
 
-        if let Some(enabled) = req.enabled {
-            query_builder = query_builder.bind(enabled);
-        }
-        if let Some(priority) = req.priority {
-            query_builder = query_builder.bind(priority);
-        }
-        if let Some(ref config) = req.config {
-            query_builder = query_builder.bind(serde_json::to_string(config)?);
-        }
+    #[tokio::test]
+    async fn test_batch_scaled_profile_versions() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool);
 
-        query_builder = query_builder.bind(connection_id);
+        let original = service
+            .create_profile(CreateProfileRequest {
+                name: "House".to_string(),
+                description: None,
+                target_total_time: Some(660),
+                target_first_crack: None,
+                target_end_temp: Some(212.0),
+                preheat_temp: None,
+                charge_temp: Some(190.0),
+                batch_size_g: Some(250.0),
+                heater_cap: Some(80),
+                points: vec![CreateProfilePointRequest {
+                    time_seconds: 0,
+                    target_temp: 190.0,
+                    fan_speed: Some(60),
+                    notes: None,
+                    target_env_temp: None,
+                    target_airflow: None,
+                }],
+            })
+            .await
+            .unwrap();
 
-        let connection = query_builder.fetch_optional(&self.db).await?;
-        Ok(connection)
-    }
+        let rule = service
+            .upsert_batch_scaling_rule(
+                "roaster-1",
+                UpsertBatchScalingRuleRequest {
+                    charge_temp_per_100g: 4.0,
+                    heater_cap_per_100g: 6.0,
+                    min_heater_cap: Some(50),
+                    max_heater_cap: Some(90),
+                    max_charge_temp: Some(200.0),
+                },
+            )
+            .await
+            .unwrap();
 
-    pub async fn remove_connection(&self, connection_id: &str) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM device_connections WHERE id = ?")
-            .bind(connection_id)
-            .execute(&self.db)
-            .await?;
+        // Larger batch: hotter charge and more heater, both capped
+        let larger = rule.suggest(&original.profile, 250.0, 450.0);
+        assert_eq!(larger.charge_temp, Some(198.0));
+        assert_eq!(larger.heater_cap, 90);
+        let smaller = rule.suggest(&original.profile, 250.0, 150.0);
+        assert_eq!(smaller.charge_temp, Some(186.0));
+        assert_eq!(smaller.heater_cap, 74);
 
-        Ok(result.rows_affected() > 0)
+        let v2 = service
+            .create_scaled_profile_version(&original, &smaller, "House (150 g)")
+            .await
+            .unwrap();
+        assert_eq!(v2.profile.version, 2);
+        assert_eq!(
+            v2.profile.parent_profile_id.as_deref(),
+            Some(&*original.profile.id)
+        );
+        assert_eq!(v2.profile.batch_size_g, Some(150.0));
+        assert_eq!(v2.profile.heater_cap, Some(74));
+        assert_eq!(v2.points.len(), 1);
+
+        // Scaling a version keeps it in the original's family
+        let v3 = service
+            .create_scaled_profile_version(&v2, &larger, "House (450 g)")
+            .await
+            .unwrap();
+        assert_eq!(v3.profile.version, 3);
+        assert_eq!(
+            v3.profile.parent_profile_id.as_deref(),
+            Some(&*original.profile.id)
+        );
+
+        let versions = service.list_profile_versions(&v2.profile.id).await.unwrap();
+        let numbers: Vec<i32> = versions.iter().map(|p| p.version).collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        assert!(service
+            .list_profile_versions("missing")
+            .await
+            .unwrap()
+            .is_empty());
     }
 
-    // ---- Register Map CRUD ----
+    #[tokio::test]
+    async fn test_profile_update_round_trip() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool);
 
-    pub async fn get_register_map(&self, device_id: &str) -> Result<Vec<ModbusRegisterMap>> {
-        let registers = sqlx::query_as::<_, ModbusRegisterMap>(
-            "SELECT * FROM modbus_register_maps WHERE device_id = ? ORDER BY register_type, address"
-        )
-        .bind(device_id)
-        .fetch_all(&self.db)
-        .await?;
+        // Create a profile with initial points
+        let created = service
+            .create_profile(CreateProfileRequest {
+                name: "Original Profile".to_string(),
+                description: Some("Initial description".to_string()),
+                target_total_time: Some(600),
+                target_first_crack: None,
+                target_end_temp: Some(210.0),
+                preheat_temp: None,
+                charge_temp: Some(180.0),
+                batch_size_g: None,
+                heater_cap: None,
+                points: vec![
+                    CreateProfilePointRequest {
+                        time_seconds: 0,
+                        target_temp: 180.0,
+                        fan_speed: Some(80),
+                        notes: None,
+                        target_env_temp: None,
+                        target_airflow: None,
+                    },
+                    CreateProfilePointRequest {
+                        time_seconds: 300,
+                        target_temp: 200.0,
+                        fan_speed: None,
+                        notes: None,
+                        target_env_temp: None,
+                        target_airflow: None,
+                    },
+                ],
+            })
+            .await
+            .unwrap();
 
-        Ok(registers)
-    }
+        assert_eq!(created.profile.name, "Original Profile");
+        assert_eq!(created.points.len(), 2);
+
+        // Update the profile with new metadata and different points
+        let updated = service
+            .update_profile(
+                &created.profile.id,
+                CreateProfileRequest {
+                    name: "Updated Profile".to_string(),
+                    description: Some("Updated description".to_string()),
+                    target_total_time: Some(720),
+                    target_first_crack: Some(360),
+                    target_end_temp: Some(220.0),
+                    preheat_temp: None,
+                    charge_temp: Some(185.0),
+                    batch_size_g: None,
+                    heater_cap: None,
+                    points: vec![
+                        CreateProfilePointRequest {
+                            time_seconds: 0,
+                            target_temp: 185.0,
+                            fan_speed: Some(90),
+                            notes: None,
+                            target_env_temp: None,
+                            target_airflow: None,
+                        },
+                        CreateProfilePointRequest {
+                            time_seconds: 200,
+                            target_temp: 195.0,
+                            fan_speed: None,
+                            notes: None,
+                            target_env_temp: None,
+                            target_airflow: None,
+                        },
+                        CreateProfilePointRequest {
+                            time_seconds: 500,
+                            target_temp: 220.0,
+                            fan_speed: Some(60),
+                            notes: Some("Finish".to_string()),
+                            target_env_temp: None,
+                            target_airflow: None,
+                        },
+                    ],
+                },
+            )
+            .await
+            .unwrap();
 
-    pub async fn set_register_map(
-        &self,
-        device_id: &str,
-        registers: Vec<CreateRegisterMapEntry>,
-    ) -> Result<Vec<ModbusRegisterMap>> {
-        // Delete existing register map and insert new entries in a transaction
-        let mut tx = self.db.begin().await?;
+        let updated = updated.expect("Profile should exist");
+        assert_eq!(updated.profile.name, "Updated Profile");
+        assert_eq!(
+            updated.profile.description,
+            Some("Updated description".to_string())
+        );
+        assert_eq!(updated.profile.target_total_time, Some(720));
+        assert_eq!(updated.profile.target_first_crack, Some(360));
+        assert_eq!(updated.profile.charge_temp, Some(185.0));
+        assert_eq!(updated.points.len(), 3);
+        assert_eq!(updated.points[0].target_temp, 185.0);
+        assert_eq!(updated.points[2].notes, Some("Finish".to_string()));
 
-        sqlx::query("DELETE FROM modbus_register_maps WHERE device_id = ?")
-            .bind(device_id)
-            .execute(&mut *tx)
-            .await?;
+        // Verify old points were replaced (not accumulated)
+        let fetched = service
+            .get_profile_with_points(&created.profile.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.points.len(), 3);
 
-        let mut result = Vec::new();
-        for entry in registers {
-            let id = Uuid::new_v4().to_string();
-            let register = sqlx::query_as::<_, ModbusRegisterMap>(
-                r#"
-                INSERT INTO modbus_register_maps (
-                    id, device_id, register_type, address, name, data_type,
-                    byte_order, scale_factor, offset, unit, description, writable
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                RETURNING *
-                "#,
+        // Update non-existent profile returns None
+        let missing = service
+            .update_profile(
+                "nonexistent-id",
+                CreateProfileRequest {
+                    name: "Ghost".to_string(),
+                    description: None,
+                    target_total_time: None,
+                    target_first_crack: None,
+                    target_end_temp: None,
+                    preheat_temp: None,
+                    charge_temp: None,
+                    batch_size_g: None,
+                    heater_cap: None,
+                    points: vec![],
+                },
             )
-            .bind(&id)
-            .bind(device_id)
-            .bind(entry.register_type.to_string())
-            .bind(entry.address)
-            .bind(&entry.name)
-            .bind(entry.data_type.to_string())
-            .bind(&entry.byte_order)
-            .bind(entry.scale_factor)
-            .bind(entry.offset)
-            .bind(&entry.unit)
-            .bind(&entry.description)
-            .bind(entry.writable.unwrap_or(false))
-            .fetch_one(&mut *tx)
-            .await?;
-
-            result.push(register);
-        }
+            .await
+            .unwrap();
 
-        tx.commit().await?;
-        Ok(result)
+        assert!(missing.is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use sqlx::SqlitePool;
+    // ---- Analytics Tests ----
 
-    async fn setup_test_db() -> SqlitePool {
-        let pool = sqlx::sqlite::SqlitePoolOptions::new()
-            .max_connections(1)
-            .connect("sqlite::memory:")
+    #[tokio::test]
+    async fn test_profile_recommendations_rank_by_similar_bean_outcomes() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool.clone());
+
+        let bean = |name: &str, origin: &str, process: &str, density: f32| CreateBeanRequest {
+            name: name.to_string(),
+            origin: Some(origin.to_string()),
+            variety: None,
+            process: Some(process.to_string()),
+            density: Some(density),
+            moisture_pct: Some(10.5),
+            notes: None,
+            cost_per_kg: None,
+        };
+        let yirga = service
+            .create_bean(bean("Yirgacheffe", "Ethiopia", "washed", 720.0))
             .await
-            .expect("Failed to create test db");
-
-        // Enable foreign keys for tests
-        sqlx::query("PRAGMA foreign_keys = ON;")
-            .execute(&pool)
+            .unwrap();
+        let guji = service
+            .create_bean(bean("Guji", "ethiopia", "Washed", 700.0))
             .await
-            .expect("Failed to enable foreign keys");
+            .unwrap();
+        let cerrado = service
+            .create_bean(bean("Cerrado", "Brazil", "natural", 650.0))
+            .await
+            .unwrap();
 
-        // Run migrations
-        let migrations: &[&str] = &[
-            include_str!("../migrations/001_roast_sessions.sql"),
-            include_str!("../migrations/002_roast_events.sql"),
-            include_str!("../migrations/003_device_configuration.sql"),
-            include_str!("../migrations/004_session_statistics.sql"),
-            include_str!("../migrations/005_auc_value.sql"),
-            include_str!("../migrations/006_cupping_scores.sql"),
-            include_str!("../migrations/007_profile_env_temp.sql"),
-        ];
-        for migration_sql in migrations {
-            for statement in migration_sql.split(';') {
-                let statement = statement.trim();
-                if !statement.is_empty() {
-                    let _ = sqlx::query(statement).execute(&pool).await;
-                }
-            }
+        let mut profiles = Vec::new();
+        for name in ["Light", "Medium", "Dark"] {
+            let profile = service
+                .create_profile(CreateProfileRequest {
+                    name: name.to_string(),
+                    description: None,
+                    target_total_time: None,
+                    target_first_crack: None,
+                    target_end_temp: None,
+                    preheat_temp: None,
+                    charge_temp: None,
+                    batch_size_g: None,
+                    heater_cap: None,
+                    points: vec![],
+                })
+                .await
+                .unwrap();
+            profiles.push(profile.profile.id);
         }
 
-        // Create settings table and seed defaults (mirrors init_db)
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS settings (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL,
-                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
-            );",
-        )
-        .execute(&pool)
-        .await
-        .expect("Failed to create settings table");
-        for (key, value) in [
-            ("profile_lookahead_seconds", "20"),
-            ("auc_base_temp", "0"),
-            ("auc_start_event", "charge"),
-            ("ror_window_seconds", "30"),
-            ("ror_smoothing_algorithm", "moving_average"),
-            ("auto_dry_temp", "150"),
-            ("auto_event_detection", "true"),
-            ("alarm_sound_enabled", "true"),
-            (
-                "roast_alarms",
-                r#"[{"name":"High Temp Warning","condition_type":"temp_above","threshold":230,"enabled":true},{"name":"FC Approaching","condition_type":"temp_above","threshold":195,"enabled":true},{"name":"Low RoR Warning","condition_type":"ror_below","threshold":5.0,"reference_event":"first_crack_start","enabled":true}]"#,
-            ),
-        ] {
-            let _ = sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES (?, ?)")
-                .bind(key)
-                .bind(value)
-                .execute(&pool)
-                .await;
+        // (profile, bean, cupping score)
+        let roasts = [
+            (0, &yirga, Some(8.5)),
+            (0, &guji, Some(8.0)),
+            (1, &yirga, Some(7.0)),
+            (1, &guji, None),
+            (2, &cerrado, Some(9.0)),
+        ];
+        for (profile, bean, cupping) in roasts {
+            let session = service
+                .create_session(CreateSessionRequest {
+                    name: bean.name.clone(),
+                    device_id: "esp32-001".to_string(),
+                    profile_id: Some(profiles[profile].clone()),
+                    bean_origin: None,
+                    bean_variety: None,
+                    green_weight: None,
+                    target_roast_level: None,
+                    notes: None,
+                    ambient_temp: None,
+                    humidity: None,
+                    roaster: None,
+                    session_type: SessionType::Profile,
+                    bean_id: Some(bean.id.clone()),
+                })
+                .await
+                .unwrap();
+            assert_eq!(session.bean_origin, bean.origin);
+            service.start_session(&session.id).await.unwrap().unwrap();
+            service
+                .complete_session(&session.id)
+                .await
+                .unwrap()
+                .unwrap();
+            if let Some(score) = cupping {
+                service
+                    .create_cupping(
+                        &session.id,
+                        CreateCuppingRequest {
+                            scoring_framework: None,
+                            notes: None,
+                            attributes: vec![CreateCuppingAttributeRequest {
+                                name: "overall".to_string(),
+                                score,
+                            }],
+                        },
+                    )
+                    .await
+                    .unwrap();
+            }
         }
 
-        pool
+        let target = BeanAttributes::from(&yirga);
+        let recs = service.recommend_profiles(&target, 5).await.unwrap();
+        // The Brazil natural is too different to count
+        let ids: Vec<&str> = recs.iter().map(|r| r.profile_id.as_str()).collect();
+        assert_eq!(ids, [profiles[0].as_str(), profiles[1].as_str()]);
+        assert_eq!(recs[0].similar_sessions, 2);
+        assert_eq!(recs[1].cupped_sessions, 1);
+        assert!(recs[0].avg_cupping_score.unwrap() > 8.0);
+        assert!(recs[0].similarity < 1.0 && recs[0].similarity > 0.9);
+
+        let natural = BeanAttributes {
+            process: Some("natural".to_string()),
+            ..Default::default()
+        };
+        let recs = service.recommend_profiles(&natural, 5).await.unwrap();
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0].profile_id, profiles[2]);
     }
 
-    // ---- Device CRUD Tests ----
-
     #[tokio::test]
-    async fn test_create_and_get_device() {
+    async fn test_sample_roasts_group_in_threes_for_evaluation() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
-
-        let req = CreateDeviceRequest {
-            name: "Test Roaster".to_string(),
+        let service = RoastSessionService::new(pool.clone());
+
+        let bean = service
+            .create_bean(CreateBeanRequest {
+                name: "Sidamo".to_string(),
+                origin: Some("Ethiopia".to_string()),
+                variety: None,
+                process: None,
+                density: None,
+                moisture_pct: None,
+                notes: None,
+                cost_per_kg: None,
+            })
+            .await
+            .unwrap();
+        let mut profiles = Vec::new();
+        for name in ["Light", "Medium", "Dark"] {
+            let profile = service
+                .create_profile(CreateProfileRequest {
+                    name: name.to_string(),
+                    description: None,
+                    target_total_time: None,
+                    target_first_crack: None,
+                    target_end_temp: None,
+                    preheat_temp: None,
+                    charge_temp: None,
+                    batch_size_g: None,
+                    heater_cap: None,
+                    points: vec![],
+                })
+                .await
+                .unwrap();
+            profiles.push(profile.profile.id);
+        }
+        let sample = |profile: usize| CreateSessionRequest {
+            name: String::new(),
             device_id: "esp32-001".to_string(),
-            profile_id: None,
-            description: Some("A test device".to_string()),
-            location: Some("Kitchen".to_string()),
+            profile_id: Some(profiles[profile].clone()),
+            bean_origin: None,
+            bean_variety: None,
+            green_weight: None,
+            target_roast_level: None,
+            notes: None,
+            ambient_temp: None,
+            humidity: None,
+            roaster: None,
+            session_type: SessionType::Sample,
+            bean_id: Some(bean.id.clone()),
         };
 
-        let device = service
-            .create_device(req)
+        let first = service.create_session(sample(0)).await.unwrap();
+        assert_eq!(first.name, "Sidamo sample");
+        assert_eq!(first.green_weight, Some(100.0));
+        let group = first.sample_group_id.clone().unwrap();
+        // The same profile again starts a second group
+        let repeat = service.create_session(sample(0)).await.unwrap();
+        assert_ne!(repeat.sample_group_id.as_deref(), Some(group.as_str()));
+        let second = service.create_session(sample(1)).await.unwrap();
+        let third = service.create_session(sample(2)).await.unwrap();
+        assert_eq!(second.sample_group_id.as_deref(), Some(group.as_str()));
+        assert_eq!(third.sample_group_id.as_deref(), Some(group.as_str()));
+
+        let groups = service.list_sample_groups(&bean.id).await.unwrap();
+        assert_eq!(groups.len(), 2);
+        let full = groups.iter().find(|g| g.group.id == group).unwrap();
+        assert_eq!((full.sample_count, full.complete), (3, false));
+
+        for (session, score) in [(&first, 7.5), (&second, 8.25), (&third, 6.0)] {
+            service.start_session(&session.id).await.unwrap().unwrap();
+            if session.id == second.id {
+                service
+                    .create_roast_event(
+                        &session.id,
+                        CreateRoastEventRequest {
+                            event_type: RoastEventType::Drop,
+                            elapsed_seconds: 600.0,
+                            temperature: Some(212.5),
+                            notes: None,
+                        },
+                    )
+                    .await
+                    .unwrap();
+            }
+            service
+                .complete_session(&session.id)
+                .await
+                .unwrap()
+                .unwrap();
+            service
+                .create_cupping(
+                    &session.id,
+                    CreateCuppingRequest {
+                        scoring_framework: None,
+                        notes: None,
+                        attributes: vec![CreateCuppingAttributeRequest {
+                            name: "overall".to_string(),
+                            score,
+                        }],
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        let sheet = service
+            .get_sample_evaluation(&group)
             .await
-            .expect("create_device failed");
-        assert_eq!(device.name, "Test Roaster");
-        assert_eq!(device.device_id, "esp32-001");
-        assert_eq!(device.status, DeviceStatus::Pending);
-        assert_eq!(device.description, Some("A test device".to_string()));
-        assert_eq!(device.location, Some("Kitchen".to_string()));
+            .unwrap()
+            .unwrap();
+        assert!(sheet.complete);
+        assert_eq!(sheet.bean.unwrap().id, bean.id);
+        assert_eq!(sheet.samples.len(), 3);
+        assert_eq!(sheet.samples[1].profile_name.as_deref(), Some("Medium"));
+        assert_eq!(sheet.samples[1].drop_temp, Some(212.5));
+        assert_eq!(sheet.samples[0].drop_temp, None);
+        assert_eq!(sheet.best_session_id.as_deref(), Some(second.id.as_str()));
+        assert!(service
+            .get_sample_evaluation("missing")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_trends_grouped_by_bean_and_week() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool.clone());
+
+        // (origin, start time, development ratio, completed)
+        let roasts = [
+            ("Ethiopia", "2026-03-02T10:00:00Z", 0.20, true),
+            ("Ethiopia", "2026-03-04T10:00:00Z", 0.22, true),
+            ("Ethiopia", "2026-03-10T10:00:00Z", 0.18, true),
+            ("Colombia", "2026-03-03T10:00:00Z", 0.25, true),
+            ("Colombia", "2026-03-05T10:00:00Z", 0.30, false),
+        ];
+        for (origin, start, dtr, completed) in roasts {
+            let session = service
+                .create_session(CreateSessionRequest {
+                    name: format!("{origin} {start}"),
+                    device_id: "esp32-001".to_string(),
+                    profile_id: None,
+                    bean_origin: Some(origin.to_string()),
+                    bean_variety: None,
+                    green_weight: None,
+                    target_roast_level: None,
+                    notes: None,
+                    ambient_temp: None,
+                    humidity: None,
+                    roaster: None,
+                    session_type: SessionType::Profile,
+                    bean_id: None,
+                })
+                .await
+                .unwrap();
+            service.start_session(&session.id).await.unwrap().unwrap();
+            service
+                .update_session(
+                    &session.id,
+                    UpdateSessionRequest {
+                        name: None,
+                        roaster: None,
+                        roasted_weight: None,
+                        notes: None,
+                        first_crack_time: None,
+                        development_time_ratio: Some(dtr),
+                    },
+                )
+                .await
+                .unwrap();
+            if completed {
+                service
+                    .complete_session(&session.id)
+                    .await
+                    .unwrap()
+                    .unwrap();
+            }
+            let start: DateTime<Utc> = start.parse().unwrap();
+            sqlx::query("UPDATE roast_sessions SET start_time = ? WHERE id = ?")
+                .bind(start)
+                .bind(&session.id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
 
-        // Get by id
-        let fetched = service
-            .get_device(&device.id)
+        crate::summaries::refresh(&pool).await.unwrap();
+        let trends = service
+            .session_trends(&TrendQuery {
+                metric: TrendMetric::DevRatio,
+                group_by: TrendGroupBy::Bean,
+                from: None,
+                to: None,
+            })
             .await
-            .expect("get_device failed");
-        assert!(fetched.is_some());
-        let fetched = fetched.unwrap();
-        assert_eq!(fetched.device.name, "Test Roaster");
-        assert!(fetched.connections.is_empty());
-
-        // Get by device_id
-        let fetched = service
-            .get_device_by_device_id("esp32-001")
+            .unwrap();
+        assert_eq!(trends.series.len(), 2);
+        let colombia = &trends.series[0];
+        assert_eq!(colombia.key.as_deref(), Some("Colombia"));
+        assert_eq!(colombia.points.len(), 1);
+        assert_eq!(colombia.points[0].count, 1);
+        let ethiopia = &trends.series[1];
+        let weeks: Vec<&str> = ethiopia.points.iter().map(|p| p.week.as_str()).collect();
+        assert_eq!(weeks, ["2026-03-02", "2026-03-09"]);
+        assert_eq!(ethiopia.points[0].count, 2);
+        assert!((ethiopia.points[0].avg - 0.21).abs() < 1e-6);
+        assert!((ethiopia.points[0].max - 0.22).abs() < 1e-6);
+
+        // Weekly series over all beans, limited to the first week
+        let trends = service
+            .session_trends(&TrendQuery {
+                metric: TrendMetric::DevRatio,
+                group_by: TrendGroupBy::Week,
+                from: None,
+                to: Some("2026-03-09T00:00:00Z".parse().unwrap()),
+            })
             .await
-            .expect("get_device_by_device_id failed");
-        assert!(fetched.is_some());
-        assert_eq!(fetched.unwrap().device.id, device.id);
+            .unwrap();
+        assert_eq!(trends.series.len(), 1);
+        assert_eq!(trends.series[0].key, None);
+        assert_eq!(trends.series[0].points.len(), 1);
+        assert_eq!(trends.series[0].points[0].count, 3);
+
+        let summary = service
+            .session_summary(&SummaryQuery {
+                group_by: SummaryGroupBy::Bean,
+                from: None,
+                to: None,
+            })
+            .await
+            .unwrap();
+        let sessions: Vec<(Option<&str>, i64)> = summary
+            .rows
+            .iter()
+            .map(|r| (r.key.as_deref(), r.sessions))
+            .collect();
+        assert_eq!(sessions, [(Some("Colombia"), 1), (Some("Ethiopia"), 3)]);
     }
 
+    // ---- Profile Scoreboard Tests ----
+
     #[tokio::test]
-    async fn test_list_devices_with_status_filter() {
+    async fn test_profile_scoreboard_live_and_on_completion() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
-
-        // Create two devices
-        service
-            .create_device(CreateDeviceRequest {
-                name: "Device 1".to_string(),
-                device_id: "d1".to_string(),
-                profile_id: None,
+        let service = RoastSessionService::new(pool);
+        let point = |time_seconds, target_temp| CreateProfilePointRequest {
+            time_seconds,
+            target_temp,
+            fan_speed: None,
+            notes: None,
+            target_env_temp: None,
+            target_airflow: None,
+        };
+        // Linear 100 -> 220 °C over 10 minutes, first crack due at 400 s (180 °C)
+        let profile = service
+            .create_profile(CreateProfileRequest {
+                name: "Linear".to_string(),
                 description: None,
-                location: None,
+                target_total_time: Some(600),
+                target_first_crack: Some(400),
+                target_end_temp: Some(220.0),
+                preheat_temp: None,
+                charge_temp: None,
+                batch_size_g: None,
+                heater_cap: None,
+                points: vec![point(0, 100.0), point(600, 220.0)],
             })
             .await
             .unwrap();
-
-        let device2 = service
-            .create_device(CreateDeviceRequest {
-                name: "Device 2".to_string(),
-                device_id: "d2".to_string(),
-                profile_id: None,
-                description: None,
-                location: None,
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Following".to_string(),
+                device_id: "esp32-001".to_string(),
+                profile_id: Some(profile.profile.id.clone()),
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
+        service.start_session(&session.id).await.unwrap().unwrap();
+
+        // Running 5 °C hot at the profile's 12 °C/min
+        let add_until = |from: i32, to: i32| {
+            let service = &service;
+            let id = session.id.clone();
+            async move {
+                for t in (from..=to).step_by(10) {
+                    let elapsed = t as f32;
+                    let bean = 105.0 + elapsed / 600.0 * 120.0;
+                    service
+                        .add_telemetry_point(&id, elapsed, Some(bean), None, None, None, None, None)
+                        .await
+                        .unwrap();
+                }
+            }
+        };
+        add_until(0, 300).await;
 
-        // Activate device 2
-        service
-            .update_device_status(&device2.id, DeviceStatus::Active)
+        let close = |a: Option<f32>, b: f32| (a.unwrap() - b).abs() < 0.01;
+        let live = service
+            .get_session_with_telemetry(&session.id)
             .await
+            .unwrap()
+            .unwrap()
+            .scoreboard
             .unwrap();
+        assert!(close(live.deviation, 5.0));
+        assert!(close(Some(live.deviation_integral), 25.0));
+        assert!(close(live.ror, 12.0));
+        // 165 °C now, 15 °C to first crack at 12 °C/min
+        assert!(close(live.projected_first_crack, 375.0));
+        assert!(close(live.first_crack_delta, -25.0));
+        assert!(close(live.projected_drop_time, 575.0));
+        assert!(close(live.projected_drop_temp, 225.0));
+        assert!(close(live.drop_temp_delta, 5.0));
 
-        // List all
-        let all = service.list_devices(None).await.unwrap();
-        assert_eq!(all.len(), 2);
-
-        // List only pending
-        let pending = service
-            .list_devices(Some(DeviceStatus::Pending))
+        service
+            .create_roast_event(
+                &session.id,
+                CreateRoastEventRequest {
+                    event_type: RoastEventType::FirstCrackStart,
+                    elapsed_seconds: 380.0,
+                    temperature: None,
+                    notes: None,
+                },
+            )
             .await
             .unwrap();
-        assert_eq!(pending.len(), 1);
-        assert_eq!(pending[0].device_id, "d1");
+        add_until(310, 600).await;
 
-        // List only active
-        let active = service
-            .list_devices(Some(DeviceStatus::Active))
+        let completed = service
+            .complete_session(&session.id)
             .await
+            .unwrap()
             .unwrap();
-        assert_eq!(active.len(), 1);
-        assert_eq!(active[0].device_id, "d2");
+        assert!(close(completed.profile_fc_delta, -20.0));
+        assert!(close(completed.profile_drop_time_delta, 0.0));
+        assert!(close(completed.profile_drop_temp_delta, 5.0));
+        assert!(close(completed.profile_deviation_integral, 50.0));
     }
 
     #[tokio::test]
-    async fn test_update_device() {
+    async fn test_profile_scoreboard_starts_at_charge() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
-
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Original".to_string(),
-                device_id: "dev1".to_string(),
-                profile_id: None,
+        let service = RoastSessionService::new(pool);
+        sqlx::query(
+            "INSERT INTO settings (key, value, updated_at) VALUES ('profile_charge_offset_secs', '5', datetime('now'))",
+        )
+        .execute(&service.db)
+        .await
+        .unwrap();
+        let point = |time_seconds, target_temp| CreateProfilePointRequest {
+            time_seconds,
+            target_temp,
+            fan_speed: None,
+            notes: None,
+            target_env_temp: None,
+            target_airflow: None,
+        };
+        let profile = service
+            .create_profile(CreateProfileRequest {
+                name: "Linear".to_string(),
                 description: None,
-                location: None,
+                target_total_time: Some(600),
+                target_first_crack: Some(400),
+                target_end_temp: Some(220.0),
+                preheat_temp: None,
+                charge_temp: None,
+                batch_size_g: None,
+                heater_cap: None,
+                points: vec![point(0, 100.0), point(600, 220.0)],
             })
             .await
             .unwrap();
-
-        let updated = service
-            .update_device(
-                &device.id,
-                UpdateDeviceRequest {
-                    name: Some("Updated Name".to_string()),
-                    profile_id: None,
-                    status: Some(DeviceStatus::Active),
-                    description: Some("Now with description".to_string()),
-                    location: None,
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Late charge".to_string(),
+                device_id: "esp32-001".to_string(),
+                profile_id: Some(profile.profile.id.clone()),
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
+            })
+            .await
+            .unwrap();
+        service.start_session(&session.id).await.unwrap().unwrap();
+        service
+            .create_roast_event(
+                &session.id,
+                CreateRoastEventRequest {
+                    event_type: RoastEventType::Charge,
+                    elapsed_seconds: 60.0,
+                    temperature: None,
+                    notes: None,
                 },
             )
             .await
             .unwrap();
+        // Idling for a minute, then 5 °C hot from 65 s (charge + offset) on
+        for t in (0..=365).step_by(5) {
+            let elapsed = t as f32;
+            let bean = if elapsed < 65.0 {
+                60.0
+            } else {
+                105.0 + (elapsed - 65.0) / 600.0 * 120.0
+            };
+            service
+                .add_telemetry_point(
+                    &session.id,
+                    elapsed,
+                    Some(bean),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
 
-        assert!(updated.is_some());
-        let updated = updated.unwrap();
-        assert_eq!(updated.name, "Updated Name");
-        assert_eq!(updated.status, DeviceStatus::Active);
+        let close = |a: Option<f32>, b: f32| (a.unwrap() - b).abs() < 0.01;
+        let live = service.session_scoreboard(&session).await.unwrap().unwrap();
+        assert_eq!(live.profile_zero, 65.0);
+        assert!(close(live.deviation, 5.0));
+        assert!(close(Some(live.deviation_integral), 25.0));
+        assert!(close(live.target_first_crack, 465.0));
+        assert!(close(live.first_crack_delta, -25.0));
+        assert!(close(live.target_drop_time, 665.0));
+    }
+
+    #[tokio::test]
+    async fn test_roaster_ror_settings_override_smoothing() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool);
+        let config = SmoothingConfig {
+            bt_window_secs: 5.0,
+            ..SmoothingConfig::default()
+        };
+        assert!(service.get_ror_settings("fluid-1").await.unwrap().is_none());
         assert_eq!(
-            updated.description,
-            Some("Now with description".to_string())
+            service.with_ror_settings("fluid-1", config).await.unwrap(),
+            config
+        );
+
+        let settings = service
+            .upsert_ror_settings(
+                "fluid-1",
+                UpsertRorSettingsRequest {
+                    window_secs: 10.0,
+                    unit: RorUnit::Per30Secs,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(settings.unit, RorUnit::Per30Secs);
+        let applied = service.with_ror_settings("fluid-1", config).await.unwrap();
+        assert_eq!(applied.bt_window_secs, 5.0);
+        assert_eq!(applied.ror_window_secs, 10.0);
+        assert_eq!(applied.ror_unit, RorUnit::Per30Secs);
+        // Other roasters keep the caller's smoothing
+        assert_eq!(
+            service.with_ror_settings("drum-1", config).await.unwrap(),
+            config
         );
+
+        assert!(service.delete_ror_settings("fluid-1").await.unwrap());
+        assert!(!service.delete_ror_settings("fluid-1").await.unwrap());
     }
 
+    // ---- Session Completion Statistics Tests ----
+
     #[tokio::test]
-    async fn test_delete_device() {
+    async fn test_complete_session_computes_dtr_and_weight_loss() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        let service = RoastSessionService::new(pool);
 
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "To Delete".to_string(),
-                device_id: "del1".to_string(),
+        // Create a session with green and roasted weights
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Stats Test Roast".to_string(),
+                device_id: "esp32-001".to_string(),
                 profile_id: None,
-                description: None,
-                location: None,
+                bean_origin: Some("Ethiopia".to_string()),
+                bean_variety: Some("Yirgacheffe".to_string()),
+                green_weight: Some(200.0),
+                target_roast_level: Some("medium".to_string()),
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
 
-        assert!(service.delete_device(&device.id).await.unwrap());
-        assert!(!service.delete_device(&device.id).await.unwrap()); // Already deleted
-
-        let fetched = service.get_device(&device.id).await.unwrap();
-        assert!(fetched.is_none());
-    }
-
-    #[tokio::test]
-    async fn test_update_last_seen() {
-        let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
-
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Seen Device".to_string(),
-                device_id: "seen1".to_string(),
-                profile_id: None,
-                description: None,
-                location: None,
-            })
+        // Start the session
+        let session = service.start_session(&session.id).await.unwrap().unwrap();
+        assert_eq!(session.status, SessionStatus::Active);
+
+        // Set roasted weight via update
+        service
+            .update_session(
+                &session.id,
+                UpdateSessionRequest {
+                    name: None,
+                    roaster: None,
+                    roasted_weight: Some(170.0),
+                    notes: None,
+                    first_crack_time: None,
+                    development_time_ratio: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        // Add telemetry points spanning 600 seconds with varying RoR
+        for i in 0..=600 {
+            if i % 10 == 0 {
+                let elapsed = i as f32;
+                let bean_temp = 100.0 + (elapsed / 600.0) * 120.0; // 100→220°C
+                let ror = if elapsed < 200.0 {
+                    15.0 // drying phase
+                } else if elapsed < 400.0 {
+                    12.0 // maillard phase
+                } else {
+                    8.0 // development phase
+                };
+                service
+                    .add_telemetry_point(
+                        &session.id,
+                        elapsed,
+                        Some(bean_temp),
+                        Some(bean_temp + 20.0),
+                        Some(ror),
+                        Some(80),
+                        Some(200),
+                        Some(bean_temp),
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+
+        // Add roast events for phase boundaries
+        service
+            .create_roast_event(
+                &session.id,
+                CreateRoastEventRequest {
+                    event_type: RoastEventType::DryingEnd,
+                    elapsed_seconds: 200.0,
+                    temperature: Some(140.0),
+                    notes: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        service
+            .create_roast_event(
+                &session.id,
+                CreateRoastEventRequest {
+                    event_type: RoastEventType::FirstCrackStart,
+                    elapsed_seconds: 400.0,
+                    temperature: Some(200.0),
+                    notes: None,
+                },
+            )
             .await
             .unwrap();
 
-        assert!(device.last_seen_at.is_none());
-
-        service.update_last_seen("seen1").await.unwrap();
-
-        let fetched = service.get_device(&device.id).await.unwrap().unwrap();
-        assert!(fetched.device.last_seen_at.is_some());
-    }
+        // Complete the session
+        let completed = service
+            .complete_session(&session.id)
+            .await
+            .unwrap()
+            .expect("session should be returned");
 
-    // ---- Device Profile Tests ----
+        assert_eq!(completed.status, SessionStatus::Completed);
 
-    #[tokio::test]
-    async fn test_create_and_list_profiles() {
-        let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        // DTR: (600 - 400) / 600 = 200/600 ≈ 0.333
+        let dtr = completed.development_time_ratio.unwrap();
+        assert!(
+            (dtr - 0.333).abs() < 0.01,
+            "DTR should be ~0.333, got {dtr}"
+        );
 
-        let profile = service
-            .create_profile(CreateDeviceProfileRequest {
-                name: "Default Profile".to_string(),
-                description: Some("Standard roasting config".to_string()),
-                default_control_mode: Some("auto".to_string()),
-                default_setpoint: Some(200.0),
-                default_fan_pwm: Some(180),
-                default_kp: Some(15.0),
-                default_ki: Some(1.0),
-                default_kd: Some(25.0),
-                max_temp: Some(240.0),
-                min_fan_pwm: Some(100),
-                telemetry_interval_ms: Some(1000),
-            })
-            .await
-            .unwrap();
+        // Weight loss: (200 - 170) / 200 * 100 = 15%
+        let wl = completed.weight_loss_pct.unwrap();
+        assert!(
+            (wl - 15.0).abs() < 0.1,
+            "Weight loss should be 15%, got {wl}"
+        );
 
-        assert_eq!(profile.name, "Default Profile");
-        assert_eq!(profile.default_setpoint, Some(200.0));
+        // Max RoR should be 15.0
+        let max_ror = completed.max_ror.unwrap();
+        assert!(
+            (max_ror - 15.0).abs() < 0.1,
+            "Max RoR should be 15.0, got {max_ror}"
+        );
 
-        let profiles = service.list_profiles().await.unwrap();
-        assert_eq!(profiles.len(), 1);
+        // First crack time should be 400 seconds
+        assert_eq!(completed.first_crack_time, Some(400));
 
-        let fetched = service.get_profile(&profile.id).await.unwrap();
-        assert!(fetched.is_some());
-        assert_eq!(fetched.unwrap().name, "Default Profile");
-    }
+        // Drying end fields
+        assert_eq!(completed.drying_end_time, Some(200));
+        assert_eq!(completed.drying_end_temp, Some(140.0));
 
-    #[tokio::test]
-    async fn test_delete_profile() {
-        let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        // Phase avg RoR values
+        let avg_dry = completed.avg_ror_drying.unwrap();
+        assert!(
+            (avg_dry - 15.0).abs() < 0.1,
+            "Avg RoR drying should be ~15.0, got {avg_dry}"
+        );
 
-        let profile = service
-            .create_profile(CreateDeviceProfileRequest {
-                name: "Temp Profile".to_string(),
-                description: None,
-                default_control_mode: None,
-                default_setpoint: None,
-                default_fan_pwm: None,
-                default_kp: None,
-                default_ki: None,
-                default_kd: None,
-                max_temp: None,
-                min_fan_pwm: None,
-                telemetry_interval_ms: None,
-            })
-            .await
-            .unwrap();
+        let avg_mail = completed.avg_ror_maillard.unwrap();
+        assert!(
+            (avg_mail - 12.0).abs() < 0.1,
+            "Avg RoR maillard should be ~12.0, got {avg_mail}"
+        );
 
-        assert!(service.delete_profile(&profile.id).await.unwrap());
-        assert!(!service.delete_profile(&profile.id).await.unwrap());
+        let avg_dev = completed.avg_ror_development.unwrap();
+        assert!(
+            (avg_dev - 8.0).abs() < 0.1,
+            "Avg RoR development should be ~8.0, got {avg_dev}"
+        );
     }
 
     #[tokio::test]
-    async fn test_device_with_profile() {
+    async fn test_complete_session_computes_auc() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        let service = RoastSessionService::new(pool);
 
-        let profile = service
-            .create_profile(CreateDeviceProfileRequest {
-                name: "Roast Profile".to_string(),
-                description: None,
-                default_control_mode: None,
-                default_setpoint: Some(200.0),
-                default_fan_pwm: None,
-                default_kp: None,
-                default_ki: None,
-                default_kd: None,
-                max_temp: None,
-                min_fan_pwm: None,
-                telemetry_interval_ms: None,
+        // Create and start a session
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "AUC Test Roast".to_string(),
+                device_id: "esp32-001".to_string(),
+                profile_id: None,
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
+        service.start_session(&session.id).await.unwrap();
 
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Profiled Device".to_string(),
-                device_id: "prof1".to_string(),
-                profile_id: Some(profile.id.clone()),
-                description: None,
-                location: None,
-            })
+        // 3-point linear curve: (0s, 100°C), (60s, 100°C), (120s, 200°C)
+        // With base_temp=0 (default):
+        //   Segment 1: dt=60, avg_temp=(100+100)/2=100 → area = 60*100 = 6000 °C·s
+        //   Segment 2: dt=60, avg_temp=(100+200)/2=150 → area = 60*150 = 9000 °C·s
+        //   Total = 15000 °C·s = 250 °C·min
+        service
+            .add_telemetry_point(&session.id, 0.0, Some(100.0), None, None, None, None, None)
             .await
             .unwrap();
-
-        assert_eq!(device.profile_id, Some(profile.id));
-    }
-
-    // ---- Connection Tests ----
-
-    #[tokio::test]
-    async fn test_add_and_remove_connection() {
-        let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
-
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Connected Device".to_string(),
-                device_id: "conn1".to_string(),
-                profile_id: None,
-                description: None,
-                location: None,
-            })
+        service
+            .add_telemetry_point(&session.id, 60.0, Some(100.0), None, None, None, None, None)
             .await
             .unwrap();
-
-        let mqtt_config = serde_json::json!({
-            "topic_prefix": "roaster/conn1",
-            "qos": 1
-        });
-
-        let conn = service
-            .add_connection(
-                &device.id,
-                CreateConnectionRequest {
-                    protocol: Protocol::Mqtt,
-                    enabled: Some(true),
-                    priority: Some(10),
-                    config: mqtt_config.clone(),
-                },
+        service
+            .add_telemetry_point(
+                &session.id,
+                120.0,
+                Some(200.0),
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
 
-        assert_eq!(conn.protocol, Protocol::Mqtt);
-        assert!(conn.enabled);
-        assert_eq!(conn.priority, 10);
-        assert_eq!(conn.config, mqtt_config);
-
-        // Verify connection shows up in device fetch
-        let fetched = service.get_device(&device.id).await.unwrap().unwrap();
-        assert_eq!(fetched.connections.len(), 1);
-
-        // Remove connection
-        assert!(service.remove_connection(&conn.id).await.unwrap());
-        assert!(!service.remove_connection(&conn.id).await.unwrap());
+        let completed = service
+            .complete_session(&session.id)
+            .await
+            .unwrap()
+            .expect("session should be returned");
 
-        let fetched = service.get_device(&device.id).await.unwrap().unwrap();
-        assert!(fetched.connections.is_empty());
+        let auc = completed.auc_value.unwrap();
+        assert!(
+            (auc - 250.0).abs() < 0.1,
+            "AUC should be 250.0 °C·min, got {auc}"
+        );
     }
 
     #[tokio::test]
-    async fn test_update_connection() {
+    async fn test_manual_session_records_control_changes() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        let service = RoastSessionService::new(pool);
 
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Update Conn Device".to_string(),
-                device_id: "uconn1".to_string(),
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Manual".to_string(),
+                device_id: "esp32-001".to_string(),
                 profile_id: None,
-                description: None,
-                location: None,
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Manual,
+                bean_id: None,
             })
             .await
             .unwrap();
+        assert_eq!(session.session_type, SessionType::Manual);
+        let status = service.manual_status(&session).await.unwrap();
+        assert_eq!(status.phase, ManualPhase::Preheat);
 
-        let conn = service.add_connection(&device.id, CreateConnectionRequest {
-            protocol: Protocol::WebSocket,
-            enabled: Some(true),
-            priority: Some(0),
-            config: serde_json::json!({"url": "ws://10.0.0.1:8080/ws", "reconnect_interval_ms": 5000}),
-        }).await.unwrap();
-
-        let updated = service
-            .update_connection(
-                &conn.id,
-                UpdateConnectionRequest {
-                    enabled: Some(false),
-                    priority: Some(5),
-                    config: None,
-                },
-            )
+        // Commands before the roast starts are audited but not session events
+        assert!(service
+            .record_control("esp32-001", "heater_pwm", "80", None)
+            .await
+            .unwrap()
+            .is_none());
+        let session = service.start_session(&session.id).await.unwrap().unwrap();
+        let event = service
+            .record_control("esp32-001", "heater_pwm", "60", Some(120.0))
+            .await
+            .unwrap()
+            .expect("heater change recorded");
+        assert_eq!(event.event_type, RoastEventType::HeaterChange);
+        assert_eq!(event.temperature, Some(120.0));
+        assert!(service
+            .record_control("esp32-001", "setpoint", "200", None)
+            .await
+            .unwrap()
+            .is_none());
+        service
+            .record_control("esp32-001", "fan_pwm", "180", None)
             .await
             .unwrap();
 
-        assert!(updated.is_some());
-        let updated = updated.unwrap();
-        assert!(!updated.enabled);
-        assert_eq!(updated.priority, 5);
+        let status = service.manual_status(&session).await.unwrap();
+        assert_eq!(status.phase, ManualPhase::Drying);
+        assert_eq!(status.heater_pwm.as_deref(), Some("60"));
+        assert_eq!(status.fan_pwm.as_deref(), Some("180"));
+        assert!(status.prompt.is_some());
+
+        for (event_type, t) in [
+            (RoastEventType::DryingEnd, 240.0),
+            (RoastEventType::FirstCrackStart, 480.0),
+            (RoastEventType::DropOut, 600.0),
+        ] {
+            service
+                .create_roast_event(
+                    &session.id,
+                    CreateRoastEventRequest {
+                        event_type,
+                        elapsed_seconds: t,
+                        temperature: None,
+                        notes: None,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        let status = service.manual_status(&session).await.unwrap();
+        assert_eq!(status.phase, ManualPhase::Finished);
+        assert_eq!(status.development_seconds, Some(120.0));
+        assert!((status.development_ratio.unwrap() - 0.2).abs() < 1e-6);
+
+        let audit = service.list_control_audit("esp32-001", 10).await.unwrap();
+        assert_eq!(audit.len(), 4);
+        assert_eq!(audit.iter().filter(|a| a.session_id.is_none()).count(), 1);
     }
 
     #[tokio::test]
-    async fn test_cascade_delete_connections() {
+    async fn test_raw_mqtt_publishes_are_audited() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        let service = RoastSessionService::new(pool);
 
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Cascade Device".to_string(),
-                device_id: "casc1".to_string(),
-                profile_id: None,
-                description: None,
-                location: None,
-            })
+        let req: MqttPublishRequest = serde_json::from_value(serde_json::json!({
+            "topic": "roaster/esp32-001/control/mode",
+            "payload": "manual",
+        }))
+        .unwrap();
+        assert_eq!((req.qos, req.retain), (0, false));
+        service
+            .record_mqtt_publish(&req, "manual", Some("admin-token"), "rest")
             .await
             .unwrap();
-
+        let req = MqttPublishRequest {
+            topic: "roaster/esp32-001/control/pid".to_string(),
+            payload: serde_json::json!({"kp": 2.0}),
+            qos: 1,
+            retain: true,
+        };
         service
-            .add_connection(
-                &device.id,
-                CreateConnectionRequest {
-                    protocol: Protocol::Mqtt,
-                    enabled: None,
-                    priority: None,
-                    config: serde_json::json!({"topic_prefix": "roaster/casc1", "qos": 0}),
-                },
-            )
+            .record_mqtt_publish(&req, r#"{"kp":2.0}"#, None, "websocket")
             .await
             .unwrap();
 
-        service.add_connection(&device.id, CreateConnectionRequest {
-            protocol: Protocol::ModbusTcp,
-            enabled: None,
-            priority: None,
-            config: serde_json::json!({"host": "10.0.0.1", "port": 502, "unit_id": 1, "poll_interval_ms": 1000}),
-        }).await.unwrap();
-
-        // Delete device should cascade delete connections
-        assert!(service.delete_device(&device.id).await.unwrap());
-
-        // Connections should be gone (we can verify by trying to get the device)
-        let fetched = service.get_device(&device.id).await.unwrap();
-        assert!(fetched.is_none());
+        let audit = service.list_mqtt_publishes(10).await.unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].topic, "roaster/esp32-001/control/pid");
+        assert_eq!((audit[0].qos, audit[0].retain), (1, true));
+        assert_eq!(audit[0].source, "websocket");
+        assert_eq!(audit[1].subject.as_deref(), Some("admin-token"));
+        assert_eq!(service.list_mqtt_publishes(1).await.unwrap().len(), 1);
     }
 
-    // ---- Register Map Tests ----
-
     #[tokio::test]
-    async fn test_set_and_get_register_map() {
+    async fn test_recompute_session_stats() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        let service = RoastSessionService::new(pool.clone());
 
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Modbus Device".to_string(),
-                device_id: "modbus1".to_string(),
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Recompute".to_string(),
+                device_id: "esp32-001".to_string(),
                 profile_id: None,
-                description: None,
-                location: None,
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
+        service.start_session(&session.id).await.unwrap();
+        // Only completed sessions carry derived stats
+        assert!(service
+            .recompute_session_stats(&session.id)
+            .await
+            .unwrap()
+            .is_none());
 
-        let registers = vec![
-            CreateRegisterMapEntry {
-                register_type: ModbusRegisterType::Input,
-                address: 0,
-                name: "bean_temp_hi".to_string(),
-                data_type: ModbusDataType::Float32,
-                byte_order: Some("ABCD".to_string()),
-                scale_factor: Some(1.0),
-                offset: Some(0.0),
-                unit: Some("C".to_string()),
-                description: Some("Bean temperature (high word)".to_string()),
-                writable: Some(false),
-            },
-            CreateRegisterMapEntry {
-                register_type: ModbusRegisterType::Holding,
-                address: 0,
-                name: "setpoint".to_string(),
-                data_type: ModbusDataType::Float32,
-                byte_order: Some("ABCD".to_string()),
-                scale_factor: Some(1.0),
-                offset: Some(0.0),
-                unit: Some("C".to_string()),
-                description: Some("Target setpoint".to_string()),
-                writable: Some(true),
-            },
-        ];
-
-        let result = service
-            .set_register_map(&device.id, registers)
+        for (t, temp) in [(0.0, 100.0), (60.0, 100.0), (120.0, 200.0)] {
+            service
+                .add_telemetry_point(&session.id, t, Some(temp), None, None, None, None, None)
+                .await
+                .unwrap();
+        }
+        let completed = service
+            .complete_session(&session.id)
             .await
+            .unwrap()
             .unwrap();
-        assert_eq!(result.len(), 2);
+        assert!((completed.auc_value.unwrap() - 250.0).abs() < 0.1);
 
-        let fetched = service.get_register_map(&device.id).await.unwrap();
-        assert_eq!(fetched.len(), 2);
-        // Ordered by register_type, address
-        assert_eq!(fetched[0].name, "setpoint"); // holding comes before input alphabetically
-        assert_eq!(fetched[1].name, "bean_temp_hi");
+        // Changing the AUC base only affects stored data after a recompute
+        sqlx::query("UPDATE settings SET value = '100' WHERE key = 'auc_base_temp'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let recomputed = service
+            .recompute_session_stats(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!((recomputed.auc_value.unwrap() - 50.0).abs() < 0.1);
+        assert_eq!(recomputed.status, SessionStatus::Completed);
+        assert_eq!(recomputed.end_time, completed.end_time);
+        assert_eq!(
+            service.completed_session_ids().await.unwrap(),
+            vec![session.id]
+        );
     }
 
     #[tokio::test]
-    async fn test_set_register_map_replaces_existing() {
+    async fn test_session_cost_and_daily_report() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
-
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Replace Map Device".to_string(),
-                device_id: "rmap1".to_string(),
+        let service = RoastSessionService::new(pool.clone());
+
+        let bean = service
+            .create_bean(CreateBeanRequest {
+                name: "Huila".to_string(),
+                origin: Some("Colombia".to_string()),
+                variety: None,
+                process: None,
+                density: None,
+                moisture_pct: None,
+                notes: None,
+                cost_per_kg: Some(10.0),
+            })
+            .await
+            .unwrap();
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Costed".to_string(),
+                device_id: "esp32-001".to_string(),
                 profile_id: None,
-                description: None,
-                location: None,
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: Some(1000.0),
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: Some(bean.id.clone()),
             })
             .await
             .unwrap();
-
-        // Set initial map
+        service.start_session(&session.id).await.unwrap();
+        // Full heater for 300 s, then a 600 s dropout that counts as 5 s
+        for t in 0..=300 {
+            service
+                .add_telemetry_point(
+                    &session.id,
+                    t as f32,
+                    None,
+                    None,
+                    None,
+                    Some(100),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
         service
-            .set_register_map(
-                &device.id,
-                vec![CreateRegisterMapEntry {
-                    register_type: ModbusRegisterType::Input,
-                    address: 0,
-                    name: "old_register".to_string(),
-                    data_type: ModbusDataType::Uint16,
-                    byte_order: None,
-                    scale_factor: None,
-                    offset: None,
-                    unit: None,
-                    description: None,
-                    writable: None,
-                }],
-            )
+            .add_telemetry_point(&session.id, 900.0, None, None, None, Some(0), None, None)
             .await
             .unwrap();
+        sqlx::query("UPDATE roast_sessions SET roasted_weight = 800 WHERE id = ?")
+            .bind(&session.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        service.complete_session(&session.id).await.unwrap();
 
-        // Replace with new map
-        service
-            .set_register_map(
-                &device.id,
-                vec![
-                    CreateRegisterMapEntry {
-                        register_type: ModbusRegisterType::Holding,
-                        address: 0,
-                        name: "new_register_1".to_string(),
-                        data_type: ModbusDataType::Float32,
-                        byte_order: None,
-                        scale_factor: None,
-                        offset: None,
-                        unit: None,
-                        description: None,
-                        writable: None,
-                    },
-                    CreateRegisterMapEntry {
-                        register_type: ModbusRegisterType::Holding,
-                        address: 2,
-                        name: "new_register_2".to_string(),
-                        data_type: ModbusDataType::Uint16,
-                        byte_order: None,
-                        scale_factor: None,
-                        offset: None,
-                        unit: None,
-                        description: None,
-                        writable: None,
-                    },
-                ],
-            )
+        // Nothing is charged for energy, labor or overhead until rates are set
+        let cost = service
+            .get_session_cost(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cost.total_cost, 10.0);
+        for (key, value) in [
+            ("cost_currency", "EUR"),
+            ("cost_heater_watts", "3600"),
+            ("cost_energy_per_kwh", "0.20"),
+            ("cost_labor_per_hour", "20"),
+            ("cost_overhead_per_batch", "1.5"),
+        ] {
+            sqlx::query("UPDATE settings SET value = ? WHERE key = ?")
+                .bind(value)
+                .bind(key)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let cost = service
+            .get_session_cost(&session.id)
             .await
+            .unwrap()
             .unwrap();
-
-        let fetched = service.get_register_map(&device.id).await.unwrap();
-        assert_eq!(fetched.len(), 2);
-        // Old register should be gone
-        assert!(fetched.iter().all(|r| r.name != "old_register"));
-        assert!(fetched.iter().any(|r| r.name == "new_register_1"));
-        assert!(fetched.iter().any(|r| r.name == "new_register_2"));
+        assert_eq!(cost.currency, "EUR");
+        assert_eq!(cost.bean_cost, Some(10.0));
+        assert_eq!(cost.energy_kwh, 0.305);
+        assert_eq!(cost.energy_cost, 0.06);
+        assert_eq!(cost.roast_hours, 0.25);
+        assert_eq!(cost.labor_cost, 5.0);
+        assert_eq!(cost.overhead_cost, 1.5);
+        assert_eq!(cost.total_cost, 16.56);
+        assert_eq!(cost.cost_per_roasted_kg, Some(20.7));
+        let details = service
+            .get_session_with_telemetry(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(details.cost, cost);
+
+        let today = Utc::now().date_naive();
+        let report = service.daily_report(today).await.unwrap();
+        assert_eq!(report.session_count, 1);
+        assert_eq!(report.green_kg, 1.0);
+        assert_eq!(report.roasted_kg, 0.8);
+        assert_eq!(report.total_cost, 16.56);
+        assert_eq!(report.cost_per_roasted_kg, Some(20.7));
+        let empty = service
+            .daily_report(today.pred_opt().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(empty.session_count, 0);
+        assert_eq!(empty.cost_per_roasted_kg, None);
     }
 
     #[tokio::test]
-    async fn test_cascade_delete_register_maps() {
+    async fn test_cupping_crud() {
         let pool = setup_test_db().await;
-        let service = DeviceService::new(pool);
+        let service = RoastSessionService::new(pool);
 
-        let device = service
-            .create_device(CreateDeviceRequest {
-                name: "Cascade Reg Device".to_string(),
-                device_id: "creg1".to_string(),
+        // Create a session
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Cupping Test".to_string(),
+                device_id: "esp32-001".to_string(),
                 profile_id: None,
-                description: None,
-                location: None,
+                bean_origin: Some("Ethiopia".to_string()),
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
 
-        service
-            .set_register_map(
-                &device.id,
-                vec![CreateRegisterMapEntry {
-                    register_type: ModbusRegisterType::Input,
-                    address: 0,
-                    name: "temp".to_string(),
-                    data_type: ModbusDataType::Float32,
-                    byte_order: None,
-                    scale_factor: None,
-                    offset: None,
-                    unit: None,
-                    description: None,
-                    writable: None,
-                }],
+        // No cupping initially
+        let cupping = service.get_cupping(&session.id).await.unwrap();
+        assert!(cupping.is_none());
+
+        // Create cupping with attributes
+        let result = service
+            .create_cupping(
+                &session.id,
+                CreateCuppingRequest {
+                    scoring_framework: Some("sca".to_string()),
+                    notes: Some("Floral, bright".to_string()),
+                    attributes: vec![
+                        CreateCuppingAttributeRequest {
+                            name: "aroma".to_string(),
+                            score: 8.0,
+                        },
+                        CreateCuppingAttributeRequest {
+                            name: "acidity".to_string(),
+                            score: 7.5,
+                        },
+                        CreateCuppingAttributeRequest {
+                            name: "body".to_string(),
+                            score: 7.0,
+                        },
+                    ],
+                },
             )
             .await
             .unwrap();
 
-        // Delete device should cascade delete register maps
-        service.delete_device(&device.id).await.unwrap();
+        assert_eq!(result.cupping.session_id, session.id);
+        assert_eq!(result.cupping.scoring_framework, "sca");
+        assert_eq!(result.cupping.notes, Some("Floral, bright".to_string()));
+        // overall_score = (8.0 + 7.5 + 7.0) / 3 = 7.5
+        let overall = result.cupping.overall_score.unwrap();
+        assert!((overall - 7.5).abs() < 0.01);
+        assert_eq!(result.attributes.len(), 3);
 
-        // Verify register maps are gone
-        let registers = service.get_register_map(&device.id).await.unwrap();
-        assert!(registers.is_empty());
+        // Fetch cupping
+        let fetched = service.get_cupping(&session.id).await.unwrap().unwrap();
+        assert_eq!(fetched.cupping.id, result.cupping.id);
+        assert_eq!(fetched.attributes.len(), 3);
+
+        // Cupping included in session with telemetry
+        let swt = service
+            .get_session_with_telemetry(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(swt.cupping.is_some());
+        assert_eq!(swt.cupping.unwrap().attributes.len(), 3);
+
+        // Delete cupping
+        service.delete_cupping(&session.id).await.unwrap();
+        let gone = service.get_cupping(&session.id).await.unwrap();
+        assert!(gone.is_none());
     }
 
-    // ---- Roast Profile CRUD Tests ----
+    #[tokio::test]
+    async fn test_artisan_import_reports_problems_and_accepts_partial() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool);
+        // Python literal with trailing commas, decimal-comma strings, a
+        // missing reading, a time going backwards and an event past the curve
+        let alog = r#"{
+            'title': 'Huila',
+            'timex': [0.0, 60.0, '120,0', 110.0, 180.0,],
+            'temp1': [200.0, 180.0, 190.0, 195.0, 210.0,],
+            'temp2': [195.0, -1, '150,5', 155.0, 170.0,],
+            'computed': {'CHARGE_time': 0.0, 'CHARGE_BT': 195.0, 'FCs_time': 480.0, 'FCs_BT': 200.0,},
+        }"#;
+        let req = || ImportArtisanProfileRequest {
+            alog_content: alog.to_string(),
+            name: None,
+        };
+        let profiles = service.list_profiles(true).await.unwrap().len();
+
+        let ImportOutcome::Rejected(report) =
+            service.import_artisan_profile(req(), false).await.unwrap()
+        else {
+            panic!("import with problems should be rejected");
+        };
+        assert_eq!(report.points_accepted, 3);
+        let skipped: Vec<_> = report.rows_skipped.iter().map(|r| r.row).collect();
+        assert_eq!(skipped, vec![1, 3]);
+        assert_eq!(report.rows_skipped[0].reason, "no bean temperature reading");
+        assert_eq!(report.events_unmatched.len(), 1);
+        assert_eq!(report.events_unmatched[0].event, "First Crack Start");
+        assert_eq!(service.list_profiles(true).await.unwrap().len(), profiles);
+
+        let ImportOutcome::Imported(imported) =
+            service.import_artisan_profile(req(), true).await.unwrap()
+        else {
+            panic!("partial import should be accepted");
+        };
+        assert_eq!(imported.import_report, report);
+        let points = &imported.profile.points;
+        let times: Vec<_> = points.iter().map(|p| p.time_seconds).collect();
+        assert_eq!(times, vec![0, 120, 180]);
+        assert_eq!(points[1].target_temp, 150.5);
+        assert_eq!(points[0].notes.as_deref(), Some("Charge"));
+        assert_eq!(imported.profile.profile.name, "Huila");
+    }
 
     #[tokio::test]
-    async fn test_profile_update_round_trip() {
+    async fn test_export_csv_and_artisan() {
         let pool = setup_test_db().await;
         let service = RoastSessionService::new(pool);
 
-        // Create a profile with initial points
-        let created = service
-            .create_profile(CreateProfileRequest {
-                name: "Original Profile".to_string(),
-                description: Some("Initial description".to_string()),
-                target_total_time: Some(600),
-                target_first_crack: None,
-                target_end_temp: Some(210.0),
-                preheat_temp: None,
-                charge_temp: Some(180.0),
-                points: vec![
-                    CreateProfilePointRequest {
-                        time_seconds: 0,
-                        target_temp: 180.0,
-                        fan_speed: Some(80),
-                        notes: None,
-                        target_env_temp: None,
-                    },
-                    CreateProfilePointRequest {
-                        time_seconds: 300,
-                        target_temp: 200.0,
-                        fan_speed: None,
-                        notes: None,
-                        target_env_temp: None,
-                    },
-                ],
+        // Create a session with metadata
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Export Test".to_string(),
+                device_id: "esp32-001".to_string(),
+                profile_id: None,
+                bean_origin: Some("Colombia".to_string()),
+                bean_variety: Some("Caturra".to_string()),
+                green_weight: Some(200.0),
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
 
-        assert_eq!(created.profile.name, "Original Profile");
-        assert_eq!(created.points.len(), 2);
+        // Start session
+        service.start_session(&session.id).await.unwrap();
 
-        // Update the profile with new metadata and different points
-        let updated = service
-            .update_profile(
-                &created.profile.id,
-                CreateProfileRequest {
-                    name: "Updated Profile".to_string(),
-                    description: Some("Updated description".to_string()),
-                    target_total_time: Some(720),
-                    target_first_crack: Some(360),
-                    target_end_temp: Some(220.0),
-                    preheat_temp: None,
-                    charge_temp: Some(185.0),
-                    points: vec![
-                        CreateProfilePointRequest {
-                            time_seconds: 0,
-                            target_temp: 185.0,
-                            fan_speed: Some(90),
-                            notes: None,
-                            target_env_temp: None,
-                        },
-                        CreateProfilePointRequest {
-                            time_seconds: 200,
-                            target_temp: 195.0,
-                            fan_speed: None,
-                            notes: None,
-                            target_env_temp: None,
-                        },
-                        CreateProfilePointRequest {
-                            time_seconds: 500,
-                            target_temp: 220.0,
-                            fan_speed: Some(60),
-                            notes: Some("Finish".to_string()),
-                            target_env_temp: None,
-                        },
-                    ],
+        // Add telemetry
+        for i in 0..5 {
+            let elapsed = i as f32 * 60.0;
+            let bt = 100.0 + i as f32 * 20.0;
+            let et = bt + 30.0;
+            service
+                .add_telemetry_point(
+                    &session.id,
+                    elapsed,
+                    Some(bt),
+                    Some(et),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        // Add an event
+        service
+            .create_roast_event(
+                &session.id,
+                CreateRoastEventRequest {
+                    event_type: RoastEventType::FirstCrackStart,
+                    elapsed_seconds: 180.0,
+                    temperature: Some(180.0),
+                    notes: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        // Test CSV export
+        let (csv, csv_filename) = service
+            .export_csv(&session.id, CsvExportOptions::default(), Locale::En)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(csv_filename.starts_with("Export_Test_"));
+        assert!(csv_filename.ends_with(".csv"));
+        assert!(csv.contains("# Session: Export Test"));
+        assert!(csv.contains("# Bean: Colombia (Caturra)"));
+        assert!(csv.contains("# Event: First Crack Start @ 3:00, 180"));
+        assert!(csv.contains("elapsed_seconds,bean_temp,env_temp"));
+        // Check we have data rows (header + 5 telemetry points)
+        let data_lines: Vec<&str> = csv.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(data_lines.len(), 6); // 1 header + 5 data rows
+        assert!(!csv.contains("# Unit"));
+        assert!(data_lines[1].starts_with("0,100,130,"));
+
+        // Formatting options: Fahrenheit, 1 decimal, m:ss, Artisan column names
+        let (csv, _) = service
+            .export_csv(
+                &session.id,
+                CsvExportOptions {
+                    units: Some(TemperatureUnit::F),
+                    decimals: Some(1),
+                    timestamp: ExportTimestamp::Mmss,
+                    preset: ExportColumnPreset::Artisan,
                 },
+                Locale::En,
             )
             .await
+            .unwrap()
+            .unwrap();
+        let data_lines: Vec<&str> = csv.lines().filter(|l| !l.starts_with('#')).collect();
+        assert!(csv.contains("# Unit: F"));
+        assert_eq!(data_lines[0], "Time,BT,ET,DeltaBT,Burner,Fan,SV");
+        assert_eq!(data_lines[1], "0:00,212.0,266.0,,,,");
+        assert!(data_lines[2].starts_with("1:00,248.0,302.0,"));
+
+        // Localized labels and event names; data rows are unchanged
+        let (csv, _) = service
+            .export_csv(&session.id, CsvExportOptions::default(), Locale::De)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(csv.contains("# Röstung: Export Test"));
+        assert!(csv.contains("# Bohne: Colombia (Caturra)"));
+        assert!(csv.contains("# Ereignis: Beginn First Crack @ 3:00, 180"));
+        assert!(csv.contains("elapsed_seconds,bean_temp,env_temp"));
+        let (csv, _) = service
+            .export_csv(&session.id, CsvExportOptions::default(), Locale::Es)
+            .await
+            .unwrap()
             .unwrap();
+        assert!(csv.contains("# Evento: Inicio del primer crack @ 3:00, 180"));
 
-        let updated = updated.expect("Profile should exist");
-        assert_eq!(updated.profile.name, "Updated Profile");
-        assert_eq!(
-            updated.profile.description,
-            Some("Updated description".to_string())
-        );
-        assert_eq!(updated.profile.target_total_time, Some(720));
-        assert_eq!(updated.profile.target_first_crack, Some(360));
-        assert_eq!(updated.profile.charge_temp, Some(185.0));
-        assert_eq!(updated.points.len(), 3);
-        assert_eq!(updated.points[0].target_temp, 185.0);
-        assert_eq!(updated.points[2].notes, Some("Finish".to_string()));
+        // Configured settings apply when the request leaves units/decimals unset
+        sqlx::query(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('export_temperature_unit', 'C'), ('export_decimal_places', '2')",
+        )
+        .execute(&service.db)
+        .await
+        .unwrap();
+        let (csv, _) = service
+            .export_csv(&session.id, CsvExportOptions::default(), Locale::En)
+            .await
+            .unwrap()
+            .unwrap();
+        let data_lines: Vec<&str> = csv.lines().filter(|l| !l.starts_with('#')).collect();
+        assert!(csv.contains("# Unit: C"));
+        assert!(csv.contains("# Event: First Crack Start @ 3:00, 180.00"));
+        assert!(data_lines[1].starts_with("0.00,100.00,130.00,"));
 
-        // Verify old points were replaced (not accumulated)
-        let fetched = service
-            .get_profile_with_points(&created.profile.id)
+        // Test Artisan JSON export
+        let (alog, alog_filename) = service
+            .export_artisan_json(&session.id)
             .await
             .unwrap()
             .unwrap();
-        assert_eq!(fetched.points.len(), 3);
+        assert!(alog_filename.starts_with("Export_Test_"));
+        assert!(alog_filename.ends_with(".alog"));
+        assert_eq!(alog["title"], "Export Test");
+        assert_eq!(alog["beans"], "Colombia");
+        let timex = alog["timex"].as_array().unwrap();
+        assert_eq!(timex.len(), 5);
+        let temp2 = alog["temp2"].as_array().unwrap();
+        assert_eq!(temp2.len(), 5);
+        // First BT should be 100.0
+        assert!((temp2[0].as_f64().unwrap() - 100.0).abs() < 0.1);
+        // timeindex should have FCs mapped
+        let timeindex = alog["timeindex"].as_array().unwrap();
+        assert_eq!(timeindex.len(), 8);
+        // CHARGE at index 0
+        assert_eq!(timeindex[0], 0);
+        // FCs should be at index 3 in telemetry (180s is closest to 180.0)
+        assert_eq!(timeindex[2], 3);
+    }
 
-        // Update non-existent profile returns None
-        let missing = service
-            .update_profile(
-                "nonexistent-id",
-                CreateProfileRequest {
-                    name: "Ghost".to_string(),
-                    description: None,
-                    target_total_time: None,
-                    target_first_crack: None,
-                    target_end_temp: None,
-                    preheat_temp: None,
-                    charge_temp: None,
-                    points: vec![],
-                },
+    #[tokio::test]
+    async fn test_roast_cues_fire_once_per_session() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool);
+        let profile = service
+            .create_profile(CreateProfileRequest {
+                name: "Cued".to_string(),
+                description: None,
+                target_total_time: None,
+                target_first_crack: None,
+                target_end_temp: None,
+                preheat_temp: None,
+                charge_temp: None,
+                batch_size_g: None,
+                heater_cap: None,
+                points: vec![],
+            })
+            .await
+            .unwrap();
+        let session = service
+            .create_session(CreateSessionRequest {
+                name: "Cued roast".to_string(),
+                device_id: "esp32-001".to_string(),
+                profile_id: Some(profile.profile.id.clone()),
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
+                notes: None,
+                ambient_temp: None,
+                humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
+            })
+            .await
+            .unwrap();
+        let cue = |message: &str, trigger_type, trigger_value| CreateCueRequest {
+            message: message.to_string(),
+            trigger_type,
+            trigger_value,
+            notify: false,
+        };
+        let color = service
+            .create_profile_cue(
+                &profile.profile.id,
+                cue("check color", CueTrigger::Temperature, 160.0),
             )
             .await
             .unwrap();
+        service
+            .create_session_cue(&session.id, cue("reduce gas", CueTrigger::Elapsed, 300.0))
+            .await
+            .unwrap();
 
-        assert!(missing.is_none());
-    }
+        let cues = service.list_session_cues(&session).await.unwrap();
+        assert_eq!(cues.len(), 2);
+        assert!(cues.iter().all(|c| c.fired_at.is_none()));
+        assert!(!color.is_due(500.0, Some(159.9)));
+        assert!(!color.is_due(500.0, None));
+        assert!(color.is_due(10.0, Some(160.0)));
 
-    // ---- Session Completion Statistics Tests ----
+        assert!(service
+            .record_cue_firing(&session.id, &color.id, 250.0, Some(161.0))
+            .await
+            .unwrap());
+        assert!(!service
+            .record_cue_firing(&session.id, &color.id, 251.0, Some(162.0))
+            .await
+            .unwrap());
+        let cues = service.list_session_cues(&session).await.unwrap();
+        let fired: Vec<_> = cues
+            .iter()
+            .filter(|c| c.fired_at.is_some())
+            .map(|c| c.cue.message.as_str())
+            .collect();
+        assert_eq!(fired, vec!["check color"]);
+
+        // Profile cues are shared, session cues are not
+        assert_eq!(
+            service
+                .list_profile_cues(&profile.profile.id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(service.delete_cue(&color.id).await.unwrap());
+        assert!(!service.delete_cue(&color.id).await.unwrap());
+        assert_eq!(service.list_session_cues(&session).await.unwrap().len(), 1);
+    }
 
     #[tokio::test]
-    async fn test_complete_session_computes_dtr_and_weight_loss() {
+    async fn test_sync_pull_push_and_conflicts() {
         let pool = setup_test_db().await;
         let service = RoastSessionService::new(pool);
-
-        // Create a session with green and roasted weights
-        let session = service
+        let server_session = service
             .create_session(CreateSessionRequest {
-                name: "Stats Test Roast".to_string(),
+                name: "Bench".to_string(),
                 device_id: "esp32-001".to_string(),
                 profile_id: None,
-                bean_origin: Some("Ethiopia".to_string()),
-                bean_variety: Some("Yirgacheffe".to_string()),
-                green_weight: Some(200.0),
-                target_roast_level: Some("medium".to_string()),
+                bean_origin: None,
+                bean_variety: None,
+                green_weight: None,
+                target_roast_level: None,
                 notes: None,
                 ambient_temp: None,
                 humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
+        service.start_session(&server_session.id).await.unwrap();
+
+        // Two journal rows for the session collapse into one change
+        let pulled = service.sync_pull(0, 100).await.unwrap();
+        assert_eq!(pulled.changes.len(), 1);
+        assert_eq!(pulled.changes[0].entity, SyncEntity::Session);
+        assert_eq!(pulled.changes[0].data.as_ref().unwrap()["status"], "active");
+        let cursor = pulled.cursor;
+
+        // A phone records a session, an event and a cupping offline
+        let push = |base_seq, changes| SyncPushRequest {
+            client_id: "phone-1".to_string(),
+            base_seq,
+            changes,
+        };
+        let change = |entity, entity_id: &str, op, data: serde_json::Value| SyncPushChange {
+            entity,
+            entity_id: entity_id.to_string(),
+            op,
+            data,
+            force: false,
+        };
+        let res = service
+            .sync_push(
+                push(
+                    cursor,
+                    vec![
+                        change(
+                            SyncEntity::Session,
+                            "farm-1",
+                            SyncOp::Upsert,
+                            serde_json::json!({"name": "Farm visit", "device_id": "esp32-001"}),
+                        ),
+                        change(
+                            SyncEntity::Event,
+                            "farm-1-fc",
+                            SyncOp::Upsert,
+                            serde_json::json!({"session_id": "farm-1", "event_type": "first_crack_start", "elapsed_seconds": 420.0}),
+                        ),
+                        change(
+                            SyncEntity::Cupping,
+                            "farm-1",
+                            SyncOp::Upsert,
+                            serde_json::json!({"attributes": [{"name": "acidity", "score": 8.0}]}),
+                        ),
+                        change(
+                            SyncEntity::Event,
+                            "orphan",
+                            SyncOp::Upsert,
+                            serde_json::json!({"session_id": "missing", "event_type": "drop", "elapsed_seconds": 600.0}),
+                        ),
+                    ],
+                ),
+                false,
+            )
+            .await
+            .unwrap();
+        let statuses: Vec<_> = res.results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                SyncPushStatus::Applied,
+                SyncPushStatus::Applied,
+                SyncPushStatus::Applied,
+                SyncPushStatus::Rejected
+            ]
+        );
+        let event = service.get_roast_event("farm-1-fc").await.unwrap().unwrap();
+        assert_eq!(event.session_id, "farm-1");
 
-        // Start the session
-        let session = service.start_session(&session.id).await.unwrap().unwrap();
-        assert_eq!(session.status, SessionStatus::Active);
+        let pulled = service.sync_pull(cursor, 100).await.unwrap();
+        let entities: Vec<_> = pulled.changes.iter().map(|c| c.entity).collect();
+        assert_eq!(
+            entities,
+            vec![SyncEntity::Session, SyncEntity::Event, SyncEntity::Cupping]
+        );
 
-        // Set roasted weight via update
+        // The server edits the bench session after the phone's last pull
         service
             .update_session(
-                &session.id,
+                &server_session.id,
                 UpdateSessionRequest {
                     name: None,
-                    roasted_weight: Some(170.0),
-                    notes: None,
+                    roaster: None,
+                    roasted_weight: None,
+                    notes: Some("server note".to_string()),
                     first_crack_time: None,
                     development_time_ratio: None,
                 },
             )
             .await
             .unwrap();
-
-        // Add telemetry points spanning 600 seconds with varying RoR
-        for i in 0..=600 {
-            if i % 10 == 0 {
-                let elapsed = i as f32;
-                let bean_temp = 100.0 + (elapsed / 600.0) * 120.0; // 100→220°C
-                let ror = if elapsed < 200.0 {
-                    15.0 // drying phase
-                } else if elapsed < 400.0 {
-                    12.0 // maillard phase
-                } else {
-                    8.0 // development phase
-                };
-                service
-                    .add_telemetry_point(
-                        &session.id,
-                        elapsed,
-                        Some(bean_temp),
-                        Some(bean_temp + 20.0),
-                        Some(ror),
-                        Some(80),
-                        Some(200),
-                        Some(bean_temp),
-                    )
-                    .await
-                    .unwrap();
-            }
-        }
-
-        // Add roast events for phase boundaries
-        service
-            .create_roast_event(
-                &session.id,
-                CreateRoastEventRequest {
-                    event_type: RoastEventType::DryingEnd,
-                    elapsed_seconds: 200.0,
-                    temperature: Some(140.0),
-                    notes: None,
-                },
-            )
+        let mut edit = change(
+            SyncEntity::Session,
+            &server_session.id,
+            SyncOp::Upsert,
+            serde_json::json!({"notes": "phone note"}),
+        );
+        let res = service
+            .sync_push(push(cursor, vec![edit]), false)
             .await
             .unwrap();
+        assert_eq!(res.results[0].status, SyncPushStatus::Conflict);
+        assert_eq!(
+            res.results[0].server.as_ref().unwrap()["notes"],
+            "server note"
+        );
 
-        service
-            .create_roast_event(
-                &session.id,
-                CreateRoastEventRequest {
-                    event_type: RoastEventType::FirstCrackStart,
-                    elapsed_seconds: 400.0,
-                    temperature: Some(200.0),
-                    notes: None,
-                },
+        // The phone's own earlier changes never conflict with it
+        let res = service
+            .sync_push(
+                push(
+                    cursor,
+                    vec![change(
+                        SyncEntity::Event,
+                        "farm-1-fc",
+                        SyncOp::Delete,
+                        serde_json::Value::Null,
+                    )],
+                ),
+                false,
             )
             .await
             .unwrap();
+        assert_eq!(res.results[0].status, SyncPushStatus::Applied);
 
-        // Complete the session
-        let completed = service
-            .complete_session(&session.id)
+        edit = change(
+            SyncEntity::Session,
+            &server_session.id,
+            SyncOp::Upsert,
+            serde_json::json!({"notes": "phone note"}),
+        );
+        edit.force = true;
+        let res = service
+            .sync_push(push(cursor, vec![edit]), false)
+            .await
+            .unwrap();
+        assert_eq!(res.results[0].status, SyncPushStatus::Applied);
+        let session = service
+            .get_session(&server_session.id)
             .await
             .unwrap()
-            .expect("session should be returned");
-
-        assert_eq!(completed.status, SessionStatus::Completed);
-
-        // DTR: (600 - 400) / 600 = 200/600 ≈ 0.333
-        let dtr = completed.development_time_ratio.unwrap();
-        assert!(
-            (dtr - 0.333).abs() < 0.01,
-            "DTR should be ~0.333, got {dtr}"
-        );
-
-        // Weight loss: (200 - 170) / 200 * 100 = 15%
-        let wl = completed.weight_loss_pct.unwrap();
-        assert!(
-            (wl - 15.0).abs() < 0.1,
-            "Weight loss should be 15%, got {wl}"
-        );
-
-        // Max RoR should be 15.0
-        let max_ror = completed.max_ror.unwrap();
-        assert!(
-            (max_ror - 15.0).abs() < 0.1,
-            "Max RoR should be 15.0, got {max_ror}"
-        );
+            .unwrap();
+        assert_eq!(session.notes.as_deref(), Some("phone note"));
 
-        // First crack time should be 400 seconds
-        assert_eq!(completed.first_crack_time, Some(400));
+        let pulled = service.sync_pull(cursor, 2).await.unwrap();
+        assert!(pulled.has_more);
+        let deleted = service.sync_pull(pulled.cursor, 100).await.unwrap();
+        assert!(deleted
+            .changes
+            .iter()
+            .any(|c| c.entity_id == "farm-1-fc" && c.op == SyncOp::Delete && c.data.is_none()));
+    }
 
-        // Drying end fields
-        assert_eq!(completed.drying_end_time, Some(200));
-        assert_eq!(completed.drying_end_temp, Some(140.0));
+    #[tokio::test]
+    async fn test_defect_tags_and_rates() {
+        let pool = setup_test_db().await;
+        let service = RoastSessionService::new(pool);
+        let profile = service
+            .create_profile(CreateProfileRequest {
+                name: "House".to_string(),
+                description: None,
+                target_total_time: None,
+                target_first_crack: None,
+                target_end_temp: None,
+                preheat_temp: None,
+                charge_temp: None,
+                batch_size_g: None,
+                heater_cap: None,
+                points: vec![],
+            })
+            .await
+            .unwrap();
+        let completed = |profile_id: Option<String>| {
+            let service = &service;
+            async move {
+                let session = service
+                    .create_session(CreateSessionRequest {
+                        name: "Roast".to_string(),
+                        device_id: "esp32-001".to_string(),
+                        profile_id,
+                        bean_origin: None,
+                        bean_variety: None,
+                        green_weight: None,
+                        target_roast_level: None,
+                        notes: None,
+                        ambient_temp: None,
+                        humidity: None,
+                        roaster: None,
+                        session_type: SessionType::Profile,
+                        bean_id: None,
+                    })
+                    .await
+                    .unwrap();
+                service.start_session(&session.id).await.unwrap();
+                service.complete_session(&session.id).await.unwrap();
+                session.id
+            }
+        };
+        let tag = |session_id: String, defect, range: Option<(f32, f32)>| {
+            let service = &service;
+            async move {
+                service
+                    .create_defect(
+                        &session_id,
+                        CreateDefectRequest {
+                            defect,
+                            start_seconds: range.map(|r| r.0),
+                            end_seconds: range.map(|r| r.1),
+                            notes: None,
+                        },
+                        Some("tester"),
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
 
-        // Phase avg RoR values
-        let avg_dry = completed.avg_ror_drying.unwrap();
-        assert!(
-            (avg_dry - 15.0).abs() < 0.1,
-            "Avg RoR drying should be ~15.0, got {avg_dry}"
-        );
+        let scorched = completed(Some(profile.profile.id.clone())).await;
+        let clean = completed(Some(profile.profile.id.clone())).await;
+        let unprofiled = completed(None).await;
+        tag(scorched.clone(), DefectType::Scorching, Some((30.0, 90.0))).await;
+        tag(
+            scorched.clone(),
+            DefectType::Scorching,
+            Some((120.0, 150.0)),
+        )
+        .await;
+        let tipping = tag(scorched.clone(), DefectType::Tipping, None).await;
+        tag(unprofiled.clone(), DefectType::Baked, None).await;
+
+        let defects = service.list_defects(&scorched).await.unwrap();
+        assert_eq!(defects.len(), 3);
+        assert_eq!(defects[0].defect, DefectType::Tipping); // no range sorts first
+        assert_eq!(defects[1].start_seconds, Some(30.0));
+        assert!(service.list_defects(&clean).await.unwrap().is_empty());
+        let with_telemetry = service
+            .get_session_with_telemetry(&scorched)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(with_telemetry.defects.len(), 3);
 
-        let avg_mail = completed.avg_ror_maillard.unwrap();
-        assert!(
-            (avg_mail - 12.0).abs() < 0.1,
-            "Avg RoR maillard should be ~12.0, got {avg_mail}"
+        let rates = service
+            .defect_rates(&DefectRateQuery {
+                group_by: DefectGroupBy::Profile,
+                from: None,
+                to: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(rates.groups.len(), 2);
+        let none = &rates.groups[0];
+        assert_eq!(none.key, None);
+        assert_eq!((none.sessions, none.defective_sessions), (1, 1));
+        let house = &rates.groups[1];
+        assert_eq!(house.label.as_deref(), Some("House"));
+        assert_eq!((house.sessions, house.defective_sessions), (2, 1));
+        assert!((house.defect_rate - 0.5).abs() < 1e-9);
+        assert_eq!(
+            house.defects,
+            vec![
+                DefectRate {
+                    defect: DefectType::Scorching,
+                    sessions: 1,
+                    rate: 0.5
+                },
+                DefectRate {
+                    defect: DefectType::Tipping,
+                    sessions: 1,
+                    rate: 0.5
+                },
+            ]
         );
 
-        let avg_dev = completed.avg_ror_development.unwrap();
-        assert!(
-            (avg_dev - 8.0).abs() < 0.1,
-            "Avg RoR development should be ~8.0, got {avg_dev}"
-        );
+        assert!(service.delete_defect(&scorched, &tipping.id).await.unwrap());
+        assert!(!service.delete_defect(&clean, &tipping.id).await.unwrap());
     }
 
     #[tokio::test]
-    async fn test_complete_session_computes_auc() {
+    async fn test_sign_off_and_roaster_filter() {
         let pool = setup_test_db().await;
         let service = RoastSessionService::new(pool);
 
-        // Create and start a session
+        let mut ids = Vec::new();
+        for roaster in ["alice", "bob"] {
+            let session = service
+                .create_session(CreateSessionRequest {
+                    name: format!("{roaster}'s roast"),
+                    device_id: "esp32-001".to_string(),
+                    profile_id: None,
+                    bean_origin: None,
+                    bean_variety: None,
+                    green_weight: None,
+                    target_roast_level: None,
+                    notes: None,
+                    ambient_temp: None,
+                    humidity: None,
+                    roaster: Some(roaster.to_string()),
+                    session_type: SessionType::Profile,
+                    bean_id: None,
+                })
+                .await
+                .unwrap();
+            ids.push(session.id);
+        }
+
+        let alice = service
+            .list_sessions(None, Some("alice"), None)
+            .await
+            .unwrap();
+        assert_eq!(alice.len(), 1);
+        assert_eq!(alice[0].roaster.as_deref(), Some("alice"));
+
+        // Only completed sessions can be signed off
+        let id = &ids[0];
+        assert!(service.sign_off_session(id, "qa").await.unwrap().is_none());
+
+        service.start_session(id).await.unwrap().unwrap();
+        service.complete_session(id).await.unwrap().unwrap();
+        let signed = service.sign_off_session(id, "qa").await.unwrap().unwrap();
+        assert!(signed.is_signed_off());
+        assert_eq!(signed.signed_off_by.as_deref(), Some("qa"));
+
+        // A second sign-off is rejected until the session is reopened
+        assert!(service.sign_off_session(id, "qa").await.unwrap().is_none());
+        let reopened = service.reopen_session(id).await.unwrap().unwrap();
+        assert!(!reopened.is_signed_off());
+        assert!(reopened.signed_off_by.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_sessions_uses_read_pool() {
+        let pool = setup_test_db().await;
+        let replica = setup_test_db().await;
+        let service = RoastSessionService::new(pool).with_read_pool(replica);
+
         let session = service
             .create_session(CreateSessionRequest {
-                name: "AUC Test Roast".to_string(),
+                name: "Write pool only".to_string(),
                 device_id: "esp32-001".to_string(),
                 profile_id: None,
                 bean_origin: None,
@@ -2721,223 +8516,218 @@ mod tests {
                 notes: None,
                 ambient_temp: None,
                 humidity: None,
+                roaster: None,
+                session_type: SessionType::Profile,
+                bean_id: None,
             })
             .await
             .unwrap();
-        service.start_session(&session.id).await.unwrap();
-
-        // 3-point linear curve: (0s, 100°C), (60s, 100°C), (120s, 200°C)
-        // With base_temp=0 (default):
-        //   Segment 1: dt=60, avg_temp=(100+100)/2=100 → area = 60*100 = 6000 °C·s
-        //   Segment 2: dt=60, avg_temp=(100+200)/2=150 → area = 60*150 = 9000 °C·s
-        //   Total = 15000 °C·s = 250 °C·min
-        service
-            .add_telemetry_point(&session.id, 0.0, Some(100.0), None, None, None, None, None)
-            .await
-            .unwrap();
-        service
-            .add_telemetry_point(&session.id, 60.0, Some(100.0), None, None, None, None, None)
-            .await
-            .unwrap();
-        service
-            .add_telemetry_point(
-                &session.id,
-                120.0,
-                Some(200.0),
-                None,
-                None,
-                None,
-                None,
-                None,
-            )
-            .await
-            .unwrap();
 
-        let completed = service
-            .complete_session(&session.id)
+        // Writes and point lookups go to the primary; listings come from the replica
+        assert!(service.get_session(&session.id).await.unwrap().is_some());
+        assert!(service
+            .list_sessions(None, None, None)
             .await
             .unwrap()
-            .expect("session should be returned");
-
-        let auc = completed.auc_value.unwrap();
-        assert!(
-            (auc - 250.0).abs() < 0.1,
-            "AUC should be 250.0 °C·min, got {auc}"
-        );
+            .is_empty());
     }
 
+    // ---- User / API Key Tests ----
+
     #[tokio::test]
-    async fn test_cupping_crud() {
+    async fn test_oidc_user_upsert_and_api_keys() {
         let pool = setup_test_db().await;
-        let service = RoastSessionService::new(pool);
+        let service = UserService::new(pool);
 
-        // Create a session
-        let session = service
-            .create_session(CreateSessionRequest {
-                name: "Cupping Test".to_string(),
-                device_id: "esp32-001".to_string(),
-                profile_id: None,
-                bean_origin: Some("Ethiopia".to_string()),
-                bean_variety: None,
-                green_weight: None,
-                target_roast_level: None,
-                notes: None,
-                ambient_temp: None,
-                humidity: None,
-            })
+        let user = service
+            .upsert_oidc_user("https://idp", "sub-1", Some("a@x"), None, UserRole::Viewer)
             .await
             .unwrap();
+        let again = service
+            .upsert_oidc_user(
+                "https://idp",
+                "sub-1",
+                Some("a@x"),
+                Some("Alice"),
+                UserRole::Admin,
+            )
+            .await
+            .unwrap();
+        assert_eq!(again.id, user.id);
+        assert_eq!(again.role, UserRole::Admin);
+        assert_eq!(again.display_name.as_deref(), Some("Alice"));
 
-        // No cupping initially
-        let cupping = service.get_cupping(&session.id).await.unwrap();
-        assert!(cupping.is_none());
-
-        // Create cupping with attributes
-        let result = service
-            .create_cupping(
-                &session.id,
-                CreateCuppingRequest {
-                    scoring_framework: Some("sca".to_string()),
-                    notes: Some("Floral, bright".to_string()),
-                    attributes: vec![
-                        CreateCuppingAttributeRequest {
-                            name: "aroma".to_string(),
-                            score: 8.0,
-                        },
-                        CreateCuppingAttributeRequest {
-                            name: "acidity".to_string(),
-                            score: 7.5,
-                        },
-                        CreateCuppingAttributeRequest {
-                            name: "body".to_string(),
-                            score: 7.0,
-                        },
-                    ],
+        let created = service
+            .create_api_key(
+                CreateApiKeyRequest {
+                    name: "ci".to_string(),
+                    role: None,
                 },
+                Some(&user.id),
             )
             .await
             .unwrap();
+        assert!(created.key.starts_with(API_KEY_PREFIX));
+        assert_eq!(created.api_key.role, UserRole::Operator);
+        assert!(created.key.starts_with(&created.api_key.key_prefix));
 
-        assert_eq!(result.cupping.session_id, session.id);
-        assert_eq!(result.cupping.scoring_framework, "sca");
-        assert_eq!(result.cupping.notes, Some("Floral, bright".to_string()));
-        // overall_score = (8.0 + 7.5 + 7.0) / 3 = 7.5
-        let overall = result.cupping.overall_score.unwrap();
-        assert!((overall - 7.5).abs() < 0.01);
-        assert_eq!(result.attributes.len(), 3);
-
-        // Fetch cupping
-        let fetched = service.get_cupping(&session.id).await.unwrap().unwrap();
-        assert_eq!(fetched.cupping.id, result.cupping.id);
-        assert_eq!(fetched.attributes.len(), 3);
-
-        // Cupping included in session with telemetry
-        let swt = service
-            .get_session_with_telemetry(&session.id)
+        let found = service
+            .authenticate_api_key(&created.key)
             .await
             .unwrap()
             .unwrap();
-        assert!(swt.cupping.is_some());
-        assert_eq!(swt.cupping.unwrap().attributes.len(), 3);
+        assert_eq!(found.id, created.api_key.id);
+        assert!(found.last_used_at.is_some());
+        assert!(service
+            .authenticate_api_key("rr_wrong")
+            .await
+            .unwrap()
+            .is_none());
 
-        // Delete cupping
-        service.delete_cupping(&session.id).await.unwrap();
-        let gone = service.get_cupping(&session.id).await.unwrap();
-        assert!(gone.is_none());
+        assert!(service.revoke_api_key(&created.api_key.id).await.unwrap());
+        assert!(service
+            .authenticate_api_key(&created.key)
+            .await
+            .unwrap()
+            .is_none());
     }
 
     #[tokio::test]
-    async fn test_export_csv_and_artisan() {
+    async fn test_smoothing_presets_resolve_per_view() {
         let pool = setup_test_db().await;
-        let service = RoastSessionService::new(pool);
+        let service = UserService::new(pool.clone());
 
-        // Create a session with metadata
-        let session = service
-            .create_session(CreateSessionRequest {
-                name: "Export Test".to_string(),
-                device_id: "esp32-001".to_string(),
-                profile_id: None,
-                bean_origin: Some("Colombia".to_string()),
-                bean_variety: Some("Caturra".to_string()),
-                green_weight: Some(200.0),
-                target_roast_level: None,
-                notes: None,
-                ambient_temp: None,
-                humidity: None,
-            })
+        // Server settings apply without a preset
+        sqlx::query(
+            "UPDATE settings SET value = 'savitzky_golay' WHERE key = 'ror_smoothing_algorithm'",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let config = service.resolve_smoothing(None, "live").await.unwrap();
+        assert_eq!(config.ror_window_secs, 30.0);
+        assert_eq!(config.ror_algorithm, RorAlgorithm::SavitzkyGolay);
+        assert_eq!(config.bt_window_secs, 0.0);
+
+        let compare = SmoothingConfig {
+            bt_window_secs: 5.0,
+            et_window_secs: 10.0,
+            ror_window_secs: 15.0,
+            ror_algorithm: RorAlgorithm::WeightedMovingAverage,
+            ror_unit: RorUnit::PerMinute,
+        };
+        service
+            .upsert_smoothing_preset("u1", "compare", &compare)
             .await
             .unwrap();
-
-        // Start session
-        service.start_session(&session.id).await.unwrap();
-
-        // Add telemetry
-        for i in 0..5 {
-            let elapsed = i as f32 * 60.0;
-            let bt = 100.0 + i as f32 * 20.0;
-            let et = bt + 30.0;
+        let updated = SmoothingConfig {
+            ror_window_secs: 20.0,
+            ..compare
+        };
+        let preset = service
+            .upsert_smoothing_preset("u1", "compare", &updated)
+            .await
+            .unwrap();
+        assert_eq!(preset.config, updated);
+        assert_eq!(
             service
-                .add_telemetry_point(
-                    &session.id,
-                    elapsed,
-                    Some(bt),
-                    Some(et),
-                    None,
-                    None,
-                    None,
-                    None,
-                )
+                .resolve_smoothing(Some("u1"), "compare")
                 .await
-                .unwrap();
-        }
+                .unwrap(),
+            updated
+        );
+        // Other views and users still get the server settings
+        assert_eq!(
+            service.resolve_smoothing(Some("u1"), "live").await.unwrap(),
+            config
+        );
+        assert_eq!(
+            service
+                .resolve_smoothing(Some("u2"), "compare")
+                .await
+                .unwrap(),
+            config
+        );
+        assert_eq!(service.list_smoothing_presets("u1").await.unwrap().len(), 1);
 
-        // Add an event
-        service
-            .create_roast_event(
-                &session.id,
-                CreateRoastEventRequest {
-                    event_type: RoastEventType::FirstCrackStart,
-                    elapsed_seconds: 180.0,
-                    temperature: Some(180.0),
-                    notes: None,
+        assert!(service
+            .delete_smoothing_preset("u1", "compare")
+            .await
+            .unwrap());
+        assert!(!service
+            .delete_smoothing_preset("u1", "compare")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_preferences_partial_update() {
+        let pool = setup_test_db().await;
+        let service = UserService::new(pool);
+
+        assert!(service.get_preferences("u1").await.unwrap().is_none());
+
+        let prefs = service
+            .update_preferences(
+                "u1",
+                UpdatePreferencesRequest {
+                    ws_subscriptions: Some(vec!["esp32-001".to_string()]),
+                    dashboard_layout: None,
                 },
             )
             .await
             .unwrap();
-
-        // Test CSV export
-        let (csv, csv_filename) = service.export_csv(&session.id).await.unwrap().unwrap();
-        assert!(csv_filename.starts_with("Export_Test_"));
-        assert!(csv_filename.ends_with(".csv"));
-        assert!(csv.contains("# Session: Export Test"));
-        assert!(csv.contains("# Bean: Colombia (Caturra)"));
-        assert!(csv.contains("elapsed_seconds,bean_temp,env_temp"));
-        // Check we have data rows (header + 5 telemetry points)
-        let data_lines: Vec<&str> = csv.lines().filter(|l| !l.starts_with('#')).collect();
-        assert_eq!(data_lines.len(), 6); // 1 header + 5 data rows
-
-        // Test Artisan JSON export
-        let (alog, alog_filename) = service
-            .export_artisan_json(&session.id)
+        assert_eq!(prefs.ws_subscriptions.0, vec!["esp32-001".to_string()]);
+        assert!(prefs.dashboard_layout.is_none());
+
+        // Updating the layout keeps the stored subscriptions
+        let prefs = service
+            .update_preferences(
+                "u1",
+                UpdatePreferencesRequest {
+                    ws_subscriptions: None,
+                    dashboard_layout: Some(serde_json::json!({"panels": ["chart"]})),
+                },
+            )
             .await
-            .unwrap()
             .unwrap();
-        assert!(alog_filename.starts_with("Export_Test_"));
-        assert!(alog_filename.ends_with(".alog"));
-        assert_eq!(alog["title"], "Export Test");
-        assert_eq!(alog["beans"], "Colombia");
-        let timex = alog["timex"].as_array().unwrap();
-        assert_eq!(timex.len(), 5);
-        let temp2 = alog["temp2"].as_array().unwrap();
-        assert_eq!(temp2.len(), 5);
-        // First BT should be 100.0
-        assert!((temp2[0].as_f64().unwrap() - 100.0).abs() < 0.1);
-        // timeindex should have FCs mapped
-        let timeindex = alog["timeindex"].as_array().unwrap();
-        assert_eq!(timeindex.len(), 8);
-        // CHARGE at index 0
-        assert_eq!(timeindex[0], 0);
-        // FCs should be at index 3 in telemetry (180s is closest to 180.0)
-        assert_eq!(timeindex[2], 3);
+        assert_eq!(prefs.ws_subscriptions.0, vec!["esp32-001".to_string()]);
+        assert_eq!(
+            prefs.dashboard_layout,
+            Some(serde_json::json!({"panels": ["chart"]}))
+        );
+    }
+
+    #[test]
+    fn test_decimate_telemetry_keeps_shape() {
+        // Flat at 100 °C with a single spike at t = 37
+        let points: Vec<SessionTelemetry> = (0..100)
+            .map(|i| SessionTelemetry {
+                id: i.to_string(),
+                session_id: "s".into(),
+                timestamp: Utc::now(),
+                elapsed_seconds: i as f32,
+                bean_temp: Some(if i == 37 { 180.0 } else { 100.0 }),
+                env_temp: None,
+                rate_of_rise: None,
+                heater_pwm: None,
+                fan_pwm: None,
+                setpoint: None,
+                airflow: None,
+            })
+            .collect();
+
+        let decimated = decimate_telemetry(points.clone(), 10);
+        assert_eq!(decimated.len(), 10);
+        assert_eq!(decimated.first().unwrap().elapsed_seconds, 0.0);
+        assert_eq!(decimated.last().unwrap().elapsed_seconds, 99.0);
+        assert!(decimated.iter().any(|p| p.elapsed_seconds == 37.0));
+        assert!(decimated
+            .windows(2)
+            .all(|w| w[0].elapsed_seconds < w[1].elapsed_seconds));
+
+        // Nothing to do when the series already fits
+        assert_eq!(decimate_telemetry(points.clone(), 100).len(), 100);
+        assert_eq!(decimate_telemetry(points, 1000).len(), 100);
     }
 }
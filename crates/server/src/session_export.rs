@@ -0,0 +1,120 @@
+//! Session-aligned telemetry republished to MQTT for external loggers.
+//!
+//! While a session is active, each telemetry sample from its device is
+//! normalized (snake_case fields, `elapsed_seconds`, server-derived values
+//! such as `airflow`) and published to `rustroast/sessions/{session_id}/telemetry`.
+//! Enabled with `RUSTROAST_SESSION_MQTT_EXPORT=true`. In a cluster only the
+//! leader publishes.
+
+use chrono::{DateTime, Utc};
+use rumqttc::QoS;
+use rustroast_core::session_telemetry_topic;
+use rustroast_mqtt::MqttService;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::cluster::Cluster;
+use crate::models::SessionStatus;
+use crate::services::RoastSessionService;
+use crate::telemetry::TelemetryEvent;
+
+/// Message published on the session telemetry topic.
+#[derive(Debug, Serialize)]
+pub struct SessionTelemetryMessage {
+    pub session_id: String,
+    pub device_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub elapsed_seconds: f64,
+    pub bean_temp: Option<f64>,
+    pub env_temp: Option<f64>,
+    pub rate_of_rise: Option<f64>,
+    pub heater_pwm: Option<i64>,
+    pub fan_pwm: Option<i64>,
+    pub setpoint: Option<f64>,
+    pub airflow: Option<f64>,
+}
+
+/// Whether `RUSTROAST_SESSION_MQTT_EXPORT` turns the export on.
+pub fn enabled_from_env() -> bool {
+    std::env::var("RUSTROAST_SESSION_MQTT_EXPORT")
+        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+#[derive(Clone)]
+pub struct SessionExporter {
+    session_service: RoastSessionService,
+    mqtt: MqttService,
+    cluster: Cluster,
+}
+
+impl SessionExporter {
+    pub fn new(session_service: RoastSessionService, mqtt: MqttService, cluster: Cluster) -> Self {
+        Self {
+            session_service,
+            mqtt,
+            cluster,
+        }
+    }
+
+    /// Republish telemetry for active sessions until the channel closes.
+    pub async fn run(self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        loop {
+            match telemetry_rx.recv().await {
+                Ok(evt) => self.export(&evt.device_id, &evt.payload).await,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "Session export lagged behind telemetry");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn export(&self, device_id: &str, payload: &serde_json::Value) {
+        if !self.cluster.is_leader() {
+            return;
+        }
+        let session = match self.session_service.get_active_session(device_id).await {
+            Ok(Some(s)) if s.status == SessionStatus::Active => s,
+            Ok(_) => return,
+            Err(e) => {
+                tracing::warn!(%device_id, error = %e, "Failed to look up active session for export");
+                return;
+            }
+        };
+        let Some(start) = session.start_time else {
+            return;
+        };
+        let now = Utc::now();
+        let f = |key: &str| payload.get(key).and_then(|v| v.as_f64());
+        let i = |key: &str| payload.get(key).and_then(|v| v.as_i64());
+        let message = SessionTelemetryMessage {
+            session_id: session.id,
+            device_id: device_id.to_string(),
+            timestamp: now,
+            elapsed_seconds: (now - start).num_milliseconds() as f64 / 1000.0,
+            bean_temp: f("beanTemp"),
+            env_temp: f("envTemp"),
+            rate_of_rise: f("rateOfRise"),
+            heater_pwm: i("heaterPWM"),
+            fan_pwm: i("fanPWM"),
+            setpoint: f("setpoint"),
+            airflow: f("airflow"),
+        };
+        let body = match serde_json::to_vec(&message) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to serialize session telemetry");
+                return;
+            }
+        };
+        let topic = session_telemetry_topic(&message.session_id);
+        if let Err(e) = self
+            .mqtt
+            .publish(&topic, QoS::AtMostOnce, false, body)
+            .await
+        {
+            tracing::warn!(%topic, error = %e, "Failed to publish session telemetry");
+        }
+    }
+}
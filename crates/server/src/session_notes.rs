@@ -0,0 +1,72 @@
+//! Voice memos and photos sent during a roast.
+//!
+//! `/ws/sessions/{id}/notes` takes each binary frame as one attachment to
+//! the active session. The type is read from the file's leading bytes
+//! rather than trusted from the client, and only the audio and image
+//! formats below are kept.
+
+use std::sync::OnceLock;
+
+const DEFAULT_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// Largest attachment accepted, from
+/// `RUSTROAST_SESSION_ATTACHMENT_MAX_BYTES` (default 2 MiB).
+pub fn max_bytes() -> usize {
+    static MAX_BYTES: OnceLock<usize> = OnceLock::new();
+    *MAX_BYTES.get_or_init(|| {
+        std::env::var("RUSTROAST_SESSION_ATTACHMENT_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_MAX_BYTES)
+    })
+}
+
+/// Formats accepted, for error messages.
+pub const ACCEPTED: &str = "PNG, JPEG, WebP, WebM, Ogg, MP4/M4A, MP3 or WAV";
+
+/// The attachment kind (`audio` or `image`) and content type of a file.
+pub fn sniff(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    let riff = |format: &[u8]| data.starts_with(b"RIFF") && data.get(8..12) == Some(format);
+    Some(if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        ("image", "image/png")
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        ("image", "image/jpeg")
+    } else if riff(b"WEBP") {
+        ("image", "image/webp")
+    } else if riff(b"WAVE") {
+        ("audio", "audio/wav")
+    } else if data.starts_with(b"\x1a\x45\xdf\xa3") {
+        // Matroska, as recorded by browsers' MediaRecorder
+        ("audio", "audio/webm")
+    } else if data.starts_with(b"OggS") {
+        ("audio", "audio/ogg")
+    } else if data.get(4..8) == Some(&b"ftyp"[..]) {
+        ("audio", "audio/mp4")
+    } else if data.starts_with(b"ID3") || data.starts_with(b"\xff\xfb") {
+        ("audio", "audio/mpeg")
+    } else {
+        return None;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_reads_leading_bytes() {
+        assert_eq!(
+            sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            Some(("image", "image/png"))
+        );
+        assert_eq!(
+            sniff(b"RIFF\x24\0\0\0WAVEfmt "),
+            Some(("audio", "audio/wav"))
+        );
+        assert_eq!(sniff(b"\0\0\0\x20ftypM4A "), Some(("audio", "audio/mp4")));
+        assert_eq!(sniff(b"RIFF\x24\0\0\0AVI "), None);
+        assert_eq!(sniff(b"<svg xmlns="), None);
+        assert_eq!(sniff(b""), None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Recovery of sessions that were active when the server went down.
+//!
+//! On boot every active session is checked against its last telemetry
+//! point. After a short outage (up to `RUSTROAST_RESUME_MAX_GAP_SECS`,
+//! default 120s) the session stays active and ingestion simply carries on,
+//! since elapsed time is measured from the session start. After a longer
+//! one the roast can't be trusted any more: the session is marked `failed`,
+//! ending at its last point, with a `custom` event annotating the gap.
+//! Paused sessions are left alone.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::{CreateRoastEventRequest, RoastEventType};
+use crate::services::RoastSessionService;
+
+const DEFAULT_MAX_GAP_SECS: i64 = 120;
+
+pub fn max_gap_from_env() -> Duration {
+    let secs = std::env::var("RUSTROAST_RESUME_MAX_GAP_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v >= 0)
+        .unwrap_or(DEFAULT_MAX_GAP_SECS);
+    Duration::seconds(secs)
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub resumed: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// Resume or fail the active sessions, judging the gap since each one's
+/// last telemetry point (or its start) at `now`.
+pub async fn recover_sessions(
+    sessions: &RoastSessionService,
+    max_gap: Duration,
+    now: DateTime<Utc>,
+) -> Result<RecoveryReport> {
+    let mut report = RecoveryReport::default();
+    for (session, last_elapsed) in sessions.active_sessions_with_last_point().await? {
+        let Some(start) = session.start_time else {
+            continue;
+        };
+        let elapsed = last_elapsed.unwrap_or(0.0).max(0.0);
+        let last_seen = start + Duration::milliseconds((elapsed * 1000.0) as i64);
+        let gap = now - last_seen;
+        if gap <= max_gap {
+            tracing::info!(
+                session_id = %session.id, device_id = %session.device_id, gap_secs = gap.num_seconds(),
+                "Resuming session interrupted by a restart"
+            );
+            report.resumed.push(session.id);
+            continue;
+        }
+        let note = format!(
+            "Interrupted: no telemetry for {} after {} while the server was down",
+            format_elapsed(gap.num_milliseconds() as f64 / 1000.0),
+            format_elapsed(elapsed)
+        );
+        sessions
+            .create_roast_event(
+                &session.id,
+                CreateRoastEventRequest {
+                    event_type: RoastEventType::Custom,
+                    elapsed_seconds: elapsed as f32,
+                    temperature: None,
+                    notes: Some(note),
+                },
+            )
+            .await?;
+        sessions
+            .fail_session(&session.id, last_seen, elapsed.round() as i32)
+            .await?;
+        tracing::warn!(
+            session_id = %session.id, device_id = %session.device_id, gap_secs = gap.num_seconds(),
+            "Marked session interrupted by a long outage as failed"
+        );
+        report.failed.push(session.id);
+    }
+    Ok(report)
+}
+
+/// `m:ss` of a roast's elapsed time.
+fn format_elapsed(secs: f64) -> String {
+    let secs = secs.round() as i64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SessionStatus;
+
+    #[tokio::test]
+    async fn test_recovery_resumes_short_gaps_and_fails_long_ones() {
+        let sessions = RoastSessionService::new(crate::init_memory_db().await.unwrap());
+        let mut ids = Vec::new();
+        for device_id in ["short", "long", "paused"] {
+            let req = serde_json::from_value(
+                serde_json::json!({"name": device_id, "device_id": device_id}),
+            )
+            .unwrap();
+            let session = sessions.create_session(req).await.unwrap();
+            sessions.start_session(&session.id).await.unwrap();
+            sessions
+                .add_telemetry_point(
+                    &session.id,
+                    300.0,
+                    Some(180.0),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            ids.push(session.id);
+        }
+        sessions.pause_session(&ids[2]).await.unwrap();
+        let start = sessions
+            .get_session(&ids[0])
+            .await
+            .unwrap()
+            .unwrap()
+            .start_time
+            .unwrap();
+
+        // 60s since the last point of a session started 6 minutes ago
+        let now = start + Duration::seconds(360);
+        let report = recover_sessions(&sessions, Duration::seconds(120), now).await;
+        assert_eq!(
+            report.unwrap(),
+            RecoveryReport {
+                resumed: ids[..2].to_vec(),
+                failed: vec![],
+            }
+        );
+
+        let now = start + Duration::seconds(900);
+        let report = recover_sessions(&sessions, Duration::seconds(120), now)
+            .await
+            .unwrap();
+        assert_eq!(report.failed.len(), 2);
+        let failed = sessions.get_session(&ids[1]).await.unwrap().unwrap();
+        assert_eq!(failed.status, SessionStatus::Failed);
+        assert_eq!(failed.total_time_seconds, Some(300));
+        assert_eq!(
+            failed.end_time,
+            Some(failed.start_time.unwrap() + Duration::seconds(300))
+        );
+        let events = sessions.get_roast_events(&ids[1]).await.unwrap();
+        assert_eq!(events[0].event_type, RoastEventType::Custom);
+        assert_eq!(
+            events[0].notes.as_deref(),
+            Some("Interrupted: no telemetry for 10:00 after 5:00 while the server was down")
+        );
+        let paused = sessions.get_session(&ids[2]).await.unwrap().unwrap();
+        assert_eq!(paused.status, SessionStatus::Paused);
+    }
+}
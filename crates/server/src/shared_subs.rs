@@ -0,0 +1,157 @@
+//! Shared subscriptions, for splitting ingestion between server instances.
+//!
+//! With `MQTT_SHARED_GROUP` set, the wildcards named in
+//! `MQTT_SHARED_WILDCARDS` (default `telemetry`) are subscribed as
+//! `$share/{group}/{filter}`: the broker hands each publish on them to one
+//! instance of the group rather than to every instance. The others stay
+//! per instance. Retained messages are not delivered on shared
+//! subscriptions, and every instance needs each device's status.
+//!
+//! The `roaster/#` catch-all behind `/ws/debug` would bring every publish
+//! to every instance again, so with a group it is replaced by the log
+//! wildcard. `/ws/debug` then shows only what the instance itself receives.
+//!
+//! Each wildcard is subscribed in both topic layouts (`roaster/+/...` and
+//! `roaster/v2/+/...`), so devices on either firmware schema are heard.
+
+use rustroast_core::TopicLayout;
+use rustroast_mqtt::shared_filter;
+
+/// Wildcards that can be shared, by the names `MQTT_SHARED_WILDCARDS` uses,
+/// with their path below the device.
+const WILDCARDS: &[(&str, &str)] = &[
+    ("telemetry", "telemetry"),
+    ("status", "status"),
+    ("autotune", "autotune/#"),
+    ("log", "log"),
+];
+
+const DEFAULT_SHARED: &[&str] = &["telemetry"];
+
+/// Every topic of every device, subscribed without a group for `/ws/debug`.
+pub const CATCH_ALL: &str = "roaster/#";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SharedSubscriptions {
+    /// `None` subscribes every wildcard per instance.
+    group: Option<String>,
+    /// Names of the wildcards shared in the group.
+    shared: Vec<String>,
+}
+
+impl SharedSubscriptions {
+    pub fn new(group: &str, shared: &[&str]) -> Result<Self, String> {
+        if group.is_empty() || group.contains(['/', '+', '#']) {
+            return Err(format!("invalid shared subscription group {:?}", group));
+        }
+        let unknown: Vec<&str> = shared
+            .iter()
+            .copied()
+            .filter(|name| !WILDCARDS.iter().any(|(w, _)| w == name))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "unknown wildcards: {} (expected telemetry, status, autotune or log)",
+                unknown.join(", ")
+            ));
+        }
+        Ok(Self {
+            group: Some(group.to_string()),
+            shared: shared.iter().map(|name| name.to_string()).collect(),
+        })
+    }
+
+    /// From `MQTT_SHARED_GROUP` and `MQTT_SHARED_WILDCARDS`. An invalid
+    /// setting is logged and subscribes per instance.
+    pub fn from_env() -> Self {
+        let Some(group) = std::env::var("MQTT_SHARED_GROUP")
+            .ok()
+            .filter(|g| !g.is_empty())
+        else {
+            return Self::default();
+        };
+        let shared = std::env::var("MQTT_SHARED_WILDCARDS").unwrap_or_default();
+        let mut shared: Vec<&str> = shared
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        if shared.is_empty() {
+            shared = DEFAULT_SHARED.to_vec();
+        }
+        Self::new(&group, &shared).unwrap_or_else(|e| {
+            tracing::error!(error = %e, "Ignoring MQTT shared subscription settings");
+            Self::default()
+        })
+    }
+
+    pub fn is_sharing(&self, name: &str) -> bool {
+        self.group.is_some() && self.shared.iter().any(|s| s == name)
+    }
+
+    /// Topic filters to subscribe to, with the wildcard's name for logs.
+    pub fn filters(&self) -> Vec<(&'static str, String)> {
+        let mut filters: Vec<(&'static str, String)> = TopicLayout::ALL
+            .iter()
+            .flat_map(|layout| {
+                WILDCARDS
+                    .iter()
+                    .map(move |(name, path)| (name, layout.wildcard(path)))
+            })
+            .filter(|(name, _)| self.group.is_some() || **name != "log")
+            .map(|(name, wildcard)| {
+                let filter = match &self.group {
+                    Some(group) if self.is_sharing(name) => shared_filter(group, &wildcard),
+                    _ => wildcard,
+                };
+                (*name, filter)
+            })
+            .collect();
+        if self.group.is_none() {
+            filters.push(("debug", CATCH_ALL.to_string()));
+        }
+        filters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_share_the_chosen_wildcards() {
+        let filters = |subs: &SharedSubscriptions| -> Vec<String> {
+            subs.filters().into_iter().map(|(_, f)| f).collect()
+        };
+        assert_eq!(
+            filters(&SharedSubscriptions::default()),
+            [
+                "roaster/+/telemetry",
+                "roaster/+/status",
+                "roaster/+/autotune/#",
+                "roaster/v2/+/telemetry",
+                "roaster/v2/+/status",
+                "roaster/v2/+/autotune/#",
+                "roaster/#"
+            ]
+        );
+
+        let subs = SharedSubscriptions::new("rustroast", &["telemetry", "log"]).unwrap();
+        assert!(subs.is_sharing("telemetry") && !subs.is_sharing("status"));
+        assert_eq!(
+            filters(&subs),
+            [
+                "$share/rustroast/roaster/+/telemetry",
+                "roaster/+/status",
+                "roaster/+/autotune/#",
+                "$share/rustroast/roaster/+/log",
+                "$share/rustroast/roaster/v2/+/telemetry",
+                "roaster/v2/+/status",
+                "roaster/v2/+/autotune/#",
+                "$share/rustroast/roaster/v2/+/log"
+            ]
+        );
+        assert!(SharedSubscriptions::new("rust/roast", &["telemetry"]).is_err());
+        assert!(SharedSubscriptions::new("rustroast", &["control"]).is_err());
+    }
+}
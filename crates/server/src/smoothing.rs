@@ -0,0 +1,211 @@
+//! Server-side curve smoothing, so every client draws the same BT, ET and
+//! RoR curves from a [`SmoothingConfig`].
+//!
+//! Windows trail each sample, as they do on a live chart. BT/ET are the
+//! mean of the window. RoR is computed from the raw bean temperatures in
+//! its window with the same algorithms the dashboard uses, reported in the
+//! config's unit (°C/min or °C/30s) and rounded to 0.1.
+
+use crate::models::{RorAlgorithm, SessionTelemetry, SmoothedPoint, SmoothingConfig};
+
+/// Smooth a session's telemetry, ordered by elapsed time.
+pub fn smooth_telemetry(
+    points: &[SessionTelemetry],
+    config: &SmoothingConfig,
+) -> Vec<SmoothedPoint> {
+    (0..points.len())
+        .map(|i| SmoothedPoint {
+            elapsed_seconds: points[i].elapsed_seconds,
+            bean_temp: trailing_mean(points, i, config.bt_window_secs, &|p| p.bean_temp),
+            env_temp: trailing_mean(points, i, config.et_window_secs, &|p| p.env_temp),
+            rate_of_rise: rate_of_rise(points, i, config),
+        })
+        .collect()
+}
+
+/// Mean of the window ending at `end`, `None` where the sample has no value.
+fn trailing_mean(
+    points: &[SessionTelemetry],
+    end: usize,
+    secs: f32,
+    value: &dyn Fn(&SessionTelemetry) -> Option<f32>,
+) -> Option<f32> {
+    let raw = value(&points[end])?;
+    if secs <= 0.0 {
+        return Some(raw);
+    }
+    let samples = window(points, end, secs, value);
+    let mean = samples.iter().map(|(_, v)| v).sum::<f64>() / samples.len() as f64;
+    Some(mean as f32)
+}
+
+/// RoR at `end` over the RoR window in the config's unit, rounded to 0.1.
+fn rate_of_rise(points: &[SessionTelemetry], end: usize, config: &SmoothingConfig) -> Option<f32> {
+    points[end].bean_temp?;
+    let samples = window(points, end, config.ror_window_secs, &|p| p.bean_temp);
+    let slope = slope(config.ror_algorithm, &samples)?;
+    let ror = slope * 60.0 * config.ror_unit.factor() as f64;
+    Some(((ror * 10.0).round() / 10.0) as f32)
+}
+
+/// `(elapsed, value)` samples within `secs` before and including `end`.
+fn window(
+    points: &[SessionTelemetry],
+    end: usize,
+    secs: f32,
+    value: &dyn Fn(&SessionTelemetry) -> Option<f32>,
+) -> Vec<(f64, f64)> {
+    let start_at = points[end].elapsed_seconds - secs;
+    let mut samples: Vec<(f64, f64)> = points[..=end]
+        .iter()
+        .rev()
+        .take_while(|p| p.elapsed_seconds >= start_at)
+        .filter_map(|p| value(p).map(|v| (p.elapsed_seconds as f64, v as f64)))
+        .collect();
+    samples.reverse();
+    samples
+}
+
+/// Slope of `(seconds, value)` samples per second, by `algorithm`.
+pub fn slope(algorithm: RorAlgorithm, samples: &[(f64, f64)]) -> Option<f64> {
+    match algorithm {
+        RorAlgorithm::MovingAverage => endpoint_slope(samples),
+        RorAlgorithm::WeightedMovingAverage => weighted_slope(samples),
+        RorAlgorithm::SavitzkyGolay => savitzky_golay_slope(samples),
+    }
+}
+
+/// Difference between the first and last sample, per second.
+fn endpoint_slope(samples: &[(f64, f64)]) -> Option<f64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let dt = last.0 - first.0;
+    (dt > 0.0).then(|| (last.1 - first.1) / dt)
+}
+
+/// Least-squares slope with linearly increasing weights, per second.
+fn weighted_slope(samples: &[(f64, f64)]) -> Option<f64> {
+    let n = samples.len();
+    if n < 2 {
+        return None;
+    }
+    let w_total = (n * (n + 1)) as f64 / 2.0;
+    let t0 = samples[0].0;
+    let (mut sw, mut swt, mut swy, mut swtt, mut swty) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (i, (t, y)) in samples.iter().enumerate() {
+        let w = (i + 1) as f64 / w_total;
+        let t = t - t0;
+        sw += w;
+        swt += w * t;
+        swy += w * y;
+        swtt += w * t * t;
+        swty += w * t * y;
+    }
+    let denom = sw * swtt - swt * swt;
+    (denom.abs() >= 1e-10).then(|| (sw * swty - swt * swy) / denom)
+}
+
+/// Slope at the window's middle sample of a quadratic least-squares fit,
+/// per second.
+fn savitzky_golay_slope(samples: &[(f64, f64)]) -> Option<f64> {
+    if samples.len() < 3 {
+        return None;
+    }
+    let t_mid = samples[samples.len() / 2].0;
+    let (mut s0, mut s1, mut s2, mut s3, mut s4) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    let (mut r0, mut r1, mut r2) = (0.0, 0.0, 0.0);
+    for (t, y) in samples {
+        let t = t - t_mid;
+        let t2 = t * t;
+        s0 += 1.0;
+        s1 += t;
+        s2 += t2;
+        s3 += t * t2;
+        s4 += t2 * t2;
+        r0 += y;
+        r1 += t * y;
+        r2 += t2 * y;
+    }
+    // Cramer's rule on the normal equations, for the linear coefficient
+    let det = s0 * (s2 * s4 - s3 * s3) - s1 * (s1 * s4 - s2 * s3) + s2 * (s1 * s3 - s2 * s2);
+    if det.abs() < 1e-10 {
+        return None;
+    }
+    let det_b = s0 * (r1 * s4 - r2 * s3) - r0 * (s1 * s4 - s2 * s3) + s2 * (s1 * r2 - s2 * r1);
+    Some(det_b / det)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RorUnit;
+    use chrono::Utc;
+
+    fn point(elapsed: f32, bean_temp: Option<f32>, env_temp: f32) -> SessionTelemetry {
+        SessionTelemetry {
+            id: String::new(),
+            session_id: String::new(),
+            timestamp: Utc::now(),
+            elapsed_seconds: elapsed,
+            bean_temp,
+            env_temp: Some(env_temp),
+            rate_of_rise: None,
+            heater_pwm: None,
+            fan_pwm: None,
+            setpoint: None,
+            airflow: None,
+        }
+    }
+
+    #[test]
+    fn test_smoothing_windows_and_ror_algorithms() {
+        // Bean temp rising 0.5 °C/s, env temp alternating around 200
+        let points: Vec<SessionTelemetry> = (0..60)
+            .map(|i| {
+                let env = if i % 2 == 0 { 199.0 } else { 201.0 };
+                point(i as f32, Some(100.0 + 0.5 * i as f32), env)
+            })
+            .collect();
+
+        for ror_algorithm in [
+            RorAlgorithm::MovingAverage,
+            RorAlgorithm::WeightedMovingAverage,
+            RorAlgorithm::SavitzkyGolay,
+        ] {
+            let config = SmoothingConfig {
+                ror_algorithm,
+                ..SmoothingConfig::default()
+            };
+            let smoothed = smooth_telemetry(&points, &config);
+            assert_eq!(smoothed[0].rate_of_rise, None);
+            assert_eq!(smoothed[59].rate_of_rise, Some(30.0), "{}", ror_algorithm);
+            // Zero windows leave BT/ET as they were
+            assert_eq!(smoothed[59].bean_temp, points[59].bean_temp);
+            assert_eq!(smoothed[59].env_temp, Some(201.0));
+        }
+
+        let config = SmoothingConfig {
+            bt_window_secs: 2.0,
+            et_window_secs: 1.0,
+            ..SmoothingConfig::default()
+        };
+        let smoothed = smooth_telemetry(&points, &config);
+        // Trailing mean of t = 8, 9, 10
+        assert_eq!(smoothed[10].bean_temp, Some(104.5));
+        assert_eq!(smoothed[10].env_temp, Some(200.0));
+
+        // A fluid-bed roaster's short window in °C/30s
+        let config = SmoothingConfig {
+            ror_window_secs: 5.0,
+            ror_unit: RorUnit::Per30Secs,
+            ..SmoothingConfig::default()
+        };
+        let smoothed = smooth_telemetry(&points, &config);
+        assert_eq!(smoothed[59].rate_of_rise, Some(15.0));
+
+        // Gaps in bean temp stay gaps
+        let gappy = [point(0.0, Some(100.0), 200.0), point(1.0, None, 200.0)];
+        let smoothed = smooth_telemetry(&gappy, &config);
+        assert_eq!(smoothed[1].bean_temp, None);
+        assert_eq!(smoothed[1].rate_of_rise, None);
+    }
+}
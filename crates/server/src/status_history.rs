@@ -0,0 +1,226 @@
+//! History of the status payloads devices publish on
+//! `roaster/{device_id}/status`, so WiFi drops, IP changes and firmware
+//! upgrades can be traced after the fact.
+//!
+//! A snapshot is stored when the status, chip id, IP or firmware version
+//! differs from the device's previous snapshot, and otherwise at most once
+//! a minute. Retained statuses replayed by the broker on subscribing are
+//! only stored when they differ.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rustroast_core::DeviceStatus;
+use sqlx::SqlitePool;
+
+use crate::models::{StatusHistoryQuery, StatusSnapshot};
+
+/// Fields whose change is always recorded.
+const SIGNIFICANT_FIELDS: [&str; 4] = ["status", "id", "ip", "version"];
+const MIN_INTERVAL_SECS: i64 = 60;
+const DEFAULT_HISTORY_LIMIT: i64 = 1000;
+const MAX_HISTORY_LIMIT: i64 = 10_000;
+
+#[derive(Clone)]
+pub struct StatusHistory {
+    db: SqlitePool,
+    /// Time and significant fields of each device's last snapshot, loaded
+    /// from the table on first use.
+    last: Arc<Mutex<HashMap<String, (i64, String)>>>,
+}
+
+impl StatusHistory {
+    pub fn new(db: SqlitePool) -> Self {
+        Self {
+            db,
+            last: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Store a status received at unix time `now` if it is due. Returns
+    /// whether it was stored.
+    pub async fn record(
+        &self,
+        device_id: &str,
+        status: &serde_json::Value,
+        now: i64,
+        retained: bool,
+    ) -> Result<bool> {
+        let key = significant_key(status);
+        let cached = self.last.lock().unwrap().get(device_id).cloned();
+        let last = match cached {
+            Some(last) => Some(last),
+            None => self.load_last(device_id).await?,
+        };
+        let changed = last.as_ref().is_none_or(|(_, k)| *k != key);
+        let due = last
+            .as_ref()
+            .is_none_or(|(ts, _)| now - ts >= MIN_INTERVAL_SECS);
+        if !(changed || (due && !retained)) {
+            return Ok(false);
+        }
+
+        let fields = DeviceStatus::from_payload(status);
+        sqlx::query(
+            r#"
+            INSERT INTO status_history (device_id, ts, status, ip, version, rssi, changed, payload)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(device_id)
+        .bind(now)
+        .bind(fields.status)
+        .bind(fields.ip)
+        .bind(fields.version)
+        .bind(fields.rssi)
+        .bind(changed)
+        .bind(sqlx::types::Json(status))
+        .execute(&self.db)
+        .await?;
+        self.last
+            .lock()
+            .unwrap()
+            .insert(device_id.to_string(), (now, key));
+        Ok(true)
+    }
+
+    /// Unix time of the device's last stored snapshot.
+    pub async fn last_ts(&self, device_id: &str) -> Result<Option<i64>> {
+        let cached = self.last.lock().unwrap().get(device_id).map(|(ts, _)| *ts);
+        match cached {
+            Some(ts) => Ok(Some(ts)),
+            None => Ok(self.load_last(device_id).await?.map(|(ts, _)| ts)),
+        }
+    }
+
+    async fn load_last(&self, device_id: &str) -> Result<Option<(i64, String)>> {
+        let row = sqlx::query_as::<_, (i64, sqlx::types::Json<serde_json::Value>)>(
+            "SELECT ts, payload FROM status_history WHERE device_id = ? ORDER BY ts DESC, id DESC LIMIT 1",
+        )
+        .bind(device_id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(row.map(|(ts, payload)| (ts, significant_key(&payload))))
+    }
+
+    /// Stored snapshots of a device, oldest first.
+    pub async fn history(
+        &self,
+        device_id: &str,
+        q: &StatusHistoryQuery,
+    ) -> Result<Vec<StatusSnapshot>> {
+        let snapshots = sqlx::query_as::<_, StatusSnapshot>(
+            r#"
+            SELECT * FROM (
+                SELECT * FROM status_history
+                WHERE device_id = ? AND ts >= ? AND ts <= ? AND (changed OR NOT ?)
+                ORDER BY ts DESC, id DESC LIMIT ?
+            ) ORDER BY ts, id
+            "#,
+        )
+        .bind(device_id)
+        .bind(q.since.unwrap_or(0))
+        .bind(q.until.unwrap_or(i64::MAX))
+        .bind(q.changes_only)
+        .bind(
+            q.limit
+                .unwrap_or(DEFAULT_HISTORY_LIMIT)
+                .clamp(1, MAX_HISTORY_LIMIT),
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(snapshots)
+    }
+}
+
+fn significant_key(status: &serde_json::Value) -> String {
+    SIGNIFICANT_FIELDS
+        .iter()
+        .map(|f| status.get(*f).map(|v| v.to_string()).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_records_changes_and_once_a_minute() {
+        let db = crate::init_memory_db().await.unwrap();
+        let history = StatusHistory::new(db);
+        let status = |ip: &str, rssi: i64| json!({"status": "online", "id": "chip-1", "ip": ip, "version": "1.2.0", "rssi": rssi});
+
+        assert!(history
+            .record("dev1", &status("10.0.0.5", -60), 1000, false)
+            .await
+            .unwrap());
+        // Only the RSSI moved
+        assert!(!history
+            .record("dev1", &status("10.0.0.5", -62), 1030, false)
+            .await
+            .unwrap());
+        assert!(history
+            .record("dev1", &status("10.0.0.9", -62), 1040, false)
+            .await
+            .unwrap());
+        assert!(history
+            .record("dev1", &status("10.0.0.9", -65), 1100, false)
+            .await
+            .unwrap());
+        // A retained replay of the same status isn't a new snapshot
+        assert!(!history
+            .record("dev1", &status("10.0.0.9", -65), 1300, true)
+            .await
+            .unwrap());
+
+        let all = history
+            .history("dev1", &StatusHistoryQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            all.iter().map(|s| s.ts).collect::<Vec<_>>(),
+            vec![1000, 1040, 1100]
+        );
+        assert_eq!(all[1].ip.as_deref(), Some("10.0.0.9"));
+        assert!(all[1].changed && !all[2].changed);
+
+        let changes = history
+            .history(
+                "dev1",
+                &StatusHistoryQuery {
+                    changes_only: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 2);
+
+        // The last snapshot is picked up again after a restart
+        let restarted = StatusHistory::new(history.db.clone());
+        assert!(!restarted
+            .record("dev1", &status("10.0.0.9", -65), 1120, false)
+            .await
+            .unwrap());
+
+        // Firmware that sends a numeric version and an impossible RSSI
+        assert!(history
+            .record(
+                "dev2",
+                &json!({"status": "online", "ip": "10.0.0.7", "version": 3, "rssi": 40}),
+                1000,
+                false
+            )
+            .await
+            .unwrap());
+        let dev2 = history
+            .history("dev2", &StatusHistoryQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(dev2[0].version.as_deref(), Some("3"));
+        assert_eq!(dev2[0].rssi, None);
+    }
+}
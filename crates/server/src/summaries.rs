@@ -0,0 +1,261 @@
+//! Materialized session summaries for analytics.
+//!
+//! `session_summaries` holds completed-session aggregates per day, bean
+//! origin and profile, which the trends and summary endpoints roll up
+//! instead of grouping every session. [`refresh`] brings it up to date
+//! incrementally: `session_summary_sources` records what each session last
+//! contributed, so only the sessions that completed, changed, were reopened
+//! or were deleted since are looked at, and only their groups recounted.
+//!
+//! The refresher runs on startup (which also fills the tables after
+//! upgrading), whenever a session completes, and every
+//! `RUSTROAST_SUMMARY_REFRESH_SECS` (default 300) to pick up edits and
+//! deletions of completed sessions.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::{Row, SqlitePool};
+use tokio::sync::broadcast;
+
+use crate::cluster::Cluster;
+use crate::models::{SessionActivity, SessionStatus};
+
+const DEFAULT_REFRESH_SECS: u64 = 300;
+
+/// A session's contribution, as stored in `session_summary_sources`.
+const SOURCE_SELECT: &str = r#"
+    SELECT s.id AS session_id,
+        date(COALESCE(s.start_time, s.created_at)) AS day,
+        COALESCE(s.bean_origin, '') AS bean_origin,
+        COALESCE(s.profile_id, '') AS profile_id,
+        CAST(s.total_time_seconds AS REAL) AS total_time_seconds,
+        CAST(s.green_weight AS REAL) AS green_weight,
+        CAST(s.roasted_weight AS REAL) AS roasted_weight,
+        CAST(s.development_time_ratio AS REAL) AS development_time_ratio,
+        CAST(s.weight_loss_pct AS REAL) AS weight_loss_pct,
+        CAST(s.first_crack_time AS REAL) AS first_crack_time
+    FROM roast_sessions s
+    WHERE s.status = ?
+"#;
+
+/// Group of `session_summaries`: day, bean origin and profile id.
+type GroupKey = (Option<String>, String, String);
+
+pub fn refresh_interval_from_env() -> Duration {
+    let secs = std::env::var("RUSTROAST_SUMMARY_REFRESH_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_REFRESH_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Bring `session_summaries` up to date. Returns the number of sessions
+/// whose contribution changed.
+pub async fn refresh(db: &SqlitePool) -> Result<usize> {
+    let completed = SessionStatus::Completed.to_string();
+    let mut tx = db.begin().await?;
+
+    let changed: Vec<String> = sqlx::query_scalar(&format!(
+        r#"
+        SELECT session_id FROM ({SOURCE_SELECT}
+            EXCEPT
+            SELECT session_id, day, bean_origin, profile_id, total_time_seconds,
+                green_weight, roasted_weight, development_time_ratio,
+                weight_loss_pct, first_crack_time
+            FROM session_summary_sources)
+        "#
+    ))
+    .bind(&completed)
+    .fetch_all(&mut *tx)
+    .await?;
+    let removed: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT session_id FROM session_summary_sources
+        WHERE session_id NOT IN (SELECT id FROM roast_sessions WHERE status = ?)
+        "#,
+    )
+    .bind(&completed)
+    .fetch_all(&mut *tx)
+    .await?;
+    if changed.is_empty() && removed.is_empty() {
+        return Ok(0);
+    }
+
+    let mut groups: HashSet<GroupKey> = HashSet::new();
+    for session_id in changed.iter().chain(&removed) {
+        let old = sqlx::query(
+            "DELETE FROM session_summary_sources WHERE session_id = ? RETURNING day, bean_origin, profile_id",
+        )
+        .bind(session_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(row) = old {
+            groups.insert(group_key(&row)?);
+        }
+    }
+    for session_id in &changed {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO session_summary_sources
+                (session_id, day, bean_origin, profile_id, total_time_seconds,
+                 green_weight, roasted_weight, development_time_ratio,
+                 weight_loss_pct, first_crack_time)
+            {SOURCE_SELECT} AND s.id = ?
+            RETURNING day, bean_origin, profile_id
+            "#
+        ))
+        .bind(&completed)
+        .bind(session_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        groups.insert(group_key(&row)?);
+    }
+
+    for (day, bean_origin, profile_id) in &groups {
+        sqlx::query(
+            "DELETE FROM session_summaries WHERE day IS ? AND bean_origin = ? AND profile_id = ?",
+        )
+        .bind(day)
+        .bind(bean_origin)
+        .bind(profile_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO session_summaries
+            SELECT day, bean_origin, profile_id, COUNT(*),
+                SUM(total_time_seconds), SUM(green_weight), SUM(roasted_weight),
+                COUNT(development_time_ratio), SUM(development_time_ratio),
+                MIN(development_time_ratio), MAX(development_time_ratio),
+                COUNT(weight_loss_pct), SUM(weight_loss_pct),
+                MIN(weight_loss_pct), MAX(weight_loss_pct),
+                COUNT(first_crack_time), SUM(first_crack_time),
+                MIN(first_crack_time), MAX(first_crack_time)
+            FROM session_summary_sources
+            WHERE day IS ? AND bean_origin = ? AND profile_id = ?
+            GROUP BY day, bean_origin, profile_id
+            "#,
+        )
+        .bind(day)
+        .bind(bean_origin)
+        .bind(profile_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(changed.len() + removed.len())
+}
+
+fn group_key(row: &sqlx::sqlite::SqliteRow) -> Result<GroupKey> {
+    Ok((
+        row.try_get("day")?,
+        row.try_get("bean_origin")?,
+        row.try_get("profile_id")?,
+    ))
+}
+
+/// Refresh on startup, as sessions complete and every `interval`. The
+/// periodic sweep is left to the cluster leader.
+pub async fn run(
+    db: SqlitePool,
+    cluster: Cluster,
+    mut activity: broadcast::Receiver<SessionActivity>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut first = true;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                if !first && !cluster.is_leader() {
+                    continue;
+                }
+                first = false;
+            }
+            msg = activity.recv() => match msg {
+                Ok(SessionActivity::Completed(_)) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        }
+        match refresh(&db).await {
+            Ok(0) => {}
+            Ok(n) => tracing::debug!(sessions = n, "Refreshed session summaries"),
+            Err(e) => tracing::warn!(error = %e, "Session summary refresh failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_session(db: &SqlitePool, id: &str, origin: &str, start: &str, dtr: f64) {
+        sqlx::query(
+            r#"
+            INSERT INTO roast_sessions
+                (id, name, device_id, status, start_time, bean_origin, development_time_ratio)
+            VALUES (?, ?, 'esp32-001', 'completed', ?, ?, ?)
+            "#,
+        )
+        .bind(id)
+        .bind(id)
+        .bind(start)
+        .bind(origin)
+        .bind(dtr)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    async fn summary(db: &SqlitePool, day: &str, origin: &str) -> Option<(i64, f64, f64)> {
+        sqlx::query_as(
+            r#"
+            SELECT sessions, development_time_ratio_sum, development_time_ratio_max
+            FROM session_summaries WHERE day = ? AND bean_origin = ?
+            "#,
+        )
+        .bind(day)
+        .bind(origin)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_refresh_recounts_only_changed_groups() {
+        let db = crate::init_memory_db().await.unwrap();
+        insert_session(&db, "s1", "Ethiopia", "2026-03-02T10:00:00Z", 0.20).await;
+        insert_session(&db, "s2", "Ethiopia", "2026-03-02T14:00:00Z", 0.22).await;
+        insert_session(&db, "s3", "Colombia", "2026-03-03T10:00:00Z", 0.25).await;
+        assert_eq!(refresh(&db).await.unwrap(), 3);
+        assert_eq!(refresh(&db).await.unwrap(), 0);
+        let (n, sum, max) = summary(&db, "2026-03-02", "Ethiopia").await.unwrap();
+        assert_eq!(n, 2);
+        assert!((sum - 0.42).abs() < 1e-9 && (max - 0.22).abs() < 1e-9);
+
+        // An edit moves the session to another group, a reopen and a
+        // deletion take sessions out
+        sqlx::query("UPDATE roast_sessions SET bean_origin = 'Kenya' WHERE id = 's2'")
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE roast_sessions SET status = 'active' WHERE id = 's1'")
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM roast_sessions WHERE id = 's3'")
+            .execute(&db)
+            .await
+            .unwrap();
+        assert_eq!(refresh(&db).await.unwrap(), 3);
+        assert_eq!(summary(&db, "2026-03-02", "Ethiopia").await, None);
+        assert_eq!(summary(&db, "2026-03-03", "Colombia").await, None);
+        let (n, _, max) = summary(&db, "2026-03-02", "Kenya").await.unwrap();
+        assert_eq!(n, 1);
+        assert!((max - 0.22).abs() < 1e-9);
+    }
+}
@@ -2,14 +2,18 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use prometheus::IntGaugeVec;
+use prometheus::{IntCounterVec, IntGaugeVec};
+use rustroast_core::DeviceError;
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use uuid::Uuid;
 
-use crate::models::DeviceStatus;
-use crate::services::DeviceService;
+use crate::derived::DerivedTelemetry;
+use crate::device_conflict::ConflictDetector;
+use crate::models::{DeviceStatus, FanCalibrationPoint};
+use crate::services::{interpolate_airflow, DeviceService};
+use crate::telemetry_wal::{TelemetryWal, WalRecord};
 
 /// Event broadcast when any device sends telemetry (from any protocol).
 #[derive(Debug, Clone)]
@@ -75,6 +79,11 @@ pub fn parse_telemetry(payload: &[u8]) -> Option<TelemetryPayload> {
     }
 }
 
+/// Most records the telemetry writer inserts in one transaction.
+const MAX_WRITE_BATCH: usize = 500;
+const WRITE_RETRY_MIN: std::time::Duration = std::time::Duration::from_millis(100);
+const WRITE_RETRY_MAX: std::time::Duration = std::time::Duration::from_secs(5);
+
 fn epoch_secs() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -91,9 +100,19 @@ pub struct TelemetryService {
     db: SqlitePool,
     device_service: DeviceService,
     telemetry_last_seen: IntGaugeVec,
+    /// Counts each time a device enters an error `systemStatus`.
+    device_errors: IntCounterVec,
     last_seen_debounce: Arc<std::sync::Mutex<HashMap<String, Instant>>>,
+    /// Fan calibration curves keyed by device_id, loaded on first use.
+    fan_curves: Arc<RwLock<HashMap<String, Arc<Vec<FanCalibrationPoint>>>>>,
     /// Broadcast channel for all processed telemetry events (any protocol).
     telemetry_tx: broadcast::Sender<TelemetryEvent>,
+    /// Devices sharing a device_id are not recorded into sessions.
+    conflicts: ConflictDetector,
+    /// On-disk journal and the batch writer's queue, when journaling.
+    wal: Option<(TelemetryWal, mpsc::UnboundedSender<WalRecord>)>,
+    /// Recent temperatures behind per-client derived values.
+    derived: DerivedTelemetry,
 }
 
 impl TelemetryService {
@@ -102,6 +121,8 @@ impl TelemetryService {
         db: SqlitePool,
         device_service: DeviceService,
         telemetry_last_seen: IntGaugeVec,
+        device_errors: IntCounterVec,
+        conflicts: ConflictDetector,
     ) -> Self {
         let (telemetry_tx, _) = broadcast::channel(256);
         Self {
@@ -109,8 +130,13 @@ impl TelemetryService {
             db,
             device_service,
             telemetry_last_seen,
+            device_errors,
             last_seen_debounce: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            fan_curves: Arc::new(RwLock::new(HashMap::new())),
             telemetry_tx,
+            conflicts,
+            wal: None,
+            derived: DerivedTelemetry::default(),
         }
     }
 
@@ -119,6 +145,10 @@ impl TelemetryService {
         self.telemetry_tx.subscribe()
     }
 
+    pub fn derived(&self) -> &DerivedTelemetry {
+        &self.derived
+    }
+
     /// Process incoming telemetry from any protocol (MQTT, WebSocket, Modbus).
     /// Updates telemetry cache, persists to DB, records to active sessions,
     /// updates metrics, and performs debounced last-seen updates.
@@ -128,7 +158,22 @@ impl TelemetryService {
         payload: &serde_json::Value,
         device_status: Option<&DeviceStatus>,
     ) {
-        let now = epoch_secs();
+        self.process_telemetry_at(device_id, payload, device_status, epoch_secs())
+            .await
+    }
+
+    /// [`process_telemetry`](Self::process_telemetry) for a reading that
+    /// arrived at `received_at` (epoch seconds) rather than just now, so a
+    /// backlog behind a slow consumer keeps its own timestamps.
+    pub async fn process_telemetry_at(
+        &self,
+        device_id: &str,
+        payload: &serde_json::Value,
+        device_status: Option<&DeviceStatus>,
+        received_at: u64,
+    ) {
+        let now = received_at;
+        let payload = &self.with_derived_fields(device_id, payload).await;
 
         // Update metric
         self.telemetry_last_seen
@@ -136,33 +181,87 @@ impl TelemetryService {
             .set(now as i64);
 
         // Always update telemetry cache
-        self.telemetry_cache
+        let previous = self
+            .telemetry_cache
             .write()
             .await
             .insert(device_id.to_string(), (payload.clone(), now));
+        self.count_device_error(device_id, payload, previous.as_ref().map(|(p, _)| p));
 
         // Broadcast to dashboard WebSocket clients
+        self.derived.observe(device_id, payload, Instant::now());
         let _ = self.telemetry_tx.send(TelemetryEvent {
             device_id: device_id.to_string(),
             payload: payload.clone(),
         });
 
-        let payload_str = serde_json::to_string(payload).unwrap_or_default();
+        // Persist to the telemetry table and the device's active session
+        // (skipping the session for disabled devices and for device_ids
+        // several boards are publishing under)
+        let is_disabled = device_status == Some(&DeviceStatus::Disabled);
+        let is_conflicted = self.conflicts.observe(device_id, payload, now).is_some();
+        let record = WalRecord {
+            seq: 0,
+            device_id: device_id.to_string(),
+            ts: now,
+            payload: payload.clone(),
+            record_session: !is_disabled && !is_conflicted,
+        };
+        self.persist(record).await;
+
+        // Debounced last-seen update (at most once per 10 seconds per device)
+        let debounce_interval = std::time::Duration::from_secs(10);
+        let should_update = {
+            let debounce = self.last_seen_debounce.lock().unwrap();
+            debounce
+                .get(device_id)
+                .map(|last| last.elapsed() >= debounce_interval)
+                .unwrap_or(true)
+        };
+        if should_update {
+            if let Err(e) = self.device_service.update_last_seen(device_id).await {
+                tracing::warn!(%device_id, error = %e, "Failed to update last_seen");
+            }
+            self.last_seen_debounce
+                .lock()
+                .unwrap()
+                .insert(device_id.to_string(), Instant::now());
+        }
+    }
 
-        // Persist to general telemetry table
-        let _ = sqlx::query("INSERT INTO telemetry (device_id, ts, payload) VALUES (?, ?, ?)")
-            .bind(device_id)
-            .bind(now as i64)
-            .bind(&payload_str)
-            .execute(&self.db)
-            .await;
+    /// Journal the record and queue it for the batch writer, or insert it
+    /// right away when there is no journal.
+    async fn persist(&self, record: WalRecord) {
+        if let Some((wal, writer)) = &self.wal {
+            match wal.append(record.clone(), writer).await {
+                Ok(()) => return,
+                Err(e) => {
+                    tracing::warn!(error = %e, path = %wal.path().display(), "Failed to journal telemetry, storing it directly");
+                }
+            }
+        }
+        if let Err(e) = self.store(std::slice::from_ref(&record)).await {
+            tracing::warn!(device_id = %record.device_id, error = %e, "Failed to store telemetry");
+        }
+    }
 
-        // Record to active session telemetry (skip for disabled devices)
-        let is_disabled = device_status == Some(&DeviceStatus::Disabled);
-        if !is_disabled {
-            let point_id = Uuid::new_v4().to_string();
-            let result = sqlx::query(r#"
-                INSERT INTO session_telemetry (id, session_id, timestamp, elapsed_seconds, bean_temp, env_temp, rate_of_rise, heater_pwm, fan_pwm, setpoint)
+    /// Insert telemetry records in one transaction, each into the telemetry
+    /// table and, when `record_session` is set, the device's active session.
+    pub async fn store(&self, records: &[WalRecord]) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+        for record in records {
+            let payload_str = serde_json::to_string(&record.payload)?;
+            sqlx::query("INSERT INTO telemetry (device_id, ts, payload) VALUES (?, ?, ?)")
+                .bind(&record.device_id)
+                .bind(record.ts as i64)
+                .bind(&payload_str)
+                .execute(&mut *tx)
+                .await?;
+            if !record.record_session {
+                continue;
+            }
+            sqlx::query(r#"
+                INSERT INTO session_telemetry (id, session_id, timestamp, elapsed_seconds, bean_temp, env_temp, rate_of_rise, heater_pwm, fan_pwm, setpoint, airflow)
                 SELECT ?, s.id, ?,
                        CASE WHEN s.start_time IS NOT NULL
                             THEN CAST(? AS REAL) - CAST(strftime('%s', s.start_time) AS REAL)
@@ -173,45 +272,160 @@ impl TelemetryService {
                        json_extract(?, '$.rateOfRise'),
                        json_extract(?, '$.heaterPWM'),
                        json_extract(?, '$.fanPWM'),
-                       json_extract(?, '$.setpoint')
+                       json_extract(?, '$.setpoint'),
+                       json_extract(?, '$.airflow')
                 FROM roast_sessions s
                 WHERE s.device_id = ? AND s.status = 'active'
             "#)
-                .bind(&point_id)
-                .bind(now as i64)
-                .bind(now as f64)
+                .bind(Uuid::new_v4().to_string())
+                .bind(record.ts as i64)
+                .bind(record.ts as f64)
                 .bind(&payload_str)
                 .bind(&payload_str)
                 .bind(&payload_str)
                 .bind(&payload_str)
                 .bind(&payload_str)
                 .bind(&payload_str)
-                .bind(device_id)
-                .execute(&self.db)
-                .await;
-            if let Err(e) = result {
-                tracing::warn!(%device_id, error = %e, "Failed to insert session telemetry");
+                .bind(&payload_str)
+                .bind(&record.device_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        // Advance the journal watermark with the rows it covers
+        if let Some(seq) = records.iter().map(|r| r.seq).max().filter(|&s| s > 0) {
+            sqlx::query(
+                "UPDATE telemetry_wal_state SET committed_seq = MAX(committed_seq, ?) WHERE id = 1",
+            )
+            .bind(seq as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Highest journal sequence number already stored.
+    pub async fn committed_wal_seq(&self) -> anyhow::Result<u64> {
+        let seq: Option<i64> =
+            sqlx::query_scalar("SELECT committed_seq FROM telemetry_wal_state WHERE id = 1")
+                .fetch_optional(&self.db)
+                .await?;
+        Ok(seq.unwrap_or(0) as u64)
+    }
+
+    /// Whether telemetry is journaled before it is stored.
+    pub fn is_journaled(&self) -> bool {
+        self.wal.is_some()
+    }
+
+    /// Journal telemetry to `wal` and store it from a background writer in
+    /// batches of whatever queued up during the previous insert. Call on
+    /// the service before it is cloned.
+    pub fn with_wal(mut self, wal: TelemetryWal) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<WalRecord>();
+        let writer = self.clone();
+        let journal = wal.clone();
+        tokio::spawn(async move {
+            let mut batch = Vec::new();
+            while let Some(record) = rx.recv().await {
+                batch.push(record);
+                while batch.len() < MAX_WRITE_BATCH {
+                    match rx.try_recv() {
+                        Ok(record) => batch.push(record),
+                        Err(_) => break,
+                    }
+                }
+                // Keep retrying while the database is locked or unwritable;
+                // the journal holds the batch meanwhile
+                let mut delay = WRITE_RETRY_MIN;
+                while let Err(e) = writer.store(&batch).await {
+                    tracing::warn!(error = %e, records = batch.len(), "Failed to store telemetry batch, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(WRITE_RETRY_MAX);
+                }
+                let seq = batch.iter().map(|r| r.seq).max().unwrap_or(0);
+                if let Err(e) = journal.committed(seq).await {
+                    tracing::warn!(error = %e, "Failed to truncate telemetry journal");
+                }
+                batch.clear();
             }
+        });
+        self.wal = Some((wal, tx));
+        self
+    }
+
+    /// Drop cached fan calibration curves so the next telemetry reloads them.
+    pub async fn invalidate_fan_curves(&self) {
+        self.fan_curves.write().await.clear();
+    }
+
+    /// Count a device entering an error `systemStatus`, rather than every
+    /// sample it stays in it.
+    fn count_device_error(
+        &self,
+        device_id: &str,
+        payload: &serde_json::Value,
+        previous: Option<&serde_json::Value>,
+    ) {
+        let Some(error) = DeviceError::from_payload(payload) else {
+            return;
+        };
+        if previous.and_then(DeviceError::from_payload) == Some(error) {
+            return;
         }
+        tracing::warn!(%device_id, code = error.code(), error = %error, "Device reported an error");
+        self.device_errors
+            .with_label_values(&[device_id, error.name()])
+            .inc();
+    }
 
-        // Debounced last-seen update (at most once per 10 seconds per device)
-        let debounce_interval = std::time::Duration::from_secs(10);
-        let should_update = {
-            let debounce = self.last_seen_debounce.lock().unwrap();
-            debounce
-                .get(device_id)
-                .map(|last| last.elapsed() >= debounce_interval)
-                .unwrap_or(true)
+    /// Add server-derived fields to a telemetry payload: `systemError`
+    /// decoding a non-zero `systemStatus`, and `airflow` from the device's
+    /// fan calibration curve when one is configured.
+    async fn with_derived_fields(
+        &self,
+        device_id: &str,
+        payload: &serde_json::Value,
+    ) -> serde_json::Value {
+        let mut payload = payload.clone();
+        if let (Some(error), Some(obj)) =
+            (DeviceError::from_payload(&payload), payload.as_object_mut())
+        {
+            obj.insert("systemError".to_string(), serde_json::json!(error.info()));
+        }
+        let Some(fan_pwm) = payload.get("fanPWM").and_then(|v| v.as_i64()) else {
+            return payload;
         };
-        if should_update {
-            if let Err(e) = self.device_service.update_last_seen(device_id).await {
-                tracing::warn!(%device_id, error = %e, "Failed to update last_seen");
-            }
-            self.last_seen_debounce
-                .lock()
-                .unwrap()
-                .insert(device_id.to_string(), Instant::now());
+        let curve = self.fan_curve(device_id).await;
+        if let (Some(airflow), Some(obj)) = (
+            interpolate_airflow(&curve, fan_pwm as i32),
+            payload.as_object_mut(),
+        ) {
+            obj.insert("airflow".to_string(), serde_json::json!(airflow));
         }
+        payload
+    }
+
+    async fn fan_curve(&self, device_id: &str) -> Arc<Vec<FanCalibrationPoint>> {
+        if let Some(curve) = self.fan_curves.read().await.get(device_id) {
+            return curve.clone();
+        }
+        let curve = match self
+            .device_service
+            .get_fan_calibration_by_device_id(device_id)
+            .await
+        {
+            Ok(points) => Arc::new(points),
+            Err(e) => {
+                tracing::warn!(%device_id, error = %e, "Failed to load fan calibration");
+                Arc::new(Vec::new())
+            }
+        };
+        self.fan_curves
+            .write()
+            .await
+            .insert(device_id.to_string(), curve.clone());
+        curve
     }
 }
 
@@ -272,4 +486,32 @@ mod tests {
         let payload = b"not json at all";
         assert!(parse_telemetry(payload).is_none());
     }
+
+    #[tokio::test]
+    async fn test_system_status_errors_are_decoded_and_counted() {
+        let db = crate::init_memory_db().await.unwrap();
+        let gauge = IntGaugeVec::new(prometheus::Opts::new("g", "g"), &["device_id"]).unwrap();
+        let errors =
+            IntCounterVec::new(prometheus::Opts::new("e", "e"), &["device_id", "error"]).unwrap();
+        let service = TelemetryService::new(
+            Arc::new(RwLock::new(HashMap::new())),
+            db.clone(),
+            DeviceService::new(db),
+            gauge,
+            errors.clone(),
+            ConflictDetector::new(0),
+        );
+
+        for status in [3, 3, 0, 3, 42] {
+            let payload = serde_json::json!({"beanTemp": 180.0, "systemStatus": status});
+            service.process_telemetry("dev1", &payload, None).await;
+        }
+        // Each entry into an error counts, a repeat of the same code doesn't
+        assert_eq!(errors.with_label_values(&["dev1", "unknown"]).get(), 3);
+
+        let cache = service.telemetry_cache.read().await;
+        let (latest, _) = &cache["dev1"];
+        assert_eq!(latest["systemError"]["code"], 42);
+        assert_eq!(latest["systemError"]["name"], "unknown");
+    }
 }
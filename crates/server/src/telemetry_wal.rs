@@ -0,0 +1,377 @@
+//! Append-only journal of telemetry samples not yet stored in SQLite.
+//!
+//! With the journal enabled the consumer appends every sample here (and
+//! syncs it to disk) before handing it to a background writer that inserts
+//! whatever has queued up in one transaction. Every record carries a
+//! sequence number and the writer stores the highest one it inserted in
+//! `telemetry_wal_state`, within that same transaction. A crash or a locked
+//! database mid-roast therefore loses nothing, and on startup only the
+//! records past that watermark are replayed into the database.
+//!
+//! The file is truncated whenever everything appended has been committed.
+//! Should the writer never fully catch up, the journal is rewritten down to
+//! its uncommitted records once it outgrows [`COMPACT_BYTES`].
+//!
+//! The file holds length-prefixed records: a little-endian `u32` byte count
+//! followed by the JSON of a [`WalRecord`]. A record torn by a crash while
+//! it was written ends the replay. Appends, truncation and compaction do
+//! blocking file I/O and run on tokio's blocking pool.
+//!
+//! `RUSTROAST_TELEMETRY_WAL` sets the path, by default next to the database
+//! (`{RUSTROAST_DB_PATH}.telemetry-wal`). `off` disables the journal and
+//! samples are inserted one by one as they arrive.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+/// Larger length prefixes mean the file is corrupt, not a real sample.
+const MAX_RECORD_BYTES: usize = 1 << 20;
+
+/// Journal size past which committed records are dropped from it even
+/// though some are still pending.
+pub const COMPACT_BYTES: u64 = 8 << 20;
+
+/// A telemetry sample waiting to be stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalRecord {
+    /// Journal sequence number, 0 for samples stored without the journal.
+    pub seq: u64,
+    pub device_id: String,
+    /// Unix seconds the sample was received.
+    pub ts: u64,
+    pub payload: serde_json::Value,
+    /// Whether to also record it into the device's active session.
+    pub record_session: bool,
+}
+
+#[derive(Clone)]
+pub struct TelemetryWal {
+    path: PathBuf,
+    inner: Arc<Mutex<WalFile>>,
+}
+
+struct WalFile {
+    file: File,
+    /// Bytes in the file.
+    len: u64,
+    /// Sequence number of the last record appended.
+    last_seq: u64,
+    /// Everything up to this sequence number is in the database.
+    committed_seq: u64,
+}
+
+impl TelemetryWal {
+    /// Journal path from `RUSTROAST_TELEMETRY_WAL`, `None` when disabled.
+    pub fn path_from_env(db_path: &str) -> Option<PathBuf> {
+        match std::env::var("RUSTROAST_TELEMETRY_WAL") {
+            Ok(v) if v == "off" || v == "false" => None,
+            Ok(v) if !v.is_empty() => Some(PathBuf::from(v)),
+            _ => Some(PathBuf::from(format!("{}.telemetry-wal", db_path))),
+        }
+    }
+
+    /// Open (or create) the journal, returning the records a previous run
+    /// left in it past `committed_seq`, the database's watermark. They stay
+    /// in the journal until [`Self::committed`].
+    pub fn open(path: &Path, committed_seq: u64) -> io::Result<(Self, Vec<WalRecord>)> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let mut records = decode(&bytes);
+        let last_seq = records
+            .iter()
+            .map(|r| r.seq)
+            .max()
+            .unwrap_or(0)
+            .max(committed_seq);
+        records.retain(|r| r.seq > committed_seq);
+        let mut inner = WalFile {
+            file,
+            len: bytes.len() as u64,
+            last_seq,
+            committed_seq,
+        };
+        if !bytes.is_empty() {
+            // Drop committed and torn records so appends follow the
+            // pending ones directly
+            inner.rewrite(path, &records)?;
+        }
+        let wal = Self {
+            path: path.to_path_buf(),
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        Ok((wal, records))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number the record, append it and sync it to disk, then pass it to
+    /// `queue`. Both happen under the journal lock, so records reach the
+    /// queue in sequence order.
+    pub async fn append(
+        &self,
+        mut record: WalRecord,
+        queue: &mpsc::UnboundedSender<WalRecord>,
+    ) -> io::Result<()> {
+        let inner = self.inner.clone();
+        let queue = queue.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = inner.lock().unwrap();
+            record.seq = inner.last_seq + 1;
+            let buf = encode(&record)?;
+            if let Err(e) = inner
+                .file
+                .write_all(&buf)
+                .and_then(|()| inner.file.sync_data())
+            {
+                // Cut off a partial write so later records stay readable
+                let len = inner.len;
+                let _ = inner.file.set_len(len);
+                return Err(e);
+            }
+            inner.len += buf.len() as u64;
+            inner.last_seq = record.seq;
+            // Should the writer be gone the journal keeps the record for
+            // replay on the next start
+            if queue.send(record).is_err() {
+                tracing::error!("Telemetry writer stopped, sample left in the journal");
+            }
+            Ok(())
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    /// Mark records up to `seq` as stored. The journal is truncated once
+    /// nothing past them is pending, and compacted once it outgrows
+    /// [`COMPACT_BYTES`].
+    pub async fn committed(&self, seq: u64) -> io::Result<()> {
+        let wal = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = wal.inner.lock().unwrap();
+            inner.committed_seq = inner.committed_seq.max(seq);
+            if inner.committed_seq >= inner.last_seq {
+                inner.truncate()
+            } else if inner.len > COMPACT_BYTES {
+                inner.compact(&wal.path)
+            } else {
+                Ok(())
+            }
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+}
+
+impl WalFile {
+    fn truncate(&mut self) -> io::Result<()> {
+        if self.len > 0 {
+            self.file.set_len(0)?;
+            self.file.sync_data()?;
+            self.len = 0;
+        }
+        Ok(())
+    }
+
+    /// Drop the records the database already holds.
+    fn compact(&mut self, path: &Path) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut bytes)?;
+        let mut records = decode(&bytes);
+        records.retain(|r| r.seq > self.committed_seq);
+        self.rewrite(path, &records)
+    }
+
+    /// Replace the journal with `records`. The new file is synced before
+    /// it is renamed over the old one, and should the rename not survive a
+    /// crash the old file only repeats records the watermark skips.
+    fn rewrite(&mut self, path: &Path, records: &[WalRecord]) -> io::Result<()> {
+        if records.is_empty() {
+            return self.truncate();
+        }
+        let tmp = PathBuf::from(format!("{}.tmp", path.display()));
+        let mut out = File::create(&tmp)?;
+        let mut len = 0;
+        for record in records {
+            let buf = encode(record)?;
+            out.write_all(&buf)?;
+            len += buf.len() as u64;
+        }
+        out.sync_data()?;
+        std::fs::rename(&tmp, path)?;
+        self.file = OpenOptions::new().read(true).append(true).open(path)?;
+        self.len = len;
+        Ok(())
+    }
+}
+
+fn encode(record: &WalRecord) -> io::Result<Vec<u8>> {
+    let json = serde_json::to_vec(record)?;
+    let mut buf = Vec::with_capacity(4 + json.len());
+    buf.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&json);
+    Ok(buf)
+}
+
+/// Records up to the first torn or oversized one. Records that are not
+/// valid JSON are skipped.
+fn decode(mut bytes: &[u8]) -> Vec<WalRecord> {
+    let mut records = Vec::new();
+    while bytes.len() >= 4 {
+        let len = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        if len > MAX_RECORD_BYTES || bytes.len() < 4 + len {
+            tracing::warn!(
+                bytes = bytes.len(),
+                "Telemetry journal ends in a torn record, ignoring it"
+            );
+            break;
+        }
+        match serde_json::from_slice(&bytes[4..4 + len]) {
+            Ok(record) => records.push(record),
+            Err(e) => tracing::warn!(error = %e, "Skipping unreadable telemetry journal record"),
+        }
+        bytes = &bytes[4 + len..];
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ts: u64) -> WalRecord {
+        WalRecord {
+            seq: 0,
+            device_id: "wal1".into(),
+            ts,
+            payload: serde_json::json!({"beanTemp": 150.0, "envTemp": 180.0}),
+            record_session: true,
+        }
+    }
+
+    fn numbered(seq: u64, ts: u64) -> WalRecord {
+        WalRecord { seq, ..record(ts) }
+    }
+
+    async fn stored_rows(db: &sqlx::SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM telemetry WHERE device_id = 'wal1'")
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_journal_survives_a_crash_and_is_replayed() {
+        let dir = std::env::temp_dir().join(format!("rr-wal-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("telemetry-wal");
+        let (queue, mut queued) = mpsc::unbounded_channel();
+
+        let (wal, replay) = TelemetryWal::open(&path, 0).unwrap();
+        assert!(replay.is_empty());
+        wal.append(record(100), &queue).await.unwrap();
+        wal.append(record(101), &queue).await.unwrap();
+        assert_eq!(queued.recv().await.unwrap(), numbered(1, 100));
+        assert_eq!(queued.recv().await.unwrap(), numbered(2, 101));
+        drop(wal);
+        // Crash halfway through writing a third record
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[200, 0, 0, 0, b'{']).unwrap();
+        drop(file);
+
+        let (wal, replay) = TelemetryWal::open(&path, 0).unwrap();
+        assert_eq!(replay, vec![numbered(1, 100), numbered(2, 101)]);
+
+        let db = crate::init_memory_db().await.unwrap();
+        let (mqtt, _published) = rustroast_mqtt::MqttService::mock();
+        let state = crate::build_state(mqtt, db.clone(), db.clone());
+        state.telemetry_service.store(&replay).await.unwrap();
+        assert_eq!(
+            state.telemetry_service.committed_wal_seq().await.unwrap(),
+            2
+        );
+        wal.committed(2).await.unwrap();
+        assert_eq!(stored_rows(&db).await, 2);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        // Appends after a truncation continue the sequence, past the torn
+        // record the reopen dropped
+        wal.append(record(102), &queue).await.unwrap();
+        drop(wal);
+        let (_, replay) = TelemetryWal::open(&path, 2).unwrap();
+        assert_eq!(replay, vec![numbered(3, 102)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_skips_records_the_database_already_holds() {
+        let dir = std::env::temp_dir().join(format!("rr-wal-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("telemetry-wal");
+        let (queue, _queued) = mpsc::unbounded_channel();
+        let db = crate::init_memory_db().await.unwrap();
+        let (mqtt, _published) = rustroast_mqtt::MqttService::mock();
+        let state = crate::build_state(mqtt, db.clone(), db.clone());
+
+        let (wal, _) = TelemetryWal::open(&path, 0).unwrap();
+        for ts in 100..103 {
+            wal.append(record(ts), &queue).await.unwrap();
+        }
+        // The first two are stored, then the process dies before the
+        // journal hears about it
+        state
+            .telemetry_service
+            .store(&[numbered(1, 100), numbered(2, 101)])
+            .await
+            .unwrap();
+        drop(wal);
+
+        let committed = state.telemetry_service.committed_wal_seq().await.unwrap();
+        let (wal, replay) = TelemetryWal::open(&path, committed).unwrap();
+        assert_eq!(replay, vec![numbered(3, 102)]);
+        state.telemetry_service.store(&replay).await.unwrap();
+        wal.committed(3).await.unwrap();
+        assert_eq!(stored_rows(&db).await, 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_journal_is_compacted_while_records_are_pending() {
+        let dir = std::env::temp_dir().join(format!("rr-wal-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("telemetry-wal");
+        let (queue, _queued) = mpsc::unbounded_channel();
+
+        let (wal, _) = TelemetryWal::open(&path, 0).unwrap();
+        let big = |ts| WalRecord {
+            payload: serde_json::json!({"pad": "x".repeat(64 << 10)}),
+            ..record(ts)
+        };
+        let mut seq = 0;
+        while std::fs::metadata(&path).unwrap().len() <= COMPACT_BYTES {
+            wal.append(big(seq), &queue).await.unwrap();
+            seq += 1;
+        }
+        // The writer always trails by one record, so the journal is never
+        // empty, yet it shrinks to that one record
+        wal.committed(seq - 1).await.unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() < 128 << 10);
+        drop(wal);
+        let (_, replay) = TelemetryWal::open(&path, seq - 1).unwrap();
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].seq, seq);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
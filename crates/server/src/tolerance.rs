@@ -0,0 +1,454 @@
+//! Profile tolerance bands: catching a roast that has left its profile.
+//!
+//! A profile can carry a band (`PUT /api/profiles/{id}/tolerance`) of
+//! `max_deviation` °C around its curve. [`ToleranceMonitor`] follows the
+//! telemetry of active sessions on such profiles, and once the bean temp has
+//! stayed outside the band for `max_outside_secs` it raises a
+//! `tolerance_breach` alert and records the breach as a `custom` roast event.
+//! Depending on the band's action it then also pauses the session or
+//! switches the roaster to auto on the profile's `safe_setpoint`. While the
+//! session stays active the alert resolves when the bean temp is back inside
+//! the band, after which a new excursion can breach again.
+//! The curve is laid from the session's charge event on, see
+//! [`profile_zero`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+
+use crate::models::{
+    AlertKind, AlertSeverity, CreateRoastEventRequest, ProfileTolerance, RoastEventType,
+    RoastSession, SessionStatus, SetProfileToleranceRequest, ToleranceAction,
+};
+use crate::profile_curve::ProfileCurve;
+use crate::services::profile_zero;
+use crate::telemetry::TelemetryEvent;
+use crate::{AppState, ControlOp, ModePayload, PublishOpts, SetpointPayload};
+
+/// How often a followed session's band is reloaded, so edits apply mid-roast.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+pub async fn get(db: &SqlitePool, profile_id: &str) -> Result<Option<ProfileTolerance>> {
+    let tolerance = sqlx::query_as::<_, ProfileTolerance>(
+        "SELECT * FROM profile_tolerances WHERE profile_id = ?",
+    )
+    .bind(profile_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(tolerance)
+}
+
+pub async fn set(
+    db: &SqlitePool,
+    profile_id: &str,
+    req: &SetProfileToleranceRequest,
+) -> Result<ProfileTolerance> {
+    let tolerance = sqlx::query_as::<_, ProfileTolerance>(
+        r#"
+        INSERT INTO profile_tolerances
+            (profile_id, max_deviation, max_outside_secs, action, safe_setpoint, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(profile_id) DO UPDATE SET
+            max_deviation = excluded.max_deviation,
+            max_outside_secs = excluded.max_outside_secs,
+            action = excluded.action,
+            safe_setpoint = excluded.safe_setpoint,
+            updated_at = excluded.updated_at
+        RETURNING *
+        "#,
+    )
+    .bind(profile_id)
+    .bind(req.max_deviation)
+    .bind(req.max_outside_secs)
+    .bind(req.action)
+    .bind(req.safe_setpoint)
+    .bind(Utc::now())
+    .fetch_one(db)
+    .await?;
+    Ok(tolerance)
+}
+
+/// Returns whether the profile had a band.
+pub async fn remove(db: &SqlitePool, profile_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM profile_tolerances WHERE profile_id = ?")
+        .bind(profile_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub fn validate(req: &SetProfileToleranceRequest) -> Result<(), String> {
+    if !(req.max_deviation.is_finite() && req.max_deviation > 0.0) {
+        return Err("max_deviation must be a positive number of °C".to_string());
+    }
+    if !(req.max_outside_secs.is_finite() && req.max_outside_secs >= 0.0) {
+        return Err("max_outside_secs must be 0 or more".to_string());
+    }
+    match (req.action, req.safe_setpoint) {
+        (ToleranceAction::SafeSetpoint, None) => {
+            Err("the safe_setpoint action needs a safe_setpoint".to_string())
+        }
+        (_, Some(t)) if !(0.0..=300.0).contains(&t) => {
+            Err("safe_setpoint must be between 0 and 300 °C".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A roast's position relative to its band.
+#[derive(Debug, Default)]
+struct Band {
+    /// Elapsed seconds at which the bean temp left the band.
+    outside_since: Option<f64>,
+    breached: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum BandChange {
+    /// Outside for longer than allowed, for this many seconds.
+    Breached(f64),
+    /// Back inside after a breach.
+    Recovered,
+}
+
+impl Band {
+    fn observe(
+        &mut self,
+        tolerance: &ProfileTolerance,
+        elapsed: f64,
+        deviation: f64,
+    ) -> Option<BandChange> {
+        if deviation.abs() <= tolerance.max_deviation as f64 {
+            self.outside_since = None;
+            return std::mem::take(&mut self.breached).then_some(BandChange::Recovered);
+        }
+        let since = *self.outside_since.get_or_insert(elapsed);
+        let outside = elapsed - since;
+        if !self.breached && outside >= tolerance.max_outside_secs as f64 {
+            self.breached = true;
+            return Some(BandChange::Breached(outside));
+        }
+        None
+    }
+}
+
+/// The band and curve a device's active session is followed against.
+struct Followed {
+    session_id: String,
+    tolerance: Option<ProfileTolerance>,
+    curve: Option<Arc<ProfileCurve>>,
+    /// Elapsed seconds the profile starts at, see [`profile_zero`].
+    zero: f64,
+    loaded_at: Instant,
+    band: Band,
+}
+
+pub struct ToleranceMonitor {
+    state: AppState,
+    followed: HashMap<String, Followed>,
+}
+
+impl ToleranceMonitor {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            followed: HashMap::new(),
+        }
+    }
+
+    pub async fn run(mut self, mut telemetry_rx: broadcast::Receiver<TelemetryEvent>) {
+        loop {
+            match telemetry_rx.recv().await {
+                // Only the cluster leader acts on breaches
+                Ok(evt) if self.state.cluster.is_leader() => {
+                    self.observe(&evt.device_id, &evt.payload).await
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "Tolerance monitor lagged behind telemetry");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn observe(&mut self, device_id: &str, payload: &serde_json::Value) {
+        let Some(bean_temp) = payload.get("beanTemp").and_then(|v| v.as_f64()) else {
+            return;
+        };
+        let session = match self
+            .state
+            .session_service
+            .get_active_session(device_id)
+            .await
+        {
+            Ok(Some(s)) if s.status == SessionStatus::Active => s,
+            Ok(_) => {
+                self.followed.remove(device_id);
+                return;
+            }
+            Err(e) => {
+                tracing::warn!(%device_id, error = %e, "Failed to look up active session for tolerance");
+                return;
+            }
+        };
+        let (Some(start), Some(profile_id)) = (session.start_time, session.profile_id.clone())
+        else {
+            return;
+        };
+        let stale = self
+            .followed
+            .get(device_id)
+            .is_none_or(|f| f.session_id != session.id || f.loaded_at.elapsed() >= RELOAD_INTERVAL);
+        if stale {
+            self.load(device_id, &session.id, &profile_id).await;
+        }
+        let Some(followed) = self.followed.get_mut(device_id) else {
+            return;
+        };
+        let Some(tolerance) = followed.tolerance.clone() else {
+            return;
+        };
+        let elapsed = (Utc::now() - start).num_milliseconds() as f64 / 1000.0;
+        // Nothing to compare with before the beans are charged
+        if elapsed < followed.zero {
+            return;
+        }
+        let Some(curve) = &followed.curve else {
+            return;
+        };
+        let target = curve.target_at((elapsed - followed.zero) as f32);
+        let deviation = bean_temp - target as f64;
+        match followed.band.observe(&tolerance, elapsed, deviation) {
+            Some(BandChange::Breached(outside)) => {
+                self.breach(&session, &tolerance, elapsed, bean_temp, deviation, outside)
+                    .await
+            }
+            Some(BandChange::Recovered) => self.clear(device_id).await,
+            None => {}
+        }
+    }
+
+    /// (Re)load the band and curve of `profile_id`, keeping the band state
+    /// while it is the same session. A breach stays open when the session
+    /// ends or is paused, until someone resolves it.
+    async fn load(&mut self, device_id: &str, session_id: &str, profile_id: &str) {
+        let tolerance = match get(&self.state.db, profile_id).await {
+            Ok(t) => t,
+            Err(e) => {
+                tracing::warn!(%profile_id, error = %e, "Failed to load tolerance band");
+                return;
+            }
+        };
+        let sessions = &self.state.session_service;
+        let curve = match &tolerance {
+            Some(_) => match sessions.get_profile_with_points(profile_id).await {
+                Ok(profile) => profile.and_then(|p| sessions.profile_curve(&p)),
+                Err(e) => {
+                    tracing::warn!(%profile_id, error = %e, "Failed to load profile for tolerance");
+                    return;
+                }
+            },
+            None => None,
+        };
+        // The charge may be marked mid-roast; reloads pick it up
+        let zero = match &tolerance {
+            Some(_) => {
+                match tokio::try_join!(
+                    sessions.get_roast_events(session_id),
+                    sessions.profile_charge_offset()
+                ) {
+                    Ok((events, offset)) => profile_zero(&events, offset) as f64,
+                    Err(e) => {
+                        tracing::warn!(%session_id, error = %e, "Failed to load the charge event for tolerance");
+                        return;
+                    }
+                }
+            }
+            None => 0.0,
+        };
+        let band = self
+            .followed
+            .remove(device_id)
+            .filter(|f| f.session_id == session_id)
+            .map(|f| f.band)
+            .unwrap_or_default();
+        self.followed.insert(
+            device_id.to_string(),
+            Followed {
+                session_id: session_id.to_string(),
+                tolerance,
+                curve,
+                zero,
+                loaded_at: Instant::now(),
+                band,
+            },
+        );
+    }
+
+    async fn breach(
+        &self,
+        session: &RoastSession,
+        tolerance: &ProfileTolerance,
+        elapsed: f64,
+        bean_temp: f64,
+        deviation: f64,
+        outside: f64,
+    ) {
+        let device_id = session.device_id.as_str();
+        let message = format!(
+            "Bean temperature {:.1} °C is {:+.1} °C off the profile, outside the ±{:.1} °C band for {:.0} s",
+            bean_temp, deviation, tolerance.max_deviation, outside
+        );
+        let action_error = self.act(session, tolerance).await;
+        let details = serde_json::json!({
+            "profile_id": tolerance.profile_id,
+            "bean_temp": bean_temp,
+            "deviation": deviation,
+            "max_deviation": tolerance.max_deviation,
+            "outside_secs": outside,
+            "action": tolerance.action,
+            "action_error": action_error,
+        });
+        if let Err(e) = self
+            .state
+            .alerts
+            .raise(
+                AlertKind::ToleranceBreach,
+                AlertSeverity::Critical,
+                Some(device_id),
+                Some(&session.id),
+                &message,
+                Some(details),
+            )
+            .await
+        {
+            tracing::warn!(%device_id, error = %e, "Failed to record tolerance alert");
+        }
+        let notes = match &action_error {
+            None => format!("Tolerance breach: {message} ({})", tolerance.action),
+            Some(e) => format!(
+                "Tolerance breach: {message} ({} failed: {e})",
+                tolerance.action
+            ),
+        };
+        let event = CreateRoastEventRequest {
+            event_type: RoastEventType::Custom,
+            elapsed_seconds: elapsed as f32,
+            temperature: Some(bean_temp as f32),
+            notes: Some(notes),
+        };
+        if let Err(e) = self
+            .state
+            .session_service
+            .create_roast_event(&session.id, event)
+            .await
+        {
+            tracing::warn!(session_id = %session.id, error = %e, "Failed to record tolerance breach");
+        }
+    }
+
+    /// Carry out the band's action, returning why it failed.
+    async fn act(&self, session: &RoastSession, tolerance: &ProfileTolerance) -> Option<String> {
+        let device_id = session.device_id.as_str();
+        match (tolerance.action, tolerance.safe_setpoint) {
+            (ToleranceAction::Alert, _) => None,
+            (ToleranceAction::Pause, _) => {
+                match self.state.session_service.pause_session(&session.id).await {
+                    Ok(_) => {
+                        tracing::warn!(session_id = %session.id, "Paused session off its profile");
+                        None
+                    }
+                    Err(e) => Some(e.to_string()),
+                }
+            }
+            (ToleranceAction::SafeSetpoint, Some(setpoint)) => {
+                let commands = [
+                    ControlOp::Mode(ModePayload {
+                        mode: "auto".to_string(),
+                    }),
+                    ControlOp::Setpoint(SetpointPayload {
+                        value: setpoint as f64,
+                    }),
+                ];
+                for op in &commands {
+                    let resp =
+                        crate::publish_control(&self.state, device_id, op, &PublishOpts::default())
+                            .await;
+                    if !resp.status().is_success() {
+                        return Some(format!(
+                            "{} command failed with status {}",
+                            op.name(),
+                            resp.status()
+                        ));
+                    }
+                }
+                tracing::warn!(%device_id, setpoint, "Sent safe setpoint for a roast off its profile");
+                None
+            }
+            (ToleranceAction::SafeSetpoint, None) => Some("no safe_setpoint set".to_string()),
+        }
+    }
+
+    async fn clear(&self, device_id: &str) {
+        if let Err(e) = self
+            .state
+            .alerts
+            .resolve_cleared(AlertKind::ToleranceBreach, device_id)
+            .await
+        {
+            tracing::warn!(%device_id, error = %e, "Failed to resolve tolerance alert");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_band_breaches_after_the_allowed_time_outside() {
+        let db = crate::init_memory_db().await.unwrap();
+        let mut req = SetProfileToleranceRequest {
+            max_deviation: 5.0,
+            max_outside_secs: 10.0,
+            action: ToleranceAction::SafeSetpoint,
+            safe_setpoint: None,
+        };
+        assert!(validate(&req).is_err());
+        req.safe_setpoint = Some(180.0);
+        validate(&req).unwrap();
+        // The band needs its profile
+        assert!(set(&db, "missing", &req).await.is_err());
+        let tolerance = set(&db, "default-medium", &req).await.unwrap();
+        assert_eq!(
+            get(&db, "default-medium").await.unwrap(),
+            Some(tolerance.clone())
+        );
+
+        let mut band = Band::default();
+        assert_eq!(band.observe(&tolerance, 0.0, 4.0), None);
+        // A short excursion is tolerated
+        assert_eq!(band.observe(&tolerance, 5.0, -6.0), None);
+        assert_eq!(band.observe(&tolerance, 9.0, 3.0), None);
+        assert_eq!(band.observe(&tolerance, 20.0, 7.0), None);
+        assert_eq!(
+            band.observe(&tolerance, 31.0, 8.0),
+            Some(BandChange::Breached(11.0))
+        );
+        assert_eq!(band.observe(&tolerance, 40.0, 9.0), None);
+        assert_eq!(
+            band.observe(&tolerance, 41.0, 1.0),
+            Some(BandChange::Recovered)
+        );
+        assert_eq!(band.observe(&tolerance, 42.0, 1.0), None);
+
+        assert!(remove(&db, "default-medium").await.unwrap());
+        assert!(get(&db, "default-medium").await.unwrap().is_none());
+    }
+}
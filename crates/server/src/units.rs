@@ -0,0 +1,235 @@
+//! Per-request temperature units.
+//!
+//! Temperatures are measured, stored and computed in °C. Read endpoints
+//! for telemetry, sessions and profiles take `?units=f` (or an
+//! `X-Temperature-Units: F` header; the query wins) and convert every
+//! temperature in their response. Rates and differences such as RoR or a
+//! deviation from the profile scale by 9/5 without the 32° offset.
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::models::*;
+
+pub const UNITS_HEADER: &str = "x-temperature-units";
+
+/// Telemetry keys holding absolute temperatures. Extra bean probes
+/// (`beanTemp2`, ...) count too.
+const TELEMETRY_TEMPERATURES: &[&str] = &["beanTemp", "envTemp", "setpoint"];
+const TELEMETRY_RATES: &[&str] = &["rateOfRise"];
+
+/// The unit a request wants temperatures in, °C unless asked otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Units(pub TemperatureUnit);
+
+#[derive(Deserialize)]
+struct UnitsQuery {
+    units: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Units {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let query = Query::<UnitsQuery>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Query(q)| q.units);
+        let requested = query.or_else(|| {
+            parts
+                .headers
+                .get(UNITS_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        });
+        match requested {
+            Some(unit) => unit
+                .trim()
+                .parse()
+                .map(Units)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e)),
+            None => Ok(Units::default()),
+        }
+    }
+}
+
+/// Rewrite the °C values of a response in another unit.
+pub trait ConvertUnits {
+    fn convert_units(&mut self, unit: TemperatureUnit);
+}
+
+impl<T: ConvertUnits> ConvertUnits for Vec<T> {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        self.iter_mut().for_each(|item| item.convert_units(unit));
+    }
+}
+
+impl<T: ConvertUnits> ConvertUnits for Option<T> {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        if let Some(item) = self {
+            item.convert_units(unit);
+        }
+    }
+}
+
+fn absolute(value: &mut Option<f32>, unit: TemperatureUnit) {
+    if let Some(v) = value {
+        *v = unit.convert(*v);
+    }
+}
+
+fn delta(value: &mut Option<f32>, unit: TemperatureUnit) {
+    if let Some(v) = value {
+        *v = unit.convert_delta(*v);
+    }
+}
+
+/// A raw telemetry payload. Converted readings are rounded to 0.01, as
+/// the conversion runs in `f32`.
+impl ConvertUnits for Value {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        if unit == TemperatureUnit::C {
+            return;
+        }
+        let Value::Object(fields) = self else {
+            return;
+        };
+        for (key, value) in fields.iter_mut() {
+            let convert: fn(TemperatureUnit, f32) -> f32 =
+                if TELEMETRY_TEMPERATURES.contains(&key.as_str()) || key.starts_with("beanTemp") {
+                    TemperatureUnit::convert
+                } else if TELEMETRY_RATES.contains(&key.as_str()) {
+                    TemperatureUnit::convert_delta
+                } else {
+                    continue;
+                };
+            let Some(reading) = value.as_f64() else {
+                continue;
+            };
+            let converted = (convert(unit, reading as f32) as f64 * 100.0).round() / 100.0;
+            if let Some(number) = serde_json::Number::from_f64(converted) {
+                *value = Value::Number(number);
+            }
+        }
+    }
+}
+
+impl ConvertUnits for SessionTelemetry {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        absolute(&mut self.bean_temp, unit);
+        absolute(&mut self.env_temp, unit);
+        absolute(&mut self.setpoint, unit);
+        delta(&mut self.rate_of_rise, unit);
+    }
+}
+
+impl ConvertUnits for RoastSession {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        absolute(&mut self.ambient_temp, unit);
+        absolute(&mut self.max_temp, unit);
+        absolute(&mut self.drying_end_temp, unit);
+        delta(&mut self.max_ror, unit);
+        delta(&mut self.avg_ror_drying, unit);
+        delta(&mut self.avg_ror_maillard, unit);
+        delta(&mut self.avg_ror_development, unit);
+        delta(&mut self.auc_value, unit);
+        delta(&mut self.profile_drop_temp_delta, unit);
+        delta(&mut self.profile_deviation_integral, unit);
+    }
+}
+
+impl ConvertUnits for RoastEvent {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        absolute(&mut self.temperature, unit);
+    }
+}
+
+impl ConvertUnits for RoastProfile {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        absolute(&mut self.target_end_temp, unit);
+        absolute(&mut self.preheat_temp, unit);
+        absolute(&mut self.charge_temp, unit);
+    }
+}
+
+impl ConvertUnits for ProfilePoint {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        self.target_temp = unit.convert(self.target_temp);
+        absolute(&mut self.target_env_temp, unit);
+    }
+}
+
+impl ConvertUnits for ProfileWithPoints {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        self.profile.convert_units(unit);
+        self.points.convert_units(unit);
+    }
+}
+
+impl ConvertUnits for ProfileScoreboard {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        self.bean_temp = unit.convert(self.bean_temp);
+        absolute(&mut self.target_temp, unit);
+        absolute(&mut self.target_drop_temp, unit);
+        absolute(&mut self.projected_drop_temp, unit);
+        delta(&mut self.deviation, unit);
+        self.deviation_integral = unit.convert_delta(self.deviation_integral);
+        self.abs_deviation_integral = unit.convert_delta(self.abs_deviation_integral);
+        delta(&mut self.ror, unit);
+        delta(&mut self.drop_temp_delta, unit);
+    }
+}
+
+impl ConvertUnits for SessionWithTelemetry {
+    fn convert_units(&mut self, unit: TemperatureUnit) {
+        self.session.convert_units(unit);
+        self.telemetry.convert_units(unit);
+        self.profile.convert_units(unit);
+        self.scoreboard.convert_units(unit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_telemetry_payload_converts_temperatures_only() {
+        let mut payload = serde_json::json!({
+            "beanTemp": 200.0,
+            "beanTemp2": 100.0,
+            "envTemp": 0.0,
+            "rateOfRise": 10.0,
+            "heaterPWM": 80,
+            "setpoint": null,
+        });
+        payload.convert_units(TemperatureUnit::F);
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "beanTemp": 392.0,
+                "beanTemp2": 212.0,
+                "envTemp": 32.0,
+                "rateOfRise": 18.0,
+                "heaterPWM": 80,
+                "setpoint": null,
+            })
+        );
+
+        let mut scoreboard = ProfileScoreboard {
+            bean_temp: 150.0,
+            target_temp: Some(160.0),
+            deviation: Some(-10.0),
+            ..Default::default()
+        };
+        scoreboard.convert_units(TemperatureUnit::F);
+        assert_eq!(scoreboard.bean_temp, 302.0);
+        assert_eq!(scoreboard.target_temp, Some(320.0));
+        assert_eq!(scoreboard.deviation, Some(-18.0));
+    }
+}
@@ -0,0 +1,500 @@
+//! Outbound webhooks, so integrations (Slack, Zapier, an ERP) hear about
+//! roasts without polling.
+//!
+//! Sessions starting and completing, new roast events and alert state
+//! changes are POSTed as JSON to every enabled webhook subscribed to the
+//! event:
+//!
+//! ```json
+//! {"id": "<delivery id>", "event": "session.started", "created_at": "...", "data": {...}}
+//! ```
+//!
+//! Each request carries `X-Rustroast-Event`, `X-Rustroast-Delivery`,
+//! `X-Rustroast-Timestamp` (Unix seconds) and `X-Rustroast-Signature`,
+//! `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}` keyed with the
+//! webhook's secret. Network errors, 429 and 5xx answers are retried
+//! `RUSTROAST_WEBHOOK_MAX_ATTEMPTS` times in all (default 5), waiting
+//! `RUSTROAST_WEBHOOK_RETRY_BASE_MS` (default 1000) and doubling after each
+//! try. Every attempt updates the delivery in `webhook_deliveries`.
+//!
+//! Activity is only broadcast on the instance where it happened, so unlike
+//! the other background tasks the dispatcher runs on every cluster member.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::export_signing::to_hex;
+use crate::models::{
+    Alert, CreateWebhookRequest, SessionActivity, UpdateWebhookRequest, Webhook, WebhookDelivery,
+    WebhookDeliveryStatus, WebhookEvent,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Rustroast-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Rustroast-Timestamp";
+pub const EVENT_HEADER: &str = "X-Rustroast-Event";
+pub const DELIVERY_HEADER: &str = "X-Rustroast-Delivery";
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_RETRY_BASE_MS: u64 = 1000;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_DELIVERY_LIMIT: i64 = 100;
+const MAX_DELIVERY_LIMIT: i64 = 1000;
+
+/// Body POSTed to a webhook.
+#[derive(Serialize)]
+struct WebhookPayload<'a, T: Serialize> {
+    id: &'a str,
+    event: WebhookEvent,
+    created_at: chrono::DateTime<Utc>,
+    data: &'a T,
+}
+
+#[derive(Clone)]
+pub struct WebhookService {
+    db: SqlitePool,
+    http: reqwest::Client,
+    max_attempts: u32,
+    retry_base: Duration,
+}
+
+impl WebhookService {
+    pub fn from_env(db: SqlitePool) -> Self {
+        let max_attempts = std::env::var("RUSTROAST_WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        let retry_base_ms = std::env::var("RUSTROAST_WEBHOOK_RETRY_BASE_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_RETRY_BASE_MS);
+        Self::new(db, max_attempts, Duration::from_millis(retry_base_ms))
+    }
+
+    pub fn new(db: SqlitePool, max_attempts: u32, retry_base: Duration) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            db,
+            http,
+            max_attempts,
+            retry_base,
+        }
+    }
+
+    // ---- Webhook CRUD ----
+
+    pub async fn list(&self) -> Result<Vec<Webhook>> {
+        let webhooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY created_at")
+            .fetch_all(&self.db)
+            .await?;
+        Ok(webhooks)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Webhook>> {
+        let webhook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(webhook)
+    }
+
+    pub async fn create(&self, req: CreateWebhookRequest) -> Result<Webhook> {
+        let now = Utc::now();
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+            INSERT INTO webhooks (id, name, url, secret, events, enabled, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&req.name)
+        .bind(&req.url)
+        .bind(&req.secret)
+        .bind(sqlx::types::Json(&req.events))
+        .bind(req.enabled.unwrap_or(true))
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(webhook)
+    }
+
+    pub async fn update(&self, id: &str, req: UpdateWebhookRequest) -> Result<Option<Webhook>> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+            UPDATE webhooks SET
+                name = COALESCE(?, name),
+                url = COALESCE(?, url),
+                secret = COALESCE(?, secret),
+                events = COALESCE(?, events),
+                enabled = COALESCE(?, enabled),
+                updated_at = ?
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(&req.name)
+        .bind(&req.url)
+        .bind(&req.secret)
+        .bind(req.events.as_ref().map(sqlx::types::Json))
+        .bind(req.enabled)
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(webhook)
+    }
+
+    /// Deletes the webhook and its delivery log.
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Newest first.
+    pub async fn list_deliveries(
+        &self,
+        webhook_id: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT * FROM webhook_deliveries WHERE webhook_id = ? ORDER BY created_at DESC LIMIT ?",
+        )
+        .bind(webhook_id)
+        .bind(
+            limit
+                .unwrap_or(DEFAULT_DELIVERY_LIMIT)
+                .clamp(1, MAX_DELIVERY_LIMIT),
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(deliveries)
+    }
+
+    // ---- Delivery ----
+
+    /// Queue `event` for every webhook that wants it and deliver in the
+    /// background. Returns the queued deliveries.
+    pub async fn dispatch<T: Serialize>(
+        &self,
+        event: WebhookEvent,
+        data: &T,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let mut deliveries = Vec::new();
+        for webhook in self.list().await? {
+            if !webhook.wants(event) {
+                continue;
+            }
+            let id = Uuid::new_v4().to_string();
+            let now = Utc::now();
+            let payload = serde_json::to_value(WebhookPayload {
+                id: &id,
+                event,
+                created_at: now,
+                data,
+            })?;
+            let delivery = sqlx::query_as::<_, WebhookDelivery>(
+                r#"
+                INSERT INTO webhook_deliveries (id, webhook_id, event, payload, status, attempts, created_at)
+                VALUES (?, ?, ?, ?, ?, 0, ?)
+                RETURNING *
+                "#,
+            )
+            .bind(&id)
+            .bind(&webhook.id)
+            .bind(event)
+            .bind(sqlx::types::Json(&payload))
+            .bind(WebhookDeliveryStatus::Pending)
+            .bind(now)
+            .fetch_one(&self.db)
+            .await?;
+            tokio::spawn(self.clone().deliver(webhook, delivery.clone()));
+            deliveries.push(delivery);
+        }
+        Ok(deliveries)
+    }
+
+    async fn deliver(self, webhook: Webhook, delivery: WebhookDelivery) {
+        let body = delivery.payload.to_string();
+        let mut delay = self.retry_base;
+        for attempt in 1..=self.max_attempts {
+            let timestamp = Utc::now().timestamp();
+            let result = self
+                .http
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, delivery.event.to_string())
+                .header(DELIVERY_HEADER, &delivery.id)
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(
+                    SIGNATURE_HEADER,
+                    sign(&webhook.secret, timestamp, body.as_bytes()),
+                )
+                .body(body.clone())
+                .send()
+                .await;
+            let (response_status, error, retry) = match result {
+                Ok(resp) if resp.status().is_success() => (Some(resp.status()), None, false),
+                Ok(resp) => {
+                    let status = resp.status();
+                    let retry = status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                    (Some(status), Some(format!("HTTP {}", status)), retry)
+                }
+                Err(e) => (None, Some(e.to_string()), true),
+            };
+            let status = match (&error, retry && attempt < self.max_attempts) {
+                (None, _) => WebhookDeliveryStatus::Delivered,
+                (Some(_), true) => WebhookDeliveryStatus::Pending,
+                (Some(_), false) => WebhookDeliveryStatus::Failed,
+            };
+            if let Err(e) = self
+                .record_attempt(
+                    &delivery.id,
+                    status,
+                    attempt,
+                    response_status.map(|s| s.as_u16()),
+                    error.as_deref(),
+                )
+                .await
+            {
+                tracing::warn!(error = %e, delivery_id = %delivery.id, "Failed to record webhook delivery");
+            }
+            if status != WebhookDeliveryStatus::Pending {
+                if let Some(error) = error {
+                    tracing::warn!(
+                        webhook_id = %webhook.id, delivery_id = %delivery.id, attempt, %error,
+                        "Webhook delivery failed"
+                    );
+                }
+                return;
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+
+    async fn record_attempt(
+        &self,
+        id: &str,
+        status: WebhookDeliveryStatus,
+        attempts: u32,
+        response_status: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?, attempts = ?, response_status = ?, error = ?, last_attempt_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(status)
+        .bind(attempts)
+        .bind(response_status)
+        .bind(error)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Dispatch session activity and alert changes until both channels close.
+    pub async fn run(
+        self,
+        mut session_rx: broadcast::Receiver<SessionActivity>,
+        mut alert_rx: broadcast::Receiver<Alert>,
+    ) {
+        loop {
+            let result = tokio::select! {
+                activity = session_rx.recv() => match activity {
+                    Ok(SessionActivity::Started(session)) => {
+                        self.dispatch(WebhookEvent::SessionStarted, &session).await
+                    }
+                    Ok(SessionActivity::Completed(session)) => {
+                        self.dispatch(WebhookEvent::SessionCompleted, &session).await
+                    }
+                    Ok(SessionActivity::EventCreated(event)) => {
+                        self.dispatch(WebhookEvent::RoastEventCreated, &event).await
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "Webhook dispatcher lagged behind sessions");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                alert = alert_rx.recv() => match alert {
+                    Ok(alert) => self.dispatch(WebhookEvent::for_alert(alert.state), &alert).await,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "Webhook dispatcher lagged behind alerts");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            if let Err(e) = result {
+                tracing::warn!(error = %e, "Failed to queue webhook deliveries");
+            }
+        }
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", to_hex(&mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{http::HeaderMap, http::StatusCode, routing::post, Router};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_webhook_retries_with_signature_until_delivered() {
+        // Receiver failing twice with 503, then accepting
+        let received: Arc<Mutex<Vec<(HeaderMap, String)>>> = Arc::default();
+        let app = Router::new().route(
+            "/hook",
+            post({
+                let received = received.clone();
+                move |headers: HeaderMap, body: String| async move {
+                    let mut received = received.lock().unwrap();
+                    received.push((headers, body));
+                    if received.len() < 3 {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        StatusCode::OK
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let db = crate::init_memory_db().await.unwrap();
+        let service = WebhookService::new(db, 5, Duration::from_millis(10));
+        let create = |name: &str, events: Vec<WebhookEvent>| CreateWebhookRequest {
+            name: name.into(),
+            url: format!("http://{}/hook", addr),
+            secret: "s3cret".into(),
+            events,
+            enabled: None,
+        };
+        let webhook = service
+            .create(create("roasts", vec![WebhookEvent::SessionCompleted]))
+            .await
+            .unwrap();
+        service
+            .create(create("other", vec![WebhookEvent::AlertFired]))
+            .await
+            .unwrap();
+
+        let data = serde_json::json!({"id": "session-1"});
+        let queued = service
+            .dispatch(WebhookEvent::SessionCompleted, &data)
+            .await
+            .unwrap();
+        assert_eq!(queued.len(), 1);
+
+        let delivery = loop {
+            let deliveries = service.list_deliveries(&webhook.id, None).await.unwrap();
+            if deliveries[0].status != WebhookDeliveryStatus::Pending {
+                break deliveries[0].clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.response_status, Some(200));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        let (headers, body) = &received[2];
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            sign("s3cret", timestamp, body.as_bytes())
+        );
+        assert_eq!(headers[EVENT_HEADER], "session.completed");
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["data"]["id"], "session-1");
+        assert_eq!(body["id"], delivery.id.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_session_start_is_dispatched_to_subscribed_webhooks() {
+        let db = crate::init_memory_db().await.unwrap();
+        let (mqtt, _published) = rustroast_mqtt::MqttService::mock();
+        let state = crate::build_state(mqtt, db.clone(), db);
+        let dispatcher = crate::spawn_webhook_dispatcher(&state);
+        // Nothing listens here, so deliveries stay queued or fail
+        let webhook = state
+            .webhooks
+            .create(CreateWebhookRequest {
+                name: "starts".into(),
+                url: "http://127.0.0.1:9/hook".into(),
+                secret: "s3cret".into(),
+                events: vec![WebhookEvent::SessionStarted],
+                enabled: None,
+            })
+            .await
+            .unwrap();
+
+        let req = serde_json::from_value(
+            serde_json::json!({"name": "Hooked", "device_id": "hook-roaster"}),
+        )
+        .unwrap();
+        let session = state.session_service.create_session(req).await.unwrap();
+        state
+            .session_service
+            .start_session(&session.id)
+            .await
+            .unwrap();
+        state
+            .session_service
+            .complete_session(&session.id)
+            .await
+            .unwrap();
+
+        let deliveries = loop {
+            let deliveries = state
+                .webhooks
+                .list_deliveries(&webhook.id, None)
+                .await
+                .unwrap();
+            if !deliveries.is_empty() {
+                break deliveries;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        dispatcher.abort();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, WebhookEvent::SessionStarted);
+        assert_eq!(deliveries[0].payload["data"]["id"], session.id.as_str());
+    }
+}
@@ -0,0 +1,37 @@
+//! Sample device payloads in the ESP32 wire format.
+
+use serde_json::{json, Value};
+
+/// A telemetry message as published on `roaster/{device_id}/telemetry`.
+pub fn telemetry_payload(bean_temp: f64, env_temp: f64) -> Value {
+    json!({
+        "timestamp": 0,
+        "beanTemp": bean_temp,
+        "envTemp": env_temp,
+        "rateOfRise": 10.0,
+        "heaterPWM": 50,
+        "fanPWM": 180,
+        "setpoint": 200.0,
+        "controlMode": 1,
+        "heaterEnable": 1,
+        "uptime": 1,
+        "Kp": 15.0,
+        "Ki": 1.0,
+        "Kd": 25.0,
+        "freeHeap": 180000,
+        "rssi": -40,
+        "systemStatus": 0
+    })
+}
+
+/// A status message as published on `roaster/{device_id}/status`.
+pub fn status_payload(device_id: &str) -> Value {
+    json!({
+        "status": "online",
+        "id": format!("{}-TEST", device_id),
+        "ip": "127.0.0.1",
+        "rssi": -40,
+        "freeHeap": 123456,
+        "version": "test"
+    })
+}
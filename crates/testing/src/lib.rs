@@ -0,0 +1,1717 @@
+//! In-process rustRoast server for integration tests.
+//!
+//! [`TestServer`] runs the real router and background tasks against an in-memory
+//! SQLite database and a channel-backed mock MQTT service, so handlers and
+//! services can be exercised end to end without a broker.
+
+pub mod fixtures;
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rustroast_mqtt::{MqttService, PublishedMessage};
+use rustroast_server::AppState;
+use sqlx::SqlitePool;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+/// How long helpers wait for asynchronous effects before giving up.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub db: SqlitePool,
+    pub mqtt: MqttService,
+    client: reqwest::Client,
+    published: Mutex<mpsc::UnboundedReceiver<PublishedMessage>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestServer {
+    /// Start a server on an ephemeral localhost port.
+    pub async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    /// [`TestServer::start`] with `configure` applied to the state first.
+    pub async fn start_with(configure: impl FnOnce(&mut AppState)) -> Self {
+        let db = rustroast_server::init_memory_db()
+            .await
+            .expect("failed to init in-memory db");
+        let (mqtt, published) = MqttService::mock();
+
+        let mut state = rustroast_server::build_state(mqtt.clone(), db.clone(), db.clone());
+        configure(&mut state);
+        let consumer = rustroast_server::spawn_mqtt_consumer(&state);
+        rustroast_server::subscribe_topics(&mqtt).await;
+        let cues = rustroast_server::spawn_cue_engine(&state);
+        let automations = rustroast_server::spawn_automation_engine(&state);
+        let exporter = rustroast_server::spawn_session_exporter(&state);
+        let alerts = rustroast_server::spawn_alert_monitor(&state);
+        let tolerance = rustroast_server::spawn_tolerance_monitor(&state);
+        let preheat = rustroast_server::spawn_preheat_monitor(&state);
+        let queue = rustroast_server::spawn_queue_monitor(&state);
+        let health = rustroast_server::spawn_device_health_monitor(&state);
+        let probes = rustroast_server::spawn_probe_monitor(&state);
+        let webhooks = rustroast_server::spawn_webhook_dispatcher(&state);
+        let app = rustroast_server::build_router(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind test listener");
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        Self {
+            addr,
+            db,
+            mqtt,
+            client: reqwest::Client::new(),
+            published: Mutex::new(published),
+            tasks: vec![
+                consumer,
+                cues,
+                automations,
+                exporter,
+                alerts,
+                tolerance,
+                preheat,
+                queue,
+                health,
+                probes,
+                webhooks,
+                server,
+            ],
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    pub async fn get(&self, path: &str) -> reqwest::Response {
+        self.client.get(self.url(path)).send().await.unwrap()
+    }
+
+    pub async fn post_json(&self, path: &str, body: &serde_json::Value) -> reqwest::Response {
+        self.client
+            .post(self.url(path))
+            .json(body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// Deliver a message to the server as if a device had published it.
+    pub fn device_publish(&self, topic: &str, payload: &serde_json::Value) {
+        self.mqtt.inject(topic, payload.to_string());
+    }
+
+    /// Publish fixture telemetry for `device_id`.
+    pub fn device_telemetry(&self, device_id: &str, bean_temp: f64, env_temp: f64) {
+        self.device_publish(
+            &rustroast_core::telemetry_topic(device_id),
+            &fixtures::telemetry_payload(bean_temp, env_temp),
+        );
+    }
+
+    /// Next message the server published to MQTT, or `None` after a timeout.
+    pub async fn next_published(&self) -> Option<PublishedMessage> {
+        let mut rx = self.published.lock().await;
+        tokio::time::timeout(WAIT_TIMEOUT, rx.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Poll `check` until it returns `Some`, for effects of background tasks such
+/// as MQTT ingestion. Panics after a timeout.
+pub async fn eventually<T, F, Fut>(mut check: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let deadline = tokio::time::Instant::now() + WAIT_TIMEOUT;
+    loop {
+        if let Some(value) = check().await {
+            return value;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            panic!("condition not met within {:?}", WAIT_TIMEOUT);
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use tokio::io::AsyncReadExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[tokio::test]
+    async fn test_control_endpoint_publishes_to_mqtt() {
+        let server = TestServer::start().await;
+
+        let resp = server
+            .post_json(
+                "/api/roaster/dev1/control/setpoint",
+                &json!({"value": 210.0}),
+            )
+            .await;
+        assert_eq!(resp.status(), 204);
+        let request_id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+
+        let msg = server.next_published().await.expect("setpoint published");
+        assert_eq!(msg.topic, "roaster/dev1/control/setpoint");
+        assert_eq!(msg.payload, b"210");
+        assert_eq!(
+            msg.user_properties,
+            vec![("request-id".to_string(), request_id)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_control_checks_device_state() {
+        let server = TestServer::start().await;
+        let setpoint = "/api/roaster/dev1/control/setpoint?strict=true";
+        let body = json!({"value": 210.0});
+
+        // Never heard from the device
+        let resp = server.post_json(setpoint, &body).await;
+        assert_eq!(resp.status(), 409);
+        assert!(resp.text().await.unwrap().contains("telemetry"));
+        // Turning the heater off is never refused
+        let resp = server
+            .post_json(
+                "/api/roaster/dev1/control/heater_enable?strict=true",
+                &json!({"enabled": false}),
+            )
+            .await;
+        assert_eq!(resp.status(), 204);
+
+        let mut telemetry = fixtures::telemetry_payload(150.0, 180.0);
+        telemetry["controlMode"] = json!(0);
+        server.device_publish("roaster/dev1/telemetry", &telemetry);
+        let resp = eventually(|| async {
+            let resp = server.post_json(setpoint, &body).await;
+            let text = resp.text().await.ok()?;
+            text.contains("manual mode").then_some(text)
+        })
+        .await;
+        assert!(resp.contains("auto"));
+
+        telemetry["controlMode"] = json!(1);
+        telemetry["systemStatus"] = json!(3);
+        server.device_publish("roaster/dev1/telemetry", &telemetry);
+        eventually(|| async {
+            let resp = server.post_json(setpoint, &body).await;
+            let text = resp.text().await.ok()?;
+            text.contains("systemStatus 3: Unknown error code")
+                .then_some(())
+        })
+        .await;
+        let latest: serde_json::Value = server
+            .get("/api/roaster/dev1/telemetry/latest")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(latest["telemetry"]["systemError"]["name"], "unknown");
+
+        telemetry["systemStatus"] = json!(0);
+        server.device_publish("roaster/dev1/telemetry", &telemetry);
+        eventually(|| async {
+            let resp = server.post_json(setpoint, &body).await;
+            (resp.status() == 204).then_some(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_control_batch_runs_in_order_and_aborts() {
+        let server = TestServer::start().await;
+
+        let resp = server
+            .post_json(
+                "/api/roaster/dev1/control/batch",
+                &json!({
+                    "abort_on_failure": true,
+                    "operations": [
+                        {"op": "mode", "mode": "manual"},
+                        {"op": "heater_enable", "enabled": true},
+                        {"op": "fan_pwm", "value": 300},
+                        {"op": "setpoint", "value": 180.0}
+                    ]
+                }),
+            )
+            .await;
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        let statuses: Vec<&str> = body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["status"].as_str().unwrap())
+            .collect();
+        assert_eq!(statuses, ["ok", "ok", "failed", "skipped"]);
+        assert_eq!(body["results"][2]["http_status"], 400);
+        assert_eq!(body["aborted"], true);
+
+        let topics = [
+            server.next_published().await.unwrap().topic,
+            server.next_published().await.unwrap().topic,
+        ];
+        assert_eq!(
+            topics,
+            [
+                "roaster/dev1/control/mode",
+                "roaster/dev1/control/heater_enable"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_telemetry_is_ingested() {
+        let server = TestServer::start().await;
+        assert_eq!(
+            server
+                .get("/api/roaster/dev1/telemetry/latest")
+                .await
+                .status(),
+            404
+        );
+
+        server.device_telemetry("dev1", 150.0, 180.0);
+
+        let latest: serde_json::Value = eventually(|| async {
+            let resp = server.get("/api/roaster/dev1/telemetry/latest").await;
+            if resp.status().is_success() {
+                resp.json().await.ok()
+            } else {
+                None
+            }
+        })
+        .await;
+        assert_eq!(latest["telemetry"]["beanTemp"], 150.0);
+
+        // Unknown devices are auto-discovered as pending
+        let discovered: serde_json::Value = server
+            .get("/api/devices/discovered")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(discovered.as_array().map(Vec::len), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_retained_autotune_results_are_adopted_once() {
+        let server = TestServer::start().await;
+        let topic = "roaster/dev1/autotune/results";
+        // Recorded before a restart, then replayed by the broker on subscribing
+        sqlx::query("INSERT INTO autotune_results (device_id, ts, payload) VALUES (?, ?, ?)")
+            .bind("dev1")
+            .bind(1_000_i64)
+            .bind(r#"{"kp":12.5}"#)
+            .execute(&server.db)
+            .await
+            .unwrap();
+        server.mqtt.inject_retained(topic, r#"{"kp":12.5}"#);
+        let latest: serde_json::Value = eventually(|| async {
+            let latest: serde_json::Value = server
+                .get("/api/roaster/dev1/autotune/results/latest")
+                .await
+                .json()
+                .await
+                .ok()?;
+            (!latest.is_null()).then_some(latest)
+        })
+        .await;
+        assert_eq!(latest["timestamp"], 1_000);
+        assert_eq!(latest["results"]["kp"], 12.5);
+
+        // Produced while the server was down
+        server.mqtt.inject_retained(topic, r#"{"kp":14.0}"#);
+        let latest: serde_json::Value = eventually(|| async {
+            let latest: serde_json::Value = server
+                .get("/api/roaster/dev1/autotune/results/latest")
+                .await
+                .json()
+                .await
+                .ok()?;
+            (latest["results"]["kp"] == 14.0).then_some(latest)
+        })
+        .await;
+        assert_ne!(latest["timestamp"], 1_000);
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM autotune_results")
+            .fetch_one(&server.db)
+            .await
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn test_autotune_apply_refuses_pathological_gains() {
+        let server = TestServer::start().await;
+        server.device_publish(
+            "roaster/dev1/autotune/results",
+            &serde_json::json!({
+                "recommended_kp": 480.0, "recommended_ki": 2.0, "recommended_kd": 0.0,
+                "tuning_method": "zn_classic", "quality": "poor",
+            }),
+        );
+        let check: serde_json::Value = eventually(|| async {
+            let check: serde_json::Value = server
+                .get("/api/roaster/dev1/autotune/results/latest/check")
+                .await
+                .json()
+                .await
+                .ok()?;
+            (!check["results"].is_null()).then_some(check)
+        })
+        .await;
+        assert_eq!(check["problems"].as_array().map(Vec::len), Some(2));
+
+        let resp = server
+            .post_json("/api/roaster/dev1/autotune/apply", &serde_json::json!({}))
+            .await;
+        assert_eq!(resp.status(), 409);
+        assert!(resp.text().await.unwrap().contains("kp 480 exceeds"));
+
+        let resp = server
+            .post_json(
+                "/api/roaster/dev1/autotune/apply?force=true",
+                &serde_json::json!({}),
+            )
+            .await;
+        assert_eq!(resp.status(), 204);
+        let msg = server.next_published().await.unwrap();
+        assert_eq!(msg.topic, "roaster/dev1/autotune/apply");
+    }
+
+    #[tokio::test]
+    async fn test_retained_status_rebuilds_the_device_registry() {
+        let server = TestServer::start().await;
+        // Last heard live before a restart
+        sqlx::query("INSERT INTO status_history (device_id, ts, status, changed, payload) VALUES (?, ?, ?, ?, ?)")
+            .bind("dev1")
+            .bind(1_000_i64)
+            .bind("online")
+            .bind(true)
+            .bind(r#"{"status":"online"}"#)
+            .execute(&server.db)
+            .await
+            .unwrap();
+        server
+            .mqtt
+            .inject_retained("roaster/dev1/status", r#"{"status":"online"}"#);
+        let device: serde_json::Value = eventually(|| async {
+            let devices: serde_json::Value = server
+                .get("/api/devices/registry")
+                .await
+                .json()
+                .await
+                .ok()?;
+            devices["devices"].get(0).cloned()
+        })
+        .await;
+        assert_eq!(device["retained"], true);
+        assert_eq!(device["last_seen"], 1_000);
+        let discovered: serde_json::Value = server
+            .get("/api/devices/discovered")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(discovered.as_array().map(Vec::len), Some(1));
+
+        server.mqtt.inject(
+            "roaster/dev1/status",
+            r#"{"status":"online","ip":"10.0.0.5"}"#,
+        );
+        let device: serde_json::Value = eventually(|| async {
+            let devices: serde_json::Value = server
+                .get("/api/devices/registry")
+                .await
+                .json()
+                .await
+                .ok()?;
+            let device = devices["devices"].get(0)?.clone();
+            device.get("retained").is_none().then_some(device)
+        })
+        .await;
+        assert_ne!(device["last_seen"], 1_000);
+        assert_eq!(device["ip"], "10.0.0.5");
+    }
+
+    #[tokio::test]
+    async fn test_retained_scan_does_not_record_telemetry_again() {
+        let server = TestServer::start().await;
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Scanned", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        let resp = server
+            .post_json(&format!("/api/sessions/{}/start", id), &json!({}))
+            .await;
+        assert!(resp.status().is_success());
+
+        let topic = rustroast_core::telemetry_topic("dev1");
+        let stale = fixtures::telemetry_payload(99.0, 120.0).to_string();
+        server
+            .mqtt
+            .publish(&topic, rumqttc::QoS::AtMostOnce, true, stale)
+            .await
+            .unwrap();
+        // The retained publish loops back live once; the scan replays it
+        let found = server
+            .mqtt
+            .scan_retained("roaster/#", Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        server.device_telemetry("dev1", 150.0, 180.0);
+
+        let path = format!("/api/sessions/{}/telemetry", id);
+        let temps = eventually(|| async {
+            let body: serde_json::Value = server.get(&path).await.json().await.ok()?;
+            let temps: Vec<f64> = body["telemetry"]
+                .as_array()?
+                .iter()
+                .filter_map(|p| p["bean_temp"].as_f64())
+                .collect();
+            temps.contains(&150.0).then_some(temps)
+        })
+        .await;
+        assert_eq!(temps, vec![99.0, 150.0]);
+    }
+
+    #[tokio::test]
+    async fn test_session_cue_fires_from_telemetry() {
+        let server = TestServer::start().await;
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Cued", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        let resp = server
+            .post_json(
+                &format!("/api/sessions/{}/cues", id),
+                &json!({"message": "check color", "trigger_type": "temperature", "trigger_value": 160.0}),
+            )
+            .await;
+        assert_eq!(resp.status(), 201);
+        let resp = server
+            .post_json(&format!("/api/sessions/{}/start", id), &json!({}))
+            .await;
+        assert!(resp.status().is_success());
+
+        server.device_telemetry("dev1", 150.0, 180.0);
+        server.device_telemetry("dev1", 165.0, 190.0);
+
+        let cues: serde_json::Value = eventually(|| async {
+            let cues: serde_json::Value = server
+                .get(&format!("/api/sessions/{}/cues", id))
+                .await
+                .json()
+                .await
+                .ok()?;
+            cues[0]["fired_at"].is_string().then_some(cues)
+        })
+        .await;
+        assert_eq!(cues[0]["message"], "check color");
+    }
+
+    #[tokio::test]
+    async fn test_quick_tap_and_bulk_events() {
+        let server = TestServer::start().await;
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Tapped", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        let tap = format!("/api/sessions/{}/events/now?type=first_crack_start", id);
+
+        // Nothing to measure elapsed time from until the session starts
+        assert_eq!(server.post_json(&tap, &json!({})).await.status(), 409);
+        let resp = server
+            .post_json(&format!("/api/sessions/{}/start", id), &json!({}))
+            .await;
+        assert!(resp.status().is_success());
+
+        server.device_telemetry("dev1", 196.5, 230.0);
+        eventually(|| async {
+            let resp = server.get("/api/roaster/dev1/telemetry/latest").await;
+            resp.status().is_success().then_some(())
+        })
+        .await;
+
+        let resp = server.post_json(&tap, &json!({})).await;
+        assert_eq!(resp.status(), 201);
+        let event: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(event["event_type"], "first_crack_start");
+        assert_eq!(event["temperature"], 196.5);
+        assert!(event["elapsed_seconds"].as_f64().unwrap() >= 0.0);
+
+        let bulk = format!("/api/sessions/{}/events/bulk", id);
+        let resp = server
+            .post_json(
+                &bulk,
+                &json!({"events": [
+                    {"event_type": "drying_end", "elapsed_seconds": 240.0, "temperature": 150.0},
+                    {"event_type": "drop", "elapsed_seconds": 600.0, "notes": "late"}
+                ]}),
+            )
+            .await;
+        assert_eq!(resp.status(), 201);
+        let created: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(created.as_array().map(Vec::len), Some(2));
+        let resp = server
+            .post_json(
+                &bulk,
+                &json!({"events": [{"event_type": "drop", "elapsed_seconds": -1.0}]}),
+            )
+            .await;
+        assert_eq!(resp.status(), 400);
+
+        let events: serde_json::Value = server
+            .get(&format!("/api/sessions/{}/events", id))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(events.as_array().map(Vec::len), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_over_temperature_alert_lifecycle() {
+        let server = TestServer::start().await;
+        server.device_telemetry("dev1", 245.0, 260.0);
+
+        let alerts: serde_json::Value = eventually(|| async {
+            let alerts: serde_json::Value = server
+                .get("/api/alerts?state=firing&kind=over_temperature")
+                .await
+                .json()
+                .await
+                .ok()?;
+            (alerts.as_array()?.len() == 1).then_some(alerts)
+        })
+        .await;
+        let id = alerts[0]["id"].as_str().unwrap();
+        assert_eq!(alerts[0]["device_id"], "dev1");
+        assert_eq!(alerts[0]["severity"], "critical");
+
+        let ack = format!("/api/alerts/{}/acknowledge", id);
+        let resp = server.post_json(&ack, &json!({"by": "alice"})).await;
+        assert_eq!(resp.status(), 200);
+        let alert: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(alert["state"], "acknowledged");
+        assert_eq!(alert["acknowledged_by"], "alice");
+        assert_eq!(
+            server.post_json(&ack, &json!({"by": "bob"})).await.status(),
+            409
+        );
+        assert_eq!(
+            server
+                .post_json("/api/alerts/missing/acknowledge", &json!({"by": "bob"}))
+                .await
+                .status(),
+            404
+        );
+
+        // Still hot within the hysteresis band, then cooled down
+        server.device_telemetry("dev1", 238.0, 250.0);
+        server.device_telemetry("dev1", 230.0, 240.0);
+        let alert: serde_json::Value = eventually(|| async {
+            let alert: serde_json::Value = server
+                .get(&format!("/api/alerts/{}", id))
+                .await
+                .json()
+                .await
+                .ok()?;
+            (alert["state"] == "resolved").then_some(alert)
+        })
+        .await;
+        assert!(alert["resolved_by"].is_null());
+        let resp = server
+            .post_json(
+                &format!("/api/alerts/{}/resolve", id),
+                &json!({"by": "alice"}),
+            )
+            .await;
+        assert_eq!(resp.status(), 409);
+
+        let all: serde_json::Value = server
+            .get("/api/alerts?device_id=dev1")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(all.as_array().map(Vec::len), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_device_log_buffer_and_troubleshooting_capture() {
+        let server = TestServer::start().await;
+        server
+            .mqtt
+            .inject("roaster/dev1/log", "[BOOT] v1.4.2\n[WIFI] up");
+        let lines: serde_json::Value = eventually(|| async {
+            let lines: serde_json::Value = server
+                .get("/api/roaster/dev1/logs")
+                .await
+                .json()
+                .await
+                .ok()?;
+            (lines.as_array()?.len() == 2).then_some(lines)
+        })
+        .await;
+        assert_eq!(lines[1]["message"], "[WIFI] up");
+        // Not flagged, so nothing was persisted
+        let history: serde_json::Value = server
+            .get("/api/roaster/dev1/logs/history")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(history, json!([]));
+
+        let resp = server
+            .client()
+            .put(server.url("/api/roaster/dev1/troubleshooting"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        server.device_publish(
+            "roaster/dev1/log",
+            &json!({"level": "error", "msg": "thermocouple open"}),
+        );
+        let history: serde_json::Value = eventually(|| async {
+            let history: serde_json::Value = server
+                .get("/api/roaster/dev1/logs/history")
+                .await
+                .json()
+                .await
+                .ok()?;
+            (history.as_array()?.len() == 1).then_some(history)
+        })
+        .await;
+        assert_eq!(history[0]["level"], "error");
+        let recent: serde_json::Value = server
+            .get("/api/roaster/dev1/logs?limit=1")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(recent[0]["message"], "thermocouple open");
+
+        let flagged: serde_json::Value = server
+            .get("/api/devices/troubleshooting")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(flagged[0]["device_id"], "dev1");
+        let stop = || {
+            server
+                .client()
+                .delete(server.url("/api/roaster/dev1/troubleshooting"))
+                .send()
+        };
+        assert_eq!(stop().await.unwrap().status(), 204);
+        assert_eq!(stop().await.unwrap().status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_automation_rules_run_once_and_are_logged() {
+        let server = TestServer::start().await;
+        for rule in [
+            json!({"name": "fan up", "trigger_type": "bean_temp_rising", "trigger_value": 150.0,
+                   "command": "fan_pwm", "value": 220}),
+            json!({"name": "ease at FC", "trigger_type": "event", "trigger_event": "first_crack_start",
+                   "command": "setpoint", "value": -5.0, "relative": true}),
+        ] {
+            let resp = server
+                .post_json("/api/roaster/dev1/automations", &rule)
+                .await;
+            assert_eq!(resp.status(), 201);
+        }
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Automated", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        let resp = server
+            .post_json(&format!("/api/sessions/{}/start", id), &json!({}))
+            .await;
+        assert!(resp.status().is_success());
+
+        let log_len = |n: usize| {
+            let path = format!("/api/sessions/{}/automations/log", id);
+            let server = &server;
+            async move {
+                let log: serde_json::Value = server.get(&path).await.json().await.ok()?;
+                let runs = log.as_array()?;
+                (runs.len() == n && runs.iter().all(|r| !r["success"].is_null())).then_some(log)
+            }
+        };
+
+        server.device_telemetry("dev1", 145.0, 180.0);
+        server.device_telemetry("dev1", 155.0, 185.0);
+        server.device_telemetry("dev1", 158.0, 185.0);
+        let log = eventually(|| log_len(1)).await;
+        assert_eq!(log[0]["command"], "fan_pwm");
+        assert_eq!(log[0]["sent_value"], "220");
+        assert_eq!(log[0]["success"], true);
+
+        let resp = server
+            .post_json(
+                &format!("/api/sessions/{}/events", id),
+                &json!({"event_type": "first_crack_start", "elapsed_seconds": 400.0}),
+            )
+            .await;
+        assert!(resp.status().is_success());
+        server.device_telemetry("dev1", 196.0, 210.0);
+        let log = eventually(|| log_len(2)).await;
+        assert_eq!(log[1]["command"], "setpoint");
+        assert_eq!(log[1]["sent_value"], "195");
+
+        // Rising past the threshold again does not re-run the rule
+        server.device_telemetry("dev1", 140.0, 180.0);
+        server.device_telemetry("dev1", 160.0, 180.0);
+        let mut controls = Vec::new();
+        while let Some(msg) = server.next_published().await {
+            if msg.topic.contains("/control/") {
+                controls.push((msg.topic, String::from_utf8(msg.payload).unwrap()));
+            }
+        }
+        assert_eq!(
+            controls,
+            [
+                (
+                    "roaster/dev1/control/fan_pwm".to_string(),
+                    "220".to_string()
+                ),
+                (
+                    "roaster/dev1/control/setpoint".to_string(),
+                    "195".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preheat_between_batches() {
+        let server = TestServer::start().await;
+        let resp = server
+            .client()
+            .put(server.url("/api/roaster/dev1/preheat"))
+            .json(&json!({"enabled": true, "max_temp": 220.0, "ready_band": 3.0}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let profile: serde_json::Value = server
+            .post_json(
+                "/api/profiles",
+                &json!({"name": "Hot charge", "charge_temp": 240.0, "points": []}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Batch 1", "device_id": "dev1", "profile_id": profile["id"]}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        for step in ["start", "complete"] {
+            let resp = server
+                .post_json(&format!("/api/sessions/{}/{}", id, step), &json!({}))
+                .await;
+            assert!(resp.status().is_success());
+        }
+
+        let mut controls = Vec::new();
+        while controls.len() < 3 {
+            let msg = server.next_published().await.expect("preheat commands");
+            if let Some(op) = msg.topic.strip_prefix("roaster/dev1/control/") {
+                controls.push((op.to_string(), String::from_utf8(msg.payload).unwrap()));
+            }
+        }
+        // The charge temp is capped at max_temp
+        assert_eq!(
+            controls,
+            [
+                ("mode".to_string(), "auto".to_string()),
+                ("heater_enable".to_string(), "1".to_string()),
+                ("setpoint".to_string(), "220".to_string()),
+            ]
+        );
+
+        let preheat_state = |expected: &'static str| {
+            let server = &server;
+            async move {
+                let status: serde_json::Value = server
+                    .get("/api/roaster/dev1/preheat")
+                    .await
+                    .json()
+                    .await
+                    .ok()?;
+                (status["run"]["state"] == expected).then_some(status)
+            }
+        };
+        eventually(|| preheat_state("heating")).await;
+        server.device_telemetry("dev1", 218.0, 230.0);
+        let status = eventually(|| preheat_state("ready")).await;
+        assert_eq!(status["run"]["target_temp"], 220.0);
+        assert!(!status["run"]["ready_at"].is_null());
+
+        // Overshooting the limit turns the heater off and alerts
+        server.device_telemetry("dev1", 224.0, 235.0);
+        eventually(|| preheat_state("aborted")).await;
+        let msg = server.next_published().await.unwrap();
+        assert_eq!(msg.topic, "roaster/dev1/control/heater_enable");
+        assert_eq!(msg.payload, b"0");
+        let alerts: serde_json::Value = server
+            .get("/api/alerts?kind=preheat_failed")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(alerts.as_array().map(Vec::len), Some(1));
+        assert_eq!(
+            server
+                .post_json("/api/roaster/dev1/preheat/cancel", &json!({}))
+                .await
+                .status(),
+            404
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preroast_checklist_gates_session_start() {
+        let server = TestServer::start().await;
+        let resp = server
+            .client()
+            .put(server.url("/api/roaster/dev1/checklist"))
+            .json(&json!({"items": ["Chaff bin emptied", "Afterburner on"]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let new_session = |name: &'static str| {
+            let server = &server;
+            async move {
+                let session: serde_json::Value = server
+                    .post_json("/api/sessions", &json!({"name": name, "device_id": "dev1"}))
+                    .await
+                    .json()
+                    .await
+                    .unwrap();
+                session["id"].as_str().unwrap().to_string()
+            }
+        };
+
+        let id = new_session("Batch 1").await;
+        let start = format!("/api/sessions/{}/start", id);
+        assert_eq!(server.post_json(&start, &json!({})).await.status(), 409);
+        for item in ["Chaff bin emptied", "Afterburner on"] {
+            let resp = server
+                .post_json(
+                    &format!("/api/sessions/{}/checklist/checks", id),
+                    &json!({"item": item, "checked_by": "sam"}),
+                )
+                .await;
+            assert_eq!(resp.status(), 200);
+        }
+        assert_eq!(server.post_json(&start, &json!({})).await.status(), 200);
+        let checklist: serde_json::Value = server
+            .get(&format!("/api/sessions/{}/checklist", id))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(checklist["complete"], true);
+        assert_eq!(checklist["items"][0]["checked_by"], "sam");
+        assert!(!checklist["recorded_at"].is_null());
+        assert!(checklist["overridden_by"].is_null());
+
+        // Starting without the checks needs a reason, which is kept
+        let id = new_session("Batch 2").await;
+        let resp = server
+            .post_json(
+                &format!(
+                    "/api/sessions/{}/start?override_checklist=burner%20test",
+                    id
+                ),
+                &json!({}),
+            )
+            .await;
+        assert_eq!(resp.status(), 200);
+        let checklist: serde_json::Value = server
+            .get(&format!("/api/sessions/{}/checklist", id))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(checklist["complete"], false);
+        assert_eq!(checklist["override_reason"], "burner test");
+        assert_eq!(checklist["overridden_by"], "anonymous");
+    }
+
+    #[tokio::test]
+    async fn test_session_telemetry_is_republished() {
+        let server = TestServer::start().await;
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Logged", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        let resp = server
+            .post_json(&format!("/api/sessions/{}/start", id), &json!({}))
+            .await;
+        assert!(resp.status().is_success());
+
+        server.device_telemetry("dev1", 150.0, 180.0);
+
+        let msg = server.next_published().await.expect("session telemetry");
+        assert_eq!(msg.topic, format!("rustroast/sessions/{}/telemetry", id));
+        let body: serde_json::Value = serde_json::from_slice(&msg.payload).unwrap();
+        assert_eq!(body["session_id"], id);
+        assert_eq!(body["device_id"], "dev1");
+        assert_eq!(body["bean_temp"], 150.0);
+        assert_eq!(body["fan_pwm"], 180);
+        assert!(body["elapsed_seconds"].as_f64().unwrap() >= 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_receive_time_carries_through_session_telemetry_and_export() {
+        let server = TestServer::start().await;
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Backlogged", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        let resp = server
+            .post_json(&format!("/api/sessions/{}/start", id), &json!({}))
+            .await;
+        assert!(resp.status().is_success());
+
+        // Stamped well away from when the consumer gets to it
+        let received_at = std::time::SystemTime::now() + Duration::from_secs(42);
+        let epoch = received_at
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        server.mqtt.inject_at(
+            &rustroast_core::telemetry_topic("dev1"),
+            fixtures::telemetry_payload(150.0, 180.0).to_string(),
+            received_at,
+        );
+
+        let path = format!("/api/sessions/{}/telemetry", id);
+        let telemetry = eventually(|| async {
+            let body: serde_json::Value = server.get(&path).await.json().await.ok()?;
+            let points = body["telemetry"].as_array()?.clone();
+            (!points.is_empty()).then_some(points)
+        })
+        .await;
+        assert_eq!(telemetry.len(), 1);
+        let elapsed = telemetry[0]["elapsed_seconds"].as_f64().unwrap();
+        assert!((41.0..=43.0).contains(&elapsed), "elapsed {elapsed}");
+
+        let csv = server
+            .get(&format!("/api/sessions/{}/export/csv?timestamp=epoch", id))
+            .await
+            .text()
+            .await
+            .unwrap();
+        let row = csv
+            .lines()
+            .find(|line| !line.starts_with('#') && line.contains("150"))
+            .expect("telemetry row in export");
+        assert_eq!(row.split(',').next(), Some(epoch.to_string().as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_signed_exports_verify_until_modified() {
+        let server = TestServer::start().await;
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Competition", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+        let server = &server;
+        let verify = |body: serde_json::Value| async move {
+            let resp = server.post_json("/api/exports/verify", &body).await;
+            assert_eq!(resp.status(), 200);
+            resp.json::<serde_json::Value>().await.unwrap()
+        };
+
+        let resp = server
+            .get(&format!("/api/sessions/{}/export/csv", id))
+            .await;
+        let integrity: serde_json::Value =
+            serde_json::from_str(resp.headers()["x-rustroast-integrity"].to_str().unwrap())
+                .unwrap();
+        assert_eq!(integrity["session_id"], id);
+        assert_eq!(
+            resp.headers()["x-content-sha256"].to_str().unwrap(),
+            integrity["sha256"]
+        );
+        let csv = resp.text().await.unwrap();
+        let result = verify(json!({"format": "csv", "content": csv, "integrity": integrity})).await;
+        assert_eq!(result["valid"], true);
+        let edited = csv.replacen("Competition", "Competitor", 1);
+        let result =
+            verify(json!({"format": "csv", "content": edited, "integrity": integrity})).await;
+        assert_eq!(result["valid"], false);
+        assert!(result["reason"].as_str().unwrap().contains("hash"));
+
+        // Artisan exports carry their integrity block
+        let doc: serde_json::Value = server
+            .get(&format!("/api/sessions/{}/export/artisan", id))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(doc["rustroast_integrity"]["format"], "artisan");
+        let result = verify(json!({"format": "artisan", "content": doc.to_string()})).await;
+        assert_eq!(result["valid"], true);
+        let mut forged = doc.clone();
+        forged["rustroast_integrity"]["signed_at"] = json!("2020-01-01T00:00:00Z");
+        let result = verify(json!({"format": "artisan", "content": forged.to_string()})).await;
+        assert_eq!(result["valid"], false);
+        assert!(result["reason"].as_str().unwrap().contains("Signature"));
+
+        let resp = server
+            .post_json(
+                "/api/exports/verify",
+                &json!({"format": "csv", "content": csv}),
+            )
+            .await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trips_typed_models() {
+        use rustroast_client::{
+            Client, ControlCommand, CreateRoastEventRequest, CreateSessionRequest, DerivedSpec,
+            RoastEventType, RorAlgorithm, SessionStatus, SmoothedTelemetryQuery,
+            TelemetryRangeQuery, WsEvent,
+        };
+
+        let server = TestServer::start().await;
+        let client = Client::new(server.url("")).unwrap();
+        client.health().await.unwrap();
+        assert!(client.latest_telemetry("dev1").await.unwrap().is_none());
+        assert!(client.get_session("missing").await.unwrap().is_none());
+
+        let session = client
+            .create_session(&CreateSessionRequest {
+                name: "Client".into(),
+                device_id: "dev1".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(session.status, SessionStatus::Planning);
+        let session = client.start_session(&session.id).await.unwrap();
+        assert_eq!(session.status, SessionStatus::Active);
+
+        let mut stream = client.telemetry_stream().await.unwrap();
+        let hint = tokio::time::timeout(WAIT_TIMEOUT, stream.next())
+            .await
+            .expect("smoothing hint")
+            .unwrap()
+            .unwrap();
+        match hint {
+            WsEvent::Smoothing { smoothing } => assert_eq!(smoothing.ror_window_secs, 30.0),
+            other => panic!("unexpected ws event {:?}", other),
+        }
+        server.device_telemetry("dev1", 150.0, 180.0);
+        let event = tokio::time::timeout(WAIT_TIMEOUT, stream.next())
+            .await
+            .expect("ws event")
+            .unwrap()
+            .unwrap();
+        match event {
+            WsEvent::Telemetry { telemetry, .. } => {
+                assert_eq!(telemetry.bean_temp, Some(150.0));
+                assert_eq!(telemetry.fan_pwm, Some(180));
+            }
+            other => panic!("unexpected ws event {:?}", other),
+        }
+        stream
+            .subscribe_derived(
+                vec!["dev1".into()],
+                DerivedSpec {
+                    units: Some("F".into()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        // Telemetry already on its way may still come without derived values
+        let derived = loop {
+            server.device_telemetry("dev1", 150.0, 180.0);
+            let event = tokio::time::timeout(WAIT_TIMEOUT, stream.next())
+                .await
+                .expect("ws event")
+                .unwrap()
+                .unwrap();
+            if let WsEvent::Telemetry {
+                derived: Some(derived),
+                ..
+            } = event
+            {
+                break derived;
+            }
+        };
+        assert_eq!(derived.unit, "F");
+        assert_eq!(derived.bean_temp, Some(302.0));
+        server.device_publish(
+            &rustroast_core::status_topic("dev1"),
+            &fixtures::status_payload("dev1"),
+        );
+        let devices = eventually(|| async {
+            let devices = client.devices().await.ok()?;
+            (!devices.is_empty()).then_some(devices)
+        })
+        .await;
+        assert_eq!(devices[0].id.as_deref(), Some("dev1-TEST"));
+        let latest = client.latest_telemetry("dev1").await.unwrap().unwrap();
+        assert_eq!(latest.telemetry.env_temp, Some(180.0));
+
+        client
+            .control("dev1", &ControlCommand::FanPwm { value: 200 })
+            .await
+            .unwrap();
+        let err = client
+            .control("dev1", &ControlCommand::HeaterPwm { value: 150 })
+            .await
+            .unwrap_err();
+        assert_eq!(err.status().map(|s| s.as_u16()), Some(400));
+
+        let event = client
+            .add_event(
+                &session.id,
+                &CreateRoastEventRequest {
+                    event_type: RoastEventType::DryingEnd,
+                    elapsed_seconds: 240.0,
+                    temperature: Some(155.0),
+                    notes: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(event.event_type, RoastEventType::DryingEnd);
+
+        let detail = client
+            .session_telemetry(&session.id, &TelemetryRangeQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(detail.session.id, session.id);
+        assert!(detail.smoothing.is_some());
+        let smoothed = client
+            .smoothed_telemetry(
+                &session.id,
+                &SmoothedTelemetryQuery {
+                    ror_algorithm: Some(RorAlgorithm::SavitzkyGolay),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            smoothed.smoothing.ror_algorithm,
+            RorAlgorithm::SavitzkyGolay
+        );
+        assert_eq!(smoothed.raw.len(), smoothed.smoothed.len());
+        let completed = client.complete_session(&session.id).await.unwrap();
+        assert_eq!(completed.status, SessionStatus::Completed);
+    }
+
+    /// Parse a raw server response into the shared wire type and back. A
+    /// field the server sends that `T` drops, renames or retypes makes the
+    /// two differ.
+    fn assert_wire_round_trip<T>(raw: &serde_json::Value)
+    where
+        T: serde::de::DeserializeOwned + serde::Serialize,
+    {
+        let name = std::any::type_name::<T>();
+        let typed: T = serde_json::from_value(raw.clone())
+            .unwrap_or_else(|e| panic!("{name} rejects the server's JSON: {e}\n{raw:#}"));
+        assert_eq!(
+            serde_json::to_value(&typed).unwrap(),
+            *raw,
+            "{name} drifted from the server's JSON"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_types_match_server_responses() {
+        use rustroast_client::{
+            Alert, DeviceControlSurface, DeviceInfo, DevicesResponse, LatestTelemetry, RoastEvent,
+            RoastSession, SessionWithTelemetry, SmoothedSessionTelemetry, SmoothingConfig,
+        };
+
+        let server = TestServer::start().await;
+        let get = |path: String| {
+            let server = &server;
+            async move {
+                let resp = server.get(&path).await;
+                assert!(resp.status().is_success(), "GET {path}: {}", resp.status());
+                resp.json::<serde_json::Value>().await.unwrap()
+            }
+        };
+
+        let resp = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Wire", "device_id": "dev1", "bean_origin": "Kenya", "green_weight": 250.0}),
+            )
+            .await;
+        let session: serde_json::Value = resp.json().await.unwrap();
+        let id = session["id"].as_str().unwrap().to_string();
+        assert_wire_round_trip::<RoastSession>(&session);
+        let resp = server
+            .post_json(&format!("/api/sessions/{id}/start"), &json!({}))
+            .await;
+        assert!(resp.status().is_success());
+
+        server.device_publish(
+            &rustroast_core::status_topic("dev1"),
+            &fixtures::status_payload("dev1"),
+        );
+        server.device_telemetry("dev1", 150.0, 180.0);
+        eventually(|| async {
+            let detail = get(format!("/api/sessions/{id}")).await;
+            (!detail["telemetry"].as_array()?.is_empty()).then_some(())
+        })
+        .await;
+        let resp = server
+            .post_json(
+                &format!("/api/sessions/{id}/events"),
+                &json!({"event_type": "first_crack_start", "elapsed_seconds": 420.0, "temperature": 196.0}),
+            )
+            .await;
+        assert!(resp.status().is_success());
+
+        assert_wire_round_trip::<SessionWithTelemetry>(&get(format!("/api/sessions/{id}")).await);
+        assert_wire_round_trip::<Vec<RoastSession>>(&get("/api/sessions".into()).await);
+        assert_wire_round_trip::<Vec<RoastEvent>>(&get(format!("/api/sessions/{id}/events")).await);
+        assert_wire_round_trip::<SmoothedSessionTelemetry>(
+            &get(format!("/api/sessions/{id}/telemetry/smoothed")).await,
+        );
+        assert_wire_round_trip::<SmoothingConfig>(&get("/api/smoothing".into()).await);
+        assert_wire_round_trip::<LatestTelemetry>(
+            &get("/api/roaster/dev1/telemetry/latest".into()).await,
+        );
+        assert_wire_round_trip::<DeviceControlSurface>(
+            &get("/api/roaster/dev1/controls".into()).await,
+        );
+        let devices = eventually(|| async {
+            let devices = get("/api/devices/registry".into()).await;
+            (!devices["devices"].as_array()?.is_empty()).then_some(devices)
+        })
+        .await;
+        assert_wire_round_trip::<DevicesResponse>(&devices);
+        assert_wire_round_trip::<Vec<DeviceInfo>>(&devices["devices"]);
+
+        server.device_telemetry("dev1", 245.0, 260.0);
+        let alerts = eventually(|| async {
+            let alerts = get("/api/alerts".into()).await;
+            (!alerts.as_array()?.is_empty()).then_some(alerts)
+        })
+        .await;
+        assert_wire_round_trip::<Vec<Alert>>(&alerts);
+    }
+
+    #[tokio::test]
+    async fn test_ws_opt_in_status_and_device_changes() {
+        use rustroast_client::{Client, WsEvent, WsOptIn};
+
+        let server = TestServer::start().await;
+        let client = Client::new(server.url("")).unwrap();
+        let mut stream = client.telemetry_stream().await.unwrap();
+        let hint = tokio::time::timeout(WAIT_TIMEOUT, stream.next()).await;
+        assert!(matches!(hint, Ok(Some(Ok(WsEvent::Smoothing { .. })))));
+
+        stream
+            .subscribe_with(vec![], vec![WsOptIn::Status, WsOptIn::Devices])
+            .await
+            .unwrap();
+        // The subscribe command races the first publish, so repeat until seen.
+        let status = tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                server.device_publish(
+                    &rustroast_core::status_topic("dev1"),
+                    &fixtures::status_payload("dev1"),
+                );
+                let next = tokio::time::timeout(Duration::from_millis(100), stream.next()).await;
+                if let Ok(Some(Ok(WsEvent::Status { device_id, status }))) = next {
+                    return (device_id, status);
+                }
+            }
+        })
+        .await
+        .expect("status event");
+        assert_eq!(status.0, "dev1");
+        assert_eq!(status.1["id"], "dev1-TEST");
+
+        server.device_telemetry("dev2", 150.0, 180.0);
+        let change = tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                match stream.next().await {
+                    Some(Ok(WsEvent::Device { device_id, device })) => return (device_id, device),
+                    Some(_) => continue,
+                    None => panic!("ws closed"),
+                }
+            }
+        })
+        .await
+        .expect("device event");
+        assert_eq!(change.0, "dev2");
+        assert_eq!(change.1["op"], "created");
+        assert_eq!(change.1["status"], "pending");
+    }
+
+    #[tokio::test]
+    async fn test_control_surface_follows_telemetry_and_profile() {
+        use rustroast_client::{Client, ControlValueKind};
+
+        let server = TestServer::start().await;
+        let client = Client::new(server.url("")).unwrap();
+        assert!(client.controls("ghost").await.unwrap().is_none());
+
+        // A registered device that has not reported yet gets every control
+        let resp = server
+            .post_json(
+                "/api/device-profiles",
+                &json!({"name": "Small drum", "max_temp": 240.0, "min_fan_pwm": 60, "default_setpoint": 210.0}),
+            )
+            .await;
+        let profile: serde_json::Value = resp.json().await.unwrap();
+        let resp = server
+            .post_json(
+                "/api/devices",
+                &json!({"name": "Drum", "device_id": "drum1", "profile_id": profile["id"]}),
+            )
+            .await;
+        assert!(resp.status().is_success());
+        let surface = client.controls("drum1").await.unwrap().unwrap();
+        assert!(!surface.reported && !surface.online);
+        assert_eq!(surface.controls.len(), 7);
+        let setpoint = surface.control("setpoint").unwrap();
+        assert_eq!(setpoint.kind, ControlValueKind::Number);
+        assert_eq!((setpoint.min, setpoint.max), (Some(0.0), Some(240.0)));
+        assert_eq!(setpoint.default, Some(json!(210.0)));
+        assert!(setpoint.blocked_reason.is_some());
+        assert_eq!(surface.control("fan_pwm").unwrap().min, Some(60.0));
+
+        // Once it reports, the controls and current values follow telemetry
+        server.device_telemetry("drum1", 150.0, 180.0);
+        let surface = eventually(|| async {
+            let surface = client.controls("drum1").await.ok()??;
+            surface.reported.then_some(surface)
+        })
+        .await;
+        assert!(surface.online);
+        let setpoint = surface.control("setpoint").unwrap();
+        assert_eq!(setpoint.current, Some(json!(200.0)));
+        assert_eq!(setpoint.blocked_reason, None);
+        assert_eq!(
+            surface.control("mode").unwrap().current,
+            Some(json!("auto"))
+        );
+        assert_eq!(
+            surface.control("pid").unwrap().current,
+            Some(json!({"kp": 15.0, "ki": 1.0, "kd": 25.0}))
+        );
+
+        // A fan-only board offers the fan and emergency stop
+        server.device_publish(
+            &rustroast_core::telemetry_topic("fan1"),
+            &json!({"beanTemp": 30.0, "fanPWM": 100}),
+        );
+        let surface = eventually(|| async {
+            let surface = client.controls("fan1").await.ok()??;
+            surface.reported.then_some(surface)
+        })
+        .await;
+        let ops: Vec<_> = surface.controls.iter().map(|c| c.op.as_str()).collect();
+        assert_eq!(ops, ["fan_pwm", "emergency_stop"]);
+    }
+
+    #[tokio::test]
+    async fn test_asyncapi_covers_topics_and_ws_messages() {
+        let server = TestServer::start().await;
+        let resp = server.get("/api-docs/asyncapi.json").await;
+        assert_eq!(resp.status(), 200);
+        let doc: serde_json::Value = resp.json().await.unwrap();
+        let channels = doc["channels"].as_object().unwrap();
+        let messages = doc["components"]["messages"].as_object().unwrap();
+
+        for topic in [
+            rustroast_core::telemetry_topic("{device_id}"),
+            rustroast_core::status_topic("{device_id}"),
+            rustroast_core::control_setpoint("{device_id}"),
+            rustroast_core::control_fan_pwm("{device_id}"),
+            rustroast_core::control_heater_pwm("{device_id}"),
+            rustroast_core::control_mode("{device_id}"),
+            rustroast_core::control_heater_enable("{device_id}"),
+            rustroast_core::control_pid("{device_id}"),
+            rustroast_core::control_emergency_stop("{device_id}"),
+            rustroast_core::session_telemetry_topic("{session_id}"),
+            rustroast_core::server_status_topic("{client_id}"),
+            rustroast_core::cluster_heartbeat_topic().to_string(),
+        ] {
+            assert!(channels.contains_key(&topic), "{topic} not described");
+        }
+
+        // Every /ws/telemetry message the conformance spec knows of
+        let spec: serde_json::Value =
+            serde_json::from_str(include_str!("../../ws-smoke/spec/ws-telemetry.json")).unwrap();
+        let sent: Vec<&str> = channels["/ws/telemetry"]["subscribe"]["message"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|m| m["$ref"].as_str()?.rsplit('/').next())
+            .collect();
+        for kind in spec["messages"].as_object().unwrap().keys() {
+            assert!(sent.contains(&kind.as_str()), "{kind} not described");
+            assert!(messages.contains_key(kind));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_temperatures_in_requested_units() {
+        let server = TestServer::start().await;
+        server.device_telemetry("dev1", 200.0, 100.0);
+        let latest = eventually(|| async {
+            let resp = server
+                .get("/api/roaster/dev1/telemetry/latest?units=f")
+                .await;
+            if resp.status() != 200 {
+                return None;
+            }
+            resp.json::<serde_json::Value>().await.ok()
+        })
+        .await;
+        assert_eq!(latest["telemetry"]["beanTemp"], 392.0);
+        assert_eq!(latest["telemetry"]["envTemp"], 212.0);
+
+        let profile: serde_json::Value = server
+            .post_json(
+                "/api/profiles",
+                &json!({
+                    "name": "Fahrenheit",
+                    "charge_temp": 200.0,
+                    "points": [{"time_seconds": 0, "target_temp": 100.0}],
+                }),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let path = format!("/api/profiles/{}", profile["id"].as_str().unwrap());
+        let resp = server
+            .client()
+            .get(server.url(&path))
+            .header("X-Temperature-Units", "F")
+            .send()
+            .await
+            .unwrap();
+        let fahrenheit: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(fahrenheit["charge_temp"], 392.0);
+        assert_eq!(fahrenheit["points"][0]["target_temp"], 212.0);
+        let celsius: serde_json::Value = server.get(&path).await.json().await.unwrap();
+        assert_eq!(celsius["points"][0]["target_temp"], 100.0);
+
+        let resp = server.get(&format!("{}?units=kelvin", path)).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_controls_follow_the_device_topic_layout() {
+        let server = TestServer::start().await;
+        server.device_publish(
+            "roaster/v2/dev2/telemetry",
+            &fixtures::telemetry_payload(150.0, 180.0),
+        );
+        eventually(|| async {
+            let resp = server.get("/api/roaster/dev2/telemetry/latest").await;
+            (resp.status() == 200).then_some(())
+        })
+        .await;
+
+        for (device_id, topic) in [
+            ("dev2", "roaster/v2/dev2/control/setpoint"),
+            ("dev1", "roaster/dev1/control/setpoint"),
+        ] {
+            let path = format!("/api/roaster/{}/control/setpoint", device_id);
+            let resp = server.post_json(&path, &json!({"value": 200.0})).await;
+            assert_eq!(resp.status(), 204);
+            assert_eq!(server.next_published().await.unwrap().topic, topic);
+        }
+
+        // An advertised schema wins over the layout a device was heard in
+        server.device_publish(
+            "roaster/v2/dev2/status",
+            &json!({"status": "online", "topic_schema": 1}),
+        );
+        eventually(|| async {
+            let resp = server
+                .post_json("/api/roaster/dev2/control/fan_pwm", &json!({"value": 120}))
+                .await;
+            assert_eq!(resp.status(), 204);
+            let msg = server.next_published().await.unwrap();
+            (msg.topic == "roaster/dev2/control/fan_pwm").then_some(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_session_notes_need_a_signed_in_operator() {
+        let server = TestServer::start().await;
+        let session: serde_json::Value = server
+            .post_json(
+                "/api/sessions",
+                &json!({"name": "Notes", "device_id": "dev1"}),
+            )
+            .await
+            .json()
+            .await
+            .unwrap();
+        let id = session["id"].as_str().unwrap();
+
+        let resp = server
+            .client()
+            .get(server.url(&format!("/ws/sessions/{}/notes", id)))
+            .header("connection", "upgrade")
+            .header("upgrade", "websocket")
+            .header("sec-websocket-version", "13")
+            .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 401);
+
+        let attachments: serde_json::Value = server
+            .get(&format!("/api/sessions/{}/attachments", id))
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(attachments, json!([]));
+        let resp = server.get("/api/sessions/missing/attachments").await;
+        assert_eq!(resp.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_ws_telemetry_pings_and_closes_after_missed_pong() {
+        let server = TestServer::start_with(|state| {
+            state.set_ws_keepalive(Duration::from_millis(100), Duration::from_millis(300));
+        })
+        .await;
+        let (mut ws, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/ws/telemetry", server.addr))
+                .await
+                .unwrap();
+
+        let ping = tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                match ws.next().await {
+                    Some(Ok(Message::Ping(_))) => break true,
+                    Some(Ok(_)) => continue,
+                    _ => break false,
+                }
+            }
+        })
+        .await;
+        assert_eq!(ping, Ok(true), "no ping from the server");
+
+        // Reading through the client would send the queued pong, so what
+        // the server sends next is read straight off the TCP stream.
+        let mut raw = Vec::new();
+        tokio::time::timeout(WAIT_TIMEOUT, ws.get_mut().read_to_end(&mut raw))
+            .await
+            .expect("server did not close the idle socket")
+            .unwrap();
+        let mut frames = raw.as_slice();
+        let close = loop {
+            let (opcode, len) = (frames[0] & 0x0f, usize::from(frames[1] & 0x7f));
+            let payload = &frames[2..2 + len];
+            if opcode == 0x8 {
+                break payload;
+            }
+            frames = &frames[2 + len..];
+        };
+        assert_eq!(u16::from_be_bytes([close[0], close[1]]), 1001);
+        assert_eq!(&close[2..], b"idle timeout");
+    }
+}
@@ -1,42 +1,508 @@
+//! Conformance checker for the `/ws/telemetry` protocol, and a reference for
+//! third-party client authors. It connects to a running server and checks
+//! it against the spec in `spec/ws-telemetry.json` (or `--spec FILE`):
+//! message schemas, ping handling, robustness to unknown commands,
+//! subscribe filtering and what a reconnect starts with. Each check is
+//! reported as passed, failed or skipped, and any failure exits with 1.
+//!
+//! The subscribe and reconnect checks need device traffic, from `--device`
+//! or the first device seen; run a device or the server's demo mode.
+
+mod spec;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
 use futures_util::{SinkExt, StreamExt};
-use tokio::time::{timeout, Duration};
-use tokio_tungstenite::connect_async;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::time::{timeout_at, Instant};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 
-#[tokio::main]
-async fn main() {
-    let url = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "ws://127.0.0.1:8082/ws/telemetry".to_string());
-    eprintln!("Connecting to {}", url);
-    let (ws_stream, _) = connect_async(url).await.expect("WS connect failed");
-    let (mut write, mut read) = ws_stream.split();
-
-    // Optional: send a ping
-    let _ = write
-        .send(tokio_tungstenite::tungstenite::Message::Ping(vec![]))
-        .await;
-
-    // Wait up to 5s for one telemetry message, then print and exit
-    match timeout(Duration::from_secs(5), read.next()).await {
-        Ok(Some(Ok(msg))) => match msg {
-            tokio_tungstenite::tungstenite::Message::Text(t) => {
-                println!("WS message: {}", t);
+use spec::Spec;
+
+const USAGE: &str = "usage: rustroast-ws-smoke [URL] [--spec FILE] [--device ID] \
+                     [--token TOKEN] [--window SECS] [--json]";
+const DEFAULT_URL: &str = "ws://127.0.0.1:8082/ws/telemetry";
+/// A device id no server knows, to subscribe to nothing.
+const NO_DEVICE: &str = "conformance-no-such-device";
+const PING_PAYLOAD: &[u8] = b"rustroast-conformance";
+/// Messages the server sent before it handled a subscribe may still arrive
+/// for this long afterwards.
+const SETTLE: Duration = Duration::from_millis(500);
+/// Wait for single replies (first message, pong).
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Options {
+    url: String,
+    spec_path: Option<String>,
+    device: Option<String>,
+    token: Option<String>,
+    /// How long to collect messages for each traffic check.
+    window: Duration,
+    json: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options {
+        url: DEFAULT_URL.to_string(),
+        spec_path: None,
+        device: None,
+        token: None,
+        window: Duration::from_secs(5),
+        json: false,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{name} needs a value"));
+        match arg.as_str() {
+            "--spec" => opts.spec_path = Some(value("--spec")?),
+            "--device" => opts.device = Some(value("--device")?),
+            "--token" => opts.token = Some(value("--token")?),
+            "--window" => {
+                let secs: f64 = value("--window")?
+                    .parse()
+                    .map_err(|_| "--window takes seconds".to_string())?;
+                opts.window = Duration::from_secs_f64(secs);
             }
-            other => {
-                println!("WS non-text message: {:?}", other);
+            "--json" => opts.json = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            url if !url.starts_with('-') => opts.url = url.to_string(),
+            other => return Err(format!("unknown option {other}\n{USAGE}")),
+        }
+    }
+    Ok(opts)
+}
+
+enum Outcome {
+    Pass(String),
+    Fail(String),
+    Skip(String),
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Pass(_) => "pass",
+            Outcome::Fail(_) => "fail",
+            Outcome::Skip(_) => "skip",
+        }
+    }
+
+    fn detail(&self) -> &str {
+        match self {
+            Outcome::Pass(d) | Outcome::Fail(d) | Outcome::Skip(d) => d,
+        }
+    }
+}
+
+struct Conn {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Conn {
+    async fn open(opts: &Options) -> Result<Self, String> {
+        let mut request = opts
+            .url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| format!("invalid URL {}: {e}", opts.url))?;
+        if let Some(token) = &opts.token {
+            let value = HeaderValue::from_str(&format!("Bearer {token}"))
+                .map_err(|_| "invalid token".to_string())?;
+            request.headers_mut().insert("authorization", value);
+        }
+        let (socket, _) = connect_async(request)
+            .await
+            .map_err(|e| format!("connecting to {}: {e}", opts.url))?;
+        Ok(Self { socket })
+    }
+
+    /// The next frame before `deadline`, `None` on timeout.
+    async fn next_frame(&mut self, deadline: Instant) -> Result<Option<Message>, String> {
+        match timeout_at(deadline, self.socket.next()).await {
+            Err(_) => Ok(None),
+            Ok(None) | Ok(Some(Ok(Message::Close(_)))) => {
+                Err("server closed the connection".to_string())
             }
-        },
-        Ok(Some(Err(e))) => {
-            eprintln!("WS receive error: {}", e);
-            std::process::exit(2);
+            Ok(Some(Ok(frame))) => Ok(Some(frame)),
+            Ok(Some(Err(e))) => Err(format!("receive error: {e}")),
         }
-        Ok(None) => {
-            eprintln!("WS closed by server");
-            std::process::exit(3);
+    }
+
+    /// The next text message before `deadline`. Server pings are answered
+    /// by the WebSocket library while reading.
+    async fn next_message(&mut self, deadline: Instant) -> Result<Option<Value>, String> {
+        loop {
+            match self.next_frame(deadline).await? {
+                None => return Ok(None),
+                Some(Message::Text(text)) => {
+                    return serde_json::from_str(&text)
+                        .map(Some)
+                        .map_err(|e| format!("message is not JSON ({e}): {text}"));
+                }
+                Some(_) => {}
+            }
         }
-        Err(_) => {
-            eprintln!("Timeout waiting for WS telemetry");
-            std::process::exit(4);
+    }
+
+    /// Every text message received for `window`.
+    async fn collect(&mut self, window: Duration) -> Result<Vec<Value>, String> {
+        let deadline = Instant::now() + window;
+        let mut messages = Vec::new();
+        while let Some(msg) = self.next_message(deadline).await? {
+            messages.push(msg);
+        }
+        Ok(messages)
+    }
+
+    async fn send(&mut self, msg: &Value) -> Result<(), String> {
+        self.send_text(msg.to_string()).await
+    }
+
+    async fn send_text(&mut self, text: String) -> Result<(), String> {
+        self.socket
+            .send(Message::Text(text))
+            .await
+            .map_err(|e| format!("send error: {e}"))
+    }
+
+    /// Send a ping and wait for its pong, skipping other messages.
+    async fn ping(&mut self) -> Result<(), String> {
+        self.socket
+            .send(Message::Ping(PING_PAYLOAD.to_vec()))
+            .await
+            .map_err(|e| format!("send error: {e}"))?;
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            match self.next_frame(deadline).await? {
+                None => return Err(format!("no pong within {REPLY_TIMEOUT:?}")),
+                Some(Message::Pong(payload)) if payload == PING_PAYLOAD => return Ok(()),
+                Some(Message::Pong(_)) => return Err("pong payload differs from the ping".into()),
+                Some(_) => {}
+            }
         }
     }
+
+    /// Subscribe and drop what was already in flight.
+    async fn subscribe(&mut self, device_ids: &[&str]) -> Result<(), String> {
+        self.send(&json!({"type": "subscribe", "device_ids": device_ids}))
+            .await?;
+        self.collect(SETTLE).await?;
+        Ok(())
+    }
+
+    async fn close(mut self) {
+        let _ = self.socket.close(None).await;
+    }
+}
+
+/// What one traffic window contained.
+#[derive(Default)]
+struct Traffic {
+    kinds: BTreeMap<String, usize>,
+    /// Devices of device-scoped messages.
+    devices: BTreeMap<String, usize>,
+    errors: Vec<String>,
+}
+
+impl Traffic {
+    fn summary(&self) -> String {
+        let kinds: Vec<_> = self.kinds.iter().map(|(k, n)| format!("{k}×{n}")).collect();
+        kinds.join(", ")
+    }
+}
+
+struct Checker {
+    spec: Spec,
+    opts: Options,
+    device: Option<String>,
+    results: Vec<(&'static str, Outcome)>,
+}
+
+impl Checker {
+    /// Check messages against the spec. No subscribe here opts in to
+    /// anything, so opt-in kinds are errors too.
+    fn inspect(&self, messages: &[Value]) -> Traffic {
+        let mut traffic = Traffic::default();
+        for msg in messages {
+            let kind = match self.spec.validate(msg) {
+                Ok(kind) => kind,
+                Err(e) => {
+                    traffic.errors.push(e);
+                    continue;
+                }
+            };
+            if let Some(opt_in) = self.spec.opt_in(kind) {
+                traffic
+                    .errors
+                    .push(format!("{kind} message without opting in to {opt_in:?}"));
+            }
+            *traffic.kinds.entry(kind.to_string()).or_default() += 1;
+            if let Some(device) = self.spec.device_of(kind, msg) {
+                *traffic.devices.entry(device.to_string()).or_default() += 1;
+            }
+        }
+        traffic
+    }
+
+    fn record(&mut self, name: &'static str, outcome: Result<Outcome, String>) {
+        self.results
+            .push((name, outcome.unwrap_or_else(Outcome::Fail)));
+    }
+
+    async fn first_message(&self, conn: &mut Conn) -> Result<Outcome, String> {
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let Some(msg) = conn.next_message(deadline).await? else {
+            return Ok(Outcome::Fail(format!(
+                "no message within {REPLY_TIMEOUT:?}"
+            )));
+        };
+        let kind = self.spec.validate(&msg)?;
+        if kind != self.spec.first_message {
+            return Ok(Outcome::Fail(format!(
+                "expected a {} message first, got {kind}",
+                self.spec.first_message
+            )));
+        }
+        Ok(Outcome::Pass(format!("{kind} message")))
+    }
+
+    async fn schemas(&mut self, conn: &mut Conn) -> Result<Outcome, String> {
+        let traffic = self.inspect(&conn.collect(self.opts.window).await?);
+        if self.device.is_none() {
+            self.device = traffic
+                .devices
+                .iter()
+                .max_by_key(|(_, n)| **n)
+                .map(|(d, _)| d.clone());
+        }
+        if let Some(error) = traffic.errors.first() {
+            return Ok(Outcome::Fail(format!(
+                "{} invalid message(s), first: {error}",
+                traffic.errors.len()
+            )));
+        }
+        if traffic.kinds.is_empty() {
+            return Ok(Outcome::Skip(format!(
+                "no messages within {:?}",
+                self.opts.window
+            )));
+        }
+        Ok(Outcome::Pass(traffic.summary()))
+    }
+
+    async fn ping(&self, conn: &mut Conn) -> Result<Outcome, String> {
+        conn.ping().await?;
+        Ok(Outcome::Pass("answered with the same payload".to_string()))
+    }
+
+    async fn unknown_commands(&self, conn: &mut Conn) -> Result<Outcome, String> {
+        conn.send_text("not json".to_string()).await?;
+        conn.send(&json!({"type": "no-such-command"})).await?;
+        conn.ping().await?;
+        Ok(Outcome::Pass("ignored, connection stays open".to_string()))
+    }
+
+    async fn subscribe_one(&self, conn: &mut Conn, device: &str) -> Result<Outcome, String> {
+        conn.subscribe(&[device]).await?;
+        let traffic = self.inspect(&conn.collect(self.opts.window).await?);
+        if let Some(other) = traffic.devices.keys().find(|d| *d != device) {
+            return Ok(Outcome::Fail(format!(
+                "got messages for {other} while subscribed to {device}"
+            )));
+        }
+        if !traffic.devices.contains_key(device) {
+            return Ok(Outcome::Fail(format!(
+                "no messages for {device} while subscribed to it"
+            )));
+        }
+        Ok(Outcome::Pass(format!(
+            "only {device}: {}",
+            traffic.summary()
+        )))
+    }
+
+    async fn subscribe_none(&self, conn: &mut Conn) -> Result<Outcome, String> {
+        conn.subscribe(&[NO_DEVICE]).await?;
+        let traffic = self.inspect(&conn.collect(self.opts.window).await?);
+        if let Some(device) = traffic.devices.keys().next() {
+            return Ok(Outcome::Fail(format!(
+                "got messages for {device} while subscribed to an unknown device"
+            )));
+        }
+        Ok(Outcome::Pass("no device messages".to_string()))
+    }
+
+    async fn subscribe_all(&self, conn: &mut Conn, device: &str) -> Result<Outcome, String> {
+        if !self.spec.subscribe.empty_means_all {
+            return Ok(Outcome::Skip("not in the spec".to_string()));
+        }
+        conn.subscribe(&[]).await?;
+        let traffic = self.inspect(&conn.collect(self.opts.window).await?);
+        if !traffic.devices.contains_key(device) {
+            return Ok(Outcome::Fail(format!(
+                "no messages for {device} after subscribing to all devices"
+            )));
+        }
+        Ok(Outcome::Pass(format!(
+            "{} device(s) again",
+            traffic.devices.len()
+        )))
+    }
+
+    async fn reconnect(&self, device: &str) -> Result<Outcome, String> {
+        let mut conn = Conn::open(&self.opts).await?;
+        conn.subscribe(&[NO_DEVICE]).await?;
+        conn.close().await;
+
+        let mut conn = Conn::open(&self.opts).await?;
+        if let Outcome::Fail(detail) = self.first_message(&mut conn).await? {
+            return Ok(Outcome::Fail(format!("after reconnecting: {detail}")));
+        }
+        let traffic = self.inspect(&conn.collect(self.opts.window).await?);
+        conn.close().await;
+        let resumed = !traffic.devices.contains_key(device);
+        Ok(match (self.spec.reconnect.subscriptions_persist, resumed) {
+            (true, true) => Outcome::Pass("kept the previous subscription".to_string()),
+            (false, false) => Outcome::Pass("started with a fresh subscription".to_string()),
+            (true, false) => Outcome::Fail("the previous subscription was lost".to_string()),
+            (false, true) => Outcome::Fail(format!(
+                "no messages for {device}: the previous subscription carried over"
+            )),
+        })
+    }
+
+    async fn run(&mut self) -> Result<(), String> {
+        let mut conn = Conn::open(&self.opts).await?;
+        let outcome = self.first_message(&mut conn).await;
+        self.record("first message", outcome);
+        let outcome = self.schemas(&mut conn).await;
+        self.record("message schemas", outcome);
+        let outcome = self.ping(&mut conn).await;
+        self.record("ping", outcome);
+        let outcome = self.unknown_commands(&mut conn).await;
+        self.record("unknown commands", outcome);
+
+        let names = [
+            "subscribe to one device",
+            "subscribe to an unknown device",
+            "subscribe to all devices",
+            "reconnect",
+        ];
+        let Some(device) = self.device.clone() else {
+            for name in names {
+                let reason = "no device traffic; pass --device or run a device".to_string();
+                self.results.push((name, Outcome::Skip(reason)));
+            }
+            conn.close().await;
+            return Ok(());
+        };
+        let outcome = self.subscribe_one(&mut conn, &device).await;
+        self.record(names[0], outcome);
+        let outcome = self.subscribe_none(&mut conn).await;
+        self.record(names[1], outcome);
+        let outcome = self.subscribe_all(&mut conn, &device).await;
+        self.record(names[2], outcome);
+        conn.close().await;
+        let outcome = self.reconnect(&device).await;
+        self.record(names[3], outcome);
+        Ok(())
+    }
+
+    fn failed(&self) -> bool {
+        self.results
+            .iter()
+            .any(|(_, o)| matches!(o, Outcome::Fail(_)))
+    }
+
+    fn report(&self) {
+        let count = |label: &str| {
+            self.results
+                .iter()
+                .filter(|(_, o)| o.label() == label)
+                .count()
+        };
+        if self.opts.json {
+            let checks: Vec<_> = self
+                .results
+                .iter()
+                .map(|(name, o)| json!({"name": name, "result": o.label(), "detail": o.detail()}))
+                .collect();
+            let report = json!({
+                "url": self.opts.url,
+                "spec_version": self.spec.version,
+                "device": self.device,
+                "checks": checks,
+                "passed": count("pass"),
+                "failed": count("fail"),
+                "skipped": count("skip"),
+            });
+            println!("{report:#}");
+            return;
+        }
+        println!("{} (spec v{})", self.opts.url, self.spec.version);
+        for (name, outcome) in &self.results {
+            let label = outcome.label().to_uppercase();
+            println!("{label:<5} {name:<32} {}", outcome.detail());
+        }
+        println!(
+            "{} passed, {} failed, {} skipped",
+            count("pass"),
+            count("fail"),
+            count("skip")
+        );
+    }
+}
+
+/// A bare `ws://host:port` gets the spec's endpoint.
+fn endpoint_url(url: &str, spec: &Spec) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if parsed.path() == "/" || parsed.path().is_empty() => {
+            parsed.set_path(&spec.endpoint);
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let mut opts = match parse_args() {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    let spec_json = match &opts.spec_path {
+        Some(path) => std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {path}: {e}");
+            std::process::exit(2);
+        }),
+        None => spec::DEFAULT_SPEC.to_string(),
+    };
+    let spec = Spec::parse(&spec_json).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
+    opts.url = endpoint_url(&opts.url, &spec);
+
+    let mut checker = Checker {
+        spec,
+        device: opts.device.clone(),
+        opts,
+        results: Vec::new(),
+    };
+    if let Err(e) = checker.run().await {
+        eprintln!("{e}");
+        std::process::exit(2);
+    }
+    checker.report();
+    if checker.failed() {
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,177 @@
+//! The machine-readable description of `/ws/telemetry` the checker runs
+//! against, see `spec/ws-telemetry.json`.
+//!
+//! Every server message is a JSON object whose kind is the one key naming a
+//! message in `messages` (`{"device_id": ..., "telemetry": {...}}` is a
+//! `telemetry` message). Field types are `string`, `number`, `integer`,
+//! `boolean`, `object`, `array` or `any`; a trailing `?` also allows null or
+//! a missing field. Object fields can list their own required fields.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// The spec shipped with the checker.
+pub const DEFAULT_SPEC: &str = include_str!("../spec/ws-telemetry.json");
+
+#[derive(Debug, Deserialize)]
+pub struct Spec {
+    pub version: u32,
+    pub endpoint: String,
+    /// Kind of the message every connection starts with.
+    pub first_message: String,
+    pub messages: BTreeMap<String, MessageSpec>,
+    pub subscribe: SubscribeSpec,
+    pub reconnect: ReconnectSpec,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageSpec {
+    /// Carries a `device_id` and is filtered by the subscription.
+    #[serde(default)]
+    pub device_scoped: bool,
+    /// The subscribe `events` entry without which it is never sent.
+    #[serde(default)]
+    pub opt_in: Option<String>,
+    pub fields: BTreeMap<String, FieldSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FieldSpec {
+    Type(String),
+    Object {
+        #[serde(rename = "type")]
+        kind: String,
+        fields: BTreeMap<String, FieldSpec>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscribeSpec {
+    /// Subscribing to no devices forwards every device.
+    pub empty_means_all: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReconnectSpec {
+    /// A new connection starts with the previous connection's device
+    /// subscription.
+    pub subscriptions_persist: bool,
+}
+
+impl Spec {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid spec: {e}"))
+    }
+
+    /// The kind of a server message, after checking it against its spec.
+    pub fn validate<'a>(&'a self, msg: &Value) -> Result<&'a str, String> {
+        let Some(obj) = msg.as_object() else {
+            return Err(format!("not a JSON object: {msg}"));
+        };
+        let mut kinds = self
+            .messages
+            .keys()
+            .filter(|kind| obj.contains_key(kind.as_str()));
+        let kind = match (kinds.next(), kinds.next()) {
+            (Some(kind), None) => kind,
+            (None, _) => return Err(format!("unknown message kind: {msg}")),
+            (Some(a), Some(b)) => return Err(format!("ambiguous message ({a} and {b}): {msg}")),
+        };
+        check_fields(kind, &self.messages[kind].fields, msg)
+            .map_err(|e| format!("{kind} message: {e}"))?;
+        Ok(kind)
+    }
+
+    /// The device a message is about, when its kind is device scoped.
+    pub fn device_of<'m>(&self, kind: &str, msg: &'m Value) -> Option<&'m str> {
+        self.messages
+            .get(kind)
+            .filter(|m| m.device_scoped)
+            .and_then(|_| msg.get("device_id"))
+            .and_then(Value::as_str)
+    }
+
+    pub fn opt_in(&self, kind: &str) -> Option<&str> {
+        self.messages.get(kind).and_then(|m| m.opt_in.as_deref())
+    }
+}
+
+fn check_fields(
+    path: &str,
+    fields: &BTreeMap<String, FieldSpec>,
+    value: &Value,
+) -> Result<(), String> {
+    for (name, field) in fields {
+        let path = format!("{path}.{name}");
+        match field {
+            FieldSpec::Type(kind) => check_type(&path, kind, value.get(name))?,
+            FieldSpec::Object { kind, fields } => {
+                check_type(&path, kind, value.get(name))?;
+                if let Some(inner) = value.get(name).filter(|v| !v.is_null()) {
+                    check_fields(&path, fields, inner)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_type(path: &str, kind: &str, value: Option<&Value>) -> Result<(), String> {
+    let (kind, nullable) = match kind.strip_suffix('?') {
+        Some(kind) => (kind, true),
+        None => (kind, false),
+    };
+    let value = match value {
+        None | Some(Value::Null) if nullable => return Ok(()),
+        None => return Err(format!("{path} is missing")),
+        Some(value) => value,
+    };
+    let ok = match kind {
+        "any" => true,
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        other => return Err(format!("{path} has unknown type {other} in the spec")),
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(format!("{path} should be {kind}, got {value}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_spec_classifies_and_checks_messages() {
+        let spec = Spec::parse(DEFAULT_SPEC).unwrap();
+        let telemetry = json!({"device_id": "dev1", "telemetry": {"beanTemp": 150.0}});
+        assert_eq!(spec.validate(&telemetry), Ok("telemetry"));
+        assert_eq!(spec.device_of("telemetry", &telemetry), Some("dev1"));
+
+        // Alerts may be about no device
+        let alert = json!({"device_id": null, "alert": {"id": "a1", "kind": "device_health", "state": "firing"}});
+        assert_eq!(spec.validate(&alert), Ok("alert"));
+        assert_eq!(spec.device_of("alert", &alert), None);
+
+        let err = spec
+            .validate(&json!({"device_id": "dev1", "queue": {"session_id": "s1", "state": "queued", "position": "1"}}))
+            .unwrap_err();
+        assert!(
+            err.contains("queue.queue.position should be integer"),
+            "{err}"
+        );
+        assert!(spec.validate(&json!({"device_id": "dev1"})).is_err());
+        assert!(spec.validate(&json!([1, 2])).is_err());
+        assert_eq!(spec.opt_in("status"), Some("status"));
+    }
+}